@@ -6,6 +6,19 @@
 #include <stdint.h>
 #include <stdlib.h>
 
+typedef enum citeproc_rs_cluster_number_kind {
+  /**
+   * The cluster has not been given a position with [citeproc_rs_driver_set_cluster_order].
+   */
+  CITEPROC_RS_CLUSTER_NUMBER_KIND_UNPOSITIONED,
+  CITEPROC_RS_CLUSTER_NUMBER_KIND_IN_TEXT,
+  CITEPROC_RS_CLUSTER_NUMBER_KIND_NOTE,
+  /**
+   * An author-only cluster, which is not part of the flow of the document.
+   */
+  CITEPROC_RS_CLUSTER_NUMBER_KIND_OUTSIDE_FLOW,
+} citeproc_rs_cluster_number_kind;
+
 enum citeproc_rs_error_code {
   CITEPROC_RS_ERROR_CODE_NONE = 0,
   CITEPROC_RS_ERROR_CODE_NULL_POINTER = 1,
@@ -20,9 +33,27 @@ enum citeproc_rs_error_code {
   CITEPROC_RS_ERROR_CODE_CLUSTER_NOT_IN_FLOW = 10,
   CITEPROC_RS_ERROR_CODE_INVALID_STYLE = 11,
   CITEPROC_RS_ERROR_CODE_SET_LOGGER = 12,
+  CITEPROC_RS_ERROR_CODE_LOCALE_FETCH = 13,
+  CITEPROC_RS_ERROR_CODE_INVALID_LANG = 14,
+  CITEPROC_RS_ERROR_CODE_INVALID_LOCALE = 15,
+  CITEPROC_RS_ERROR_CODE_LOCALE_LANG_MISMATCH = 16,
+  CITEPROC_RS_ERROR_CODE_REFERENCE_NOT_FOUND = 17,
+  CITEPROC_RS_ERROR_CODE_LIMIT_EXCEEDED = 18,
+  CITEPROC_RS_ERROR_CODE_BUNDLE = 19,
+  CITEPROC_RS_ERROR_CODE_UNAVAILABLE_FORMAT = 20,
 };
 typedef int32_t citeproc_rs_error_code;
 
+/**
+ * Whether [citeproc_rs_driver_format_references] formats references as citations or as
+ * bibliography entries.
+ */
+enum citeproc_rs_format_mode {
+  CITEPROC_RS_FORMAT_MODE_CITATION,
+  CITEPROC_RS_FORMAT_MODE_BIBLIOGRAPHY,
+};
+typedef uint8_t citeproc_rs_format_mode;
+
 enum citeproc_rs_level_filter {
   CITEPROC_RS_LEVEL_FILTER_OFF,
   /**
@@ -100,18 +131,26 @@ typedef struct citeproc_rs_cluster citeproc_rs_cluster;
 /**
  * Wrapper for a driver, initialized with one style and any required locales.
  *
- * Not thread safe.
+ * Not thread safe. Use [citeproc_rs_driver_snapshot] to format from other threads.
  *
  * Contains an Option<citeproc_rs::Processor>, because to survive panics we want to be able to
  * write a safe value that won't be in an inconsistent state after panicking.
  */
 typedef struct citeproc_rs_driver citeproc_rs_driver;
 
+/**
+ * A read-only view of a [Driver]. Create one with [citeproc_rs_driver_snapshot] and free it
+ * with [citeproc_rs_snapshot_free].
+ */
+typedef struct citeproc_rs_driver_snapshot citeproc_rs_driver_snapshot;
+
 typedef struct citeproc_rs_locale_slot citeproc_rs_locale_slot;
 
 /**
  * A callback signature that is expected to write a string into `slot` via
- * [citeproc_rs_locale_slot_write]
+ * [citeproc_rs_locale_slot_write], or to call [citeproc_rs_locale_slot_not_found] if there is
+ * no such locale. If it does neither, e.g. because a download failed, the driver will ask again
+ * the next time it needs that locale.
  */
 typedef void (*citeproc_rs_locale_fetch_callback)(void *context, struct citeproc_rs_locale_slot *slot, const char*);
 
@@ -154,6 +193,11 @@ typedef struct citeproc_rs_buffer_ops {
 typedef struct citeproc_rs_init_options {
   const char *style;
   uintptr_t style_len;
+  /**
+   * Passed to `locale_fetch_callback`. The driver keeps it for locales needed later, e.g. after
+   * [citeproc_rs_driver_set_locale_override], so it must stay valid until
+   * [citeproc_rs_driver_free].
+   */
   void *locale_fetch_context;
   citeproc_rs_locale_fetch_callback locale_fetch_callback;
   citeproc_rs_output_format format;
@@ -181,6 +225,21 @@ typedef struct citeproc_rs_cluster_position {
   uint32_t note_number;
 } citeproc_rs_cluster_position;
 
+/**
+ * Where a cluster ended up after [citeproc_rs_driver_set_cluster_order].
+ */
+typedef struct citeproc_rs_cluster_number {
+  enum citeproc_rs_cluster_number_kind kind;
+  /**
+   * The in-text cluster number, or the footnote number. Zero for the other kinds.
+   */
+  uint32_t number;
+  /**
+   * For note clusters, the position of the cluster within its footnote, starting at zero.
+   */
+  uint32_t index_in_note;
+} citeproc_rs_cluster_number;
+
 typedef void (*citeproc_rs_logger_write_callback)(void *user_data, citeproc_rs_log_level level, const uint8_t *module_path, uintptr_t module_path_len, const uint8_t *src, uintptr_t src_len);
 
 typedef void (*citeproc_rs_logger_flush_callback)(void *user_data);
@@ -221,7 +280,12 @@ typedef int64_t citeproc_rs_u32_or_error;
 void citeproc_rs_log_init(void);
 
 /**
- * Write an XML string into a LocaleSlot. Returns an error code if the XML does not parse cleanly.
+ * Write an XML string into a LocaleSlot.
+ *
+ * Nothing is stored, and an error code returned, if the string is not UTF-8
+ * ([ErrorCode::Utf8]), the XML does not parse cleanly ([ErrorCode::InvalidLocale]), or its
+ * `xml:lang` is not the language the slot is for ([ErrorCode::LocaleLangMismatch]). The
+ * callback can then call [citeproc_rs_locale_slot_not_found], or try another source.
  *
  * # Safety:
  *
@@ -232,8 +296,25 @@ citeproc_rs_error_code citeproc_rs_locale_slot_write(struct citeproc_rs_locale_s
                                                      const char *locale_xml,
                                                      uintptr_t locale_xml_len);
 
+/**
+ * Tells the driver there is no locale for the slot's language, so it falls back to another
+ * and does not ask for this one again.
+ *
+ * # Safety:
+ *
+ * Only safe to use inside a [LocaleFetchCallback]. You must pass the slot pointer from the
+ * arguments to the callback.
+ */
+citeproc_rs_error_code citeproc_rs_locale_slot_not_found(struct citeproc_rs_locale_slot *slot);
+
 /**
  * Creates a new Processor from InitOptions. Free with [citeproc_rs_driver_free].
+ *
+ * # Safety
+ *
+ * `init.locale_fetch_context` must stay valid until the driver is freed with
+ * [citeproc_rs_driver_free], as the locale fetch callback can be called with it by any
+ * function that takes the driver.
  */
 struct citeproc_rs_driver *citeproc_rs_driver_new(struct citeproc_rs_init_options init);
 
@@ -246,6 +327,62 @@ struct citeproc_rs_driver *citeproc_rs_driver_new(struct citeproc_rs_init_option
  */
 void citeproc_rs_driver_free(struct citeproc_rs_driver *driver);
 
+/**
+ * Switches the output format ([citeproc::Processor::set_output_format]), e.g. when the user
+ * asks to copy citations as plain text. Clusters and references are kept, and the
+ * `link_anchors` setting carries over.
+ *
+ * # Safety
+ *
+ * `driver` must be a valid pointer to a Driver.
+ */
+citeproc_rs_error_code citeproc_rs_driver_set_output_format(struct citeproc_rs_driver *driver,
+                                                            citeproc_rs_output_format format);
+
+/**
+ * Turns clickable links on or off, for URLs and DOIs in HTML and RTF output. See
+ * [citeproc::prelude::FormatOptions::with_link_anchors].
+ *
+ * # Safety
+ *
+ * `driver` must be a valid pointer to a Driver.
+ */
+citeproc_rs_error_code citeproc_rs_driver_set_link_anchors(struct citeproc_rs_driver *driver,
+                                                           bool link_anchors);
+
+/**
+ * Renders with the locale for `lang`, like `"de-AT"`, instead of the style's default-locale
+ * ([citeproc::Processor::set_locale_override]). An empty `lang` goes back to the style's.
+ * Any locales this needs are requested from the locale fetch callback given to
+ * [citeproc_rs_driver_new] before this returns.
+ *
+ * # Safety
+ *
+ * `driver` must be a valid pointer to a Driver. Either `lang` must refer to a byte array of
+ * length `lang_len`, or `lang_len` must be zero.
+ */
+citeproc_rs_error_code citeproc_rs_driver_set_locale_override(struct citeproc_rs_driver *driver,
+                                                              const char *lang,
+                                                              uintptr_t lang_len);
+
+/**
+ * Stores locales the host already has in memory, e.g. ones bundled with an app, using
+ * [citeproc::Processor::store_locales]. Locales stored this way are never requested from the
+ * locale fetch callback.
+ *
+ * `locales_json` is a JSON object mapping language tags to locale XML, like
+ * `{"de-DE": "<locale ...>"}`. Every locale is checked as in [citeproc_rs_locale_slot_write]
+ * first, and if any of them fails, none are stored.
+ *
+ * # Safety
+ *
+ * `driver` must be a valid pointer to a Driver. Either `locales_json` must refer to a byte
+ * array of length `locales_json_len`, or `locales_json_len` must be zero.
+ */
+citeproc_rs_error_code citeproc_rs_driver_store_locales(struct citeproc_rs_driver *driver,
+                                                        const char *locales_json,
+                                                        uintptr_t locales_json_len);
+
 /**
  * [citeproc::Processor::set_cluster_order], but using an ffi-compatible [ClusterPosition]
  *
@@ -259,6 +396,19 @@ citeproc_rs_error_code citeproc_rs_driver_set_cluster_order(struct citeproc_rs_d
                                                             const struct citeproc_rs_cluster_position *positions,
                                                             uintptr_t positions_len);
 
+/**
+ * Writes the position of a cluster in the document
+ * ([citeproc::Processor::get_cluster_note_number]) into `out`.
+ *
+ * # Safety
+ *
+ * Driver must be a valid pointer to a Driver. `out` must be a valid pointer to a
+ * [ClusterNumber].
+ */
+citeproc_rs_error_code citeproc_rs_driver_cluster_number(struct citeproc_rs_driver *driver,
+                                                         citeproc_rs_cluster_id cluster_id,
+                                                         struct citeproc_rs_cluster_number *out);
+
 /**
  * Writes a formatted cluster ([citeproc::Processor::get_cluster]) into a buffer.
  *
@@ -270,6 +420,23 @@ citeproc_rs_error_code citeproc_rs_driver_format_cluster(struct citeproc_rs_driv
                                                          citeproc_rs_cluster_id cluster_id,
                                                          void *user_buf);
 
+/**
+ * Writes a cluster into a buffer as a JSON array of text runs
+ * ([citeproc::Processor::get_cluster_runs]), so you can build a native attributed string
+ * instead of parsing HTML. Each run looks like
+ * `{ "text": "Title", "italic": true, "link": "https://..." }`; the attributes are `italic`,
+ * `bold`, `smallCaps`, `underline`, `superscript`, `subscript` and `link`, and are left out
+ * when not set. The driver's output format does not matter.
+ *
+ * # Safety
+ *
+ * `driver` must be a valid pointer to a Driver. `user_buf` must match the expected user data
+ * in the BufferOps struct passed to the driver's init call.
+ */
+citeproc_rs_error_code citeproc_rs_driver_built_cluster_runs(struct citeproc_rs_driver *driver,
+                                                             citeproc_rs_cluster_id cluster_id,
+                                                             void *user_buf);
+
 /**
  * Writes a bibliography into a buffer, using [citeproc::Processor::get_bibliography]
  */
@@ -293,6 +460,54 @@ citeproc_rs_error_code citeproc_rs_driver_preview_reference(struct citeproc_rs_d
                                                             citeproc_rs_output_format format,
                                                             void *user_buf);
 
+/**
+ * Formats each of a list of references on its own, without creating any clusters, using
+ * [citeproc::Processor::format_references].
+ *
+ * `ids_json` is a JSON array of reference ids. The results are written into user_buf one
+ * per line, in the same order.
+ *
+ * # Safety
+ *
+ * `driver` must be a valid pointer to a Driver. Either `ids_json` must refer to a byte array
+ * of length `ids_json_len`, or `ids_json_len` must be zero. `user_buf` must match the
+ * expected user data in the BufferOps struct passed to driver's init call.
+ */
+citeproc_rs_error_code citeproc_rs_driver_format_references(struct citeproc_rs_driver *driver,
+                                                            const char *ids_json,
+                                                            uintptr_t ids_json_len,
+                                                            citeproc_rs_format_mode mode,
+                                                            void *user_buf);
+
+/**
+ * Writes the stored reference with this id into a buffer as a CSL-JSON object
+ * ([citeproc::Processor::export_reference]).
+ *
+ * Returns [ErrorCode::ReferenceNotFound] if there is no such reference.
+ *
+ * # Safety
+ *
+ * `driver` must be a valid pointer to a Driver. Either `id` must refer to a byte array of
+ * length `id_len`, or `id_len` must be zero. `user_buf` must match the expected user data in
+ * the BufferOps struct passed to the driver's init call.
+ */
+citeproc_rs_error_code citeproc_rs_driver_get_reference(struct citeproc_rs_driver *driver,
+                                                        const char *id,
+                                                        uintptr_t id_len,
+                                                        void *user_buf);
+
+/**
+ * Writes the ids of every stored reference into a buffer, as a JSON array of strings in the
+ * order they were inserted ([citeproc::Processor::reference_ids]).
+ *
+ * # Safety
+ *
+ * `driver` must be a valid pointer to a Driver. `user_buf` must match the expected user data
+ * in the BufferOps struct passed to the driver's init call.
+ */
+citeproc_rs_error_code citeproc_rs_driver_list_references(struct citeproc_rs_driver *driver,
+                                                          void *user_buf);
+
 /**
  * Inserts a reference. [citeproc::Processor::insert_reference]
  *
@@ -308,6 +523,34 @@ citeproc_rs_error_code citeproc_rs_driver_insert_reference(struct citeproc_rs_dr
                                                            const char *ref_json,
                                                            uintptr_t ref_json_len);
 
+/**
+ * Tells the driver that its style was chosen via this dependent style, using
+ * [citeproc::Processor::set_dependent_style]. The dependent style's default-locale becomes
+ * the locale override, unless one was set with [citeproc_rs_driver_set_locale_override], and
+ * its id, title and independent-parent link are reported by [citeproc_rs_driver_style_info].
+ *
+ * # Safety
+ *
+ * `driver` must be a valid pointer to a Driver. Either `style` must refer to a byte array of
+ * length `style_len`, or `style_len` must be zero.
+ */
+citeproc_rs_error_code citeproc_rs_driver_set_dependent_style(struct citeproc_rs_driver *driver,
+                                                              const char *style,
+                                                              uintptr_t style_len);
+
+/**
+ * Writes a JSON description of the style driving the processor
+ * ([citeproc::Processor::style_info]) into a buffer: its id, title, citation format, class,
+ * default and override locales, and the dependent style it was chosen through, if any.
+ *
+ * # Safety
+ *
+ * `driver` must be a valid pointer to a Driver. `user_buf` must match the expected user data
+ * in the BufferOps struct passed to driver's init call.
+ */
+citeproc_rs_error_code citeproc_rs_driver_style_info(struct citeproc_rs_driver *driver,
+                                                     void *user_buf);
+
 /**
  * Clear the last error (thread local).
  */
@@ -515,6 +758,65 @@ citeproc_rs_error_code citeproc_rs_cluster_cite_set_suffix(struct citeproc_rs_cl
                                                            const char *suffix,
                                                            uintptr_t suffix_len);
 
+/**
+ * Takes a read-only snapshot of the driver, which can be used from another thread. See the
+ * thread safety notes on [DriverSnapshot]. Returns null on error.
+ *
+ * # Safety
+ *
+ * `driver` must be a valid pointer to a Driver.
+ */
+struct citeproc_rs_driver_snapshot *citeproc_rs_driver_snapshot(struct citeproc_rs_driver *driver);
+
+/**
+ * Frees a [DriverSnapshot].
+ *
+ * # Safety
+ *
+ * The snapshot must either be from [citeproc_rs_driver_snapshot] or be null.
+ */
+void citeproc_rs_snapshot_free(struct citeproc_rs_driver_snapshot *snapshot);
+
+/**
+ * Writes a formatted cluster into a buffer, like [crate::citeproc_rs_driver_format_cluster].
+ *
+ * # Safety
+ *
+ * `snapshot` must be a valid pointer to a DriverSnapshot. `user_buf` must match the expected
+ * user data in the BufferOps struct passed to the driver's init call.
+ */
+citeproc_rs_error_code citeproc_rs_snapshot_format_cluster(struct citeproc_rs_driver_snapshot *snapshot,
+                                                           citeproc_rs_cluster_id cluster_id,
+                                                           void *user_buf);
+
+/**
+ * Writes a bibliography into a buffer, like [crate::citeproc_rs_driver_format_bibliography].
+ *
+ * # Safety
+ *
+ * `snapshot` must be a valid pointer to a DriverSnapshot. `user_buf` must match the expected
+ * user data in the BufferOps struct passed to the driver's init call.
+ */
+citeproc_rs_error_code citeproc_rs_snapshot_format_bibliography(struct citeproc_rs_driver_snapshot *snapshot,
+                                                                void *user_buf);
+
+/**
+ * Formats a bibliography entry for a reference that need not be in the document, like
+ * [crate::citeproc_rs_driver_preview_reference], using
+ * [citeproc::Processor::preview_reference_read_only].
+ *
+ * # Safety
+ *
+ * `snapshot` must be a valid pointer to a DriverSnapshot. Either `ref_json` must refer to a
+ * byte array of length `ref_json_len`, or `ref_json_len` must be zero. `user_buf` must match
+ * the expected user data in the BufferOps struct passed to the driver's init call.
+ */
+citeproc_rs_error_code citeproc_rs_snapshot_preview_reference(struct citeproc_rs_driver_snapshot *snapshot,
+                                                              const char *ref_json,
+                                                              uintptr_t ref_json_len,
+                                                              citeproc_rs_output_format format,
+                                                              void *user_buf);
+
 /**
  * If you use this as your buffer_write_callback, then you must call [citeproc_rs_cstring_free] on
  * the resulting buffers, or the memory will leak.
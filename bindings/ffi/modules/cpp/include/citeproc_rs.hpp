@@ -9,6 +9,15 @@
 
 namespace citeproc_rs {
 
+enum class ClusterNumberKind {
+  /// The cluster has not been given a position with [citeproc_rs_driver_set_cluster_order].
+  unpositioned,
+  in_text,
+  note,
+  /// An author-only cluster, which is not part of the flow of the document.
+  outside_flow,
+};
+
 enum class ErrorCode : int32_t {
   none = 0,
   null_pointer = 1,
@@ -23,6 +32,21 @@ enum class ErrorCode : int32_t {
   cluster_not_in_flow = 10,
   invalid_style = 11,
   set_logger = 12,
+  locale_fetch = 13,
+  invalid_lang = 14,
+  invalid_locale = 15,
+  locale_lang_mismatch = 16,
+  reference_not_found = 17,
+  limit_exceeded = 18,
+  bundle = 19,
+  unavailable_format = 20,
+};
+
+/// Whether [citeproc_rs_driver_format_references] formats references as citations or as
+/// bibliography entries.
+enum class FormatMode : uint8_t {
+  citation,
+  bibliography,
 };
 
 enum class LevelFilter : uintptr_t {
@@ -85,16 +109,22 @@ struct Cluster;
 
 /// Wrapper for a driver, initialized with one style and any required locales.
 ///
-/// Not thread safe.
+/// Not thread safe. Use [citeproc_rs_driver_snapshot] to format from other threads.
 ///
 /// Contains an Option<citeproc_rs::Processor>, because to survive panics we want to be able to
 /// write a safe value that won't be in an inconsistent state after panicking.
 struct Driver;
 
+/// A read-only view of a [Driver]. Create one with [citeproc_rs_driver_snapshot] and free it
+/// with [citeproc_rs_snapshot_free].
+struct DriverSnapshot;
+
 struct LocaleSlot;
 
 /// A callback signature that is expected to write a string into `slot` via
-/// [citeproc_rs_locale_slot_write]
+/// [citeproc_rs_locale_slot_write], or to call [citeproc_rs_locale_slot_not_found] if there is
+/// no such locale. If it does neither, e.g. because a download failed, the driver will ask again
+/// the next time it needs that locale.
 using LocaleFetchCallback = void(*)(void *context, LocaleSlot *slot, const char*);
 
 /// Should write src_len bytes from src into some structure referenced by user_data.
@@ -130,6 +160,9 @@ struct BufferOps {
 struct InitOptions {
   const char *style;
   uintptr_t style_len;
+  /// Passed to `locale_fetch_callback`. The driver keeps it for locales needed later, e.g. after
+  /// [citeproc_rs_driver_set_locale_override], so it must stay valid until
+  /// [citeproc_rs_driver_free].
   void *locale_fetch_context;
   LocaleFetchCallback locale_fetch_callback;
   OutputFormat format;
@@ -149,6 +182,15 @@ struct ClusterPosition {
   uint32_t note_number;
 };
 
+/// Where a cluster ended up after [citeproc_rs_driver_set_cluster_order].
+struct ClusterNumber {
+  ClusterNumberKind kind;
+  /// The in-text cluster number, or the footnote number. Zero for the other kinds.
+  uint32_t number;
+  /// For note clusters, the position of the cluster within its footnote, starting at zero.
+  uint32_t index_in_note;
+};
+
 using LoggerWriteCallback = void(*)(void *user_data, LogLevel level, const uint8_t *module_path, uintptr_t module_path_len, const uint8_t *src, uintptr_t src_len);
 
 using LoggerFlushCallback = void(*)(void *user_data);
@@ -186,7 +228,12 @@ extern "C" {
 /// Initialises the Rust `log` crate globally. No-op when called a second time.
  void citeproc_rs_log_init();
 
-/// Write an XML string into a LocaleSlot. Returns an error code if the XML does not parse cleanly.
+/// Write an XML string into a LocaleSlot.
+///
+/// Nothing is stored, and an error code returned, if the string is not UTF-8
+/// ([ErrorCode::Utf8]), the XML does not parse cleanly ([ErrorCode::InvalidLocale]), or its
+/// `xml:lang` is not the language the slot is for ([ErrorCode::LocaleLangMismatch]). The
+/// callback can then call [citeproc_rs_locale_slot_not_found], or try another source.
 ///
 /// # Safety:
 ///
@@ -197,7 +244,22 @@ ErrorCode citeproc_rs_locale_slot_write(LocaleSlot *slot,
                                         const char *locale_xml,
                                         uintptr_t locale_xml_len);
 
+/// Tells the driver there is no locale for the slot's language, so it falls back to another
+/// and does not ask for this one again.
+///
+/// # Safety:
+///
+/// Only safe to use inside a [LocaleFetchCallback]. You must pass the slot pointer from the
+/// arguments to the callback.
+ ErrorCode citeproc_rs_locale_slot_not_found(LocaleSlot *slot);
+
 /// Creates a new Processor from InitOptions. Free with [citeproc_rs_driver_free].
+///
+/// # Safety
+///
+/// `init.locale_fetch_context` must stay valid until the driver is freed with
+/// [citeproc_rs_driver_free], as the locale fetch callback can be called with it by any
+/// function that takes the driver.
  Driver *citeproc_rs_driver_new(InitOptions init);
 
 /// Frees a [Driver].
@@ -207,6 +269,54 @@ ErrorCode citeproc_rs_locale_slot_write(LocaleSlot *slot,
 /// The driver must either be from [citeproc_rs_driver_new] or be null.
  void citeproc_rs_driver_free(Driver *driver);
 
+/// Switches the output format ([citeproc::Processor::set_output_format]), e.g. when the user
+/// asks to copy citations as plain text. Clusters and references are kept, and the
+/// `link_anchors` setting carries over.
+///
+/// # Safety
+///
+/// `driver` must be a valid pointer to a Driver.
+ ErrorCode citeproc_rs_driver_set_output_format(Driver *driver, OutputFormat format);
+
+/// Turns clickable links on or off, for URLs and DOIs in HTML and RTF output. See
+/// [citeproc::prelude::FormatOptions::with_link_anchors].
+///
+/// # Safety
+///
+/// `driver` must be a valid pointer to a Driver.
+ ErrorCode citeproc_rs_driver_set_link_anchors(Driver *driver, bool link_anchors);
+
+/// Renders with the locale for `lang`, like `"de-AT"`, instead of the style's default-locale
+/// ([citeproc::Processor::set_locale_override]). An empty `lang` goes back to the style's.
+/// Any locales this needs are requested from the locale fetch callback given to
+/// [citeproc_rs_driver_new] before this returns.
+///
+/// # Safety
+///
+/// `driver` must be a valid pointer to a Driver. Either `lang` must refer to a byte array of
+/// length `lang_len`, or `lang_len` must be zero.
+
+ErrorCode citeproc_rs_driver_set_locale_override(Driver *driver,
+                                                 const char *lang,
+                                                 uintptr_t lang_len);
+
+/// Stores locales the host already has in memory, e.g. ones bundled with an app, using
+/// [citeproc::Processor::store_locales]. Locales stored this way are never requested from the
+/// locale fetch callback.
+///
+/// `locales_json` is a JSON object mapping language tags to locale XML, like
+/// `{"de-DE": "<locale ...>"}`. Every locale is checked as in [citeproc_rs_locale_slot_write]
+/// first, and if any of them fails, none are stored.
+///
+/// # Safety
+///
+/// `driver` must be a valid pointer to a Driver. Either `locales_json` must refer to a byte
+/// array of length `locales_json_len`, or `locales_json_len` must be zero.
+
+ErrorCode citeproc_rs_driver_store_locales(Driver *driver,
+                                           const char *locales_json,
+                                           uintptr_t locales_json_len);
+
 /// [citeproc::Processor::set_cluster_order], but using an ffi-compatible [ClusterPosition]
 ///
 /// # Safety
@@ -219,6 +329,18 @@ ErrorCode citeproc_rs_driver_set_cluster_order(Driver *driver,
                                                const ClusterPosition *positions,
                                                uintptr_t positions_len);
 
+/// Writes the position of a cluster in the document
+/// ([citeproc::Processor::get_cluster_note_number]) into `out`.
+///
+/// # Safety
+///
+/// Driver must be a valid pointer to a Driver. `out` must be a valid pointer to a
+/// [ClusterNumber].
+
+ErrorCode citeproc_rs_driver_cluster_number(Driver *driver,
+                                            ClusterId cluster_id,
+                                            ClusterNumber *out);
+
 /// Writes a formatted cluster ([citeproc::Processor::get_cluster]) into a buffer.
 ///
 /// # Safety
@@ -226,6 +348,22 @@ ErrorCode citeproc_rs_driver_set_cluster_order(Driver *driver,
 ///
  ErrorCode citeproc_rs_driver_format_cluster(Driver *driver, ClusterId cluster_id, void *user_buf);
 
+/// Writes a cluster into a buffer as a JSON array of text runs
+/// ([citeproc::Processor::get_cluster_runs]), so you can build a native attributed string
+/// instead of parsing HTML. Each run looks like
+/// `{ "text": "Title", "italic": true, "link": "https://..." }`; the attributes are `italic`,
+/// `bold`, `smallCaps`, `underline`, `superscript`, `subscript` and `link`, and are left out
+/// when not set. The driver's output format does not matter.
+///
+/// # Safety
+///
+/// `driver` must be a valid pointer to a Driver. `user_buf` must match the expected user data
+/// in the BufferOps struct passed to the driver's init call.
+
+ErrorCode citeproc_rs_driver_built_cluster_runs(Driver *driver,
+                                                ClusterId cluster_id,
+                                                void *user_buf);
+
 /// Writes a bibliography into a buffer, using [citeproc::Processor::get_bibliography]
  ErrorCode citeproc_rs_driver_format_bibliography(Driver *driver, void *user_buf);
 
@@ -245,6 +383,49 @@ ErrorCode citeproc_rs_driver_preview_reference(Driver *driver,
                                                OutputFormat format,
                                                void *user_buf);
 
+/// Formats each of a list of references on its own, without creating any clusters, using
+/// [citeproc::Processor::format_references].
+///
+/// `ids_json` is a JSON array of reference ids. The results are written into user_buf one
+/// per line, in the same order.
+///
+/// # Safety
+///
+/// `driver` must be a valid pointer to a Driver. Either `ids_json` must refer to a byte array
+/// of length `ids_json_len`, or `ids_json_len` must be zero. `user_buf` must match the
+/// expected user data in the BufferOps struct passed to driver's init call.
+
+ErrorCode citeproc_rs_driver_format_references(Driver *driver,
+                                               const char *ids_json,
+                                               uintptr_t ids_json_len,
+                                               FormatMode mode,
+                                               void *user_buf);
+
+/// Writes the stored reference with this id into a buffer as a CSL-JSON object
+/// ([citeproc::Processor::export_reference]).
+///
+/// Returns [ErrorCode::ReferenceNotFound] if there is no such reference.
+///
+/// # Safety
+///
+/// `driver` must be a valid pointer to a Driver. Either `id` must refer to a byte array of
+/// length `id_len`, or `id_len` must be zero. `user_buf` must match the expected user data in
+/// the BufferOps struct passed to the driver's init call.
+
+ErrorCode citeproc_rs_driver_get_reference(Driver *driver,
+                                           const char *id,
+                                           uintptr_t id_len,
+                                           void *user_buf);
+
+/// Writes the ids of every stored reference into a buffer, as a JSON array of strings in the
+/// order they were inserted ([citeproc::Processor::reference_ids]).
+///
+/// # Safety
+///
+/// `driver` must be a valid pointer to a Driver. `user_buf` must match the expected user data
+/// in the BufferOps struct passed to the driver's init call.
+ ErrorCode citeproc_rs_driver_list_references(Driver *driver, void *user_buf);
+
 /// Inserts a reference. [citeproc::Processor::insert_reference]
 ///
 /// Returns an error code.
@@ -259,6 +440,30 @@ ErrorCode citeproc_rs_driver_insert_reference(Driver *driver,
                                               const char *ref_json,
                                               uintptr_t ref_json_len);
 
+/// Tells the driver that its style was chosen via this dependent style, using
+/// [citeproc::Processor::set_dependent_style]. The dependent style's default-locale becomes
+/// the locale override, unless one was set with [citeproc_rs_driver_set_locale_override], and
+/// its id, title and independent-parent link are reported by [citeproc_rs_driver_style_info].
+///
+/// # Safety
+///
+/// `driver` must be a valid pointer to a Driver. Either `style` must refer to a byte array of
+/// length `style_len`, or `style_len` must be zero.
+
+ErrorCode citeproc_rs_driver_set_dependent_style(Driver *driver,
+                                                 const char *style,
+                                                 uintptr_t style_len);
+
+/// Writes a JSON description of the style driving the processor
+/// ([citeproc::Processor::style_info]) into a buffer: its id, title, citation format, class,
+/// default and override locales, and the dependent style it was chosen through, if any.
+///
+/// # Safety
+///
+/// `driver` must be a valid pointer to a Driver. `user_buf` must match the expected user data
+/// in the BufferOps struct passed to driver's init call.
+ ErrorCode citeproc_rs_driver_style_info(Driver *driver, void *user_buf);
+
 /// Clear the last error (thread local).
  void citeproc_rs_last_error_clear();
 
@@ -428,6 +633,56 @@ ErrorCode citeproc_rs_cluster_cite_set_suffix(Cluster *cluster,
                                               const char *suffix,
                                               uintptr_t suffix_len);
 
+/// Takes a read-only snapshot of the driver, which can be used from another thread. See the
+/// thread safety notes on [DriverSnapshot]. Returns null on error.
+///
+/// # Safety
+///
+/// `driver` must be a valid pointer to a Driver.
+ DriverSnapshot *citeproc_rs_driver_snapshot(Driver *driver);
+
+/// Frees a [DriverSnapshot].
+///
+/// # Safety
+///
+/// The snapshot must either be from [citeproc_rs_driver_snapshot] or be null.
+ void citeproc_rs_snapshot_free(DriverSnapshot *snapshot);
+
+/// Writes a formatted cluster into a buffer, like [crate::citeproc_rs_driver_format_cluster].
+///
+/// # Safety
+///
+/// `snapshot` must be a valid pointer to a DriverSnapshot. `user_buf` must match the expected
+/// user data in the BufferOps struct passed to the driver's init call.
+
+ErrorCode citeproc_rs_snapshot_format_cluster(DriverSnapshot *snapshot,
+                                              ClusterId cluster_id,
+                                              void *user_buf);
+
+/// Writes a bibliography into a buffer, like [crate::citeproc_rs_driver_format_bibliography].
+///
+/// # Safety
+///
+/// `snapshot` must be a valid pointer to a DriverSnapshot. `user_buf` must match the expected
+/// user data in the BufferOps struct passed to the driver's init call.
+ ErrorCode citeproc_rs_snapshot_format_bibliography(DriverSnapshot *snapshot, void *user_buf);
+
+/// Formats a bibliography entry for a reference that need not be in the document, like
+/// [crate::citeproc_rs_driver_preview_reference], using
+/// [citeproc::Processor::preview_reference_read_only].
+///
+/// # Safety
+///
+/// `snapshot` must be a valid pointer to a DriverSnapshot. Either `ref_json` must refer to a
+/// byte array of length `ref_json_len`, or `ref_json_len` must be zero. `user_buf` must match
+/// the expected user data in the BufferOps struct passed to the driver's init call.
+
+ErrorCode citeproc_rs_snapshot_preview_reference(DriverSnapshot *snapshot,
+                                                 const char *ref_json,
+                                                 uintptr_t ref_json_len,
+                                                 OutputFormat format,
+                                                 void *user_buf);
+
 } // extern "C"
 
 /// If you use this as your buffer_write_callback, then you must call [citeproc_rs_cstring_free] on
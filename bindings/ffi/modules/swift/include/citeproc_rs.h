@@ -9,6 +9,19 @@
 #include <stdlib.h>
 #include <CoreFoundation/CoreFoundation.h>
 
+typedef enum CRClusterNumberKind {
+  /**
+   * The cluster has not been given a position with [citeproc_rs_driver_set_cluster_order].
+   */
+  CRClusterNumberKind_Unpositioned,
+  CRClusterNumberKind_InText,
+  CRClusterNumberKind_Note,
+  /**
+   * An author-only cluster, which is not part of the flow of the document.
+   */
+  CRClusterNumberKind_OutsideFlow,
+} CRClusterNumberKind;
+
 typedef CF_ENUM(int32_t, CRErrorCode) {
   CRErrorCode_None = 0,
   CRErrorCode_NullPointer = 1,
@@ -23,6 +36,23 @@ typedef CF_ENUM(int32_t, CRErrorCode) {
   CRErrorCode_ClusterNotInFlow = 10,
   CRErrorCode_InvalidStyle = 11,
   CRErrorCode_SetLogger = 12,
+  CRErrorCode_LocaleFetch = 13,
+  CRErrorCode_InvalidLang = 14,
+  CRErrorCode_InvalidLocale = 15,
+  CRErrorCode_LocaleLangMismatch = 16,
+  CRErrorCode_ReferenceNotFound = 17,
+  CRErrorCode_LimitExceeded = 18,
+  CRErrorCode_Bundle = 19,
+  CRErrorCode_UnavailableFormat = 20,
+};
+
+/**
+ * Whether [citeproc_rs_driver_format_references] formats references as citations or as
+ * bibliography entries.
+ */
+typedef CF_ENUM(uint8_t, CRFormatMode) {
+  CRFormatMode_Citation,
+  CRFormatMode_Bibliography,
 };
 
 typedef CF_ENUM(uintptr_t, CRLevelFilter) {
@@ -98,18 +128,26 @@ typedef struct CRCluster CRCluster;
 /**
  * Wrapper for a driver, initialized with one style and any required locales.
  *
- * Not thread safe.
+ * Not thread safe. Use [citeproc_rs_driver_snapshot] to format from other threads.
  *
  * Contains an Option<citeproc_rs::Processor>, because to survive panics we want to be able to
  * write a safe value that won't be in an inconsistent state after panicking.
  */
 typedef struct CRDriver CRDriver;
 
+/**
+ * A read-only view of a [Driver]. Create one with [citeproc_rs_driver_snapshot] and free it
+ * with [citeproc_rs_snapshot_free].
+ */
+typedef struct CRDriverSnapshot CRDriverSnapshot;
+
 typedef struct CRLocaleSlot CRLocaleSlot;
 
 /**
  * A callback signature that is expected to write a string into `slot` via
- * [citeproc_rs_locale_slot_write]
+ * [citeproc_rs_locale_slot_write], or to call [citeproc_rs_locale_slot_not_found] if there is
+ * no such locale. If it does neither, e.g. because a download failed, the driver will ask again
+ * the next time it needs that locale.
  */
 typedef void (*CRLocaleFetchCallback)(void *context, struct CRLocaleSlot *slot, const char*);
 
@@ -152,6 +190,11 @@ typedef struct CRBufferOps {
 typedef struct CRInitOptions {
   const char *style;
   uintptr_t style_len;
+  /**
+   * Passed to `locale_fetch_callback`. The driver keeps it for locales needed later, e.g. after
+   * [citeproc_rs_driver_set_locale_override], so it must stay valid until
+   * [citeproc_rs_driver_free].
+   */
   void *locale_fetch_context;
   CRLocaleFetchCallback locale_fetch_callback;
   CROutputFormat format;
@@ -179,6 +222,21 @@ typedef struct CRClusterPosition {
   uint32_t note_number;
 } CRClusterPosition;
 
+/**
+ * Where a cluster ended up after [citeproc_rs_driver_set_cluster_order].
+ */
+typedef struct CRClusterNumber {
+  enum CRClusterNumberKind kind;
+  /**
+   * The in-text cluster number, or the footnote number. Zero for the other kinds.
+   */
+  uint32_t number;
+  /**
+   * For note clusters, the position of the cluster within its footnote, starting at zero.
+   */
+  uint32_t index_in_note;
+} CRClusterNumber;
+
 typedef void (*CRLoggerWriteCallback)(void *user_data, CRLogLevel level, const uint8_t *module_path, uintptr_t module_path_len, const uint8_t *src, uintptr_t src_len);
 
 typedef void (*CRLoggerFlushCallback)(void *user_data);
@@ -219,7 +277,12 @@ typedef int64_t CRU32OrError;
 void citeproc_rs_log_init(void) CF_SWIFT_NAME(citeproc_rs_log_init());
 
 /**
- * Write an XML string into a LocaleSlot. Returns an error code if the XML does not parse cleanly.
+ * Write an XML string into a LocaleSlot.
+ *
+ * Nothing is stored, and an error code returned, if the string is not UTF-8
+ * ([ErrorCode::Utf8]), the XML does not parse cleanly ([ErrorCode::InvalidLocale]), or its
+ * `xml:lang` is not the language the slot is for ([ErrorCode::LocaleLangMismatch]). The
+ * callback can then call [citeproc_rs_locale_slot_not_found], or try another source.
  *
  * # Safety:
  *
@@ -230,8 +293,25 @@ CRErrorCode citeproc_rs_locale_slot_write(struct CRLocaleSlot *slot,
                                           const char *locale_xml,
                                           uintptr_t locale_xml_len) CF_SWIFT_NAME(citeproc_rs_locale_slot_write(slot:locale_xml:locale_xml_len:));
 
+/**
+ * Tells the driver there is no locale for the slot's language, so it falls back to another
+ * and does not ask for this one again.
+ *
+ * # Safety:
+ *
+ * Only safe to use inside a [LocaleFetchCallback]. You must pass the slot pointer from the
+ * arguments to the callback.
+ */
+CRErrorCode citeproc_rs_locale_slot_not_found(struct CRLocaleSlot *slot) CF_SWIFT_NAME(citeproc_rs_locale_slot_not_found(slot:));
+
 /**
  * Creates a new Processor from InitOptions. Free with [citeproc_rs_driver_free].
+ *
+ * # Safety
+ *
+ * `init.locale_fetch_context` must stay valid until the driver is freed with
+ * [citeproc_rs_driver_free], as the locale fetch callback can be called with it by any
+ * function that takes the driver.
  */
 struct CRDriver *citeproc_rs_driver_new(struct CRInitOptions init) CF_SWIFT_NAME(citeproc_rs_driver_new(init:));
 
@@ -244,6 +324,62 @@ struct CRDriver *citeproc_rs_driver_new(struct CRInitOptions init) CF_SWIFT_NAME
  */
 void citeproc_rs_driver_free(struct CRDriver *driver) CF_SWIFT_NAME(citeproc_rs_driver_free(driver:));
 
+/**
+ * Switches the output format ([citeproc::Processor::set_output_format]), e.g. when the user
+ * asks to copy citations as plain text. Clusters and references are kept, and the
+ * `link_anchors` setting carries over.
+ *
+ * # Safety
+ *
+ * `driver` must be a valid pointer to a Driver.
+ */
+CRErrorCode citeproc_rs_driver_set_output_format(struct CRDriver *driver,
+                                                 CROutputFormat format) CF_SWIFT_NAME(citeproc_rs_driver_set_output_format(driver:format:));
+
+/**
+ * Turns clickable links on or off, for URLs and DOIs in HTML and RTF output. See
+ * [citeproc::prelude::FormatOptions::with_link_anchors].
+ *
+ * # Safety
+ *
+ * `driver` must be a valid pointer to a Driver.
+ */
+CRErrorCode citeproc_rs_driver_set_link_anchors(struct CRDriver *driver,
+                                                bool link_anchors) CF_SWIFT_NAME(citeproc_rs_driver_set_link_anchors(driver:link_anchors:));
+
+/**
+ * Renders with the locale for `lang`, like `"de-AT"`, instead of the style's default-locale
+ * ([citeproc::Processor::set_locale_override]). An empty `lang` goes back to the style's.
+ * Any locales this needs are requested from the locale fetch callback given to
+ * [citeproc_rs_driver_new] before this returns.
+ *
+ * # Safety
+ *
+ * `driver` must be a valid pointer to a Driver. Either `lang` must refer to a byte array of
+ * length `lang_len`, or `lang_len` must be zero.
+ */
+CRErrorCode citeproc_rs_driver_set_locale_override(struct CRDriver *driver,
+                                                   const char *lang,
+                                                   uintptr_t lang_len) CF_SWIFT_NAME(citeproc_rs_driver_set_locale_override(driver:lang:lang_len:));
+
+/**
+ * Stores locales the host already has in memory, e.g. ones bundled with an app, using
+ * [citeproc::Processor::store_locales]. Locales stored this way are never requested from the
+ * locale fetch callback.
+ *
+ * `locales_json` is a JSON object mapping language tags to locale XML, like
+ * `{"de-DE": "<locale ...>"}`. Every locale is checked as in [citeproc_rs_locale_slot_write]
+ * first, and if any of them fails, none are stored.
+ *
+ * # Safety
+ *
+ * `driver` must be a valid pointer to a Driver. Either `locales_json` must refer to a byte
+ * array of length `locales_json_len`, or `locales_json_len` must be zero.
+ */
+CRErrorCode citeproc_rs_driver_store_locales(struct CRDriver *driver,
+                                             const char *locales_json,
+                                             uintptr_t locales_json_len) CF_SWIFT_NAME(citeproc_rs_driver_store_locales(driver:locales_json:locales_json_len:));
+
 /**
  * [citeproc::Processor::set_cluster_order], but using an ffi-compatible [ClusterPosition]
  *
@@ -257,6 +393,19 @@ CRErrorCode citeproc_rs_driver_set_cluster_order(struct CRDriver *driver,
                                                  const struct CRClusterPosition *positions,
                                                  uintptr_t positions_len) CF_SWIFT_NAME(citeproc_rs_driver_set_cluster_order(driver:positions:positions_len:));
 
+/**
+ * Writes the position of a cluster in the document
+ * ([citeproc::Processor::get_cluster_note_number]) into `out`.
+ *
+ * # Safety
+ *
+ * Driver must be a valid pointer to a Driver. `out` must be a valid pointer to a
+ * [ClusterNumber].
+ */
+CRErrorCode citeproc_rs_driver_cluster_number(struct CRDriver *driver,
+                                              CRClusterId cluster_id,
+                                              struct CRClusterNumber *out) CF_SWIFT_NAME(citeproc_rs_driver_cluster_number(driver:cluster_id:out:));
+
 /**
  * Writes a formatted cluster ([citeproc::Processor::get_cluster]) into a buffer.
  *
@@ -268,6 +417,23 @@ CRErrorCode citeproc_rs_driver_format_cluster(struct CRDriver *driver,
                                               CRClusterId cluster_id,
                                               void *user_buf) CF_SWIFT_NAME(citeproc_rs_driver_format_cluster(driver:cluster_id:user_buf:));
 
+/**
+ * Writes a cluster into a buffer as a JSON array of text runs
+ * ([citeproc::Processor::get_cluster_runs]), so you can build a native attributed string
+ * instead of parsing HTML. Each run looks like
+ * `{ "text": "Title", "italic": true, "link": "https://..." }`; the attributes are `italic`,
+ * `bold`, `smallCaps`, `underline`, `superscript`, `subscript` and `link`, and are left out
+ * when not set. The driver's output format does not matter.
+ *
+ * # Safety
+ *
+ * `driver` must be a valid pointer to a Driver. `user_buf` must match the expected user data
+ * in the BufferOps struct passed to the driver's init call.
+ */
+CRErrorCode citeproc_rs_driver_built_cluster_runs(struct CRDriver *driver,
+                                                  CRClusterId cluster_id,
+                                                  void *user_buf) CF_SWIFT_NAME(citeproc_rs_driver_built_cluster_runs(driver:cluster_id:user_buf:));
+
 /**
  * Writes a bibliography into a buffer, using [citeproc::Processor::get_bibliography]
  */
@@ -291,6 +457,54 @@ CRErrorCode citeproc_rs_driver_preview_reference(struct CRDriver *driver,
                                                  CROutputFormat format,
                                                  void *user_buf) CF_SWIFT_NAME(citeproc_rs_driver_preview_reference(driver:ref_json:ref_json_len:format:user_buf:));
 
+/**
+ * Formats each of a list of references on its own, without creating any clusters, using
+ * [citeproc::Processor::format_references].
+ *
+ * `ids_json` is a JSON array of reference ids. The results are written into user_buf one
+ * per line, in the same order.
+ *
+ * # Safety
+ *
+ * `driver` must be a valid pointer to a Driver. Either `ids_json` must refer to a byte array
+ * of length `ids_json_len`, or `ids_json_len` must be zero. `user_buf` must match the
+ * expected user data in the BufferOps struct passed to driver's init call.
+ */
+CRErrorCode citeproc_rs_driver_format_references(struct CRDriver *driver,
+                                                 const char *ids_json,
+                                                 uintptr_t ids_json_len,
+                                                 CRFormatMode mode,
+                                                 void *user_buf) CF_SWIFT_NAME(citeproc_rs_driver_format_references(driver:ids_json:ids_json_len:mode:user_buf:));
+
+/**
+ * Writes the stored reference with this id into a buffer as a CSL-JSON object
+ * ([citeproc::Processor::export_reference]).
+ *
+ * Returns [ErrorCode::ReferenceNotFound] if there is no such reference.
+ *
+ * # Safety
+ *
+ * `driver` must be a valid pointer to a Driver. Either `id` must refer to a byte array of
+ * length `id_len`, or `id_len` must be zero. `user_buf` must match the expected user data in
+ * the BufferOps struct passed to the driver's init call.
+ */
+CRErrorCode citeproc_rs_driver_get_reference(struct CRDriver *driver,
+                                             const char *id,
+                                             uintptr_t id_len,
+                                             void *user_buf) CF_SWIFT_NAME(citeproc_rs_driver_get_reference(driver:id:id_len:user_buf:));
+
+/**
+ * Writes the ids of every stored reference into a buffer, as a JSON array of strings in the
+ * order they were inserted ([citeproc::Processor::reference_ids]).
+ *
+ * # Safety
+ *
+ * `driver` must be a valid pointer to a Driver. `user_buf` must match the expected user data
+ * in the BufferOps struct passed to the driver's init call.
+ */
+CRErrorCode citeproc_rs_driver_list_references(struct CRDriver *driver,
+                                               void *user_buf) CF_SWIFT_NAME(citeproc_rs_driver_list_references(driver:user_buf:));
+
 /**
  * Inserts a reference. [citeproc::Processor::insert_reference]
  *
@@ -306,6 +520,34 @@ CRErrorCode citeproc_rs_driver_insert_reference(struct CRDriver *driver,
                                                 const char *ref_json,
                                                 uintptr_t ref_json_len) CF_SWIFT_NAME(citeproc_rs_driver_insert_reference(driver:ref_json:ref_json_len:));
 
+/**
+ * Tells the driver that its style was chosen via this dependent style, using
+ * [citeproc::Processor::set_dependent_style]. The dependent style's default-locale becomes
+ * the locale override, unless one was set with [citeproc_rs_driver_set_locale_override], and
+ * its id, title and independent-parent link are reported by [citeproc_rs_driver_style_info].
+ *
+ * # Safety
+ *
+ * `driver` must be a valid pointer to a Driver. Either `style` must refer to a byte array of
+ * length `style_len`, or `style_len` must be zero.
+ */
+CRErrorCode citeproc_rs_driver_set_dependent_style(struct CRDriver *driver,
+                                                   const char *style,
+                                                   uintptr_t style_len) CF_SWIFT_NAME(citeproc_rs_driver_set_dependent_style(driver:style:style_len:));
+
+/**
+ * Writes a JSON description of the style driving the processor
+ * ([citeproc::Processor::style_info]) into a buffer: its id, title, citation format, class,
+ * default and override locales, and the dependent style it was chosen through, if any.
+ *
+ * # Safety
+ *
+ * `driver` must be a valid pointer to a Driver. `user_buf` must match the expected user data
+ * in the BufferOps struct passed to driver's init call.
+ */
+CRErrorCode citeproc_rs_driver_style_info(struct CRDriver *driver,
+                                          void *user_buf) CF_SWIFT_NAME(citeproc_rs_driver_style_info(driver:user_buf:));
+
 CRErrorCode test_panic(void) CF_SWIFT_NAME(test_panic());
 
 CRErrorCode test_panic_poison_driver(struct CRDriver *_driver) CF_SWIFT_NAME(test_panic_poison_driver(_driver:));
@@ -524,6 +766,65 @@ CRErrorCode citeproc_rs_cluster_cite_set_suffix(struct CRCluster *cluster,
                                                 const char *suffix,
                                                 uintptr_t suffix_len) CF_SWIFT_NAME(citeproc_rs_cluster_cite_set_suffix(cluster:cite_index:suffix:suffix_len:));
 
+/**
+ * Takes a read-only snapshot of the driver, which can be used from another thread. See the
+ * thread safety notes on [DriverSnapshot]. Returns null on error.
+ *
+ * # Safety
+ *
+ * `driver` must be a valid pointer to a Driver.
+ */
+struct CRDriverSnapshot *citeproc_rs_driver_snapshot(struct CRDriver *driver) CF_SWIFT_NAME(citeproc_rs_driver_snapshot(driver:));
+
+/**
+ * Frees a [DriverSnapshot].
+ *
+ * # Safety
+ *
+ * The snapshot must either be from [citeproc_rs_driver_snapshot] or be null.
+ */
+void citeproc_rs_snapshot_free(struct CRDriverSnapshot *snapshot) CF_SWIFT_NAME(citeproc_rs_snapshot_free(snapshot:));
+
+/**
+ * Writes a formatted cluster into a buffer, like [crate::citeproc_rs_driver_format_cluster].
+ *
+ * # Safety
+ *
+ * `snapshot` must be a valid pointer to a DriverSnapshot. `user_buf` must match the expected
+ * user data in the BufferOps struct passed to the driver's init call.
+ */
+CRErrorCode citeproc_rs_snapshot_format_cluster(struct CRDriverSnapshot *snapshot,
+                                                CRClusterId cluster_id,
+                                                void *user_buf) CF_SWIFT_NAME(citeproc_rs_snapshot_format_cluster(snapshot:cluster_id:user_buf:));
+
+/**
+ * Writes a bibliography into a buffer, like [crate::citeproc_rs_driver_format_bibliography].
+ *
+ * # Safety
+ *
+ * `snapshot` must be a valid pointer to a DriverSnapshot. `user_buf` must match the expected
+ * user data in the BufferOps struct passed to the driver's init call.
+ */
+CRErrorCode citeproc_rs_snapshot_format_bibliography(struct CRDriverSnapshot *snapshot,
+                                                     void *user_buf) CF_SWIFT_NAME(citeproc_rs_snapshot_format_bibliography(snapshot:user_buf:));
+
+/**
+ * Formats a bibliography entry for a reference that need not be in the document, like
+ * [crate::citeproc_rs_driver_preview_reference], using
+ * [citeproc::Processor::preview_reference_read_only].
+ *
+ * # Safety
+ *
+ * `snapshot` must be a valid pointer to a DriverSnapshot. Either `ref_json` must refer to a
+ * byte array of length `ref_json_len`, or `ref_json_len` must be zero. `user_buf` must match
+ * the expected user data in the BufferOps struct passed to the driver's init call.
+ */
+CRErrorCode citeproc_rs_snapshot_preview_reference(struct CRDriverSnapshot *snapshot,
+                                                   const char *ref_json,
+                                                   uintptr_t ref_json_len,
+                                                   CROutputFormat format,
+                                                   void *user_buf) CF_SWIFT_NAME(citeproc_rs_snapshot_preview_reference(snapshot:ref_json:ref_json_len:format:user_buf:));
+
 /**
  * If you use this as your buffer_write_callback, then you must call [citeproc_rs_cstring_free] on
  * the resulting buffers, or the memory will leak.
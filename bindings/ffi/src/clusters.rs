@@ -24,6 +24,7 @@ ffi_fn_nullify! {
             id: rust::ClusterId(id),
             cites: Vec::new(),
             mode: None,
+            lang: None,
         }));
         Box::into_raw(boxed)
     }
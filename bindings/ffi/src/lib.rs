@@ -19,12 +19,15 @@ pub mod buffer;
 mod nullable;
 
 mod clusters;
+mod snapshot;
 pub use clusters::*;
 pub use errors::*;
+pub use snapshot::*;
 
 use thiserror::Error;
 
 use libc::{c_char, c_void};
+use std::collections::HashMap;
 use std::ffi::CStr;
 use std::sync::Arc;
 
@@ -57,6 +60,22 @@ pub enum FFIError {
     InvalidStyle(#[from] csl::StyleError),
     #[error("could not set logger: {0}")]
     SetLogger(#[from] log::SetLoggerError),
+    #[error("locale fetch error: {0}")]
+    LocaleFetch(#[from] rust::LocaleFetchError),
+    #[error("invalid language tag: {0:?}")]
+    InvalidLang(String),
+    #[error("locale error: {0}")]
+    InvalidLocale(csl::StyleError),
+    #[error("locale slot for {expected} was given a locale for {found}")]
+    LocaleLangMismatch { expected: Lang, found: Lang },
+    #[error("no reference with id {0:?}")]
+    ReferenceNotFound(String),
+    #[error("{0}")]
+    LimitExceeded(#[from] citeproc::LimitExceeded),
+    #[error("{0}")]
+    Bundle(#[from] citeproc::BundleError),
+    #[error("the {} output format was left out of this build", .0.as_str())]
+    UnavailableFormat(rust::SupportedFormat),
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
@@ -75,6 +94,14 @@ pub enum ErrorCode {
     ClusterNotInFlow = 10,
     InvalidStyle = 11,
     SetLogger = 12,
+    LocaleFetch = 13,
+    InvalidLang = 14,
+    InvalidLocale = 15,
+    LocaleLangMismatch = 16,
+    ReferenceNotFound = 17,
+    LimitExceeded = 18,
+    Bundle = 19,
+    UnavailableFormat = 20,
 }
 
 impl FFIError {
@@ -101,6 +128,28 @@ impl FFIError {
             Self::ClusterNotInFlow(_) => ErrorCode::ClusterNotInFlow,
             Self::InvalidStyle(_) => ErrorCode::InvalidStyle,
             Self::SetLogger(_) => ErrorCode::SetLogger,
+            Self::LocaleFetch(_) => ErrorCode::LocaleFetch,
+            Self::InvalidLang(_) => ErrorCode::InvalidLang,
+            Self::InvalidLocale(_) => ErrorCode::InvalidLocale,
+            Self::LocaleLangMismatch { .. } => ErrorCode::LocaleLangMismatch,
+            Self::ReferenceNotFound(_) => ErrorCode::ReferenceNotFound,
+            Self::LimitExceeded(_) => ErrorCode::LimitExceeded,
+            Self::Bundle(_) => ErrorCode::Bundle,
+            Self::UnavailableFormat(_) => ErrorCode::UnavailableFormat,
+        }
+    }
+}
+
+impl From<citeproc::Error> for FFIError {
+    fn from(e: citeproc::Error) -> Self {
+        match e {
+            citeproc::Error::Style(e) => FFIError::InvalidStyle(e),
+            citeproc::Error::LocaleFetch(e) => FFIError::LocaleFetch(e),
+            citeproc::Error::Json(e) => FFIError::SerdeJson(e),
+            citeproc::Error::Reordering(e) => FFIError::Reordering(e),
+            citeproc::Error::LimitExceeded(e) => FFIError::LimitExceeded(e),
+            citeproc::Error::Bundle(e) => FFIError::Bundle(e),
+            citeproc::Error::UnavailableFormat(format) => FFIError::UnavailableFormat(format),
         }
     }
 }
@@ -118,7 +167,7 @@ pub extern "C" fn citeproc_rs_log_init() {
 
 /// Wrapper for a driver, initialized with one style and any required locales.
 ///
-/// Not thread safe.
+/// Not thread safe. Use [citeproc_rs_driver_snapshot] to format from other threads.
 ///
 /// Contains an Option<citeproc_rs::Processor>, because to survive panics we want to be able to
 /// write a safe value that won't be in an inconsistent state after panicking.
@@ -127,6 +176,38 @@ pub struct Driver {
     buffer_ops: buffer::BufferOps,
     /// Scratch buffer for translating FFI cluster positions
     positions_scratch: Vec<rust::ClusterPosition>,
+    format: OutputFormat,
+    /// Kept from InitOptions, for locales needed after a change of configuration
+    locale_fetch_context: *mut c_void,
+    locale_fetch_callback: LocaleFetchCallback,
+    /// Languages the host said it has no locale for, which it won't be asked for again
+    locales_not_found: Vec<Lang>,
+}
+
+impl Driver {
+    /// Asks the host, via the locale fetch callback, for any locales the processor needs but does
+    /// not have yet.
+    fn fetch_missing_locales(&mut self) -> Result<(), FFIError> {
+        let proc = self.processor.as_mut().ok_or(FFIError::Poisoned)?;
+        let not_found = &self.locales_not_found;
+        let mut langs = proc.get_langs_in_use();
+        langs.retain(|lang| !proc.has_cached_locale(lang) && !not_found.contains(lang));
+        if langs.is_empty() || self.locale_fetch_callback.is_none() {
+            return Ok(());
+        }
+        let ffi_locales = LocaleFetcher {
+            callback: self.locale_fetch_callback,
+            context: self.locale_fetch_context,
+            storage: LocaleStorage {
+                locales: Vec::with_capacity(langs.len()),
+                not_found: Vec::new(),
+            },
+        };
+        let storage = ffi_locales.build(&langs);
+        proc.store_locales(storage.locales);
+        self.locales_not_found.extend(storage.not_found);
+        Ok(())
+    }
 }
 
 /// This writes the safe state (None) and also drops the processor and all its memory. If you
@@ -139,7 +220,9 @@ impl macros::MakeUnwindSafe for Driver {
 }
 
 /// A callback signature that is expected to write a string into `slot` via
-/// [citeproc_rs_locale_slot_write]
+/// [citeproc_rs_locale_slot_write], or to call [citeproc_rs_locale_slot_not_found] if there is
+/// no such locale. If it does neither, e.g. because a download failed, the driver will ask again
+/// the next time it needs that locale.
 pub type LocaleFetchCallback =
     Option<unsafe extern "C" fn(context: *mut c_void, slot: *mut LocaleSlot, *const c_char)>;
 
@@ -181,12 +264,31 @@ impl LocaleFetcher {
     }
 }
 
+/// We'll parse locales preliminarily so you catch errors before they become invisible as
+/// mysteriously missing locales
+fn check_locale(lang: &Lang, locale_xml: &str) -> Result<(), FFIError> {
+    let locale = Locale::parse(locale_xml).map_err(FFIError::InvalidLocale)?;
+    match locale.lang {
+        Some(found) if found != *lang => Err(FFIError::LocaleLangMismatch {
+            expected: lang.clone(),
+            found,
+        }),
+        _ => Ok(()),
+    }
+}
+
 struct LocaleStorage {
     locales: Vec<(Lang, String)>,
+    not_found: Vec<Lang>,
 }
 
 ffi_fn_nullify! {
-    /// Write an XML string into a LocaleSlot. Returns an error code if the XML does not parse cleanly.
+    /// Write an XML string into a LocaleSlot.
+    ///
+    /// Nothing is stored, and an error code returned, if the string is not UTF-8
+    /// ([ErrorCode::Utf8]), the XML does not parse cleanly ([ErrorCode::InvalidLocale]), or its
+    /// `xml:lang` is not the language the slot is for ([ErrorCode::LocaleLangMismatch]). The
+    /// callback can then call [citeproc_rs_locale_slot_not_found], or try another source.
     ///
     /// # Safety:
     ///
@@ -198,15 +300,32 @@ ffi_fn_nullify! {
             let slot = unsafe { borrow_raw_ptr_mut(slot) } ?;
             // Safety: we asked folks to give us an XML string.
             let locale_xml = unsafe { borrow_utf8_slice(locale_xml, locale_xml_len) } ?;
-            // We'll parse it preliminarily so you catch errors before they become invisible as
-            // mysteriously missing locales
-            let _ = Locale::parse(locale_xml)?;
-            // Safety: we control slot, and the only time
-            let storage = unsafe { borrow_raw_ptr_mut(slot.storage) } ?;
             // Safety: we control slot
             let lang = unsafe { borrow_raw_ptr(slot.lang) } ?;
+            check_locale(lang, locale_xml)?;
+            // Safety: we control slot, and the only time
+            let storage = unsafe { borrow_raw_ptr_mut(slot.storage) } ?;
             storage.locales.push((lang.clone(), locale_xml.to_owned()));
-            // println!("added locale for lang: {}", lang);
+            Ok(ErrorCode::None)
+        })
+    }
+}
+
+ffi_fn_nullify! {
+    /// Tells the driver there is no locale for the slot's language, so it falls back to another
+    /// and does not ask for this one again.
+    ///
+    /// # Safety:
+    ///
+    /// Only safe to use inside a [LocaleFetchCallback]. You must pass the slot pointer from the
+    /// arguments to the callback.
+    @safety unsafe fn citeproc_rs_locale_slot_not_found(slot: *mut LocaleSlot) -> ErrorCode {
+        result_to_error_code(|| {
+            let slot = unsafe { borrow_raw_ptr_mut(slot) } ?;
+            let lang = unsafe { borrow_raw_ptr(slot.lang) } ?;
+            let storage = unsafe { borrow_raw_ptr_mut(slot.storage) } ?;
+            log::info!("no locale available for {}", lang);
+            storage.not_found.push(lang.clone());
             Ok(ErrorCode::None)
         })
     }
@@ -220,10 +339,31 @@ pub enum OutputFormat {
     Plain,
 }
 
+/// Whether [citeproc_rs_driver_format_references] formats references as citations or as
+/// bibliography entries.
+#[derive(Copy, Clone, Debug)]
+#[repr(u8)]
+pub enum FormatMode {
+    Citation,
+    Bibliography,
+}
+
+impl FormatMode {
+    fn to_cite_or_bib(self) -> csl::CiteOrBib {
+        match self {
+            FormatMode::Citation => csl::CiteOrBib::Citation,
+            FormatMode::Bibliography => csl::CiteOrBib::Bibliography,
+        }
+    }
+}
+
 #[repr(C)]
 pub struct InitOptions {
     pub style: *const c_char,
     pub style_len: usize,
+    /// Passed to `locale_fetch_callback`. The driver keeps it for locales needed later, e.g. after
+    /// [citeproc_rs_driver_set_locale_override], so it must stay valid until
+    /// [citeproc_rs_driver_free].
     pub locale_fetch_context: *mut libc::c_void,
     pub locale_fetch_callback: LocaleFetchCallback,
     pub format: OutputFormat,
@@ -242,6 +382,12 @@ impl OutputFormat {
 
 ffi_fn! {
     /// Creates a new Processor from InitOptions. Free with [citeproc_rs_driver_free].
+    ///
+    /// # Safety
+    ///
+    /// `init.locale_fetch_context` must stay valid until the driver is freed with
+    /// [citeproc_rs_driver_free], as the locale fetch callback can be called with it by any
+    /// function that takes the driver.
     fn citeproc_rs_driver_new(init: InitOptions) -> *mut Driver {
 
         result_to_error_code(|| {
@@ -252,24 +398,18 @@ ffi_fn! {
                 fetcher: Some(Arc::new(rust::PredefinedLocales::bundled_en_us())),
                 ..Default::default()
             };
-            let mut proc = Processor::new(rs_init)?;
-            let langs = proc.get_langs_in_use();
-            if !langs.is_empty() {
-                if let Some(_) = init.locale_fetch_callback {
-                    let ffi_locales = LocaleFetcher {
-                        callback: init.locale_fetch_callback,
-                        context: init.locale_fetch_context,
-                        storage: LocaleStorage { locales: Vec::with_capacity(langs.len()) },
-                    };
-                    let locales = ffi_locales.build(&langs).locales;
-                    proc.store_locales(locales)
-                }
-            }
-            Ok(Box::into_raw(Box::new(Driver {
+            let proc = Processor::new(rs_init)?;
+            let mut driver = Driver {
                 processor: Some(proc),
                 buffer_ops: init.buffer_ops,
                 positions_scratch: Vec::new(),
-            })))
+                format: init.format,
+                locale_fetch_context: init.locale_fetch_context,
+                locale_fetch_callback: init.locale_fetch_callback,
+                locales_not_found: Vec::new(),
+            };
+            driver.fetch_missing_locales()?;
+            Ok(Box::into_raw(Box::new(driver)))
         })
     }
 }
@@ -287,6 +427,100 @@ ffi_fn_nullify! {
     }
 }
 
+ffi_fn_nullify! {
+    /// Switches the output format ([citeproc::Processor::set_output_format]), e.g. when the user
+    /// asks to copy citations as plain text. Clusters and references are kept, and the
+    /// `link_anchors` setting carries over.
+    ///
+    /// # Safety
+    ///
+    /// `driver` must be a valid pointer to a Driver.
+    @safety unsafe fn citeproc_rs_driver_set_output_format(#[nullify_on_panic] driver: *mut Driver, format: OutputFormat) -> ErrorCode {
+        result_to_error_code(|| {
+            let driver = unsafe { borrow_raw_ptr_mut(driver) } ?;
+            let proc = driver.processor.as_mut().ok_or(FFIError::Poisoned)?;
+            let options = proc.format_options();
+            proc.set_output_format(format.to_supported_format(), options)?;
+            driver.format = format;
+            Ok(ErrorCode::None)
+        })
+    }
+}
+
+ffi_fn_nullify! {
+    /// Turns clickable links on or off, for URLs and DOIs in HTML and RTF output. See
+    /// [citeproc::prelude::FormatOptions::with_link_anchors].
+    ///
+    /// # Safety
+    ///
+    /// `driver` must be a valid pointer to a Driver.
+    @safety unsafe fn citeproc_rs_driver_set_link_anchors(#[nullify_on_panic] driver: *mut Driver, link_anchors: bool) -> ErrorCode {
+        result_to_error_code(|| {
+            let driver = unsafe { borrow_raw_ptr_mut(driver) } ?;
+            let proc = driver.processor.as_mut().ok_or(FFIError::Poisoned)?;
+            let options = proc.format_options().with_link_anchors(link_anchors);
+            proc.set_output_format(driver.format.to_supported_format(), options)?;
+            Ok(ErrorCode::None)
+        })
+    }
+}
+
+ffi_fn_nullify! {
+    /// Renders with the locale for `lang`, like `"de-AT"`, instead of the style's default-locale
+    /// ([citeproc::Processor::set_locale_override]). An empty `lang` goes back to the style's.
+    /// Any locales this needs are requested from the locale fetch callback given to
+    /// [citeproc_rs_driver_new] before this returns.
+    ///
+    /// # Safety
+    ///
+    /// `driver` must be a valid pointer to a Driver. Either `lang` must refer to a byte array of
+    /// length `lang_len`, or `lang_len` must be zero.
+    @safety unsafe fn citeproc_rs_driver_set_locale_override(#[nullify_on_panic] driver: *mut Driver, lang: *const c_char, lang_len: usize) -> ErrorCode {
+        result_to_error_code(|| {
+            let driver = unsafe { borrow_raw_ptr_mut(driver) } ?;
+            let proc = driver.processor.as_mut().ok_or(FFIError::Poisoned)?;
+            let lang = unsafe { borrow_utf8_slice(lang, lang_len) } ?;
+            let lang = if lang.is_empty() {
+                None
+            } else {
+                Some(lang.parse::<Lang>().map_err(|_| FFIError::InvalidLang(lang.into()))?)
+            };
+            proc.set_locale_override(lang);
+            driver.fetch_missing_locales()?;
+            Ok(ErrorCode::None)
+        })
+    }
+}
+
+ffi_fn_nullify! {
+    /// Stores locales the host already has in memory, e.g. ones bundled with an app, using
+    /// [citeproc::Processor::store_locales]. Locales stored this way are never requested from the
+    /// locale fetch callback.
+    ///
+    /// `locales_json` is a JSON object mapping language tags to locale XML, like
+    /// `{"de-DE": "<locale ...>"}`. Every locale is checked as in [citeproc_rs_locale_slot_write]
+    /// first, and if any of them fails, none are stored.
+    ///
+    /// # Safety
+    ///
+    /// `driver` must be a valid pointer to a Driver. Either `locales_json` must refer to a byte
+    /// array of length `locales_json_len`, or `locales_json_len` must be zero.
+    @safety unsafe fn citeproc_rs_driver_store_locales(#[nullify_on_panic] driver: *mut Driver, locales_json: *const c_char, locales_json_len: usize) -> ErrorCode {
+        result_to_error_code(|| {
+            let driver = unsafe { borrow_raw_ptr_mut(driver) } ?;
+            let proc = driver.processor.as_mut().ok_or(FFIError::Poisoned)?;
+            let locales_json = unsafe { borrow_utf8_slice(locales_json, locales_json_len) } ?;
+            let locales: HashMap<Lang, String> = serde_json::from_str(locales_json)?;
+            for (lang, xml) in &locales {
+                check_locale(lang, xml)?;
+            }
+            driver.locales_not_found.retain(|lang| !locales.contains_key(lang));
+            proc.store_locales(locales.into_iter().collect());
+            Ok(ErrorCode::None)
+        })
+    }
+}
+
 #[repr(C)]
 pub struct ClusterPosition {
     pub is_preview_marker: bool,
@@ -334,6 +568,67 @@ ffi_fn_nullify! {
     }
 }
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ClusterNumberKind {
+    /// The cluster has not been given a position with [citeproc_rs_driver_set_cluster_order].
+    Unpositioned,
+    InText,
+    Note,
+    /// An author-only cluster, which is not part of the flow of the document.
+    OutsideFlow,
+}
+
+/// Where a cluster ended up after [citeproc_rs_driver_set_cluster_order].
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ClusterNumber {
+    pub kind: ClusterNumberKind,
+    /// The in-text cluster number, or the footnote number. Zero for the other kinds.
+    pub number: u32,
+    /// For note clusters, the position of the cluster within its footnote, starting at zero.
+    pub index_in_note: u32,
+}
+
+impl From<Option<rust::ClusterNumber>> for ClusterNumber {
+    fn from(number: Option<rust::ClusterNumber>) -> Self {
+        let (kind, number, index_in_note) = match number {
+            None => (ClusterNumberKind::Unpositioned, 0, 0),
+            Some(rust::ClusterNumber::OutsideFlow) => (ClusterNumberKind::OutsideFlow, 0, 0),
+            Some(rust::ClusterNumber::InText(n)) => (ClusterNumberKind::InText, n, 0),
+            Some(rust::ClusterNumber::Note(intra)) => (
+                ClusterNumberKind::Note,
+                intra.note_number(),
+                intra.index_in_note(),
+            ),
+        };
+        ClusterNumber {
+            kind,
+            number,
+            index_in_note,
+        }
+    }
+}
+
+ffi_fn_nullify! {
+    /// Writes the position of a cluster in the document
+    /// ([citeproc::Processor::get_cluster_note_number]) into `out`.
+    ///
+    /// # Safety
+    ///
+    /// Driver must be a valid pointer to a Driver. `out` must be a valid pointer to a
+    /// [ClusterNumber].
+    @safety unsafe fn citeproc_rs_driver_cluster_number(#[nullify_on_panic] driver: *mut Driver, cluster_id: ClusterId, out: *mut ClusterNumber) -> ErrorCode {
+        result_to_error_code(|| {
+            let driver = unsafe { borrow_raw_ptr_mut(driver) } ?;
+            let proc = driver.processor.as_mut().ok_or(FFIError::Poisoned)?;
+            let out = unsafe { borrow_raw_ptr_mut(out) } ?;
+            *out = proc.get_cluster_note_number(rust::ClusterId(cluster_id)).into();
+            Ok(ErrorCode::None)
+        })
+    }
+}
+
 ffi_fn_nullify! {
     /// Writes a formatted cluster ([citeproc::Processor::get_cluster]) into a buffer.
     ///
@@ -354,6 +649,33 @@ ffi_fn_nullify! {
     }
 }
 
+ffi_fn_nullify! {
+    /// Writes a cluster into a buffer as a JSON array of text runs
+    /// ([citeproc::Processor::get_cluster_runs]), so you can build a native attributed string
+    /// instead of parsing HTML. Each run looks like
+    /// `{ "text": "Title", "italic": true, "link": "https://..." }`; the attributes are `italic`,
+    /// `bold`, `smallCaps`, `underline`, `superscript`, `subscript` and `link`, and are left out
+    /// when not set. The driver's output format does not matter.
+    ///
+    /// # Safety
+    ///
+    /// `driver` must be a valid pointer to a Driver. `user_buf` must match the expected user data
+    /// in the BufferOps struct passed to the driver's init call.
+    @safety unsafe fn citeproc_rs_driver_built_cluster_runs(#[nullify_on_panic] driver: *mut Driver, cluster_id: ClusterId, user_buf: *mut c_void) -> ErrorCode {
+        result_to_error_code(|| {
+            let driver = unsafe { borrow_raw_ptr_mut(driver) } ?;
+            let proc = driver.processor.as_mut().ok_or(FFIError::Poisoned)?;
+            let id = rust::ClusterId(cluster_id);
+            let runs = proc.get_cluster_runs(id).ok_or(FFIError::ClusterNotInFlow(id))?;
+            let json = serde_json::to_string(&runs)?;
+            let mut buffer = unsafe { BufferWriter::new(driver.buffer_ops, user_buf) };
+            buffer.clear();
+            buffer.write_str(&json)?;
+            Ok(ErrorCode::None)
+        })
+    }
+}
+
 ffi_fn_nullify! {
     /// Writes a bibliography into a buffer, using [citeproc::Processor::get_bibliography]
     @safety unsafe fn citeproc_rs_driver_format_bibliography(#[nullify_on_panic] driver: *mut Driver, user_buf: *mut c_void) -> ErrorCode {
@@ -404,6 +726,83 @@ ffi_fn_nullify! {
     }
 }
 
+ffi_fn_nullify! {
+    /// Formats each of a list of references on its own, without creating any clusters, using
+    /// [citeproc::Processor::format_references].
+    ///
+    /// `ids_json` is a JSON array of reference ids. The results are written into user_buf one
+    /// per line, in the same order.
+    ///
+    /// # Safety
+    ///
+    /// `driver` must be a valid pointer to a Driver. Either `ids_json` must refer to a byte array
+    /// of length `ids_json_len`, or `ids_json_len` must be zero. `user_buf` must match the
+    /// expected user data in the BufferOps struct passed to driver's init call.
+    @safety unsafe fn citeproc_rs_driver_format_references(#[nullify_on_panic] driver: *mut Driver, ids_json: *const c_char, ids_json_len: usize, mode: FormatMode, user_buf: *mut c_void) -> ErrorCode {
+        result_to_error_code(|| {
+            let driver = unsafe { borrow_raw_ptr_mut(driver) } ?;
+            let proc = driver.processor.as_mut().ok_or(FFIError::Poisoned)?;
+            let ids_json = unsafe { borrow_utf8_slice(ids_json, ids_json_len) } ?;
+            let ids: Vec<rust::Atom> = serde_json::from_str(ids_json)?;
+            let mut buffer = unsafe { BufferWriter::new(driver.buffer_ops, user_buf) };
+            buffer.clear();
+            for formatted in proc.format_references(&ids, mode.to_cite_or_bib()) {
+                buffer.write_str(&formatted)?;
+                buffer.write_str("\n")?;
+            }
+            Ok(ErrorCode::None)
+        })
+    }
+}
+
+ffi_fn_nullify! {
+    /// Writes the stored reference with this id into a buffer as a CSL-JSON object
+    /// ([citeproc::Processor::export_reference]).
+    ///
+    /// Returns [ErrorCode::ReferenceNotFound] if there is no such reference.
+    ///
+    /// # Safety
+    ///
+    /// `driver` must be a valid pointer to a Driver. Either `id` must refer to a byte array of
+    /// length `id_len`, or `id_len` must be zero. `user_buf` must match the expected user data in
+    /// the BufferOps struct passed to the driver's init call.
+    @safety unsafe fn citeproc_rs_driver_get_reference(#[nullify_on_panic] driver: *mut Driver, id: *const c_char, id_len: usize, user_buf: *mut c_void) -> ErrorCode {
+        result_to_error_code(|| {
+            let driver = unsafe { borrow_raw_ptr_mut(driver) } ?;
+            let proc = driver.processor.as_mut().ok_or(FFIError::Poisoned)?;
+            let id = unsafe { borrow_utf8_slice(id, id_len) } ?;
+            let json = proc
+                .export_reference(rust::Atom::from(id))
+                .ok_or_else(|| FFIError::ReferenceNotFound(id.into()))?;
+            let mut buffer = unsafe { BufferWriter::new(driver.buffer_ops, user_buf) };
+            buffer.clear();
+            buffer.write_str(&json)?;
+            Ok(ErrorCode::None)
+        })
+    }
+}
+
+ffi_fn_nullify! {
+    /// Writes the ids of every stored reference into a buffer, as a JSON array of strings in the
+    /// order they were inserted ([citeproc::Processor::reference_ids]).
+    ///
+    /// # Safety
+    ///
+    /// `driver` must be a valid pointer to a Driver. `user_buf` must match the expected user data
+    /// in the BufferOps struct passed to the driver's init call.
+    @safety unsafe fn citeproc_rs_driver_list_references(#[nullify_on_panic] driver: *mut Driver, user_buf: *mut c_void) -> ErrorCode {
+        result_to_error_code(|| {
+            let driver = unsafe { borrow_raw_ptr_mut(driver) } ?;
+            let proc = driver.processor.as_mut().ok_or(FFIError::Poisoned)?;
+            let json = serde_json::to_string(&proc.reference_ids())?;
+            let mut buffer = unsafe { BufferWriter::new(driver.buffer_ops, user_buf) };
+            buffer.clear();
+            buffer.write_str(&json)?;
+            Ok(ErrorCode::None)
+        })
+    }
+}
+
 ffi_fn_nullify! {
     /// Inserts a reference. [citeproc::Processor::insert_reference]
     ///
@@ -428,6 +827,50 @@ ffi_fn_nullify! {
     }
 }
 
+ffi_fn_nullify! {
+    /// Tells the driver that its style was chosen via this dependent style, using
+    /// [citeproc::Processor::set_dependent_style]. The dependent style's default-locale becomes
+    /// the locale override, unless one was set with [citeproc_rs_driver_set_locale_override], and
+    /// its id, title and independent-parent link are reported by [citeproc_rs_driver_style_info].
+    ///
+    /// # Safety
+    ///
+    /// `driver` must be a valid pointer to a Driver. Either `style` must refer to a byte array of
+    /// length `style_len`, or `style_len` must be zero.
+    @safety unsafe fn citeproc_rs_driver_set_dependent_style(#[nullify_on_panic] driver: *mut Driver, style: *const c_char, style_len: usize) -> ErrorCode {
+        result_to_error_code(|| {
+            let driver = unsafe { borrow_raw_ptr_mut(driver) } ?;
+            let proc = driver.processor.as_mut().ok_or(FFIError::Poisoned)?;
+            let style = unsafe { borrow_utf8_slice(style, style_len) } ?;
+            let meta = csl::StyleMeta::parse(style)?;
+            proc.set_dependent_style(&meta);
+            Ok(ErrorCode::None)
+        })
+    }
+}
+
+ffi_fn_nullify! {
+    /// Writes a JSON description of the style driving the processor
+    /// ([citeproc::Processor::style_info]) into a buffer: its id, title, citation format, class,
+    /// default and override locales, and the dependent style it was chosen through, if any.
+    ///
+    /// # Safety
+    ///
+    /// `driver` must be a valid pointer to a Driver. `user_buf` must match the expected user data
+    /// in the BufferOps struct passed to driver's init call.
+    @safety unsafe fn citeproc_rs_driver_style_info(#[nullify_on_panic] driver: *mut Driver, user_buf: *mut c_void) -> ErrorCode {
+        result_to_error_code(|| {
+            let driver = unsafe { borrow_raw_ptr_mut(driver) } ?;
+            let proc = driver.processor.as_mut().ok_or(FFIError::Poisoned)?;
+            let json = serde_json::to_string(&proc.style_info())?;
+            let mut buffer = unsafe { BufferWriter::new(driver.buffer_ops, user_buf) };
+            buffer.clear();
+            buffer.write_str(&json)?;
+            Ok(ErrorCode::None)
+        })
+    }
+}
+
 #[cfg(feature = "testability")]
 ffi_fn! {
     fn test_panic() -> ErrorCode {
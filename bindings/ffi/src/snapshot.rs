@@ -0,0 +1,144 @@
+//! Read-only snapshots of a [Driver], for formatting on other threads.
+//!
+//! # Thread safety
+//!
+//! A [Driver] must only be used from one thread at a time. A [DriverSnapshot] may be moved to
+//! any other thread and used there, but it too must only be used from one thread at a time; take
+//! one snapshot per thread.
+//!
+//! A snapshot sees the document as it was when it was taken. While any snapshot is alive, calls
+//! that change the driver (inserting references, setting the cluster order, etc.) wait until
+//! every snapshot is freed. So free snapshots as soon as you are done with them, and never hold
+//! one on the thread that changes the driver.
+
+use libc::{c_char, c_void};
+
+use citeproc::prelude as rust;
+use rust::{ParallelDatabase, Processor, Reference, Snapshot};
+
+use crate::buffer::{self, BufferWriter};
+use crate::macros::nullify_on_panic;
+use crate::util::*;
+use crate::{ClusterId, Driver, ErrorCode, FFIError, OutputFormat};
+
+/// A read-only view of a [Driver]. Create one with [citeproc_rs_driver_snapshot] and free it
+/// with [citeproc_rs_snapshot_free].
+pub struct DriverSnapshot {
+    snapshot: Option<Snapshot<Processor>>,
+    buffer_ops: buffer::BufferOps,
+}
+
+/// Drops the snapshot, which lets the driver change again.
+impl crate::macros::MakeUnwindSafe for DriverSnapshot {
+    fn make_unwind_safe(&mut self) {
+        log::error!("making DriverSnapshot unwind safe, by dropping its snapshot");
+        self.snapshot = None;
+    }
+}
+
+/// Fails to compile unless snapshots can really be sent to other threads.
+#[allow(dead_code)]
+fn assert_snapshot_is_send() {
+    fn is_send<T: Send>() {}
+    is_send::<DriverSnapshot>();
+}
+
+ffi_fn_nullify! {
+    /// Takes a read-only snapshot of the driver, which can be used from another thread. See the
+    /// thread safety notes on [DriverSnapshot]. Returns null on error.
+    ///
+    /// # Safety
+    ///
+    /// `driver` must be a valid pointer to a Driver.
+    @safety unsafe fn citeproc_rs_driver_snapshot(#[nullify_on_panic] driver: *mut Driver) -> *mut DriverSnapshot {
+        result_to_error_code(|| {
+            let driver = unsafe { borrow_raw_ptr_mut(driver) } ?;
+            let proc = driver.processor.as_ref().ok_or(FFIError::Poisoned)?;
+            Ok(Box::into_raw(Box::new(DriverSnapshot {
+                snapshot: Some(proc.snapshot()),
+                buffer_ops: driver.buffer_ops,
+            })))
+        })
+    }
+}
+
+ffi_fn_nullify! {
+    /// Frees a [DriverSnapshot].
+    ///
+    /// # Safety
+    ///
+    /// The snapshot must either be from [citeproc_rs_driver_snapshot] or be null.
+    fn citeproc_rs_snapshot_free(snapshot: *mut DriverSnapshot) {
+        if !snapshot.is_null() {
+            drop(unsafe { Box::from_raw(snapshot) });
+        }
+    }
+}
+
+ffi_fn_nullify! {
+    /// Writes a formatted cluster into a buffer, like [crate::citeproc_rs_driver_format_cluster].
+    ///
+    /// # Safety
+    ///
+    /// `snapshot` must be a valid pointer to a DriverSnapshot. `user_buf` must match the expected
+    /// user data in the BufferOps struct passed to the driver's init call.
+    @safety unsafe fn citeproc_rs_snapshot_format_cluster(#[nullify_on_panic] snapshot: *mut DriverSnapshot, cluster_id: ClusterId, user_buf: *mut c_void) -> ErrorCode {
+        result_to_error_code(|| {
+            let snapshot = unsafe { borrow_raw_ptr_mut(snapshot) } ?;
+            let proc = snapshot.snapshot.as_ref().ok_or(FFIError::Poisoned)?;
+            let mut buffer = unsafe { BufferWriter::new(snapshot.buffer_ops, user_buf) };
+            let id = rust::ClusterId(cluster_id);
+            let built = proc.get_cluster(id).ok_or(FFIError::ClusterNotInFlow(id))?;
+            buffer.clear();
+            buffer.write_str(built.as_str())?;
+            Ok(ErrorCode::None)
+        })
+    }
+}
+
+ffi_fn_nullify! {
+    /// Writes a bibliography into a buffer, like [crate::citeproc_rs_driver_format_bibliography].
+    ///
+    /// # Safety
+    ///
+    /// `snapshot` must be a valid pointer to a DriverSnapshot. `user_buf` must match the expected
+    /// user data in the BufferOps struct passed to the driver's init call.
+    @safety unsafe fn citeproc_rs_snapshot_format_bibliography(#[nullify_on_panic] snapshot: *mut DriverSnapshot, user_buf: *mut c_void) -> ErrorCode {
+        result_to_error_code(|| {
+            let snapshot = unsafe { borrow_raw_ptr_mut(snapshot) } ?;
+            let proc = snapshot.snapshot.as_ref().ok_or(FFIError::Poisoned)?;
+            let mut buffer = unsafe { BufferWriter::new(snapshot.buffer_ops, user_buf) };
+            buffer.clear();
+            for entry in proc.get_bibliography() {
+                buffer.write_str(entry.value.as_str())?;
+                buffer.write_str("\n")?;
+            }
+            Ok(ErrorCode::None)
+        })
+    }
+}
+
+ffi_fn_nullify! {
+    /// Formats a bibliography entry for a reference that need not be in the document, like
+    /// [crate::citeproc_rs_driver_preview_reference], using
+    /// [citeproc::Processor::preview_reference_read_only].
+    ///
+    /// # Safety
+    ///
+    /// `snapshot` must be a valid pointer to a DriverSnapshot. Either `ref_json` must refer to a
+    /// byte array of length `ref_json_len`, or `ref_json_len` must be zero. `user_buf` must match
+    /// the expected user data in the BufferOps struct passed to the driver's init call.
+    @safety unsafe fn citeproc_rs_snapshot_preview_reference(#[nullify_on_panic] snapshot: *mut DriverSnapshot, ref_json: *const c_char, ref_json_len: usize, format: OutputFormat, user_buf: *mut c_void) -> ErrorCode {
+        result_to_error_code(|| {
+            let snapshot = unsafe { borrow_raw_ptr_mut(snapshot) } ?;
+            let proc = snapshot.snapshot.as_ref().ok_or(FFIError::Poisoned)?;
+            let ref_json = unsafe { borrow_utf8_slice(ref_json, ref_json_len) } ?;
+            let reference: Reference = serde_json::from_str(ref_json)?;
+            let mut buffer = unsafe { BufferWriter::new(snapshot.buffer_ops, user_buf) };
+            let result = proc.preview_reference_read_only(&reference, Some(format.to_supported_format()));
+            buffer.clear();
+            buffer.write_str(&result)?;
+            Ok(ErrorCode::None)
+        })
+    }
+}
@@ -67,6 +67,7 @@ fn basic_cluster_get_cite_id(proc: &mut Processor, cluster_id: ClusterId, id: &s
         id: cluster_id,
         cites: vec![Cite::basic(id)],
         mode: None,
+        lang: None,
     };
     proc.insert_cluster(cluster);
     let id = proc
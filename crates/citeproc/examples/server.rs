@@ -0,0 +1,260 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright © 2021 Corporation for Digital Scholarship
+
+//! A long-running citeproc-rs process for editors that can't link Rust directly.
+//!
+//! It reads one JSON request per line on stdin and writes one JSON response per line on stdout.
+//! Each request names a document, and every document gets its own processor from a
+//! [ProcessorPool], so one server can look after all of an editor's open files at once.
+//!
+//! ```text
+//! $ cargo run --example server
+//! {"id":1,"document":"a.md","method":"open","params":{"style":"<style ...>...</style>"}}
+//! {"id":1,"result":{"missingLangs":[]}}
+//! {"id":2,"document":"a.md","method":"insertReferences","params":{"references":[{"id":"smith","type":"book","title":"Title"}]}}
+//! {"id":2,"result":null}
+//! {"id":3,"document":"a.md","method":"insertCluster","params":{"cluster":{"id":"c1","cites":[{"id":"smith"}]}}}
+//! {"id":3,"result":null}
+//! {"id":4,"document":"a.md","method":"setClusterOrder","params":{"positions":[{"id":"c1"}]}}
+//! {"id":4,"result":null}
+//! {"id":5,"document":"a.md","method":"batchedUpdates"}
+//! {"id":5,"result":{"clusters":[["c1","Title"]],"bibliography":null}}
+//! ```
+//!
+//! Errors come back as `{"id":..,"error":{"kind":..,"message":..}}`, where `kind` is an
+//! [ErrorKind] name, or `"Request"` if the request itself could not be understood. A bad request
+//! never stops the server; it exits when stdin is closed.
+//!
+//! Only en-US is bundled. Other locales can be sent with `storeLocales`, which applies to every
+//! document; `open` and `storeLocales` both answer with the languages still missing.
+
+use citeproc::prelude::*;
+use citeproc::string_id;
+use csl::Lang;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{self, BufRead, Write};
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    #[serde(default)]
+    document: SmartString,
+    #[serde(flatten)]
+    method: Method,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "camelCase")]
+enum Method {
+    #[serde(rename_all = "camelCase")]
+    Open {
+        style: String,
+        #[serde(default)]
+        format: SupportedFormat,
+        #[serde(default)]
+        locale_override: Option<Lang>,
+    },
+    Close,
+    /// Every open document, not only the one named in the request.
+    StoreLocales {
+        locales: Vec<(Lang, String)>,
+    },
+    ResetReferences {
+        references: Vec<Reference>,
+    },
+    InsertReferences {
+        references: Vec<Reference>,
+    },
+    RemoveReference {
+        id: Atom,
+    },
+    InsertCluster {
+        cluster: string_id::Cluster,
+    },
+    RemoveCluster {
+        id: SmartString,
+    },
+    SetClusterOrder {
+        positions: Vec<string_id::ClusterPosition>,
+    },
+    BatchedUpdates,
+    Bibliography,
+    Warnings,
+    Documents,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MissingLangs {
+    missing_langs: Vec<Lang>,
+}
+
+#[derive(Serialize)]
+struct Response {
+    id: Value,
+    #[serde(flatten)]
+    outcome: Outcome,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+enum Outcome {
+    Result(Value),
+    Error { kind: String, message: String },
+}
+
+impl Outcome {
+    fn request_error(message: impl ToString) -> Self {
+        Outcome::Error {
+            kind: "Request".into(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<Error> for Outcome {
+    fn from(e: Error) -> Self {
+        Outcome::Error {
+            kind: format!("{:?}", e.kind()),
+            message: e.to_string(),
+        }
+    }
+}
+
+struct Server {
+    pool: ProcessorPool,
+}
+
+impl Server {
+    fn handle(&mut self, line: &str) -> Response {
+        let request: Request = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(e) => {
+                // Still try to echo the id back, so the client can match up the failure.
+                let id = serde_json::from_str::<Value>(line)
+                    .ok()
+                    .and_then(|v| v.get("id").cloned())
+                    .unwrap_or(Value::Null);
+                return Response {
+                    id,
+                    outcome: Outcome::request_error(e),
+                };
+            }
+        };
+        let outcome = match self.dispatch(&request.document, request.method) {
+            Ok(value) => Outcome::Result(value),
+            Err(outcome) => outcome,
+        };
+        Response {
+            id: request.id,
+            outcome,
+        }
+    }
+
+    fn dispatch(&mut self, document: &str, method: Method) -> Result<Value, Outcome> {
+        match method {
+            Method::Open {
+                style,
+                format,
+                locale_override,
+            } => {
+                self.pool.open(
+                    document,
+                    InitOptions {
+                        style: &style,
+                        format,
+                        locale_override,
+                        ..Default::default()
+                    },
+                )?;
+                return to_value(MissingLangs {
+                    missing_langs: self.pool.missing_langs(),
+                });
+            }
+            Method::StoreLocales { locales } => {
+                self.pool.store_locales(locales);
+                return to_value(MissingLangs {
+                    missing_langs: self.pool.missing_langs(),
+                });
+            }
+            Method::Documents => {
+                let mut ids: Vec<&str> = self.pool.document_ids().collect();
+                ids.sort_unstable();
+                return to_value(ids);
+            }
+            Method::Close => {
+                return match self.pool.close(document) {
+                    Some(_) => Ok(Value::Null),
+                    None => Err(no_such_document(document)),
+                };
+            }
+            _ => {}
+        }
+        let processor = self
+            .pool
+            .get_mut(document)
+            .ok_or_else(|| no_such_document(document))?;
+        match method {
+            Method::ResetReferences { references } => processor.reset_references(references),
+            Method::InsertReferences { references } => processor.extend_references(references),
+            Method::RemoveReference { id } => processor.remove_reference(id),
+            Method::InsertCluster { cluster } => processor.insert_cluster_str(cluster),
+            Method::RemoveCluster { id } => processor.remove_cluster_str(&id),
+            Method::SetClusterOrder { positions } => processor
+                .set_cluster_order_str(&positions)
+                .map_err(|e| Outcome::Error {
+                    kind: format!("{:?}", ErrorKind::Reordering),
+                    message: e.to_string(),
+                })?,
+            Method::BatchedUpdates => return to_value(processor.batched_updates_str()),
+            Method::Bibliography => return to_value(processor.get_bibliography()),
+            Method::Warnings => {
+                let warnings: Vec<String> = processor
+                    .take_warnings()
+                    .iter()
+                    .map(|w| w.to_string())
+                    .collect();
+                return to_value(warnings);
+            }
+            Method::Open { .. }
+            | Method::StoreLocales { .. }
+            | Method::Documents
+            | Method::Close => unreachable!("handled above"),
+        }
+        Ok(Value::Null)
+    }
+}
+
+fn no_such_document(document: &str) -> Outcome {
+    Outcome::request_error(format!("no open document {:?}", document))
+}
+
+fn to_value(value: impl Serialize) -> Result<Value, Outcome> {
+    serde_json::to_value(value).map_err(|e| Error::from(e).into())
+}
+
+fn main() -> io::Result<()> {
+    let mut server = Server {
+        pool: ProcessorPool::new(Arc::new(PredefinedLocales::bundled_en_us())),
+    };
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for line in stdin.lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = server.handle(&line);
+        serde_json::to_writer(&mut out, &response)?;
+        out.write_all(b"\n")?;
+        out.flush()?;
+    }
+    Ok(())
+}
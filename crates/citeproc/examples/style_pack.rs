@@ -0,0 +1,51 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright © 2021 Corporation for Digital Scholarship
+
+//! Compiles directories of styles and locales into a style pack.
+//!
+//! ```text
+//! $ git clone https://github.com/citation-style-language/styles
+//! $ git clone https://github.com/citation-style-language/locales
+//! $ cargo run --example style_pack -- styles.pack styles locales
+//! ```
+//!
+//! Styles and locales that fail to parse are listed and left out. Pass `--csl-m` to parse the
+//! styles as CSL-M.
+
+use citeproc::prelude::*;
+use std::process::exit;
+
+fn main() -> std::io::Result<()> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let mut options = csl::ParseOptions::default();
+    if let Some(i) = args.iter().position(|a| a == "--csl-m") {
+        args.remove(i);
+        options.mode = StyleMode::CslM;
+    }
+    if args.len() < 2 {
+        eprintln!("usage: style_pack [--csl-m] OUTPUT DIR...");
+        exit(2);
+    }
+    let output = args.remove(0);
+    let mut builder = StylePackBuilder::new(options);
+    let mut failures = 0;
+    for dir in &args {
+        for (path, error) in builder.add_dir(dir)? {
+            eprintln!("{}: {}", path.display(), error);
+            failures += 1;
+        }
+    }
+    let bytes = builder.build();
+    let pack = StylePack::from_bytes(&bytes).expect("a pack just built should be readable");
+    println!(
+        "{}: {} styles, {} bytes, {} skipped",
+        output,
+        pack.styles().len(),
+        bytes.len(),
+        failures
+    );
+    std::fs::write(&output, &bytes)
+}
@@ -9,12 +9,14 @@
 use super::processor::Interner;
 use citeproc_db::ClusterId;
 use citeproc_io::output::{markup::Markup, OutputFormat};
-use citeproc_io::{Cite, ClusterMode, SmartString};
-use csl::Atom;
+use citeproc_io::{Cite, CiteMode, ClusterMode, Locator, Locators, NumberLike, SmartString};
+use csl::{Atom, Lang, LocatorType, Position};
 use fnv::FnvHashMap;
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
 
 /// See [Special Citation Forms](https://citeproc-js.readthedocs.io/en/latest/running.html#special-citation-forms)
 ///
@@ -32,25 +34,30 @@ use std::sync::Arc;
 /// , { "id": 4, "cites": [{ "id": "smith" }], "mode": "Composite" }
 /// , { "id": 5, "cites": [{ "id": "smith" }, { "id": "jones" }],
 ///     "mode": "Composite", "suppressFirst": 2 }
+/// , { "id": 6, "cites": [{ "id": "smith" }], "lang": "de-DE" }
 /// ]"#;
 /// let clusters: Vec<Cluster<Markup, i32>> = serde_json::from_str(json).unwrap();
 /// use pretty_assertions::assert_eq;
 /// assert_eq!(clusters, vec![
-///     Cluster { id: 1, cites: vec![Cite::basic("smith")], mode: None, },
-///     Cluster { id: 2, cites: vec![Cite::basic("smith")], mode: Some(ClusterMode::AuthorOnly), },
-///     Cluster { id: 2, cites: vec![Cite::basic("smith")], mode: Some(ClusterMode::SuppressAuthor
-///     { suppress_first: 1 }), },
-///     Cluster { id: 3, cites: vec![Cite::basic("smith"), Cite::basic("jones")],
-///               mode: Some(ClusterMode::SuppressAuthor { suppress_first: 2 }), },
-///     Cluster { id: 4, cites: vec![Cite::basic("smith")], mode: Some(ClusterMode::Composite
-///     { infix: None, suppress_first: 1 }), },
-///     Cluster { id: 5, cites: vec![Cite::basic("smith"), Cite::basic("jones")],
-///               mode: Some(ClusterMode::Composite { infix: None, suppress_first: 2 }), },
+///     Cluster::new(1, vec![Cite::basic("smith")], None),
+///     Cluster::new(2, vec![Cite::basic("smith")], Some(ClusterMode::AuthorOnly)),
+///     Cluster::new(2, vec![Cite::basic("smith")], Some(ClusterMode::SuppressAuthor
+///     { suppress_first: 1 })),
+///     Cluster::new(3, vec![Cite::basic("smith"), Cite::basic("jones")],
+///               Some(ClusterMode::SuppressAuthor { suppress_first: 2 })),
+///     Cluster::new(4, vec![Cite::basic("smith")], Some(ClusterMode::Composite
+///     { infix: None, suppress_first: 1 })),
+///     Cluster::new(5, vec![Cite::basic("smith"), Cite::basic("jones")],
+///               Some(ClusterMode::Composite { infix: None, suppress_first: 2 })),
+///     Cluster {
+///         lang: Some("de-DE".parse().unwrap()),
+///         ..Cluster::new(6, vec![Cite::basic("smith")], None)
+///     },
 /// ]);
 /// ```
-#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(bound(
-    serialize = "Id: serde::Serialize",
+    serialize = "Id: serde::Serialize, O::Input: serde::Serialize",
     deserialize = "Id: serde::Deserialize<'de>"
 ))]
 pub struct Cluster<O: OutputFormat = Markup, Id = ClusterId> {
@@ -58,11 +65,21 @@ pub struct Cluster<O: OutputFormat = Markup, Id = ClusterId> {
     pub cites: Vec<Cite<O>>,
     #[serde(flatten, default, skip_serializing_if = "Option::is_none")]
     pub mode: Option<ClusterMode>,
+    /// Takes terms from this language's locale instead of the default one, e.g. for a footnote
+    /// quoting a German source in an English document. The locale must have been fetched or
+    /// stored, like any other.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lang: Option<Lang>,
 }
 
 impl<Id> Cluster<Markup, Id> {
     pub fn new(id: Id, cites: Vec<Cite<Markup>>, mode: Option<ClusterMode>) -> Self {
-        Self { id, cites, mode }
+        Self {
+            id,
+            cites,
+            mode,
+            lang: None,
+        }
     }
 }
 
@@ -120,12 +137,222 @@ pub struct PreviewCluster {
     pub cites: Vec<Cite<Markup>>,
     #[serde(flatten, default, skip_serializing_if = "Option::is_none")]
     pub mode: Option<ClusterMode>,
+    /// Renders every cite in this position instead of the one the preview would have in the
+    /// document, e.g. to show a cite as `ibid-with-locator` without building a document where it
+    /// is one. In JSON, `"positionOverride": "ibid-with-locator"`. An ibid of either kind is
+    /// also near-note.
+    #[serde(
+        rename = "positionOverride",
+        default,
+        deserialize_with = "position_override"
+    )]
+    pub position_override: Option<Position>,
 }
 
 impl PreviewCluster {
     /// Makes a preview cluster, with no ID. Pass to [[crate::Processor::preview_citation_cluster]]
     pub fn new(cites: Vec<Cite<Markup>>, mode: Option<ClusterMode>) -> Self {
-        Self { cites, mode }
+        Self {
+            cites,
+            mode,
+            position_override: None,
+        }
+    }
+
+    /// Renders the cites as if they were in `position`. See
+    /// [`position_override`](Self::position_override).
+    pub fn with_position(mut self, position: Position) -> Self {
+        self.position_override = Some(position);
+        self
+    }
+}
+
+/// Positions as they are written in CSL, like `ibid-with-locator`.
+fn position_override<'de, D>(deserializer: D) -> Result<Option<Position>, D::Error>
+where
+    D: serde::de::Deserializer<'de>,
+{
+    use serde::de::Error as DeError;
+    let s = match Option::<String>::deserialize(deserializer)? {
+        Some(s) => s,
+        None => return Ok(None),
+    };
+    match Position::from_str(s.as_str()) {
+        // Internal to the processor, not positions a style can test for
+        Ok(Position::IbidNear) | Ok(Position::IbidWithLocatorNear) | Err(_) => {
+            Err(DeError::custom(format!("unknown position {}", s.as_str())))
+        }
+        Ok(position) => Ok(Some(position)),
+    }
+}
+
+/// A citation in the shape citeproc-js uses, and Zotero and others store in documents. Use
+/// [`Processor::import_citeproc_js`](crate::Processor::import_citeproc_js) and
+/// [`Processor::export_citeproc_js`](crate::Processor::export_citeproc_js) to convert whole
+/// documents' worth.
+///
+/// A `noteIndex` of zero means the citation is in-text.
+///
+/// ```
+/// use citeproc::CiteprocJsCitation;
+/// let json = r#"
+/// { "citationID": "abc",
+///   "citationItems": [
+///     { "id": "smith", "locator": "12", "label": "page", "suppress-author": true },
+///     { "id": 2 }
+///   ],
+///   "properties": { "noteIndex": 3 } }"#;
+/// let citation: CiteprocJsCitation = serde_json::from_str(json).unwrap();
+/// assert_eq!(citation.citation_id.as_deref(), Some("abc"));
+/// assert_eq!(citation.properties.note_index, 3);
+/// let cites: Vec<_> = citation.citation_items.into_iter().map(|item| item.into_cite()).collect();
+/// assert_eq!(&*cites[1].ref_id, "2");
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CiteprocJsCitation {
+    #[serde(
+        rename = "citationID",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub citation_id: Option<SmartString>,
+    pub citation_items: Vec<CiteprocJsCitationItem>,
+    #[serde(default)]
+    pub properties: CiteprocJsProperties,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CiteprocJsProperties {
+    #[serde(default)]
+    pub note_index: u32,
+}
+
+/// One of a [CiteprocJsCitation]'s `citationItems`. An item cannot be both `author-only` and
+/// `suppress-author`; JSON with both is rejected.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", try_from = "RawCitationItem")]
+pub struct CiteprocJsCitationItem {
+    pub id: NumberLike,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locator: Option<NumberLike>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_locator_type"
+    )]
+    pub label: Option<LocatorType>,
+    /// Every locator, when a cite has more than one. citeproc-js only reads `locator` and
+    /// `label`, which hold the first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub locators: Vec<Locator>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<SmartString>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suffix: Option<SmartString>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub suppress_author: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub author_only: bool,
+}
+
+/// [CiteprocJsCitationItem] as written, before checking it.
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct RawCitationItem {
+    id: NumberLike,
+    #[serde(default)]
+    locator: Option<NumberLike>,
+    #[serde(default)]
+    label: Option<LocatorType>,
+    #[serde(default)]
+    locators: Vec<Locator>,
+    #[serde(default)]
+    prefix: Option<SmartString>,
+    #[serde(default)]
+    suffix: Option<SmartString>,
+    #[serde(default)]
+    suppress_author: bool,
+    #[serde(default)]
+    author_only: bool,
+}
+
+impl std::convert::TryFrom<RawCitationItem> for CiteprocJsCitationItem {
+    type Error = &'static str;
+    fn try_from(raw: RawCitationItem) -> Result<Self, Self::Error> {
+        if raw.author_only && raw.suppress_author {
+            return Err("a citation item cannot be both author-only and suppress-author");
+        }
+        Ok(CiteprocJsCitationItem {
+            id: raw.id,
+            locator: raw.locator,
+            label: raw.label,
+            locators: raw.locators,
+            prefix: raw.prefix,
+            suffix: raw.suffix,
+            suppress_author: raw.suppress_author,
+            author_only: raw.author_only,
+        })
+    }
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+fn serialize_locator_type<S: serde::Serializer>(
+    label: &Option<LocatorType>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    label.map(|l| l.as_ref().to_owned()).serialize(serializer)
+}
+
+impl CiteprocJsCitationItem {
+    pub fn into_cite(self) -> Cite<Markup> {
+        let mut cite = Cite::basic(self.id.into_string().as_str());
+        cite.prefix = self.prefix;
+        cite.suffix = self.suffix;
+        cite.locators = if self.locators.len() > 1 {
+            Some(Locators::Multiple {
+                locators: self.locators,
+            })
+        } else {
+            self.locator.map(|locator| {
+                Locators::Single(Locator {
+                    locator,
+                    loc_type: self.label.unwrap_or_default(),
+                })
+            })
+        };
+        cite.mode = if self.author_only {
+            Some(CiteMode::AuthorOnly)
+        } else if self.suppress_author {
+            Some(CiteMode::SuppressAuthor)
+        } else {
+            None
+        };
+        cite
+    }
+
+    /// Anything citeproc-js has no field for, like `suppress_names`, is left out. Several
+    /// locators are kept in `locators`, which citeproc-rs reads back.
+    pub fn from_cite(cite: &Cite<Markup>) -> Self {
+        let locator = cite.locators.as_ref().and_then(|l| l.single());
+        let locators = match &cite.locators {
+            Some(Locators::Multiple { locators }) if locators.len() > 1 => locators.clone(),
+            _ => Vec::new(),
+        };
+        CiteprocJsCitationItem {
+            id: NumberLike::Str(SmartString::from(&*cite.ref_id)),
+            locator: locator.map(|l| l.value().clone()),
+            label: locator.map(|l| l.type_of()),
+            locators,
+            prefix: cite.prefix.clone(),
+            suffix: cite.suffix.clone(),
+            suppress_author: cite.mode == Some(CiteMode::SuppressAuthor),
+            author_only: cite.mode == Some(CiteMode::AuthorOnly),
+        }
     }
 }
 
@@ -137,6 +364,11 @@ pub struct ClusterPosition {
     /// You should only supply one of those.
     pub id: Option<ClusterId>,
     /// If this is None, the piece is an in-text cluster. If it is Some, it is a note cluster.
+    ///
+    /// Several clusters may share a note number. Their order within the footnote is the order
+    /// they are given in, and is available afterwards as the second half of
+    /// [`IntraNote::Multi`](citeproc_db::IntraNote::Multi) from
+    /// [`Processor::get_cluster_note_number`](crate::Processor::get_cluster_note_number).
     pub note: Option<u32>,
 }
 
@@ -179,6 +411,12 @@ pub enum ReorderingError {
     DidNotSupplyZeroPosition,
     #[error("non-existent cluster {0:?}")]
     NonExistentCluster(ClusterId),
+    #[error("cluster {0:?} is not next to the previous cluster in the document")]
+    NonAdjacentCluster(ClusterId),
+    #[error("no clusters were given")]
+    NoClusters,
+    #[error("cluster {0:?} cannot be split before cite {1}")]
+    SplitOutOfRange(ClusterId, usize),
 }
 
 impl ReorderingError {
@@ -196,10 +434,68 @@ impl ReorderingError {
     }
 }
 
+/// From [`Processor::restore_cluster_ids`](crate::Processor::restore_cluster_ids): this processor
+/// has already given another string the [ClusterId] that was saved for `id`.
+#[derive(Debug, Clone, thiserror::Error, PartialEq)]
+#[error("cannot give cluster {id:?} its saved id {saved:?}, as another cluster already has it")]
+pub struct ClusterIdConflict {
+    pub id: SmartString,
+    pub saved: ClusterId,
+}
+
+/// Everything a [`Processor`](crate::Processor) can fail with. Input the processor cannot use is
+/// reported here rather than by panicking, because neither FFI nor WebAssembly consumers can
+/// recover from a panic.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("style error: {0}")]
+    Style(#[from] csl::StyleError),
+    #[error("{0}")]
+    LocaleFetch(#[from] citeproc_db::LocaleFetchError),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("{0}")]
+    Reordering(#[from] ReorderingError),
+    #[error("{0}")]
+    LimitExceeded(#[from] LimitExceeded),
+    #[error("{0}")]
+    Bundle(#[from] crate::bundle::BundleError),
+    #[error("the {} output format was left out of this build", .0.as_str())]
+    UnavailableFormat(SupportedFormat),
+}
+
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Style(_) => ErrorKind::Style,
+            Error::LocaleFetch(_) => ErrorKind::LocaleFetch,
+            Error::Json(_) => ErrorKind::Json,
+            Error::Reordering(_) => ErrorKind::Reordering,
+            Error::LimitExceeded(_) => ErrorKind::LimitExceeded,
+            Error::Bundle(_) => ErrorKind::Bundle,
+            Error::UnavailableFormat(_) => ErrorKind::UnavailableFormat,
+        }
+    }
+}
+
+/// Which variant of [Error] you have. The discriminants are stable, so bindings can hand them to
+/// other languages as plain numbers; new kinds only ever get new numbers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize)]
+#[repr(u32)]
+pub enum ErrorKind {
+    Style = 1,
+    LocaleFetch = 2,
+    Json = 3,
+    Reordering = 4,
+    LimitExceeded = 5,
+    Bundle = 6,
+    UnavailableFormat = 7,
+}
+
 pub mod string_id {
     //! This is the API using string IDs only, useful for exposing citeproc-rs to non-Rust
     //! consumers.
-    use super::{BibEntry, BibliographyUpdate};
+    use super::{BibEntry, BibliographyUpdate, InputChange};
     use citeproc_io::{
         output::{markup::Markup, OutputFormat},
         SmartString,
@@ -251,6 +547,9 @@ pub mod string_id {
         /// A list of clusters that were updated, paired with the formatted output for each
         pub clusters: Vec<(SmartString, Arc<O::Output>)>,
         pub bibliography: Option<BibliographyUpdate>,
+        /// See [super::UpdateSummary::causes].
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub causes: Option<Vec<(SmartString, Vec<InputChange>)>>,
     }
 
     #[derive(Serialize, Default, Debug, Clone, PartialEq, Eq)]
@@ -320,21 +619,222 @@ pub struct UpdateSummary<O: OutputFormat = Markup> {
     /// A list of clusters that were updated, paired with the formatted output for each
     pub clusters: Vec<(ClusterId, Arc<O::Output>)>,
     pub bibliography: Option<BibliographyUpdate>,
+    /// Only with [`Processor::set_invalidation_tracing`](crate::Processor::set_invalidation_tracing)
+    /// on: for each updated cluster, the input changes since the last update that it depends on.
+    pub causes: Option<Vec<(ClusterId, Vec<InputChange>)>>,
+}
+
+/// A change to the processor's inputs, recorded while invalidation tracing is on. See
+/// [`UpdateSummary::causes`].
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(tag = "kind", content = "id", rename_all = "camelCase")]
+pub enum InputChange {
+    /// The style or one of its jurisdiction modules was replaced.
+    Style,
+    /// The locale override changed, or locales were stored.
+    Locale,
+    /// The output format or its options changed.
+    OutputFormat,
+    /// Another setting that applies to every cite, like the compat mode or case exceptions.
+    Settings,
+    /// A reference was inserted, replaced or removed.
+    Reference(Atom),
+    /// A cluster's cites, mode or language changed, or it was removed. Identified by its string
+    /// id, or `#` and its number if it was not made with
+    /// [`Processor::cluster_id`](crate::Processor::cluster_id).
+    Cluster(SmartString),
+    /// The cluster order was set.
+    ClusterOrder,
+    /// The uncited references to include changed.
+    UncitedReferences,
 }
 
 #[derive(Serialize, Default, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct BibEntry<O: OutputFormat = Markup> {
     pub id: Atom,
+    /// See [entry_anchor]. HTML output with `entry_anchors` starts with an empty `<a>` that has
+    /// this as its `id`.
+    pub anchor: SmartString,
     pub value: Arc<O::Output>,
 }
 
+/// One labelled part of a categorised bibliography, from
+/// [`Processor::get_bibliography_sections`](crate::Processor::get_bibliography_sections).
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct BibliographySection<O: OutputFormat = Markup> {
+    pub label: SmartString,
+    pub entries: Vec<BibEntry<O>>,
+}
+
+/// A single `field`/`value` test for a [`BibliographyFilter`].
+///
+/// `field` is `type`, `id`, or the CSL name of an ordinary variable such as `keyword`. Values of
+/// `keyword` are also matched against each of their `;`- or `,`-separated terms.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct FieldMatch {
+    pub field: SmartString,
+    pub value: SmartString,
+}
+
+impl FieldMatch {
+    fn matches(&self, refr: &citeproc_io::Reference) -> bool {
+        match self.field.as_str() {
+            "type" => refr.csl_type.as_ref() == self.value.as_str(),
+            "id" => &*refr.id == self.value.as_str(),
+            field => {
+                let var = match csl::Variable::from_str(field) {
+                    Ok(var) => var,
+                    Err(_) => return false,
+                };
+                let string = match refr.ordinary.get(&var) {
+                    Some(string) => string,
+                    None => return false,
+                };
+                if string == self.value.as_str() {
+                    return true;
+                }
+                var == csl::Variable::Keyword
+                    && string
+                        .split(|c| c == ';' || c == ',')
+                        .any(|term| term.trim() == self.value.as_str())
+            }
+        }
+    }
+}
+
+/// Chooses which references appear in a bibliography, like the `select`, `include`, `exclude`
+/// and `quash` options to citeproc-js's `makeBibliography`. Used with
+/// [`Processor::get_bibliography_filtered`](crate::Processor::get_bibliography_filtered).
+///
+/// Empty lists impose no condition. Reference categories are not kept by citeproc-rs, so they
+/// cannot be filtered on.
+///
+/// ```
+/// use citeproc::BibliographyFilter;
+/// let filter: BibliographyFilter = serde_json::from_str(r#"{
+///     "select": [{ "field": "type", "value": "book" }],
+///     "exclude": [{ "field": "keyword", "value": "secondary" }]
+/// }"#).unwrap();
+/// assert_eq!(filter.select.len(), 1);
+/// ```
+#[derive(Deserialize, Serialize, Default, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct BibliographyFilter {
+    /// Every one of these must match.
+    pub select: Vec<FieldMatch>,
+    /// At least one of these must match.
+    pub include: Vec<FieldMatch>,
+    /// None of these may match.
+    pub exclude: Vec<FieldMatch>,
+    /// References matching all of these are left out.
+    pub quash: Vec<FieldMatch>,
+}
+
+impl BibliographyFilter {
+    /// Whether `refr` passes the filter.
+    pub fn matches(&self, refr: &citeproc_io::Reference) -> bool {
+        self.select.iter().all(|m| m.matches(refr))
+            && (self.include.is_empty() || self.include.iter().any(|m| m.matches(refr)))
+            && !self.exclude.iter().any(|m| m.matches(refr))
+            && (self.quash.is_empty() || !self.quash.iter().all(|m| m.matches(refr)))
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Eq)]
 pub struct FullRender {
     pub all_clusters: FnvHashMap<ClusterId, Arc<SmartString>>,
     pub bib_entries: Vec<BibEntry<Markup>>,
 }
 
+/// Facts about a built cluster, for integrations that need to join it up with the surrounding
+/// text, e.g. to avoid a doubled full stop at the end of a footnote.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ClusterMeta {
+    /// The length of the cluster as plain text, in `char`s.
+    pub plain_len: usize,
+    /// Whether the cluster contains any `display` elements, which are rendered as blocks.
+    pub has_block_display: bool,
+    /// Whether the last character, ignoring closing quotes, is one of `.,;:!?`.
+    pub ends_with_punctuation: bool,
+}
+
+/// The same citation in every supported output format, from
+/// [`Processor::formatted_bundle`](crate::Processor::formatted_bundle). Clipboards generally want
+/// all three posted at once.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct FormattedBundle {
+    pub html: SmartString,
+    /// Empty if citeproc was built without the `rtf` feature.
+    pub rtf: SmartString,
+    pub plain: SmartString,
+}
+
+/// A cite that could still have come from other references after every disambiguation method
+/// the style allows, from [`Processor::still_ambiguous`](crate::Processor::still_ambiguous).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AmbiguousCite {
+    pub cluster_id: ClusterId,
+    /// The position of the cite in the cluster, in the order the cites were inserted.
+    pub cite_index: usize,
+    pub ref_id: Atom,
+    /// The references it is indistinguishable from.
+    pub ambiguous_with: Vec<Atom>,
+}
+
+/// The part of a cluster's output produced by one of its cites, as a byte range.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CiteSpan {
+    /// The position of the cite in the cluster, in the order the cites were inserted.
+    pub cite_index: usize,
+    pub ref_id: Atom,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A built cluster along with its [`CiteSpan`]s, from
+/// [`Processor::get_cluster_with_spans`](crate::Processor::get_cluster_with_spans).
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ClusterWithSpans {
+    pub output: Arc<SmartString>,
+    /// In order of `start`. Cites collapsed away entirely have no span, and an author-only
+    /// rendering in-text gives a cite a second span.
+    pub spans: Vec<CiteSpan>,
+}
+
+/// The style driving a processor, from [`Processor::style_info`](crate::Processor::style_info).
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct StyleInfo {
+    pub id: String,
+    pub title: String,
+    /// From `<category citation-format="...">`.
+    pub citation_format: Option<csl::CitationFormat>,
+    pub class: csl::StyleClass,
+    pub default_locale: Option<Lang>,
+    /// Set by a dependent style or [`InitOptions::locale_override`](crate::InitOptions), and used
+    /// instead of `default_locale`.
+    pub locale_override: Option<Lang>,
+    pub has_bibliography: bool,
+    /// The dependent style the user chose, if this style is its independent parent.
+    pub dependent: Option<DependentStyleInfo>,
+}
+
+/// A dependent style recorded with
+/// [`Processor::set_dependent_style`](crate::Processor::set_dependent_style).
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DependentStyleInfo {
+    pub id: String,
+    pub title: String,
+    /// The `independent-parent` link.
+    pub independent_parent: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, Ord, PartialOrd, PartialEq)]
 pub enum IncludeUncited {
     /// The default
@@ -351,12 +851,158 @@ impl Default for IncludeUncited {
     }
 }
 
+/// What to do with the `accessed` date on references as they are inserted into the processor.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AccessedDatePolicy {
+    /// The default. References are stored as given.
+    Keep,
+    /// Web references (`webpage`, `post` and `post-weblog`) with no `accessed` date are given this
+    /// one. Pass today's date from your own clock, and update it with
+    /// [`Processor::set_accessed_date_policy`](crate::Processor::set_accessed_date_policy) if the
+    /// processor lives across midnight.
+    FillMissing(citeproc_io::Date),
+    /// The `accessed` date is removed from every reference.
+    Strip,
+}
+
+impl Default for AccessedDatePolicy {
+    fn default() -> Self {
+        AccessedDatePolicy::Keep
+    }
+}
+
+impl AccessedDatePolicy {
+    pub(crate) fn apply(&self, refr: &mut citeproc_io::Reference) {
+        use csl::{CslType, DateVariable};
+        match *self {
+            AccessedDatePolicy::Keep => {}
+            AccessedDatePolicy::Strip => {
+                refr.date.remove(&DateVariable::Accessed);
+            }
+            AccessedDatePolicy::FillMissing(today) => {
+                if matches!(
+                    refr.csl_type,
+                    CslType::Webpage | CslType::Post | CslType::PostWeblog
+                ) {
+                    refr.date
+                        .entry(DateVariable::Accessed)
+                        .or_insert(citeproc_io::DateOrRange::Single(today));
+                }
+            }
+        }
+    }
+
+    /// Whether `refr`, as given, is the reference already stored as `stored`. An `accessed` date
+    /// this policy filled in counts as the one `refr` would get, whatever day that was.
+    pub(crate) fn is_unchanged(
+        &self,
+        refr: &citeproc_io::Reference,
+        stored: &citeproc_io::Reference,
+    ) -> bool {
+        use csl::DateVariable;
+        let mut refr = refr.clone();
+        if let AccessedDatePolicy::FillMissing(_) = *self {
+            if let Some(accessed) = stored.date.get(&DateVariable::Accessed) {
+                refr.date
+                    .entry(DateVariable::Accessed)
+                    .or_insert_with(|| accessed.clone());
+            }
+        }
+        self.apply(&mut refr);
+        refr == *stored
+    }
+}
+
+/// Caps on what a [`Processor`](crate::Processor) will take on, for services that render styles
+/// and libraries they did not write. Everything is unlimited by default.
+///
+/// A style over the size limit is an [Error::LimitExceeded]. The rest are not errors, as the
+/// methods they apply to cannot fail: whatever is over the limit is left out, and a
+/// [`Warning`] with [`WarningCode::LimitExceeded`] says so. See
+/// [`Processor::take_warnings`](crate::Processor::take_warnings).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// The largest style or jurisdiction module, in bytes, that will be parsed. A style that comes
+    /// already parsed, e.g. from a [StyleBundle](crate::StyleBundle), is measured by the size of
+    /// its serialized form.
+    pub max_style_bytes: Option<usize>,
+    /// How many macros one cite may expand. Later macros in that cite render nothing.
+    pub max_macro_expansions: Option<u32>,
+    /// How many references the processor will hold. New references past that are dropped;
+    /// replacing an existing one is always allowed.
+    pub max_references: Option<usize>,
+    /// How many cites one cluster may have. Any more are dropped from the end.
+    pub max_cites_per_cluster: Option<usize>,
+    /// How long [`Processor::compute`](crate::Processor::compute) keeps starting on new clusters.
+    /// Clusters it did not get to are left for the next call. One cluster is never interrupted
+    /// halfway, so a single slow cluster can still go over.
+    ///
+    /// Ignored where there is no monotonic clock to measure it with, i.e. on
+    /// `wasm32-unknown-unknown`.
+    pub render_timeout: Option<Duration>,
+}
+
+impl ResourceLimits {
+    /// When [`Processor::compute`](crate::Processor::compute) should stop, if it started now.
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        // Instant::now panics there
+        if cfg!(all(target_arch = "wasm32", target_os = "unknown")) {
+            return None;
+        }
+        self.render_timeout.map(|t| Instant::now() + t)
+    }
+
+    pub(crate) fn check_style(&self, text: &str) -> Result<(), LimitExceeded> {
+        match self.max_style_bytes {
+            Some(limit) if text.len() > limit => Err(LimitExceeded::StyleTooLarge {
+                size: text.len(),
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_parsed_style(&self, style: &csl::Style) -> Result<(), LimitExceeded> {
+        let limit = match self.max_style_bytes {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        let size = serde_cbor::to_vec(style).map_or(0, |bytes| bytes.len());
+        if size > limit {
+            return Err(LimitExceeded::StyleTooLarge { size, limit });
+        }
+        Ok(())
+    }
+}
+
+/// A [ResourceLimits] limit that stopped a style from being used.
+#[derive(Debug, Clone, thiserror::Error, PartialEq, Eq)]
+pub enum LimitExceeded {
+    #[error("style is {size} bytes, more than the limit of {limit}")]
+    StyleTooLarge { size: usize, limit: usize },
+}
+
+#[doc(inline)]
+pub use citeproc_io::output::markup::{
+    CustomFormat, FormatOptions, HtmlOptions, PlainOptions, RtfOptions, TextRun,
+};
+
+#[doc(inline)]
+pub use citeproc_io::{CaseExceptions, Date, DateOrRange};
+
 #[doc(inline)]
-pub use citeproc_io::output::markup::FormatOptions;
+pub use citeproc_proc::{
+    entry_anchor, CitationNumbering, CompatMode, EntryDecoration, EtAlTerm, NameTermOverrides,
+    ReprintSort, SortKeyData, SortKeyValue, VariableCoverage, Warning, WarningCode,
+};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum SupportedFormat {
     Html,
+    /// Needs the `rtf` feature. Without it, [`Processor::set_output_format`] rejects this, and
+    /// the previews that take a format write plain text instead.
+    ///
+    /// [`Processor::set_output_format`]: crate::Processor::set_output_format
     Rtf,
     Plain,
 }
@@ -368,11 +1014,27 @@ impl Default for SupportedFormat {
 }
 
 impl SupportedFormat {
+    /// Whether this build can write the format. RTF needs the `rtf` feature.
+    pub fn is_available(&self) -> bool {
+        match self {
+            SupportedFormat::Rtf => cfg!(feature = "rtf"),
+            SupportedFormat::Html | SupportedFormat::Plain => true,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SupportedFormat::Html => "html",
+            SupportedFormat::Rtf => "rtf",
+            SupportedFormat::Plain => "plain",
+        }
+    }
+
     pub(crate) fn make_markup(&self, options: FormatOptions) -> Markup {
         match self {
-            SupportedFormat::Html => Markup::Html(options),
-            SupportedFormat::Rtf => Markup::Rtf(options),
-            SupportedFormat::Plain => Markup::Plain(options),
+            SupportedFormat::Html => Markup::Html(options.html),
+            SupportedFormat::Rtf => Markup::Rtf(options.rtf),
+            SupportedFormat::Plain => Markup::Plain(options.plain),
         }
     }
 }
@@ -0,0 +1,115 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright © 2021 Corporation for Digital Scholarship
+
+//! A parsed style and reference library saved in a compact binary form, so that a processor can
+//! start without parsing the style's XML or the library's CSL-JSON again. This is for cold starts
+//! in serverless functions and WebAssembly, where every request would otherwise pay for parsing.
+//!
+//! Make one with [`Processor::style_bundle`] ahead of time, ship the bytes from
+//! [`StyleBundle::to_bytes`], and start each processor with [`Processor::from_bundle`].
+//!
+//! The bytes are a short header and then CBOR. Bincode and postcard are smaller, but they need
+//! every field in a fixed order, and the style and reference types skip empty fields and write
+//! some values in their own formats. CBOR describes itself, so those round trip unchanged.
+
+use crate::api::Error;
+use citeproc_io::Reference;
+use csl::Style;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+const MAGIC: &[u8; 4] = b"CSLB";
+
+/// The format of the bytes after the header. It goes up whenever the serialized form of [Style]
+/// or [Reference] changes, and bundles written with any other version are refused.
+pub const BUNDLE_VERSION: u16 = 1;
+
+/// A parsed style and a reference library.
+///
+/// ```
+/// use citeproc::prelude::*;
+///
+/// let style = r#"<style class="in-text"><citation><layout><text variable="title" /></layout></citation></style>"#;
+/// let options = InitOptions { style, test_mode: true, ..Default::default() };
+/// let mut processor = Processor::new(options).unwrap();
+/// let mut refr = Reference::empty("smith".into(), csl::CslType::Book);
+/// refr.ordinary.insert(csl::Variable::Title, "Title".into());
+/// processor.insert_reference(refr);
+/// let bytes = processor.style_bundle().to_bytes();
+///
+/// // Later, on a cold start
+/// let bundle = StyleBundle::from_bytes(&bytes).unwrap();
+/// let mut processor = Processor::from_bundle(bundle, InitOptions::default()).unwrap();
+/// let id = processor.cluster_id("c1");
+/// processor.insert_cites(id, &[Cite::basic("smith")]);
+/// processor.set_cluster_order(&[ClusterPosition::in_text(id)]).unwrap();
+/// assert_eq!(processor.get_cluster(id).unwrap().as_str(), "Title");
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StyleBundle {
+    pub style: Arc<Style>,
+    pub references: Vec<Reference>,
+}
+
+/// Why a [StyleBundle] or [StylePack](crate::StylePack) could not be read.
+#[derive(Debug, thiserror::Error)]
+pub enum BundleError {
+    #[error("not a style bundle")]
+    NotABundle,
+    #[error("not a style pack")]
+    NotAStylePack,
+    #[error("written in format version {found}, but only version {expected} can be read")]
+    Version { found: u16, expected: u16 },
+    #[error("corrupt data: {0}")]
+    Decode(#[from] serde_cbor::Error),
+    #[error("corrupt data: could not decompress")]
+    Decompress,
+    #[error("no style named {0:?} in the style pack")]
+    NoSuchStyle(String),
+    #[error("the parent {parent:?} of dependent style {style:?} is not in the style pack")]
+    MissingParent { style: String, parent: String },
+}
+
+impl StyleBundle {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_header(&mut bytes, MAGIC, BUNDLE_VERSION);
+        serde_cbor::to_writer(&mut bytes, self).expect("serializing a style bundle cannot fail");
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let rest = read_header(bytes, MAGIC, BUNDLE_VERSION).ok_or(BundleError::NotABundle)??;
+        let bundle = serde_cbor::from_slice(rest).map_err(BundleError::from)?;
+        Ok(bundle)
+    }
+}
+
+pub(crate) fn write_header(bytes: &mut Vec<u8>, magic: &[u8; 4], version: u16) {
+    bytes.extend_from_slice(magic);
+    bytes.extend_from_slice(&version.to_le_bytes());
+}
+
+/// Checks the magic number and version at the start of `bytes`, and returns what follows them.
+/// `None` if there is no magic number.
+pub(crate) fn read_header<'a>(
+    bytes: &'a [u8],
+    magic: &[u8; 4],
+    version: u16,
+) -> Option<Result<&'a [u8], BundleError>> {
+    let header_len = magic.len() + 2;
+    if bytes.len() < header_len || &bytes[..magic.len()] != magic {
+        return None;
+    }
+    let found = u16::from_le_bytes([bytes[magic.len()], bytes[magic.len() + 1]]);
+    if found != version {
+        return Some(Err(BundleError::Version {
+            found,
+            expected: version,
+        }));
+    }
+    Some(Ok(&bytes[header_len..]))
+}
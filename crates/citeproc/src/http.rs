@@ -0,0 +1,188 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright © 2021 Corporation for Digital Scholarship
+
+//! Fetching locales and styles from the official CSL repositories over HTTP. Requires the
+//! `http-fetcher` feature.
+//!
+//! Responses are cached on disk alongside their `ETag`, so a file is only downloaded again when it
+//! has changed upstream, and a cached copy is used if the network is unavailable.
+//!
+//! ```no_run
+//! use citeproc::http::{HttpFetcher, StyleFetcher};
+//! use citeproc::{InitOptions, Processor};
+//! use std::sync::Arc;
+//!
+//! let fetcher = Arc::new(HttpFetcher::default());
+//! let style = fetcher.fetch_style("apa").unwrap().expect("no such style");
+//! let processor = Processor::new(InitOptions {
+//!     style: &style,
+//!     fetcher: Some(fetcher),
+//!     ..Default::default()
+//! });
+//! ```
+
+use citeproc_db::{LocaleFetchError, LocaleFetcher, PredefinedLocales};
+use csl::Lang;
+use reqwest::blocking::Client;
+use reqwest::header::{ETAG, IF_NONE_MATCH};
+use reqwest::StatusCode;
+use std::fs;
+use std::io;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+
+pub const CSL_LOCALES_URL: &str =
+    "https://raw.githubusercontent.com/citation-style-language/locales/master";
+pub const CSL_STYLES_URL: &str =
+    "https://raw.githubusercontent.com/citation-style-language/styles/master";
+
+/// Fetches a style's XML by name, like `apa` or `chicago-note-bibliography`.
+pub trait StyleFetcher {
+    /// `Ok(None)` means there is no such style.
+    fn fetch_style(&self, name: &str) -> Result<Option<String>, LocaleFetchError>;
+}
+
+/// A [LocaleFetcher] and [StyleFetcher] that downloads `locales-xx-XX.xml` and `name.xml` files.
+///
+/// Like the CLI's filesystem fetcher, it falls back to the bundled en-US locale if there is no
+/// en-US file to be had.
+pub struct HttpFetcher {
+    client: AssertUnwindSafe<Client>,
+    locales_url: String,
+    styles_url: String,
+    cache_dir: Option<PathBuf>,
+}
+
+impl Default for HttpFetcher {
+    /// Uses the official CSL repositories, and caches to the platform's cache directory.
+    fn default() -> Self {
+        let cache_dir = directories::ProjectDirs::from("net", "cormacrelf", "citeproc-rs")
+            .map(|pd| pd.cache_dir().join("http"));
+        HttpFetcher::new(CSL_LOCALES_URL, CSL_STYLES_URL, cache_dir)
+    }
+}
+
+impl HttpFetcher {
+    /// `locales_url` and `styles_url` are the directories the files are requested from, without
+    /// a trailing slash. With no `cache_dir`, every fetch is a full download.
+    pub fn new(
+        locales_url: impl Into<String>,
+        styles_url: impl Into<String>,
+        cache_dir: Option<PathBuf>,
+    ) -> Self {
+        HttpFetcher {
+            client: AssertUnwindSafe(Client::new()),
+            locales_url: locales_url.into(),
+            styles_url: styles_url.into(),
+            cache_dir,
+        }
+    }
+
+    fn fetch_cached(&self, url: &str, file_name: &str) -> Result<Option<String>, LocaleFetchError> {
+        let cached = self.cache_dir.as_ref().map(|dir| dir.join(file_name));
+        let etag = cached
+            .as_ref()
+            .and_then(|path| fs::read_to_string(etag_path(path)).ok());
+        let mut request = self.client.get(url);
+        if let Some(etag) = &etag {
+            request = request.header(IF_NONE_MATCH, etag.as_str());
+        }
+        let response = match request.send() {
+            Ok(response) => response,
+            Err(e) => {
+                // Offline, probably. Anything we have is better than nothing.
+                return match cached.as_ref().map(fs::read_to_string) {
+                    Some(Ok(text)) => Ok(Some(text)),
+                    _ => Err(LocaleFetchError::Other(e.to_string())),
+                };
+            }
+        };
+        match response.status() {
+            StatusCode::NOT_MODIFIED if cached.is_some() => {
+                if let Some(Ok(text)) = cached.as_ref().map(fs::read_to_string) {
+                    return Ok(Some(text));
+                }
+                // The cache was cleared after we read the ETag. Start again without it.
+                let response = self.client.get(url).send().map_err(other)?;
+                self.save(cached.as_deref(), response)
+            }
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => self.save(cached.as_deref(), response),
+            status => Err(LocaleFetchError::Other(format!("{}: {}", url, status))),
+        }
+    }
+
+    fn save(
+        &self,
+        cached: Option<&Path>,
+        response: reqwest::blocking::Response,
+    ) -> Result<Option<String>, LocaleFetchError> {
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from);
+        let text = response.text().map_err(other)?;
+        if let Some(path) = cached {
+            // Failing to cache shouldn't fail the fetch.
+            if let Err(e) = write_cache(path, &text, etag.as_deref()) {
+                log::warn!("citeproc-rs: could not cache {}: {}", path.display(), e);
+            }
+        }
+        Ok(Some(text))
+    }
+}
+
+fn other(e: reqwest::Error) -> LocaleFetchError {
+    LocaleFetchError::Other(e.to_string())
+}
+
+fn etag_path(path: &Path) -> PathBuf {
+    path.with_extension("xml.etag")
+}
+
+fn write_cache(path: &Path, text: &str, etag: Option<&str>) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, text)?;
+    match etag {
+        Some(etag) => fs::write(etag_path(path), etag),
+        None => match fs::remove_file(etag_path(path)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        },
+    }
+}
+
+impl LocaleFetcher for HttpFetcher {
+    fn fetch_string(&self, lang: &Lang) -> Result<Option<String>, LocaleFetchError> {
+        let file_name = format!("locales-{}.xml", lang);
+        let url = format!("{}/{}", self.locales_url, file_name);
+        match self.fetch_cached(&url, &file_name)? {
+            Some(text) => Ok(Some(text)),
+            None => PredefinedLocales::bundled_en_us().fetch_string(lang),
+        }
+    }
+}
+
+impl StyleFetcher for HttpFetcher {
+    /// Names are limited to what the CSL styles repository uses, lowercase letters, digits and
+    /// `-`. Anything else, like `../secret`, is no such style, so a name cannot reach outside
+    /// `styles_url` or the cache directory.
+    fn fetch_style(&self, name: &str) -> Result<Option<String>, LocaleFetchError> {
+        let valid = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-';
+        if name.is_empty() || !name.chars().all(valid) {
+            return Ok(None);
+        }
+        let file_name = format!("{}.xml", name);
+        let url = format!("{}/{}", self.styles_url, file_name);
+        self.fetch_cached(
+            &url,
+            &Path::new("styles").join(&file_name).to_string_lossy(),
+        )
+    }
+}
@@ -0,0 +1,144 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright © 2021 Corporation for Digital Scholarship
+
+//! A record of the edits made to a [Processor], for playing back into a fresh one.
+//!
+//! Turn it on with [`Processor::set_journaling`] and read it back with [`Processor::journal`].
+//! The journal is JSON, so a user can attach it to a bug report, and the host application's
+//! integration tests can replay a known sequence of edits and check the result.
+//!
+//! Only the document is recorded: the style and its jurisdiction modules, references, clusters
+//! and their order. Settings come from [InitOptions](crate::InitOptions), so replay into a
+//! processor made with the same options.
+//! Previews are not recorded, as they leave the document as it was.
+
+use crate::api::{string_id, ClusterPosition, Error};
+use crate::processor::Processor;
+use citeproc_io::{Reference, SmartString};
+use csl::Atom;
+use serde::{Deserialize, Serialize};
+
+/// One edit to the document.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "camelCase")]
+pub enum JournalEntry {
+    SetStyle {
+        style: String,
+    },
+    SetJurisdictionModule {
+        jurisdiction: SmartString,
+        style: String,
+    },
+    RemoveJurisdictionModule {
+        jurisdiction: SmartString,
+    },
+    /// Inserts a reference, or replaces the one with the same id. The reference is recorded as it
+    /// was stored, i.e. after the [AccessedDatePolicy](crate::AccessedDatePolicy) applied, so
+    /// replaying does not depend on what day it is.
+    InsertReference {
+        reference: Reference,
+    },
+    RemoveReference {
+        id: Atom,
+    },
+    ClearReferences,
+    /// Replaces every cluster, as [`Processor::init_clusters`] does.
+    InitClusters {
+        clusters: Vec<string_id::Cluster>,
+    },
+    /// The whole of one cluster after it changed, whichever method changed it.
+    ReplaceCluster {
+        cluster: string_id::Cluster,
+    },
+    RemoveCluster {
+        id: SmartString,
+    },
+    SetClusterOrder {
+        positions: Vec<string_id::ClusterPosition>,
+    },
+}
+
+impl JournalEntry {
+    /// Makes the same edit to `processor`.
+    pub fn apply(self, processor: &mut Processor) -> Result<(), Error> {
+        match self {
+            JournalEntry::SetStyle { style } => processor.set_style_text(&style)?,
+            JournalEntry::SetJurisdictionModule {
+                jurisdiction,
+                style,
+            } => processor.set_jurisdiction_module(&jurisdiction, &style)?,
+            JournalEntry::RemoveJurisdictionModule { jurisdiction } => {
+                processor.remove_jurisdiction_module(&jurisdiction)
+            }
+            JournalEntry::InsertReference { reference } => processor.insert_reference(reference),
+            JournalEntry::RemoveReference { id } => processor.remove_reference(id),
+            JournalEntry::ClearReferences => processor.clear_references(),
+            JournalEntry::InitClusters { clusters } => processor.init_clusters_str(clusters),
+            JournalEntry::ReplaceCluster { cluster } => processor.insert_cluster_str(cluster),
+            JournalEntry::RemoveCluster { id } => processor.remove_cluster_str(&id),
+            JournalEntry::SetClusterOrder { positions } => {
+                let positions: Vec<ClusterPosition> = positions
+                    .into_iter()
+                    .map(|pos| ClusterPosition {
+                        id: pos.id.map(|id| processor.cluster_id(id)),
+                        note: pos.note,
+                    })
+                    .collect();
+                processor.set_cluster_order(&positions)?
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Every edit made while journaling was on, oldest first. Serializes as a JSON array.
+///
+/// ```
+/// use citeproc::prelude::*;
+///
+/// let options = || InitOptions {
+///     style: r#"<style class="in-text"><citation><layout><text variable="title" /></layout></citation></style>"#,
+///     test_mode: true,
+///     ..Default::default()
+/// };
+/// let mut processor = Processor::new(options()).unwrap();
+/// processor.set_journaling(true);
+/// let mut refr = Reference::empty("smith".into(), csl::CslType::Book);
+/// refr.ordinary.insert(csl::Variable::Title, "Title".into());
+/// processor.insert_reference(refr);
+/// let id = processor.cluster_id("c1");
+/// processor.insert_cites(id, &[Cite::basic("smith")]);
+/// processor.set_cluster_order(&[ClusterPosition::in_text(id)]).unwrap();
+///
+/// let saved = processor.journal().to_json();
+/// let mut replayed = Processor::new(options()).unwrap();
+/// Journal::from_json(&saved).unwrap().replay(&mut replayed).unwrap();
+/// let id = replayed.cluster_id("c1");
+/// assert_eq!(replayed.get_cluster(id).unwrap().as_str(), "Title");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Journal {
+    pub entries: Vec<JournalEntry>,
+}
+
+impl Journal {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("serializing a journal cannot fail")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Makes every edit in the journal, in order. Stops at the first one that fails.
+    pub fn replay(&self, processor: &mut Processor) -> Result<(), Error> {
+        for entry in &self.entries {
+            entry.clone().apply(processor)?;
+        }
+        Ok(())
+    }
+}
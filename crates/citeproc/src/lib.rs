@@ -8,27 +8,52 @@
 // extern crate log;
 
 pub(crate) mod api;
+pub(crate) mod bundle;
+#[cfg(feature = "http-fetcher")]
+pub mod http;
+pub(crate) mod journal;
+pub(crate) mod pool;
 pub(crate) mod processor;
+pub(crate) mod style_cache;
+pub(crate) mod style_pack;
+pub(crate) mod style_preview;
+pub(crate) mod style_registry;
+#[cfg(feature = "translation-server")]
+pub mod translation_server;
 
 #[cfg(test)]
 mod test;
 
 pub use self::api::*;
 
-pub use self::processor::{InitOptions, Processor};
+pub use self::bundle::{BundleError, StyleBundle, BUNDLE_VERSION};
+pub use self::journal::{Journal, JournalEntry};
+pub use self::pool::{ProcessorPool, SharedFetcher};
+pub use self::processor::{Checkpoint, InitOptions, Processor};
+pub use self::style_cache::StyleCache;
+pub use self::style_pack::{PackedStyle, StylePack, StylePackBuilder, STYLE_PACK_VERSION};
+pub use self::style_preview::{PreviewRender, StylePreview};
+pub use self::style_registry::{ResolvedStyle, StyleEntry, StyleQuery, StyleRegistry};
 
 pub mod prelude {
     pub use crate::api::*;
-    pub use crate::processor::{InitOptions, Processor};
+    pub use crate::bundle::{BundleError, StyleBundle};
+    pub use crate::journal::{Journal, JournalEntry};
+    pub use crate::pool::{ProcessorPool, SharedFetcher};
+    pub use crate::processor::{Checkpoint, InitOptions, Processor};
+    pub use crate::style_pack::{PackedStyle, StylePack, StylePackBuilder};
+    pub use crate::style_preview::{PreviewRender, StylePreview};
+    pub use crate::style_registry::{ResolvedStyle, StyleEntry, StyleQuery, StyleRegistry};
     pub use citeproc_db::PredefinedLocales;
     pub use citeproc_db::{
-        CiteDatabase, CiteId, ClusterNumber, IntraNote, LocaleDatabase, LocaleFetchError,
-        LocaleFetcher, StyleDatabase,
+        BackReference, CiteDatabase, CiteId, ClusterNumber, IntraNote, LocaleDatabase,
+        LocaleFetchError, LocaleFetcher, StyleDatabase,
     };
     pub use citeproc_io::output::{markup::Markup, OutputFormat};
     pub use citeproc_io::{Cite, Reference, SmartString};
     pub use citeproc_proc::db::{ImplementationDetails, IrDatabase};
-    pub use csl::Atom;
+    pub use csl::{Atom, StyleMode};
+    pub use salsa::{ParallelDatabase, Snapshot};
 
     pub use citeproc_db::ClusterId;
     pub use citeproc_io::{CiteMode, ClusterMode, Locator, Locators, NumberLike};
@@ -0,0 +1,197 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright © 2021 Corporation for Digital Scholarship
+
+//! Owning many processors at once, one for each open document.
+//!
+//! Each document still needs its own [Processor], because the processor holds all of the cluster
+//! and reference state for a document. What can be shared is everything that comes from outside
+//! the document, which is mostly locales. A [ProcessorPool] hands every processor it creates the
+//! same [SharedFetcher], so a locale is fetched once for the whole application, and
+//! [ProcessorPool::store_locales] makes a locale available to every document in one call.
+
+use crate::api::{Error, UpdateSummary};
+use crate::processor::{InitOptions, Processor};
+use citeproc_db::{LocaleFetchError, LocaleFetcher};
+use citeproc_io::SmartString;
+use csl::Lang;
+use fnv::FnvHashMap;
+use parking_lot::Mutex;
+use std::collections::hash_map::Entry;
+use std::sync::Arc;
+
+/// A [LocaleFetcher] that remembers everything its inner fetcher returns, and anything stored in
+/// it directly.
+///
+/// Cached strings are handed out as clones, so the underlying fetcher (which may hit the disk or
+/// the network) is only consulted once per language.
+pub struct SharedFetcher {
+    inner: Arc<dyn LocaleFetcher>,
+    cache: Mutex<FnvHashMap<Lang, Option<Arc<String>>>>,
+}
+
+impl SharedFetcher {
+    pub fn new(inner: Arc<dyn LocaleFetcher>) -> Self {
+        SharedFetcher {
+            inner,
+            cache: Mutex::new(Default::default()),
+        }
+    }
+
+    /// Overrides whatever the inner fetcher would return for each language.
+    pub fn store(&self, locales: Vec<(Lang, String)>) {
+        let mut cache = self.cache.lock();
+        for (lang, xml) in locales {
+            cache.insert(lang, Some(Arc::new(xml)));
+        }
+    }
+
+    /// Forgets every cached locale, including ones stored directly.
+    pub fn clear(&self) {
+        self.cache.lock().clear();
+    }
+
+    pub fn is_cached(&self, lang: &Lang) -> bool {
+        self.cache.lock().contains_key(lang)
+    }
+}
+
+impl LocaleFetcher for SharedFetcher {
+    fn fetch_string(&self, lang: &Lang) -> Result<Option<String>, LocaleFetchError> {
+        if let Some(cached) = self.cache.lock().get(lang) {
+            return Ok(cached.as_ref().map(|s| String::clone(s)));
+        }
+        // Don't hold the lock while fetching; errors are not cached, so they can be retried.
+        let fetched = self.inner.fetch_string(lang)?;
+        self.cache
+            .lock()
+            .insert(lang.clone(), fetched.clone().map(Arc::new));
+        Ok(fetched)
+    }
+}
+
+/// Manages one [Processor] per open document, all sharing a single [SharedFetcher].
+///
+/// Documents are identified by whatever string the application likes, e.g. a file path or a window
+/// id.
+///
+/// ```
+/// use citeproc::prelude::*;
+/// use std::sync::Arc;
+///
+/// let style = r#"<style class="in-text"><citation><layout></layout></citation></style>"#;
+/// let mut pool = ProcessorPool::new(Arc::new(PredefinedLocales::bundled_en_us()));
+/// for doc in &["chapter-1.md", "chapter-2.md"] {
+///     pool.open(*doc, InitOptions { style, test_mode: true, ..Default::default() })
+///         .unwrap();
+/// }
+/// let one = pool.get_mut("chapter-1.md").unwrap();
+/// let id = one.cluster_id("a");
+/// one.insert_cites(id, &[Cite::basic("smith")]);
+/// assert_eq!(pool.len(), 2);
+/// assert!(pool.close("chapter-2.md").is_some());
+/// ```
+pub struct ProcessorPool {
+    fetcher: Arc<SharedFetcher>,
+    documents: FnvHashMap<SmartString, Processor>,
+}
+
+impl ProcessorPool {
+    pub fn new(fetcher: Arc<dyn LocaleFetcher>) -> Self {
+        ProcessorPool {
+            fetcher: Arc::new(SharedFetcher::new(fetcher)),
+            documents: Default::default(),
+        }
+    }
+
+    pub fn fetcher(&self) -> &Arc<SharedFetcher> {
+        &self.fetcher
+    }
+
+    /// Creates a processor for a document. Any `fetcher` in the options is ignored in favour of the
+    /// pool's shared one. If a document with this id was already open, it is replaced.
+    pub fn open(
+        &mut self,
+        id: impl Into<SmartString>,
+        options: InitOptions,
+    ) -> Result<&mut Processor, Error> {
+        let fetcher: Arc<dyn LocaleFetcher> = self.fetcher.clone();
+        let processor = Processor::new(InitOptions {
+            fetcher: Some(fetcher),
+            ..options
+        })?;
+        Ok(match self.documents.entry(id.into()) {
+            Entry::Occupied(mut entry) => {
+                entry.insert(processor);
+                entry.into_mut()
+            }
+            Entry::Vacant(entry) => entry.insert(processor),
+        })
+    }
+
+    /// Removes a document from the pool, returning its processor.
+    pub fn close(&mut self, id: &str) -> Option<Processor> {
+        self.documents.remove(id)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Processor> {
+        self.documents.get(id)
+    }
+
+    pub fn get_mut(&mut self, id: &str) -> Option<&mut Processor> {
+        self.documents.get_mut(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    /// The ids of all open documents, in no particular order.
+    pub fn document_ids(&self) -> impl Iterator<Item = &str> {
+        self.documents.keys().map(|k| k.as_str())
+    }
+
+    /// Stores locales once, for every open document and every document opened later.
+    ///
+    /// Each processor is told to re-fetch its locales, so documents that had fallen back to a
+    /// parent locale pick up the new one on their next update.
+    pub fn store_locales(&mut self, locales: Vec<(Lang, String)>) {
+        self.fetcher.store(locales);
+        for processor in self.documents.values_mut() {
+            processor.refetch_locales();
+        }
+    }
+
+    /// The languages needed by any open document that have not been fetched or stored yet.
+    pub fn missing_langs(&self) -> Vec<Lang> {
+        let mut langs: Vec<Lang> = self
+            .documents
+            .values()
+            .flat_map(|p| p.get_langs_in_use())
+            .filter(|lang| !self.fetcher.is_cached(lang))
+            .collect();
+        langs.sort();
+        langs.dedup();
+        langs
+    }
+
+    /// Routes a `batched_updates` call to one document.
+    pub fn batched_updates(&self, id: &str) -> Option<UpdateSummary> {
+        self.documents.get(id).map(|p| p.batched_updates())
+    }
+
+    /// Collects updates from every document that has any.
+    pub fn all_batched_updates(&self) -> Vec<(SmartString, UpdateSummary)> {
+        self.documents
+            .iter()
+            .map(|(id, p)| (id.clone(), p.batched_updates()))
+            .filter(|(_, summary)| !summary.clusters.is_empty() || summary.bibliography.is_some())
+            .collect()
+    }
+}
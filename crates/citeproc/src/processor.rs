@@ -9,10 +9,15 @@
 #![allow(clippy::enum_variant_names)]
 
 use crate::prelude::*;
+use crate::style_cache::StyleCache;
 
 use crate::api::{
-    string_id, BibEntry, BibliographyMeta, BibliographyUpdate, ClusterPosition, IncludeUncited,
-    ReorderingError, SecondFieldAlign, UpdateSummary,
+    string_id, AccessedDatePolicy, AmbiguousCite, BibEntry, BibliographyFilter, BibliographyMeta,
+    BibliographySection, BibliographyUpdate, CiteSpan, CiteprocJsCitation, CiteprocJsCitationItem,
+    CiteprocJsProperties, ClusterIdConflict, ClusterMeta, ClusterPosition, ClusterWithSpans,
+    DependentStyleInfo, EntryDecoration, Error, FormattedBundle, IncludeUncited, InputChange,
+    ReorderingError, ResourceLimits, SecondFieldAlign, SortKeyValue, StyleInfo, TextRun,
+    UpdateSummary,
 };
 use citeproc_db::{
     CiteData, CiteDatabaseStorage, HasFetcher, LocaleDatabaseStorage, StyleDatabaseStorage, Uncited,
@@ -21,14 +26,15 @@ use citeproc_io::output::markup::FormatOptions;
 use citeproc_proc::db::IrDatabaseStorage;
 use citeproc_proc::BibNumber;
 use indexmap::set::IndexSet;
+use parking_lot::{Mutex, RwLock};
 
 use salsa::{Database, Durability, SweepStrategy};
-#[cfg(feature = "rayon")]
 use salsa::{ParallelDatabase, Snapshot};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::sync::{Mutex, RwLock};
+use std::time::Instant;
 
-use csl::{Lang, Style, StyleError};
+use csl::{AnyVariable, CiteOrBib, CslType, Lang, Style, StyleMeta, StyleMode};
 
 use citeproc_io::output::{markup::Markup, OutputFormat};
 use citeproc_io::{Cite, ClusterMode, Reference, SmartString};
@@ -38,6 +44,8 @@ use string_interner::{backend::StringBackend, StringInterner};
 pub(crate) type Interner =
     StringInterner<ClusterId, StringBackend<ClusterId>, std::collections::hash_map::RandomState>;
 
+const PREVIEW_REFERENCE_ID: &'static str = "REFERENCE-2b4e3fe4429cb";
+
 #[allow(dead_code)]
 type MarkupBuild = <Markup as OutputFormat>::Build;
 #[allow(dead_code)]
@@ -73,11 +81,24 @@ pub struct Processor {
     last_clusters: Arc<Mutex<FnvHashMap<ClusterId, Arc<SmartString>>>>,
     interner: Arc<RwLock<Interner>>,
     preview_cluster_id: ClusterId,
+    accessed_date_policy: AccessedDatePolicy,
+    style_mode: StyleMode,
+    max_macro_depth: Option<usize>,
+    limits: ResourceLimits,
+    warnings: Arc<Mutex<IndexSet<Warning>>>,
+    dependent_style: Option<Arc<csl::Info>>,
+    /// The locale override asked for in [InitOptions] or with [`Processor::set_locale_override`],
+    /// which a dependent style's `default-locale` does not replace.
+    user_locale_override: Option<Lang>,
+    invalidation_log: Option<Arc<Mutex<Vec<InputChange>>>>,
+    journal: Option<Arc<Mutex<Vec<JournalEntry>>>>,
 }
 
 impl Database for Processor {}
 
-#[cfg(feature = "rayon")]
+/// Snapshots are read-only views of the processor as it was when they were taken. While any
+/// snapshot is alive, changing the processor's inputs (inserting references or clusters, setting
+/// the style, etc.) blocks until every snapshot is dropped.
 impl ParallelDatabase for Processor {
     fn snapshot(&self) -> Snapshot<Self> {
         Snapshot::new(Processor {
@@ -89,6 +110,15 @@ impl ParallelDatabase for Processor {
             last_clusters: self.last_clusters.clone(),
             interner: self.interner.clone(),
             preview_cluster_id: self.preview_cluster_id,
+            accessed_date_policy: self.accessed_date_policy,
+            style_mode: self.style_mode,
+            max_macro_depth: self.max_macro_depth,
+            limits: self.limits.clone(),
+            warnings: self.warnings.clone(),
+            dependent_style: self.dependent_style.clone(),
+            user_locale_override: self.user_locale_override.clone(),
+            invalidation_log: self.invalidation_log.clone(),
+            journal: self.journal.clone(),
         })
     }
 }
@@ -104,9 +134,13 @@ impl ImplementationDetails for Processor {
         self.formatter()
     }
     fn lookup_cluster_id(&self, symbol: ClusterId) -> Option<SmartString> {
-        let reader = self.interner.read().unwrap();
+        let reader = self.interner.read();
         reader.resolve(symbol).map(SmartString::from)
     }
+    fn report_warning(&self, warning: Warning) {
+        log::warn!("{}", warning);
+        self.warnings.lock().insert(warning);
+    }
 }
 
 // need a Clone impl for map_with
@@ -128,9 +162,25 @@ impl Clone for Snap {
 #[derive(Clone, Default)]
 pub struct InitOptions<'a> {
     pub format: SupportedFormat,
+    /// Options for each output format. Only those for `format` apply at first, but the others
+    /// are kept for previewing in another format or making a [Processor::formatted_bundle].
+    ///
+    /// `link_anchors` on HTML and RTF (on by default) is the equivalent of citeproc-js's
+    /// `development_extensions.wrap_url_and_doi`. It renders the URL, DOI, PMID and PMCID variables
+    /// as `<a>` tags in HTML and `HYPERLINK` fields in RTF. DOIs, PMIDs and PMCIDs are written out
+    /// as full `https://` URLs, whether the reference had `doi:10.1000/xyz`, a bare `10.1000/xyz`,
+    /// or the style added the `https://doi.org/` prefix itself.
     pub format_options: FormatOptions,
+    /// An output format implemented outside citeproc-rs. If present, it is used instead of
+    /// `format`.
+    pub custom_format: Option<Arc<dyn CustomFormat>>,
     /// A full independent style.
     pub style: &'a str,
+    /// A style that has been parsed already, e.g. from a [StyleBundle]. If present, it is used
+    /// instead of `style`, and `csl_features`, `style_mode` and `test_mode` do not apply to it.
+    /// Its macros are still checked for cycles and against `max_macro_depth`, and its size against
+    /// `limits`, since the bytes it came from may not be trusted.
+    pub parsed_style: Option<Arc<Style>>,
     /// You might get this from a dependent style via `StyleMeta::parse(dependent_xml_string)`
     pub locale_override: Option<Lang>,
     /// Mechanism for fetching the locale you provide, if necessary.
@@ -146,6 +196,35 @@ pub struct InitOptions<'a> {
     /// Disables sorting on the bibliography (enabled by default)
     pub bibliography_no_sort: bool,
 
+    /// Whether citation numbers follow the bibliography or the order of first citation.
+    pub citation_numbering: CitationNumbering,
+
+    /// Fills in or strips the `accessed` date on references as they are inserted.
+    pub accessed_date_policy: AccessedDatePolicy,
+
+    /// Replaces the style's "and" and et-al terms, e.g. to use "&" with any style.
+    pub name_term_overrides: NameTermOverrides,
+
+    /// Words like "mRNA" or "iOS" that title case and sentence case must not change.
+    pub case_exceptions: CaseExceptions,
+
+    /// Whether to parse styles leniently (the default), as strict CSL or as CSL-M. Also applies
+    /// to styles and jurisdiction modules set later.
+    pub style_mode: StyleMode,
+
+    /// Rejects styles whose macros call other macros more than this many deep. Worth setting when
+    /// the styles come from users. Macros that call themselves are rejected regardless.
+    pub max_macro_depth: Option<usize>,
+
+    /// Whether to follow the CSL spec (the default) or citeproc-js where the two disagree.
+    pub compat_mode: CompatMode,
+
+    /// Whether sorting on `issued` uses a reprint's `original-date` instead.
+    pub reprint_sort: ReprintSort,
+
+    /// Caps on style size, references, cites and rendering time, for untrusted input.
+    pub limits: ResourceLimits,
+
     #[doc(hidden)]
     pub use_default_default: private::CannotConstruct,
 }
@@ -170,6 +249,15 @@ impl Processor {
             // This uses DefaultBackend, which is
             interner: Arc::new(RwLock::new(interner)),
             preview_cluster_id,
+            accessed_date_policy: AccessedDatePolicy::default(),
+            style_mode: StyleMode::default(),
+            max_macro_depth: None,
+            limits: ResourceLimits::default(),
+            warnings: Default::default(),
+            dependent_style: None,
+            user_locale_override: None,
+            invalidation_log: None,
+            journal: None,
         };
         citeproc_db::safe_default(&mut db);
         citeproc_proc::safe_default(&mut db);
@@ -178,58 +266,338 @@ impl Processor {
         db
     }
 
-    pub fn new(options: InitOptions) -> Result<Self, StyleError> {
+    pub fn new(options: InitOptions) -> Result<Self, Error> {
         // The only thing you need from a dependent style is the override language, which may well
         // be none.
         let InitOptions {
             style,
+            parsed_style,
             locale_override,
             fetcher,
             format,
             format_options,
+            custom_format,
             csl_features,
             test_mode,
             bibliography_no_sort,
+            citation_numbering,
+            accessed_date_policy,
+            name_term_overrides,
+            case_exceptions,
+            style_mode,
+            max_macro_depth,
+            compat_mode,
+            reprint_sort,
+            limits,
             use_default_default: _,
         } = options;
 
         let fetcher =
             fetcher.unwrap_or_else(|| Arc::new(citeproc_db::PredefinedLocales::bundled_en_us()));
         let mut db = Processor::safe_default(fetcher);
-        let style = Style::parse_with_opts(
-            &style,
-            csl::ParseOptions {
-                allow_no_info: test_mode,
-                features: csl_features,
-                ..Default::default()
-            },
-        )?;
-        db.set_style_with_durability(Arc::new(style), Durability::HIGH);
-        db.set_output_format(format, format_options);
+        db.accessed_date_policy = accessed_date_policy;
+        db.style_mode = style_mode;
+        db.max_macro_depth = max_macro_depth;
+        let style = match parsed_style {
+            Some(style) => {
+                limits.check_parsed_style(&style)?;
+                style
+                    .check_macros(max_macro_depth)
+                    .map_err(csl::StyleError::from)?;
+                style
+            }
+            None => {
+                limits.check_style(&style)?;
+                StyleCache::global().get_or_parse(
+                    &style,
+                    csl::ParseOptions {
+                        allow_no_info: test_mode,
+                        features: csl_features,
+                        mode: style_mode,
+                        max_macro_depth,
+                        ..Default::default()
+                    },
+                )?
+            }
+        };
+        db.set_style_with_durability(style, Durability::HIGH);
+        db.set_output_format(format, format_options)?;
+        if let Some(custom_format) = custom_format {
+            db.set_custom_output_format(custom_format);
+        }
+        db.user_locale_override = locale_override.clone();
         db.set_default_lang_override_with_durability(locale_override, Durability::HIGH);
         db.set_bibliography_no_sort_with_durability(bibliography_no_sort, Durability::HIGH);
+        db.set_citation_numbering_with_durability(citation_numbering, Durability::HIGH);
+        db.set_name_term_overrides_with_durability(name_term_overrides, Durability::HIGH);
+        db.set_case_exceptions_with_durability(case_exceptions, Durability::HIGH);
+        db.set_compat_mode_with_durability(compat_mode, Durability::HIGH);
+        db.set_reprint_sort_with_durability(reprint_sort, Durability::HIGH);
+        db.set_max_macro_expansions_with_durability(limits.max_macro_expansions, Durability::HIGH);
+        db.limits = limits;
         Ok(db)
     }
 
     /// Sets the output format. Will require nearly everything to be recomputed, so call sparingly.
-    pub fn set_output_format(&mut self, format: SupportedFormat, options: FormatOptions) {
+    ///
+    /// Fails with [Error::UnavailableFormat] if the format was left out of this build, e.g. RTF
+    /// without the `rtf` feature.
+    pub fn set_output_format(
+        &mut self,
+        format: SupportedFormat,
+        options: FormatOptions,
+    ) -> Result<(), Error> {
+        if !format.is_available() {
+            return Err(Error::UnavailableFormat(format));
+        }
         self.format_options = options;
-        let formatter = format.make_markup(options);
+        self.set_formatter_markup(format.make_markup(options));
+        Ok(())
+    }
+
+    /// The options last given to [set_output_format](Processor::set_output_format) or
+    /// [InitOptions].
+    pub fn format_options(&self) -> FormatOptions {
+        self.format_options
+    }
+
+    /// Sets an output format implemented outside citeproc-rs. Like
+    /// [set_output_format](Processor::set_output_format), everything will be recomputed.
+    pub fn set_custom_output_format(&mut self, format: Arc<dyn CustomFormat>) {
+        self.set_formatter_markup(Markup::custom(format));
+    }
+
+    fn set_formatter_markup(&mut self, formatter: Markup) {
         if self.formatter == formatter {
             // Avoid recomputing everything if possible
             return;
         }
         self.formatter = formatter.clone();
         self.set_formatter_with_durability(formatter, Durability::HIGH);
+        self.record_change(|| InputChange::OutputFormat);
+    }
+
+    /// Replaces the style's "and" and et-al terms, taking precedence over the style. Every cite
+    /// with names in it will be recomputed.
+    pub fn set_name_term_overrides(&mut self, overrides: NameTermOverrides) {
+        self.set_name_term_overrides_with_durability(overrides, Durability::HIGH);
+        self.record_change(|| InputChange::Settings);
+    }
+
+    /// Replaces the list of words that text-case transforms leave spelled as given. Every cite is
+    /// recomputed, but only clusters and bibliography entries whose output changed will show up
+    /// in the next batched update.
+    pub fn set_case_exceptions(&mut self, exceptions: CaseExceptions) {
+        self.set_case_exceptions_with_durability(exceptions, Durability::HIGH);
+        self.record_change(|| InputChange::Settings);
+    }
+
+    /// Switches between following the CSL spec and citeproc-js where they disagree. See
+    /// [CompatMode]. Everything will be recomputed.
+    pub fn set_compat_mode(&mut self, mode: CompatMode) {
+        if self.compat_mode() != mode {
+            self.set_compat_mode_with_durability(mode, Durability::HIGH);
+            self.record_change(|| InputChange::Settings);
+        }
+    }
+
+    /// Chooses whether sorting on `issued` uses a reprint's `original-date`. See [ReprintSort].
+    /// Only sorting is recomputed, but that can reorder the bibliography and renumber citations.
+    pub fn set_reprint_sort(&mut self, reprint_sort: ReprintSort) {
+        if self.reprint_sort() != reprint_sort {
+            self.set_reprint_sort_with_durability(reprint_sort, Durability::HIGH);
+            self.record_change(|| InputChange::Settings);
+        }
+    }
+
+    /// Only the macro expansion limit affects anything already rendered. The others apply to what
+    /// is inserted from now on.
+    pub fn set_resource_limits(&mut self, limits: ResourceLimits) {
+        if self.max_macro_expansions() != limits.max_macro_expansions {
+            self.set_max_macro_expansions_with_durability(
+                limits.max_macro_expansions,
+                Durability::HIGH,
+            );
+            self.record_change(|| InputChange::Settings);
+        }
+        self.limits = limits;
+    }
+
+    fn limit_warning(&self, message: String) {
+        self.report_warning(Warning::new(WarningCode::LimitExceeded, message, None));
+    }
+
+    /// Drops the new references in `refs` that would take the library past
+    /// [ResourceLimits::max_references]. Replacing one already in `keys` is always fine.
+    fn within_reference_limit(
+        &self,
+        keys: &IndexSet<Atom>,
+        refs: Vec<Reference>,
+    ) -> Vec<Reference> {
+        let limit = match self.limits.max_references {
+            Some(limit) => limit,
+            None => return refs,
+        };
+        let mut added = FnvHashSet::default();
+        let mut dropped = 0;
+        let refs: Vec<Reference> = refs
+            .into_iter()
+            .filter(|r| {
+                if keys.contains(&r.id) || added.contains(&r.id) {
+                    true
+                } else if keys.len() + added.len() < limit {
+                    added.insert(r.id.clone());
+                    true
+                } else {
+                    dropped += 1;
+                    false
+                }
+            })
+            .collect();
+        if dropped > 0 {
+            self.limit_warning(format!(
+                "{} references left out, as the processor holds at most {}",
+                dropped, limit
+            ));
+        }
+        refs
+    }
+
+    /// Drops cites from the end of a cluster with more than [ResourceLimits::max_cites_per_cluster].
+    fn within_cite_limit<T>(&self, cites: &mut Vec<T>) {
+        if let Some(limit) = self.limits.max_cites_per_cluster {
+            if cites.len() > limit {
+                self.limit_warning(format!(
+                    "{} cites left out of a cluster, as clusters have at most {}",
+                    cites.len() - limit,
+                    limit
+                ));
+                cites.truncate(limit);
+            }
+        }
+    }
+
+    fn parse_options(&self) -> csl::ParseOptions {
+        csl::ParseOptions {
+            mode: self.style_mode,
+            max_macro_depth: self.max_macro_depth,
+            ..Default::default()
+        }
     }
 
     /// Sets the CSL style to be used. Will require nearly everything to be recomputed, so call sparingly.
-    pub fn set_style_text(&mut self, style_text: &str) -> Result<(), StyleError> {
-        let style = Style::parse(style_text)?;
-        self.set_style_with_durability(Arc::new(style), Durability::HIGH);
+    pub fn set_style_text(&mut self, style_text: &str) -> Result<(), Error> {
+        self.limits.check_style(style_text)?;
+        let style = StyleCache::global().get_or_parse(style_text, self.parse_options())?;
+        self.check_jurisdiction_macros(&style, &self.jurisdiction_modules())?;
+        self.set_style_with_durability(style, Durability::HIGH);
+        self.record_change(|| InputChange::Style);
+        self.record_journal(|| JournalEntry::SetStyle {
+            style: style_text.to_owned(),
+        });
         Ok(())
     }
 
+    /// Uses `lang` instead of the style's `default-locale`, or goes back to the style's with
+    /// `None`. Any locales this needs have to be fetched or stored, as with
+    /// [InitOptions::locale_override].
+    pub fn set_locale_override(&mut self, lang: Option<Lang>) {
+        self.user_locale_override = lang.clone();
+        if self.default_lang_override() != lang {
+            self.set_default_lang_override_with_durability(lang, Durability::HIGH);
+            self.record_change(|| InputChange::Locale);
+        }
+    }
+
+    /// Records that the style was chosen via a dependent style, which is reported by
+    /// [`Processor::style_info`], and applies the dependent style's `default-locale` as the locale
+    /// override, unless one was set with [InitOptions::locale_override] or
+    /// [`Processor::set_locale_override`]. The processor's own style should be the independent
+    /// parent it links to.
+    pub fn set_dependent_style(&mut self, dependent: &StyleMeta) {
+        let lang = self
+            .user_locale_override
+            .clone()
+            .or_else(|| dependent.default_locale.clone());
+        self.set_default_lang_override_with_durability(lang, Durability::HIGH);
+        self.dependent_style = Some(Arc::new(dependent.info.clone()));
+        self.record_change(|| InputChange::Locale);
+    }
+
+    /// Describes the style driving the processor, e.g. so an application can show which style
+    /// is really in use after following a dependent style to its parent.
+    pub fn style_info(&self) -> StyleInfo {
+        let style = self.style();
+        StyleInfo {
+            id: style.info.id.to_string(),
+            title: style.info.title.value.clone(),
+            citation_format: style.info.citation_format,
+            class: style.class,
+            default_locale: style.default_locale.clone(),
+            locale_override: self.default_lang_override(),
+            has_bibliography: cfg!(feature = "bibliography") && style.bibliography.is_some(),
+            dependent: self
+                .dependent_style
+                .as_ref()
+                .map(|info| DependentStyleInfo {
+                    id: info.id.to_string(),
+                    title: info.title.value.clone(),
+                    independent_parent: info.independent_parent_id(),
+                }),
+        }
+    }
+
+    /// Adds or replaces the CSL-M style module for a jurisdiction like `us` or `us:ca`. For
+    /// references with that jurisdiction or one beneath it, macros defined in the module take the
+    /// place of the main style's macros of the same name. The most specific module wins.
+    pub fn set_jurisdiction_module(
+        &mut self,
+        jurisdiction: &str,
+        style_text: &str,
+    ) -> Result<(), Error> {
+        self.limits.check_style(style_text)?;
+        let module = StyleCache::global().get_or_parse(style_text, self.parse_options())?;
+        let mut modules = (*self.jurisdiction_modules()).clone();
+        modules.insert(jurisdiction.into(), module);
+        self.check_jurisdiction_macros(&self.style(), &modules)?;
+        self.set_jurisdiction_modules_with_durability(Arc::new(modules), Durability::HIGH);
+        self.record_change(|| InputChange::Style);
+        self.record_journal(|| JournalEntry::SetJurisdictionModule {
+            jurisdiction: jurisdiction.into(),
+            style: style_text.to_owned(),
+        });
+        Ok(())
+    }
+
+    /// Parsing checks each module's macros on their own, but a module's macros take the place
+    /// of the style's, under the style's macros that call them. So check the set each
+    /// jurisdiction actually uses for cycles and depth.
+    fn check_jurisdiction_macros(
+        &self,
+        style: &Style,
+        modules: &FnvHashMap<SmartString, Arc<Style>>,
+    ) -> Result<(), Error> {
+        let mut jurisdictions: Vec<&SmartString> = modules.keys().collect();
+        jurisdictions.sort();
+        for jurisdiction in jurisdictions {
+            let macros = citeproc_proc::merged_macros(style, modules, jurisdiction);
+            csl::check_macro_map(&macros, self.max_macro_depth).map_err(csl::StyleError::from)?;
+        }
+        Ok(())
+    }
+
+    /// Removes a module added with [`Processor::set_jurisdiction_module`].
+    pub fn remove_jurisdiction_module(&mut self, jurisdiction: &str) {
+        let mut modules = (*self.jurisdiction_modules()).clone();
+        if modules.remove(jurisdiction).is_some() {
+            self.set_jurisdiction_modules_with_durability(Arc::new(modules), Durability::HIGH);
+            self.record_change(|| InputChange::Style);
+            self.record_journal(|| JournalEntry::RemoveJurisdictionModule {
+                jurisdiction: jurisdiction.into(),
+            });
+        }
+    }
+
     #[cfg(feature = "rayon")]
     fn snap(&self) -> Snap {
         Snap(self.snapshot())
@@ -261,6 +629,9 @@ impl Processor {
         }
 
         let clusters = self.clusters_cites_sorted();
+        let deadline = self.limits.deadline();
+        let out_of_time = || deadline.map_or(false, |d| Instant::now() >= d);
+        let skipped = AtomicUsize::new(0);
 
         #[cfg(feature = "rayon")]
         let result = {
@@ -279,8 +650,12 @@ impl Processor {
             clusters
                 .par_iter()
                 .map_with(self.snap(), |snap, cluster| {
+                    if out_of_time() {
+                        skipped.fetch_add(1, Ordering::Relaxed);
+                        return None;
+                    }
                     let built = snap.0.built_cluster(cluster.id);
-                    let mut into_hashmap = snap.0.last_clusters.lock().unwrap();
+                    let mut into_hashmap = snap.0.last_clusters.lock();
                     upsert_diff(into_hashmap.deref_mut(), cluster.id, built)
                 })
                 .filter_map(|x| x)
@@ -288,16 +663,28 @@ impl Processor {
         };
         #[cfg(not(feature = "rayon"))]
         let result = {
-            let mut into_hashmap = self.last_clusters.lock().unwrap();
+            let mut into_hashmap = self.last_clusters.lock();
             clusters
                 .iter()
                 .filter_map(|cluster| {
+                    if out_of_time() {
+                        skipped.fetch_add(1, Ordering::Relaxed);
+                        return None;
+                    }
                     let built = self.built_cluster(cluster.id);
                     upsert_diff(&mut into_hashmap, cluster.id, built)
                 })
                 .collect()
         };
 
+        let skipped = skipped.into_inner();
+        if skipped > 0 {
+            self.limit_warning(format!(
+                "ran out of time with {} clusters left, they will be rendered next time",
+                skipped
+            ));
+        }
+
         // Run salsa GC.
         self.sweep_all(SweepStrategy::discard_outdated());
         result
@@ -305,32 +692,173 @@ impl Processor {
 
     pub fn batched_updates(&self) -> UpdateSummary {
         let delta = self.compute();
+        let causes = self.take_changes().map(|changes| {
+            delta
+                .iter()
+                .map(|&(cid, _)| (cid, self.explain_rebuild(&changes, cid)))
+                .collect()
+        });
         UpdateSummary {
             clusters: delta,
             bibliography: self.save_and_diff_bibliography(),
+            causes,
         }
     }
 
     pub fn batched_updates_str(&self) -> string_id::UpdateSummary {
         let delta = self.compute();
+        let changes = self.take_changes();
         let mut delta_str = Vec::with_capacity(delta.len());
-        let interner = self.interner.read().unwrap();
+        let mut causes = changes.as_ref().map(|_| Vec::with_capacity(delta.len()));
+        let interner = self.interner.read();
         for (cid, neu) in delta {
             if let Some(resolved) = interner.resolve(cid) {
+                if let (Some(causes), Some(changes)) = (causes.as_mut(), changes.as_ref()) {
+                    let why = self.explain_rebuild_named(changes, cid, resolved);
+                    causes.push((SmartString::from(resolved), why));
+                }
                 delta_str.push((SmartString::from(resolved), neu));
             }
         }
         string_id::UpdateSummary {
             clusters: delta_str,
             bibliography: self.save_and_diff_bibliography(),
+            causes,
         }
     }
 
     pub fn drain(&mut self) {
         let _ = self.compute();
+        let _ = self.take_changes();
+    }
+
+    /// Turns on recording of input changes, for debugging why clusters were rebuilt. While it is
+    /// on, [`UpdateSummary::causes`] says which recorded changes each updated cluster depends on,
+    /// e.g. "the style" or "reference X". Turning it off discards anything recorded.
+    pub fn set_invalidation_tracing(&mut self, enabled: bool) {
+        self.invalidation_log = if enabled {
+            Some(Arc::new(Mutex::new(Vec::new())))
+        } else {
+            None
+        };
+    }
+
+    fn record_change(&self, change: impl FnOnce() -> InputChange) {
+        if let Some(changes) = &self.invalidation_log {
+            let change = change();
+            log::debug!("input changed: {:?}", change);
+            let mut changes = changes.lock();
+            if !changes.contains(&change) {
+                changes.push(change);
+            }
+        }
+    }
+
+    /// Turns on recording of every edit to the document, so it can be saved with
+    /// [`Processor::journal`] and replayed into another processor. Turning it off discards
+    /// anything recorded.
+    pub fn set_journaling(&mut self, enabled: bool) {
+        self.journal = if enabled {
+            Some(Arc::new(Mutex::new(Vec::new())))
+        } else {
+            None
+        };
+    }
+
+    /// Everything recorded since journaling was turned on. Empty if it is off.
+    pub fn journal(&self) -> Journal {
+        Journal {
+            entries: self
+                .journal
+                .as_ref()
+                .map(|entries| entries.lock().clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn record_journal(&self, entry: impl FnOnce() -> JournalEntry) {
+        if let Some(entries) = &self.journal {
+            let entry = entry();
+            entries.lock().push(entry);
+        }
+    }
+
+    fn journal_cluster(&self, cluster_id: ClusterId) {
+        self.record_journal(|| JournalEntry::ReplaceCluster {
+            cluster: self.cluster_str(cluster_id),
+        });
+    }
+
+    /// A cluster as it is stored, in the form [`Processor::insert_cluster_str`] takes.
+    fn cluster_str(&self, cluster_id: ClusterId) -> string_id::Cluster {
+        string_id::Cluster {
+            id: self.journal_cluster_id(cluster_id),
+            cites: self
+                .cluster_cites(cluster_id)
+                .iter()
+                .map(|cite_id| Cite::clone(&cite_id.lookup(self)))
+                .collect(),
+            mode: self.cluster_mode(cluster_id),
+            lang: self.cluster_langs().get(&cluster_id).cloned(),
+        }
+    }
+
+    /// The string a cluster is journaled and traced under. A [ClusterId] made by hand rather than
+    /// with [`Processor::cluster_id`] has none, so it goes by `#` and its number, which replay
+    /// interns like any other string.
+    fn journal_cluster_id(&self, cluster_id: ClusterId) -> SmartString {
+        self.lookup_cluster_id(cluster_id)
+            .unwrap_or_else(|| SmartString::from(format!("#{}", cluster_id.0)))
+    }
+
+    fn record_cluster_change(&self, cluster_id: ClusterId) {
+        self.record_change(|| InputChange::Cluster(self.journal_cluster_id(cluster_id)));
+    }
+
+    /// The changes recorded since last time, if tracing is on.
+    fn take_changes(&self) -> Option<Vec<InputChange>> {
+        self.invalidation_log
+            .as_ref()
+            .map(|changes| std::mem::take(&mut *changes.lock()))
+    }
+
+    fn explain_rebuild(&self, changes: &[InputChange], cluster_id: ClusterId) -> Vec<InputChange> {
+        let name = self.journal_cluster_id(cluster_id);
+        self.explain_rebuild_named(changes, cluster_id, &name)
+    }
+
+    /// The changes that `cluster_id` depends on directly: global ones, its own cites, and the
+    /// references it cites. If there are none, it must have been rebuilt indirectly, e.g. because
+    /// disambiguation against another reference changed, so all of them are given.
+    fn explain_rebuild_named(
+        &self,
+        changes: &[InputChange],
+        cluster_id: ClusterId,
+        name: &str,
+    ) -> Vec<InputChange> {
+        let cites = self.cluster_cites(cluster_id);
+        let direct: Vec<InputChange> = changes
+            .iter()
+            .filter(|change| match change {
+                InputChange::Reference(id) => cites.iter().any(|c| c.lookup(self).ref_id == *id),
+                InputChange::Cluster(id) => id == name,
+                InputChange::UncitedReferences => false,
+                _ => true,
+            })
+            .cloned()
+            .collect();
+        if direct.is_empty() {
+            changes.to_vec()
+        } else {
+            direct
+        }
     }
 
     pub fn clear_references(&mut self) {
+        for id in self.all_keys().iter() {
+            self.record_change(|| InputChange::Reference(id.clone()));
+        }
+        self.record_journal(|| JournalEntry::ClearReferences);
         self.set_all_keys_with_durability(Arc::new(IndexSet::new()), Durability::MEDIUM);
     }
 
@@ -355,13 +883,13 @@ impl Processor {
     /// ]);
     /// ```
     pub fn cluster_id(&self, string: impl AsRef<str>) -> ClusterId {
-        let mut w = self.interner.write().unwrap();
+        let mut w = self.interner.write();
         w.get_or_intern(string)
     }
 
     /// Returns a random cluster id, with an extra guarantee that it isn't already in use.
     pub fn random_cluster_id_str(&self) -> SmartString {
-        let interner = self.interner.read().unwrap();
+        let interner = self.interner.read();
         loop {
             let smart_string = crate::random_cluster_id();
             if interner.get(&smart_string).is_none() {
@@ -373,12 +901,64 @@ impl Processor {
     /// Returns a random cluster id, with an extra guarantee that it isn't already in use.
     pub fn random_cluster_id(&self) -> ClusterId {
         let rand_id = self.random_cluster_id_str();
-        self.interner.write().unwrap().get_or_intern(rand_id)
+        self.interner.write().get_or_intern(rand_id)
+    }
+
+    /// Every string id this processor has given a [ClusterId], in order, such that the `n`th one
+    /// has `ClusterId(n)`. Save this alongside a document and hand it to
+    /// [`Processor::restore_cluster_ids`] when loading it again, if you keep [ClusterId]s around.
+    pub fn cluster_ids(&self) -> Vec<SmartString> {
+        let interner = self.interner.read();
+        (&*interner)
+            .into_iter()
+            .map(|(_, string)| SmartString::from(string))
+            .collect()
+    }
+
+    /// Gives every string id in `saved`, from [`Processor::cluster_ids`], the same [ClusterId] it
+    /// had when it was saved, so that ids the host kept still point at the right clusters.
+    ///
+    /// Call this on a new processor, before anything else makes cluster ids. Ids that were
+    /// already made are fine as long as they match. Otherwise nothing is changed and the first
+    /// conflicting id is returned.
+    pub fn restore_cluster_ids(&mut self, saved: &[SmartString]) -> Result<(), ClusterIdConflict> {
+        let mut interner = self.interner.write();
+        let existing = interner.len();
+        for (index, id) in saved.iter().enumerate() {
+            let saved_id = ClusterId::new(index as u32);
+            let ok = match interner.get(id) {
+                Some(cluster_id) => cluster_id == saved_id,
+                None => index >= existing,
+            };
+            if !ok {
+                return Err(ClusterIdConflict {
+                    id: id.clone(),
+                    saved: saved_id,
+                });
+            }
+        }
+        for id in saved.iter().skip(existing) {
+            interner.get_or_intern(id);
+        }
+        Ok(())
+    }
+
+    /// Sets the policy for `accessed` dates. It applies to references inserted from now on; call
+    /// [`Processor::reset_references`] again to apply it to the existing ones.
+    pub fn set_accessed_date_policy(&mut self, policy: AccessedDatePolicy) {
+        self.accessed_date_policy = policy;
     }
 
     pub fn reset_references(&mut self, refs: Vec<Reference>) {
+        let refs = self.within_reference_limit(&IndexSet::new(), refs);
         let keys: IndexSet<Atom> = refs.iter().map(|r| r.id.clone()).collect();
-        for r in refs {
+        self.record_journal(|| JournalEntry::ClearReferences);
+        for mut r in refs {
+            self.accessed_date_policy.apply(&mut r);
+            self.record_change(|| InputChange::Reference(r.id.clone()));
+            self.record_journal(|| JournalEntry::InsertReference {
+                reference: r.clone(),
+            });
             self.set_reference_input_with_durability(r.id.clone(), Arc::new(r), Durability::MEDIUM);
         }
         self.set_all_keys_with_durability(Arc::new(keys), Durability::MEDIUM);
@@ -386,9 +966,15 @@ impl Processor {
 
     pub fn extend_references(&mut self, refs: Vec<Reference>) {
         let keys = self.all_keys();
+        let refs = self.within_reference_limit(&keys, refs);
         let mut keys = IndexSet::clone(&keys);
-        for r in refs {
+        for mut r in refs {
+            self.accessed_date_policy.apply(&mut r);
             keys.insert(r.id.clone());
+            self.record_change(|| InputChange::Reference(r.id.clone()));
+            self.record_journal(|| JournalEntry::InsertReference {
+                reference: r.clone(),
+            });
             self.set_reference_input_with_durability(r.id.clone(), Arc::new(r), Durability::MEDIUM);
         }
         self.set_all_keys_with_durability(Arc::new(keys), Durability::MEDIUM);
@@ -396,8 +982,17 @@ impl Processor {
 
     pub fn insert_reference(&mut self, refr: Reference) {
         let keys = self.all_keys();
+        let mut refr = match self.within_reference_limit(&keys, vec![refr]).pop() {
+            Some(refr) => refr,
+            None => return,
+        };
+        self.accessed_date_policy.apply(&mut refr);
         let mut keys = IndexSet::clone(&keys);
         keys.insert(refr.id.clone());
+        self.record_change(|| InputChange::Reference(refr.id.clone()));
+        self.record_journal(|| JournalEntry::InsertReference {
+            reference: refr.clone(),
+        });
         self.set_reference_input_with_durability(
             refr.id.clone(),
             Arc::new(refr),
@@ -406,7 +1001,86 @@ impl Processor {
         self.set_all_keys_with_durability(Arc::new(keys), Durability::MEDIUM);
     }
 
+    /// Like [`Processor::insert_reference`], but first compares `refr` with the reference already
+    /// stored under its id, and leaves the database alone if they are the same. Returns whether
+    /// anything changed. The comparison is made before the [`AccessedDatePolicy`] fills in an
+    /// `accessed` date, so a web reference synced again on a later day is still the same.
+    ///
+    /// Setting a reference, even to an identical one, makes everything that depends on it check
+    /// again, so use this when syncing a whole library where most references haven't changed.
+    pub fn update_reference(&mut self, mut refr: Reference) -> bool {
+        let keys = self.all_keys();
+        let is_new = !keys.contains(&refr.id);
+        if !is_new {
+            let stored = self.reference_input(refr.id.clone());
+            if self.accessed_date_policy.is_unchanged(&refr, &stored) {
+                return false;
+            }
+        }
+        self.accessed_date_policy.apply(&mut refr);
+        if is_new {
+            refr = match self.within_reference_limit(&keys, vec![refr]).pop() {
+                Some(refr) => refr,
+                None => return false,
+            };
+            let mut keys = IndexSet::clone(&keys);
+            keys.insert(refr.id.clone());
+            self.set_all_keys_with_durability(Arc::new(keys), Durability::MEDIUM);
+        }
+        self.record_change(|| InputChange::Reference(refr.id.clone()));
+        self.record_journal(|| JournalEntry::InsertReference {
+            reference: refr.clone(),
+        });
+        self.set_reference_input_with_durability(
+            refr.id.clone(),
+            Arc::new(refr),
+            Durability::MEDIUM,
+        );
+        true
+    }
+
+    /// Serializes the stored references with these ids back to CSL-JSON, as an array in the same
+    /// order. Ids with no stored reference are skipped.
+    ///
+    /// The output is canonical (stable key order, dates as `date-parts`), so two exports of the
+    /// same data compare equal. References are exported as stored, which is after the
+    /// [`AccessedDatePolicy`] has been applied but before anything is derived from them.
+    pub fn export_references(&self, ids: &[Atom]) -> String {
+        let keys = self.all_keys();
+        let refs: Vec<Arc<Reference>> = ids
+            .iter()
+            .filter(|id| keys.contains(*id))
+            .map(|id| self.reference_input(id.clone()))
+            .collect();
+        serde_json::to_string_pretty(&refs).expect("serializing references cannot fail")
+    }
+
+    /// Like [`Processor::export_references`] for a single reference, as one compact CSL-JSON
+    /// object. `None` if there is no reference with this id.
+    pub fn export_reference(&self, id: Atom) -> Option<String> {
+        if !self.all_keys().contains(&id) {
+            return None;
+        }
+        let refr = self.reference_input(id);
+        Some(serde_json::to_string(&*refr).expect("serializing references cannot fail"))
+    }
+
+    /// The ids of all the stored references, in the order they were inserted.
+    pub fn reference_ids(&self) -> Vec<Atom> {
+        self.all_keys().iter().cloned().collect()
+    }
+
+    /// Sets the court classes used to fill in `court-class` for `legal_case` references that
+    /// don't have one, keyed by the court's name as it appears in `authority`. Only styles with
+    /// the `cslm-legal-variables` feature use them.
+    pub fn set_court_classes(&mut self, classes: FnvHashMap<SmartString, SmartString>) {
+        self.set_court_classes_with_durability(Arc::new(classes), Durability::MEDIUM);
+        self.record_change(|| InputChange::Settings);
+    }
+
     pub fn remove_reference(&mut self, id: Atom) {
+        self.record_change(|| InputChange::Reference(id.clone()));
+        self.record_journal(|| JournalEntry::RemoveReference { id: id.clone() });
         let keys = self.all_keys();
         let mut keys = IndexSet::clone(&keys);
         keys.remove(&id);
@@ -422,17 +1096,25 @@ impl Processor {
             }
         };
         self.set_all_uncited_with_durability(Arc::new(db_uncited), Durability::MEDIUM);
+        self.record_change(|| InputChange::UncitedReferences);
     }
 
     pub fn init_clusters(&mut self, clusters: Vec<Cluster>) {
         let mut new_all = FnvHashSet::default();
         new_all.reserve(clusters.len());
+        let mut langs = FnvHashMap::default();
+        let mut order = Vec::with_capacity(clusters.len());
         for cluster in clusters {
             let Cluster {
                 id: cluster_id,
-                cites,
+                mut cites,
                 mode,
+                lang,
             } = cluster;
+            if let Some(lang) = lang {
+                langs.insert(cluster_id, lang);
+            }
+            self.within_cite_limit(&mut cites);
             let mut ids = Vec::with_capacity(cites.len());
             for (index, cite) in cites.into_iter().enumerate() {
                 let cite_id = self.cite(CiteData::RealCite {
@@ -443,25 +1125,37 @@ impl Processor {
                 ids.push(cite_id);
             }
             self.set_cluster_cites(cluster_id, Arc::new(ids));
+            self.record_cluster_change(cluster_id);
             self.set_cluster_note_number(cluster_id, None);
             self.set_cluster_mode(cluster_id, mode);
             new_all.insert(cluster_id);
+            order.push(cluster_id);
         }
         self.set_all_cluster_ids(Arc::new(new_all));
+        self.set_cluster_langs(Arc::new(langs));
+        self.journal_init_clusters(&order);
     }
 
     pub fn init_clusters_str(&mut self, clusters: Vec<string_id::Cluster>) {
         let mut new_all = FnvHashSet::default();
         new_all.reserve(clusters.len());
+        let mut langs = FnvHashMap::default();
+        let mut order = Vec::with_capacity(clusters.len());
         let interner_arc = self.interner.clone();
-        let mut interner = interner_arc.write().unwrap();
+        let mut interner = interner_arc.write();
         for cluster in clusters {
             let string_id::Cluster {
                 id: cluster_id,
-                cites,
+                mut cites,
                 mode,
+                lang,
             } = cluster;
+            let cluster_name = cluster_id.clone();
             let cluster_id = interner.get_or_intern(cluster_id);
+            if let Some(lang) = lang {
+                langs.insert(cluster_id, lang);
+            }
+            self.within_cite_limit(&mut cites);
             let mut ids = Vec::with_capacity(cites.len());
             for (index, cite) in cites.into_iter().enumerate() {
                 let cite_id = self.cite(CiteData::RealCite {
@@ -472,26 +1166,66 @@ impl Processor {
                 ids.push(cite_id);
             }
             self.set_cluster_cites(cluster_id, Arc::new(ids));
+            self.record_change(|| InputChange::Cluster(cluster_name.clone()));
             self.set_cluster_note_number(cluster_id, None);
             self.set_cluster_mode(cluster_id, mode);
             new_all.insert(cluster_id);
+            order.push(cluster_id);
         }
+        drop(interner);
         self.set_all_cluster_ids(Arc::new(new_all));
+        self.set_cluster_langs(Arc::new(langs));
+        self.journal_init_clusters(&order);
+    }
+
+    fn journal_init_clusters(&self, order: &[ClusterId]) {
+        self.record_journal(|| JournalEntry::InitClusters {
+            clusters: order.iter().map(|&id| self.cluster_str(id)).collect(),
+        });
     }
 
     // cluster_ids is maintained manually
     // the cluster_cites relation is maintained manually
 
     pub fn remove_cluster(&mut self, cluster_id: ClusterId) {
+        self.record_cluster_change(cluster_id);
+        self.record_journal(|| JournalEntry::RemoveCluster {
+            id: self.journal_cluster_id(cluster_id),
+        });
         self.set_cluster_cites(cluster_id, Arc::new(Vec::new()));
         self.set_cluster_note_number(cluster_id, None);
         self.set_cluster_mode(cluster_id, None);
+        self.store_cluster_lang(cluster_id, None);
         let all_cluster_ids = self.all_cluster_ids();
         let mut new_all = (*all_cluster_ids).clone();
         new_all.remove(&cluster_id);
         self.set_all_cluster_ids(Arc::new(new_all));
     }
 
+    /// Sets the language a cluster takes its terms from, as with [`Cluster::lang`]. `None` goes
+    /// back to the default locale.
+    pub fn set_cluster_lang(&mut self, cluster_id: ClusterId, lang: Option<Lang>) {
+        if self.store_cluster_lang(cluster_id, lang) {
+            self.journal_cluster(cluster_id);
+        }
+    }
+
+    /// Returns whether the language changed.
+    fn store_cluster_lang(&mut self, cluster_id: ClusterId, lang: Option<Lang>) -> bool {
+        let langs = self.cluster_langs();
+        if langs.get(&cluster_id) == lang.as_ref() {
+            return false;
+        }
+        let mut langs = FnvHashMap::clone(&langs);
+        match lang {
+            Some(lang) => langs.insert(cluster_id, lang),
+            None => langs.remove(&cluster_id),
+        };
+        self.set_cluster_langs(Arc::new(langs));
+        self.record_cluster_change(cluster_id);
+        true
+    }
+
     pub fn remove_cluster_str(&mut self, cluster_id: &str) {
         let cid = self.cluster_id(cluster_id);
         self.remove_cluster(cid);
@@ -511,7 +1245,8 @@ impl Processor {
         }
     }
 
-    fn insert_cites_only(&mut self, cluster_id: ClusterId, cites: Vec<Cite<Markup>>) {
+    fn insert_cites_only(&mut self, cluster_id: ClusterId, mut cites: Vec<Cite<Markup>>) {
+        self.within_cite_limit(&mut cites);
         let mut ids = Vec::new();
         for (index, cite) in cites.into_iter().enumerate() {
             let cite_id = self.cite(CiteData::RealCite {
@@ -522,6 +1257,7 @@ impl Processor {
             ids.push(cite_id);
         }
         self.set_cluster_cites(cluster_id, Arc::new(ids));
+        self.record_cluster_change(cluster_id);
     }
 
     pub fn insert_cluster(&mut self, cluster: Cluster) {
@@ -530,19 +1266,28 @@ impl Processor {
             id: cluster_id,
             cites,
             mode,
+            lang,
         } = cluster;
         self.ensure_cluster_in_all(cluster_id);
         self.insert_cites_only(cluster_id, cites);
         self.set_cluster_mode(cluster_id, mode);
+        self.store_cluster_lang(cluster_id, lang);
+        self.journal_cluster(cluster_id);
     }
 
     fn intern_cluster(&mut self, cluster: string_id::Cluster) -> Cluster {
-        let string_id::Cluster { id, cites, mode } = cluster;
+        let string_id::Cluster {
+            id,
+            cites,
+            mode,
+            lang,
+        } = cluster;
         let interned = self.cluster_id(id);
         Cluster {
             id: interned,
             cites,
             mode,
+            lang,
         }
     }
 
@@ -555,6 +1300,7 @@ impl Processor {
         let cites = cites.to_owned();
         self.ensure_cluster_in_all(cluster_id);
         self.insert_cites_only(cluster_id, cites);
+        self.journal_cluster(cluster_id);
     }
 
     pub fn insert_cites_str(&mut self, cluster_id: &str, cites: &[Cite<Markup>]) {
@@ -562,6 +1308,87 @@ impl Processor {
         self.insert_cites(interned, cites);
     }
 
+    /// Replaces every cluster with citations in the citeproc-js shape, in document order, and
+    /// sets the cluster order from their `noteIndex`es. Citations without a `citationID` are
+    /// given a random one. Returns the cluster ids in the same order.
+    ///
+    /// citeproc-js has no cluster modes, only `author-only` and `suppress-author` on each item,
+    /// so those become [`CiteMode`](citeproc_io::CiteMode)s on the cites.
+    pub fn import_citeproc_js(
+        &mut self,
+        citations: Vec<CiteprocJsCitation>,
+    ) -> Result<Vec<SmartString>, string_id::ReorderingError> {
+        let mut ids = Vec::with_capacity(citations.len());
+        let mut clusters = Vec::with_capacity(citations.len());
+        let mut positions = Vec::with_capacity(citations.len());
+        for citation in citations {
+            let id = citation
+                .citation_id
+                .unwrap_or_else(|| self.random_cluster_id_str());
+            let cites = citation
+                .citation_items
+                .into_iter()
+                .map(CiteprocJsCitationItem::into_cite)
+                .collect();
+            positions.push(match citation.properties.note_index {
+                0 => string_id::ClusterPosition::in_text(id.clone()),
+                note => string_id::ClusterPosition::note(id.clone(), note),
+            });
+            clusters.push(string_id::Cluster::new(id.clone(), cites, None));
+            ids.push(id);
+        }
+        self.init_clusters_str(clusters);
+        self.set_cluster_order_str(&positions)?;
+        Ok(ids)
+    }
+
+    /// The clusters in document order, as citeproc-js citations. Clusters that have not been
+    /// given a position are left out.
+    ///
+    /// An `AuthorOnly` or `SuppressAuthor` cluster mode is written as `author-only` or
+    /// `suppress-author` on the items it applies to. citeproc-js has nothing like `Composite`, so
+    /// it is written as `SuppressAuthor`.
+    pub fn export_citeproc_js(&self) -> Vec<CiteprocJsCitation> {
+        let clusters = self.clusters_ordered();
+        clusters
+            .iter()
+            .map(|&cluster_id| {
+                let mut items: Vec<CiteprocJsCitationItem> = self
+                    .cluster_cites(cluster_id)
+                    .iter()
+                    .map(|cite_id| CiteprocJsCitationItem::from_cite(&cite_id.lookup(self)))
+                    .collect();
+                match self.cluster_mode(cluster_id) {
+                    Some(ClusterMode::AuthorOnly) => items.iter_mut().for_each(|item| {
+                        item.author_only = true;
+                        item.suppress_author = false;
+                    }),
+                    Some(ClusterMode::SuppressAuthor { suppress_first })
+                    | Some(ClusterMode::Composite { suppress_first, .. }) => {
+                        let n = match suppress_first {
+                            0 => items.len(),
+                            n => n as usize,
+                        };
+                        items.iter_mut().take(n).for_each(|item| {
+                            item.suppress_author = true;
+                            item.author_only = false;
+                        })
+                    }
+                    None => {}
+                }
+                let note_index = match self.cluster_note_number(cluster_id) {
+                    Some(ClusterNumber::Note(intra)) => intra.note_number(),
+                    _ => 0,
+                };
+                CiteprocJsCitation {
+                    citation_id: self.lookup_cluster_id(cluster_id),
+                    citation_items: items,
+                    properties: CiteprocJsProperties { note_index },
+                }
+            })
+            .collect()
+    }
+
     // Getters, because the query groups have too much exposed to publish.
 
     /// Returns None if the cluster has not been assigned a position in the document.
@@ -573,6 +1400,130 @@ impl Processor {
         }
     }
 
+    /// Like [`Processor::get_cluster`], but in another output format, with that format's
+    /// [FormatOptions] from this processor. Only the final step of writing out the string is done
+    /// separately for each format, so e.g. a plain text preview next to the HTML is cheap.
+    pub fn get_cluster_in(
+        &self,
+        cluster_id: ClusterId,
+        format: SupportedFormat,
+    ) -> Option<Arc<MarkupOutput>> {
+        self.cluster_note_number(cluster_id)?;
+        let fmt = format.make_markup(self.format_options);
+        Some(citeproc_proc::db::built_cluster_for(self, cluster_id, &fmt))
+    }
+
+    /// Like [`Processor::get_cluster`], but as runs of text with their formatting attributes
+    /// (italic, bold, superscript, link target, etc.), so a UI can build its own attributed string.
+    pub fn get_cluster_runs(&self, cluster_id: ClusterId) -> Option<Vec<TextRun>> {
+        self.cluster_note_number(cluster_id)?;
+        Some(citeproc_proc::db::built_cluster_runs(self, cluster_id))
+    }
+
+    /// Like [`Processor::get_cluster`], but also reports which part of the output each cite
+    /// produced, e.g. so an editor can open the right reference when a citation is clicked. This
+    /// renders the cluster again, so use it when the spans are needed rather than for every update.
+    pub fn get_cluster_with_spans(&self, cluster_id: ClusterId) -> Option<ClusterWithSpans> {
+        let output = self.get_cluster(cluster_id)?;
+        let fmt = self.formatter();
+        let (marked_output, spans) = citeproc_proc::built_cluster_spans(self, cluster_id, &fmt);
+        let cites = self.cluster_cites(cluster_id);
+        let spans = if marked_output == *output {
+            spans
+                .into_iter()
+                .map(|(cite_index, range)| CiteSpan {
+                    cite_index,
+                    ref_id: cites[cite_index].lookup(self).ref_id.clone(),
+                    start: range.start,
+                    end: range.end,
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        Some(ClusterWithSpans { output, spans })
+    }
+
+    /// Finds the cites in the document whose output is identical to that of another reference,
+    /// even after disambiguation, so authors can be warned about them. In document order.
+    pub fn still_ambiguous(&self) -> Vec<AmbiguousCite> {
+        let mut ambiguous = Vec::new();
+        for cluster in self.clusters_sorted().iter() {
+            for (cite_index, &cite_id) in cluster.cites.iter().enumerate() {
+                let others = self.cite_ambiguous_with(cite_id);
+                if others.is_empty() {
+                    continue;
+                }
+                ambiguous.push(AmbiguousCite {
+                    cluster_id: cluster.id,
+                    cite_index,
+                    ref_id: cite_id.lookup(self).ref_id.clone(),
+                    ambiguous_with: others.to_vec(),
+                });
+            }
+        }
+        ambiguous
+    }
+
+    /// Returns the non-fatal problems found while rendering since the last call, such as terms
+    /// missing from the locale or style features that are not supported. Rendering is cached, so
+    /// each is reported once, when the output it affects is first computed; call this after
+    /// fetching that output.
+    pub fn take_warnings(&self) -> Vec<Warning> {
+        let mut warnings = self.warnings.lock();
+        warnings.drain(..).collect()
+    }
+
+    /// Reports which fields of each reference the style renders, and which it ignores, e.g. to
+    /// explain why a reference's `archive` never shows up. The style's `<choose>` branches are
+    /// followed as they would be for each reference. Ids not in the library are skipped.
+    pub fn variable_coverage(&self, ids: &[Atom]) -> Vec<VariableCoverage> {
+        ids.iter()
+            .filter_map(|id| self.reference(id.clone()))
+            .map(|refr| citeproc_proc::variable_coverage(self, &refr))
+            .collect()
+    }
+
+    /// Lists the references that are neither cited anywhere nor included with
+    /// [`Processor::include_uncited`], e.g. to offer to prune them from the library.
+    pub fn unused_references(&self) -> Vec<Atom> {
+        CiteDatabase::unused_references(self).to_vec()
+    }
+
+    /// Lists the clusters that cite `ref_id`, e.g. to show its usages before deleting it. Clusters
+    /// in the document come first, in document order.
+    pub fn clusters_citing(&self, ref_id: Atom) -> Vec<ClusterId> {
+        CiteDatabase::clusters_citing(self, ref_id).to_vec()
+    }
+
+    /// The note numbers, or in-text cluster positions, that cite `ref_id`, for printing "cited on"
+    /// lists under bibliography entries. Follows [`Processor::set_cluster_order`].
+    pub fn back_references(&self, ref_id: Atom) -> Vec<BackReference> {
+        CiteDatabase::back_references(self, ref_id).to_vec()
+    }
+
+    /// Returns None if the cluster has not been assigned a position in the document.
+    pub fn get_cluster_meta(&self, cluster_id: ClusterId) -> Option<ClusterMeta> {
+        use citeproc_io::output::markup::is_punc;
+        self.cluster_note_number(cluster_id)?;
+        let build = (*self.built_cluster_inlines(cluster_id)).clone();
+        let has_block_display = build.iter().any(|el| el.has_display());
+        let piq = citeproc_proc::db::get_piq(self);
+        let plain = Markup::plain().output(build, piq);
+        let ends_with_punctuation = plain
+            .trim_end_matches(|c: char| {
+                matches!(c, '"' | '\'' | '\u{201D}' | '\u{2019}' | '\u{00BB}')
+            })
+            .chars()
+            .next_back()
+            .map_or(false, is_punc);
+        Some(ClusterMeta {
+            plain_len: plain.chars().count(),
+            has_block_display,
+            ends_with_punctuation,
+        })
+    }
+
     pub fn get_cluster_note_number(&self, cluster_id: ClusterId) -> Option<ClusterNumber> {
         self.cluster_note_number(cluster_id)
     }
@@ -587,9 +1538,12 @@ impl Processor {
         self.bib_item(ref_id)
     }
 
+    /// None if the style has no bibliography, or citeproc was built without the `bibliography`
+    /// feature.
     pub fn get_bibliography_meta(&self) -> Option<BibliographyMeta> {
         let style = self.get_style();
-        style.bibliography.as_ref().map(|bib| {
+        let bib = style.bibliography.as_ref();
+        bib.filter(|_| cfg!(feature = "bibliography")).map(|bib| {
             BibliographyMeta {
                 // TODO
                 max_offset: 0,
@@ -607,10 +1561,10 @@ impl Processor {
     }
 
     fn save_and_diff_bibliography(&self) -> Option<BibliographyUpdate> {
-        if self.get_style().bibliography.is_none() {
+        if !cfg!(feature = "bibliography") || self.get_style().bibliography.is_none() {
             return None;
         }
-        let mut last_bibliography = self.last_bibliography.lock().unwrap();
+        let mut last_bibliography = self.last_bibliography.lock();
         let new = self.get_bibliography_map();
         let old = std::mem::replace(&mut *last_bibliography, SavedBib::new());
         let mut update = BibliographyUpdate::new();
@@ -647,7 +1601,7 @@ impl Processor {
 
     pub fn all_clusters_str(&self) -> FnvHashMap<SmartString, Arc<MarkupOutput>> {
         let all_cluster_ids = self.all_cluster_ids();
-        let interner = self.interner.read().unwrap();
+        let interner = self.interner.read();
         let mut mapping = FnvHashMap::default();
         mapping.reserve(all_cluster_ids.len());
         for &cid in all_cluster_ids.iter() {
@@ -668,6 +1622,7 @@ impl Processor {
             .filter_map(|k| bib_map.get(k).map(|v| (k, v)))
             .map(|(k, v)| BibEntry {
                 id: k.clone(),
+                anchor: citeproc_proc::entry_anchor(k),
                 value: if v.is_empty() {
                     Arc::new(SmartString::from(
                         "[CSL STYLE ERROR: reference with no printed form.]",
@@ -679,6 +1634,129 @@ impl Processor {
             .collect()
     }
 
+    /// Splits the bibliography into labelled sections, e.g. primary and secondary sources.
+    ///
+    /// `section_for` gives the label of the section each reference belongs in, or `None` to leave
+    /// it out. Sections appear in the order of their first entry, and entries keep their order
+    /// from [`Processor::get_bibliography`]. To group by reference type, pass
+    /// `|refr| Some(refr.csl_type.as_ref().into())`.
+    pub fn get_bibliography_sections(
+        &self,
+        mut section_for: impl FnMut(&Reference) -> Option<SmartString>,
+    ) -> Vec<BibliographySection> {
+        let mut sections: Vec<BibliographySection> = Vec::new();
+        for entry in self.get_bibliography() {
+            let label = match self.reference(entry.id.clone()) {
+                Some(refr) => section_for(&refr),
+                None => None,
+            };
+            let label = match label {
+                Some(label) => label,
+                None => continue,
+            };
+            match sections.iter_mut().find(|s| s.label == label) {
+                Some(section) => section.entries.push(entry),
+                None => sections.push(BibliographySection {
+                    label,
+                    entries: vec![entry],
+                }),
+            }
+        }
+        sections
+    }
+
+    /// Like [`Processor::get_bibliography`], but only with the references that pass `filter`, e.g.
+    /// to make a per-chapter or per-type bibliography from one set of clusters. Numbering and
+    /// disambiguation still take the whole bibliography into account.
+    pub fn get_bibliography_filtered(&self, filter: &BibliographyFilter) -> Vec<BibEntry> {
+        self.get_bibliography()
+            .into_iter()
+            .filter(|entry| match self.reference(entry.id.clone()) {
+                Some(refr) => filter.matches(&refr),
+                None => false,
+            })
+            .collect()
+    }
+
+    /// Adds host-supplied text before and after one reference's bibliography entry, or removes it
+    /// with `None`. Only that entry is reformatted, and it will show up in the next batched
+    /// update. See [EntryDecoration].
+    pub fn set_entry_decoration(&mut self, ref_id: Atom, decoration: Option<EntryDecoration>) {
+        let current = self.entry_decorations();
+        if current.get(&ref_id) == decoration.as_ref() {
+            return;
+        }
+        let mut decorations = FnvHashMap::clone(&current);
+        match decoration {
+            Some(decoration) => decorations.insert(ref_id, decoration),
+            None => decorations.remove(&ref_id),
+        };
+        self.set_entry_decorations_with_durability(Arc::new(decorations), Durability::MEDIUM);
+        self.record_change(|| InputChange::Settings);
+    }
+
+    /// Replaces every entry decoration at once.
+    pub fn set_entry_decorations(&mut self, decorations: FnvHashMap<Atom, EntryDecoration>) {
+        self.set_entry_decorations_with_durability(Arc::new(decorations), Durability::MEDIUM);
+        self.record_change(|| InputChange::Settings);
+    }
+
+    /// Keeps every citation number assigned so far, even if the order of citations or the
+    /// bibliography changes later. References cited for the first time afterwards are numbered
+    /// after the frozen ones. Call this again whenever numbers should be locked in, e.g. after
+    /// each round of updates for publishers that never renumber.
+    pub fn freeze_citation_numbers(&mut self) {
+        let sorted_refs = self.sorted_refs();
+        let frozen = sorted_refs
+            .1
+            .iter()
+            .map(|(id, bn)| (id.clone(), bn.get()))
+            .collect();
+        self.set_frozen_citation_numbers_with_durability(Arc::new(frozen), Durability::MEDIUM);
+    }
+
+    /// Lets citation numbers be reassigned freely again.
+    pub fn unfreeze_citation_numbers(&mut self) {
+        self.set_frozen_citation_numbers_with_durability(Default::default(), Durability::MEDIUM);
+    }
+
+    /// The keys this processor sorts the bibliography by, as computed for one reference. Empty if
+    /// the style has no bibliography `<sort>` or the reference is not in the bibliography.
+    ///
+    /// Keys that use the citation number give the number the reference ends up with, which is
+    /// not always the one the sort compared; see [citeproc_proc::bib_sort_keys].
+    pub fn sort_key(&self, ref_id: Atom) -> Vec<SortKeyValue> {
+        citeproc_proc::bib_sort_keys(self, ref_id).unwrap_or_default()
+    }
+
+    /// The `<name>` options, et-al settings included, that names rendered for the `key_index`th
+    /// `<key>` of the citation or bibliography `<sort>` start from. A names element in the key's
+    /// macro may set its own options, but the key's `names-min`, `names-use-first` and
+    /// `names-use-last` win over them. `None` if there is no such key.
+    pub fn sort_key_name_options(&self, which: CiteOrBib, key_index: usize) -> Option<csl::Name> {
+        let style = self.style();
+        let (sort, (_delim, inherited)) = match which {
+            CiteOrBib::Citation => (style.citation.sort.as_ref(), style.name_info_citation()),
+            CiteOrBib::Bibliography => (
+                style.bibliography.as_ref()?.sort.as_ref(),
+                style.name_info_bibliography(),
+            ),
+        };
+        let key = sort?.keys.get(key_index)?;
+        Some(key.effective_name_options(&inherited))
+    }
+
+    /// Looks up a term in the default locale, merged with the style's own locale overrides, e.g.
+    /// `get_term("accessed", None, false)` or `get_term("and", Some("symbol"), false)`. `form`
+    /// defaults to long, and falls back as it does in a style. `None` for unknown terms and
+    /// forms, ordinal terms, and terms the locale does not define.
+    pub fn get_term(&self, term: &str, form: Option<&str>, plural: bool) -> Option<String> {
+        let style = self.style();
+        let selector = csl::TextTermSelector::from_term_form(term, form, &style.features)?;
+        let locale = self.default_locale();
+        locale.get_text_term(selector, plural).map(String::from)
+    }
+
     pub fn get_reference(&self, ref_id: Atom) -> Option<Arc<Reference>> {
         self.reference(ref_id)
     }
@@ -687,6 +1765,78 @@ impl Processor {
         self.style()
     }
 
+    /// The reference fields the style could render or sort by for items of `csl_type`, e.g. to
+    /// mark them as needed in a data entry form. See [`Style::requirements_for`].
+    pub fn style_requirements_for(&self, csl_type: CslType) -> Vec<AnyVariable> {
+        self.style().requirements_for(csl_type)
+    }
+
+    /// The style and all the stored references, in the order they were inserted, for saving with
+    /// [`StyleBundle::to_bytes`].
+    pub fn style_bundle(&self) -> StyleBundle {
+        StyleBundle {
+            style: self.style(),
+            references: self
+                .all_keys()
+                .iter()
+                .map(|id| (*self.reference_input(id.clone())).clone())
+                .collect(),
+        }
+    }
+
+    /// Creates a processor with a bundle's style and references. The bundle's style is used
+    /// instead of `options.style`, as if it were passed in [InitOptions::parsed_style].
+    pub fn from_bundle(bundle: StyleBundle, options: InitOptions) -> Result<Self, Error> {
+        let mut db = Processor::new(InitOptions {
+            parsed_style: Some(bundle.style),
+            ..options
+        })?;
+        db.reset_references(bundle.references);
+        Ok(db)
+    }
+
+    /// Creates a processor with a style from a [StylePack], found by name or `<id>` as in
+    /// [`StylePack::get`], instead of `options.style`. A dependent style uses its parent, and its
+    /// default locale if `options` has no `locale_override`, as with
+    /// [`Processor::set_dependent_style`]. Locales come from the pack unless `options` has a
+    /// fetcher.
+    pub fn from_style_pack(
+        bytes: &[u8],
+        style_id: &str,
+        options: InitOptions,
+    ) -> Result<Self, Error> {
+        let pack = StylePack::from_bytes(bytes)?;
+        let packed = pack
+            .get(style_id)
+            .ok_or_else(|| BundleError::NoSuchStyle(style_id.to_owned()))?;
+        let style = pack.load_style(packed)?;
+        let fetcher: Arc<dyn LocaleFetcher> = match &options.fetcher {
+            Some(fetcher) => fetcher.clone(),
+            None => Arc::new(pack.locales()),
+        };
+        let dependent = packed.is_dependent();
+        let user_locale_override = options.locale_override.clone();
+        let locale_override = if dependent {
+            options
+                .locale_override
+                .clone()
+                .or_else(|| packed.default_locale.clone())
+        } else {
+            options.locale_override.clone()
+        };
+        let mut db = Processor::new(InitOptions {
+            parsed_style: Some(style),
+            fetcher: Some(fetcher),
+            locale_override,
+            ..options
+        })?;
+        if dependent {
+            db.dependent_style = Some(Arc::new(packed.info.clone()));
+        }
+        db.user_locale_override = user_locale_override;
+        Ok(db)
+    }
+
     pub fn store_locales(&mut self, locales: Vec<(Lang, String)>) {
         let mut langs = (*self.locale_input_langs()).clone();
         for (lang, xml) in locales {
@@ -694,6 +1844,7 @@ impl Processor {
             self.set_locale_input_xml_with_durability(lang, Arc::new(xml), Durability::HIGH);
         }
         self.set_locale_input_langs(Arc::new(langs));
+        self.record_change(|| InputChange::Locale);
     }
 
     pub fn get_langs_in_use(&self) -> Vec<Lang> {
@@ -704,10 +1855,210 @@ impl Processor {
         vec
     }
 
+    /// Fetches every locale this document could need and stores them, so nothing has to be
+    /// fetched lazily later. That is everything in
+    /// [`required_locales`](CiteDatabase::required_locales). With the `rayon` feature, the
+    /// fetcher is called for all of them concurrently.
+    ///
+    /// Locales already stored are skipped. Returns the languages the fetcher had nothing for.
+    pub fn prefetch_locales(&mut self) -> Vec<Lang> {
+        let mut langs = Vec::clone(&self.required_locales());
+        langs.retain(|lang| !self.has_cached_locale(lang));
+
+        let fetcher = &self.fetcher;
+        let fetch = |lang: Lang| match fetcher.fetch_string(&lang) {
+            Ok(xml) => (lang, xml),
+            Err(e) => {
+                log::warn!("citeproc-rs: failed to fetch locale {}: {:?}", lang, e);
+                (lang, None)
+            }
+        };
+        #[cfg(feature = "rayon")]
+        let fetched: Vec<(Lang, Option<String>)> = {
+            use rayon::prelude::*;
+            langs.into_par_iter().map(fetch).collect()
+        };
+        #[cfg(not(feature = "rayon"))]
+        let fetched: Vec<(Lang, Option<String>)> = langs.into_iter().map(fetch).collect();
+
+        let mut missing = Vec::new();
+        let mut found = Vec::new();
+        for (lang, xml) in fetched {
+            match xml {
+                Some(xml) => found.push((lang, xml)),
+                None => missing.push(lang),
+            }
+        }
+        self.store_locales(found);
+        missing
+    }
+
     pub fn has_cached_locale(&self, lang: &Lang) -> bool {
         let langs = self.locale_input_langs();
         langs.contains(lang)
     }
+
+    /// Asks the fetcher for any locales not stored via `store_locales` again, the next time they
+    /// are needed. Use this when the fetcher has learned about new locales since they were last
+    /// requested.
+    pub fn refetch_locales(&mut self) {
+        // Fetched locales are not salsa inputs, so re-setting the input they are read alongside is
+        // what invalidates them.
+        let langs = self.locale_input_langs();
+        self.set_locale_input_langs_with_durability(langs, Durability::HIGH);
+    }
+}
+
+/// The document's references, clusters and cluster order at one point in time, from
+/// [`Processor::checkpoint`].
+///
+/// Checkpoints share the processor's own `Arc`s rather than copying references and cites, so
+/// taking one before every edit is cheap. Cites are kept rather than their interned ids, which the
+/// processor may discard and reuse once no cluster has them.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    all_keys: Arc<IndexSet<Atom>>,
+    /// One for each of `all_keys`, in the same order.
+    references: Vec<Arc<Reference>>,
+    all_cluster_ids: Arc<FnvHashSet<ClusterId>>,
+    clusters: Vec<SavedCluster>,
+    cluster_langs: Arc<FnvHashMap<ClusterId, Lang>>,
+    clusters_ordered: Arc<Vec<ClusterId>>,
+}
+
+#[derive(Debug, Clone)]
+struct SavedCluster {
+    id: ClusterId,
+    cites: Vec<Arc<Cite<Markup>>>,
+    mode: Option<ClusterMode>,
+    note_number: Option<ClusterNumber>,
+}
+
+impl Processor {
+    /// Saves the references, clusters and cluster order, to go back to with
+    /// [`Processor::revert_to`]. For undo, take one before each edit the user makes and keep them
+    /// on the editor's undo stack; for redo, take one before reverting.
+    ///
+    /// The style and settings are not saved.
+    pub fn checkpoint(&self) -> Checkpoint {
+        let all_keys = self.all_keys();
+        let references = all_keys
+            .iter()
+            .map(|id| self.reference_input(id.clone()))
+            .collect();
+        let all_cluster_ids = self.all_cluster_ids();
+        let clusters = all_cluster_ids
+            .iter()
+            .map(|&id| SavedCluster {
+                id,
+                cites: self
+                    .cluster_cites(id)
+                    .iter()
+                    .map(|cite_id| cite_id.lookup(self))
+                    .collect(),
+                mode: self.cluster_mode(id),
+                note_number: self.cluster_note_number(id),
+            })
+            .collect();
+        Checkpoint {
+            all_keys,
+            references,
+            all_cluster_ids,
+            clusters,
+            cluster_langs: self.cluster_langs(),
+            clusters_ordered: self.clusters_ordered(),
+        }
+    }
+
+    /// Puts the references, clusters and cluster order back the way they were at `checkpoint`,
+    /// and returns the clusters and bibliography that changed as a result, as
+    /// [`Processor::batched_updates`] does.
+    ///
+    /// Only what differs from the checkpoint is set, so anything the edits since then did not
+    /// touch is not recomputed.
+    pub fn revert_to(&mut self, checkpoint: &Checkpoint) -> UpdateSummary {
+        let current_keys = self.all_keys();
+        for (id, refr) in checkpoint.all_keys.iter().zip(&checkpoint.references) {
+            if current_keys.contains(id) && Arc::ptr_eq(&self.reference_input(id.clone()), refr) {
+                continue;
+            }
+            self.record_change(|| InputChange::Reference(id.clone()));
+            self.record_journal(|| JournalEntry::InsertReference {
+                reference: Reference::clone(refr),
+            });
+            self.set_reference_input_with_durability(id.clone(), refr.clone(), Durability::MEDIUM);
+        }
+        for id in current_keys.difference(&checkpoint.all_keys) {
+            self.record_change(|| InputChange::Reference(id.clone()));
+            self.record_journal(|| JournalEntry::RemoveReference { id: id.clone() });
+        }
+        if !Arc::ptr_eq(&current_keys, &checkpoint.all_keys) {
+            self.set_all_keys_with_durability(checkpoint.all_keys.clone(), Durability::MEDIUM);
+        }
+
+        let current_ids = self.all_cluster_ids();
+        for &id in current_ids.difference(&checkpoint.all_cluster_ids) {
+            self.remove_cluster(id);
+        }
+        let current_langs = self.cluster_langs();
+        if !Arc::ptr_eq(&current_langs, &checkpoint.cluster_langs) {
+            self.set_cluster_langs(checkpoint.cluster_langs.clone());
+        }
+        for saved in &checkpoint.clusters {
+            let id = saved.id;
+            let existed = current_ids.contains(&id);
+            let mut changed = current_langs.get(&id) != checkpoint.cluster_langs.get(&id);
+            // Interning the same cites again gives the same ids if they are still in use
+            let cites: Vec<CiteId> = saved
+                .cites
+                .iter()
+                .enumerate()
+                .map(|(index, cite)| {
+                    self.cite(CiteData::RealCite {
+                        cluster: id,
+                        index: index as u32,
+                        cite: cite.clone(),
+                    })
+                })
+                .collect();
+            if !existed || *self.cluster_cites(id) != cites {
+                self.set_cluster_cites(id, Arc::new(cites));
+                changed = true;
+            }
+            if !existed || self.cluster_mode(id) != saved.mode {
+                self.set_cluster_mode(id, saved.mode.clone());
+                changed = true;
+            }
+            if !existed || self.cluster_note_number(id) != saved.note_number {
+                self.set_cluster_note_number(id, saved.note_number);
+            }
+            if changed {
+                self.record_cluster_change(id);
+                self.journal_cluster(id);
+            }
+        }
+        if !Arc::ptr_eq(&self.all_cluster_ids(), &checkpoint.all_cluster_ids) {
+            self.set_all_cluster_ids(checkpoint.all_cluster_ids.clone());
+        }
+        let current_order = self.clusters_ordered();
+        if !Arc::ptr_eq(&current_order, &checkpoint.clusters_ordered) {
+            // Clusters coming back into the document have to be reported even if their output is
+            // what it was when they left.
+            let mut last_clusters = self.last_clusters.lock();
+            for id in checkpoint.clusters_ordered.iter() {
+                if !current_order.contains(id) {
+                    last_clusters.remove(id);
+                }
+            }
+            drop(last_clusters);
+            self.set_clusters_ordered(checkpoint.clusters_ordered.clone());
+            self.record_change(|| InputChange::ClusterOrder);
+            self.record_journal(|| JournalEntry::SetClusterOrder {
+                positions: self.positions_str(&self.current_cluster_order()),
+            });
+        }
+        self.batched_updates()
+    }
 }
 
 /// Stores all the relevant #[salsa::input] entries from CiteDatabase.
@@ -777,7 +2128,8 @@ impl Processor {
     /// The position must be to either replace a single cluster, or to supply a complete document
     /// re-ordering with exactly one id set to 0. If you supply a PreviewPosition::MarkWithZero
     /// with only one position total, then it is as if the document only has one cluster. Prefer
-    /// generating a complete reordering, with one position edited or inserted.
+    /// generating a complete reordering, with one position edited or inserted. To see the cites
+    /// in a particular position, such as ibid, set [`PreviewCluster::position_override`].
     ///
     /// Format defaults (if None) to the processor's native format, but may be set to another
     /// format. Note this is output only, so any disambiguation specialisation for a particular
@@ -791,6 +2143,21 @@ impl Processor {
         position: PreviewPosition<'a>,
         format: Option<SupportedFormat>,
     ) -> Result<Arc<MarkupOutput>, ReorderingError> {
+        let formatter = format
+            .map(|fmt| fmt.make_markup(self.format_options))
+            .unwrap_or_else(|| self.get_formatter());
+        self.with_preview_cluster(preview_cluster, position, |db, id| {
+            citeproc_proc::db::built_cluster_preview(db, id, &formatter)
+        })
+    }
+
+    /// Inserts the preview cluster, runs `f` against it, and restores the previous cluster state.
+    fn with_preview_cluster<'a, T>(
+        &mut self,
+        preview_cluster: PreviewCluster,
+        position: PreviewPosition<'a>,
+        f: impl FnOnce(&Self, ClusterId) -> T,
+    ) -> Result<T, ReorderingError> {
         let (id, state) = match position {
             PreviewPosition::ReplaceCluster(cluster_id) => {
                 let ids = self.all_cluster_ids();
@@ -822,17 +2189,21 @@ impl Processor {
         self.insert_cites_only(id, preview_cluster.cites);
         self.set_cluster_mode(id, preview_cluster.mode);
         // we do set_cluster_note_number in preview_marked_init
+        let position_override = preview_cluster.position_override;
+        if let Some(position) = position_override {
+            self.set_position_override(Some((id, position)));
+        }
 
-        let formatter = format
-            .map(|fmt| fmt.make_markup(self.format_options))
-            .unwrap_or_else(|| self.get_formatter());
-        let markup = citeproc_proc::db::built_cluster_preview(self, id, &formatter);
+        let result = f(self, id);
         let cluster_cites_sorted = self.cluster_cites_sorted(id);
         let nn = self.cluster_note_number(id);
         log::debug!("cluster_cites_sorted: {:?}", cluster_cites_sorted);
         log::debug!("cluster_note_number: {:?}", nn);
+        if position_override.is_some() {
+            self.set_position_override(None);
+        }
         self.restore_cluster_state(state);
-        Ok(markup)
+        Ok(result)
     }
 
     pub fn preview_reference(
@@ -840,7 +2211,6 @@ impl Processor {
         mut refr: Reference,
         format: Option<SupportedFormat>,
     ) -> SmartString {
-        const PREVIEW_REFERENCE_ID: &'static str = "REFERENCE-2b4e3fe4429cb";
         let preview_ref_id = Atom::from(PREVIEW_REFERENCE_ID);
         refr.id = preview_ref_id.clone();
         let arc = Arc::new(refr);
@@ -851,6 +2221,110 @@ impl Processor {
         citeproc_proc::bib_item_preview(self, preview_ref_id.clone(), arc.as_ref(), &formatter)
     }
 
+    /// Like [`Processor::preview_reference`], but without storing the reference in the processor,
+    /// so it can be called on a [Snapshot].
+    pub fn preview_reference_read_only(
+        &self,
+        refr: &Reference,
+        format: Option<SupportedFormat>,
+    ) -> SmartString {
+        let formatter = format
+            .map(|fmt| fmt.make_markup(self.format_options))
+            .unwrap_or_else(|| self.get_formatter().clone());
+        citeproc_proc::bib_item_preview(self, Atom::from(PREVIEW_REFERENCE_ID), refr, &formatter)
+    }
+
+    /// Renders the style's macro `name` for a reference on its own, e.g. just its authors or its
+    /// date. `context` picks whether it renders as in a first cite or in the bibliography, which
+    /// matters for `<if context="...">` and for inherited name options. Nothing is disambiguated
+    /// against other references.
+    ///
+    /// `None` if there is no such reference or macro, or the macro renders nothing.
+    pub fn render_macro(
+        &self,
+        name: &str,
+        ref_id: Atom,
+        context: CiteOrBib,
+    ) -> Option<SmartString> {
+        let refr = self.get_reference(ref_id)?;
+        citeproc_proc::macro_preview(self, name, &refr, context, &self.get_formatter())
+    }
+
+    /// A reference's authors, formatted as in a first cite of it, e.g. for a "Doe et al." chip in
+    /// an editor. The style's et-al and initialization settings for the citation are used, taken
+    /// from the first `<names variable="author">` in it, without its affixes or label.
+    ///
+    /// `max` overrides the style's et-al settings, showing at most that many names (and at least
+    /// one) before "et al.". `None` if there is no such reference or it has no authors.
+    pub fn author_list(&self, ref_id: Atom, max: Option<u32>) -> Option<SmartString> {
+        let refr = self.get_reference(ref_id)?;
+        citeproc_proc::author_list_preview(self, &refr, max, &self.get_formatter())
+    }
+
+    /// Formats each of `ids` on its own, as if it were the only citation in the document
+    /// (`CiteOrBib::Citation`) or the first entry in the bibliography (`CiteOrBib::Bibliography`).
+    /// No clusters are left behind, and the document's clusters are not affected.
+    ///
+    /// There is one string per id, in the same order. A missing reference renders as `???` in a
+    /// citation, and as an empty string in the bibliography, as does every item if the style has
+    /// no bibliography.
+    pub fn format_references(&mut self, ids: &[Atom], mode: CiteOrBib) -> Vec<String> {
+        let formatter = self.get_formatter();
+        ids.iter()
+            .map(|id| match mode {
+                CiteOrBib::Citation => {
+                    let preview = PreviewCluster::new(vec![Cite::basic(id.clone())], None);
+                    let position = [ClusterPosition::preview_in_text()];
+                    // Exactly one preview marker, so this never fails
+                    self.preview_citation_cluster(
+                        preview,
+                        PreviewPosition::MarkWithZero(&position),
+                        None,
+                    )
+                    .map(|built| built.to_string())
+                    .unwrap_or_default()
+                }
+                CiteOrBib::Bibliography => match self.get_reference(id.clone()) {
+                    Some(refr) => {
+                        citeproc_proc::bib_item_preview(self, id.clone(), &refr, &formatter)
+                            .to_string()
+                    }
+                    None => String::new(),
+                },
+            })
+            .collect()
+    }
+
+    /// Formats a citation of one reference, as if it were the only citation in the document, as
+    /// HTML, RTF and plain text at once. The citation is only built once, and then written out
+    /// in each format.
+    ///
+    /// Any disambiguation that depends on the processor's own output format is kept in all three,
+    /// as with [`Processor::preview_citation_cluster`].
+    pub fn formatted_bundle(&mut self, ref_id: Atom) -> FormattedBundle {
+        let preview = PreviewCluster::new(vec![Cite::basic(ref_id)], None);
+        let position = [ClusterPosition::preview_in_text()];
+        // Exactly one preview marker, so this never fails
+        let build = self
+            .with_preview_cluster(
+                preview,
+                PreviewPosition::MarkWithZero(&position),
+                |db, id| citeproc_proc::built_cluster_before_output(db, id, &db.get_formatter()),
+            )
+            .unwrap_or_default();
+        let piq = citeproc_proc::db::get_piq(self);
+        let options = self.format_options;
+        FormattedBundle {
+            html: Markup::Html(options.html).output(build.clone(), piq),
+            rtf: if SupportedFormat::Rtf.is_available() {
+                Markup::Rtf(options.rtf).output(build.clone(), piq)
+            } else {
+                SmartString::new()
+            },
+            plain: Markup::Plain(options.plain).output(build, piq),
+        }
+    }
+
     fn preview_marked_init<'a>(
         &mut self,
         positions: &[ClusterPosition],
@@ -875,6 +2349,118 @@ impl Processor {
 // static PREVIEW_CLUSTER_ID: &'static str = "PREVIEW-7b2b4e3fe4429cb";
 
 impl Processor {
+    /// The current document order, as it would have been passed to
+    /// [`Processor::set_cluster_order`].
+    fn current_cluster_order(&self) -> Vec<ClusterPosition> {
+        self.clusters_ordered()
+            .iter()
+            .filter(|&&id| id != self.preview_cluster_id)
+            .map(|&id| ClusterPosition {
+                id: Some(id),
+                note: match self.cluster_note_number(id) {
+                    Some(ClusterNumber::Note(intra)) => Some(intra.note_number()),
+                    _ => None,
+                },
+            })
+            .collect()
+    }
+
+    /// Combines clusters that are next to each other in the document into the first of them, for
+    /// when a user deletes the text between two citations. The cites keep their order and
+    /// locators, and the other clusters are removed from the document.
+    ///
+    /// All of the clusters must already have positions from [`Processor::set_cluster_order`], and
+    /// be given in document order.
+    pub fn merge_clusters(&mut self, ids: &[ClusterId]) -> Result<ClusterId, ReorderingError> {
+        let (&first, rest) = ids.split_first().ok_or(ReorderingError::NoClusters)?;
+        let order = self.clusters_ordered();
+        let start = order
+            .iter()
+            .position(|&id| id == first)
+            .ok_or(ReorderingError::NonExistentCluster(first))?;
+        for (i, &id) in ids.iter().enumerate() {
+            if order.get(start + i) != Some(&id) {
+                return Err(if order.contains(&id) {
+                    ReorderingError::NonAdjacentCluster(id)
+                } else {
+                    ReorderingError::NonExistentCluster(id)
+                });
+            }
+        }
+        let cites: Vec<Cite<Markup>> = ids
+            .iter()
+            .flat_map(|&id| self.cluster_cites(id).to_vec())
+            .map(|cite_id| Cite::clone(&cite_id.lookup(self)))
+            .collect();
+        let mut positions = self.current_cluster_order();
+        positions.retain(|pos| !pos.id.map_or(false, |id| rest.contains(&id)));
+        for &id in rest {
+            self.remove_cluster(id);
+        }
+        self.insert_cites_only(first, cites);
+        self.journal_cluster(first);
+        self.set_cluster_order(&positions)?;
+        Ok(first)
+    }
+
+    /// String-id version of [`Processor::merge_clusters`]. The clusters are merged into the first
+    /// id.
+    pub fn merge_clusters_str(&mut self, ids: &[&str]) -> Result<(), string_id::ReorderingError> {
+        let ids: Vec<ClusterId> = ids.iter().map(|id| self.cluster_id(id)).collect();
+        self.merge_clusters(&ids).map(|_| ()).map_err(|e| {
+            let reader = self.interner.read();
+            e.to_external(&reader)
+        })
+    }
+
+    /// Divides a cluster in two before the cite at `at_cite_index`, for when a user breaks up a
+    /// citation. The original cluster keeps the cites before that index and its mode; a new
+    /// cluster with a random id and no mode gets the rest, and is placed straight after it in the
+    /// same note. Returns the ids of both halves.
+    ///
+    /// The cluster must already have a position from [`Processor::set_cluster_order`].
+    pub fn split_cluster(
+        &mut self,
+        id: ClusterId,
+        at_cite_index: usize,
+    ) -> Result<(ClusterId, ClusterId), ReorderingError> {
+        if !self.clusters_ordered().contains(&id) {
+            return Err(ReorderingError::NonExistentCluster(id));
+        }
+        let cite_ids = self.cluster_cites(id);
+        if at_cite_index == 0 || at_cite_index >= cite_ids.len() {
+            return Err(ReorderingError::SplitOutOfRange(id, at_cite_index));
+        }
+        let mut cites: Vec<Cite<Markup>> = cite_ids
+            .iter()
+            .map(|cite_id| Cite::clone(&cite_id.lookup(self)))
+            .collect();
+        let second_cites = cites.split_off(at_cite_index);
+        let second = self.random_cluster_id();
+        let mut positions = self.current_cluster_order();
+        let index = positions
+            .iter()
+            .position(|pos| pos.id == Some(id))
+            .ok_or(ReorderingError::NonExistentCluster(id))?;
+        let note = positions[index].note;
+        positions.insert(
+            index + 1,
+            ClusterPosition {
+                id: Some(second),
+                note,
+            },
+        );
+        self.insert_cites_only(id, cites);
+        self.journal_cluster(id);
+        // Both halves keep the cluster's language. Stored first, so that it is journaled with the
+        // new cluster's cites.
+        let lang = self.cluster_langs().get(&id).cloned();
+        self.store_cluster_lang(second, lang);
+        self.insert_cites(second, &second_cites);
+        self.set_cluster_order(&positions)?;
+        Ok((id, second))
+    }
+
     /// Specifies which clusters are actually considered to be in the document, and sets their
     /// order. You may insert as many clusters as you like, but the ones provided here are the only
     /// ones used.
@@ -902,18 +2488,32 @@ impl Processor {
         &mut self,
         positions: &[ClusterPosition],
     ) -> Result<(), ReorderingError> {
-        self.set_cluster_order_inner(positions.iter(), |_, _| {})
+        self.set_cluster_order_inner(positions.iter(), |_, _| {})?;
+        self.record_journal(|| JournalEntry::SetClusterOrder {
+            positions: self.positions_str(positions),
+        });
+        Ok(())
+    }
+
+    fn positions_str(&self, positions: &[ClusterPosition]) -> Vec<string_id::ClusterPosition> {
+        positions
+            .iter()
+            .map(|pos| string_id::ClusterPosition {
+                id: pos.id.map(|id| self.journal_cluster_id(id)),
+                note: pos.note,
+            })
+            .collect()
     }
 
     pub fn set_cluster_order_str(
         &mut self,
         positions: &[string_id::ClusterPosition],
     ) -> Result<(), string_id::ReorderingError> {
-        let positions = positions.iter().map({
+        let interned = positions.iter().map({
             // Move a clone of the arc into the iterator.
             let interner = self.interner.clone();
             move |pos| {
-                let mut interner = interner.write().unwrap();
+                let mut interner = interner.write();
                 let string_id::ClusterPosition { id, note } = pos;
                 let interned_id = id.as_ref().map(|id| interner.get_or_intern(id));
                 ClusterPosition {
@@ -922,11 +2522,15 @@ impl Processor {
                 }
             }
         });
-        self.set_cluster_order_inner(positions, |_, _| {})
+        self.set_cluster_order_inner(interned, |_, _| {})
             .map_err(|e| {
-                let reader = self.interner.read().unwrap();
+                let reader = self.interner.read();
                 e.to_external(&reader)
-            })
+            })?;
+        self.record_journal(|| JournalEntry::SetClusterOrder {
+            positions: positions.to_vec(),
+        });
+        Ok(())
     }
 
     /// Variant of the above that allows logging the changes.
@@ -1008,6 +2612,7 @@ impl Processor {
         }
         // This removes any clusters that did not appear.
         self.set_clusters_ordered(Arc::new(new_clusters_ordered));
+        self.record_change(|| InputChange::ClusterOrder);
         Ok(())
     }
 }
@@ -0,0 +1,107 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright © 2021 Corporation for Digital Scholarship
+
+use csl::{Features, ParseOptions, Style, StyleError, StyleMode};
+use fnv::FnvHashMap;
+use parking_lot::Mutex;
+use std::sync::{Arc, Weak};
+
+/// A cache of parsed styles, so that processors created with the same style text share one
+/// `Arc<Style>` instead of parsing it again.
+///
+/// Entries are keyed by the style text and the parse options, and only hold weak references; a
+/// style is dropped as soon as the last processor using it is.
+///
+/// [`Processor::new`](crate::Processor::new) and
+/// [`Processor::set_style_text`](crate::Processor::set_style_text) both go through
+/// [`StyleCache::global`].
+pub struct StyleCache {
+    map: Mutex<Option<FnvHashMap<CacheKey, Weak<Style>>>>,
+}
+
+/// The whole text is kept, not just a hash of it, so two styles can never be mistaken for each
+/// other.
+#[derive(PartialEq, Eq, Hash)]
+struct CacheKey {
+    text: Box<str>,
+    allow_no_info: bool,
+    features: Option<Features>,
+    mode: StyleMode,
+    max_macro_depth: Option<usize>,
+}
+
+static GLOBAL: StyleCache = StyleCache::new();
+
+impl Default for StyleCache {
+    fn default() -> Self {
+        StyleCache::new()
+    }
+}
+
+impl StyleCache {
+    pub const fn new() -> Self {
+        StyleCache {
+            map: parking_lot::const_mutex(None),
+        }
+    }
+
+    /// The process-wide cache.
+    pub fn global() -> &'static StyleCache {
+        &GLOBAL
+    }
+
+    fn key(text: &str, options: &ParseOptions) -> CacheKey {
+        CacheKey {
+            text: text.into(),
+            allow_no_info: options.allow_no_info,
+            features: options.features.clone(),
+            mode: options.mode,
+            max_macro_depth: options.max_macro_depth,
+        }
+    }
+
+    /// Returns the cached style for this text and options, or parses and caches it.
+    ///
+    /// Errors are not cached.
+    pub fn get_or_parse(&self, text: &str, options: ParseOptions) -> Result<Arc<Style>, StyleError> {
+        let key = Self::key(text, &options);
+        if let Some(style) = self.get(&key) {
+            return Ok(style);
+        }
+        // Parse without holding the lock, parsing a big style takes a while.
+        let style = Arc::new(Style::parse_with_opts(text, options)?);
+        let mut guard = self.map.lock();
+        let map = guard.get_or_insert_with(Default::default);
+        // Someone else may have parsed it in the meantime. Prefer theirs so it is still shared.
+        if let Some(existing) = map.get(&key).and_then(Weak::upgrade) {
+            return Ok(existing);
+        }
+        map.retain(|_, weak| weak.strong_count() > 0);
+        map.insert(key, Arc::downgrade(&style));
+        Ok(style)
+    }
+
+    fn get(&self, key: &CacheKey) -> Option<Arc<Style>> {
+        let guard = self.map.lock();
+        guard.as_ref()?.get(key)?.upgrade()
+    }
+
+    /// The number of styles currently cached and still in use.
+    pub fn len(&self) -> usize {
+        let guard = self.map.lock();
+        guard.as_ref().map_or(0, |map| {
+            map.values().filter(|weak| weak.strong_count() > 0).count()
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        *self.map.lock() = None;
+    }
+}
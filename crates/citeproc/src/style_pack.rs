@@ -0,0 +1,282 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright © 2021 Corporation for Digital Scholarship
+
+//! Many styles and locales, checked and parsed ahead of time and compressed into one file, for
+//! applications that ship a whole style repository.
+//!
+//! Build one with a [StylePackBuilder], e.g. from a checkout of the CSL styles and locales
+//! repositories (`cargo run --example style_pack` does this), and start a processor with
+//! [`Processor::from_style_pack`].
+//!
+//! Each style is compressed on its own, so loading one style out of thousands only decompresses
+//! the index and that style. Dependent styles are stored as their `<info>` and default locale,
+//! and load their independent parent from the same pack.
+//!
+//! The format is a header like a [StyleBundle](crate::StyleBundle)'s, the length of the index,
+//! the index, and then the styles. Everything after the header is CBOR compressed with DEFLATE.
+
+use crate::api::Error;
+use crate::bundle::{read_header, write_header, BundleError};
+use citeproc_db::PredefinedLocales;
+use csl::{Info, Lang, Locale, ParseOptions, Style, StyleError, StyleMeta};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+const MAGIC: &[u8; 4] = b"CSLP";
+
+/// The format of a style pack. It goes up whenever the serialized form of [Style] changes, and
+/// packs written with any other version are refused.
+pub const STYLE_PACK_VERSION: u16 = 1;
+
+const COMPRESSION_LEVEL: u8 = 6;
+
+/// The most an index or a style may inflate to, so a small corrupt or malicious pack cannot
+/// allocate without bound. Far more than the whole CSL styles repository's index needs.
+const MAX_INFLATED_BYTES: usize = 64 << 20;
+
+/// One style in a [StylePack].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackedStyle {
+    /// What the style was added as, usually its file name without `.csl`.
+    pub name: String,
+    pub info: Info,
+    pub default_locale: Option<Lang>,
+    /// Where the compressed style is, after the index. `None` for a dependent style.
+    range: Option<(usize, usize)>,
+}
+
+impl PackedStyle {
+    pub fn is_dependent(&self) -> bool {
+        self.info.parent.is_some()
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Index {
+    styles: Vec<PackedStyle>,
+    locales: BTreeMap<Lang, String>,
+}
+
+/// Checks, parses and compresses styles and locales into a [StylePack].
+#[derive(Default)]
+pub struct StylePackBuilder {
+    options: ParseOptions,
+    index: Index,
+    data: Vec<u8>,
+}
+
+impl StylePackBuilder {
+    /// Styles are parsed with these options, e.g. to accept CSL-M styles.
+    pub fn new(options: ParseOptions) -> Self {
+        StylePackBuilder {
+            options,
+            ..Default::default()
+        }
+    }
+
+    /// Adds a style, independent or dependent, or replaces the one added with the same name.
+    pub fn add_style(&mut self, name: impl Into<String>, xml: &str) -> Result<(), StyleError> {
+        let name = name.into();
+        let meta = StyleMeta::parse(xml)?;
+        let compressed = if meta.is_dependent() {
+            None
+        } else {
+            let style = Style::parse_with_opts(xml, self.options.clone())?;
+            let cbor = serde_cbor::to_vec(&style).expect("serializing a style cannot fail");
+            Some(miniz_oxide::deflate::compress_to_vec(
+                &cbor,
+                COMPRESSION_LEVEL,
+            ))
+        };
+        let existing = self.index.styles.iter().position(|s| s.name == name);
+        if let Some(index) = existing {
+            let old = self.index.styles.remove(index);
+            if let Some((start, end)) = old.range {
+                self.remove_data(start, end);
+            }
+        }
+        let range = compressed.map(|compressed| {
+            let start = self.data.len();
+            self.data.extend_from_slice(&compressed);
+            (start, self.data.len())
+        });
+        let packed = PackedStyle {
+            name,
+            info: meta.info,
+            default_locale: meta.default_locale,
+            range,
+        };
+        match existing {
+            Some(index) => self.index.styles.insert(index, packed),
+            None => self.index.styles.push(packed),
+        }
+        Ok(())
+    }
+
+    /// Drops a replaced style's data, and moves the styles after it down to fill the gap.
+    fn remove_data(&mut self, start: usize, end: usize) {
+        self.data.drain(start..end);
+        let len = end - start;
+        for style in &mut self.index.styles {
+            if let Some((s, e)) = style.range.as_mut() {
+                if *s >= end {
+                    *s -= len;
+                    *e -= len;
+                }
+            }
+        }
+    }
+
+    /// Adds a locale, or replaces the one for the same language.
+    pub fn add_locale(&mut self, lang: Lang, xml: String) -> Result<(), StyleError> {
+        Locale::parse(&xml)?;
+        self.index.locales.insert(lang, xml);
+        Ok(())
+    }
+
+    /// Adds every `*.csl` style in `dir` under its file name, and every `locales-xx-XX.xml`
+    /// locale. Subdirectories such as `dependent/` are included. Files that fail to parse are
+    /// skipped and returned, so one broken style does not hold up the rest.
+    pub fn add_dir(&mut self, dir: impl AsRef<Path>) -> io::Result<Vec<(PathBuf, StyleError)>> {
+        let mut failed = Vec::new();
+        for path in files_in(dir.as_ref())? {
+            let (stem, ext) = match (path.file_stem(), path.extension()) {
+                (Some(stem), Some(ext)) => (
+                    stem.to_string_lossy().into_owned(),
+                    ext.to_string_lossy().into_owned(),
+                ),
+                _ => continue,
+            };
+            let result = if ext == "csl" {
+                let xml = std::fs::read_to_string(&path)?;
+                self.add_style(stem, &xml)
+            } else if ext == "xml" && stem.starts_with("locales-") {
+                match Lang::from_str(&stem["locales-".len()..]) {
+                    Ok(lang) => {
+                        let xml = std::fs::read_to_string(&path)?;
+                        self.add_locale(lang, xml)
+                    }
+                    Err(_) => continue,
+                }
+            } else {
+                continue;
+            };
+            if let Err(e) = result {
+                failed.push((path, e));
+            }
+        }
+        Ok(failed)
+    }
+
+    pub fn build(self) -> Vec<u8> {
+        let index = serde_cbor::to_vec(&self.index).expect("serializing an index cannot fail");
+        let index = miniz_oxide::deflate::compress_to_vec(&index, COMPRESSION_LEVEL);
+        let mut bytes = Vec::with_capacity(12 + index.len() + self.data.len());
+        write_header(&mut bytes, MAGIC, STYLE_PACK_VERSION);
+        bytes.extend_from_slice(&(index.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&index);
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+}
+
+/// A style pack, read from the bytes a [StylePackBuilder] made. Styles are decompressed and
+/// deserialized only when asked for.
+pub struct StylePack<'a> {
+    index: Index,
+    data: &'a [u8],
+}
+
+impl<'a> StylePack<'a> {
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, Error> {
+        let rest =
+            read_header(bytes, MAGIC, STYLE_PACK_VERSION).ok_or(BundleError::NotAStylePack)??;
+        if rest.len() < 4 {
+            return Err(BundleError::NotAStylePack.into());
+        }
+        let index_len = u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        let rest = &rest[4..];
+        if rest.len() < index_len {
+            return Err(BundleError::Decompress.into());
+        }
+        let index = inflate(&rest[..index_len])?;
+        let index = serde_cbor::from_slice(&index).map_err(BundleError::from)?;
+        Ok(StylePack {
+            index,
+            data: &rest[index_len..],
+        })
+    }
+
+    /// Every style in the pack, in the order they were added.
+    pub fn styles(&self) -> &[PackedStyle] {
+        &self.index.styles
+    }
+
+    /// Finds a style by the name it was added as, or by its `<id>`.
+    pub fn get(&self, name_or_id: &str) -> Option<&PackedStyle> {
+        let styles = &self.index.styles;
+        styles
+            .iter()
+            .find(|s| s.name == name_or_id)
+            .or_else(|| styles.iter().find(|s| s.info.id.to_string() == name_or_id))
+    }
+
+    /// Decompresses an independent style. For a dependent style, this is its parent.
+    pub fn load_style(&self, packed: &PackedStyle) -> Result<Arc<Style>, Error> {
+        let independent = match packed.info.parent.as_ref() {
+            Some(parent) => {
+                let parent = parent.href.to_string();
+                self.get(&parent)
+                    .filter(|p| !p.is_dependent())
+                    .ok_or_else(|| BundleError::MissingParent {
+                        style: packed.name.clone(),
+                        parent,
+                    })?
+            }
+            None => packed,
+        };
+        let (start, end) = independent.range.ok_or(BundleError::Decompress)?;
+        let compressed = self.data.get(start..end).ok_or(BundleError::Decompress)?;
+        let cbor = inflate(compressed)?;
+        let style: Style = serde_cbor::from_slice(&cbor).map_err(BundleError::from)?;
+        Ok(Arc::new(style))
+    }
+
+    /// The pack's locales, with the bundled en-US added if the pack has none for it.
+    pub fn locales(&self) -> PredefinedLocales {
+        let mut locales = PredefinedLocales::bundled_en_us();
+        for (lang, xml) in &self.index.locales {
+            locales.0.insert(lang.clone(), xml.clone());
+        }
+        locales
+    }
+}
+
+/// Every file in `dir` and its subdirectories, sorted within each directory.
+pub(crate) fn files_in(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<_>>()?;
+    entries.sort();
+    let mut files = Vec::with_capacity(entries.len());
+    for path in entries {
+        if path.is_dir() {
+            files.extend(files_in(&path)?);
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+fn inflate(compressed: &[u8]) -> Result<Vec<u8>, BundleError> {
+    miniz_oxide::inflate::decompress_to_vec_with_limit(compressed, MAX_INFLATED_BYTES)
+        .map_err(|_| BundleError::Decompress)
+}
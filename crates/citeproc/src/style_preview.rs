@@ -0,0 +1,169 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright © 2021 Corporation for Digital Scholarship
+
+//! Live previews for a style editor: a fixed set of sample references, cited and listed in a
+//! bibliography, re-rendered as the style is edited.
+//!
+//! The editor keeps the style's text here, and can replace the whole style or patch one macro.
+//! An edit that does not parse is kept as the [source](StylePreview::source), so the error's
+//! ranges point into it, while the preview goes on rendering with the last style that parsed.
+//! Macro edits are made to that last good style, so one bad edit does not block the next.
+//! The references are stored once, so an edit only costs formatting them again.
+
+use crate::api::{ClusterPosition, Error, Warning};
+use crate::processor::{InitOptions, Processor};
+use citeproc_db::ClusterId;
+use citeproc_io::{Cite, Reference};
+use csl::StyleError;
+
+/// A style being edited, and its output for some sample references.
+///
+/// ```
+/// use citeproc::prelude::*;
+/// use citeproc::StylePreview;
+///
+/// let style = r#"<style class="in-text">
+///     <macro name="title"><text variable="title" /></macro>
+///     <citation><layout><text macro="title" /></layout></citation>
+/// </style>"#;
+/// let mut refr = Reference::empty("smith".into(), csl::CslType::Book);
+/// refr.ordinary.insert(csl::Variable::Title, "Title".into());
+/// let options = InitOptions { style, test_mode: true, ..Default::default() };
+/// let mut preview = StylePreview::new(options, vec![refr]).unwrap();
+/// assert_eq!(preview.render().citations, vec!["Title"]);
+///
+/// preview.set_macro("title", r#"<text variable="title" text-case="uppercase" />"#).unwrap();
+/// assert_eq!(preview.render().citations, vec!["TITLE"]);
+/// ```
+pub struct StylePreview {
+    processor: Processor,
+    source: String,
+    /// The last style that parsed, which `set_macro` edits.
+    parsed: String,
+    clusters: Vec<ClusterId>,
+}
+
+/// The output of a [StylePreview].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreviewRender {
+    /// One citation for each sample reference, cited on its own, in order.
+    pub citations: Vec<String>,
+    /// Empty if the style has no bibliography.
+    pub bibliography: Vec<String>,
+    /// Problems found while rendering, such as missing terms, since the last render.
+    pub warnings: Vec<Warning>,
+}
+
+impl StylePreview {
+    /// Each reference is cited in a footnote of its own, in order, so note styles show their
+    /// first-cite forms.
+    pub fn new(options: InitOptions, references: Vec<Reference>) -> Result<Self, Error> {
+        let source = options.style.to_owned();
+        let mut processor = Processor::new(options)?;
+        let mut clusters = Vec::with_capacity(references.len());
+        let mut positions = Vec::with_capacity(references.len());
+        for (i, refr) in references.iter().enumerate() {
+            let id = processor.cluster_id(format!("preview-{}", i));
+            processor.insert_cites(id, &[Cite::basic(refr.id.clone())]);
+            clusters.push(id);
+            positions.push(ClusterPosition::note(id, i as u32 + 1));
+        }
+        processor.reset_references(references);
+        processor.set_cluster_order(&positions)?;
+        Ok(StylePreview {
+            processor,
+            parsed: source.clone(),
+            source,
+            clusters,
+        })
+    }
+
+    /// The style as last edited, which may not parse.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The processor rendering the preview, e.g. to check the style it is using.
+    pub fn processor(&self) -> &Processor {
+        &self.processor
+    }
+
+    /// Replaces the whole style. If it does not parse, the preview keeps the previous style.
+    pub fn set_style(&mut self, style: &str) -> Result<(), Error> {
+        self.source = style.to_owned();
+        self.processor.set_style_text(style)?;
+        self.parsed = self.source.clone();
+        Ok(())
+    }
+
+    /// Replaces the contents of `<macro name="...">` with `content`, or adds the macro before
+    /// `<citation>` if there is none by that name. The rest of the style's text is untouched.
+    ///
+    /// The macro is spliced into the last style that parsed, so if the current
+    /// [source](StylePreview::source) is a broken `set_style`, that edit is dropped.
+    pub fn set_macro(&mut self, name: &str, content: &str) -> Result<(), Error> {
+        let source = splice_macro(&self.parsed, name, content)?;
+        self.set_style(&source)
+    }
+
+    /// Formats every sample citation and the bibliography with the current style, and takes
+    /// the warnings raised since the last render.
+    pub fn render(&self) -> PreviewRender {
+        let citations = self
+            .clusters
+            .iter()
+            .map(|&id| {
+                self.processor
+                    .get_cluster(id)
+                    .map(|built| built.to_string())
+                    .unwrap_or_default()
+            })
+            .collect();
+        let bibliography = self
+            .processor
+            .get_bibliography()
+            .into_iter()
+            .map(|entry| entry.value.to_string())
+            .collect();
+        PreviewRender {
+            citations,
+            bibliography,
+            warnings: self.processor.take_warnings(),
+        }
+    }
+}
+
+fn splice_macro(source: &str, name: &str, content: &str) -> Result<String, StyleError> {
+    let doc = roxmltree::Document::parse(source)?;
+    let root = doc.root_element();
+    let is = |node: &roxmltree::Node, tag: &str| node.is_element() && node.tag_name().name() == tag;
+    let existing = root
+        .children()
+        .find(|n| is(n, "macro") && n.attribute("name") == Some(name));
+    let range = match existing {
+        Some(node) => node.range(),
+        None => {
+            let start = match root.children().find(|n| is(n, "citation")) {
+                Some(citation) => citation.range().start,
+                None => root.last_child().map_or(source.len(), |n| n.range().end),
+            };
+            start..start
+        }
+    };
+    let escaped = name
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('"', "&quot;");
+    let mut spliced = String::with_capacity(source.len() + content.len());
+    spliced.push_str(&source[..range.start]);
+    spliced.push_str("<macro name=\"");
+    spliced.push_str(&escaped);
+    spliced.push_str("\">");
+    spliced.push_str(content);
+    spliced.push_str("</macro>");
+    spliced.push_str(&source[range.end..]);
+    Ok(spliced)
+}
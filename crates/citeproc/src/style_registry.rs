@@ -0,0 +1,256 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright © 2021 Corporation for Digital Scholarship
+
+//! An index of the `<info>` of many styles, for building a style picker.
+//!
+//! Fill a [StyleRegistry] from a directory of `.csl` files or from a [StylePack], then search it
+//! with a [StyleQuery]. Only each style's `<info>` and default locale are read, so indexing a
+//! whole style repository is quick; load the style you pick with
+//! [`Processor::from_style_pack`](crate::Processor::from_style_pack) or from its
+//! [path](StyleEntry::path). To find a journal's style, use [`StyleRegistry::for_issn`], which
+//! also finds the independent parent of the journal's dependent style.
+
+use crate::style_pack::{files_in, StylePack};
+use csl::{Category, CitationFormat, Info, Lang, StyleError, StyleMeta};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// One style in a [StyleRegistry].
+#[derive(Debug, Clone, PartialEq)]
+pub struct StyleEntry {
+    /// The file name without `.csl`, or the style's name in a style pack.
+    pub name: String,
+    pub info: Info,
+    pub default_locale: Option<Lang>,
+    /// The file it was read from, if it came from a directory.
+    pub path: Option<PathBuf>,
+}
+
+impl StyleEntry {
+    pub fn is_dependent(&self) -> bool {
+        self.info.parent.is_some()
+    }
+
+    fn has_issn(&self, normalized: &str) -> bool {
+        let info = &self.info;
+        let mut issns = info.issn.iter().chain(&info.eissn).chain(&info.issnl);
+        issns.any(|x| normalize_issn(x) == normalized)
+    }
+}
+
+/// A style found in a [StyleRegistry], and the independent style that does its formatting.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResolvedStyle<'a> {
+    /// The style that was asked for, often a journal's dependent style.
+    pub style: &'a StyleEntry,
+    /// The same as `style` if it is independent, otherwise its parent.
+    pub independent: &'a StyleEntry,
+}
+
+impl<'a> ResolvedStyle<'a> {
+    /// The locale to format with, unless the user picked one. A dependent style's default
+    /// locale overrides its parent's.
+    pub fn default_locale(&self) -> Option<&'a Lang> {
+        self.style
+            .default_locale
+            .as_ref()
+            .or_else(|| self.independent.default_locale.as_ref())
+    }
+}
+
+/// What to search a [StyleRegistry] for. Every field that is set must match.
+///
+/// ```
+/// use citeproc::StyleQuery;
+///
+/// let query = StyleQuery {
+///     title: Some("chicago author".into()),
+///     field: Some(csl::Category::History),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StyleQuery {
+    /// Words that must all appear in the title or the short title, ignoring case.
+    pub title: Option<String>,
+    /// Matches the `issn`, `eissn` or `issnl`, ignoring hyphens and case.
+    pub issn: Option<String>,
+    /// From `<category field="...">`.
+    pub field: Option<Category>,
+    /// From `<category citation-format="...">`.
+    pub citation_format: Option<CitationFormat>,
+    /// Leaves out dependent styles.
+    pub independent_only: bool,
+}
+
+impl StyleQuery {
+    pub fn matches(&self, entry: &StyleEntry) -> bool {
+        let info = &entry.info;
+        if self.independent_only && entry.is_dependent() {
+            return false;
+        }
+        if let Some(field) = self.field {
+            if !info.categories.contains(&field) {
+                return false;
+            }
+        }
+        if let Some(format) = self.citation_format {
+            if info.citation_format != Some(format) {
+                return false;
+            }
+        }
+        if let Some(issn) = self.issn.as_ref() {
+            if !entry.has_issn(&normalize_issn(issn)) {
+                return false;
+            }
+        }
+        if let Some(title) = self.title.as_ref() {
+            let full = info.title.value.to_lowercase();
+            let short = info
+                .title_short
+                .as_ref()
+                .map(|s| s.value.to_lowercase())
+                .unwrap_or_default();
+            let all_found = title
+                .to_lowercase()
+                .split_whitespace()
+                .all(|word| full.contains(word) || short.contains(word));
+            if !all_found {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+pub(crate) fn normalize_issn(issn: &str) -> String {
+    issn.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// The styles an application has available, searchable by their `<info>`.
+#[derive(Debug, Clone, Default)]
+pub struct StyleRegistry {
+    entries: Vec<StyleEntry>,
+}
+
+impl StyleRegistry {
+    pub fn new() -> Self {
+        StyleRegistry::default()
+    }
+
+    /// Adds a style, or replaces the one with the same name.
+    pub fn insert(&mut self, entry: StyleEntry) {
+        match self.entries.iter_mut().find(|e| e.name == entry.name) {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+    }
+
+    /// Reads the `<info>` of a style, independent or dependent, and adds it.
+    pub fn add_style(
+        &mut self,
+        name: impl Into<String>,
+        xml: &str,
+        path: Option<PathBuf>,
+    ) -> Result<(), StyleError> {
+        let meta = StyleMeta::parse(xml)?;
+        self.insert(StyleEntry {
+            name: name.into(),
+            info: meta.info,
+            default_locale: meta.default_locale,
+            path,
+        });
+        Ok(())
+    }
+
+    /// Adds every `*.csl` style in `dir` and its subdirectories, named after its file. Styles
+    /// that fail to parse are skipped and returned.
+    pub fn add_dir(&mut self, dir: impl AsRef<Path>) -> io::Result<Vec<(PathBuf, StyleError)>> {
+        let mut failed = Vec::new();
+        for path in files_in(dir.as_ref())? {
+            if path.extension().map_or(true, |ext| ext != "csl") {
+                continue;
+            }
+            let name = match path.file_stem() {
+                Some(stem) => stem.to_string_lossy().into_owned(),
+                None => continue,
+            };
+            let xml = std::fs::read_to_string(&path)?;
+            if let Err(e) = self.add_style(name, &xml, Some(path.clone())) {
+                failed.push((path, e));
+            }
+        }
+        Ok(failed)
+    }
+
+    /// Adds every style in a style pack.
+    pub fn add_pack(&mut self, pack: &StylePack) {
+        for packed in pack.styles() {
+            self.insert(StyleEntry {
+                name: packed.name.clone(),
+                info: packed.info.clone(),
+                default_locale: packed.default_locale.clone(),
+                path: None,
+            });
+        }
+    }
+
+    /// Every style, in the order they were added.
+    pub fn entries(&self) -> &[StyleEntry] {
+        &self.entries
+    }
+
+    /// Finds a style by its name or its `<id>`.
+    pub fn get(&self, name_or_id: &str) -> Option<&StyleEntry> {
+        self.entries
+            .iter()
+            .find(|e| e.name == name_or_id)
+            .or_else(|| {
+                self.entries
+                    .iter()
+                    .find(|e| e.info.id.to_string() == name_or_id)
+            })
+    }
+
+    /// Follows a dependent style to its independent parent. `None` if the parent is not in the
+    /// registry, or is itself dependent, which CSL does not allow.
+    pub fn resolve<'a>(&'a self, entry: &'a StyleEntry) -> Option<ResolvedStyle<'a>> {
+        let independent = match entry.info.parent.as_ref() {
+            Some(parent) => self
+                .get(&parent.href.to_string())
+                .filter(|p| !p.is_dependent())?,
+            None => entry,
+        };
+        Some(ResolvedStyle {
+            style: entry,
+            independent,
+        })
+    }
+
+    /// The style for a journal, by its ISSN, e-ISSN or ISSN-L, with or without the hyphen.
+    ///
+    /// Journals usually have a dependent style naming the journal's ISSNs, so this is normally
+    /// that dependent style and its parent. Styles whose parent is missing are passed over, and
+    /// of the rest, the one added first wins.
+    pub fn for_issn(&self, issn: &str) -> Option<ResolvedStyle> {
+        let issn = normalize_issn(issn);
+        self.entries
+            .iter()
+            .filter(|e| e.has_issn(&issn))
+            .find_map(|e| self.resolve(e))
+    }
+
+    /// The styles matching `query`, sorted by title.
+    pub fn search(&self, query: &StyleQuery) -> Vec<&StyleEntry> {
+        let mut found: Vec<&StyleEntry> =
+            self.entries.iter().filter(|e| query.matches(e)).collect();
+        found.sort_by_cached_key(|e| e.info.title.value.to_lowercase());
+        found
+    }
+}
@@ -101,11 +101,13 @@ mod position {
                 id: one,
                 cites: vec![Cite::basic("one")],
                 mode: None,
+                lang: None,
             },
             Cluster {
                 id: two,
                 cites: vec![Cite::basic("one")],
                 mode: None,
+                lang: None,
             },
         ]);
         db.set_cluster_order(&ordering(one, two)).unwrap();
@@ -297,6 +299,58 @@ mod preview {
         assert_cluster!(db.get_cluster(one), Some("Book one"));
         assert_cluster!(db.get_cluster(two), Some("Book two"));
     }
+
+    #[test]
+    fn preview_cluster_position_override() {
+        let mut db = mk_db();
+        let one = cid(&mut db, 1);
+        let preview = db.preview_citation_cluster(
+            PreviewCluster::new(vec![Cite::basic("one")], None).with_position(Position::Ibid),
+            PreviewPosition::ReplaceCluster(one),
+            None,
+        );
+        assert_cluster!(preview, Ok("Book one, ibid"));
+        assert_cluster!(db.get_cluster(one), Some("Book one"));
+
+        let json = r#"{ "cites": [{ "id": "three" }], "positionOverride": "subsequent" }"#;
+        let preview: PreviewCluster = serde_json::from_str(json).unwrap();
+        assert_eq!(preview.position_override, Some(Position::Subsequent));
+        let positions = &[ClusterPosition::preview_note(1)];
+        let preview =
+            db.preview_citation_cluster(preview, PreviewPosition::MarkWithZero(positions), None);
+        assert_cluster!(preview, Ok("Book three, subsequent"));
+        let bad = r#"{ "cites": [], "positionOverride": "sometimes" }"#;
+        assert!(serde_json::from_str::<PreviewCluster>(bad).is_err());
+        let internal = r#"{ "cites": [], "positionOverride": "ibid-near" }"#;
+        assert!(serde_json::from_str::<PreviewCluster>(internal).is_err());
+    }
+
+    #[test]
+    fn preview_cluster_position_override_near() {
+        let mut db = test_db(Some(
+            r#"<style class="note" version="1.0.1">
+                <citation>
+                    <layout>
+                        <text variable="title" />
+                        <choose>
+                            <if position="near-note"><text value=", near" /></if>
+                        </choose>
+                    </layout>
+                </citation>
+            </style>"#,
+        ));
+        insert_basic_refs(&mut db, &["one"]);
+        insert_ascending_notes(&mut db, &["one"]);
+        let one = cid(&mut db, 1);
+        for &position in &[Position::Ibid, Position::IbidWithLocator] {
+            let preview = db.preview_citation_cluster(
+                PreviewCluster::new(vec![Cite::basic("one")], None).with_position(position),
+                PreviewPosition::ReplaceCluster(one),
+                None,
+            );
+            assert_cluster!(preview, Ok("Book one, near"));
+        }
+    }
 }
 
 mod terms {
@@ -416,4 +470,3218 @@ mod terms {
             Some("USA"),
         )
     }
+
+    #[test]
+    fn prefetch_locales() {
+        let fr_fr = Lang::Iso(IsoLang::French, Some(IsoCountry::FR));
+        let fetcher = predefined_xml(&[
+            (Lang::en_us(), r#"<term name="and">and</term>"#),
+            (fr_fr.clone(), r#"<term name="and">et</term>"#),
+        ]);
+        let mut db = Processor::new(InitOptions {
+            style: r#"<style version="1.0" class="in-text">
+                <locale xml:lang="en-AU"><terms><term name="ibid">idem</term></terms></locale>
+                <citation><layout></layout></citation>
+            </style>"#,
+            fetcher: Some(Arc::new(fetcher)),
+            test_mode: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let mut refr = Reference::empty("r1".into(), CslType::Book);
+        refr.language = Some(fr_fr.clone());
+        db.insert_reference(refr);
+        let required = db.required_locales();
+        assert!(required.contains(&fr_fr));
+        assert!(required.contains(&en_au()));
+        let missing = db.prefetch_locales();
+        assert!(db.has_cached_locale(&Lang::en_us()));
+        assert!(db.has_cached_locale(&fr_fr));
+        assert!(missing.contains(&en_au()));
+        assert!(!missing.contains(&fr_fr));
+        // everything it could get is stored now
+        assert_eq!(db.prefetch_locales(), missing);
+    }
+
+    #[test]
+    fn cluster_lang() {
+        let de_de = Lang::Iso(IsoLang::Deutsch, Some(IsoCountry::DE));
+        let fetcher = predefined_xml(&[
+            (Lang::en_us(), r#"<term name="and">and</term>"#),
+            (de_de.clone(), r#"<term name="and">und</term>"#),
+        ]);
+        let mut db = Processor::new(InitOptions {
+            style: r#"<style version="1.0" class="note">
+                <citation><layout><text term="and" /></layout></citation>
+            </style>"#,
+            format: SupportedFormat::Plain,
+            fetcher: Some(Arc::new(fetcher)),
+            test_mode: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let one = db.cluster_id("one");
+        let two = db.cluster_id("two");
+        db.insert_cluster(Cluster::new(one, vec![Cite::basic("r1")], None));
+        db.insert_cluster(Cluster {
+            lang: Some(de_de.clone()),
+            ..Cluster::new(two, vec![Cite::basic("r1")], None)
+        });
+        db.set_cluster_order(&[ClusterPosition::note(one, 1), ClusterPosition::note(two, 2)])
+            .unwrap();
+        assert!(!db.prefetch_locales().contains(&de_de));
+        assert!(db.has_cached_locale(&de_de));
+        assert_cluster!(db.get_cluster(one), Some("and"));
+        assert_cluster!(db.get_cluster(two), Some("und"));
+        db.set_cluster_lang(two, None);
+        assert_cluster!(db.get_cluster(two), Some("and"));
+    }
+
+    #[test]
+    fn split_cluster_keeps_lang() {
+        let de_de = Lang::Iso(IsoLang::Deutsch, Some(IsoCountry::DE));
+        let fetcher = predefined_xml(&[
+            (Lang::en_us(), r#"<term name="and">and</term>"#),
+            (de_de.clone(), r#"<term name="and">und</term>"#),
+        ]);
+        let mut db = Processor::new(InitOptions {
+            style: r#"<style version="1.0" class="note">
+                <citation><layout delimiter="; "><text term="and" /></layout></citation>
+            </style>"#,
+            format: SupportedFormat::Plain,
+            fetcher: Some(Arc::new(fetcher)),
+            test_mode: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let one = db.cluster_id("one");
+        db.insert_cluster(Cluster {
+            lang: Some(de_de.clone()),
+            ..Cluster::new(one, vec![Cite::basic("r1"), Cite::basic("r2")], None)
+        });
+        db.set_cluster_order(&[ClusterPosition::note(one, 1)])
+            .unwrap();
+        assert_cluster!(db.get_cluster(one), Some("und; und"));
+        db.set_journaling(true);
+        let (_, second) = db.split_cluster(one, 1).unwrap();
+        assert_cluster!(db.get_cluster(one), Some("und"));
+        assert_cluster!(db.get_cluster(second), Some("und"));
+        let second_name = db.lookup_cluster_id(second).unwrap();
+        let journaled = db.journal().entries.into_iter().any(|entry| match entry {
+            JournalEntry::ReplaceCluster { cluster } => {
+                cluster.id == second_name && cluster.lang == Some(de_de.clone())
+            }
+            _ => false,
+        });
+        assert!(journaled);
+    }
+}
+
+mod pool {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct CountingFetcher(AtomicUsize);
+
+    impl LocaleFetcher for CountingFetcher {
+        fn fetch_string(&self, lang: &Lang) -> Result<Option<String>, LocaleFetchError> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            PredefinedLocales::bundled_en_us().fetch_string(lang)
+        }
+    }
+
+    fn open(pool: &mut ProcessorPool, id: &str) {
+        pool.open(
+            id,
+            InitOptions {
+                style: r#"<style version="1.0" class="in-text">
+                    <citation><layout><text term="and" /></layout></citation>
+                </style>"#,
+                format: SupportedFormat::Plain,
+                test_mode: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let db = pool.get_mut(id).unwrap();
+        let one = db.cluster_id("one");
+        db.insert_cites(one, &[Cite::basic("r1")]);
+        db.set_cluster_order(&[ClusterPosition::in_text(one)])
+            .unwrap();
+    }
+
+    #[test]
+    fn shares_fetched_locales() {
+        let counter = Arc::new(CountingFetcher(AtomicUsize::new(0)));
+        let mut pool = ProcessorPool::new(counter.clone());
+        open(&mut pool, "a");
+        open(&mut pool, "b");
+        let updates = pool.all_batched_updates();
+        assert_eq!(updates.len(), 2);
+        let fetches = counter.0.load(Ordering::SeqCst);
+        open(&mut pool, "c");
+        pool.all_batched_updates();
+        assert_eq!(counter.0.load(Ordering::SeqCst), fetches);
+        assert!(pool.missing_langs().is_empty());
+    }
+
+    #[test]
+    fn store_locales_reaches_open_documents() {
+        let mut pool = ProcessorPool::new(Arc::new(PredefinedLocales::bundled_en_us()));
+        open(&mut pool, "a");
+        let one = pool.get("a").unwrap().cluster_id("one");
+        assert_cluster!(pool.get("a").unwrap().get_cluster(one), Some("and"));
+        pool.store_locales(vec![(
+            Lang::en_us(),
+            r#"<locale xml:lang="en-US"><terms><term name="and">und</term></terms></locale>"#
+                .into(),
+        )]);
+        assert_cluster!(pool.get("a").unwrap().get_cluster(one), Some("und"));
+    }
+}
+
+#[test]
+fn style_cache_shares_parsed_style() {
+    let style = r#"<style version="1.0" class="in-text">
+        <citation><layout><text value="shared" /></layout></citation>
+    </style>"#;
+    let a = test_db(Some(style));
+    let mut b = test_db(Some(style));
+    assert!(Arc::ptr_eq(&a.get_style(), &b.get_style()));
+    b.set_style_text(style).unwrap_err(); // no <info>, test_mode only applies to `new`
+    let c = test_db(None);
+    assert!(!Arc::ptr_eq(&a.get_style(), &c.get_style()));
+}
+
+#[test]
+fn link_anchors() {
+    let style = r#"<style version="1.0" class="in-text">
+        <citation><layout><text variable="DOI" prefix="https://doi.org/" /></layout></citation>
+    </style>"#;
+    let render = |doi: &str, link_anchors: bool| {
+        let mut db = Processor::new(InitOptions {
+            style,
+            format: SupportedFormat::Html,
+            format_options: FormatOptions::default().with_link_anchors(link_anchors),
+            test_mode: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let mut refr = Reference::empty(Atom::from("r1"), CslType::ArticleJournal);
+        refr.ordinary.insert(Variable::DOI, doi.to_string());
+        db.insert_reference(refr);
+        let id = db.cluster_id("one");
+        db.insert_cites(id, &[Cite::basic("r1")]);
+        db.set_cluster_order(&[ClusterPosition::in_text(id)])
+            .unwrap();
+        db.get_cluster(id).unwrap().to_string()
+    };
+    let anchor =
+        r#"<a href="https://doi.org/10.1000/xyz">https://doi.org/10.1000/xyz</a>"#.to_string();
+    assert_eq!(render("10.1000/xyz", true), anchor);
+    assert_eq!(render("doi:10.1000/xyz", true), anchor);
+    assert_eq!(render("10.1000/xyz", false), "https://doi.org/10.1000/xyz");
+}
+
+#[test]
+fn plain_ascii_only() {
+    let style = r#"<style version="1.0" class="in-text">
+        <citation><layout delimiter=", ">
+            <text variable="title" quotes="true" />
+            <number variable="page" />
+        </layout></citation>
+    </style>"#;
+    let render = |ascii_only: bool| {
+        let mut db = Processor::new(InitOptions {
+            style,
+            format: SupportedFormat::Plain,
+            format_options: FormatOptions {
+                plain: PlainOptions {
+                    ascii_only,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            test_mode: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let mut refr = Reference::empty(Atom::from("r1"), CslType::ArticleJournal);
+        refr.ordinary.insert(Variable::Title, "Title".to_string());
+        refr.number.insert(NumberVariable::Page, NumberLike::Str("1-5".into()));
+        db.insert_reference(refr);
+        let id = db.cluster_id("one");
+        db.insert_cites(id, &[Cite::basic("r1")]);
+        db.set_cluster_order(&[ClusterPosition::in_text(id)])
+            .unwrap();
+        db.get_cluster(id).unwrap().to_string()
+    };
+    assert_eq!(render(false), "\u{201C}Title,\u{201D} 1\u{2013}5");
+    assert_eq!(render(true), "\"Title,\" 1-5");
+}
+
+#[test]
+fn custom_output_format() {
+    use citeproc_io::output::FormatCmd;
+    use citeproc_io::SmartString;
+
+    /// Just enough of Typst for one title.
+    #[derive(Debug)]
+    struct Typst;
+    impl CustomFormat for Typst {
+        fn write_text(&self, dest: &mut SmartString, text: &str) {
+            for c in text.chars() {
+                if matches!(c, '_' | '*' | '#' | '[' | ']') {
+                    dest.push('\\');
+                }
+                dest.push(c);
+            }
+        }
+        fn open(&self, dest: &mut SmartString, cmd: FormatCmd) {
+            if cmd == FormatCmd::FontStyleItalic {
+                dest.push_str("#emph[");
+            }
+        }
+        fn close(&self, dest: &mut SmartString, cmd: FormatCmd) {
+            if cmd == FormatCmd::FontStyleItalic {
+                dest.push(']');
+            }
+        }
+        fn write_link(&self, dest: &mut SmartString, url: &str, text: &str) {
+            dest.push_str("#link(\"");
+            dest.push_str(url);
+            dest.push_str("\")[");
+            self.write_text(dest, text);
+            dest.push(']');
+        }
+    }
+
+    let mut db = Processor::new(InitOptions {
+        style: r#"<style version="1.0" class="in-text">
+            <citation><layout delimiter=", ">
+                <text variable="title" font-style="italic" />
+                <text variable="URL" />
+            </layout></citation>
+        </style>"#,
+        custom_format: Some(Arc::new(Typst)),
+        test_mode: true,
+        ..Default::default()
+    })
+    .unwrap();
+    let mut refr = Reference::empty(Atom::from("r1"), CslType::Webpage);
+    refr.ordinary.insert(Variable::Title, "snake_case".to_string());
+    refr.ordinary.insert(Variable::URL, "https://example.com/".to_string());
+    db.insert_reference(refr);
+    let id = db.cluster_id("one");
+    db.insert_cites(id, &[Cite::basic("r1")]);
+    db.set_cluster_order(&[ClusterPosition::in_text(id)])
+        .unwrap();
+    assert_eq!(
+        db.get_cluster(id).unwrap().to_string(),
+        r#"#emph[snake\_case], #link("https://example.com/")[https://example.com/]"#
+    );
+    db.set_output_format(SupportedFormat::Plain, Default::default())
+        .unwrap();
+    assert_eq!(
+        db.get_cluster(id).unwrap().to_string(),
+        "snake_case, https://example.com/"
+    );
+}
+
+#[test]
+fn bidi_isolation() {
+    let style = r#"<style version="1.0" class="in-text">
+        <citation><layout suffix=".">
+            <text variable="title" prefix="In: " />
+        </layout></citation>
+    </style>"#;
+    let render = |format: SupportedFormat| {
+        let mut db = Processor::new(InitOptions {
+            style,
+            format,
+            format_options: FormatOptions {
+                html: HtmlOptions {
+                    bidi_isolation: true,
+                    ..Default::default()
+                },
+                plain: PlainOptions {
+                    bidi_isolation: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            test_mode: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let mut refr = Reference::empty(Atom::from("r1"), CslType::Book);
+        refr.ordinary.insert(Variable::Title, "ספר הזוהר".to_string());
+        db.insert_reference(refr);
+        let id = db.cluster_id("one");
+        db.insert_cites(id, &[Cite::basic("r1")]);
+        db.set_cluster_order(&[ClusterPosition::in_text(id)])
+            .unwrap();
+        db.get_cluster(id).unwrap().to_string()
+    };
+    assert_eq!(
+        render(SupportedFormat::Html),
+        r#"In: <span dir="rtl">ספר הזוהר</span>."#
+    );
+    assert_eq!(render(SupportedFormat::Plain), "In: \u{2067}ספר הזוהר\u{2069}.");
+}
+
+#[test]
+fn cluster_in_other_format() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+            <citation><layout>
+                <text variable="title" font-style="italic" />
+            </layout></citation>
+        </style>"#,
+    ));
+    db.set_output_format(SupportedFormat::Html, Default::default())
+        .unwrap();
+    insert_basic_refs(&mut db, &["one"]);
+    let id = cid(&mut db, 1);
+    db.insert_cites(id, &[Cite::basic("one")]);
+    db.set_cluster_order(&[ClusterPosition::in_text(id)])
+        .unwrap();
+    assert_cluster!(db.get_cluster(id), Some("<i>Book one</i>"));
+    assert_cluster!(
+        db.get_cluster_in(id, SupportedFormat::Plain),
+        Some("Book one")
+    );
+    assert_cluster!(
+        db.get_cluster_in(id, SupportedFormat::Html),
+        Some("<i>Book one</i>")
+    );
+    let unordered = cid(&mut db, 2);
+    assert_cluster!(db.get_cluster_in(unordered, SupportedFormat::Plain), None);
+}
+
+#[test]
+fn cluster_runs() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+            <citation><layout delimiter="; ">
+                <text variable="title" font-style="italic" />
+                <text value=" 1" vertical-align="sup" />
+            </layout></citation>
+        </style>"#,
+    ));
+    insert_basic_refs(&mut db, &["one"]);
+    let id = cid(&mut db, 1);
+    db.insert_cites(id, &[Cite::basic("one")]);
+    db.set_cluster_order(&[ClusterPosition::in_text(id)])
+        .unwrap();
+    let runs = db.get_cluster_runs(id).unwrap();
+    assert_eq!(
+        runs,
+        vec![
+            TextRun {
+                text: "Book one".into(),
+                italic: true,
+                ..Default::default()
+            },
+            TextRun {
+                text: " 1".into(),
+                superscript: true,
+                ..Default::default()
+            },
+        ]
+    );
+    let unordered = cid(&mut db, 2);
+    assert_eq!(db.get_cluster_runs(unordered), None);
+}
+
+#[test]
+fn accessed_date_policy() {
+    use citeproc_io::{Date, DateOrRange};
+    let today = Date::new(2021, 3, 4);
+    let mut db = test_db(None);
+    let web = |id: &str, csl_type| {
+        let mut refr = Reference::empty(Atom::from(id), csl_type);
+        if id == "dated" {
+            refr.date
+                .insert(DateVariable::Accessed, DateOrRange::new(2019, 1, 1));
+        }
+        refr
+    };
+    let accessed = |db: &Processor, id: &str| {
+        db.reference_input(Atom::from(id))
+            .date
+            .get(&DateVariable::Accessed)
+            .cloned()
+    };
+    db.set_accessed_date_policy(AccessedDatePolicy::FillMissing(today));
+    db.reset_references(vec![
+        web("page", CslType::Webpage),
+        web("dated", CslType::Webpage),
+        web("book", CslType::Book),
+    ]);
+    assert_eq!(accessed(&db, "page"), Some(DateOrRange::Single(today)));
+    assert_eq!(accessed(&db, "dated"), Some(DateOrRange::new(2019, 1, 1)));
+    assert_eq!(accessed(&db, "book"), None);
+    db.set_accessed_date_policy(AccessedDatePolicy::Strip);
+    db.insert_reference(web("dated", CslType::Webpage));
+    assert_eq!(accessed(&db, "dated"), None);
+}
+
+#[test]
+fn update_reference() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+            <citation><layout><text variable="title" /></layout></citation>
+        </style>"#,
+    ));
+    insert_basic_refs(&mut db, &["one"]);
+    let id = cid(&mut db, 1);
+    db.insert_cites(id, &[Cite::basic("one")]);
+    db.set_cluster_order(&[ClusterPosition::in_text(id)])
+        .unwrap();
+    assert_cluster!(db.get_cluster(id), Some("Book one"));
+
+    let mut refr = Reference::empty(Atom::from("one"), CslType::Book);
+    refr.ordinary
+        .insert(Variable::Title, "Book one".to_string());
+    assert!(!db.update_reference(refr.clone()));
+    refr.ordinary
+        .insert(Variable::Title, "Book two".to_string());
+    assert!(db.update_reference(refr));
+    assert_cluster!(db.get_cluster(id), Some("Book two"));
+
+    let refr = Reference::empty(Atom::from("new"), CslType::Book);
+    assert!(db.update_reference(refr.clone()));
+    assert!(db.all_keys().contains(&Atom::from("new")));
+    assert!(!db.update_reference(refr));
+
+    // Filled in on one day, synced again the next
+    use citeproc_io::{Date, DateOrRange};
+    let accessed = |db: &Processor| {
+        db.reference_input(Atom::from("page"))
+            .date
+            .get(&DateVariable::Accessed)
+            .cloned()
+    };
+    let mut page = Reference::empty(Atom::from("page"), CslType::Webpage);
+    db.set_accessed_date_policy(AccessedDatePolicy::FillMissing(Date::new(2021, 3, 4)));
+    assert!(db.update_reference(page.clone()));
+    db.set_accessed_date_policy(AccessedDatePolicy::FillMissing(Date::new(2021, 3, 5)));
+    assert!(!db.update_reference(page.clone()));
+    assert_eq!(accessed(&db), Some(DateOrRange::new(2021, 3, 4)));
+    page.ordinary.insert(Variable::Title, "Page".to_string());
+    assert!(db.update_reference(page));
+    assert_eq!(accessed(&db), Some(DateOrRange::new(2021, 3, 5)));
+}
+
+#[test]
+fn style_info() {
+    let mut db = Processor::new(InitOptions {
+        style: r#"<style version="1.0" class="in-text" default-locale="en-GB">
+            <info>
+                <id>http://www.zotero.org/styles/parent</id>
+                <title>Parent Style</title>
+                <updated>2020-01-01T00:00:00Z</updated>
+                <category citation-format="author-date" />
+            </info>
+            <citation><layout></layout></citation>
+        </style>"#,
+        ..Default::default()
+    })
+    .unwrap();
+    let info = db.style_info();
+    assert_eq!(info.id, "http://www.zotero.org/styles/parent");
+    assert_eq!(info.title, "Parent Style");
+    assert_eq!(info.citation_format, Some(csl::CitationFormat::AuthorDate));
+    assert_eq!(info.default_locale, Some("en-GB".parse().unwrap()));
+    assert_eq!(info.locale_override, None);
+    assert!(!info.has_bibliography);
+    assert_eq!(info.dependent, None);
+
+    let dependent = StyleMeta::parse(
+        r#"<style version="1.0" class="in-text" default-locale="de-DE">
+            <info>
+                <id>http://www.zotero.org/styles/dependent</id>
+                <title>Dependent Style</title>
+                <updated>2020-01-01T00:00:00Z</updated>
+                <link rel="independent-parent" href="http://www.zotero.org/styles/parent" />
+            </info>
+        </style>"#,
+    )
+    .unwrap();
+    db.set_dependent_style(&dependent);
+    let info = db.style_info();
+    assert_eq!(info.locale_override, Some("de-DE".parse().unwrap()));
+    assert_eq!(
+        info.dependent,
+        Some(DependentStyleInfo {
+            id: "http://www.zotero.org/styles/dependent".into(),
+            title: "Dependent Style".into(),
+            independent_parent: Some("http://www.zotero.org/styles/parent".into()),
+        })
+    );
+
+    // A locale override the user asked for is not replaced by the dependent style's.
+    db.set_locale_override(Some("fr-FR".parse().unwrap()));
+    db.set_dependent_style(&dependent);
+    assert_eq!(
+        db.style_info().locale_override,
+        Some("fr-FR".parse().unwrap())
+    );
+}
+
+#[test]
+fn export_references() {
+    let mut db = test_db(None);
+    let input: Vec<Reference> = serde_json::from_str(
+        r#"[{
+            "type": "book",
+            "title": "Jellybeans",
+            "id": "r1",
+            "edition": 2,
+            "author": [{ "given": "Jane", "family": "Smith" }, { "literal": "ACME" }],
+            "issued": { "date-parts": [["1999", "13"]], "circa": true },
+            "language": "en-AU"
+        }]"#,
+    )
+    .unwrap();
+    db.reset_references(input.clone());
+    let exported = db.export_references(&[Atom::from("r1"), Atom::from("missing")]);
+    assert_eq!(
+        exported,
+        r#"[
+  {
+    "id": "r1",
+    "type": "book",
+    "author": [
+      {
+        "family": "Smith",
+        "given": "Jane"
+      },
+      {
+        "literal": "ACME"
+      }
+    ],
+    "edition": 2,
+    "issued": {
+      "circa": true,
+      "date-parts": [
+        [
+          1999,
+          21
+        ]
+      ]
+    },
+    "language": "en-AU",
+    "title": "Jellybeans"
+  }
+]"#
+    );
+    let round_trip: Vec<Reference> = serde_json::from_str(&exported).unwrap();
+    assert_eq!(round_trip, input);
+}
+
+#[test]
+fn reference_introspection() {
+    let mut db = test_db(None);
+    insert_basic_refs(&mut db, &["two", "one"]);
+    assert_eq!(
+        db.reference_ids(),
+        vec![Atom::from("two"), Atom::from("one")]
+    );
+    let exported = db.export_reference(Atom::from("one")).unwrap();
+    let round_trip: Reference = serde_json::from_str(&exported).unwrap();
+    let mut expected = Reference::empty(Atom::from("one"), CslType::Book);
+    expected
+        .ordinary
+        .insert(Variable::Title, "Book one".to_string());
+    assert_eq!(round_trip, expected);
+    assert_eq!(db.export_reference(Atom::from("missing")), None);
+    db.remove_reference(Atom::from("two"));
+    assert_eq!(db.reference_ids(), vec![Atom::from("one")]);
+}
+
+#[test]
+fn sort_key() {
+    use citeproc_io::DateOrRange;
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+        <macro name="title"><text variable="title" /></macro>
+        <citation><layout></layout></citation>
+        <bibliography>
+            <sort>
+                <key macro="title" />
+                <key variable="issued" sort="descending" />
+                <key variable="citation-number" />
+            </sort>
+            <layout><text variable="title" /></layout>
+        </bibliography>
+    </style>"#,
+    ));
+    let mut refr = Reference::empty(Atom::from("r1"), CslType::Book);
+    refr.ordinary.insert(Variable::Title, "Title".into());
+    refr.date
+        .insert(DateVariable::Issued, DateOrRange::new(2000, 1, 0));
+    db.insert_reference(refr);
+    db.include_uncited(IncludeUncited::All);
+    let keys = db.sort_key(Atom::from("r1"));
+    assert_eq!(
+        keys,
+        vec![
+            SortKeyValue {
+                direction: SortDirection::Ascending,
+                value: SortKeyData::Macro(Some("Title".into())),
+            },
+            SortKeyValue {
+                direction: SortDirection::Descending,
+                value: SortKeyData::Date(Some(DateOrRange::new(2000, 1, 0))),
+            },
+            SortKeyValue {
+                direction: SortDirection::Ascending,
+                value: SortKeyData::CitationNumber(None),
+            },
+        ]
+    );
+    assert!(db.sort_key(Atom::from("missing")).is_empty());
+}
+
+#[test]
+fn sort_by_date() {
+    use citeproc_io::{Date, DateOrRange};
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+        <citation><layout></layout></citation>
+        <bibliography>
+            <sort>
+                <key variable="issued" />
+            </sort>
+            <layout><text variable="title" /></layout>
+        </bibliography>
+    </style>"#,
+    ));
+    let dates = vec![
+        (
+            "literal",
+            DateOrRange::Literal {
+                literal: "forthcoming".into(),
+                circa: false,
+            },
+        ),
+        ("may", DateOrRange::new(2000, 5, 0)),
+        ("spring", DateOrRange::new(2000, 13, 0)),
+        (
+            "range",
+            DateOrRange::Range(Date::new(2000, 0, 0), Date::new(2001, 0, 0)),
+        ),
+        ("circa", DateOrRange::Single(Date::new_circa(2000, 0, 0))),
+        ("year", DateOrRange::new(2000, 0, 0)),
+        ("earlier", DateOrRange::new(1999, 0, 0)),
+    ];
+    for (id, date) in dates {
+        let mut refr = Reference::empty(Atom::from(id), CslType::Book);
+        refr.ordinary.insert(Variable::Title, id.to_string());
+        refr.date.insert(DateVariable::Issued, date);
+        db.insert_reference(refr);
+    }
+    db.include_uncited(IncludeUncited::All);
+    let bib = db.get_bibliography();
+    let ids: Vec<_> = bib.iter().map(|e| &*e.id).collect();
+    // Seasons only break ties, circa comes after the plain date, a range after a date it starts
+    // with, and literals go last.
+    assert_eq!(
+        ids,
+        vec!["earlier", "year", "range", "circa", "spring", "may", "literal"]
+    );
+}
+
+#[test]
+fn style_mode() {
+    let init = |style, style_mode| {
+        Processor::new(InitOptions {
+            style,
+            style_mode,
+            test_mode: true,
+            ..Default::default()
+        })
+    };
+    let cslm = r#"<style version="1.1mlz1" class="note">
+        <citation><layout><text variable="court-class" /></layout></citation>
+    </style>"#;
+    match init(cslm, StyleMode::Csl) {
+        Err(Error::Style(StyleError::Invalid(CslError(errors)))) => {
+            assert!(errors[0].message.contains("CSL-M style"))
+        }
+        _ => panic!("CSL-M style loaded in strict mode"),
+    }
+    assert!(init(cslm, StyleMode::CslM).is_ok());
+
+    // CSL-M-only variables need CSL-M mode, or a feature that enables them
+    let document_name = r#"<style version="1.0" class="note">
+        <citation><layout><text variable="document-name" /></layout></citation>
+    </style>"#;
+    assert!(init(document_name, StyleMode::Csl).is_err());
+    assert!(init(document_name, StyleMode::CslM).is_ok());
+    // The default still takes them, as it always has
+    assert_eq!(StyleMode::default(), StyleMode::Lenient);
+    assert!(init(document_name, StyleMode::Lenient).is_ok());
+    let module = r#"<style version="1.0" variant="csl-m" class="note">
+        <citation><layout><text variable="court-class" /></layout></citation>
+    </style>"#;
+    assert!(init(module, StyleMode::Lenient).is_ok());
+    assert!(init(module, StyleMode::Csl).is_err());
+    let with_feature = r#"<style version="1.0" class="note">
+        <features><feature name="cslm-legal-variables" /></features>
+        <citation><layout><text variable="court-class" /></layout></citation>
+    </style>"#;
+    assert!(init(with_feature, StyleMode::Csl).is_ok());
+}
+
+#[test]
+fn get_term() {
+    let db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+        <locale><terms><term name="ibid">idem</term></terms></locale>
+        <citation><layout></layout></citation>
+    </style>"#,
+    ));
+    assert_eq!(
+        db.get_term("accessed", None, false).as_deref(),
+        Some("accessed")
+    );
+    assert_eq!(
+        db.get_term("and", Some("symbol"), false).as_deref(),
+        Some("&")
+    );
+    assert_eq!(
+        db.get_term("page", Some("short"), true).as_deref(),
+        Some("pp.")
+    );
+    assert_eq!(db.get_term("ibid", None, false).as_deref(), Some("idem"));
+    assert_eq!(db.get_term("not-a-term", None, false), None);
+    assert_eq!(db.get_term("and", Some("not-a-form"), false), None);
+}
+
+#[test]
+fn sort_key_name_options() {
+    let db = test_db(Some(
+        r#"<style version="1.0" class="in-text" et-al-min="10" et-al-use-first="7">
+        <macro name="author"><names variable="author" /></macro>
+        <citation><layout></layout></citation>
+        <bibliography et-al-subsequent-min="5">
+            <sort>
+                <key macro="author" names-min="3" names-use-first="1" />
+                <key macro="author" names-use-last="true" />
+            </sort>
+            <layout><text variable="title" /></layout>
+        </bibliography>
+    </style>"#,
+    ));
+    let first = db
+        .sort_key_name_options(CiteOrBib::Bibliography, 0)
+        .unwrap();
+    assert_eq!(first.et_al_min, Some(3));
+    assert_eq!(first.et_al_subsequent_min, Some(3));
+    assert_eq!(first.et_al_use_first, Some(1));
+    assert_eq!(first.name_as_sort_order, Some(NameAsSortOrder::All));
+    let second = db
+        .sort_key_name_options(CiteOrBib::Bibliography, 1)
+        .unwrap();
+    assert_eq!(second.et_al_min, Some(10));
+    assert_eq!(second.et_al_subsequent_min, Some(5));
+    assert_eq!(second.et_al_use_first, Some(7));
+    assert_eq!(second.et_al_use_last, Some(true));
+    assert_eq!(db.sort_key_name_options(CiteOrBib::Bibliography, 2), None);
+    assert_eq!(db.sort_key_name_options(CiteOrBib::Citation, 0), None);
+}
+
+#[test]
+fn cluster_meta() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="note">
+        <citation><layout suffix=".">
+            <text variable="title" font-style="italic" />
+            <text variable="note" display="block" />
+        </layout></citation>
+    </style>"#,
+    ));
+    let mut refr = Reference::empty(Atom::from("r1"), CslType::Book);
+    refr.ordinary.insert(Variable::Title, "Ti & tle".into());
+    db.insert_reference(refr);
+    let mut refr = Reference::empty(Atom::from("r2"), CslType::Book);
+    refr.ordinary.insert(Variable::Note, "Note".into());
+    db.insert_reference(refr);
+    let one = db.cluster_id("one");
+    let two = db.cluster_id("two");
+    db.insert_cites(one, &[Cite::basic("r1")]);
+    db.insert_cites(two, &[Cite::basic("r2")]);
+    assert_eq!(db.get_cluster_meta(one), None);
+    db.set_cluster_order(&[ClusterPosition::note(one, 1), ClusterPosition::note(two, 2)])
+        .unwrap();
+    assert_eq!(
+        db.get_cluster_meta(one),
+        Some(ClusterMeta {
+            plain_len: 9,
+            has_block_display: false,
+            ends_with_punctuation: true,
+        })
+    );
+    assert_eq!(
+        db.get_cluster_meta(two).map(|meta| meta.has_block_display),
+        Some(true)
+    );
+}
+
+#[test]
+fn merge_clusters() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+        <citation><layout delimiter="; "><text variable="title" /></layout></citation>
+    </style>"#,
+    ));
+    for (id, title) in &[("r1", "One"), ("r2", "Two"), ("r3", "Three")] {
+        let mut refr = Reference::empty(Atom::from(*id), CslType::Book);
+        refr.ordinary.insert(Variable::Title, title.to_string());
+        db.insert_reference(refr);
+    }
+    let a = db.cluster_id("a");
+    let b = db.cluster_id("b");
+    let c = db.cluster_id("c");
+    db.insert_cites(a, &[Cite::basic("r1")]);
+    db.insert_cites(b, &[Cite::basic("r2")]);
+    db.insert_cites(c, &[Cite::basic("r3")]);
+    db.set_cluster_order(&[
+        ClusterPosition::in_text(a),
+        ClusterPosition::in_text(b),
+        ClusterPosition::in_text(c),
+    ])
+    .unwrap();
+    assert_eq!(
+        db.merge_clusters(&[a, c]),
+        Err(ReorderingError::NonAdjacentCluster(c))
+    );
+    assert_eq!(db.merge_clusters(&[]), Err(ReorderingError::NoClusters));
+    assert_eq!(db.merge_clusters(&[b, c]), Ok(b));
+    assert_cluster!(db.get_cluster(b), Some("Two; Three"));
+    assert_cluster!(db.get_cluster(c), None);
+    assert_eq!(
+        db.get_cluster_note_number(b),
+        Some(ClusterNumber::InText(2))
+    );
+}
+
+#[test]
+fn split_cluster() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="note">
+        <citation><layout delimiter="; "><text variable="title" /></layout></citation>
+    </style>"#,
+    ));
+    for (id, title) in &[("r1", "One"), ("r2", "Two"), ("r3", "Three")] {
+        let mut refr = Reference::empty(Atom::from(*id), CslType::Book);
+        refr.ordinary.insert(Variable::Title, title.to_string());
+        db.insert_reference(refr);
+    }
+    let a = db.cluster_id("a");
+    let b = db.cluster_id("b");
+    db.insert_cites(
+        a,
+        &[Cite::basic("r1"), Cite::basic("r2"), Cite::basic("r3")],
+    );
+    db.insert_cites(b, &[Cite::basic("r1")]);
+    db.set_cluster_order(&[ClusterPosition::note(a, 1), ClusterPosition::note(b, 2)])
+        .unwrap();
+    assert_eq!(
+        db.split_cluster(a, 3),
+        Err(ReorderingError::SplitOutOfRange(a, 3))
+    );
+    let (first, second) = db.split_cluster(a, 1).unwrap();
+    assert_eq!(first, a);
+    assert_cluster!(db.get_cluster(a), Some("One"));
+    assert_cluster!(db.get_cluster(second), Some("Two; Three"));
+    assert_eq!(
+        db.get_cluster_note_number(second),
+        Some(ClusterNumber::Note(IntraNote::Multi(1, 1)))
+    );
+    assert_eq!(
+        db.get_cluster_note_number(b),
+        Some(ClusterNumber::Note(IntraNote::Multi(2, 0)))
+    );
+}
+
+#[test]
+fn intra_note_numbering() {
+    let mut db = test_db(None);
+    let ids: Vec<ClusterId> = ["a", "b", "c"].iter().map(|s| db.cluster_id(s)).collect();
+    for &id in &ids {
+        db.insert_cites(id, &[Cite::basic("r1")]);
+    }
+    db.set_cluster_order(&[
+        ClusterPosition::note(ids[0], 1),
+        ClusterPosition::note(ids[1], 1),
+        ClusterPosition::note(ids[2], 2),
+    ])
+    .unwrap();
+    let numbers: Vec<_> = ids
+        .iter()
+        .map(|&id| db.get_cluster_note_number(id).unwrap())
+        .collect();
+    assert_eq!(
+        serde_json::to_string(&numbers).unwrap(),
+        r#"[{"note":[1,0]},{"note":[1,1]},{"note":[2,0]}]"#
+    );
+    assert!(IntraNote::Single(1) < IntraNote::Multi(1, 1));
+    assert!(IntraNote::Multi(1, 3) < IntraNote::Single(2));
+}
+
+#[test]
+fn hybrid_in_text_and_note_positions() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="note">
+        <citation><layout>
+            <choose>
+                <if position="ibid"><text value="ibid" /></if>
+                <else-if position="near-note"><text value="near" /></else-if>
+                <else-if position="subsequent"><text value="subsequent" /></else-if>
+                <else><text value="first" /></else>
+            </choose>
+        </layout></citation>
+    </style>"#,
+    ));
+    db.insert_reference(Reference::empty(Atom::from("r1"), CslType::Book));
+    let ids: Vec<ClusterId> = ["a", "b", "c"].iter().map(|s| db.cluster_id(s)).collect();
+    for &id in &ids {
+        db.insert_cites(id, &[Cite::basic("r1")]);
+    }
+    db.set_cluster_order(&[
+        ClusterPosition::note(ids[0], 1),
+        ClusterPosition::in_text(ids[1]),
+        ClusterPosition::note(ids[2], 2),
+    ])
+    .unwrap();
+    // The in-text cite comes after the footnote, so it is subsequent, but not near. It also
+    // separates the two footnotes, so the second one is not ibid.
+    assert_cluster!(db.get_cluster(ids[0]), Some("first"));
+    assert_cluster!(db.get_cluster(ids[1]), Some("subsequent"));
+    assert_cluster!(db.get_cluster(ids[2]), Some("near"));
+}
+
+#[test]
+fn citation_numbering() {
+    let style = r#"<style version="1.0" class="in-text">
+        <citation><layout><text variable="citation-number" /></layout></citation>
+        <bibliography>
+            <sort><key variable="title" /></sort>
+            <layout><text variable="title" /></layout>
+        </bibliography>
+    </style>"#;
+    let setup = |citation_numbering| {
+        let mut db = Processor::new(InitOptions {
+            style,
+            format: SupportedFormat::Plain,
+            test_mode: true,
+            citation_numbering,
+            ..Default::default()
+        })
+        .unwrap();
+        for (id, title) in &[("r1", "B"), ("r2", "A"), ("r3", "C")] {
+            let mut refr = Reference::empty(Atom::from(*id), CslType::Book);
+            refr.ordinary.insert(Variable::Title, title.to_string());
+            db.insert_reference(refr);
+        }
+        let a = db.cluster_id("a");
+        let b = db.cluster_id("b");
+        db.insert_cites(a, &[Cite::basic("r1")]);
+        db.insert_cites(b, &[Cite::basic("r2")]);
+        db.set_cluster_order(&[ClusterPosition::in_text(a), ClusterPosition::in_text(b)])
+            .unwrap();
+        (db, a, b)
+    };
+
+    let (db, a, b) = setup(CitationNumbering::BibliographyOrder);
+    assert_cluster!(db.get_cluster(a), Some("2"));
+    assert_cluster!(db.get_cluster(b), Some("1"));
+
+    let (mut db, a, b) = setup(CitationNumbering::FirstCited);
+    assert_cluster!(db.get_cluster(a), Some("1"));
+    assert_cluster!(db.get_cluster(b), Some("2"));
+
+    // A new first citation would renumber everything, unless the numbers are frozen.
+    db.freeze_citation_numbers();
+    let c = db.cluster_id("c");
+    db.insert_cites(c, &[Cite::basic("r3")]);
+    db.set_cluster_order(&[
+        ClusterPosition::in_text(c),
+        ClusterPosition::in_text(a),
+        ClusterPosition::in_text(b),
+    ])
+    .unwrap();
+    assert_cluster!(db.get_cluster(a), Some("1"));
+    assert_cluster!(db.get_cluster(b), Some("2"));
+    assert_cluster!(db.get_cluster(c), Some("3"));
+    db.unfreeze_citation_numbers();
+    assert_cluster!(db.get_cluster(c), Some("1"));
+}
+
+#[test]
+fn bibliography_sections() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+        <citation><layout></layout></citation>
+        <bibliography>
+            <sort><key variable="title" /></sort>
+            <layout><text variable="title" /></layout>
+        </bibliography>
+    </style>"#,
+    ));
+    for (id, title, csl_type) in &[
+        ("r1", "D", CslType::Manuscript),
+        ("r2", "C", CslType::Book),
+        ("r3", "B", CslType::Manuscript),
+        ("r4", "A", CslType::Webpage),
+    ] {
+        let mut refr = Reference::empty(Atom::from(*id), *csl_type);
+        refr.ordinary.insert(Variable::Title, title.to_string());
+        db.insert_reference(refr);
+    }
+    db.include_uncited(IncludeUncited::All);
+    let sections = db.get_bibliography_sections(|refr| match refr.csl_type {
+        CslType::Manuscript => Some("Primary sources".into()),
+        CslType::Book => Some("Secondary sources".into()),
+        _ => None,
+    });
+    let summary: Vec<(&str, Vec<&str>)> = sections
+        .iter()
+        .map(|s| {
+            let ids = s.entries.iter().map(|e| &*e.id).collect();
+            (s.label.as_str(), ids)
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("Primary sources", vec!["r3", "r1"]),
+            ("Secondary sources", vec!["r2"]),
+        ]
+    );
+}
+
+#[test]
+fn bibliography_filter() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+        <citation><layout></layout></citation>
+        <bibliography>
+            <sort><key variable="title" /></sort>
+            <layout><text variable="title" /></layout>
+        </bibliography>
+    </style>"#,
+    ));
+    for (id, title, csl_type, keyword) in &[
+        ("r1", "D", CslType::Book, "primary; law"),
+        ("r2", "C", CslType::Book, "secondary"),
+        ("r3", "B", CslType::Manuscript, "primary"),
+        ("r4", "A", CslType::Book, ""),
+    ] {
+        let mut refr = Reference::empty(Atom::from(*id), *csl_type);
+        refr.ordinary.insert(Variable::Title, title.to_string());
+        if !keyword.is_empty() {
+            refr.ordinary.insert(Variable::Keyword, keyword.to_string());
+        }
+        db.insert_reference(refr);
+    }
+    db.include_uncited(IncludeUncited::All);
+    let m = |field: &str, value: &str| FieldMatch {
+        field: field.into(),
+        value: value.into(),
+    };
+    let ids = |filter: BibliographyFilter| -> Vec<String> {
+        db.get_bibliography_filtered(&filter)
+            .iter()
+            .map(|e| e.id.to_string())
+            .collect()
+    };
+    assert_eq!(
+        ids(BibliographyFilter::default()),
+        vec!["r4", "r3", "r2", "r1"]
+    );
+    assert_eq!(
+        ids(BibliographyFilter {
+            select: vec![m("type", "book"), m("keyword", "primary")],
+            ..Default::default()
+        }),
+        vec!["r1"]
+    );
+    assert_eq!(
+        ids(BibliographyFilter {
+            include: vec![m("keyword", "primary"), m("keyword", "secondary")],
+            exclude: vec![m("id", "r2")],
+            ..Default::default()
+        }),
+        vec!["r3", "r1"]
+    );
+    assert_eq!(
+        ids(BibliographyFilter {
+            quash: vec![m("type", "book"), m("keyword", "primary")],
+            ..Default::default()
+        }),
+        vec!["r4", "r3", "r2"]
+    );
+}
+
+#[test]
+fn clusters_citing() {
+    let mut db = test_db(None);
+    for id in &["r1", "r2", "r3"] {
+        db.insert_reference(Reference::empty(Atom::from(*id), CslType::Book));
+    }
+    let one = db.cluster_id("one");
+    let two = db.cluster_id("two");
+    let three = db.cluster_id("three");
+    db.insert_cites(one, &[Cite::basic("r1"), Cite::basic("r2")]);
+    db.insert_cites(two, &[Cite::basic("r2")]);
+    db.insert_cites(three, &[Cite::basic("r1")]);
+    db.set_cluster_order(&[ClusterPosition::note(two, 1), ClusterPosition::note(one, 2)])
+        .unwrap();
+    assert_eq!(db.clusters_citing("r1".into()), vec![one, three]);
+    assert_eq!(db.clusters_citing("r2".into()), vec![two, one]);
+    assert_eq!(db.clusters_citing("r3".into()), vec![]);
+}
+
+#[test]
+fn back_references() {
+    let mut db = test_db(None);
+    for id in &["r1", "r2"] {
+        db.insert_reference(Reference::empty(Atom::from(*id), CslType::Book));
+    }
+    let one = db.cluster_id("one");
+    let two = db.cluster_id("two");
+    let three = db.cluster_id("three");
+    db.insert_cites(one, &[Cite::basic("r1")]);
+    db.insert_cites(two, &[Cite::basic("r1"), Cite::basic("r2")]);
+    db.insert_cites(three, &[Cite::basic("r1")]);
+    db.set_cluster_order(&[
+        ClusterPosition::note(one, 1),
+        ClusterPosition::note(two, 1),
+        ClusterPosition::note(three, 4),
+    ])
+    .unwrap();
+    use BackReference::*;
+    assert_eq!(db.back_references("r1".into()), vec![Note(1), Note(4)]);
+    assert_eq!(db.back_references("r2".into()), vec![Note(1)]);
+    db.set_cluster_order(&[
+        ClusterPosition::in_text(three),
+        ClusterPosition::in_text(two),
+    ])
+    .unwrap();
+    assert_eq!(db.back_references("r1".into()), vec![InText(1), InText(2)]);
+    assert_eq!(db.back_references("r2".into()), vec![InText(2)]);
+}
+
+#[test]
+fn restore_cluster_ids() {
+    let db = test_db(None);
+    let a = db.cluster_id("a");
+    let b = db.cluster_id("b");
+    let saved = db.cluster_ids();
+    assert_eq!(saved, vec![SmartString::from("a"), "b".into()]);
+
+    let mut restored = test_db(None);
+    restored.restore_cluster_ids(&saved).unwrap();
+    assert_eq!(restored.cluster_id("b"), b);
+    assert_eq!(restored.cluster_id("a"), a);
+    assert_ne!(restored.cluster_id("c"), a);
+
+    let mut conflicting = test_db(None);
+    conflicting.cluster_id("b");
+    assert_eq!(
+        conflicting.restore_cluster_ids(&saved),
+        Err(ClusterIdConflict {
+            id: "a".into(),
+            saved: a,
+        })
+    );
+    assert_eq!(conflicting.cluster_ids(), vec![SmartString::from("b")]);
+}
+
+#[test]
+fn citeproc_js_citations() {
+    let mut db = test_db(None);
+    let citations: Vec<CiteprocJsCitation> = serde_json::from_str(
+        r#"[
+            { "citationID": "a", "citationItems": [{ "id": "r1", "locator": "12", "label": "page" }],
+              "properties": { "noteIndex": 1 } },
+            { "citationID": "b", "citationItems": [{ "id": "r2", "suppress-author": true }, { "id": "r1" }],
+              "properties": { "noteIndex": 1 } },
+            { "citationID": "c", "citationItems": [{ "id": "r2", "prefix": "see " }],
+              "properties": { "noteIndex": 4 } },
+            { "citationID": "d", "citationItems": [{ "id": "r1", "locator": "1", "label": "chapter",
+                "locators": [{ "locator": "1", "label": "chapter" }, { "locator": "5", "label": "page" }] }],
+              "properties": { "noteIndex": 5 } }
+        ]"#,
+    )
+    .unwrap();
+    let ids = db.import_citeproc_js(citations.clone()).unwrap();
+    let ids: Vec<&str> = ids.iter().map(|id| id.as_str()).collect();
+    assert_eq!(ids, vec!["a", "b", "c", "d"]);
+    let b = db.cluster_id("b");
+    assert_eq!(
+        db.get_cluster_note_number(b),
+        Some(ClusterNumber::Note(IntraNote::Multi(1, 1)))
+    );
+    assert_eq!(db.export_citeproc_js(), citations);
+
+    let out_of_order = vec![citations[2].clone(), citations[0].clone()];
+    assert!(db.import_citeproc_js(out_of_order).is_err());
+
+    let both = r#"{ "id": "r1", "author-only": true, "suppress-author": true }"#;
+    assert!(serde_json::from_str::<CiteprocJsCitationItem>(both).is_err());
+}
+
+#[test]
+fn unused_references() {
+    let mut db = test_db(None);
+    for id in &["r1", "r2", "r3", "r4"] {
+        db.insert_reference(Reference::empty(Atom::from(*id), CslType::Book));
+    }
+    let one = db.cluster_id("one");
+    db.insert_cites(one, &[Cite::basic("r2"), Cite::basic("missing")]);
+    db.set_cluster_order(&[ClusterPosition::note(one, 1)])
+        .unwrap();
+    let atoms = |ids: &[&str]| ids.iter().map(|&id| Atom::from(id)).collect::<Vec<_>>();
+    assert_eq!(db.unused_references(), atoms(&["r1", "r3", "r4"]));
+    db.include_uncited(IncludeUncited::Specific(vec!["r3".into()]));
+    assert_eq!(db.unused_references(), atoms(&["r1", "r4"]));
+    db.include_uncited(IncludeUncited::All);
+    assert_eq!(db.unused_references(), atoms(&[]));
+}
+
+#[test]
+fn cluster_spans() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+        <citation>
+            <layout prefix="(" suffix=")" delimiter="; "><text variable="title" /></layout>
+        </citation>
+    </style>"#,
+    ));
+    for (id, title) in &[("r1", "One"), ("r2", "Two")] {
+        let mut refr = Reference::empty(Atom::from(*id), CslType::Book);
+        refr.ordinary.insert(Variable::Title, title.to_string());
+        db.insert_reference(refr);
+    }
+    let id = db.cluster_id("one");
+    let mut second = Cite::basic("r2");
+    second.prefix = Some("see".into());
+    db.insert_cites(id, &[Cite::basic("r1"), second]);
+    db.set_cluster_order(&[ClusterPosition::note(id, 1)])
+        .unwrap();
+    let with_spans = db.get_cluster_with_spans(id).unwrap();
+    assert_eq!(with_spans.output.as_str(), "(One; see Two)");
+    let spans: Vec<_> = with_spans
+        .spans
+        .iter()
+        .map(|s| (s.cite_index, &*s.ref_id, &with_spans.output[s.start..s.end]))
+        .collect();
+    assert_eq!(spans, vec![(0, "r1", "One"), (1, "r2", "Two")]);
+}
+
+#[test]
+fn still_ambiguous() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+        <citation><layout delimiter="; "><text variable="title" /></layout></citation>
+    </style>"#,
+    ));
+    for (id, title) in &[("r1", "Same"), ("r2", "Same"), ("r3", "Other")] {
+        let mut refr = Reference::empty(Atom::from(*id), CslType::Book);
+        refr.ordinary.insert(Variable::Title, title.to_string());
+        db.insert_reference(refr);
+    }
+    let one = db.cluster_id("one");
+    let two = db.cluster_id("two");
+    db.insert_cites(one, &[Cite::basic("r3"), Cite::basic("r1")]);
+    db.insert_cites(two, &[Cite::basic("r2")]);
+    db.set_cluster_order(&[ClusterPosition::note(one, 1), ClusterPosition::note(two, 2)])
+        .unwrap();
+    let summary: Vec<_> = db
+        .still_ambiguous()
+        .into_iter()
+        .map(|a| (a.cluster_id, a.cite_index, a.ref_id, a.ambiguous_with))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (one, 1, Atom::from("r1"), vec![Atom::from("r2")]),
+            (two, 0, Atom::from("r2"), vec![Atom::from("r1")]),
+        ]
+    );
+}
+
+#[test]
+fn take_warnings() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+        <citation>
+            <layout>
+                <group delimiter=" ">
+                    <text variable="title" />
+                    <text term="year-range-delimiter" />
+                </group>
+            </layout>
+        </citation>
+    </style>"#,
+    ));
+    let mut refr = Reference::empty(Atom::from("r1"), CslType::Book);
+    refr.ordinary.insert(Variable::Title, "Title".to_string());
+    db.insert_reference(refr);
+    let id = db.cluster_id("one");
+    db.insert_cites(id, &[Cite::basic("r1")]);
+    db.set_cluster_order(&[ClusterPosition::note(id, 1)])
+        .unwrap();
+    assert_cluster!(db.get_cluster(id), Some("Title"));
+    let warnings = db.take_warnings();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].code, WarningCode::MissingTerm);
+    assert_eq!(warnings[0].ref_id, Some(Atom::from("r1")));
+    assert_eq!(db.take_warnings(), vec![]);
+}
+
+#[test]
+fn field_markup_warning() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+        <citation><layout><text variable="title" /></layout></citation>
+    </style>"#,
+    ));
+    let mut refr = Reference::empty(Atom::from("r1"), CslType::Book);
+    refr.ordinary
+        .insert(Variable::Title, "<i>Unclosed title".to_string());
+    db.insert_reference(refr);
+    let id = db.cluster_id("one");
+    db.insert_cites(id, &[Cite::basic("r1")]);
+    db.set_cluster_order(&[ClusterPosition::note(id, 1)])
+        .unwrap();
+    db.get_cluster(id);
+    let warnings = db.take_warnings();
+    assert!(!warnings.is_empty());
+    assert!(warnings.iter().all(|w| w.code == WarningCode::FieldMarkup));
+    assert_eq!(warnings[0].ref_id, Some(Atom::from("r1")));
+}
+
+#[test]
+fn jurisdiction_modules() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+        <macro name="court"><text value="Court" /></macro>
+        <citation>
+            <layout delimiter="; ">
+                <group delimiter=" ">
+                    <text macro="court" />
+                    <choose>
+                        <if jurisdiction="us" subjurisdictions="1" match="all">
+                            <text value="(state)" />
+                        </if>
+                        <else-if jurisdiction="us"><text value="(federal)" /></else-if>
+                    </choose>
+                </group>
+            </layout>
+        </citation>
+    </style>"#,
+    ));
+    // Modules are parsed without test_mode, so they need an <info>
+    let module = r#"<style version="1.0" class="in-text">
+        <info>
+            <id>https://example.com/us</id>
+            <title>US</title>
+            <updated>2020-01-01T00:00:00Z</updated>
+        </info>
+        <macro name="court"><text value="US Court" /></macro>
+        <citation><layout></layout></citation>
+    </style>"#;
+    db.set_jurisdiction_module("us", module).unwrap();
+    for (id, jurisdiction) in &[("fed", Some("us")), ("ca", Some("us:ca")), ("uk", None)] {
+        let mut refr = Reference::empty(Atom::from(*id), CslType::LegalCase);
+        if let Some(j) = jurisdiction {
+            refr.ordinary.insert(Variable::Jurisdiction, j.to_string());
+        }
+        db.insert_reference(refr);
+    }
+    let id = db.cluster_id("one");
+    db.insert_cites(
+        id,
+        &[Cite::basic("fed"), Cite::basic("ca"), Cite::basic("uk")],
+    );
+    db.set_cluster_order(&[ClusterPosition::note(id, 1)])
+        .unwrap();
+    assert_cluster!(
+        db.get_cluster(id),
+        Some("US Court (federal); US Court (state); Court")
+    );
+    db.remove_jurisdiction_module("us");
+    assert_cluster!(
+        db.get_cluster(id),
+        Some("Court (federal); Court (state); Court")
+    );
+
+    db.set_journaling(true);
+    db.set_invalidation_tracing(true);
+    db.set_jurisdiction_module("us", module).unwrap();
+    assert_eq!(
+        db.batched_updates().causes,
+        Some(vec![(id, vec![InputChange::Style])])
+    );
+    db.remove_jurisdiction_module("us");
+    assert_eq!(
+        db.journal().entries,
+        vec![
+            JournalEntry::SetJurisdictionModule {
+                jurisdiction: "us".into(),
+                style: module.to_owned(),
+            },
+            JournalEntry::RemoveJurisdictionModule {
+                jurisdiction: "us".into(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn jurisdiction_module_macro_depth() {
+    // Styles set after `new` need an <info>
+    let with_macros = |macros: &str, layout: &str| {
+        format!(
+            r#"<style version="1.0" class="in-text">
+                <info>
+                    <id>https://example.com/style</id>
+                    <title>Style</title>
+                    <updated>2020-01-01T00:00:00Z</updated>
+                </info>
+                {}
+                <citation><layout>{}</layout></citation>
+            </style>"#,
+            macros, layout
+        )
+    };
+    let court = r#"<macro name="court"><text value="Court" /></macro>"#;
+    let flat = with_macros(court, r#"<text macro="court" />"#);
+    let mut db = Processor::new(InitOptions {
+        style: &flat,
+        max_macro_depth: Some(2),
+        ..Default::default()
+    })
+    .unwrap();
+    // Two deep on its own
+    let module = with_macros(
+        r#"<macro name="court"><text macro="name" /></macro>
+        <macro name="name"><text value="US Court" /></macro>"#,
+        "",
+    );
+    db.set_jurisdiction_module("us", &module).unwrap();
+
+    // Also two deep on its own, but three with the module's `court`
+    let nested = with_macros(
+        &format!(
+            r#"<macro name="cite"><text macro="court" /></macro>{}"#,
+            court
+        ),
+        r#"<text macro="cite" />"#,
+    );
+    let err = db.set_style_text(&nested).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Style);
+
+    db.remove_jurisdiction_module("us");
+    db.set_style_text(&nested).unwrap();
+    let err = db.set_jurisdiction_module("us", &module).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Style);
+    assert!(db.jurisdiction_modules().is_empty());
+}
+
+#[test]
+fn legal_case_variables() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="note">
+        <features>
+            <feature name="cslm-legal-variables" />
+        </features>
+        <citation>
+            <layout>
+                <group delimiter=", ">
+                    <text variable="title" />
+                    <names variable="authority" />
+                    <text variable="court-class" />
+                    <text variable="publisher" />
+                    <text variable="number" />
+                </group>
+            </layout>
+        </citation>
+    </style>"#,
+    ));
+    let mut classes = fnv::FnvHashMap::default();
+    classes.insert("Supreme Court".into(), "supreme".into());
+    db.set_court_classes(classes);
+    let refr: Reference = serde_json::from_str(
+        r#"{
+            "id": "case",
+            "type": "legal_case",
+            "title": "Doe v. Roe",
+            "authority": "Supreme Court",
+            "publisher": "West",
+            "docket-number": "No. 12-345"
+        }"#,
+    )
+    .unwrap();
+    db.insert_reference(refr.clone());
+    let id = db.cluster_id("one");
+    db.insert_cites(id, &[Cite::basic("case")]);
+    db.set_cluster_order(&[ClusterPosition::note(id, 1)])
+        .unwrap();
+    assert_cluster!(
+        db.get_cluster(id),
+        Some("Doe v. Roe, Supreme Court, supreme, No. 12-345")
+    );
+
+    // `docket-number` is read as `number`, and written back out that way.
+    assert_eq!(NumberVariable::Number.as_ref(), "number");
+    let exported = db.export_references(&[Atom::from("case")]);
+    assert!(exported.contains(r#""number": "No. 12-345""#));
+    let reparsed: Vec<Reference> = serde_json::from_str(&exported).unwrap();
+    assert_eq!(reparsed[0].number, refr.number);
+}
+
+#[test]
+fn suppress_names() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+        <citation et-al-min="3" et-al-use-first="1">
+            <layout delimiter="; ">
+                <names variable="author">
+                    <name form="short" and="text" />
+                </names>
+            </layout>
+        </citation>
+        <bibliography>
+            <layout>
+                <names variable="author">
+                    <name form="short" and="text" />
+                </names>
+            </layout>
+        </bibliography>
+    </style>"#,
+    ));
+    let refr: Reference = serde_json::from_str(
+        r#"{
+            "id": "r1",
+            "type": "book",
+            "author": [{ "family": "Smith" }, { "family": "Jones" }, { "family": "Brown" }]
+        }"#,
+    )
+    .unwrap();
+    db.insert_reference(refr);
+    let cites: Vec<Cite<Markup>> = serde_json::from_str(
+        r#"[
+            { "id": "r1" },
+            { "id": "r1", "suppress-names": ["Smith"] },
+            { "id": "r1", "suppress-names": [0, 2] }
+        ]"#,
+    )
+    .unwrap();
+    let id = db.cluster_id("one");
+    db.insert_cites(id, &cites);
+    db.set_cluster_order(&[ClusterPosition::note(id, 1)])
+        .unwrap();
+    assert_cluster!(
+        db.get_cluster(id),
+        Some("Smith et al.; Jones and Brown; Jones")
+    );
+    // Only the cites leave them out
+    let bib = db.get_bibliography();
+    assert_eq!(bib[0].value.as_str(), "Smith, Jones, and Brown");
+}
+
+#[test]
+fn name_label_plurals() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+        <locale>
+            <terms>
+                <term name="author" form="short">
+                    <single>auth.</single>
+                    <multiple>auths.</multiple>
+                </term>
+                <term name="editor" form="verb">
+                    <single>edited by</single>
+                    <multiple>jointly edited by</multiple>
+                </term>
+            </terms>
+        </locale>
+        <citation>
+            <layout delimiter="; ">
+                <group delimiter=", ">
+                    <names variable="author">
+                        <name form="short" and="text" />
+                        <label form="short" prefix=" (" suffix=")" />
+                    </names>
+                    <names variable="editor">
+                        <label form="verb" suffix=" " />
+                        <name form="short" and="text" />
+                    </names>
+                </group>
+            </layout>
+        </citation>
+    </style>"#,
+    ));
+    let refr: Reference = serde_json::from_str(
+        r#"{
+            "id": "r1",
+            "type": "book",
+            "author": [{ "family": "Smith" }, { "family": "Jones" }],
+            "editor": [{ "family": "Brown" }]
+        }"#,
+    )
+    .unwrap();
+    db.insert_reference(refr);
+    let refr: Reference = serde_json::from_str(
+        r#"{
+            "id": "r2",
+            "type": "book",
+            "editor": [{ "family": "Brown" }, { "family": "Green" }]
+        }"#,
+    )
+    .unwrap();
+    db.insert_reference(refr);
+    let cites: Vec<Cite<Markup>> = serde_json::from_str(
+        r#"[
+            { "id": "r1" },
+            { "id": "r1", "suppress-names": ["Smith"] },
+            { "id": "r2" }
+        ]"#,
+    )
+    .unwrap();
+    let id = db.cluster_id("one");
+    db.insert_cites(id, &cites);
+    db.set_cluster_order(&[ClusterPosition::note(id, 1)])
+        .unwrap();
+    // The label counts the names that are left after suppressing some of them
+    assert_cluster!(
+        db.get_cluster(id),
+        Some(
+            "Smith and Jones (auths.), edited by Brown; Jones (auth.), edited by Brown; \
+             jointly edited by Brown and Green"
+        )
+    );
+}
+
+#[test]
+fn nested_group_delimiters() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="note">
+        <citation>
+            <layout>
+                <group delimiter=", ">
+                    <text value="A" />
+                    <group delimiter=" ">
+                        <text variable="volume" />
+                        <text variable="issue" />
+                    </group>
+                    <choose>
+                        <if type="book">
+                            <choose>
+                                <if variable="title">
+                                    <text variable="title" />
+                                    <text value="B" />
+                                </if>
+                            </choose>
+                        </if>
+                    </choose>
+                </group>
+            </layout>
+        </citation>
+    </style>"#,
+    ));
+    let mut refr = Reference::empty(Atom::from("r1"), CslType::Book);
+    refr.ordinary.insert(Variable::Title, "Title".to_string());
+    db.insert_reference(refr);
+    let id = db.cluster_id("one");
+    db.insert_cites(id, &[Cite::basic("r1")]);
+    db.set_cluster_order(&[ClusterPosition::note(id, 1)])
+        .unwrap();
+    // The empty inner group leaves no extra ", ", and the inner choose still uses the outer
+    // group's delimiter.
+    assert_cluster!(db.get_cluster(id), Some("A, Title, B"));
+}
+
+#[test]
+fn affixes_outside_text_case() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="note">
+        <macro name="title">
+            <text variable="title" />
+        </macro>
+        <citation>
+            <layout>
+                <group delimiter=" ">
+                    <text macro="title" text-case="uppercase" prefix="see " />
+                    <label variable="page" form="short" strip-periods="true" />
+                    <text variable="page" />
+                </group>
+            </layout>
+        </citation>
+    </style>"#,
+    ));
+    let mut refr = Reference::empty(Atom::from("r1"), CslType::Book);
+    refr.ordinary.insert(Variable::Title, "Title".to_string());
+    refr.number
+        .insert(NumberVariable::Page, NumberLike::Str("4-5".into()));
+    db.insert_reference(refr);
+    let id = db.cluster_id("one");
+    db.insert_cites(id, &[Cite::basic("r1")]);
+    db.set_cluster_order(&[ClusterPosition::note(id, 1)])
+        .unwrap();
+    assert_cluster!(db.get_cluster(id), Some("see TITLE pp 4–5"));
+}
+
+#[test]
+fn date_display_and_text_case() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+        <macro name="title">
+            <text variable="title" />
+        </macro>
+        <citation><layout></layout></citation>
+        <bibliography>
+            <layout>
+                <group prefix="see ">
+                    <text macro="title" text-case="uppercase" prefix="in " />
+                </group>
+                <date variable="issued" text-case="uppercase" prefix="from " display="block">
+                    <date-part name="month" />
+                    <date-part name="year" prefix=" " />
+                </date>
+            </layout>
+        </bibliography>
+    </style>"#,
+    ));
+    db.set_output_format(SupportedFormat::Html, Default::default())
+        .unwrap();
+    let mut refr = Reference::empty(Atom::from("r1"), CslType::Book);
+    refr.ordinary.insert(Variable::Title, "Title".to_string());
+    refr.date
+        .insert(DateVariable::Issued, DateOrRange::new(2000, 3, 0));
+    db.insert_reference(refr);
+    db.include_uncited(IncludeUncited::All);
+    // Neither the group's nor the elements' own affixes are cased, and the date is a block
+    let bib = db.get_bibliography();
+    assert_eq!(
+        bib[0].value.as_str(),
+        r#"see in TITLE<div class="csl-block">from MARCH 2000</div>"#
+    );
+}
+
+#[test]
+fn composite_locators() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="note">
+        <citation>
+            <layout delimiter="; ">
+                <group delimiter=" ">
+                    <text variable="title" />
+                    <label variable="locator" form="short" />
+                    <text variable="locator" />
+                </group>
+            </layout>
+        </citation>
+    </style>"#,
+    ));
+    let mut refr = Reference::empty(Atom::from("r1"), CslType::Book);
+    refr.ordinary.insert(Variable::Title, "Title".to_string());
+    db.insert_reference(refr);
+    let cites: Vec<Cite<Markup>> = serde_json::from_str(
+        r#"[
+            { "id": "r1", "locator": "12-13, 15 n. 3", "label": "page" },
+            { "id": "r1", "locator": "4, para. 2 line 7-9", "label": "chapter" }
+        ]"#,
+    )
+    .unwrap();
+    let id = db.cluster_id("one");
+    db.insert_cites(id, &cites);
+    db.set_cluster_order(&[ClusterPosition::note(id, 1)])
+        .unwrap();
+    // The cite's label only covers the first part, the rest carry their own
+    assert_cluster!(
+        db.get_cluster(id),
+        Some("Title pp. 12–13, 15 n. 3; Title chap. 4, para. 2 ll. 7–9")
+    );
+}
+
+#[test]
+fn error_kinds() {
+    let style = Processor::new(InitOptions {
+        style: "<style",
+        ..Default::default()
+    })
+    .err()
+    .unwrap();
+    assert_eq!(style.kind(), ErrorKind::Style);
+    let json: Error = serde_json::from_str::<Reference>("{").unwrap_err().into();
+    assert_eq!(json.kind(), ErrorKind::Json);
+    let fetch: Error = LocaleFetchError::Other("offline".into()).into();
+    assert_eq!(fetch.kind(), ErrorKind::LocaleFetch);
+    assert_eq!(fetch.to_string(), "could not fetch locale: offline");
+    // Bindings pass these on as plain numbers
+    assert_eq!(ErrorKind::Reordering as u32, 4);
+    assert_eq!(ErrorKind::LimitExceeded as u32, 5);
+    assert_eq!(ErrorKind::Bundle as u32, 6);
+}
+
+#[test]
+fn resource_limits() {
+    let style = r#"<style version="1.0" class="note">
+        <macro name="title"><text variable="title" /></macro>
+        <citation>
+            <layout delimiter="; ">
+                <group delimiter=" ">
+                    <text macro="title" />
+                    <text macro="title" />
+                </group>
+            </layout>
+        </citation>
+    </style>"#;
+    let too_small = Processor::new(InitOptions {
+        style,
+        test_mode: true,
+        limits: ResourceLimits {
+            max_style_bytes: Some(100),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .err()
+    .unwrap();
+    assert_eq!(too_small.kind(), ErrorKind::LimitExceeded);
+
+    let mut db = Processor::new(InitOptions {
+        style,
+        format: SupportedFormat::Plain,
+        test_mode: true,
+        limits: ResourceLimits {
+            max_macro_expansions: Some(1),
+            max_references: Some(2),
+            max_cites_per_cluster: Some(1),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .unwrap();
+    insert_basic_refs(&mut db, &["one", "two", "three"]);
+    assert_eq!(db.reference_ids().len(), 2);
+    // Replacing an existing reference is still fine
+    assert!(db.update_reference(Reference::empty(Atom::from("one"), CslType::Article)));
+    let id = db.cluster_id("one");
+    db.insert_cites(id, &[Cite::basic("two"), Cite::basic("one")]);
+    db.set_cluster_order(&[ClusterPosition::note(id, 1)])
+        .unwrap();
+    // Only the first cite is kept, and its second macro is left out
+    assert_cluster!(db.get_cluster(id), Some("Book two"));
+    let warnings = db.take_warnings();
+    assert_eq!(warnings.len(), 3);
+    assert!(warnings
+        .iter()
+        .all(|w| w.code == WarningCode::LimitExceeded));
+
+    db.set_resource_limits(ResourceLimits {
+        render_timeout: Some(std::time::Duration::from_secs(0)),
+        ..Default::default()
+    });
+    assert!(db.compute().is_empty());
+    assert_eq!(db.take_warnings()[0].code, WarningCode::LimitExceeded);
+    db.set_resource_limits(ResourceLimits::default());
+    assert_eq!(db.compute().len(), 1);
+    assert_cluster!(db.get_cluster(id), Some("Book two Book two"));
+}
+
+#[test]
+fn reproducible_across_insertion_order() {
+    let style = r#"<style version="1.0" class="in-text">
+        <citation disambiguate-add-year-suffix="true">
+            <layout delimiter="; ">
+                <group delimiter=" ">
+                    <text variable="title" />
+                    <text variable="year-suffix" />
+                </group>
+            </layout>
+        </citation>
+    </style>"#;
+    let ids = ["r1", "r2", "r3", "r4"];
+    let render = |insert_order: &[&str]| {
+        let mut db = test_db(Some(style));
+        for &id in insert_order {
+            let mut refr = Reference::empty(Atom::from(id), CslType::Book);
+            refr.ordinary.insert(Variable::Title, "Same".to_string());
+            db.insert_reference(refr);
+        }
+        insert_ascending_notes(&mut db, &ids);
+        (1..=ids.len() as u32)
+            .map(|n| {
+                let id = cid(&mut db, n);
+                db.get_cluster(id)
+            })
+            .collect::<Vec<_>>()
+    };
+    let forwards = render(&["r1", "r2", "r3", "r4"]);
+    let backwards = render(&["r4", "r3", "r2", "r1"]);
+    let shuffled = render(&["r3", "r1", "r4", "r2"]);
+    assert_eq!(forwards, backwards);
+    assert_eq!(forwards, shuffled);
+    let unique: std::collections::HashSet<_> = forwards.iter().collect();
+    assert_eq!(unique.len(), ids.len());
+}
+
+#[test]
+fn explicit_year_suffix_suppresses_implicit() {
+    use citeproc_io::DateOrRange;
+    let date = r#"<date variable="issued" form="numeric" date-parts="year" />"#;
+    let explicit = r#"<text variable="year-suffix" />"#;
+    let render = |cite_suffix: &str, bib_suffix: &str| {
+        let style = format!(
+            r#"<style version="1.0" class="in-text">
+            <citation disambiguate-add-year-suffix="true">
+                <layout delimiter="; ">
+                    <group delimiter=" ">
+                        <text variable="title" />
+                        <group>{}{}</group>
+                    </group>
+                </layout>
+            </citation>
+            <bibliography>
+                <layout>
+                    <group delimiter=" ">
+                        <text variable="title" />
+                        <group prefix="(" suffix=")">{}{}</group>
+                    </group>
+                </layout>
+            </bibliography>
+        </style>"#,
+            date, cite_suffix, date, bib_suffix
+        );
+        let mut db = test_db(Some(&style));
+        for &id in &["r1", "r2"] {
+            let mut refr = Reference::empty(Atom::from(id), CslType::Book);
+            refr.ordinary.insert(Variable::Title, "Same".to_string());
+            refr.date
+                .insert(DateVariable::Issued, DateOrRange::new(2000, 0, 0));
+            db.insert_reference(refr);
+        }
+        insert_ascending_notes(&mut db, &["r1", "r2"]);
+        let one = cid(&mut db, 1);
+        let cluster = db.get_cluster(one).unwrap().to_string();
+        let bib: Vec<_> = db
+            .get_bibliography()
+            .into_iter()
+            .map(|entry| entry.value.to_string())
+            .collect();
+        (cluster, bib)
+    };
+    // Implicit everywhere
+    assert_eq!(
+        render("", ""),
+        (
+            "Same 2000a".into(),
+            vec!["Same (2000a)".into(), "Same (2000b)".into()]
+        )
+    );
+    // Explicit in both, rendered once each
+    assert_eq!(
+        render(explicit, explicit),
+        (
+            "Same 2000a".into(),
+            vec!["Same (2000a)".into(), "Same (2000b)".into()]
+        )
+    );
+    // Explicit in the citation only: no implicit suffix in the bibliography
+    assert_eq!(
+        render(explicit, ""),
+        (
+            "Same 2000a".into(),
+            vec!["Same (2000)".into(), "Same (2000)".into()]
+        )
+    );
+    // And vice versa
+    assert_eq!(
+        render("", explicit),
+        (
+            "Same 2000".into(),
+            vec!["Same (2000a)".into(), "Same (2000b)".into()]
+        )
+    );
+}
+
+#[test]
+fn format_references() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+        <citation><layout><text variable="title" /></layout></citation>
+        <bibliography><layout><text variable="title" prefix="Bib: " /></layout></bibliography>
+    </style>"#,
+    ));
+    insert_basic_refs(&mut db, &["r1", "r2"]);
+    insert_ascending_notes(&mut db, &["r1"]);
+    let ids = [Atom::from("r2"), Atom::from("missing"), Atom::from("r1")];
+    assert_eq!(
+        db.format_references(&ids, CiteOrBib::Citation),
+        vec!["Book r2", "???", "Book r1"]
+    );
+    assert_eq!(
+        db.format_references(&ids, CiteOrBib::Bibliography),
+        vec!["Bib: Book r2", "", "Bib: Book r1"]
+    );
+    // the document is untouched
+    let one = cid(&mut db, 1);
+    assert_cluster!(db.get_cluster(one), Some("Book r1"));
+    assert_eq!(db.clusters_citing(Atom::from("r2")), vec![]);
+}
+
+#[test]
+fn rtf_needs_its_feature() {
+    let mut db = test_db(None);
+    let result = db.set_output_format(SupportedFormat::Rtf, Default::default());
+    assert_eq!(result.is_ok(), cfg!(feature = "rtf"));
+}
+
+#[test]
+fn formatted_bundle() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+        <citation><layout><text variable="title" font-style="italic" /></layout></citation>
+    </style>"#,
+    ));
+    insert_basic_refs(&mut db, &["r1"]);
+    let bundle = db.formatted_bundle(Atom::from("r1"));
+    assert_eq!(bundle.html, "<i>Book r1</i>");
+    if cfg!(feature = "rtf") {
+        assert_eq!(bundle.rtf, "{\\i Book r1}");
+    } else {
+        assert_eq!(bundle.rtf, "");
+    }
+    assert_eq!(bundle.plain, "Book r1");
+    assert!(db.all_clusters().is_empty());
+}
+
+#[test]
+fn name_term_overrides() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+        <citation et-al-min="3" et-al-use-first="1">
+            <layout delimiter="; ">
+                <names variable="author">
+                    <name form="short" and="text" />
+                </names>
+            </layout>
+        </citation>
+    </style>"#,
+    ));
+    let refs: Vec<Reference> = serde_json::from_str(
+        r#"[
+            {"id": "two", "type": "book", "author": [{"family": "Smith"}, {"family": "Jones"}]},
+            {"id": "three", "type": "book", "author": [
+                {"family": "Smith"}, {"family": "Jones"}, {"family": "Brown"}
+            ]}
+        ]"#,
+    )
+    .unwrap();
+    db.reset_references(refs);
+    insert_ascending_notes(&mut db, &["two", "three"]);
+    let one = cid(&mut db, 1);
+    let two = cid(&mut db, 2);
+    assert_cluster!(db.get_cluster(one), Some("Smith and Jones"));
+    assert_cluster!(db.get_cluster(two), Some("Smith et al."));
+    db.set_name_term_overrides(NameTermOverrides {
+        and: Some(NameAnd::Symbol),
+        et_al: Some(EtAlTerm::AndOthers),
+    });
+    assert_cluster!(db.get_cluster(one), Some("Smith & Jones"));
+    assert_cluster!(db.get_cluster(two), Some("Smith and others"));
+}
+
+#[test]
+fn case_exceptions() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+        <citation>
+            <layout delimiter="; ">
+                <text variable="title" text-case="title" />
+            </layout>
+        </citation>
+    </style>"#,
+    ));
+    let refs: Vec<Reference> = serde_json::from_str(
+        r#"[
+            {"id": "one", "type": "book", "title": "building ios apps with mrna"},
+            {"id": "two", "type": "book", "title": "a history of the iphone"}
+        ]"#,
+    )
+    .unwrap();
+    db.reset_references(refs);
+    insert_ascending_notes(&mut db, &["one", "two"]);
+    let one = cid(&mut db, 1);
+    let two = cid(&mut db, 2);
+    assert_cluster!(db.get_cluster(one), Some("Building Ios Apps with Mrna"));
+    assert_cluster!(db.get_cluster(two), Some("A History of the Iphone"));
+    db.set_case_exceptions(CaseExceptions::new(&["iOS", "mRNA"]));
+    assert_cluster!(db.get_cluster(one), Some("Building iOS Apps with mRNA"));
+    assert_cluster!(db.get_cluster(two), Some("A History of the Iphone"));
+    db.set_case_exceptions(CaseExceptions::new(&["iPhone"]));
+    assert_cluster!(db.get_cluster(one), Some("Building Ios Apps with Mrna"));
+    assert_cluster!(db.get_cluster(two), Some("A History of the iPhone"));
+}
+
+#[test]
+fn compat_mode() {
+    let style = r#"<style version="1.0" class="in-text">
+        <citation><layout suffix=".">
+            <text variable="title" quotes="true" />
+            <choose>
+                <if is-numeric="page-first"><text value="numeric" prefix=" " /></if>
+            </choose>
+        </layout></citation>
+    </style>"#;
+    let mut locales = HashMap::new();
+    locales.insert(
+        Lang::en_us(),
+        r#"<locale xml:lang="en-US"><terms></terms></locale>"#.to_string(),
+    );
+    let mut db = Processor::new(InitOptions {
+        style,
+        format: SupportedFormat::Plain,
+        fetcher: Some(Arc::new(citeproc_db::PredefinedLocales(locales))),
+        test_mode: true,
+        ..Default::default()
+    })
+    .unwrap();
+    let mut refr = Reference::empty(Atom::from("r1"), CslType::Book);
+    refr.ordinary.insert(Variable::Title, "Title".to_string());
+    refr.number
+        .insert(NumberVariable::Page, NumberLike::Str("12-15".into()));
+    db.insert_reference(refr);
+    let id = db.cluster_id("one");
+    db.insert_cites(id, &[Cite::basic("r1")]);
+    db.set_cluster_order(&[ClusterPosition::in_text(id)])
+        .unwrap();
+    assert_cluster!(db.get_cluster(id), Some("\u{201C}Title\u{201D} numeric."));
+    db.set_compat_mode(CompatMode::CiteprocJs);
+    assert_cluster!(db.get_cluster(id), Some("\u{201C}Title.\u{201D}"));
+}
+
+#[test]
+fn role_terms() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+        <citation>
+            <layout delimiter="; ">
+                <names variable="curator">
+                    <name form="short" and="text" />
+                    <label prefix=", " />
+                </names>
+                <names variable="script-writer">
+                    <label form="verb" suffix=" " />
+                    <name form="short" />
+                </names>
+                <names variable="composer">
+                    <name form="short" />
+                    <label form="short" prefix=" (" suffix=")" />
+                </names>
+                <names variable="collection-editor">
+                    <label form="verb-short" suffix=" " />
+                    <name form="short" />
+                </names>
+            </layout>
+        </citation>
+    </style>"#,
+    ));
+    let refr: Reference = serde_json::from_str(
+        r#"{
+            "id": "r1",
+            "type": "motion_picture",
+            "curator": [{ "family": "Ng" }, { "family": "Ito" }],
+            "script-writer": [{ "family": "Kaur" }],
+            "composer": [{ "family": "Okafor" }],
+            "collection-editor": [{ "family": "Silva" }]
+        }"#,
+    )
+    .unwrap();
+    db.insert_reference(refr);
+    insert_ascending_notes(&mut db, &["r1"]);
+    let one = cid(&mut db, 1);
+    assert_cluster!(
+        db.get_cluster(one),
+        Some("Ng and Ito, curators; written by Kaur; Okafor (comp.); ed. by Silva")
+    );
+}
+
+#[test]
+fn role_substitution_order() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+        <macro name="author">
+            <names variable="author">
+                <name and="text" />
+                <label form="short" prefix=" (" suffix=")" />
+                <substitute>
+                    <names variable="container-author" />
+                    <names variable="editor" />
+                    <names variable="reviewed-author" />
+                    <names variable="original-author" />
+                    <names variable="host" />
+                    <text variable="title" />
+                </substitute>
+            </names>
+        </macro>
+        <citation>
+            <layout delimiter="; ">
+                <group delimiter=", ">
+                    <text macro="author" />
+                    <names variable="editor">
+                        <label form="verb-short" suffix=" " />
+                        <name />
+                    </names>
+                </group>
+            </layout>
+        </citation>
+    </style>"#,
+    ));
+    let refs: Vec<Reference> = serde_json::from_str(
+        r#"[
+            {"id": "a", "type": "chapter", "container-author": [{"family": "Ng"}], "editor": [{"family": "Ito"}]},
+            {"id": "b", "type": "review", "editor": [{"family": "Ito"}], "reviewed-author": [{"family": "Kaur"}]},
+            {"id": "c", "type": "review", "reviewed-author": [{"family": "Kaur"}], "original-author": [{"family": "Okafor"}]},
+            {"id": "d", "type": "book", "original-author": [{"family": "Okafor"}]},
+            {"id": "e", "type": "broadcast", "host": [{"family": "Silva"}, {"family": "Lee"}]},
+            {"id": "f", "type": "book", "title": "Untitled"}
+        ]"#,
+    )
+    .unwrap();
+    db.reset_references(refs);
+    insert_ascending_notes(&mut db, &["a", "b", "c", "d", "e", "f"]);
+    let rendered: Vec<_> = (1..=6)
+        .map(|n| {
+            let id = cid(&mut db, n);
+            db.get_cluster(id).map(|c| c.to_string())
+        })
+        .collect();
+    // The first role present stands in for the author, takes its own label, and is not
+    // rendered again later in the cite.
+    assert_eq!(
+        rendered,
+        vec![
+            Some("Ng, ed. by Ito".to_owned()),
+            Some("Ito (ed.)".to_owned()),
+            Some("Kaur".to_owned()),
+            Some("Okafor".to_owned()),
+            Some("Silva and Lee (hosts)".to_owned()),
+            Some("Untitled".to_owned()),
+        ]
+    );
+}
+
+#[test]
+fn original_variables() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+        <citation><layout></layout></citation>
+        <bibliography>
+            <sort><key variable="issued" /></sort>
+            <layout>
+                <group delimiter=" ">
+                    <text variable="title" />
+                    <date variable="issued" form="numeric" date-parts="year" prefix="(" suffix=")" />
+                    <choose>
+                        <if variable="original-date">
+                            <group delimiter=", " prefix="[" suffix="]">
+                                <text variable="original-title" font-style="italic" />
+                                <text variable="original-publisher-place" />
+                                <text variable="original-publisher" />
+                                <date variable="original-date" form="numeric" date-parts="year" />
+                            </group>
+                        </if>
+                    </choose>
+                </group>
+            </layout>
+        </bibliography>
+    </style>"#,
+    ));
+    let reprint: Reference = serde_json::from_str(
+        r#"{
+            "id": "reprint",
+            "type": "book",
+            "title": "War and Peace",
+            "issued": { "date-parts": [[1990]] },
+            "original-date": { "date-parts": [[1869]] },
+            "original-title": "Война и мир",
+            "original-publisher": "The Russian Messenger",
+            "original-publisher-place": "Moscow"
+        }"#,
+    )
+    .unwrap();
+    let modern: Reference = serde_json::from_str(
+        r#"{ "id": "modern", "type": "book", "title": "Tolstoy", "issued": { "date-parts": [[1950]] } }"#,
+    )
+    .unwrap();
+    db.extend_references(vec![reprint, modern]);
+    db.include_uncited(IncludeUncited::All);
+    let bib = |db: &Processor| -> Vec<String> {
+        db.get_bibliography()
+            .into_iter()
+            .map(|entry| entry.value.to_string())
+            .collect()
+    };
+    let war_and_peace = "War and Peace (1990) [Война и мир, Moscow, The Russian Messenger, 1869]";
+    assert_eq!(bib(&db), vec!["Tolstoy (1950)", war_and_peace]);
+    db.set_reprint_sort(ReprintSort::Original);
+    assert_eq!(bib(&db), vec![war_and_peace, "Tolstoy (1950)"]);
+}
+
+#[test]
+fn entry_decorations() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+        <citation><layout></layout></citation>
+        <bibliography>
+            <sort><key variable="title" /></sort>
+            <layout suffix="."><text variable="title" /></layout>
+        </bibliography>
+    </style>"#,
+    ));
+    for (id, title) in &[("r1", "A"), ("r2", "B")] {
+        let mut refr = Reference::empty(Atom::from(*id), CslType::Book);
+        refr.ordinary.insert(Variable::Title, title.to_string());
+        db.insert_reference(refr);
+    }
+    db.include_uncited(IncludeUncited::All);
+    db.batched_updates();
+    let r2 = Atom::from("r2");
+    db.set_entry_decoration(
+        r2.clone(),
+        Some(EntryDecoration {
+            prefix: Some("[Online] ".into()),
+            suffix: Some(" QA76.73".into()),
+        }),
+    );
+    let update = db.batched_updates().bibliography.unwrap();
+    assert_eq!(update.entry_ids, None);
+    assert_eq!(update.updated_entries.len(), 1);
+    assert_eq!(update.updated_entries[&r2].as_str(), "[Online] B. QA76.73");
+    db.set_entry_decoration(r2.clone(), None);
+    let update = db.batched_updates().bibliography.unwrap();
+    assert_eq!(update.updated_entries[&r2].as_str(), "B.");
+}
+
+#[test]
+fn invalidation_tracing() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+            <citation><layout><text variable="title" /></layout></citation>
+        </style>"#,
+    ));
+    insert_basic_refs(&mut db, &["one", "two"]);
+    let c1 = cid(&mut db, 1);
+    let c2 = cid(&mut db, 2);
+    db.insert_cites(c1, &[Cite::basic("one")]);
+    db.insert_cites(c2, &[Cite::basic("two")]);
+    db.set_cluster_order(&[ClusterPosition::in_text(c1), ClusterPosition::in_text(c2)])
+        .unwrap();
+    assert!(db.batched_updates().causes.is_none());
+
+    db.set_invalidation_tracing(true);
+    let mut refr = Reference::empty(Atom::from("two"), CslType::Book);
+    refr.ordinary.insert(Variable::Title, "Renamed".to_string());
+    db.insert_reference(refr);
+    let summary = db.batched_updates();
+    assert_eq!(
+        summary.causes,
+        Some(vec![(c2, vec![InputChange::Reference(Atom::from("two"))])])
+    );
+
+    db.insert_cites(c1, &[Cite::basic("two")]);
+    let summary = db.batched_updates_str();
+    assert_eq!(
+        summary.causes,
+        Some(vec![(
+            SmartString::from("1"),
+            vec![InputChange::Cluster("1".into())]
+        )])
+    );
+    assert!(db.batched_updates().causes.unwrap().is_empty());
+
+    // Never interned, so it goes by its number
+    let numeric = ClusterId::new(1000);
+    db.insert_cites(numeric, &[Cite::basic("one")]);
+    db.set_cluster_order(&[
+        ClusterPosition::in_text(c1),
+        ClusterPosition::in_text(c2),
+        ClusterPosition::in_text(numeric),
+    ])
+    .unwrap();
+    let causes = db.batched_updates().causes.unwrap();
+    let (_, why) = causes.iter().find(|(id, _)| *id == numeric).unwrap();
+    assert!(why.contains(&InputChange::Cluster("#1000".into())));
+}
+
+#[test]
+fn entry_anchors() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+        <citation><layout></layout></citation>
+        <bibliography>
+            <layout><text variable="title" /></layout>
+        </bibliography>
+    </style>"#,
+    ));
+    db.set_output_format(SupportedFormat::Html, Default::default())
+        .unwrap();
+    let mut refr = Reference::empty(Atom::from("Smith 2000_a"), CslType::Book);
+    refr.ordinary.insert(Variable::Title, "A".to_string());
+    db.insert_reference(refr.clone());
+    db.include_uncited(IncludeUncited::All);
+    let bib = db.get_bibliography();
+    assert_eq!(bib[0].anchor, "ref-Smith_202000_5fa");
+    assert_eq!(bib[0].value.as_str(), "A");
+    let mut options = FormatOptions::default();
+    options.html.entry_anchors = true;
+    db.set_output_format(SupportedFormat::Html, options)
+        .unwrap();
+    let bib = db.get_bibliography();
+    assert_eq!(bib[0].anchor, "ref-Smith_202000_5fa");
+    assert_eq!(
+        bib[0].value.as_str(),
+        r#"<a id="ref-Smith_202000_5fa"></a>A"#
+    );
+    // Previews have nothing to link to
+    assert_eq!(db.preview_reference(refr, None).as_str(), "A");
+}
+
+#[test]
+fn journal_replay() {
+    let style = r#"<style version="1.0" class="note">
+        <citation>
+            <layout delimiter="; ">
+                <text variable="title" />
+            </layout>
+        </citation>
+    </style>"#;
+    let mut db = test_db(Some(style));
+    db.set_journaling(true);
+    insert_basic_refs(&mut db, &["one", "two", "three"]);
+    db.remove_reference(Atom::from("three"));
+    insert_ascending_notes(&mut db, &["one", "two"]);
+    let one = cid(&mut db, 1);
+    let two = cid(&mut db, 2);
+    db.merge_clusters(&[one, two]).unwrap();
+    let (_, second) = db.split_cluster(one, 1).unwrap();
+    // Previews leave no trace
+    db.preview_citation_cluster(
+        PreviewCluster::new(vec![Cite::basic("one")], None),
+        PreviewPosition::ReplaceCluster(one),
+        None,
+    )
+    .unwrap();
+
+    let journal = db.journal();
+    assert_eq!(journal.entries.len(), 12);
+    let json = journal.to_json();
+    let mut replayed = test_db(Some(style));
+    replayed.set_journaling(true);
+    Journal::from_json(&json)
+        .unwrap()
+        .replay(&mut replayed)
+        .unwrap();
+    assert_eq!(replayed.reference_ids(), db.reference_ids());
+    let second_name = db.lookup_cluster_id(second).unwrap();
+    for name in &["1", "2", second_name.as_str()] {
+        let a = db.cluster_id(name);
+        let b = replayed.cluster_id(name);
+        assert_eq!(replayed.get_cluster(b), db.get_cluster(a));
+    }
+    assert_cluster!(
+        replayed.get_cluster(cid(&mut replayed, 1)),
+        Some("Book one")
+    );
+    // Replaying records the same edits again
+    assert_eq!(replayed.journal(), journal);
+}
+
+#[test]
+fn journal_replay_numeric_ids_and_locators() {
+    let style = r#"<style version="1.0" class="note">
+        <citation>
+            <layout delimiter="; ">
+                <text variable="title" />
+                <text variable="locator" prefix=" at " />
+            </layout>
+        </citation>
+    </style>"#;
+    let mut db = test_db(Some(style));
+    db.set_journaling(true);
+    insert_basic_refs(&mut db, &["one"]);
+    let mut cite = Cite::basic("one");
+    cite.locators = Some(Locators::Multiple {
+        locators: vec![
+            Locator {
+                locator: NumberLike::Str("19".into()),
+                loc_type: LocatorType::Chapter,
+            },
+            Locator {
+                locator: NumberLike::Str("581".into()),
+                loc_type: LocatorType::Page,
+            },
+        ],
+    });
+    // Never interned
+    let id = ClusterId::new(1000);
+    db.insert_cites(id, &[cite.clone()]);
+    db.set_cluster_order(&[ClusterPosition::note(id, 1)])
+        .unwrap();
+
+    let json = db.journal().to_json();
+    let mut replayed = test_db(Some(style));
+    Journal::from_json(&json)
+        .unwrap()
+        .replay(&mut replayed)
+        .unwrap();
+    let replayed_id = replayed.cluster_id("#1000");
+    let cites: Vec<Cite<Markup>> = replayed
+        .cluster_cites(replayed_id)
+        .iter()
+        .map(|cite_id| Cite::clone(&cite_id.lookup(&replayed)))
+        .collect();
+    assert_eq!(cites, vec![cite]);
+    assert_eq!(replayed.get_cluster(replayed_id), db.get_cluster(id));
+}
+
+#[test]
+fn checkpoint_revert() {
+    let style = r#"<style version="1.0" class="note">
+        <citation>
+            <layout delimiter="; ">
+                <text variable="title" />
+            </layout>
+        </citation>
+    </style>"#;
+    let mut db = test_db(Some(style));
+    insert_basic_refs(&mut db, &["one", "two"]);
+    insert_ascending_notes(&mut db, &["one", "two"]);
+    db.batched_updates();
+    let before = db.checkpoint();
+
+    let mut refr = Reference::empty(Atom::from("one"), CslType::Book);
+    refr.ordinary.insert(Variable::Title, "Edited".to_string());
+    db.insert_reference(refr);
+    let three = cid(&mut db, 3);
+    db.insert_cites(three, &[Cite::basic("two")]);
+    db.set_cluster_order(&[
+        ClusterPosition::note(three, 1),
+        ClusterPosition::note(cid(&mut db, 1), 2),
+    ])
+    .unwrap();
+    db.batched_updates();
+    let after = db.checkpoint();
+
+    // Undo
+    let summary = db.revert_to(&before);
+    let one = cid(&mut db, 1);
+    let two = cid(&mut db, 2);
+    let mut updated: Vec<ClusterId> = summary.clusters.iter().map(|(id, _)| *id).collect();
+    updated.sort();
+    let mut expected = vec![one, two];
+    expected.sort();
+    assert_eq!(updated, expected);
+    assert_cluster!(db.get_cluster(one), Some("Book one"));
+    assert_cluster!(db.get_cluster(two), Some("Book two"));
+    assert_cluster!(db.get_cluster(three), None);
+    // Nothing left to do
+    assert!(db.revert_to(&before).clusters.is_empty());
+
+    // Redo
+    db.revert_to(&after);
+    assert_cluster!(db.get_cluster(one), Some("Edited"));
+    assert_cluster!(db.get_cluster(three), Some("Book two"));
+    assert_cluster!(db.get_cluster(two), None);
+}
+
+#[test]
+fn checkpoint_revert_after_compute() {
+    let style = r#"<style version="1.0" class="note">
+        <citation>
+            <layout delimiter="; ">
+                <text variable="title" />
+            </layout>
+        </citation>
+    </style>"#;
+    let mut db = test_db(Some(style));
+    insert_basic_refs(&mut db, &["one", "two"]);
+    insert_ascending_notes(&mut db, &["one"]);
+    db.batched_updates();
+    let before = db.checkpoint();
+
+    let one = cid(&mut db, 1);
+    db.insert_cites(one, &[Cite::basic("two")]);
+    // Collects the cites that only the checkpoint still has
+    db.compute();
+    db.insert_cites(one, &[Cite::basic("two"), Cite::basic("two")]);
+    db.compute();
+
+    let summary = db.revert_to(&before);
+    assert_eq!(summary.clusters.len(), 1);
+    assert_cluster!(db.get_cluster(one), Some("Book one"));
+}
+
+#[test]
+fn style_bundle_round_trip() {
+    let style = r#"<style version="1.0" class="in-text">
+        <features><feature name="conditions" /></features>
+        <locale><terms><term name="and">und</term></terms></locale>
+        <macro name="author">
+            <names variable="author">
+                <name and="text" initialize-with=". " />
+                <substitute><text variable="title" font-style="italic" /></substitute>
+            </names>
+        </macro>
+        <citation>
+            <sort><key macro="author" /></sort>
+            <layout delimiter="; " prefix="(" suffix=")">
+                <group delimiter=", ">
+                    <text macro="author" />
+                    <choose>
+                        <if type="book"><date variable="issued" form="numeric" date-parts="year" /></if>
+                        <else><text term="no date" form="short" /></else>
+                    </choose>
+                </group>
+            </layout>
+        </citation>
+    </style>"#;
+    let mut db = test_db(Some(style));
+    let refs: Vec<Reference> = serde_json::from_str(
+        r#"[
+            {"id": "a", "type": "book", "author": [{"given": "Jane", "family": "Smith"}, {"given": "Li", "family": "Wei"}], "issued": {"date-parts": [[2001, 4]]}},
+            {"id": "b", "type": "article", "title": "Untitled"}
+        ]"#,
+    )
+    .unwrap();
+    db.reset_references(refs);
+    let bytes = db.style_bundle().to_bytes();
+
+    let bundle = StyleBundle::from_bytes(&bytes).unwrap();
+    assert_eq!(bundle, db.style_bundle());
+    let mut loaded = Processor::from_bundle(
+        bundle,
+        InitOptions {
+            format: SupportedFormat::Plain,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    for processor in &mut [&mut db, &mut loaded] {
+        let id = processor.cluster_id("c1");
+        processor.insert_cites(id, &[Cite::basic("a"), Cite::basic("b")]);
+        processor
+            .set_cluster_order(&[ClusterPosition::in_text(id)])
+            .unwrap();
+    }
+    let id = loaded.cluster_id("c1");
+    assert_cluster!(
+        loaded.get_cluster(id),
+        Some("(J. Smith und L. Wei, 2001; Untitled, n.d.)")
+    );
+    assert_eq!(loaded.get_cluster(id), db.get_cluster(db.cluster_id("c1")));
+
+    let mut other_version = bytes.clone();
+    other_version[4] = other_version[4].wrapping_add(1);
+    let err = StyleBundle::from_bytes(&other_version).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Bundle);
+    assert!(StyleBundle::from_bytes(b"<style />").is_err());
+    assert!(StyleBundle::from_bytes(&bytes[..bytes.len() / 2]).is_err());
+}
+
+#[test]
+fn style_bundle_checks_style() {
+    let style = r#"<style version="1.0" class="in-text">
+        <macro name="a"><text macro="b" /></macro>
+        <macro name="b"><text value="b" /></macro>
+        <citation><layout><text macro="a" /></layout></citation>
+    </style>"#;
+    let bundle = test_db(Some(style)).style_bundle();
+    let load = |bundle: StyleBundle, options: InitOptions| {
+        Processor::from_bundle(bundle, options)
+            .err()
+            .map(|e| e.kind())
+    };
+    assert_eq!(load(bundle.clone(), InitOptions::default()), None);
+
+    let too_deep = InitOptions {
+        max_macro_depth: Some(1),
+        ..Default::default()
+    };
+    assert_eq!(load(bundle.clone(), too_deep), Some(ErrorKind::Style));
+
+    let too_large = InitOptions {
+        limits: ResourceLimits {
+            max_style_bytes: Some(10),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    assert_eq!(
+        load(bundle.clone(), too_large),
+        Some(ErrorKind::LimitExceeded)
+    );
+
+    // Bundles can be edited by hand, so a cycle that could never be parsed is caught too.
+    let mut cyclic = (*bundle.style).clone();
+    let calls_b = cyclic.macros["a"].clone();
+    cyclic.macros.insert("b".into(), calls_b);
+    let cyclic = StyleBundle {
+        style: Arc::new(cyclic),
+        references: Vec::new(),
+    };
+    assert_eq!(load(cyclic, InitOptions::default()), Some(ErrorKind::Style));
+}
+
+#[test]
+fn style_pack() {
+    let parent = r#"<style version="1.0" class="in-text">
+        <info>
+            <id>http://www.zotero.org/styles/parent</id>
+            <title>Parent Style</title>
+            <updated>2020-01-01T00:00:00Z</updated>
+        </info>
+        <citation><layout><text term="and" /></layout></citation>
+    </style>"#;
+    let dependent = r#"<style version="1.0" class="in-text" default-locale="fr-FR">
+        <info>
+            <id>http://www.zotero.org/styles/dependent</id>
+            <title>Dependent Style</title>
+            <updated>2020-01-01T00:00:00Z</updated>
+            <link rel="independent-parent" href="http://www.zotero.org/styles/parent" />
+        </info>
+    </style>"#;
+    let fr_fr = r#"<locale xml:lang="fr-FR"><terms><term name="and">et</term></terms></locale>"#;
+    let mut builder = StylePackBuilder::default();
+    builder.add_style("parent", parent).unwrap();
+    builder.add_style("dependent", dependent).unwrap();
+    builder
+        .add_locale("fr-FR".parse().unwrap(), fr_fr.to_owned())
+        .unwrap();
+    assert!(builder.add_style("broken", "<style").is_err());
+    let bytes = builder.build();
+
+    let pack = StylePack::from_bytes(&bytes).unwrap();
+    let names: Vec<&str> = pack.styles().iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, vec!["parent", "dependent"]);
+    assert_eq!(
+        pack.get("http://www.zotero.org/styles/dependent")
+            .map(|s| s.name.as_str()),
+        Some("dependent")
+    );
+
+    let render = |style_id: &str| {
+        let mut db = Processor::from_style_pack(&bytes, style_id, InitOptions::default()).unwrap();
+        db.insert_reference(Reference::empty(Atom::from("r1"), CslType::Book));
+        let id = db.cluster_id("c1");
+        db.insert_cites(id, &[Cite::basic("r1")]);
+        db.set_cluster_order(&[ClusterPosition::in_text(id)])
+            .unwrap();
+        (
+            db.get_cluster(id).unwrap().to_string(),
+            db.style_info().dependent.map(|d| d.id),
+        )
+    };
+    assert_eq!(render("parent"), ("and".to_string(), None));
+    assert_eq!(
+        render("dependent"),
+        (
+            "et".to_string(),
+            Some("http://www.zotero.org/styles/dependent".to_string())
+        )
+    );
+
+    let missing = Processor::from_style_pack(&bytes, "missing", InitOptions::default());
+    assert_eq!(missing.err().map(|e| e.kind()), Some(ErrorKind::Bundle));
+    assert!(StylePack::from_bytes(&bytes[..12]).is_err());
+
+    // Replacing a style leaves nothing of the old one behind.
+    let pack_len = |times: usize| {
+        let mut builder = StylePackBuilder::default();
+        for _ in 0..times {
+            builder.add_style("parent", parent).unwrap();
+        }
+        builder.build().len()
+    };
+    assert_eq!(pack_len(2), pack_len(1));
+}
+
+#[test]
+fn style_registry() {
+    let style = |id: &str, title: &str, extra: &str| {
+        format!(
+            r#"<style version="1.0" class="in-text">
+                <info>
+                    <id>http://www.zotero.org/styles/{}</id>
+                    <title>{}</title>
+                    <updated>2020-01-01T00:00:00Z</updated>
+                    {}
+                </info>
+                <citation><layout><text variable="title" /></layout></citation>
+            </style>"#,
+            id, title, extra
+        )
+    };
+    let dir = std::env::temp_dir().join(format!("citeproc-style-registry-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("dependent")).unwrap();
+    let write = |path: &str, xml: &str| std::fs::write(dir.join(path), xml).unwrap();
+    write(
+        "history-journal.csl",
+        &style(
+            "history-journal",
+            "Journal of Modern History",
+            r#"<category citation-format="note" /><category field="history" /><issn>0022-2801</issn>"#,
+        ),
+    );
+    write(
+        "apa.csl",
+        &style(
+            "apa",
+            "American Psychological Association 7th edition",
+            r#"<title-short>APA</title-short><category citation-format="author-date" /><category field="psychology" />"#,
+        ),
+    );
+    write(
+        "dependent/psych-letters.csl",
+        &style(
+            "psych-letters",
+            "Psychology Letters",
+            r#"<link rel="independent-parent" href="http://www.zotero.org/styles/apa" /><category citation-format="author-date" /><category field="psychology" /><eissn>1234-567x</eissn>"#,
+        ),
+    );
+    write("broken.csl", "<style");
+    write("README.md", "not a style");
+
+    let mut registry = StyleRegistry::new();
+    let failed = registry.add_dir(&dir).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(failed.len(), 1);
+    assert!(failed[0].0.ends_with("broken.csl"));
+    assert_eq!(registry.entries().len(), 3);
+
+    let names = |query: StyleQuery| -> Vec<String> {
+        registry
+            .search(&query)
+            .iter()
+            .map(|e| e.name.clone())
+            .collect()
+    };
+    assert_eq!(
+        names(StyleQuery::default()),
+        vec!["apa", "history-journal", "psych-letters"]
+    );
+    assert_eq!(
+        names(StyleQuery {
+            title: Some("apa".into()),
+            ..Default::default()
+        }),
+        vec!["apa"]
+    );
+    assert_eq!(
+        names(StyleQuery {
+            title: Some("modern JOURNAL".into()),
+            ..Default::default()
+        }),
+        vec!["history-journal"]
+    );
+    assert_eq!(
+        names(StyleQuery {
+            issn: Some("1234567X".into()),
+            ..Default::default()
+        }),
+        vec!["psych-letters"]
+    );
+    assert_eq!(
+        names(StyleQuery {
+            field: Some(Category::Psychology),
+            citation_format: Some(CitationFormat::AuthorDate),
+            ..Default::default()
+        }),
+        vec!["apa", "psych-letters"]
+    );
+    assert_eq!(
+        names(StyleQuery {
+            field: Some(Category::Psychology),
+            independent_only: true,
+            ..Default::default()
+        }),
+        vec!["apa"]
+    );
+    let dependent = registry
+        .get("http://www.zotero.org/styles/psych-letters")
+        .unwrap();
+    assert!(dependent.is_dependent());
+    let path = dependent.path.as_ref().unwrap();
+    assert!(path.ends_with("dependent/psych-letters.csl"));
+
+    let journal = registry.for_issn("1234-567X").unwrap();
+    assert_eq!(journal.style.name, "psych-letters");
+    assert_eq!(journal.independent.name, "apa");
+    let journal = registry.for_issn("00222801").unwrap();
+    assert_eq!(journal.style, journal.independent);
+    assert_eq!(registry.for_issn("9999-9999"), None);
+
+    let mut builder = StylePackBuilder::default();
+    builder
+        .add_style("from-pack", &style("from-pack", "Packed Style", ""))
+        .unwrap();
+    let bytes = builder.build();
+    registry.add_pack(&StylePack::from_bytes(&bytes).unwrap());
+    assert_eq!(registry.get("from-pack").unwrap().path, None);
+    assert_eq!(registry.entries().len(), 4);
+}
+
+#[test]
+fn variable_coverage() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="note">
+            <macro name="author">
+                <names variable="author">
+                    <substitute>
+                        <names variable="editor" />
+                        <text variable="title" form="short" />
+                    </substitute>
+                </names>
+            </macro>
+            <citation>
+                <layout>
+                    <text macro="author" />
+                    <choose>
+                        <if type="book">
+                            <text variable="publisher" />
+                            <date variable="issued" form="numeric" />
+                        </if>
+                        <else>
+                            <text variable="container-title" />
+                        </else>
+                    </choose>
+                    <number variable="page" />
+                </layout>
+            </citation>
+        </style>"#,
+    ));
+    let mut book = Reference::empty(Atom::from("book"), CslType::Book);
+    book.ordinary.insert(Variable::Title, "Title".into());
+    book.ordinary.insert(Variable::Archive, "Archive".into());
+    book.ordinary
+        .insert(Variable::ContainerTitle, "Container".into());
+    let editor = citeproc_io::Name::literal("Editor".into());
+    book.name.insert(NameVariable::Editor, vec![editor]);
+    db.insert_reference(book);
+
+    let coverage = db.variable_coverage(&[Atom::from("book"), Atom::from("missing")]);
+    assert_eq!(coverage.len(), 1);
+    let coverage = &coverage[0];
+    assert_eq!(coverage.ref_id, Atom::from("book"));
+    // The editor substitutes for the author, so the title is not reached
+    assert_eq!(coverage.used, vec![AnyVariable::Name(NameVariable::Editor)]);
+    assert_eq!(
+        coverage.ignored,
+        vec![
+            AnyVariable::Ordinary(Variable::Archive),
+            AnyVariable::Ordinary(Variable::ContainerTitle),
+            AnyVariable::Ordinary(Variable::Title),
+        ]
+    );
+    assert_eq!(
+        coverage.missing,
+        vec![
+            AnyVariable::Name(NameVariable::Author),
+            AnyVariable::Ordinary(Variable::Publisher),
+            AnyVariable::Date(DateVariable::Issued),
+            AnyVariable::Number(NumberVariable::Page),
+        ]
+    );
+}
+
+#[test]
+fn style_preview() {
+    let style = r#"<style version="1.0" class="in-text">
+        <macro name="title"><text variable="title" /></macro>
+        <citation><layout><text macro="title" /></layout></citation>
+    </style>"#;
+    let mut a = Reference::empty(Atom::from("a"), CslType::Book);
+    a.ordinary.insert(Variable::Title, "First".into());
+    let mut b = Reference::empty(Atom::from("b"), CslType::Book);
+    b.ordinary.insert(Variable::Title, "Second".into());
+    let options = InitOptions {
+        style,
+        test_mode: true,
+        ..Default::default()
+    };
+    let mut preview = StylePreview::new(options, vec![a, b]).unwrap();
+    let render = preview.render();
+    assert_eq!(render.citations, vec!["First", "Second"]);
+    assert!(render.bibliography.is_empty());
+
+    preview
+        .set_macro(
+            "title",
+            r#"<text variable="title" text-case="uppercase" />"#,
+        )
+        .unwrap();
+    assert_eq!(preview.render().citations, vec!["FIRST", "SECOND"]);
+
+    // A new macro goes before <citation>, where the bibliography can use it
+    preview
+        .set_macro("bib", r#"<text variable="title" prefix="Bib: " />"#)
+        .unwrap();
+    let with_bib = preview.source().replace(
+        "</style>",
+        r#"<bibliography><layout><text macro="bib" /></layout></bibliography></style>"#,
+    );
+    preview.set_style(&with_bib).unwrap();
+    assert_eq!(
+        preview.render().bibliography,
+        vec!["Bib: First", "Bib: Second"]
+    );
+
+    // A broken edit is kept as the source, and the last good style goes on rendering
+    let broken = preview.set_macro("title", r#"<text variable="title""#);
+    assert!(matches!(broken, Err(Error::Style(_))));
+    assert!(preview.source().contains("<bibliography>"));
+    let broken = preview.set_style("<style>");
+    assert!(matches!(broken, Err(Error::Style(_))));
+    assert_eq!(preview.source(), "<style>");
+    assert_eq!(preview.render().citations, vec!["FIRST", "SECOND"]);
+
+    // Macro edits go on from the last good style
+    preview
+        .set_macro("title", r#"<text variable="title" prefix="T: " />"#)
+        .unwrap();
+    assert!(preview.source().contains("<bibliography>"));
+    let render = preview.render();
+    assert_eq!(render.citations, vec!["T: First", "T: Second"]);
+    assert_eq!(render.bibliography, vec!["Bib: First", "Bib: Second"]);
+}
+
+#[test]
+fn render_macro() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+            <macro name="author">
+                <names variable="author"><name initialize-with=". " /></names>
+            </macro>
+            <macro name="where">
+                <choose>
+                    <if context="citation"><text value="cite" /></if>
+                    <else><text value="bib" /></else>
+                </choose>
+            </macro>
+            <macro name="nothing"><text variable="publisher" /></macro>
+            <citation et-al-min="3" et-al-use-first="1">
+                <layout><text macro="author" /></layout>
+            </citation>
+            <bibliography><layout><text macro="author" /></layout></bibliography>
+        </style>"#,
+    ));
+    let refr: Reference = serde_json::from_str(
+        r#"{"id": "r1", "type": "book", "author": [
+            {"family": "Smith", "given": "Jane"},
+            {"family": "Jones", "given": "Jane"},
+            {"family": "Brown", "given": "Jane"}
+        ]}"#,
+    )
+    .unwrap();
+    db.insert_reference(refr);
+
+    let render = |name: &str, context: CiteOrBib| {
+        db.render_macro(name, Atom::from("r1"), context)
+            .map(|s| s.to_string())
+    };
+    // Name options are inherited from <citation> or <bibliography>
+    assert_eq!(
+        render("author", CiteOrBib::Citation).as_deref(),
+        Some("J. Smith et al.")
+    );
+    assert_eq!(
+        render("author", CiteOrBib::Bibliography).as_deref(),
+        Some("J. Smith, J. Jones, J. Brown")
+    );
+    assert_eq!(
+        render("where", CiteOrBib::Citation).as_deref(),
+        Some("cite")
+    );
+    assert_eq!(
+        render("where", CiteOrBib::Bibliography).as_deref(),
+        Some("bib")
+    );
+    assert_eq!(render("nothing", CiteOrBib::Citation), None);
+    assert_eq!(render("no-such-macro", CiteOrBib::Citation), None);
+    assert_eq!(
+        db.render_macro("author", Atom::from("missing"), CiteOrBib::Citation),
+        None
+    );
+}
+
+#[test]
+fn author_list() {
+    let mut db = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+            <macro name="author">
+                <names variable="author" prefix="(" suffix=")">
+                    <name and="symbol" />
+                    <label prefix=" " />
+                </names>
+            </macro>
+            <citation et-al-min="4" et-al-use-first="1" initialize-with=". ">
+                <layout>
+                    <choose>
+                        <if type="book"><text macro="author" /></if>
+                    </choose>
+                </layout>
+            </citation>
+        </style>"#,
+    ));
+    let refr: Reference = serde_json::from_str(
+        r#"{"id": "r1", "type": "book", "author": [
+            {"family": "Doe", "given": "Jane"},
+            {"family": "Roe", "given": "John"},
+            {"family": "Poe", "given": "Edgar"},
+            {"family": "Loe", "given": "Ann"}
+        ]}"#,
+    )
+    .unwrap();
+    db.insert_reference(refr.clone());
+    let mut solo = Reference::empty(Atom::from("solo"), CslType::Book);
+    solo.ordinary.insert(Variable::Title, "Title".into());
+    db.insert_reference(solo);
+
+    let list = |max: Option<u32>| db.author_list(Atom::from("r1"), max).map(|s| s.to_string());
+    // The <names> is found through the macro and the <choose>, without its affixes and label
+    assert_eq!(list(None).as_deref(), Some("J. Doe et al."));
+    assert_eq!(list(Some(2)).as_deref(), Some("J. Doe, J. Roe, et al."));
+    assert_eq!(
+        list(Some(4)).as_deref(),
+        Some("J. Doe, J. Roe, E. Poe, & A. Loe")
+    );
+    assert_eq!(list(Some(0)).as_deref(), Some("J. Doe et al."));
+    assert_eq!(db.author_list(Atom::from("solo"), None), None);
+    assert_eq!(db.author_list(Atom::from("missing"), None), None);
+
+    // `max` overrides et-al-use-last as well
+    let mut last = test_db(Some(
+        r#"<style version="1.0" class="in-text">
+            <citation et-al-min="3" et-al-use-first="1" et-al-use-last="true">
+                <layout><names variable="author" /></layout>
+            </citation>
+        </style>"#,
+    ));
+    last.insert_reference(refr);
+    assert_eq!(
+        last.author_list(Atom::from("r1"), Some(2)).as_deref(),
+        Some("Jane Doe, John Roe, et al.")
+    );
 }
@@ -0,0 +1,125 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright © 2021 Corporation for Digital Scholarship
+
+//! Importing references from a [Zotero translation-server](https://github.com/zotero/translation-server).
+//! Requires the `translation-server` feature.
+//!
+//! The server turns a DOI, ISBN, PMID, arXiv ID or web page URL into Zotero items, and then
+//! exports those as CSL-JSON, which is what we store in the processor.
+//!
+//! ```no_run
+//! use citeproc::translation_server::TranslationServer;
+//! use citeproc::{InitOptions, Processor};
+//!
+//! let mut processor = Processor::new(InitOptions::default()).unwrap();
+//! let server = TranslationServer::new("http://127.0.0.1:1969");
+//! let ids = server
+//!     .import_into(&mut processor, &["10.1145/3290368", "https://example.com/article"])
+//!     .unwrap();
+//! ```
+
+use crate::processor::Processor;
+use citeproc_io::Reference;
+use csl::Atom;
+use reqwest::blocking::Client;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::StatusCode;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImportError {
+    #[error("translation-server request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("translation-server could not find anything for {0:?}")]
+    NotFound(String),
+    #[error("{0:?} matched more than one item; use a more specific identifier")]
+    MultipleChoices(String),
+    #[error("translation-server returned {1} for {0:?}")]
+    Status(String, StatusCode),
+    #[error("translation-server returned invalid CSL-JSON: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// A client for one translation-server instance.
+pub struct TranslationServer {
+    client: Client,
+    url: String,
+}
+
+impl TranslationServer {
+    /// `url` is where the server is listening, like `http://127.0.0.1:1969`.
+    pub fn new(url: impl Into<String>) -> Self {
+        let mut url = url.into();
+        while url.ends_with('/') {
+            url.pop();
+        }
+        TranslationServer {
+            client: Client::new(),
+            url,
+        }
+    }
+
+    /// Looks up one identifier or URL, and returns the references it describes. Anything that
+    /// starts with `http://` or `https://` is scraped as a web page; everything else is searched
+    /// for as an identifier.
+    pub fn import(&self, identifier: &str) -> Result<Vec<Reference>, ImportError> {
+        let identifier = identifier.trim();
+        let endpoint = if identifier.starts_with("http://") || identifier.starts_with("https://")
+        {
+            "web"
+        } else {
+            "search"
+        };
+        let items = self.post(endpoint, identifier.to_owned(), "text/plain", identifier)?;
+        let csl_json = self.post(
+            "export?format=csljson",
+            items,
+            "application/json",
+            identifier,
+        )?;
+        Ok(serde_json::from_str(&csl_json)?)
+    }
+
+    /// Imports each identifier and inserts the references into `processor`, replacing any with
+    /// the same id. Returns the ids in the order they were imported. Stops at the first identifier
+    /// that fails, having inserted everything before it.
+    pub fn import_into(
+        &self,
+        processor: &mut Processor,
+        identifiers: &[&str],
+    ) -> Result<Vec<Atom>, ImportError> {
+        let mut ids = Vec::new();
+        for identifier in identifiers {
+            for refr in self.import(identifier)? {
+                ids.push(refr.id.clone());
+                processor.insert_reference(refr);
+            }
+        }
+        Ok(ids)
+    }
+
+    fn post(
+        &self,
+        endpoint: &str,
+        body: String,
+        content_type: &str,
+        identifier: &str,
+    ) -> Result<String, ImportError> {
+        let response = self
+            .client
+            .post(&format!("{}/{}", self.url, endpoint))
+            .header(CONTENT_TYPE, content_type)
+            .body(body)
+            .send()?;
+        match response.status() {
+            status if status.is_success() => Ok(response.text()?),
+            StatusCode::NOT_FOUND | StatusCode::NOT_IMPLEMENTED => {
+                Err(ImportError::NotFound(identifier.into()))
+            }
+            StatusCode::MULTIPLE_CHOICES => Err(ImportError::MultipleChoices(identifier.into())),
+            status => Err(ImportError::Status(identifier.into(), status)),
+        }
+    }
+}
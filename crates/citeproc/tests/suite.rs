@@ -24,7 +24,10 @@ cfg_if! {
 }
 
 mod test_format;
-use test_format::{humans::parse_human_test, yaml::parse_yaml_test};
+use test_format::yaml::{parse_yaml_test, YamlTestCase};
+use test_format::{humans::parse_human_test, Mode, TestCase};
+
+use citeproc::SupportedFormat;
 
 use lazy_static::lazy_static;
 use pretty_assertions::assert_eq;
@@ -87,6 +90,25 @@ fn is_snapshot(path: &Path) -> bool {
     SNAPSHOTS.contains(&fname.into_owned())
 }
 
+/// With the `parallel` feature, runs a test case again on a single rayon thread and checks that
+/// thread scheduling made no difference to the output.
+#[cfg(feature = "parallel")]
+fn check_deterministic(res: &Option<String>, make_case: impl FnOnce() -> TestCase + Send) {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()
+        .unwrap();
+    let single = pool.install(|| make_case().execute());
+    assert_eq!(
+        single.as_deref().map(PrettyString),
+        res.as_deref().map(PrettyString),
+        "output on a single thread differs from output on many"
+    );
+}
+
+#[cfg(not(feature = "parallel"))]
+fn check_deterministic(_res: &Option<String>, _make_case: impl FnOnce() -> TestCase + Send) {}
+
 use std::sync::Once;
 
 static INIT: Once = Once::new();
@@ -103,7 +125,9 @@ fn csl_test_suite(path: &Path) {
     setup();
     let input = read_to_string(path).unwrap();
     let mut test_case = parse_human_test(&input, None);
-    if let Some(res) = test_case.execute() {
+    let res = test_case.execute();
+    check_deterministic(&res, || parse_human_test(&input, None));
+    if let Some(res) = res {
         let pass = res == test_case.result;
         if !pass && is_snapshot(path) {
             let name = path.file_name().unwrap().to_string_lossy();
@@ -121,25 +145,63 @@ fn humans(path: &Path) {
     setup();
     let input = read_to_string(path).unwrap();
     let mut test_case = parse_yaml_test(&input).unwrap();
-    if let Some(res) = test_case.execute() {
+    let res = test_case.execute();
+    check_deterministic(&res, || parse_yaml_test(&input).unwrap());
+    if let Some(res) = res {
         assert_eq!(PrettyString(&res), PrettyString(&test_case.result));
     }
 }
 
+/// Renders each fixture in every output format and snapshots the outputs side by side, so a change
+/// that only shows up in one format is still caught.
+#[cfg(feature = "rtf")]
+#[datatest::files("tests/data/formats", {
+    path in r"^(.*)\.yml",
+})]
+fn formats(path: &Path) {
+    setup();
+    let input = read_to_string(path).unwrap();
+    let mut outputs = Vec::new();
+    for &format in &[
+        SupportedFormat::Html,
+        SupportedFormat::Rtf,
+        SupportedFormat::Plain,
+    ] {
+        let mut yaml: YamlTestCase = serde_yaml::from_str(&input).unwrap();
+        yaml.options.format = format;
+        yaml.options.normalise = false;
+        let mut test_case = TestCase::from(yaml);
+        let mut res = test_case.execute().expect("fixture failed to render");
+        if test_case.mode == Mode::Bibliography {
+            // The usual bibliography string is wrapped in HTML divs whatever the format
+            let entries: Vec<_> = test_case
+                .processor
+                .get_bibliography()
+                .into_iter()
+                .map(|entry| entry.value.to_string())
+                .collect();
+            res = entries.join("\n");
+        }
+        outputs.push(format!("{:?}:\n{}", format, res));
+    }
+    let name = path.file_name().unwrap().to_string_lossy();
+    insta::assert_snapshot!(name.as_ref(), outputs.join("\n\n"));
+}
+
 #[datatest::files("tests/data/fixtures-local", {
     path in r"^(.*)\.txt" if !is_ignore,
 })]
 fn fixtures_local(path: &Path) {
     setup();
     let input = read_to_string(path).unwrap();
-    let mut test_case = parse_human_test(
-        &input,
-        Some(csl::Features {
-            custom_intext: true,
-            ..Default::default()
-        }),
-    );
-    if let Some(res) = test_case.execute() {
+    let features = csl::Features {
+        custom_intext: true,
+        ..Default::default()
+    };
+    let mut test_case = parse_human_test(&input, Some(features.clone()));
+    let res = test_case.execute();
+    check_deterministic(&res, || parse_human_test(&input, Some(features)));
+    if let Some(res) = res {
         let pass = res == test_case.result;
         if !pass && is_snapshot(path) {
             let name = path.file_name().unwrap().to_string_lossy();
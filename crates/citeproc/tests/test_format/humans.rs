@@ -47,6 +47,7 @@ impl CompatCitationItem {
             id: index.to_string().into(),
             cites: v,
             mode,
+            lang: None,
         }
     }
 }
@@ -454,10 +455,8 @@ pub fn parse_human_test(contents: &str, csl_features: Option<csl::Features>) ->
 
     let options = TestInitOptions {
         format: mode.map(|(_, f, _)| f).unwrap_or(SupportedFormat::Html),
-        format_options: FormatOptions {
-            // disable these for txt format tests
-            link_anchors: false,
-        },
+        // disable link anchors for txt format tests
+        format_options: FormatOptions::test_suite(),
         csl_features,
         bibliography_no_sort: mode.map_or(false, |(_, _, nosort)| nosort),
         locale_override: None,
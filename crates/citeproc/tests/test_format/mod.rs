@@ -125,6 +125,7 @@ impl TestCase {
                     id: processor.cluster_id(&str_cluster.id),
                     cites: str_cluster.cites,
                     mode: str_cluster.mode,
+                    lang: str_cluster.lang,
                 })
                 .collect()
         });
@@ -175,6 +176,7 @@ impl TestCase {
                     id: self.processor.random_cluster_id(),
                     cites,
                     mode: None,
+                    lang: None,
                 });
                 &clusters_auto
             };
@@ -7,7 +7,7 @@
 use super::humans::{CiteprocJsInstruction, CompatCitationItem};
 use super::{Mode, TestCase};
 use anyhow::Error;
-use citeproc::{FormatOptions, SupportedFormat};
+use citeproc::{FormatOptions, PlainOptions, SupportedFormat};
 use citeproc_io::Reference;
 use serde::Deserialize;
 
@@ -24,6 +24,8 @@ pub struct YamlTestCase {
     pub options: TestInitOptions,
     pub csl: String,
     pub input: Vec<Reference>,
+    /// Not needed for snapshot tests
+    #[serde(default)]
     pub result: String,
     pub clusters: Option<Vec<CompatCitationItem>>,
     pub process_citation_clusters: Option<Vec<CiteprocJsInstruction>>,
@@ -33,11 +35,30 @@ fn bool_true() -> bool {
     true
 }
 
+/// Test files use one flat set of format options, applied to every format that has each one.
 #[derive(Deserialize)]
-#[serde(remote = "FormatOptions", rename_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
 struct KebabFormatOpts {
     #[serde(default = "bool_true")]
     link_anchors: bool,
+    #[serde(default)]
+    ascii_only: bool,
+    #[serde(default)]
+    bidi_isolation: bool,
+}
+
+fn kebab_format_options<'de, D>(d: D) -> Result<FormatOptions, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let opts = KebabFormatOpts::deserialize(d)?;
+    let mut format_options = FormatOptions::default().with_link_anchors(opts.link_anchors);
+    format_options.html.bidi_isolation = opts.bidi_isolation;
+    format_options.plain = PlainOptions {
+        ascii_only: opts.ascii_only,
+        bidi_isolation: opts.bidi_isolation,
+    };
+    Ok(format_options)
 }
 
 #[derive(Debug, Deserialize, PartialEq, Default, Clone)]
@@ -48,7 +69,7 @@ pub struct TestInitOptions {
     // Optional
     #[serde(default)]
     pub format: SupportedFormat,
-    #[serde(default, with = "KebabFormatOpts")]
+    #[serde(default, deserialize_with = "kebab_format_options")]
     pub format_options: FormatOptions,
     /// You might get this from a dependent style via `StyleMeta::parse(dependent_xml_string)`
     #[serde(default)]
@@ -4,71 +4,32 @@
 //
 // Copyright © 2019 Corporation for Digital Scholarship
 
-use csl::{InvalidCsl, StyleError};
-use std::ops::Range;
-
-use csl::Severity as CslSeverity;
-
-use codespan::{ByteIndex, ByteSpan, CodeMap, FileMap, Span};
-use codespan_reporting::termcolor::{ColorChoice, StandardStream};
-use codespan_reporting::{emit, Diagnostic, Label, Severity};
-
-fn convert_sev(csl: CslSeverity) -> Severity {
-    match csl {
-        CslSeverity::Warning => Severity::Warning,
-        CslSeverity::Error => Severity::Error,
-    }
-}
-
-pub fn file_diagnostics<'a>(err: &StyleError, filename: &'a str, document: &'a str) {
-    let mut code_map = CodeMap::new();
-    let file_map = code_map.add_filemap(filename.to_owned().into(), document.to_string());
-    let writer = StandardStream::stderr(ColorChoice::Auto);
-    for diag in diagnostics(err, &file_map) {
-        if let Ok(d) = diag {
-            emit(&mut writer.lock(), &code_map, &d).unwrap();
-            eprintln!();
-        } else if let Err(emsg) = diag {
-            eprintln!("{}", emsg);
-        }
-    }
-}
-
-pub(crate) fn diagnostics(err: &StyleError, file_map: &FileMap) -> Vec<Result<Diagnostic, String>> {
-    match *err {
-        StyleError::Invalid(ref invs) => invs
-            .0
-            .iter()
-            .map(|e| to_diagnostic(e).ok_or(e.message.clone()))
-            .collect(),
-        StyleError::ParseError(ref e) => {
-            let pos = e.pos();
-
-            let str_start =
-                file_map.byte_index((pos.row - 1 as u32).into(), (pos.col - 1 as u32).into());
-
-            if let Ok(start) = str_start {
-                vec![Ok(Diagnostic::new(Severity::Error, format!("{}", e))
-                    .with_label(
-                        Label::new_primary(Span::from_offset(start, (1 as i64).into()))
-                            .with_message(""),
-                    ))]
-            } else {
-                vec![]
+use csl::StyleError;
+
+/// Prints each problem with a style or locale as `file:line:column: message`, the way compilers
+/// do, so editors can jump to it.
+pub fn file_diagnostics(err: &StyleError, filename: &str, document: &str) {
+    match err {
+        StyleError::Invalid(invs) => {
+            for inv in &invs.0 {
+                let (line, col) = line_col(document, inv.range.start);
+                eprintln!(
+                    "{}:{}:{}: {:?}: {}",
+                    filename, line, col, inv.severity, inv.message
+                );
+                if !inv.hint.is_empty() {
+                    eprintln!("    hint: {}", inv.hint);
+                }
             }
         }
+        _ => eprintln!("{}: {}", filename, err),
     }
 }
 
-fn span_from_range(range: &Range<usize>) -> ByteSpan {
-    Span::new(
-        ByteIndex(range.start as u32 + 1),
-        ByteIndex(range.end as u32 + 1),
-    )
-}
-
-pub fn to_diagnostic(inv: &InvalidCsl) -> Option<Diagnostic> {
-    let label = Label::new_primary(span_from_range(&inv.range)).with_message(inv.hint.to_string());
-    let diag = Diagnostic::new(convert_sev(inv.severity), inv.message.clone()).with_label(label);
-    Some(diag)
+/// One-based line and column of a byte offset.
+fn line_col(document: &str, offset: usize) -> (usize, usize) {
+    let before = document.get(..offset).unwrap_or(document);
+    let line = before.matches('\n').count() + 1;
+    let col = before.rsplit('\n').next().map_or(0, |l| l.chars().count()) + 1;
+    (line, col)
 }
@@ -6,7 +6,7 @@
 
 use cfg_if::cfg_if;
 cfg_if! {
-    if #[cfg(feature="jemalloc")] {
+    if #[cfg(all(feature = "jemalloc", not(target_os = "wasi")))] {
         use jemallocator::Jemalloc;
         #[global_allocator]
         static A: Jemalloc = Jemalloc;
@@ -17,32 +17,22 @@ cfg_if! {
     }
 }
 
-use citeproc::input::Reference;
 use clap::{App, Arg, SubCommand};
-use directories::ProjectDirs;
 use std::fs;
+use std::io;
 use std::path::PathBuf;
+use std::process::exit;
 use std::str::FromStr;
 use std::sync::Arc;
 
 mod error;
-mod pandoc;
-use pandoc_types::definition::{Inline, MetaValue, Pandoc as PandocDocument};
 
-use citeproc::{LocaleFetchError, LocaleFetcher, Processor};
+use citeproc::prelude::*;
 use csl::{Lang, Locale};
 
 fn main() {
-    // heuristically determine if we're running as an external pandoc filter
-    // TODO: work out earliest pandoc that sets PANDOC_VERSION
-    let not_a_tty = !atty::is(atty::Stream::Stdin) && !atty::is(atty::Stream::Stdout);
-    if std::env::var("PANDOC_VERSION").is_ok() && not_a_tty {
-        do_pandoc();
-        return;
-    }
-
-    let matches = App::new("citeproc")
-        .version("0.0.0")
+    let matches = App::new("citeproc-rs")
+        .version(env!("CARGO_PKG_VERSION"))
         .author("Cormac Relf")
         .about("Processes citations")
         .subcommand(
@@ -55,21 +45,6 @@ fn main() {
                         .takes_value(true),
                 ),
         )
-        .subcommand(SubCommand::with_name("pandoc").about(
-            "Force Pandoc JSON filter mode. Operates on stdin > stdout.\
-             \nNormally, you can just use `pandoc -F citeproc-rs`.",
-        ))
-        .subcommand(
-            SubCommand::with_name("disamb-index")
-                .about("Prints the inverted disambiguation index for the reference library"),
-        )
-        // .arg(
-        //     Arg::with_name("format")
-        //         .short("f")
-        //         .long("format")
-        //         .value_name("FORMAT")
-        //         .takes_value(true),
-        // )
         .arg(
             Arg::with_name("library")
                 .short("l")
@@ -91,9 +66,25 @@ fn main() {
                 .short("k")
                 .long("key")
                 .value_name("CITEKEY")
-                .help("Run against a specific citekey")
+                .help("Cite a specific reference, once per key (default: every reference)")
+                .takes_value(true)
+                .multiple(true),
+        )
+        .arg(
+            Arg::with_name("format")
+                .short("f")
+                .long("format")
+                .value_name("FORMAT")
+                .possible_values(&["html", "rtf", "plain"])
+                .default_value("plain")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("bibliography")
+                .short("b")
+                .long("bibliography")
+                .help("Print the bibliography after the citations"),
+        )
         .arg(
             Arg::with_name("locales-dir")
                 .long("locales-dir")
@@ -101,230 +92,220 @@ fn main() {
                 .help("Directory with locales-xx-XX.xml files in it")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("import")
+                .long("import")
+                .value_name("DOI|ISBN|URL")
+                .help("Add a reference from a Zotero translation-server (needs the translation-server feature)")
+                .takes_value(true)
+                .multiple(true),
+        )
+        .arg(
+            Arg::with_name("translation-server")
+                .long("translation-server")
+                .value_name("URL")
+                .default_value("http://127.0.0.1:1969")
+                .takes_value(true),
+        )
         .get_matches();
 
-    let lib_text = r#"
-    [
-        {
-            "id": "quagmire2018",
-            "type": "legal_case",
-            "volume": "4",
-            "edition": "128th & 7-9, 17th",
-            "page": "1-5",
-            "container-title": "TASCC",
-            "title": "Solomon v Garrity",
-            "author": [
-                {"family": "Beethoven", "dropping-particle": "van", "given": "Ludwig"}
-            ],
-            "editor": [
-                {"family": "Paul", "given": "John", "suffix": "II"},
-                {"family": "Mozart", "given": "Wolfgang Amadeus"},
-                {"family": "Beethoven", "dropping-particle": "van", "given": "Ludwig"}
-            ],
-            "issued": {"raw": "1995-03-01"}
-        }
-    ]
-    "#;
-
-    let filesystem_fetcher = {
-        let locales_dir = matches
-            .value_of("locales-dir")
-            .map(PathBuf::from)
-            .unwrap_or_else(|| {
-                let pd = ProjectDirs::from("net", "cormacrelf", "citeproc-rs")
-                    .expect("No home directory found.");
-                let mut locales_dir = pd.cache_dir().to_owned();
-                locales_dir.push("locales");
-                locales_dir
-            });
-        if matches.subcommand_matches("parse-locale").is_some() {
-            let locales_dir = locales_dir.clone();
-            dbg!(locales_dir);
-        }
-        Arc::new(Filesystem::new(locales_dir))
-    };
+    let fetcher = Arc::new(match matches.value_of("locales-dir") {
+        Some(dir) => Filesystem::new(dir),
+        None => Filesystem::default(),
+    });
 
     if let Some(matches) = matches.subcommand_matches("parse-locale") {
-        let lang = if let Some(lan) = matches.value_of("lang") {
-            if let Ok(l) = Lang::from_str(lan) {
-                l
-            } else {
+        let lang = match matches.value_of("lang").map(Lang::from_str) {
+            Some(Ok(l)) => l,
+            Some(Err(_)) => {
                 eprintln!(
                     "`{}` is not a valid language",
                     matches.value_of("lang").unwrap_or("")
                 );
-                return;
+                exit(2);
             }
-        } else {
-            Lang::en_us()
+            None => Lang::en_us(),
         };
-        fn fetch_cli(fetcher: &Filesystem, lang: &Lang) -> Option<Locale> {
-            let string = match fetcher.fetch_string(lang) {
-                Ok(opt) => opt?,
-                Err(e) => panic!("failed to read locale file, exiting\n{:?}", e),
-            };
-            let with_errors = |s: &str| Ok(Locale::from_str(s)?);
-            match with_errors(&string) {
-                Ok(l) => Some(l),
-                Err(e) => {
-                    self::error::file_diagnostics(&e, "input", &string);
-                    None
-                }
+        let path = fetcher.path_for(&lang);
+        let string = match fetcher.fetch_string(&lang) {
+            Ok(Some(string)) => string,
+            Ok(None) => {
+                eprintln!("no locale file at {}", path.display());
+                exit(1);
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                exit(1);
+            }
+        };
+        match Locale::from_str(&string) {
+            Ok(locale) => println!("{:#?}", locale),
+            Err(e) => {
+                error::file_diagnostics(&e, &path.to_string_lossy(), &string);
+                exit(1);
             }
         }
-        let locale = fetch_cli(&filesystem_fetcher, &lang);
-        dbg!(locale);
         return;
     }
 
-    // if let Some(_) = matches.subcommand_matches("disamb-index") {
-    //     let mut db = Processor::new(filesystem_fetcher);
-    //     db.insert_references(refs);
-    //     for (tok, ids) in db.inverted_index().iter() {
-    //         // if ids.len() > 1 {
-    //         let token = tok.clone();
-    //         let citekeys: Vec<_> = ids.iter().map(|atom| atom.to_string()).collect();
-    //         dbg!((token, citekeys));
-    //         // }
-    //     }
-    //     return;
-    // }
-
-    if let Some(csl_path) = matches.value_of("csl") {
-        let key = matches
-            .value_of("key")
-            .map(citeproc::Atom::from)
-            .unwrap_or("quagmire2018".into());
-
-        let text = fs::read_to_string(&csl_path).expect("No CSL file found at that path");
-
-        match Processor::new(&text, filesystem_fetcher) {
-            Ok(mut db) => {
-                let refs = if let Some(library_path) = matches.value_of("library") {
-                    expect_refs(library_path)
-                } else {
-                    serde_json::from_str(&lib_text).expect("sample lib_text not parseable")
-                };
-
-                db.init_clusters(vec![citeproc::input::Cluster {
-                    id: 0,
-                    note_number: 1,
-                    cites: vec![citeproc::input::Cite::basic(key)],
-                }]);
-                db.inssert_references(refs);
+    let csl_path = match matches.value_of("csl") {
+        Some(path) => path,
+        None => {
+            eprintln!("{}", matches.usage());
+            exit(2);
+        }
+    };
+    let text = read_or_exit(csl_path);
+    let format = matches
+        .value_of("format")
+        .and_then(|f| SupportedFormat::from_str(f).ok())
+        .unwrap_or(SupportedFormat::Plain);
 
-                let inlines = db.get_cluster(0).to_vec();
+    let mut processor = match Processor::new(InitOptions {
+        style: &text,
+        fetcher: Some(fetcher),
+        format,
+        ..Default::default()
+    }) {
+        Ok(processor) => processor,
+        Err(Error::Style(e)) => {
+            error::file_diagnostics(&e, csl_path, &text);
+            exit(1);
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    };
 
-                use pandoc_types::definition::{Block, Meta, Pandoc};
-                let doc = Pandoc(Meta::null(), vec![Block::Para(inlines)]);
-                let out = serde_json::to_string(&doc).unwrap();
-                println!("{}", out);
-            }
+    let refs: Vec<Reference> = match matches.value_of("library") {
+        Some(library_path) => match serde_json::from_str(&read_or_exit(library_path)) {
+            Ok(refs) => refs,
             Err(e) => {
-                self::error::file_diagnostics(&e, &csl_path, &text);
+                eprintln!("{}: {}", library_path, e);
+                exit(1);
             }
+        },
+        None => Vec::new(),
+    };
+    let mut keys: Vec<Atom> = match matches.values_of("key") {
+        Some(keys) => keys.map(Atom::from).collect(),
+        None => refs.iter().map(|r| r.id.clone()).collect(),
+    };
+    processor.reset_references(refs);
+    if let Some(identifiers) = matches.values_of("import") {
+        let identifiers: Vec<&str> = identifiers.collect();
+        let imported = import(
+            &mut processor,
+            matches.value_of("translation-server").unwrap_or_default(),
+            &identifiers,
+        );
+        if matches.values_of("key").is_none() {
+            keys.extend(imported);
         }
     }
-}
 
-fn pandoc_meta_str<'a>(doc: &'a PandocDocument, key: &str) -> Option<&'a str> {
-    doc.0.lookup(key).and_then(|value| match value {
-        // for metadata passed through the command line
-        // --metadata csl=my-style.csl
-        MetaValue::MetaString(s) => Some(s.as_str()),
-        MetaValue::MetaInlines(inlines) => match &inlines[..] {
-            // for inline paths with no spaces (otherwise they get split with
-            // Inline::Space)
-            // csl: "my-style.csl"
-            &[Inline::Str(ref s)] => Some(s.as_str()),
-            // for inline paths with spaces
-            // csl: "`my style.csl`{=raw}"
-            &[Inline::RawInline(_, ref s)] => Some(s.as_str()),
-            _ => None,
-        },
-        _ => None,
-    })
+    let mut positions = Vec::with_capacity(keys.len());
+    for (ix, key) in keys.into_iter().enumerate() {
+        let id = processor.cluster_id(&*key);
+        processor.insert_cites(id, &[Cite::basic(key)]);
+        positions.push(ClusterPosition::note(id, ix as u32 + 1));
+    }
+    if let Err(e) = processor.set_cluster_order(&positions) {
+        eprintln!("{}", e);
+        exit(1);
+    }
+    for pos in &positions {
+        if let Some(built) = pos.id.and_then(|id| processor.get_cluster(id)) {
+            println!("{}", built);
+        }
+    }
+    if matches.is_present("bibliography") {
+        for entry in processor.get_bibliography() {
+            println!("{}", entry.value);
+        }
+    }
 }
 
-fn do_pandoc() {
-    let filter_args = App::new("pandoc_filter")
-        .arg(Arg::with_name("output_format").required(false).index(1))
-        .get_matches();
-    let _output_format = filter_args.value_of("output_format").unwrap_or("none");
-    let input = std::io::stdin();
-    // already LineWriter buffered, but we're only writing one line of JSON so not a problem
-    let output = std::io::stdout();
-
-    let mut doc: PandocDocument =
-        serde_json::from_reader(input).expect("could not parse pandoc json");
+#[cfg(feature = "translation-server")]
+fn import(processor: &mut Processor, server: &str, identifiers: &[&str]) -> Vec<Atom> {
+    use citeproc::translation_server::TranslationServer;
+    match TranslationServer::new(server).import_into(processor, identifiers) {
+        Ok(ids) => ids,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    }
+}
 
-    let csl_path = pandoc_meta_str(&doc, "csl").expect("No csl path provided through metadata");
-    let text = fs::read_to_string(&csl_path).expect("No CSL file found at that path");
+#[cfg(not(feature = "translation-server"))]
+fn import(_: &mut Processor, _: &str, _: &[&str]) -> Vec<Atom> {
+    eprintln!("--import needs citeproc-rs built with the translation-server feature");
+    exit(2);
+}
 
-    match Processor::new(&text, Arc::new(Filesystem::default())) {
-        Ok(mut db) => {
-            if let Some(library_path) = pandoc_meta_str(&doc, "bibliography") {
-                db.reset_references(expect_refs(library_path));
-            }
-            db.init_clusters(pandoc::get_clusters(&mut doc));
-            db.compute();
-            pandoc::write_clusters(&mut doc, &db);
-            serde_json::to_writer(output, &doc).expect("could not write pandoc json");
-        }
+fn read_or_exit(path: &str) -> String {
+    match fs::read_to_string(path) {
+        Ok(text) => text,
         Err(e) => {
-            self::error::file_diagnostics(&e, &csl_path, &text);
+            eprintln!("{}: {}", path, e);
+            exit(1);
         }
     }
 }
 
+/// Reads `locales-xx-XX.xml` files from a directory, like the ones in the
+/// [CSL locales repository](https://github.com/citation-style-language/locales). Falls back to the
+/// bundled en-US locale if there is no file for it.
 pub struct Filesystem {
     root: PathBuf,
 }
 
 impl Default for Filesystem {
+    /// Uses `$CITEPROC_LOCALES_DIR` if it is set, and otherwise a platform default.
     fn default() -> Self {
-        let locales_dir = None
-            // TODO: read metadata
-            .unwrap_or_else(|| {
-                let pd = ProjectDirs::from("net", "cormacrelf", "citeproc-rs")
-                    .expect("No home directory found.");
-                let mut locales_dir = pd.cache_dir().to_owned();
-                locales_dir.push("locales");
-                locales_dir
-            });
-        Filesystem::new(locales_dir)
+        let root = std::env::var_os("CITEPROC_LOCALES_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(default_locales_dir);
+        Filesystem::new(root)
     }
 }
 
+/// WASI programs have no home directory, only the directories the host preopens, so we expect
+/// one mounted at `/locales`, e.g. `wasmtime run --dir ./locales::/locales citeproc-rs.wasm`.
+#[cfg(target_os = "wasi")]
+fn default_locales_dir() -> PathBuf {
+    PathBuf::from("/locales")
+}
+
+#[cfg(not(target_os = "wasi"))]
+fn default_locales_dir() -> PathBuf {
+    directories::ProjectDirs::from("net", "cormacrelf", "citeproc-rs")
+        .map(|pd| pd.cache_dir().join("locales"))
+        .unwrap_or_else(|| PathBuf::from("locales"))
+}
+
 impl Filesystem {
     pub fn new(repo_dir: impl Into<PathBuf>) -> Self {
         Filesystem {
             root: repo_dir.into(),
         }
     }
-}
 
-use std::io;
+    fn path_for(&self, lang: &Lang) -> PathBuf {
+        self.root.join(format!("locales-{}.xml", lang))
+    }
+}
 
 impl LocaleFetcher for Filesystem {
     fn fetch_string(&self, lang: &Lang) -> Result<Option<String>, LocaleFetchError> {
-        let mut path = self.root.clone();
-        path.push(&format!("locales-{}.xml", lang));
-        let read = fs::read_to_string(path);
-        match read {
+        match fs::read_to_string(self.path_for(lang)) {
             Ok(string) => Ok(Some(string)),
             Err(e) => match e.kind() {
-                io::ErrorKind::NotFound => Ok(None),
+                io::ErrorKind::NotFound => PredefinedLocales::bundled_en_us().fetch_string(lang),
                 _ => Err(LocaleFetchError::Io(e)),
             },
         }
     }
 }
-
-fn expect_refs(library_path: &str) -> Vec<Reference> {
-    use std::fs::File;
-    use std::io::BufReader;
-    let file = File::open(&library_path).expect("No library found at that path");
-    let reader = BufReader::new(file);
-    serde_json::from_reader(reader).expect("Could not parse JSON")
-}
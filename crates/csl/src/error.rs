@@ -185,6 +185,13 @@ impl NeedVarType {
     }
 }
 
+/// The range of an attribute, or of the whole node if the attribute has gone missing, so that
+/// building an error can never panic.
+fn attr_range(node: &Node, attr: impl Into<ExpName>) -> Range<usize> {
+    node.attribute_node(attr)
+        .map_or_else(|| node.range(), |at| at.range())
+}
+
 impl InvalidCsl {
     pub fn new(node: &Node, message: impl Into<String>) -> Self {
         let range = node.range();
@@ -207,8 +214,7 @@ impl InvalidCsl {
     }
 
     pub fn bad_int(node: &Node, attr: &str, uav: &ParseIntError) -> Self {
-        let at = node.attribute_node(attr).unwrap();
-        let range = at.range();
+        let range = attr_range(node, attr);
         InvalidCsl {
             range,
             message: format!("Invalid integer value for {}: {:?}", attr, uav),
@@ -223,11 +229,10 @@ impl InvalidCsl {
 
     pub fn attr_val(node: &Node, attr: impl Into<ExpName>, uav: &str) -> Self {
         let attr = attr.into();
-        let at = node.attribute_node(attr).unwrap();
-        let range = at.range();
+        let message = format!("Unknown attribute value for `{:?}`: \"{}\"", attr, uav);
         InvalidCsl {
-            range,
-            message: format!("Unknown attribute value for `{:?}`: \"{}\"", attr, uav),
+            range: attr_range(node, attr),
+            message,
             hint: "".to_string(),
             severity: Severity::Error,
         }
@@ -264,8 +269,7 @@ impl InvalidCsl {
         needed: NeedVarType,
         got: Option<AnyVariable>,
     ) -> Self {
-        let at = node.attribute_node(attr).unwrap();
-        let range = at.range();
+        let range = attr_range(node, attr);
         let (message, hint, severity) = needed.hint(attr, uav, got);
         InvalidCsl {
             range,
@@ -5,7 +5,7 @@
 // Copyright © 2020 Corporation for Digital Scholarship
 
 use crate::error::{ChildGetterError, ChildGetterResult, CslError, InvalidCsl};
-use crate::version::Features;
+use crate::version::{Features, StyleMode};
 use crate::SmartString;
 use fnv::FnvHashSet;
 use roxmltree::{Attribute, Node};
@@ -42,6 +42,12 @@ pub struct ParseOptions {
     /// Feature overrides. Allows you to enable features programmatically. Features declared in the
     /// style will be added to this.
     pub features: Option<Features>,
+    /// Lenient (the default), strict CSL or CSL-M.
+    pub mode: StyleMode,
+    /// How many macros deep a chain of `<text macro="..."/>` calls may go before the style is
+    /// rejected, for servers that take styles from users. Macros that call themselves are always
+    /// rejected.
+    pub max_macro_depth: Option<usize>,
     #[doc(hidden)]
     pub use_default_default: private::CannotConstruct,
 }
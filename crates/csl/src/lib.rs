@@ -557,6 +557,20 @@ impl TextTermSelector {
         }
     }
 
+    /// Like [TextTermSelector::from_term_form_unwrap], but `None` for an unknown term or form,
+    /// or an ordinal term.
+    pub fn from_term_form(term: &str, form: Option<&str>, features: &Features) -> Option<Self> {
+        let term = AnyTermName::get_attr(term, features).ok()?;
+        let form = form.unwrap_or("long");
+        TextTermSelector::from_term_and_form(
+            &term,
+            || TermForm::get_attr(form, features).map_err(|_| ()),
+            || TermFormExtended::get_attr(form, features).map_err(|_| ()),
+            || (),
+        )
+        .ok()
+    }
+
     pub fn from_term_form_unwrap(term: &str, form: Option<&str>, features: &Features) -> Self {
         let term =
             AnyTermName::get_attr(term, features).expect("Could not parse input term as a term.");
@@ -755,7 +769,10 @@ impl ConditionParser {
         };
         let cond = ConditionParser {
             match_type: Match::from_node(node, info)?,
-            jurisdiction: attribute_option(node, "jurisdiction", info)?,
+            jurisdiction: node
+                .attribute("jurisdiction")
+                .map(|list| list.split_whitespace().map(SmartString::from).collect())
+                .unwrap_or_default(),
             subjurisdictions: attribute_option_int(node, "subjurisdictions")?,
             context: attribute_option(node, "context", info)?,
             disambiguate: bool::attribute_option(node, "disambiguate", info)?,
@@ -1417,8 +1434,23 @@ impl FromNode for TermForm {
 }
 
 impl FromNode for CslVersionReq {
-    fn from_node(node: &Node, _info: &ParseInfo) -> FromNodeResult<Self> {
+    fn from_node(node: &Node, info: &ParseInfo) -> FromNodeResult<Self> {
+        if info.options.mode == StyleMode::CslM {
+            let CslCslMVersionReq(_variant, req) = CslCslMVersionReq::from_node(node, info)?;
+            return Ok(CslVersionReq(req));
+        }
         let version = attribute_string(node, "version");
+        let is_cslm = version.ends_with("mlz1")
+            || node
+                .attribute("variant")
+                .map_or(false, |v| v.eq_ignore_ascii_case("csl-m"));
+        if is_cslm && info.options.mode == StyleMode::Csl {
+            return Err(InvalidCsl::new(
+                node,
+                "This is a CSL-M style, but CSL-M is not enabled. Parse it with StyleMode::CslM.",
+            )
+            .into());
+        }
         let req = VersionReq::parse(&version).map_err(|_| {
             InvalidCsl::new(
                 node,
@@ -1501,21 +1533,6 @@ impl FromNode for Features {
     const CHILD_DESC: &'static str = "features";
 }
 
-// type MacroDict = HashMap<SmartString, Vec<Element>>;
-// fn check_recursion(macros: &MacroDict, layout: &Layout, cite_or_bib_node: &Node) -> FromNodeResult<()> {
-//     let mut stack: Vec<SmartString> = Vec::new();
-//     let mut check_element = |macros: &MacroDict, element: &Element| -> bool {
-//         if let Element::Text(TextElement { source: TextSource::Macro(m), .. }) = &element {
-//             if stack.contains(m) {
-//                 Err(InvalidCsl::new(cite_or_bib_node, format!("Macros cannot be mutually recursive {:?}", stack)))
-//             } else {
-//                 stack.push(m.clone());
-//                 Ok(())
-//             }
-//         }
-//     };
-// }
-
 fn whitelist_child_nodes(node: &Node, whitelist: &[&str], errors: &mut Vec<InvalidCsl>) {
     node.children()
         .filter(|x| x.is_element() && !whitelist.contains(&x.tag_name().name()))
@@ -1543,7 +1560,7 @@ impl Style {
         let whitelist: &[&str] = &whitelist_intext[..6];
 
         // Parse features first so we know how to interpret the rest.
-        let features = max_one_child::<Features>(node, default_info, &mut errors)
+        let mut features = max_one_child::<Features>(node, default_info, &mut errors)
             .ok()
             .flatten()
             .unwrap_or_else(|| {
@@ -1553,6 +1570,7 @@ impl Style {
                     .clone()
                     .unwrap_or_else(Default::default)
             });
+        default_info.options.mode.apply(&mut features);
 
         whitelist_child_nodes(
             node,
@@ -1604,6 +1622,14 @@ impl Style {
                 macros.insert(mac.name, mac.elements);
             }
         }
+        if errors.is_empty() {
+            style::check_macros(
+                node,
+                &macros,
+                parse_info.options.max_macro_depth,
+                &mut errors,
+            );
+        }
 
         if !errors.is_empty() {
             return Err(CslError(errors));
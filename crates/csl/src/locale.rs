@@ -12,17 +12,23 @@ use crate::{attr::*, CslError, Severity};
 use crate::{AttrChecker, FromNode, FromNodeResult, ParseInfo, SmartString};
 use fnv::FnvHashMap;
 use roxmltree::{Document, Node};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
 mod lang;
+mod write;
 pub use self::lang::{IsoCountry, IsoLang, Lang, LocaleSource};
 
 pub const EN_US: &str = include_str!("locales-en-US.xml");
 
 #[derive(Default, Debug, Clone, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct LocaleOptionsNode {
     pub limit_day_ordinals_to_day_1: Option<bool>,
     pub punctuation_in_quote: Option<bool>,
+    /// `leading-articles="the a an"`, overriding [`Lang::leading_articles`] for this locale.
+    pub leading_articles: Option<Vec<SmartString>>,
 }
 
 impl LocaleOptionsNode {
@@ -31,9 +37,13 @@ impl LocaleOptionsNode {
             .limit_day_ordinals_to_day_1
             .or(self.limit_day_ordinals_to_day_1);
         self.punctuation_in_quote = other.punctuation_in_quote.or(self.punctuation_in_quote);
+        if other.leading_articles.is_some() {
+            self.leading_articles = other.leading_articles.clone();
+        }
     }
 }
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct LocaleOptions {
     pub limit_ordinals_to_day_1: bool,
     pub punctuation_in_quote: bool,
@@ -64,6 +74,7 @@ impl Default for LocaleOptions {
 pub type DateMapping = FnvHashMap<DateForm, LocaleDate>;
 
 #[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Locale {
     pub version: String,
     pub lang: Option<Lang>,
@@ -193,6 +204,7 @@ impl FromNode for Locale {
 
 /// A date element defined inside a `<cs:locale>`
 #[derive(Debug, Eq, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct LocaleDate {
     pub form: DateForm,
     pub date_parts: Vec<DatePart>,
@@ -288,11 +300,27 @@ impl FromNode for LocaleOptionsNode {
                 info,
             )?,
             punctuation_in_quote: attribute_option(node, "punctuation-in-quote", info)?,
+            leading_articles: node
+                .attribute("leading-articles")
+                .map(|list| list.split_whitespace().map(SmartString::from).collect()),
         })
     }
 }
 
 impl Locale {
+    /// Removes a leading article from `s`, using the locale's `leading-articles` option if it has
+    /// one and otherwise the [built-in list](Lang::leading_articles) for its language.
+    pub fn strip_leading_article<'a>(&self, s: &'a str) -> &'a str {
+        match self.options_node.leading_articles {
+            Some(ref articles) => lang::strip_article(articles.iter().map(|a| a.as_str()), s),
+            None => self
+                .lang
+                .clone()
+                .unwrap_or_default()
+                .strip_leading_article(s),
+        }
+    }
+
     /// May return Some("") if the term is defined but empty. Not all code renders None in that
     /// case, so each call site should decide whether to slap .filter(|x| !x.is_empty()) after
     /// .get_text_term().
@@ -409,6 +437,11 @@ impl Locale {
         }
     }
 
+    /// Overlays `with` on top of this locale, as happens when a locale inherits from a more general
+    /// one (e.g. `en-AU` from `en-US`), or when a style's inline `<locale>` overrides a file.
+    ///
+    /// Terms and dates in `with` replace those here. Ordinal terms are replaced as a group if
+    /// `with` defines any of them.
     pub fn merge(&mut self, with: &Self) {
         fn extend<K: Clone + Eq + std::hash::Hash, V: Clone>(
             map: &mut FnvHashMap<K, V>,
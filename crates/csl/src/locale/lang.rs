@@ -111,6 +111,65 @@ impl Lang {
             _ => false,
         }
     }
+
+    /// Articles that can begin a title or an institution's name in this language, to be ignored
+    /// when sorting. Elided forms end with an apostrophe and attach to the next word.
+    ///
+    /// This is a built-in table covering only a handful of languages; every other language gets
+    /// an empty list. A locale can supply its own list with
+    /// `<style-options leading-articles="..."/>`, which
+    /// [`Locale::strip_leading_article`](crate::Locale::strip_leading_article) prefers over this.
+    pub fn leading_articles(&self) -> &'static [&'static str] {
+        let iso = match self {
+            Lang::Iso(iso, _) => iso,
+            _ => return &[],
+        };
+        match iso {
+            IsoLang::English => &["the", "a", "an"],
+            IsoLang::Deutsch => &["der", "die", "das", "ein", "eine"],
+            IsoLang::French => &["le", "la", "les", "l'", "l’", "un", "une"],
+            IsoLang::Spanish => &["el", "la", "los", "las", "un", "una"],
+            IsoLang::Portuguese => &["o", "a", "os", "as", "um", "uma"],
+            IsoLang::Other(code) => match code.as_str() {
+                "it" => &[
+                    "il", "lo", "la", "i", "gli", "le", "l'", "l’", "un", "una", "uno",
+                ],
+                "nl" => &["de", "het", "een"],
+                _ => &[],
+            },
+            _ => &[],
+        }
+    }
+
+    /// Removes a [leading article](Lang::leading_articles) from `s`, e.g. "The Beatles" becomes
+    /// "Beatles". Leaves `s` alone if nothing would be left.
+    pub fn strip_leading_article<'a>(&self, s: &'a str) -> &'a str {
+        strip_article(self.leading_articles().iter().cloned(), s)
+    }
+}
+
+pub(crate) fn strip_article<'a, 'b>(
+    articles: impl IntoIterator<Item = &'b str>,
+    s: &'a str,
+) -> &'a str {
+    for article in articles {
+        let head = match s.get(..article.len()) {
+            Some(head) if head.eq_ignore_ascii_case(article) => head,
+            _ => continue,
+        };
+        let rest = &s[head.len()..];
+        let rest = if article.ends_with(|c| c == '\'' || c == '’') {
+            rest
+        } else if rest.starts_with(char::is_whitespace) {
+            rest.trim_start()
+        } else {
+            continue;
+        };
+        if !rest.is_empty() {
+            return rest;
+        }
+    }
+    s
 }
 
 use crate::attr::GetAttribute;
@@ -392,6 +451,19 @@ fn parse_lang_garbage(inp: &str) -> IResult<&str, Lang> {
     alt((parse_unofficial, parse_iana, parse_iso, parse_iso_garbage))(inp)
 }
 
+#[test]
+fn strip_leading_article() {
+    let en = Lang::en_us();
+    assert_eq!(en.strip_leading_article("The Beatles"), "Beatles");
+    assert_eq!(en.strip_leading_article("Anthology"), "Anthology");
+    assert_eq!(en.strip_leading_article("A"), "A");
+    let fr = Lang::Iso(IsoLang::French, None);
+    assert_eq!(fr.strip_leading_article("L'Étranger"), "Étranger");
+    assert_eq!(fr.strip_leading_article("Les Misérables"), "Misérables");
+    let de = Lang::Iso(IsoLang::Deutsch, None);
+    assert_eq!(de.strip_leading_article("Der Prozess"), "Prozess");
+}
+
 #[test]
 fn lang_from_str() {
     let de_at = Lang::Iso(IsoLang::Deutsch, Some(IsoCountry::AT));
@@ -0,0 +1,310 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright © 2021 Corporation for Digital Scholarship
+
+//! Serialises a [`Locale`] back into a `<locale>` XML document.
+//!
+//! Maps are written out in a sorted order, so that writing the same locale twice produces the same
+//! file.
+
+use super::{Locale, LocaleDate, LocaleOptionsNode};
+use crate::style::{DatePart, DatePartForm, Formatting, TextCase};
+use crate::terms::*;
+use strum::AsStaticRef;
+
+const CSL_NAMESPACE: &str = "http://purl.org/net/xbiblio/csl";
+
+impl Locale {
+    /// Writes this locale out as a standalone CSL locale file.
+    ///
+    /// The output can be parsed again with [`Locale::parse`] to produce an equal `Locale`. This is
+    /// useful for caching the result of [`Locale::merge`], or for producing an
+    /// organisation-specific locale file from a customised one.
+    pub fn to_xml(&self) -> String {
+        let mut w = String::new();
+        w.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        w.push_str("<locale xmlns=\"");
+        w.push_str(CSL_NAMESPACE);
+        w.push('"');
+        attr(&mut w, "version", &self.version);
+        if let Some(lang) = &self.lang {
+            attr(&mut w, "xml:lang", &lang.to_string());
+        }
+        w.push_str(">\n");
+        write_options(&mut w, &self.options_node);
+        write_dates(&mut w, self);
+        write_terms(&mut w, self);
+        w.push_str("</locale>\n");
+        w
+    }
+}
+
+fn escape(w: &mut String, s: &str) {
+    for c in s.chars() {
+        match c {
+            '&' => w.push_str("&amp;"),
+            '<' => w.push_str("&lt;"),
+            '>' => w.push_str("&gt;"),
+            '"' => w.push_str("&quot;"),
+            c => w.push(c),
+        }
+    }
+}
+
+fn attr(w: &mut String, name: &str, value: &str) {
+    w.push(' ');
+    w.push_str(name);
+    w.push_str("=\"");
+    escape(w, value);
+    w.push('"');
+}
+
+fn write_options(w: &mut String, options: &LocaleOptionsNode) {
+    if options.limit_day_ordinals_to_day_1.is_none()
+        && options.punctuation_in_quote.is_none()
+        && options.leading_articles.is_none()
+    {
+        return;
+    }
+    w.push_str("  <style-options");
+    if let Some(x) = options.limit_day_ordinals_to_day_1 {
+        attr(
+            w,
+            "limit-day-ordinals-to-day-1",
+            if x { "true" } else { "false" },
+        );
+    }
+    if let Some(x) = options.punctuation_in_quote {
+        attr(w, "punctuation-in-quote", if x { "true" } else { "false" });
+    }
+    if let Some(ref articles) = options.leading_articles {
+        attr(w, "leading-articles", &articles.join(" "));
+    }
+    w.push_str("/>\n");
+}
+
+fn write_formatting(w: &mut String, formatting: Option<&Formatting>) {
+    let f = match formatting {
+        Some(f) => f,
+        None => return,
+    };
+    if let Some(x) = f.font_style {
+        attr(w, "font-style", x.as_ref());
+    }
+    if let Some(x) = f.font_variant {
+        attr(w, "font-variant", x.as_ref());
+    }
+    if let Some(x) = f.font_weight {
+        attr(w, "font-weight", x.as_ref());
+    }
+    if let Some(x) = f.text_decoration {
+        attr(w, "text-decoration", x.as_ref());
+    }
+    if let Some(x) = f.vertical_alignment {
+        attr(w, "vertical-align", x.as_ref());
+    }
+}
+
+fn write_text_case(w: &mut String, text_case: TextCase) {
+    if text_case != TextCase::None {
+        attr(w, "text-case", text_case.as_ref());
+    }
+}
+
+fn write_dates(w: &mut String, locale: &Locale) {
+    let mut dates: Vec<&LocaleDate> = locale.dates.values().collect();
+    dates.sort_by(|a, b| a.form.as_ref().cmp(b.form.as_ref()));
+    for date in dates {
+        w.push_str("  <date");
+        attr(w, "form", date.form.as_ref());
+        if let Some(delim) = &date.delimiter {
+            attr(w, "delimiter", delim);
+        }
+        write_text_case(w, date.text_case);
+        write_formatting(w, date.formatting.as_ref());
+        w.push_str(">\n");
+        for part in &date.date_parts {
+            write_date_part(w, part);
+        }
+        w.push_str("  </date>\n");
+    }
+}
+
+fn write_date_part(w: &mut String, part: &DatePart) {
+    w.push_str("    <date-part");
+    match part.form {
+        DatePartForm::Day(form) => {
+            attr(w, "name", "day");
+            attr(w, "form", form.as_ref());
+        }
+        DatePartForm::Month(form, strip_periods) => {
+            attr(w, "name", "month");
+            attr(w, "form", form.as_ref());
+            if strip_periods {
+                attr(w, "strip-periods", "true");
+            }
+        }
+        DatePartForm::Year(form) => {
+            attr(w, "name", "year");
+            attr(w, "form", form.as_ref());
+        }
+    }
+    if let Some(affixes) = &part.affixes {
+        attr(w, "prefix", &affixes.prefix);
+        attr(w, "suffix", &affixes.suffix);
+    }
+    if let Some(text_case) = part.text_case {
+        write_text_case(w, text_case);
+    }
+    write_formatting(w, part.formatting.as_ref());
+    if let Some(range_delimiter) = &part.range_delimiter {
+        attr(w, "range-delimiter", range_delimiter.as_ref());
+    }
+    w.push_str("/>\n");
+}
+
+fn ordinal_name(term: OrdinalTerm) -> String {
+    match term {
+        OrdinalTerm::Ordinal => "ordinal".into(),
+        OrdinalTerm::Mod100(n, _) => format!("ordinal-{:02}", n),
+        long => format!("long-ordinal-{:02}", long.to_number()),
+    }
+}
+
+/// One `<term>` element, with everything already converted to strings so the terms can be sorted.
+struct TermLine<'a> {
+    name: String,
+    form: Option<&'static str>,
+    gender: Option<&'static str>,
+    gender_form: Option<&'static str>,
+    match_: Option<&'static str>,
+    content: TermContent<'a>,
+}
+
+enum TermContent<'a> {
+    Plurality(&'a TermPlurality),
+    Text(&'a str),
+}
+
+fn form_attr(form: TermForm) -> Option<&'static str> {
+    match form {
+        TermForm::Long => None,
+        TermForm::Short => Some("short"),
+        TermForm::Symbol => Some("symbol"),
+    }
+}
+
+fn form_ext_attr(form: TermFormExtended) -> Option<&'static str> {
+    match form {
+        TermFormExtended::Long => None,
+        TermFormExtended::Short => Some("short"),
+        TermFormExtended::Symbol => Some("symbol"),
+        TermFormExtended::Verb => Some("verb"),
+        TermFormExtended::VerbShort => Some("verb-short"),
+    }
+}
+
+fn gender_attr(gender: Gender) -> Option<&'static str> {
+    match gender {
+        Gender::Neuter => None,
+        g => Some(g.as_static()),
+    }
+}
+
+fn write_terms(w: &mut String, locale: &Locale) {
+    let mut lines = Vec::new();
+    for (sel, content) in &locale.simple_terms {
+        let (name, form) = match *sel {
+            SimpleTermSelector::Misc(t, form) => (t.as_ref().to_owned(), form_ext_attr(form)),
+            SimpleTermSelector::Category(t, form) => (t.as_ref().to_owned(), form_attr(form)),
+            SimpleTermSelector::Quote(t) => (t.as_ref().to_owned(), None),
+        };
+        lines.push(TermLine {
+            name,
+            form,
+            gender: None,
+            gender_form: None,
+            match_: None,
+            content: TermContent::Plurality(content),
+        });
+    }
+    for (sel, GenderedTerm(content, gender)) in &locale.gendered_terms {
+        let (name, form) = match *sel {
+            GenderedTermSelector::Number(t, form) => (t.as_ref().to_owned(), form),
+            GenderedTermSelector::Locator(t, form) => (t.as_ref().to_owned(), form),
+            GenderedTermSelector::Month(t, form) => (t.as_ref().to_owned(), form),
+            GenderedTermSelector::Season(t, form) => (t.as_ref().to_owned(), form),
+        };
+        lines.push(TermLine {
+            name,
+            form: form_attr(form),
+            gender: gender_attr(*gender),
+            gender_form: None,
+            match_: None,
+            content: TermContent::Plurality(content),
+        });
+    }
+    for (RoleTermSelector(t, form), content) in &locale.role_terms {
+        lines.push(TermLine {
+            name: t.as_ref().to_owned(),
+            form: form_ext_attr(*form),
+            gender: None,
+            gender_form: None,
+            match_: None,
+            content: TermContent::Plurality(content),
+        });
+    }
+    for (OrdinalTermSelector(t, gender), content) in &locale.ordinal_terms {
+        let match_ = match *t {
+            OrdinalTerm::Mod100(n, m) if m != OrdinalMatch::default_for(n) => Some(m.as_static()),
+            _ => None,
+        };
+        lines.push(TermLine {
+            name: ordinal_name(*t),
+            form: None,
+            gender: None,
+            gender_form: gender_attr(*gender),
+            match_,
+            content: TermContent::Text(content),
+        });
+    }
+    if lines.is_empty() {
+        return;
+    }
+    lines.sort_by(|a, b| {
+        (&a.name, a.form, a.gender_form, a.match_).cmp(&(&b.name, b.form, b.gender_form, b.match_))
+    });
+    w.push_str("  <terms>\n");
+    for line in lines {
+        w.push_str("    <term");
+        attr(w, "name", &line.name);
+        if let Some(form) = line.form {
+            attr(w, "form", form);
+        }
+        if let Some(gender) = line.gender {
+            attr(w, "gender", gender);
+        }
+        if let Some(gender_form) = line.gender_form {
+            attr(w, "gender-form", gender_form);
+        }
+        if let Some(match_) = line.match_ {
+            attr(w, "match", match_);
+        }
+        w.push('>');
+        match line.content {
+            TermContent::Text(s) => escape(w, s),
+            TermContent::Plurality(TermPlurality::Invariant(s)) => escape(w, s),
+            TermContent::Plurality(TermPlurality::Pluralized { single, multiple }) => {
+                w.push_str("\n      <single>");
+                escape(w, single);
+                w.push_str("</single>\n      <multiple>");
+                escape(w, multiple);
+                w.push_str("</multiple>\n    ");
+            }
+        }
+        w.push_str("</term>\n");
+    }
+    w.push_str("  </terms>\n");
+}
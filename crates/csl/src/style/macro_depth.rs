@@ -0,0 +1,163 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright © 2021 Corporation for Digital Scholarship
+
+//! Macros may call other macros, but never themselves, directly or through others. The processor
+//! expands macros as it goes, so a cycle would otherwise run until the stack overflows. This finds
+//! them when the style is parsed, along with any chain of macro calls deeper than
+//! [ParseOptions::max_macro_depth](crate::ParseOptions::max_macro_depth).
+
+use super::*;
+use roxmltree::Node;
+
+/// Adds an error for each `<macro>` under `style_node` that calls itself, or goes deeper than
+/// `limit`.
+pub(crate) fn check_macros(
+    style_node: &Node,
+    macros: &FnvHashMap<SmartString, Vec<Element>>,
+    limit: Option<usize>,
+    errors: &mut Vec<InvalidCsl>,
+) {
+    let mut depths = MacroDepths::new(macros);
+    let macro_nodes = style_node
+        .children()
+        .filter(|n| n.is_element() && n.has_tag_name("macro"));
+    for node in macro_nodes {
+        let name = match node.attribute("name") {
+            Some(name) if macros.contains_key(name) => name,
+            _ => continue,
+        };
+        if let Some(message) = depths.problem(name, limit) {
+            errors.push(InvalidCsl::new(&node, message));
+        }
+    }
+}
+
+impl Style {
+    /// Checks the macros of a style that was not parsed from XML, e.g. one deserialized from a
+    /// bundle, as [`Style::parse_with_opts`] would have. There is no XML to point to, so the
+    /// errors have an empty range.
+    pub fn check_macros(&self, max_depth: Option<usize>) -> Result<(), CslError> {
+        check_macro_map(&self.macros, max_depth)
+    }
+}
+
+/// [check_macros] for a set of macros without their XML, e.g. a style's merged with a CSL-M
+/// module's.
+pub fn check_macro_map(
+    macros: &FnvHashMap<SmartString, Vec<Element>>,
+    limit: Option<usize>,
+) -> Result<(), CslError> {
+    let mut depths = MacroDepths::new(macros);
+    let mut names: Vec<&str> = macros.keys().map(|k| k.as_str()).collect();
+    names.sort_unstable();
+    let errors: Vec<InvalidCsl> = names
+        .into_iter()
+        .filter_map(|name| depths.problem(name, limit))
+        .map(|message| InvalidCsl {
+            severity: Severity::Error,
+            range: 0..0,
+            message,
+            hint: String::new(),
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(CslError(errors))
+    }
+}
+
+struct MacroDepths<'a> {
+    macros: &'a FnvHashMap<SmartString, Vec<Element>>,
+    /// How many macros deep each one goes, counting itself. A macro on its own is 1.
+    known: FnvHashMap<&'a str, usize>,
+    stack: Vec<&'a str>,
+}
+
+impl<'a> MacroDepths<'a> {
+    fn new(macros: &'a FnvHashMap<SmartString, Vec<Element>>) -> Self {
+        MacroDepths {
+            macros,
+            known: FnvHashMap::default(),
+            stack: Vec::new(),
+        }
+    }
+
+    /// What is wrong with the macro `name`, if anything.
+    fn problem(&mut self, name: &'a str, limit: Option<usize>) -> Option<String> {
+        match self.depth(name) {
+            // Macros that only call into someone else's cycle get left alone, the macros in the
+            // cycle are reported already.
+            Err(cycle) if cycle[0] != name => None,
+            Err(cycle) => Some(format!("macro calls itself: {}", cycle.join(" -> "))),
+            Ok(depth) => limit.filter(|&l| depth > l).map(|limit| {
+                format!(
+                    "macro `{}` nests {} macros deep, more than the limit of {}",
+                    name, depth, limit
+                )
+            }),
+        }
+    }
+
+    /// The error is the cycle, starting and ending with the same macro.
+    fn depth(&mut self, name: &'a str) -> Result<usize, Vec<&'a str>> {
+        if let Some(&depth) = self.known.get(name) {
+            return Ok(depth);
+        }
+        if let Some(pos) = self.stack.iter().position(|&m| m == name) {
+            let mut cycle = self.stack[pos..].to_vec();
+            cycle.push(name);
+            return Err(cycle);
+        }
+        // An undefined macro is already an error elsewhere
+        let (key, elements) = match self.macros.get_key_value(name) {
+            Some(found) => found,
+            None => return Ok(0),
+        };
+        let mut called = Vec::new();
+        macro_calls(elements, &mut called);
+        self.stack.push(key);
+        let mut deepest = 0;
+        for callee in called {
+            match self.depth(callee) {
+                Ok(d) => deepest = deepest.max(d),
+                Err(cycle) => {
+                    self.stack.pop();
+                    return Err(cycle);
+                }
+            }
+        }
+        self.stack.pop();
+        self.known.insert(key, deepest + 1);
+        Ok(deepest + 1)
+    }
+}
+
+fn macro_calls<'a>(elements: &'a [Element], out: &mut Vec<&'a str>) {
+    for el in elements {
+        match el {
+            Element::Text(TextElement {
+                source: TextSource::Macro(name),
+                ..
+            }) => out.push(name),
+            Element::Group(g) => macro_calls(&g.elements, out),
+            Element::Choose(c) => {
+                let Choose(iff, elseifs, elsee) = &**c;
+                macro_calls(&iff.1, out);
+                for elseif in elseifs {
+                    macro_calls(&elseif.1, out);
+                }
+                macro_calls(&elsee.0, out);
+            }
+            Element::Names(names) => {
+                if let Some(Substitute(elements)) = &names.substitute {
+                    macro_calls(elements, out);
+                }
+            }
+            _ => {}
+        }
+    }
+}
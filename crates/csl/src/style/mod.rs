@@ -15,20 +15,27 @@ use crate::version::{CslVersionReq, Features};
 use crate::SmartString;
 use fnv::{FnvHashMap, FnvHashSet};
 #[cfg(feature = "serde")]
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::str::FromStr;
 use std::sync::Arc;
 
 pub mod dependent;
 pub mod info;
+mod macro_depth;
+mod required_terms;
+mod requirements;
+pub mod visit;
 use info::Info;
+pub use macro_depth::check_macro_map;
+pub(crate) use macro_depth::check_macros;
 
 type TermPlural = bool;
 type StripPeriods = bool;
 type Quotes = bool;
 
 #[derive(Debug, Eq, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum TextSource {
     Macro(SmartString),
     Value(SmartString),
@@ -42,6 +49,7 @@ impl Default for TextSource {
 }
 
 #[derive(Default, Debug, Eq, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TextElement {
     pub source: TextSource,
     pub formatting: Option<Formatting>,
@@ -53,6 +61,7 @@ pub struct TextElement {
 }
 
 #[derive(Debug, Eq, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct LabelElement {
     pub variable: NumberVariable,
     pub form: TermForm,
@@ -64,6 +73,7 @@ pub struct LabelElement {
 }
 
 #[derive(Debug, Eq, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct NumberElement {
     pub variable: NumberVariable,
     pub form: NumericForm,
@@ -74,6 +84,7 @@ pub struct NumberElement {
 }
 
 #[derive(Debug, Eq, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Element {
     /// <cs:text>
     Text(TextElement),
@@ -94,6 +105,7 @@ pub enum Element {
 }
 
 #[derive(Debug, Eq, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Group {
     pub formatting: Option<Formatting>,
     pub delimiter: Option<SmartString>,
@@ -105,6 +117,7 @@ pub struct Group {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum BodyDate {
     Indep(IndependentDate),
     Local(LocalizedDate),
@@ -121,6 +134,7 @@ impl BodyDate {
 
 /// e.g. for <text variable="title" form="short" />
 #[derive(AsRefStr, EnumString, EnumProperty, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum VariableForm {
     Long,
@@ -135,6 +149,7 @@ impl Default for VariableForm {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum NumericForm {
     Numeric,
@@ -151,6 +166,7 @@ impl Default for NumericForm {
 }
 
 #[derive(Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Affixes {
     pub prefix: SmartString,
     pub suffix: SmartString,
@@ -165,7 +181,7 @@ impl Default for Affixes {
     }
 }
 
-#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Eq, Copy, Clone, Default, PartialEq, Hash)]
 pub struct Formatting {
     #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
@@ -245,7 +261,7 @@ impl fmt::Debug for Formatting {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
-#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum DisplayMode {
     Block,
@@ -256,6 +272,7 @@ pub enum DisplayMode {
 impl EnumGetAttribute for DisplayMode {}
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum TextCase {
     None,
@@ -275,7 +292,7 @@ impl Default for TextCase {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
-#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum FontStyle {
     Normal,
@@ -291,7 +308,7 @@ impl Default for FontStyle {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
-#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum FontVariant {
     Normal,
@@ -306,7 +323,7 @@ impl Default for FontVariant {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
-#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum FontWeight {
     Normal,
@@ -322,7 +339,7 @@ impl Default for FontWeight {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
-#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum TextDecoration {
     None,
@@ -337,7 +354,7 @@ impl Default for TextDecoration {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
-#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum VerticalAlignment {
     #[strum(serialize = "baseline")]
     Baseline,
@@ -355,6 +372,7 @@ impl Default for VerticalAlignment {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum Plural {
     Contextual,
@@ -383,6 +401,7 @@ impl IsIndependent for Cond {
 }
 
 #[derive(Debug, Eq, Hash, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Cond {
     IsNumeric(AnyVariable),
     Variable(AnyVariable),
@@ -401,6 +420,7 @@ pub enum Cond {
 }
 
 #[derive(Debug, Eq, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CondSet {
     pub match_type: Match,
     pub conds: FnvHashSet<Cond>,
@@ -458,7 +478,8 @@ pub(crate) struct ConditionParser {
     pub is_uncertain_date: Vec<DateVariable>,
 
     // TODO: do not populate in plain CSL mode
-    pub jurisdiction: Option<SmartString>,
+    /// Space-separated, each one a colon-separated path like `us:ca`.
+    pub jurisdiction: Vec<SmartString>,
     pub subjurisdictions: Option<u32>,
 
     /// https://citeproc-js.readthedocs.io/en/latest/csl-m/index.html#has-year-only-extension
@@ -478,6 +499,7 @@ pub(crate) struct ConditionParser {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum Context {
     Citation,
@@ -497,7 +519,7 @@ impl ConditionParser {
             && self.has_year_only.is_empty()
             && self.has_day.is_empty()
             && self.has_month_or_season.is_empty()
-            && self.jurisdiction.is_none()
+            && self.jurisdiction.is_empty()
             && self.subjurisdictions.is_none()
             && self.is_plural.is_empty()
             && self.context.is_none()
@@ -505,13 +527,14 @@ impl ConditionParser {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum Match {
     Any,
     All,
     None,
     /// CSL-M only
-    #[strum(props(csl = "0", cslM = "1"))]
+    #[strum(props(csl = "0", cslM = "1", cslFeature = "conditions"))]
     Nand,
 }
 
@@ -523,19 +546,24 @@ impl Default for Match {
 }
 
 #[derive(Debug, Eq, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 // in CSL 1.0.1, conditions.len() == 1
 pub struct IfThen(pub Conditions, pub Vec<Element>);
 
 #[derive(Debug, Eq, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Conditions(pub Match, pub Vec<CondSet>);
 
 #[derive(Debug, Eq, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Else(pub Vec<Element>);
 
 #[derive(Debug, Eq, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Choose(pub IfThen, pub Vec<IfThen>, pub Else);
 
 #[derive(Debug, Default, Eq, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Names {
     // inheritable.
     pub delimiter: Option<SmartString>,
@@ -563,6 +591,7 @@ pub struct Names {
 /// cs:name. Similarly, names-delimiter corresponds to the delimiter attribute on cs:names.
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum NameAnd {
     Text,
@@ -573,12 +602,14 @@ impl EnumGetAttribute for NameAnd {}
 
 /// It is not entirely clear which attributes `<cs:with>` supports.
 #[derive(Debug, Eq, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct NameWith {
     pub formatting: Option<Formatting>,
     pub affixes: Option<Affixes>,
 }
 
 #[derive(Debug, Eq, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Institution {
     pub and: Option<NameAnd>,
     pub delimiter: Option<SmartString>,
@@ -601,6 +632,7 @@ pub struct Institution {
 }
 
 #[derive(Debug, Eq, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct InstitutionPart {
     pub name: InstitutionPartName,
     pub formatting: Option<Formatting>,
@@ -612,6 +644,7 @@ pub struct InstitutionPart {
 type IfShort = bool;
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum InstitutionPartName {
     Long(IfShort),
@@ -627,6 +660,7 @@ impl Default for InstitutionPartName {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum InstitutionParts {
     Long,
@@ -644,6 +678,7 @@ impl Default for InstitutionParts {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum InstitutionUseFirst {
     /// Set with `use-first="1"`
@@ -656,6 +691,7 @@ pub enum InstitutionUseFirst {
 }
 
 #[derive(Debug, Eq, Clone, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Name {
     pub and: Option<NameAnd>,
     /// Between individual names for the same variable
@@ -795,6 +831,7 @@ impl Name {
     }
 }
 #[derive(Debug, Default, Eq, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct NameLabelInput {
     pub form: Option<TermFormExtended>,
     pub formatting: Option<Formatting>,
@@ -838,6 +875,7 @@ impl NameLabelInput {
 }
 
 #[derive(Debug, Eq, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct NameLabel {
     pub form: TermFormExtended,
     pub formatting: Option<Formatting>,
@@ -849,6 +887,7 @@ pub struct NameLabel {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct NameEtAl {
     // TODO: only accept "et-al" or "and others"
     pub term: String,
@@ -856,6 +895,7 @@ pub struct NameEtAl {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum DemoteNonDroppingParticle {
     Never,
@@ -871,6 +911,7 @@ impl Default for DemoteNonDroppingParticle {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum DelimiterPrecedes {
     Contextual,
@@ -887,6 +928,7 @@ impl Default for DelimiterPrecedes {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum NameForm {
     Long,
@@ -896,6 +938,7 @@ pub enum NameForm {
 impl EnumGetAttribute for NameForm {}
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum NameAsSortOrder {
     First,
@@ -904,6 +947,7 @@ pub enum NameAsSortOrder {
 impl EnumGetAttribute for NameAsSortOrder {}
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum NamePartName {
     Given,
@@ -912,6 +956,7 @@ pub enum NamePartName {
 impl EnumGetAttribute for NamePartName {}
 
 #[derive(Debug, Eq, Clone, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct NamePart {
     pub name: NamePartName,
     pub affixes: Option<Affixes>,
@@ -920,9 +965,11 @@ pub struct NamePart {
 }
 
 #[derive(Debug, Eq, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Substitute(pub Vec<Element>);
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum GivenNameDisambiguationRule {
     AllNames,
@@ -941,6 +988,7 @@ impl Default for GivenNameDisambiguationRule {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum Collapse {
     CitationNumber,
@@ -951,6 +999,7 @@ pub enum Collapse {
 impl EnumGetAttribute for Collapse {}
 
 #[derive(Debug, Eq, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Citation {
     pub disambiguate_add_names: bool,
     pub disambiguate_add_givenname: bool,
@@ -1014,6 +1063,7 @@ impl Citation {
 }
 
 #[derive(Debug, Eq, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Bibliography {
     pub sort: Option<Sort>,
     pub layout: Layout,
@@ -1029,6 +1079,7 @@ pub struct Bibliography {
 
 /// cs:intext element
 #[derive(Debug, Eq, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct InText {
     pub layout: Layout,
     pub and: Option<NameAnd>,
@@ -1037,6 +1088,7 @@ pub struct InText {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum SecondFieldAlign {
     Flush,
@@ -1045,6 +1097,7 @@ pub enum SecondFieldAlign {
 impl EnumGetAttribute for SecondFieldAlign {}
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum SubsequentAuthorSubstituteRule {
     CompleteAll,
@@ -1061,11 +1114,13 @@ impl Default for SubsequentAuthorSubstituteRule {
 }
 
 #[derive(Debug, Eq, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Sort {
     pub keys: Vec<SortKey>,
 }
 
 #[derive(Debug, Eq, Clone, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SortKey {
     pub sort_source: SortSource,
     pub names_min: Option<u32>,
@@ -1090,16 +1145,39 @@ impl SortKey {
             direction: None,
         }
     }
+
+    /// The `<name>` options this key imposes on any names rendered for it. `names-min` and
+    /// `names-use-first` replace both the first and the subsequent et-al settings, and names are
+    /// always in sort order.
+    pub fn name_overrides(&self) -> Name {
+        Name {
+            et_al_min: self.names_min,
+            et_al_subsequent_min: self.names_min,
+            et_al_use_first: self.names_use_first,
+            et_al_subsequent_use_first: self.names_use_first,
+            et_al_use_last: self.names_use_last,
+            name_as_sort_order: Some(NameAsSortOrder::All),
+            ..Default::default()
+        }
+    }
+
+    /// The options actually used for a names element that would otherwise have `inherited`,
+    /// when it is rendered for this key.
+    pub fn effective_name_options(&self, inherited: &Name) -> Name {
+        inherited.merge(&self.name_overrides())
+    }
 }
 
 /// You must sort on either a variable or a macro
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum SortSource {
     Variable(AnyVariable),
     Macro(SmartString),
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum SortDirection {
     Ascending,
@@ -1115,6 +1193,7 @@ impl Default for SortDirection {
 
 // TODO: Multiple layouts in CSL-M with locale="en es de" etc
 #[derive(Default, Debug, Eq, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Layout {
     pub affixes: Option<Affixes>,
     pub formatting: Option<Formatting>,
@@ -1132,7 +1211,7 @@ pub struct MacroMap {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq)]
-#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
 #[strum(serialize_all = "kebab_case")]
 pub enum StyleClass {
@@ -1148,6 +1227,7 @@ impl Default for StyleClass {
 }
 
 #[derive(Debug, Eq, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Style {
     pub class: StyleClass,
     pub macros: FnvHashMap<SmartString, Vec<Element>>,
@@ -1155,6 +1235,7 @@ pub struct Style {
     pub bibliography: Option<Bibliography>,
     pub intext: Option<InText>,
     pub info: Info,
+    #[cfg_attr(feature = "serde", serde(with = "crate::version::features_lossless"))]
     pub features: Features,
     pub name_inheritance: Name,
     pub names_delimiter: Option<SmartString>,
@@ -1224,6 +1305,7 @@ impl Style {
 }
 
 #[derive(Debug, Eq, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct RangeDelimiter(pub SmartString);
 
 impl Default for RangeDelimiter {
@@ -1246,6 +1328,7 @@ impl FromStr for RangeDelimiter {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum DateParts {
     YearMonthDay,
@@ -1271,6 +1354,7 @@ pub(crate) enum DatePartName {
 impl EnumGetAttribute for DatePartName {}
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum DayForm {
     Numeric,
@@ -1285,6 +1369,7 @@ impl Default for DayForm {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum MonthForm {
     Long,
@@ -1300,6 +1385,7 @@ impl Default for MonthForm {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum YearForm {
     Long,
@@ -1313,6 +1399,7 @@ impl Default for YearForm {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum DateForm {
     Text,
@@ -1321,6 +1408,7 @@ pub enum DateForm {
 impl EnumGetAttribute for DateForm {}
 
 #[derive(Debug, Display, Eq, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum DatePartForm {
     Day(DayForm),
     Month(MonthForm, StripPeriods),
@@ -1352,6 +1440,7 @@ impl PartialOrd for DatePartForm {
 }
 
 #[derive(Debug, Default, Eq, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DatePart {
     pub form: DatePartForm,
     pub affixes: Option<Affixes>,
@@ -1370,6 +1459,7 @@ impl Default for DatePartForm {
 /// A date element that fully defines its own output.
 /// It is 'independent' of any localization.
 #[derive(Debug, Eq, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct IndependentDate {
     pub variable: DateVariable,
     // TODO: limit each <date-part name="XXX"> to one per?
@@ -1383,6 +1473,7 @@ pub struct IndependentDate {
 
 /// A date element in the main body of a style that refers to a `LocaleDate`
 #[derive(Debug, Eq, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct LocalizedDate {
     pub variable: DateVariable,
     pub parts_selector: DateParts,
@@ -1395,6 +1486,7 @@ pub struct LocalizedDate {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum Position {
     First,
@@ -1448,6 +1540,7 @@ impl Position {
 
 /// [Spec](https://docs.citationstyles.org/en/stable/specification.html#appendix-v-page-range-formats)
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum PageRangeFormat {
     Chicago,
@@ -1458,6 +1551,7 @@ pub enum PageRangeFormat {
 impl EnumGetAttribute for PageRangeFormat {}
 
 #[derive(AsRefStr, EnumProperty, EnumIter, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum CslType {
     Article,
@@ -0,0 +1,221 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright © 2021 Corporation for Digital Scholarship
+
+use super::*;
+use crate::terms::*;
+use strum::IntoEnumIterator;
+
+impl Style {
+    /// Lists every locale term rendering this style could look up, in the order they are first
+    /// found. Packagers can use this to trim locales down to what a style needs.
+    ///
+    /// This errs on the side of listing too much: a localized date lists every month, and
+    /// `<names>` lists `and` and `et-al` whatever the name options are. Terms every document uses,
+    /// like the quote marks, are always included. `ordinal` stands for the whole `ordinal-00`
+    /// through `ordinal-99` family, as locales only define a few of them.
+    pub fn required_terms(&self) -> Vec<AnyTermName> {
+        let mut walker = TermWalker {
+            style: self,
+            seen_macros: FnvHashSet::default(),
+            terms: Vec::new(),
+        };
+        walker.push(AnyTermName::Quote(QuoteTerm::OpenQuote));
+        walker.push(AnyTermName::Quote(QuoteTerm::CloseQuote));
+        walker.push(AnyTermName::Quote(QuoteTerm::OpenInnerQuote));
+        walker.push(AnyTermName::Quote(QuoteTerm::CloseInnerQuote));
+        walker.layout(&self.citation.layout, self.citation.sort.as_ref());
+        if let Some(intext) = &self.intext {
+            walker.layout(&intext.layout, None);
+        }
+        if let Some(bib) = &self.bibliography {
+            walker.layout(&bib.layout, bib.sort.as_ref());
+        }
+        walker.terms
+    }
+}
+
+struct TermWalker<'a> {
+    style: &'a Style,
+    seen_macros: FnvHashSet<&'a str>,
+    terms: Vec<AnyTermName>,
+}
+
+impl<'a> TermWalker<'a> {
+    fn push(&mut self, term: AnyTermName) {
+        if !self.terms.contains(&term) {
+            self.terms.push(term);
+        }
+    }
+
+    fn layout(&mut self, layout: &'a Layout, sort: Option<&'a Sort>) {
+        self.elements(&layout.elements);
+        for key in sort.iter().flat_map(|sort| &sort.keys) {
+            if let SortSource::Macro(name) = &key.sort_source {
+                self.macro_(name);
+            }
+        }
+    }
+
+    fn elements(&mut self, elements: &'a [Element]) {
+        for el in elements {
+            self.element(el);
+        }
+    }
+
+    fn macro_(&mut self, name: &'a str) {
+        // Also stops macros that call themselves from looping forever
+        if !self.seen_macros.insert(name) {
+            return;
+        }
+        if let Some(elements) = self.style.macros.get(name) {
+            self.elements(elements);
+        }
+    }
+
+    fn element(&mut self, el: &'a Element) {
+        match el {
+            Element::Text(text) => match &text.source {
+                TextSource::Macro(name) => self.macro_(name),
+                TextSource::Term(sel, _) => self.push(term_name(*sel)),
+                TextSource::Variable(StandardVariable::Number(var), _) => self.number(*var),
+                _ => {}
+            },
+            Element::Label(label) => match label.variable {
+                NumberVariable::Locator => {
+                    for loc in LocatorType::iter() {
+                        self.push(AnyTermName::Loc(loc));
+                    }
+                }
+                var => self.push(AnyTermName::Number(var)),
+            },
+            Element::Number(num) => {
+                self.number(num.variable);
+                match num.form {
+                    NumericForm::Ordinal => self.ordinals(false),
+                    NumericForm::LongOrdinal => self.ordinals(true),
+                    _ => {}
+                }
+            }
+            Element::Group(g) => self.elements(&g.elements),
+            Element::Choose(c) => {
+                let Choose(iff, elseifs, elsee) = &**c;
+                self.elements(&iff.1);
+                for elseif in elseifs {
+                    self.elements(&elseif.1);
+                }
+                self.elements(&elsee.0);
+            }
+            Element::Names(names) => self.names(names),
+            Element::Date(date) => self.date(date),
+        }
+    }
+
+    /// Numbers can be lists and ranges, joined with `and` or a range delimiter.
+    fn number(&mut self, var: NumberVariable) {
+        self.push(AnyTermName::Misc(MiscTerm::And));
+        match var {
+            NumberVariable::Page
+            | NumberVariable::Locator
+            | NumberVariable::Issue
+            | NumberVariable::Volume
+            | NumberVariable::Edition
+            | NumberVariable::Number => self.push(AnyTermName::Misc(MiscTerm::PageRangeDelimiter)),
+            NumberVariable::CollectionNumber => {
+                self.push(AnyTermName::Misc(MiscTerm::YearRangeDelimiter))
+            }
+            _ => {}
+        }
+    }
+
+    fn ordinals(&mut self, long: bool) {
+        if long {
+            for n in 1..=10 {
+                self.push(AnyTermName::Ordinal(OrdinalTerm::from_number_long(n)));
+            }
+        }
+        // Long ordinals fall back to these for numbers above ten
+        self.push(AnyTermName::Ordinal(OrdinalTerm::Ordinal));
+    }
+
+    fn names(&mut self, names: &'a Names) {
+        self.push(AnyTermName::Misc(MiscTerm::And));
+        let et_al = match &names.et_al {
+            Some(el) if el.term == "and others" => MiscTerm::AndOthers,
+            _ => MiscTerm::EtAl,
+        };
+        self.push(AnyTermName::Misc(et_al));
+        if names.label.is_some() {
+            for &var in &names.variables {
+                if let Some(role) = RoleTerm::from_name_var(var) {
+                    self.push(AnyTermName::Role(role));
+                }
+            }
+            if names.variables.contains(&NameVariable::Editor)
+                && names.variables.contains(&NameVariable::Translator)
+            {
+                self.push(AnyTermName::Role(RoleTerm::EditorTranslator));
+            }
+        }
+        if let Some(Substitute(elements)) = &names.substitute {
+            self.elements(elements);
+        }
+    }
+
+    fn date(&mut self, date: &'a BodyDate) {
+        let (year, month, ordinal_day) = match date {
+            BodyDate::Indep(indep) => {
+                let mut parts = (false, false, false);
+                for part in &indep.date_parts {
+                    match part.form {
+                        DatePartForm::Year(_) => parts.0 = true,
+                        DatePartForm::Month(MonthForm::Long, _)
+                        | DatePartForm::Month(MonthForm::Short, _) => parts.1 = true,
+                        DatePartForm::Day(DayForm::Ordinal) => parts.2 = true,
+                        _ => {}
+                    }
+                }
+                parts
+            }
+            // The parts' forms come from the locale, so assume the worst
+            BodyDate::Local(local) => match local.parts_selector {
+                DateParts::Year => (true, false, false),
+                DateParts::YearMonth => (true, true, false),
+                DateParts::YearMonthDay => (true, true, true),
+            },
+        };
+        if year {
+            self.push(AnyTermName::Misc(MiscTerm::Ad));
+            self.push(AnyTermName::Misc(MiscTerm::Bc));
+        }
+        if month {
+            for m in 1..=12 {
+                if let Some(month) = MonthTerm::from_u32(m) {
+                    self.push(AnyTermName::Month(month));
+                }
+            }
+            self.push(AnyTermName::Season(SeasonTerm::Season01));
+            self.push(AnyTermName::Season(SeasonTerm::Season02));
+            self.push(AnyTermName::Season(SeasonTerm::Season03));
+            self.push(AnyTermName::Season(SeasonTerm::Season04));
+        }
+        if ordinal_day {
+            self.ordinals(false);
+        }
+    }
+}
+
+fn term_name(sel: TextTermSelector) -> AnyTermName {
+    match sel {
+        TextTermSelector::Simple(SimpleTermSelector::Misc(t, _)) => AnyTermName::Misc(t),
+        TextTermSelector::Simple(SimpleTermSelector::Category(t, _)) => AnyTermName::Category(t),
+        TextTermSelector::Simple(SimpleTermSelector::Quote(t)) => AnyTermName::Quote(t),
+        TextTermSelector::Gendered(GenderedTermSelector::Number(t, _)) => AnyTermName::Number(t),
+        TextTermSelector::Gendered(GenderedTermSelector::Locator(t, _)) => AnyTermName::Loc(t),
+        TextTermSelector::Gendered(GenderedTermSelector::Month(t, _)) => AnyTermName::Month(t),
+        TextTermSelector::Gendered(GenderedTermSelector::Season(t, _)) => AnyTermName::Season(t),
+        TextTermSelector::Role(RoleTermSelector(t, _)) => AnyTermName::Role(t),
+    }
+}
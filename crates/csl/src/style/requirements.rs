@@ -0,0 +1,179 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright © 2021 Corporation for Digital Scholarship
+
+use super::visit::{walk_names, walk_text, Visitor};
+use super::*;
+
+impl Style {
+    /// Lists the reference fields this style could render or sort by for an item of type
+    /// `csl_type`, in the order they are first found, so a data entry form can mark them.
+    ///
+    /// Only `type` and `context` conditions are known without a reference, so a `<choose>` is
+    /// followed down every branch that could be taken, and every `<substitute>` is included. This
+    /// lists a field if any reference of that type could need it. Variables that do not come from
+    /// the reference, like `locator` and `citation-number`, are left out. A short form lists the
+    /// fields it falls back to, e.g. `title-short` and then `title`.
+    pub fn requirements_for(&self, csl_type: CslType) -> Vec<AnyVariable> {
+        let mut walker = RequirementsWalker {
+            style: self,
+            csl_type,
+            context: Context::Citation,
+            variables: Vec::new(),
+        };
+        walker.layout(&self.citation.layout, self.citation.sort.as_ref());
+        if let Some(intext) = &self.intext {
+            walker.layout(&intext.layout, None);
+        }
+        if let Some(bib) = &self.bibliography {
+            walker.context = Context::Bibliography;
+            walker.layout(&bib.layout, bib.sort.as_ref());
+        }
+        walker.variables
+    }
+}
+
+struct RequirementsWalker<'a> {
+    style: &'a Style,
+    csl_type: CslType,
+    context: Context,
+    variables: Vec<AnyVariable>,
+}
+
+impl<'a> RequirementsWalker<'a> {
+    fn push(&mut self, var: AnyVariable) {
+        let var = match var {
+            AnyVariable::Number(NumberVariable::PageFirst) => {
+                AnyVariable::Number(NumberVariable::Page)
+            }
+            AnyVariable::Ordinary(Variable::CitationLabel)
+            | AnyVariable::Number(NumberVariable::CitationNumber)
+            | AnyVariable::Name(NameVariable::Dummy) => return,
+            var if var.is_independent() => return,
+            var => var,
+        };
+        if !self.variables.contains(&var) {
+            self.variables.push(var);
+        }
+    }
+
+    fn layout(&mut self, layout: &'a Layout, sort: Option<&'a Sort>) {
+        self.elements(&layout.elements);
+        for key in sort.iter().flat_map(|sort| &sort.keys) {
+            match &key.sort_source {
+                SortSource::Variable(var) => self.push(*var),
+                SortSource::Macro(name) => {
+                    self.macro_(name);
+                }
+            }
+        }
+    }
+
+    /// `None` when it depends on more than the type and the context.
+    fn conditions(&self, conditions: &Conditions) -> Option<bool> {
+        let Conditions(match_type, sets) = conditions;
+        let results = sets.iter().map(|set| self.cond_set(set));
+        run_matcher(results, match_type)
+    }
+
+    fn cond_set(&self, set: &CondSet) -> Option<bool> {
+        let features = &self.style.features;
+        let results = set.conds.iter().filter_map(|cond| match cond {
+            Cond::Type(typ) => Some(Some(*typ == self.csl_type)),
+            Cond::Context(context) => Some(Some(*context == self.context)),
+            // As in a processor, these are ignored without the feature
+            Cond::HasYearOnly(_) | Cond::HasMonthOrSeason(_) | Cond::HasDay(_)
+                if !features.condition_date_parts =>
+            {
+                None
+            }
+            _ => Some(None),
+        });
+        run_matcher(results, &set.match_type)
+    }
+}
+
+impl<'a> Visitor<'a> for RequirementsWalker<'a> {
+    fn style(&self) -> &'a Style {
+        self.style
+    }
+
+    fn text(&mut self, text: &'a TextElement) -> bool {
+        match &text.source {
+            TextSource::Variable(StandardVariable::Ordinary(var), form) => {
+                match var.form_fallbacks(*form) {
+                    Some(fallbacks) => {
+                        for &var in fallbacks {
+                            self.push(AnyVariable::Ordinary(var));
+                        }
+                    }
+                    None => self.push(AnyVariable::Ordinary(*var)),
+                }
+            }
+            TextSource::Variable(var, _) => self.push(var.into()),
+            _ => return walk_text(self, text),
+        }
+        false
+    }
+
+    fn number(&mut self, number: &'a NumberElement) -> bool {
+        self.push(AnyVariable::Number(number.variable));
+        false
+    }
+
+    fn names(&mut self, names: &'a Names) -> bool {
+        for &var in &names.variables {
+            self.push(AnyVariable::Name(var));
+        }
+        walk_names(self, names)
+    }
+
+    fn date(&mut self, date: &'a BodyDate) -> bool {
+        self.push(AnyVariable::Date(date.variable()));
+        false
+    }
+
+    /// Takes every branch that might be taken, stopping at one that certainly is.
+    fn choose(&mut self, choose: &'a Choose) -> bool {
+        let Choose(iff, elseifs, elsee) = choose;
+        for branch in std::iter::once(iff).chain(elseifs) {
+            match self.conditions(&branch.0) {
+                Some(true) => return self.elements(&branch.1),
+                None => {
+                    self.elements(&branch.1);
+                }
+                Some(false) => {}
+            }
+        }
+        self.elements(&elsee.0)
+    }
+}
+
+/// Three-valued `match`, where `None` is unknown.
+fn run_matcher(results: impl Iterator<Item = Option<bool>>, match_type: &Match) -> Option<bool> {
+    let (mut any_true, mut any_false, mut any_unknown) = (false, false, false);
+    for result in results {
+        match result {
+            Some(true) => any_true = true,
+            Some(false) => any_false = true,
+            None => any_unknown = true,
+        }
+    }
+    let known = |certain: bool| {
+        if certain {
+            Some(true)
+        } else if any_unknown {
+            None
+        } else {
+            Some(false)
+        }
+    };
+    match *match_type {
+        Match::Any => known(any_true),
+        Match::All => known(any_false).map(|b| !b),
+        Match::None => known(any_true).map(|b| !b),
+        Match::Nand => known(any_false),
+    }
+}
@@ -0,0 +1,93 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright © 2021 Corporation for Digital Scholarship
+
+//! A walk over a style's elements for code that looks at a style without rendering it. It goes
+//! into groups, macros, every branch of `<choose>` and `<names>` substitutes.
+//!
+//! Macros are expanded where they are called. Parsing rejects macros that call themselves (see
+//! [check_macro_map](crate::check_macro_map)), so the walk does not keep track of them.
+
+use super::*;
+
+/// Each method defaults to walking on into its element with the `walk_*` function of the same
+/// name. Override one to look at an element, and call that function to carry on into it.
+/// Returning `true` from any method stops the whole walk.
+pub trait Visitor<'a> {
+    /// The style whose macros are expanded.
+    fn style(&self) -> &'a Style;
+
+    fn elements(&mut self, elements: &'a [Element]) -> bool {
+        elements.iter().any(|el| self.element(el))
+    }
+    fn element(&mut self, element: &'a Element) -> bool {
+        walk_element(self, element)
+    }
+    fn text(&mut self, text: &'a TextElement) -> bool {
+        walk_text(self, text)
+    }
+    fn macro_(&mut self, name: &'a str) -> bool {
+        walk_macro(self, name)
+    }
+    fn choose(&mut self, choose: &'a Choose) -> bool {
+        walk_choose(self, choose)
+    }
+    fn names(&mut self, names: &'a Names) -> bool {
+        walk_names(self, names)
+    }
+    fn label(&mut self, _label: &'a LabelElement) -> bool {
+        false
+    }
+    fn number(&mut self, _number: &'a NumberElement) -> bool {
+        false
+    }
+    fn date(&mut self, _date: &'a BodyDate) -> bool {
+        false
+    }
+}
+
+pub fn walk_element<'a, V: Visitor<'a> + ?Sized>(v: &mut V, element: &'a Element) -> bool {
+    match element {
+        Element::Text(text) => v.text(text),
+        Element::Label(label) => v.label(label),
+        Element::Number(number) => v.number(number),
+        Element::Group(group) => v.elements(&group.elements),
+        Element::Choose(choose) => v.choose(choose),
+        Element::Names(names) => v.names(names),
+        Element::Date(date) => v.date(date),
+    }
+}
+
+/// Goes into a macro, the only text with anything inside it.
+pub fn walk_text<'a, V: Visitor<'a> + ?Sized>(v: &mut V, text: &'a TextElement) -> bool {
+    match &text.source {
+        TextSource::Macro(name) => v.macro_(name),
+        _ => false,
+    }
+}
+
+/// An undefined macro is already an error elsewhere, and has nothing to walk.
+pub fn walk_macro<'a, V: Visitor<'a> + ?Sized>(v: &mut V, name: &'a str) -> bool {
+    match v.style().macros.get(name) {
+        Some(elements) => v.elements(elements),
+        None => false,
+    }
+}
+
+/// Every branch, in order, including the `<else>`.
+pub fn walk_choose<'a, V: Visitor<'a> + ?Sized>(v: &mut V, choose: &'a Choose) -> bool {
+    let Choose(iff, elseifs, elsee) = choose;
+    std::iter::once(iff)
+        .chain(elseifs)
+        .any(|branch| v.elements(&branch.1))
+        || v.elements(&elsee.0)
+}
+
+pub fn walk_names<'a, V: Visitor<'a> + ?Sized>(v: &mut V, names: &'a Names) -> bool {
+    match &names.substitute {
+        Some(Substitute(elements)) => v.elements(elements),
+        None => false,
+    }
+}
@@ -9,12 +9,14 @@ use crate::error::*;
 use crate::version::Features;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::str::FromStr;
 
 use super::attr::{EnumGetAttribute, GetAttribute};
 use super::variables::{NameVariable, NumberVariable};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum TextTermSelector {
     Simple(SimpleTermSelector),
     Gendered(GenderedTermSelector),
@@ -47,6 +49,9 @@ tts_eq!(MonthTerm, |x| Self::Gendered(GenderedTermSelector::Month(t, _)) => x ==
 tts_eq!(SeasonTerm, |x| Self::Gendered(GenderedTermSelector::Season(t, _)) => x == t);
 tts_eq!(RoleTerm, |x| Self::Role(RoleTermSelector(t, _)) => x == t);
 
+/// Any term a locale can define, by its `name` attribute alone.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum AnyTermName {
     Number(NumberVariable),
     Month(MonthTerm),
@@ -62,6 +67,27 @@ pub enum AnyTermName {
     Ordinal(OrdinalTerm),
 }
 
+/// Writes the term's `name` attribute, as it appears in a locale file.
+impl fmt::Display for AnyTermName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AnyTermName::Number(t) => f.write_str(t.as_ref()),
+            AnyTermName::Month(t) => f.write_str(t.as_ref()),
+            // hyphenated as a variable matcher, spaced as a term name
+            AnyTermName::Loc(LocatorType::SubVerbo) => f.write_str("sub verbo"),
+            AnyTermName::Loc(t) => f.write_str(t.as_ref()),
+            AnyTermName::Misc(t) => f.write_str(t.as_ref()),
+            AnyTermName::Category(t) => f.write_str(t.as_ref()),
+            AnyTermName::Season(t) => f.write_str(t.as_ref()),
+            AnyTermName::Quote(t) => f.write_str(t.as_ref()),
+            AnyTermName::Role(t) => f.write_str(t.as_ref()),
+            AnyTermName::Ordinal(OrdinalTerm::Ordinal) => f.write_str("ordinal"),
+            AnyTermName::Ordinal(OrdinalTerm::Mod100(n, _)) => write!(f, "ordinal-{:02}", n),
+            AnyTermName::Ordinal(long) => write!(f, "long-ordinal-{:02}", long.to_number()),
+        }
+    }
+}
+
 impl EnumGetAttribute for MonthTerm {}
 impl EnumGetAttribute for LocatorType {}
 impl EnumGetAttribute for MiscTerm {}
@@ -103,6 +129,7 @@ impl GetAttribute for AnyTermName {
 
 /// TermSelector is used
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum SimpleTermSelector {
     Misc(MiscTerm, TermFormExtended),
     Category(Category, TermForm),
@@ -129,6 +156,7 @@ impl SimpleTermSelector {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct OrdinalTermSelector(pub OrdinalTerm, pub Gender);
 
 struct OrdinalTermIter(Option<OrdinalTerm>);
@@ -179,6 +207,7 @@ impl OrdinalTermSelector {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum GenderedTermSelector {
     /// Edition is the only MiscTerm that can have a gender, so it's here instead
     Number(NumberVariable, TermForm),
@@ -226,10 +255,7 @@ impl GenderedTermSelector {
                 term_form,
             )
         } else {
-            GenderedTermSelector::Month(
-                MonthTerm::from_u32(month_or_season).expect("we know it's a month now"),
-                term_form,
-            )
+            GenderedTermSelector::Month(MonthTerm::from_u32(month_or_season)?, term_form)
         };
         Some(sel)
     }
@@ -267,6 +293,7 @@ impl GenderedTermSelector {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct RoleTermSelector(pub RoleTerm, pub TermFormExtended);
 
 impl RoleTermSelector {
@@ -280,9 +307,11 @@ impl RoleTermSelector {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct GenderedTerm(pub TermPlurality, pub Gender);
 
 #[derive(AsRefStr, EnumString, EnumProperty, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum TermForm {
     Long,
@@ -319,6 +348,7 @@ impl Iterator for TermFallbackIter {
 }
 /// Includes the extra Verb and VerbShort variants
 #[derive(AsRefStr, EnumString, EnumProperty, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum TermFormExtended {
     Long,
@@ -360,6 +390,7 @@ impl Iterator for TermFallbackExtendedIter {
 }
 
 #[derive(AsRefStr, EnumString, EnumProperty, Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum TermPlurality {
     Pluralized { single: String, multiple: String },
@@ -430,6 +461,7 @@ impl TermPlurality {
 ///    2. Would also look up OridnalMatch::LastTwoDigits Neuter
 ///
 #[derive(AsStaticStr, EnumString, EnumProperty, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum Gender {
     Masculine,
@@ -447,6 +479,7 @@ impl Default for Gender {
 /// [Spec](https://docs.citationstyles.org/en/stable/specification.html#ordinal-suffixes)
 /// LastTwoDigits is the default
 #[derive(AsStaticStr, EnumString, EnumProperty, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum OrdinalMatch {
     /// Default for `Mod100(n) if n < 10`. Matches 9, 29, 109, 129.
@@ -464,8 +497,8 @@ impl Default for OrdinalMatch {
 }
 
 /// [Spec](https://docs.citationstyles.org/en/stable/specification.html#locators)
-#[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
-#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[derive(AsRefStr, EnumProperty, EnumIter, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 #[strum(serialize_all = "kebab_case")]
 #[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
 #[non_exhaustive]
@@ -521,6 +554,7 @@ impl Default for LocatorType {
 
 /// [Spec](https://docs.citationstyles.org/en/stable/specification.html#quotes)
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 pub enum QuoteTerm {
     OpenQuote,
@@ -530,6 +564,7 @@ pub enum QuoteTerm {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 // Strum's auto kebab_case doesn't hyphenate to "season-01", so manual it is
 pub enum SeasonTerm {
     #[strum(serialize = "season-01")]
@@ -545,6 +580,7 @@ pub enum SeasonTerm {
 /// Yes, this differs slightly from NameVariable.
 /// It includes "editortranslator" for the names special case.
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 #[non_exhaustive]
 pub enum RoleTerm {
@@ -627,7 +663,7 @@ impl RoleTerm {
 /// here](https://docs.citationstyles.org/en/stable/specification.html#gender-specific-ordinals)
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
-#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 #[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
 #[non_exhaustive]
@@ -666,6 +702,7 @@ pub enum Category {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 #[non_exhaustive]
 pub enum MiscTerm {
@@ -713,6 +750,7 @@ pub enum MiscTerm {
 
 /// [Spec](https://docs.citationstyles.org/en/stable/specification.html#months)
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[non_exhaustive]
 pub enum MonthTerm {
     #[strum(serialize = "month-01")]
@@ -764,6 +802,7 @@ impl MonthTerm {
 
 /// [Spec](https://docs.citationstyles.org/en/stable/specification.html#quotes)
 #[derive(EnumProperty, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum OrdinalTerm {
     Ordinal,
     Mod100(u32, OrdinalMatch),
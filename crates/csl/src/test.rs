@@ -170,3 +170,244 @@ fn wrong_tag_name() {
     "#
     );
 }
+
+#[test]
+fn locale_to_xml_roundtrip() {
+    let en_us = Locale::parse(crate::locale::EN_US).unwrap();
+    let written = en_us.to_xml();
+    assert_eq!(Locale::parse(&written).unwrap(), en_us);
+
+    let mut merged = en_us.clone();
+    let custom = Locale::parse(::indoc::indoc!(
+        r#"
+        <locale xml:lang="en-AU">
+            <style-options punctuation-in-quote="false" leading-articles="the a an ye" />
+            <terms>
+                <term name="editor" form="verb">compiled &amp; edited by</term>
+                <term name="ordinal-11" gender-form="feminine" match="whole-number">th</term>
+                <term name="page" form="short">
+                    <single>p</single>
+                    <multiple>pp</multiple>
+                </term>
+            </terms>
+        </locale>
+    "#
+    ))
+    .unwrap();
+    merged.merge(&custom);
+    let written = merged.to_xml();
+    assert_eq!(Locale::parse(&written).unwrap(), merged);
+    assert_eq!(written, Locale::parse(&written).unwrap().to_xml());
+}
+
+#[test]
+fn locale_leading_articles() {
+    let en_us = Locale::parse(crate::locale::EN_US).unwrap();
+    assert_eq!(en_us.strip_leading_article("The Hobbit"), "Hobbit");
+    assert_eq!(
+        en_us.strip_leading_article("Ye Olde Shoppe"),
+        "Ye Olde Shoppe"
+    );
+
+    let custom = Locale::parse(::indoc::indoc!(
+        r#"
+        <locale xml:lang="en-US">
+            <style-options leading-articles="ye" />
+        </locale>
+    "#
+    ))
+    .unwrap();
+    assert_eq!(
+        custom.strip_leading_article("Ye Olde Shoppe"),
+        "Olde Shoppe"
+    );
+    assert_eq!(custom.strip_leading_article("The Hobbit"), "The Hobbit");
+
+    let cy = Locale::parse(r#"<locale xml:lang="cy"><style-options /></locale>"#).unwrap();
+    assert_eq!(cy.strip_leading_article("Y Ddraig Goch"), "Y Ddraig Goch");
+}
+
+#[test]
+fn macro_recursion() {
+    let style = |macros: &str| {
+        format!(
+            r#"<style class="in-text" version="1.0">
+            {}
+            <citation><layout><text macro="a" /></layout></citation>
+        </style>"#,
+            macros
+        )
+    };
+    let messages = |xml: &str, max_macro_depth: Option<usize>| -> Vec<String> {
+        let options = ParseOptions {
+            max_macro_depth,
+            ..Default::default()
+        };
+        match Style::parse_for_test(xml, Some(options)) {
+            Ok(_) => vec![],
+            Err(StyleError::Invalid(CslError(errs))) => {
+                errs.into_iter().map(|e| e.message).collect()
+            }
+            Err(e) => panic!("{}", e),
+        }
+    };
+    let cycle = style(
+        r#"<macro name="a"><text macro="b" /></macro>
+        <macro name="b"><group><text macro="c" /></group></macro>
+        <macro name="c">
+            <choose><if type="book"><text macro="a" /></if></choose>
+        </macro>
+        <macro name="d"><text macro="b" /></macro>"#,
+    );
+    // d only calls into the cycle, so is not reported
+    assert_eq!(
+        messages(&cycle, None),
+        vec![
+            "macro calls itself: a -> b -> c -> a",
+            "macro calls itself: b -> c -> a -> b",
+            "macro calls itself: c -> a -> b -> c",
+        ]
+    );
+    let itself = style(r#"<macro name="a"><text macro="a" /></macro>"#);
+    assert_eq!(messages(&itself, None), vec!["macro calls itself: a -> a"]);
+    let chain = style(
+        r#"<macro name="a"><text macro="b" /></macro>
+        <macro name="b"><text macro="c" /></macro>
+        <macro name="c"><text value="c" /></macro>"#,
+    );
+    assert!(messages(&chain, None).is_empty());
+    assert!(messages(&chain, Some(3)).is_empty());
+    assert_eq!(
+        messages(&chain, Some(2)),
+        vec!["macro `a` nests 3 macros deep, more than the limit of 2"]
+    );
+}
+
+#[test]
+fn required_terms() {
+    let style = Style::parse_for_test(
+        r#"<style class="in-text" version="1.0">
+        <macro name="nested">
+            <text term="ibid" />
+            <text macro="sorting" />
+        </macro>
+        <macro name="sorting">
+            <text term="no date" />
+        </macro>
+        <citation>
+            <sort><key macro="sorting" /></sort>
+            <layout>
+                <group>
+                    <label variable="locator" form="short" />
+                    <number variable="edition" form="long-ordinal" />
+                </group>
+                <choose>
+                    <if position="subsequent"><text macro="nested" /></if>
+                    <else>
+                        <names variable="editor translator">
+                            <et-al term="and others" />
+                            <label />
+                        </names>
+                    </else>
+                </choose>
+                <date variable="issued"><date-part name="year" /></date>
+            </layout>
+        </citation>
+    </style>"#,
+        None,
+    )
+    .unwrap();
+    let names: Vec<String> = style
+        .required_terms()
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    let has = |name: &str| names.iter().any(|n| n == name);
+    for name in &[
+        "open-quote",
+        "sub verbo",
+        "page",
+        "page-range-delimiter",
+        "long-ordinal-01",
+        "long-ordinal-10",
+        "ordinal",
+        "ibid",
+        "and others",
+        "editor",
+        "translator",
+        "editortranslator",
+        "ad",
+        "no date",
+    ] {
+        assert!(has(name), "missing {}", name);
+    }
+    for name in &["et-al", "month-01", "season-01", "edition"] {
+        assert!(!has(name), "unexpected {}", name);
+    }
+    assert_eq!(names.iter().filter(|n| *n == "ibid").count(), 1);
+}
+
+#[test]
+fn requirements_for() {
+    let style = Style::parse_for_test(
+        r#"<style class="in-text" version="1.0">
+        <macro name="author">
+            <names variable="author">
+                <substitute><names variable="editor" /></substitute>
+            </names>
+        </macro>
+        <citation>
+            <sort><key variable="issued" /></sort>
+            <layout>
+                <text macro="author" />
+                <choose>
+                    <if type="book" variable="publisher" match="all">
+                        <text variable="publisher" />
+                    </if>
+                    <else-if type="book chapter" match="any">
+                        <text variable="container-title" form="short" />
+                    </else-if>
+                    <else>
+                        <text variable="URL" />
+                    </else>
+                </choose>
+                <text variable="locator" />
+                <number variable="page-first" />
+            </layout>
+        </citation>
+        <bibliography>
+            <layout>
+                <choose>
+                    <if context="citation"><text variable="note" /></if>
+                    <else><text variable="title" form="short" /></else>
+                </choose>
+            </layout>
+        </bibliography>
+    </style>"#,
+        None,
+    )
+    .unwrap();
+    let book = vec![
+        AnyVariable::Name(NameVariable::Author),
+        AnyVariable::Name(NameVariable::Editor),
+        AnyVariable::Ordinary(Variable::Publisher),
+        AnyVariable::Ordinary(Variable::ContainerTitleShort),
+        AnyVariable::Ordinary(Variable::JournalAbbreviation),
+        AnyVariable::Ordinary(Variable::ContainerTitle),
+        AnyVariable::Number(NumberVariable::Page),
+        AnyVariable::Date(DateVariable::Issued),
+        AnyVariable::Ordinary(Variable::TitleShort),
+        AnyVariable::Ordinary(Variable::Title),
+    ];
+    assert_eq!(style.requirements_for(CslType::Book), book);
+    let webpage = vec![
+        AnyVariable::Name(NameVariable::Author),
+        AnyVariable::Name(NameVariable::Editor),
+        AnyVariable::Ordinary(Variable::URL),
+        AnyVariable::Number(NumberVariable::Page),
+        AnyVariable::Date(DateVariable::Issued),
+        AnyVariable::Ordinary(Variable::TitleShort),
+        AnyVariable::Ordinary(Variable::Title),
+    ];
+    assert_eq!(style.requirements_for(CslType::Webpage), webpage);
+}
@@ -4,15 +4,18 @@
 //
 // Copyright © 2018 Corporation for Digital Scholarship
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
 use super::attr::{EnumGetAttribute, GetAttribute};
 use super::error::*;
 use super::version::Features;
 use super::IsIndependent;
-use super::Style;
+use super::{Style, VariableForm};
 
 #[derive(Debug, Eq, Copy, Clone, PartialEq, EnumProperty, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum AnyVariable {
     Ordinary(Variable),
     Name(NameVariable),
@@ -64,6 +67,7 @@ impl GetAttribute for AnyVariable {
 /// [Spec](https://docs.citationstyles.org/en/stable/specification.html#number-variables)
 
 #[derive(Debug, Eq, Copy, Clone, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum StandardVariable {
     Ordinary(Variable),
     Number(NumberVariable),
@@ -121,6 +125,7 @@ impl IsIndependent for Variable {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 #[non_exhaustive]
 pub enum Variable {
@@ -243,8 +248,7 @@ pub enum Variable {
     /// CSL-M only
     #[strum(props(csl = "0", cslM = "1"))]
     LocatorExtra,
-    /// CSL-M only
-    #[strum(props(csl = "0", cslM = "1"))]
+    /// title of the volume, in CSL-M and (since 1.0.2) CSL
     VolumeTitle,
 
     /// CSL-M only
@@ -253,6 +257,13 @@ pub enum Variable {
     #[strum(props(csl = "0", cslM = "1"))]
     Committee,
 
+    /// CSL-M only
+    ///
+    /// The rank of the court in `authority`, e.g. "supreme" or "appellate". If a `legal_case`
+    /// reference doesn't have one, it is looked up from the court classes given to the processor.
+    #[strum(props(csl = "0", cslM = "1", cslFeature = "cslm_legal_variables"))]
+    CourtClass,
+
     /// CSL-M only
     ///
     /// Not documented in the CSL-M spec. See [Indigo Book][ib] section 'R26. Short Form
@@ -280,6 +291,26 @@ pub enum Variable {
 }
 
 impl Variable {
+    /// The fields `<text variable="..." form="...">` tries in order, if it does not just read
+    /// this one. The short forms of `title` and `container-title` fall back to the long ones, and
+    /// `container-title-short` to `journalAbbreviation`.
+    pub fn form_fallbacks(self, form: VariableForm) -> Option<&'static [Variable]> {
+        match (self, form) {
+            (Variable::Title, VariableForm::Short) => {
+                Some(&[Variable::TitleShort, Variable::Title])
+            }
+            (Variable::ContainerTitleShort, _) => {
+                Some(&[Variable::ContainerTitleShort, Variable::JournalAbbreviation])
+            }
+            (Variable::ContainerTitle, VariableForm::Short) => Some(&[
+                Variable::ContainerTitleShort,
+                Variable::JournalAbbreviation,
+                Variable::ContainerTitle,
+            ]),
+            _ => None,
+        }
+    }
+
     pub fn should_replace_hyphens(self) -> bool {
         false
     }
@@ -305,6 +336,7 @@ impl IsIndependent for NumberVariable {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 #[non_exhaustive]
 pub enum NumberVariable {
@@ -312,6 +344,7 @@ pub enum NumberVariable {
     CollectionNumber,
     Edition,
     Issue,
+    /// Also the docket number of a legal case, which some CSL-JSON calls `docket-number`.
     Number,
     NumberOfPages,
     NumberOfVolumes,
@@ -370,6 +403,7 @@ impl NumberVariable {
 #[derive(
     AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd,
 )]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 #[non_exhaustive]
 pub enum NameVariable {
@@ -407,8 +441,10 @@ pub enum NameVariable {
     )]
     EditorTranslator,
 
-    /// CSL-M only
-    #[strum(props(feature = "cslm_legal"))]
+    /// issuing or judicial authority as an institutional name, e.g. a court
+    ///
+    /// feature = cslm_legal_variables
+    #[strum(props(feature = "cslm_legal_variables"))]
     Authority,
 
     /// The dummy name variable is always empty. Use it to force all name variables called through
@@ -441,6 +477,7 @@ impl Default for NameVariable {
 }
 
 #[derive(AsRefStr, EnumProperty, EnumString, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[strum(serialize_all = "kebab_case")]
 #[non_exhaustive]
 pub enum DateVariable {
@@ -38,7 +38,19 @@ impl serde::Serialize for CslVersionReq {
     }
 }
 
-#[allow(dead_code)]
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for CslVersionReq {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = <String as serde::Deserialize>::deserialize(deserializer)?;
+        VersionReq::parse(&s)
+            .map(CslVersionReq)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct CslCslMVersionReq(pub CslVariant, pub VersionReq);
 
@@ -77,6 +89,61 @@ impl CslVariant {
     }
 }
 
+/// Which dialect of CSL a style is parsed as, and so which extensions the processor applies.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum StyleMode {
+    /// The default, and how styles were always parsed: CSL-M-only variables and attribute values
+    /// are accepted in any style, without turning on the rest of CSL-M. CSL-M versions like
+    /// `1.1mlz1` are not.
+    Lenient,
+    /// Strict CSL. CSL-M styles are rejected, as are CSL-M-only variables and attribute values,
+    /// unless a declared feature covers them.
+    Csl,
+    /// CSL-M. Accepts `version="1.1mlz1"` and `variant="csl-m"` styles, and turns on all the
+    /// CSL-M features (legal types and variables, `<conditions>`, etc).
+    CslM,
+}
+
+impl Default for StyleMode {
+    fn default() -> Self {
+        StyleMode::Lenient
+    }
+}
+
+impl StyleMode {
+    pub(crate) fn apply(self, features: &mut Features) {
+        features.mode = Some(self);
+        if self == StyleMode::CslM {
+            features.conditions = true;
+            features.condition_date_parts = true;
+            features.cslm_legal_types = true;
+            features.cslm_legal_variables = true;
+            features.legal_locators = true;
+            features.var_locator_date = true;
+        }
+    }
+
+    /// Checks the `csl` / `cslM` props on an attribute value. In CSL mode, a CSL-M-only value can
+    /// still be enabled by the feature named in its `cslFeature` prop.
+    fn accepts<T: EnumProperty>(self, val: &T, features: &Features) -> bool {
+        match self {
+            StyleMode::Lenient => true,
+            StyleMode::Csl => match val.get_str("csl") {
+                Some("0") => val
+                    .get_str("cslFeature")
+                    .map_or(false, |feat| features.str_enabled(feat)),
+                _ => true,
+            },
+            StyleMode::CslM => val.get_str("cslM") != Some("0"),
+        }
+    }
+}
+
 // These macros `set` and `declare_features` are from Rustc's `src/syntax/feature_gate.rs`.
 // Copyright 2013-2019 The Rust Project Developers.
 // Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
@@ -109,13 +176,17 @@ macro_rules! declare_features {
         }
 
         /// A set of features declared / enabled by a style.
-        #[derive(Clone, Eq, PartialEq, Default)]
+        #[derive(Clone, Eq, PartialEq, Hash, Default)]
         #[cfg_attr(feature = "serde", derive(serde::Serialize))]
         #[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
         pub struct Features {
             /// `(name, Option<since_version>)`: already accepted features that have nevertheless been declared by a style
             #[cfg_attr(feature = "serde", serde(skip_serializing))]
             pub declared_lang_features: Vec<(Atom, Option<Atom>)>,
+            /// The mode a style was parsed in. `None` elsewhere, e.g. reading CSL-JSON, where
+            /// CSL-M-only values are not checked.
+            #[cfg_attr(feature = "serde", serde(skip_serializing))]
+            pub mode: Option<StyleMode>,
             $(
                 $(#[$feat_meta])*
                 #[cfg_attr(feature = "serde", serde(skip_serializing_if = "is_false"))]
@@ -127,6 +198,7 @@ macro_rules! declare_features {
             pub fn new() -> Features {
                 Features {
                     declared_lang_features: Vec::new(),
+                    mode: None,
                     $($feature: false),+
                 }
             }
@@ -145,7 +217,10 @@ macro_rules! declare_features {
                         }
                     }
                 }
-                Some(val)
+                match self.mode {
+                    Some(mode) if !mode.accepts(&val, self) => None,
+                    _ => Some(val),
+                }
             }
 
             pub(crate) fn str_enabled(&self, fstr: &str) -> bool {
@@ -249,6 +324,9 @@ declare_features!(
     (active, edtf_dates, "1.1", None, None),
     /// includes types: gazette, hearing, regulation
     (active, cslm_legal_types, "1.0.1", None, None),
+    /// `<names variable="authority">`, the `court-class` variable, and CSL-M handling of
+    /// `legal_case` items, which never render a publisher
+    (active, cslm_legal_variables, "1.0.1", None, None),
     /// `locator-date` date variable
     (active, var_locator_date, "1.0.1", None, None),
     /// `<names variable="dummy">`
@@ -266,6 +344,9 @@ declare_features!(
     (active, legal_locators, "1.0.1", None, None),
     /// `<text term="unpublished">`
     (active, term_unpublished, "1.0.1", None, None),
+    /// In note styles, cites that are still ambiguous with other works by the same author after
+    /// adding names get the short title appended, like citeproc-js's `disambiguate_add_titles`.
+    (active, disambiguate_add_titles, "1.1", None, None),
 );
 
 // status, name, first added version, tracking issue, edition, None
@@ -407,3 +488,53 @@ impl<'de> serde::Deserialize<'de> for Features {
         deserializer.deserialize_seq(FeatureVisitor)
     }
 }
+
+/// [Features] as a serialized [Style](crate::Style) stores them. The usual form is a list of the
+/// enabled features for showing to a user; this one also keeps what only the parser sets.
+#[cfg(feature = "serde")]
+pub(crate) mod features_lossless {
+    use super::{Features, StyleMode};
+    use crate::Atom;
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct Lossless {
+        enabled: Vec<String>,
+        declared: Vec<(Atom, Option<Atom>)>,
+        mode: Option<StyleMode>,
+    }
+
+    pub fn serialize<S>(features: &Features, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut enabled = Vec::new();
+        features.walk_feature_fields(|name, on| {
+            if on {
+                enabled.push(name.to_owned());
+            }
+        });
+        Lossless {
+            enabled,
+            declared: features.declared_lang_features.clone(),
+            mode: features.mode,
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Features, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let lossless = Lossless::deserialize(deserializer)?;
+        let mut features = Features::new();
+        for name in &lossless.enabled {
+            features
+                .try_set_feature(name)
+                .map_err(|name| D::Error::custom(format_args!("unknown feature {}", name)))?;
+        }
+        features.declared_lang_features = lossless.declared;
+        features.mode = lossless.mode;
+        Ok(features)
+    }
+}
@@ -10,10 +10,10 @@ use super::xml::{LocaleDatabase, StyleDatabase};
 use std::sync::Arc;
 
 use citeproc_io::output::markup::Markup;
-use citeproc_io::{Cite, ClusterMode, Reference};
-use csl::Atom;
+use citeproc_io::{Cite, ClusterMode, Reference, SmartString};
+use csl::{Atom, CslType, Lang, Locale, Variable};
 
-use fnv::FnvHashSet;
+use fnv::{FnvHashMap, FnvHashSet};
 
 use indexmap::set::IndexSet;
 
@@ -23,6 +23,10 @@ pub trait CiteDatabase: LocaleDatabase + StyleDatabase {
     fn reference_input(&self, key: Atom) -> Arc<Reference>;
     fn reference(&self, key: Atom) -> Option<Arc<Reference>>;
 
+    /// Maps the name of a court, as in the `authority` variable, to its `court-class`.
+    #[salsa::input]
+    fn court_classes(&self) -> Arc<FnvHashMap<SmartString, SmartString>>;
+
     #[salsa::input]
     fn all_keys(&self) -> Arc<IndexSet<Atom>>;
 
@@ -45,6 +49,20 @@ pub trait CiteDatabase: LocaleDatabase + StyleDatabase {
     #[salsa::input]
     fn cluster_cites(&self, key: ClusterId) -> Arc<Vec<CiteId>>;
 
+    /// Languages set on individual clusters, e.g. a footnote quoting a German source in an
+    /// English document.
+    #[salsa::input]
+    fn cluster_langs(&self) -> Arc<FnvHashMap<ClusterId, Lang>>;
+
+    /// The locale a cluster takes its terms from: the merged locale for its own language if it
+    /// has one, otherwise the default locale.
+    fn cluster_locale(&self, key: ClusterId) -> Arc<Locale>;
+
+    /// Every language rendering this document could ask the fetcher for, sorted. That is the
+    /// default locale, the style's inline `<locale>` languages, the `language` of each reference
+    /// and each cluster's `lang`, along with their fallbacks.
+    fn required_locales(&self) -> Arc<Vec<Lang>>;
+
     #[salsa::interned]
     fn cite(&self, data: CiteData) -> CiteId;
 
@@ -59,10 +77,21 @@ pub trait CiteDatabase: LocaleDatabase + StyleDatabase {
     /// Also represents "the refs that will be in the bibliography if we generate one"
     fn disamb_participants(&self) -> Arc<IndexSet<Atom>>;
 
+    /// Keys in the library that are neither cited nor marked uncited, in library order.
+    fn unused_references(&self) -> Arc<Vec<Atom>>;
+
     // All cite ids, in the order they are cited in the document
     fn all_cite_ids(&self) -> Arc<Vec<CiteId>>;
 
     fn clusters_sorted(&self) -> Arc<Vec<ClusterData>>;
+
+    /// Every cluster with a cite of `ref_id`. Clusters in the document come first, in document
+    /// order, followed by any that have no note number.
+    fn clusters_citing(&self, ref_id: Atom) -> Arc<Vec<ClusterId>>;
+
+    /// The notes or in-text clusters citing `ref_id`, in document order. A note is only listed
+    /// once however many times it cites the reference. Author-only clusters are left out.
+    fn back_references(&self, ref_id: Atom) -> Arc<Vec<BackReference>>;
 }
 
 #[macro_export]
@@ -106,6 +135,36 @@ pub enum CiteData {
     BibliographyGhost { cite: Arc<Cite<Markup>> },
 }
 
+fn cluster_locale(db: &dyn CiteDatabase, key: ClusterId) -> Arc<Locale> {
+    match db.cluster_langs().get(&key) {
+        Some(lang) => db.merged_locale(lang.clone()),
+        None => db.default_locale(),
+    }
+}
+
+fn required_locales(db: &dyn CiteDatabase) -> Arc<Vec<Lang>> {
+    let style = db.style();
+    let mut langs: Vec<Lang> = db.default_lang().iter_fetchable_langs().collect();
+    langs.extend(
+        style
+            .locale_overrides
+            .keys()
+            .flatten()
+            .flat_map(|lang| lang.iter_fetchable_langs()),
+    );
+    for id in db.all_keys().iter() {
+        if let Some(lang) = db.reference(id.clone()).and_then(|r| r.language.clone()) {
+            langs.extend(lang.iter_fetchable_langs());
+        }
+    }
+    for lang in db.cluster_langs().values() {
+        langs.extend(lang.iter_fetchable_langs());
+    }
+    langs.sort();
+    langs.dedup();
+    Arc::new(langs)
+}
+
 fn ghost_cite(_db: &dyn CiteDatabase, ref_id: Atom) -> Arc<Cite<Markup>> {
     Arc::new(Cite::basic(ref_id))
 }
@@ -119,12 +178,37 @@ pub struct ClusterData {
 
 fn reference(db: &dyn CiteDatabase, key: Atom) -> Option<Arc<Reference>> {
     if db.all_keys().contains(&key) {
-        Some(db.reference_input(key))
+        Some(legal_case(db, db.reference_input(key)))
     } else {
         None
     }
 }
 
+/// CSL-M semantics for case law, for styles with the `cslm-legal-variables` feature. A court is
+/// never a publisher, so `publisher` is dropped, and `court-class` is filled in from the court
+/// classes if the reference doesn't have one.
+fn legal_case(db: &dyn CiteDatabase, refr: Arc<Reference>) -> Arc<Reference> {
+    if refr.csl_type != CslType::LegalCase || !db.style().features.cslm_legal_variables {
+        return refr;
+    }
+    let court_class = match refr.ordinary.get(&Variable::Authority) {
+        Some(court) if !refr.ordinary.contains_key(&Variable::CourtClass) => {
+            db.court_classes().get(court.as_str()).cloned()
+        }
+        _ => None,
+    };
+    if court_class.is_none() && !refr.ordinary.contains_key(&Variable::Publisher) {
+        return refr;
+    }
+    let mut refr = (*refr).clone();
+    refr.ordinary.remove(&Variable::Publisher);
+    if let Some(class) = court_class {
+        refr.ordinary
+            .insert(Variable::CourtClass, class.to_string());
+    }
+    Arc::new(refr)
+}
+
 /// Type to represent which references should appear in a bibiliography even if they are not cited
 /// in the document. The default is that references only appear if they are cited.
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -173,6 +257,17 @@ fn disamb_participants(db: &dyn CiteDatabase) -> Arc<IndexSet<Atom>> {
     }
 }
 
+fn unused_references(db: &dyn CiteDatabase) -> Arc<Vec<Atom>> {
+    let participants = db.disamb_participants();
+    let all = db.all_keys();
+    Arc::new(
+        all.iter()
+            .filter(|key| !participants.contains(*key))
+            .cloned()
+            .collect(),
+    )
+}
+
 fn all_cite_ids(db: &dyn CiteDatabase) -> Arc<Vec<CiteId>> {
     let clusters = db.clusters_sorted();
     let mut ids = Vec::with_capacity(clusters.len());
@@ -194,6 +289,45 @@ fn clusters_sorted(db: &dyn CiteDatabase) -> Arc<Vec<ClusterData>> {
     Arc::new(clusters)
 }
 
+fn clusters_citing(db: &dyn CiteDatabase, ref_id: Atom) -> Arc<Vec<ClusterId>> {
+    let cites_ref = |cites: &[CiteId]| cites.iter().any(|id| id.lookup(db).ref_id == ref_id);
+    let mut ids: Vec<ClusterId> = db
+        .clusters_sorted()
+        .iter()
+        .filter(|cluster| cites_ref(&cluster.cites))
+        .map(|cluster| cluster.id)
+        .collect();
+    let mut unpositioned: Vec<ClusterId> = db
+        .all_cluster_ids()
+        .iter()
+        .cloned()
+        .filter(|&id| db.cluster_note_number(id).is_none() && cites_ref(&db.cluster_cites(id)))
+        .collect();
+    unpositioned.sort_by_key(|id| id.0);
+    ids.extend(unpositioned);
+    Arc::new(ids)
+}
+
+fn back_references(db: &dyn CiteDatabase, ref_id: Atom) -> Arc<Vec<BackReference>> {
+    let mut backrefs: Vec<BackReference> = db
+        .clusters_sorted()
+        .iter()
+        .filter(|cluster| {
+            cluster
+                .cites
+                .iter()
+                .any(|id| id.lookup(db).ref_id == ref_id)
+        })
+        .filter_map(|cluster| match cluster.number {
+            ClusterNumber::Note(note) => Some(BackReference::Note(note.note_number())),
+            ClusterNumber::InText(n) => Some(BackReference::InText(n)),
+            ClusterNumber::OutsideFlow => None,
+        })
+        .collect();
+    backrefs.dedup();
+    Arc::new(backrefs)
+}
+
 pub fn get_cluster_data(db: &dyn CiteDatabase, id: ClusterId) -> Option<ClusterData> {
     db.cluster_note_number(id).map(|mut number| {
         if let Some(ClusterMode::AuthorOnly) = db.cluster_mode(id) {
@@ -4,7 +4,7 @@
 //
 // Copyright © 2020 Corporation for Digital Scholarship
 
-use serde_derive::Deserialize;
+use serde_derive::{Deserialize, Serialize};
 use string_interner::symbol::Symbol;
 
 /// A symbol that identifies a cluster; a newtyped u32. This corresponds to an interned string
@@ -33,7 +33,11 @@ impl Symbol for ClusterId {
     }
 }
 
-#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Ord)]
+/// Where a note cluster is: the footnote number, and for footnotes with more than one cluster in
+/// them, the cluster's index within the footnote.
+///
+/// Serialized as either `3` or `[3, 1]`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 #[serde(untagged)]
 pub enum IntraNote {
     Single(u32),
@@ -46,27 +50,32 @@ impl IntraNote {
             IntraNote::Single(x) | IntraNote::Multi(x, _) => *x,
         }
     }
+    /// The position of the cluster within its footnote, starting at zero.
+    pub fn index_in_note(&self) -> u32 {
+        match self {
+            IntraNote::Single(_) => 0,
+            IntraNote::Multi(_, i) => *i,
+        }
+    }
+}
+
+impl Ord for IntraNote {
+    /// Orders by note number, then by index within the note. A `Single` only sorts before a
+    /// `Multi` when they share a note number and index.
+    fn cmp(&self, other: &IntraNote) -> Ordering {
+        use IntraNote::*;
+        let key = |x: &IntraNote| (x.note_number(), x.index_in_note(), matches!(x, Multi(..)));
+        key(self).cmp(&key(other))
+    }
 }
 
 impl PartialOrd for IntraNote {
     fn partial_cmp(&self, other: &IntraNote) -> Option<Ordering> {
-        use IntraNote::*;
-        match (self, other) {
-            (Single(_), Multi(..)) => Some(Ordering::Less),
-            (Multi(..), Single(_)) => Some(Ordering::Greater),
-            (Single(a), Single(b)) => a.partial_cmp(b),
-            (Multi(a, b), Multi(c, d)) => a.partial_cmp(c).and_then(|e| {
-                if e == Ordering::Equal {
-                    b.partial_cmp(d)
-                } else {
-                    Some(e)
-                }
-            }),
-        }
+        Some(self.cmp(other))
     }
 }
 
-#[derive(Deserialize, Ord, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Ord, Eq, PartialEq)]
 #[serde(rename_all = "camelCase")]
 #[derive(Clone, Copy, Debug)]
 pub enum ClusterNumber {
@@ -91,6 +100,19 @@ impl ClusterNumber {
     }
 }
 
+/// One place in the document that cites a reference, for the "cited on" lists some styles put
+/// under bibliography entries.
+///
+/// Serialized as `{ "note": 3 }` or `{ "inText": 2 }`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum BackReference {
+    /// A footnote number.
+    Note(u32),
+    /// The position of an in-text cluster among the in-text clusters, starting at 1.
+    InText(u32),
+}
+
 use std::cmp::Ordering;
 impl PartialOrd for ClusterNumber {
     fn partial_cmp(&self, other: &ClusterNumber) -> Option<Ordering> {
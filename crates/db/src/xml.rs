@@ -11,9 +11,10 @@ use citeproc_io::output::markup::Markup;
 use csl::{
     locale::{Lang, Locale, LocaleSource, EN_US},
     style::{Name, Style, TextElement, TextSource},
+    terms::AnyTermName,
     SmartString,
 };
-use fnv::FnvHashSet;
+use fnv::{FnvHashMap, FnvHashSet};
 
 pub trait HasFetcher {
     fn get_fetcher(&self) -> Arc<dyn LocaleFetcher>;
@@ -25,6 +26,11 @@ pub trait StyleDatabase {
     #[salsa::input]
     fn style(&self) -> Arc<Style>;
 
+    /// CSL-M jurisdiction modules, keyed by jurisdiction like `us:ca`. Their macros replace the
+    /// style's own when rendering references in that jurisdiction or below it.
+    #[salsa::input]
+    fn jurisdiction_modules(&self) -> Arc<FnvHashMap<SmartString, Arc<Style>>>;
+
     #[salsa::input]
     fn formatter(&self) -> Markup;
 
@@ -37,6 +43,9 @@ pub trait StyleDatabase {
 
     /// Lists every <names> block in the style, with each name variable it is used for
     fn name_configurations(&self) -> Arc<Vec<(NameVariable, Name)>>;
+
+    /// Every locale term the style could look up. See [Style::required_terms].
+    fn required_terms(&self) -> Arc<Vec<AnyTermName>>;
 }
 
 fn name_info_citation(db: &dyn StyleDatabase) -> (Option<SmartString>, Arc<Name>) {
@@ -49,6 +58,11 @@ fn name_info_bibliography(db: &dyn StyleDatabase) -> (Option<SmartString>, Arc<N
     style.name_info_bibliography()
 }
 
+fn required_terms(db: &dyn StyleDatabase) -> Arc<Vec<AnyTermName>> {
+    let style = db.style();
+    Arc::new(style.required_terms())
+}
+
 use csl::Element;
 use csl::NameVariable;
 
@@ -315,6 +329,24 @@ impl From<io::Error> for LocaleFetchError {
     }
 }
 
+impl std::fmt::Display for LocaleFetchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LocaleFetchError::Io(e) => write!(f, "could not read locale: {}", e),
+            LocaleFetchError::Other(e) => write!(f, "could not fetch locale: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for LocaleFetchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LocaleFetchError::Io(e) => Some(e),
+            LocaleFetchError::Other(_) => None,
+        }
+    }
+}
+
 use std::collections::HashMap;
 
 pub struct PredefinedLocales(pub HashMap<Lang, String>);
@@ -5,11 +5,13 @@
 // Copyright © 2018 Corporation for Digital Scholarship
 
 use super::output::{markup::Markup, OutputFormat};
+use crate::names::Name;
 use crate::NumberLike;
 use crate::String;
 use csl::Atom;
 use csl::LocatorType;
 use serde::de::{Deserialize, Deserializer};
+use serde::Serialize;
 
 /// Represents one cite in someone's document, to exactly one reference.
 ///
@@ -41,25 +43,66 @@ use serde::de::{Deserialize, Deserializer};
 ///     basic_mode("smith", CiteMode::AuthorOnly),
 /// ])
 /// ```
-#[derive(Clone, Deserialize, PartialEq, Eq)]
-#[serde(rename_all = "camelCase", bound(deserialize = ""))]
+#[derive(Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(
+    rename_all = "camelCase",
+    bound(deserialize = "", serialize = "O::Input: Serialize")
+)]
 pub struct Cite<O: OutputFormat> {
     #[serde(rename = "id", deserialize_with = "get_ref_id")]
     pub ref_id: Atom,
 
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub prefix: Option<O::Input>,
 
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub suffix: Option<O::Input>,
 
-    /// Multiple locator functionality needs CSL support, so it is disabled via using
-    /// `Locators::single_locator` for now.
-    #[serde(default, flatten, deserialize_with = "Locators::single_locator")]
+    /// Either form of [Locators] is read back, so a cite survives a round trip through JSON.
+    /// Multiple locator functionality needs CSL support, so only the first one is rendered for
+    /// now.
+    #[serde(
+        default,
+        flatten,
+        deserialize_with = "Locators::get_locators",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub locators: Option<Locators>,
 
-    #[serde(default, flatten)]
+    #[serde(default, flatten, skip_serializing_if = "Option::is_none")]
     pub mode: Option<CiteMode>,
+
+    /// Authors to leave out of this cite only, e.g. to write "as Smith and colleagues note
+    /// (Jones et al. 2001)". Et al. is applied to the authors that remain. The bibliography entry
+    /// for the reference still lists every author.
+    #[serde(
+        default,
+        rename = "suppress-names",
+        alias = "suppressNames",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub suppress_names: Vec<NameSelector>,
+}
+
+/// Picks out one of a reference's authors, for [`Cite::suppress_names`].
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(untagged)]
+pub enum NameSelector {
+    /// The author's position in the list, starting at 0.
+    Index(u32),
+    /// The author's family name, or the whole of a literal name.
+    Family(String),
+}
+
+impl NameSelector {
+    /// Whether this picks out `name`, found at `index` in its list.
+    pub fn selects(&self, index: usize, name: &Name) -> bool {
+        match (self, name) {
+            (NameSelector::Index(i), _) => *i as usize == index,
+            (NameSelector::Family(family), Name::Person(pn)) => pn.family.as_ref() == Some(family),
+            (NameSelector::Family(family), Name::Literal { literal, .. }) => literal == family,
+        }
+    }
 }
 
 use std::fmt;
@@ -80,6 +123,9 @@ impl<O: OutputFormat> fmt::Debug for Cite<O> {
         if let Some(mode) = self.mode.as_ref() {
             write!(f, ", mode: {:?}", mode)?;
         }
+        if !self.suppress_names.is_empty() {
+            write!(f, ", suppress_names: {:?}", self.suppress_names)?;
+        }
         write!(f, ")")
     }
 }
@@ -128,6 +174,9 @@ pub struct CiteCompat {
 
     #[serde(default, flatten, deserialize_with = "CiteMode::compat")]
     pub mode: Option<CiteMode>,
+
+    #[serde(default, rename = "suppress-names", alias = "suppressNames")]
+    pub suppress_names: Vec<NameSelector>,
 }
 
 pub mod cite_compat_vec {
@@ -144,7 +193,7 @@ pub mod cite_compat_vec {
     }
 }
 
-#[derive(Clone, Eq, PartialEq, Hash, Debug, Deserialize)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Deserialize, Serialize)]
 pub struct Locator {
     pub locator: NumberLike,
     #[serde(default, rename = "label")]
@@ -172,7 +221,7 @@ where
 /// Accepts either
 /// `{ "locator": "54", "label": "page" }` or
 /// `{ "locators": [["chapter", "19"], ["page", "581"]] }`.
-#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
 #[serde(untagged)]
 pub enum Locators {
     Single(Locator),
@@ -186,8 +235,6 @@ impl Locators {
             Locators::Multiple { locators } => locators.get(0),
         }
     }
-    // Used by get_locators
-    #[allow(dead_code)]
     fn into_option(self) -> Option<Self> {
         match self {
             Locators::Multiple { locators } => {
@@ -216,9 +263,6 @@ impl Locators {
 
     /// Single length locators arrays => Some(Locators::Single)
     /// Zero length => None
-    ///
-    /// Not yet used, pending CSL 1.1 locator array
-    #[allow(dead_code)]
     fn get_locators<'de, D>(d: D) -> Result<Option<Locators>, D::Error>
     where
         D: Deserializer<'de>,
@@ -227,7 +271,7 @@ impl Locators {
     }
 }
 
-#[derive(Clone, Eq, PartialEq, Hash, Debug, Deserialize)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Deserialize, Serialize)]
 #[serde(tag = "mode")]
 pub enum CiteMode {
     AuthorOnly,
@@ -311,6 +355,7 @@ impl<O: OutputFormat> Hash for Cite<O> {
         self.prefix.hash(h);
         self.suffix.hash(h);
         self.locators.hash(h);
+        self.suppress_names.hash(h);
     }
 }
 
@@ -322,6 +367,7 @@ impl<O: OutputFormat> Cite<O> {
             suffix: Default::default(),
             locators: None,
             mode: None,
+            suppress_names: Vec::new(),
         }
     }
     pub fn has_affix(&self) -> bool {
@@ -13,6 +13,7 @@ mod cow_str;
 use crate::names::Name;
 use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
 use serde::de::{Error, IgnoredAny};
+use serde::ser::{Serialize, SerializeMap, Serializer};
 use std::borrow::Cow;
 use std::fmt;
 use std::str::FromStr;
@@ -27,6 +28,7 @@ use csl::CslType;
 use csl::Features;
 use csl::GetAttribute;
 use csl::Lang;
+use csl::{NameVariable, Variable};
 
 use super::date::{Date, DateOrRange};
 use super::reference::Reference;
@@ -95,12 +97,23 @@ enum Field<'a> {
     Id,
     Type,
     Language,
+    SortKeys,
+    TitleSort,
     // don't use plain `&'a str`, because that would fail when parsing from a serde::Value.
     #[serde(borrow, deserialize_with = "cow_str::deserialize_cow_str")]
     Any(Cow<'a, str>),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Hash)]
+/// CSL-M treats `authority` (e.g. a court) as an institutional name, but CSL-JSON more often has
+/// it as a plain string. We accept either, and keep it as both an ordinary and a name variable.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum StringOrNames {
+    Str(std::string::String),
+    Names(Vec<Name>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Hash)]
 #[serde(untagged)]
 pub enum NumberLike {
     Str(String),
@@ -211,6 +224,7 @@ impl<'de> Deserialize<'de> for Reference {
                 let mut number = FnvHashMap::default();
                 let mut name = FnvHashMap::default();
                 let mut date = FnvHashMap::default();
+                let mut sort_keys = FnvHashMap::default();
                 while let Some(key) = map.next_key()? {
                     match key {
                         Field::Id => {
@@ -223,13 +237,55 @@ impl<'de> Deserialize<'de> for Reference {
                             let wrap: WrapLang = map.next_value()?;
                             language = wrap.0;
                         }
+                        Field::SortKeys => {
+                            let keys: FnvHashMap<std::string::String, std::string::String> =
+                                map.next_value()?;
+                            for (var_name, value) in keys {
+                                // Names, dates and numbers sort by their own structure, so only
+                                // the ordinary variables can be given a sort string.
+                                match Variable::get_attr(&var_name, &Features::default()) {
+                                    Ok(var) => {
+                                        sort_keys.entry(var).or_insert(value);
+                                    }
+                                    Err(_) => log::warn!(
+                                        "sort-keys only applies to ordinary variables, not `{}`",
+                                        var_name
+                                    ),
+                                }
+                            }
+                        }
+                        Field::TitleSort => {
+                            let title_sort = map.next_value()?;
+                            sort_keys.insert(Variable::Title, title_sort);
+                        }
                         Field::Any(var_name) => {
-                            match AnyVariable::get_attr(&var_name, &Features::default()) {
+                            // Some CSL-JSON has the docket number of a legal case as
+                            // `docket-number`. It is written back out as `number`.
+                            let var_name = match &*var_name {
+                                "docket-number" => "number",
+                                other => other,
+                            };
+                            match AnyVariable::get_attr(var_name, &Features::default()) {
                                 Err(_unknown) => {
                                     // Unknown variable. Let it slide.
                                     log::warn!("reference had unknown variable `{}`", var_name);
                                     let _: IgnoredAny = map.next_value()?;
                                 }
+                                Ok(AnyVariable::Ordinary(Variable::Authority)) => {
+                                    let (string, names) = match map.next_value()? {
+                                        StringOrNames::Str(s) => {
+                                            let names = vec![Name::literal(s.as_str().into())];
+                                            (s, names)
+                                        }
+                                        StringOrNames::Names(names) => {
+                                            let plain: Vec<_> =
+                                                names.iter().map(Name::plain_text).collect();
+                                            (plain.join(", "), names)
+                                        }
+                                    };
+                                    ordinary.insert(Variable::Authority, string);
+                                    name.insert(NameVariable::Authority, names);
+                                }
                                 Ok(AnyVariable::Ordinary(v)) => {
                                     ordinary.insert(v, map.next_value()?);
                                 }
@@ -259,6 +315,7 @@ impl<'de> Deserialize<'de> for Reference {
                     number,
                     name,
                     date,
+                    sort_keys,
                 })
             }
         }
@@ -619,3 +676,150 @@ impl<'de> Deserialize<'de> for MaybeDate {
         deserializer.deserialize_struct("DateOrRange", DATE_TYPES, DateVisitor)
     }
 }
+
+/// Writes a reference back out as CSL-JSON. The output is canonical: `id` and `type` come first,
+/// then every other key in alphabetical order, and dates are always `date-parts` (or `literal`),
+/// with seasons as months 21-24.
+impl Serialize for Reference {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        enum Value<'a> {
+            Str(&'a str),
+            Owned(std::string::String),
+            Number(&'a NumberLike),
+            Names(&'a [Name]),
+            Date(&'a DateOrRange),
+            SortKeys(Vec<(&'a str, &'a str)>),
+        }
+
+        impl Serialize for Value<'_> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                match self {
+                    Value::Str(s) => s.serialize(serializer),
+                    Value::Owned(s) => s.serialize(serializer),
+                    Value::Number(n) => n.serialize(serializer),
+                    Value::Names(names) => serializer.collect_seq(names.iter().map(ExportName)),
+                    Value::Date(date) => serialize_date(date, serializer),
+                    Value::SortKeys(keys) => {
+                        let mut map = serializer.serialize_map(Some(keys.len()))?;
+                        for (k, v) in keys {
+                            map.serialize_entry(k, v)?;
+                        }
+                        map.end()
+                    }
+                }
+            }
+        }
+
+        let mut entries: Vec<(&str, Value)> = Vec::new();
+        if let Some(lang) = &self.language {
+            entries.push(("language", Value::Owned(lang.to_string())));
+        }
+        for (var, value) in &self.ordinary {
+            // This is also stored as a name variable, which loses less.
+            if *var == Variable::Authority && self.name.contains_key(&NameVariable::Authority) {
+                continue;
+            }
+            entries.push((var.as_ref(), Value::Str(value)));
+        }
+        for (var, value) in &self.number {
+            entries.push((var.as_ref(), Value::Number(value)));
+        }
+        for (var, names) in &self.name {
+            entries.push((var.as_ref(), Value::Names(names)));
+        }
+        for (var, date) in &self.date {
+            entries.push((var.as_ref(), Value::Date(date)));
+        }
+        if !self.sort_keys.is_empty() {
+            let mut keys: Vec<(&str, &str)> = self
+                .sort_keys
+                .iter()
+                .map(|(var, value)| (var.as_ref(), value.as_str()))
+                .collect();
+            keys.sort_unstable();
+            entries.push(("sort-keys", Value::SortKeys(keys)));
+        }
+        entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+
+        let mut map = serializer.serialize_map(Some(entries.len() + 2))?;
+        let csl_type: &str = self.csl_type.as_ref();
+        map.serialize_entry("id", &*self.id)?;
+        map.serialize_entry("type", csl_type)?;
+        for (key, value) in &entries {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+/// The derived `Serialize` for [Name] is not CSL-JSON; this writes only the fields that are set.
+struct ExportName<'a>(&'a Name);
+
+impl Serialize for ExportName<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        match self.0 {
+            Name::Literal { literal, .. } => map.serialize_entry("literal", literal)?,
+            Name::Person(p) => {
+                let fields = [
+                    ("family", &p.family),
+                    ("given", &p.given),
+                    ("non-dropping-particle", &p.non_dropping_particle),
+                    ("dropping-particle", &p.dropping_particle),
+                    ("suffix", &p.suffix),
+                ];
+                for (key, value) in fields.iter() {
+                    if let Some(value) = value {
+                        map.serialize_entry(key, value)?;
+                    }
+                }
+                if p.static_particles {
+                    map.serialize_entry("static-particles", &true)?;
+                }
+                if p.comma_suffix {
+                    map.serialize_entry("comma-suffix", &true)?;
+                }
+                if p.static_ordering {
+                    map.serialize_entry("static-ordering", &true)?;
+                }
+                if let Some(romanized) = &p.romanized {
+                    map.serialize_entry("romanized", romanized)?;
+                }
+            }
+        }
+        map.end()
+    }
+}
+
+fn serialize_date<S: Serializer>(date: &DateOrRange, serializer: S) -> Result<S::Ok, S::Error> {
+    fn parts(date: &Date) -> Vec<i32> {
+        let mut parts = vec![date.year];
+        if date.month != 0 {
+            // seasons are 13-16 internally, but CSL-JSON has them as 21-24
+            let month = if date.month > 12 {
+                date.month + 8
+            } else {
+                date.month
+            };
+            parts.push(month as i32);
+            if date.day != 0 {
+                parts.push(date.day as i32);
+            }
+        }
+        parts
+    }
+    let circa = date.is_uncertain_date();
+    let mut map = serializer.serialize_map(Some(if circa { 2 } else { 1 }))?;
+    if circa {
+        map.serialize_entry("circa", &true)?;
+    }
+    match date {
+        DateOrRange::Single(d) => map.serialize_entry("date-parts", &[parts(d)])?,
+        DateOrRange::Range(d1, d2) => map.serialize_entry("date-parts", &[parts(d1), parts(d2)])?,
+        DateOrRange::Literal { literal, .. } => map.serialize_entry("literal", literal)?,
+    }
+    map.end()
+}
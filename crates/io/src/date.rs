@@ -32,27 +32,48 @@ impl PartialOrd for Date {
         Some(self.cmp(other))
     }
 }
+
+/// The order the spec gives for sorting: by year, then month, then day, with less specific dates
+/// first (2000 < May 2000 < 1 May 2000). Seasons are ignored, as they are in different months in
+/// each hemisphere, except to put e.g. Spring 2000 after 2000 when that is all that differs.
+/// Circa dates come after the same date without it.
 impl Ord for Date {
     fn cmp(&self, other: &Self) -> Ordering {
-        let mut cmp = self.year.cmp(&other.year);
-        if self.month < 13 && other.month < 13 {
-            // less specific comes first, so zeroes (absent) can just be compared directly
-            cmp = cmp.then(self.month.cmp(&other.month))
-        }
-        cmp.then(self.day.cmp(&other.day))
+        self.year
+            .cmp(&other.year)
+            .then(self.month_or_zero().cmp(&other.month_or_zero()))
+            .then(self.day.cmp(&other.day))
+            .then(self.season_or_zero().cmp(&other.season_or_zero()))
+            .then(self.circa.cmp(&other.circa))
     }
 }
 
 impl PartialOrd for DateOrRange {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Dates and ranges are ordered by their start, then single dates before ranges (2000 <
+/// 2000–2002), then by the end of the range. Literal dates can't be compared with the others, so
+/// they all come last, in alphabetical order.
+impl Ord for DateOrRange {
+    fn cmp(&self, other: &Self) -> Ordering {
+        use DateOrRange::*;
         match (self, other) {
-            (DateOrRange::Literal { .. }, _) | (_, DateOrRange::Literal { .. }) => None,
-            (DateOrRange::Single(a), DateOrRange::Single(b)) => Some(a.cmp(b)),
-            (DateOrRange::Range(a1, a2), DateOrRange::Single(b)) => Some(a1.cmp(b).then(a2.cmp(b))),
-            (DateOrRange::Single(a), DateOrRange::Range(b1, b2)) => Some(a.cmp(b1).then(a.cmp(b2))),
-            (DateOrRange::Range(a1, a2), DateOrRange::Range(b1, b2)) => {
-                Some(a1.cmp(b1).then(a2.cmp(b2)))
-            }
+            (
+                Literal { literal, circa },
+                Literal {
+                    literal: literal2,
+                    circa: circa2,
+                },
+            ) => literal.cmp(literal2).then(circa.cmp(circa2)),
+            (Literal { .. }, _) => Ordering::Greater,
+            (_, Literal { .. }) => Ordering::Less,
+            (Single(a), Single(b)) => a.cmp(b),
+            (Single(a), Range(b1, _)) => a.cmp(b1).then(Ordering::Less),
+            (Range(a1, _), Single(b)) => a1.cmp(b).then(Ordering::Greater),
+            (Range(a1, a2), Range(b1, b2)) => a1.cmp(b1).then(a2.cmp(b2)),
         }
     }
 }
@@ -66,14 +87,75 @@ fn test_date_ord() {
     assert!(Date::new(2000, 5, 0) < Date::new(2000, 5, 1));
 
     assert!(Date::new(2000, 0, 0) < Date::new(2001, 0, 0));
+    // BC
+    assert!(Date::new(-100, 0, 0) < Date::new(-50, 0, 0));
+    // Seasons are ignored, unless that's the only difference
+    assert!(Date::new(2000, 13, 0) < Date::new(2000, 1, 0));
+    assert!(Date::new(2000, 0, 0) < Date::new(2000, 13, 0));
+    assert!(Date::new(2000, 13, 0) < Date::new(2000, 16, 0));
+    assert!(Date::new(2000, 0, 0) < Date::new_circa(2000, 0, 0));
 }
 
-// TODO: implement PartialOrd?
+#[test]
+fn test_date_or_range_ord() {
+    let mut dates = vec![
+        DateOrRange::Literal {
+            literal: "n.d.".into(),
+            circa: false,
+        },
+        DateOrRange::Range(Date::new(2002, 0, 0), Date::new(2009, 0, 0)),
+        DateOrRange::Range(Date::new(2000, 0, 0), Date::new(2005, 0, 0)),
+        DateOrRange::Range(Date::new(2002, 0, 0), Date::new(2003, 0, 0)),
+        DateOrRange::Literal {
+            literal: "forthcoming".into(),
+            circa: false,
+        },
+        DateOrRange::new(2000, 0, 0),
+        DateOrRange::Range(Date::new(2000, 0, 0), Date::new(2001, 0, 0)),
+        DateOrRange::new(2001, 0, 0),
+    ];
+    dates.sort();
+    assert_eq!(
+        dates,
+        vec![
+            DateOrRange::new(2000, 0, 0),
+            DateOrRange::Range(Date::new(2000, 0, 0), Date::new(2001, 0, 0)),
+            DateOrRange::Range(Date::new(2000, 0, 0), Date::new(2005, 0, 0)),
+            DateOrRange::new(2001, 0, 0),
+            DateOrRange::Range(Date::new(2002, 0, 0), Date::new(2003, 0, 0)),
+            DateOrRange::Range(Date::new(2002, 0, 0), Date::new(2009, 0, 0)),
+            DateOrRange::Literal {
+                literal: "forthcoming".into(),
+                circa: false,
+            },
+            DateOrRange::Literal {
+                literal: "n.d.".into(),
+                circa: false,
+            },
+        ]
+    );
+}
 
 impl Date {
     pub fn has_month(&self) -> bool {
         self.month != 0
     }
+    /// The month, or 0 if there is none or this is a season.
+    pub fn month_or_zero(&self) -> u32 {
+        if self.month <= 12 {
+            self.month
+        } else {
+            0
+        }
+    }
+    /// The season, 1 to 4, or 0 if there is none.
+    pub fn season_or_zero(&self) -> u32 {
+        if self.month > 12 {
+            self.month - 12
+        } else {
+            0
+        }
+    }
     pub fn has_day(&self) -> bool {
         self.day != 0
     }
@@ -159,6 +241,32 @@ impl DateOrRange {
             _ => None,
         }
     }
+    fn all_dates(&self, f: impl Fn(&Date) -> bool) -> bool {
+        match self {
+            DateOrRange::Single(d) => f(d),
+            DateOrRange::Range(d1, d2) => f(d1) && f(d2),
+            DateOrRange::Literal { .. } => false,
+        }
+    }
+    fn any_date(&self, f: impl Fn(&Date) -> bool) -> bool {
+        match self {
+            DateOrRange::Single(d) => f(d),
+            DateOrRange::Range(d1, d2) => f(d1) || f(d2),
+            DateOrRange::Literal { .. } => false,
+        }
+    }
+    /// Whether every date in it is a bare year. False for literals.
+    pub fn is_year_only(&self) -> bool {
+        self.all_dates(|d| d.month == 0 && d.day == 0)
+    }
+    /// Whether any date in it has a month or a season. False for literals.
+    pub fn has_month_or_season(&self) -> bool {
+        self.any_date(Date::has_month)
+    }
+    /// Whether any date in it has a day. False for literals.
+    pub fn has_day(&self) -> bool {
+        self.any_date(Date::has_day)
+    }
     pub fn from_parts(parts: &[&[i32]]) -> Option<Self> {
         if parts.is_empty() {
             None
@@ -35,6 +35,7 @@ pub mod utils;
 
 pub use csl_json::NumberLike;
 pub use output::micro_html::micro_html_to_string;
+pub use output::micro_html;
 
 #[doc(inline)]
 pub use self::cite::*;
@@ -79,6 +80,10 @@ pub struct IngestOptions {
     pub no_parse_quotes: bool,
     /// For affixes in a csl style, etc. No HTML parsing, but does parse super/subscript.
     pub is_attribute: bool,
+    /// Words the text-case transforms must not change.
+    pub case_exceptions: CaseExceptions,
+    /// The language of the text, for the few casing rules that depend on it.
+    pub case_language: CaseLanguage,
 }
 
 impl IngestOptions {
@@ -92,3 +97,4 @@ impl IngestOptions {
 
 pub mod lazy;
 mod text_case;
+pub use text_case::{CaseExceptions, CaseLanguage};
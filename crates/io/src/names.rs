@@ -19,6 +19,18 @@ struct PersonNameInput {
     pub static_particles: bool,
     #[serde(default, deserialize_with = "RelaxedBool::deserialize_bool")]
     pub comma_suffix: bool,
+    #[serde(default, deserialize_with = "RelaxedBool::deserialize_bool")]
+    pub static_ordering: bool,
+    #[serde(default)]
+    pub romanized: Option<RomanizedName>,
+}
+
+/// A transliteration of a name written in another script, e.g. "Mao Zedong" for 毛泽东. Names
+/// that have one are sorted by it.
+#[derive(Default, Debug, Eq, PartialEq, Hash, Deserialize, Serialize, Clone)]
+pub struct RomanizedName {
+    pub family: Option<String>,
+    pub given: Option<String>,
 }
 
 // kebab-case here is the same as Strum's "kebab_case",
@@ -36,6 +48,12 @@ pub struct PersonName {
     pub static_particles: bool,
     #[serde(default)]
     pub comma_suffix: bool,
+    /// Always family name first, with no comma, even for a name in Latin script. Names that are
+    /// not in Latin or Cyrillic script are always ordered like this.
+    #[serde(default)]
+    pub static_ordering: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub romanized: Option<RomanizedName>,
     #[serde(default, skip_serializing)]
     pub is_latin_cyrillic: bool,
 }
@@ -88,6 +106,37 @@ impl From<NameInput> for Name {
     }
 }
 
+impl Name {
+    /// A name given as a single string, like a court or another institution.
+    pub fn literal(literal: String) -> Self {
+        NameInput::Literal { literal }.into()
+    }
+
+    /// The name written out in full, given name first, with no style applied.
+    pub fn plain_text(&self) -> String {
+        match self {
+            Name::Literal { literal, .. } => literal.clone(),
+            Name::Person(pn) => {
+                let parts = [
+                    &pn.given,
+                    &pn.dropping_particle,
+                    &pn.non_dropping_particle,
+                    &pn.family,
+                    &pn.suffix,
+                ];
+                let mut out = String::new();
+                for part in parts.iter().filter_map(|p| p.as_ref()) {
+                    if !out.is_empty() {
+                        out.push(' ');
+                    }
+                    out.push_str(part);
+                }
+                out
+            }
+        }
+    }
+}
+
 // Now we implement From<PersonNameInput> for PersonName
 
 macro_rules! regex {
@@ -187,6 +236,8 @@ impl From<PersonNameInput> for PersonName {
             suffix,
             static_particles,
             comma_suffix,
+            static_ordering,
+            romanized,
         } = input;
 
         let mut pn = PersonName {
@@ -197,6 +248,8 @@ impl From<PersonNameInput> for PersonName {
             suffix,
             static_particles,
             comma_suffix,
+            static_ordering,
+            romanized,
             is_latin_cyrillic,
         };
 
@@ -208,7 +261,7 @@ impl From<PersonNameInput> for PersonName {
             suffix,
             static_particles,
             comma_suffix,
-            is_latin_cyrillic: _,
+            ..
         } = &mut pn;
 
         // Don't parse if these are supplied
@@ -10,8 +10,43 @@ use std::borrow::Cow;
 
 pub mod roman;
 
+/// Either a parsed vector of numeric tokens, or the raw string input.
+///
+/// Relevant parts of the Spec:
+///
+/// * [`<choose is-numeric="...">`](https://docs.citationstyles.org/en/stable/specification.html#choose)
+/// * [`<number>`](https://docs.citationstyles.org/en/stable/specification.html#number)
+///
+/// We parse:
+///
+/// ```text
+/// "2, 4"         => Tokens([Num(2), Comma, Num(4)])
+/// "2-4, 5"       => Tokens([Num(2), Hyphen, Num(4), Comma, Num(5)])
+/// "2 -4    , 5"  => Tokens([Num(2), Hyphen, Num(4), Comma, Num(5)])
+/// "2nd"          => Tokens([Affixed("2nd")])
+/// "L2"           => Tokens([Affixed("L2")])
+/// "L2tp"         => Tokens([Affixed("L2tp")])
+/// "2nd-4th"      => Tokens([Affixed("2nd"), Hyphen, Affixed("4th")])
+/// ```
+///
+/// We don't parse:
+///
+/// ```text
+/// "2nd edition"  => Err("edition") -> not numeric -> Str("2nd edition")
+/// "-5"           => Err("-5") -> not numeric -> Str("-5")
+/// "5,,7"         => Err(",7") -> not numeric -> Str("5,,7")
+/// "5 7 9 11"     => Err("7 9 11") -> not numeric -> Str("5 7 9 11")
+/// "5,"           => Err("") -> not numeric -> Str("5,")
+/// ```
+///
+/// It's a number, then a { comma|hyphen|ampersand } with any whitespace, then another number, and
+/// so on. All numbers are unsigned.
+///
+/// Parse one with [NumericValue::parse]. The derived `Ord` compares the verbatim input first; use
+/// [NumericValue::cmp_for_sort] to order values the way `<sort>` does.
 #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub enum NumericValue<'a> {
+    /// The verbatim input, its tokens, and whether it was parsed perfectly.
     Tokens(
         Cow<'a, str>,
         Vec<NumericToken>,
@@ -21,6 +56,7 @@ pub enum NumericValue<'a> {
     Str(Cow<'a, str>),
 }
 
+/// A [NumericValue] without its verbatim input, for comparing values when sorting.
 #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub enum NumericValueOwned {
     Tokens(Vec<NumericToken>),
@@ -35,6 +71,7 @@ impl From<NumericValue<'_>> for NumericValueOwned {
     }
 }
 
+/// One piece of a parsed [NumericValue]: a number, or a separator between numbers.
 #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub enum NumericToken {
     Num(u32),
@@ -60,45 +97,55 @@ impl NumericToken {
     }
 }
 
-/// Either a parsed vector of numeric tokens, or the raw string input.
-///
-/// Relevant parts of the Spec:
-///
-/// * [`<choose is-numeric="...">`](https://docs.citationstyles.org/en/stable/specification.html#choose)
-/// * [`<number>`](https://docs.citationstyles.org/en/stable/specification.html#number)
-///
-/// We parse:
-///
-/// ```text
-/// "2, 4"         => Tokens([Num(2), Comma, Num(4)])
-/// "2-4, 5"       => Tokens([Num(2), Hyphen, Num(4), Comma, Num(5)])
-/// "2 -4    , 5"  => Tokens([Num(2), Hyphen, Num(4), Comma, Num(5)])
-/// "2nd"          => Tokens([Affixed("2nd")])
-/// "L2"           => Tokens([Affixed("L2")])
-/// "L2tp"         => Tokens([Affixed("L2tp")])
-/// "2nd-4th"      => Tokens([Affixed("2nd"), Hyphen, Affixed("4th")])
-/// ```
-///
-/// We don't parse:
-///
-/// ```text
-/// "2nd edition"  => Err("edition") -> not numeric -> Str("2nd edition")
-/// "-5"           => Err("-5") -> not numeric -> Str("-5")
-/// "5,,7"         => Err(",7") -> not numeric -> Str("5,,7")
-/// "5 7 9 11"     => Err("7 9 11") -> not numeric -> Str("5 7 9 11")
-/// "5,"           => Err("") -> not numeric -> Str("5,")
-/// ```
-///
-/// It's a number, then a { comma|hyphen|ampersand } with any whitespace, then another number, and
-/// so on. All numbers are unsigned.
-
 impl<'a> NumericValue<'a> {
+    /// A single number.
     pub fn num(i: u32) -> Self {
         NumericValue::Tokens(format!("{}", i).into(), vec![Num(i)], true)
     }
+    /// The first number of a page range or list, e.g. `2` for `"2-5, 9"`.
     pub fn page_first(&self) -> Option<Self> {
         self.first_num().map(NumericValue::num)
     }
+    /// Whether this contains a range, like `"2-5"` or `"ii-iv, 7"`.
+    pub fn is_range(&self) -> bool {
+        match self {
+            NumericValue::Tokens(_, ts, _) => ts.contains(&Hyphen),
+            NumericValue::Str(_) => false,
+        }
+    }
+    /// The next value after a single number, keeping its style: `"9"` gives `10`, `"iv"` gives
+    /// `v`, and `"A9b"` gives `A10b`. `None` for lists, ranges, and anything not numeric.
+    pub fn successor(&self) -> Option<NumericValue<'static>> {
+        let ts = match self {
+            NumericValue::Tokens(_, ts, true) if ts.len() == 1 => ts,
+            _ => return None,
+        };
+        let (verbatim, token) = match &ts[0] {
+            Num(n) => return n.checked_add(1).map(NumericValue::num),
+            Roman(n, upper) => {
+                let next = n + 1;
+                let lower = roman::to(next)?;
+                let verbatim = if *upper {
+                    lower.to_ascii_uppercase()
+                } else {
+                    lower.into()
+                };
+                (verbatim, Roman(next, *upper))
+            }
+            Affixed(prefix, n, suffix) => {
+                let next = n.checked_add(1)?;
+                let verbatim = format!("{}{}{}", prefix, next, suffix);
+                (verbatim, Affixed(prefix.clone(), next, suffix.clone()))
+            }
+            _ => return None,
+        };
+        Some(NumericValue::Tokens(verbatim.into(), vec![token], true))
+    }
+    /// Orders two values the way sorting on a number variable does: by their tokens, so `"9"`
+    /// sorts before `"10"`, ignoring how they were written.
+    pub fn cmp_for_sort(&self, other: &NumericValue) -> std::cmp::Ordering {
+        NumericValueOwned::from(self.clone()).cmp(&NumericValueOwned::from(other.clone()))
+    }
     fn first_num(&self) -> Option<u32> {
         match *self {
             NumericValue::Tokens(_, ref ts, _) => ts.get(0).and_then(|token| token.get_num()),
@@ -173,8 +220,9 @@ impl<'a> NumericValue<'a> {
             NumericValue::Str(input.into())
         }
     }
-    #[cfg(test)]
-    fn parse(input: &'a str) -> Self {
+    /// Parses a number variable's value, with "and" as the word separating two numbers. Use
+    /// [NumericValue::from_localized] to have the locale's "and" term instead.
+    pub fn parse(input: &'a str) -> Self {
         NumericValue::parse_full(input, "and")
     }
     pub fn from_localized(and_term: &'a str) -> impl Fn(&'a NumberLike) -> NumericValue<'a> + 'a {
@@ -184,6 +232,10 @@ impl<'a> NumericValue<'a> {
             NumberLike::Num(n) => NumericValue::num(*n),
         }
     }
+    /// Like [NumericValue::from_localized], for a number that is only part of a string.
+    pub fn from_localized_str(input: &'a str, and_term: &'a str) -> Self {
+        NumericValue::parse_full(input.trim(), and_term)
+    }
 }
 
 use nom::{
@@ -536,3 +588,23 @@ fn test_page_first() {
         NumericValue::num(2)
     );
 }
+
+#[test]
+fn test_successor_and_range() {
+    let succ = |s| NumericValue::parse(s).successor();
+    assert_eq!(succ("9"), Some(NumericValue::num(10)));
+    assert_eq!(succ("iv").as_ref().map(NumericValue::verbatim), Some("v"));
+    assert_eq!(succ("XIX").as_ref().map(NumericValue::verbatim), Some("XX"));
+    assert_eq!(
+        succ("A9b").as_ref().map(NumericValue::verbatim),
+        Some("A10b")
+    );
+    assert_eq!(succ("2-5"), None);
+    assert_eq!(succ("2nd edition"), None);
+    assert!(NumericValue::parse("2-5, 9").is_range());
+    assert!(!NumericValue::parse("2, 9").is_range());
+    assert_eq!(
+        NumericValue::parse("9").cmp_for_sort(&NumericValue::parse("10")),
+        std::cmp::Ordering::Less
+    );
+}
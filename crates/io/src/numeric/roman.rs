@@ -106,9 +106,10 @@ fn from_lax(txt: &str) -> Option<u32> {
             ch == c
         })?;
         if val < max {
-            n -= val;
+            // "IIIIM" and friends are not numerals, but must not underflow either
+            n = n.checked_sub(val)?;
         } else {
-            n += val;
+            n = n.checked_add(val)?;
             max = val;
         }
     }
@@ -118,6 +119,7 @@ fn from_lax(txt: &str) -> Option<u32> {
 #[test]
 fn test_from() {
     assert!(from("I").is_some());
+    assert_eq!(from(&format!("{}m", "i".repeat(1001))), None);
 }
 
 #[test]
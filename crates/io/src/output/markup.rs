@@ -16,7 +16,9 @@ use csl::{
 };
 use url::Url;
 
+#[cfg(feature = "rtf")]
 mod rtf;
+#[cfg(feature = "rtf")]
 use self::rtf::RtfWriter;
 
 mod html;
@@ -25,6 +27,14 @@ use self::html::HtmlWriter;
 mod plain;
 use self::plain::PlainWriter;
 
+mod custom;
+use self::custom::CustomWriter;
+pub use self::custom::{CustomFormat, CustomMarkup};
+
+mod runs;
+use self::runs::RunsWriter;
+pub use self::runs::TextRun;
+
 mod flip_flop;
 use self::flip_flop::FlipFlopState;
 mod move_punctuation;
@@ -36,32 +46,84 @@ use crate::String;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Markup {
-    Html(FormatOptions),
-    Rtf(FormatOptions),
-    Plain(FormatOptions),
+    Html(HtmlOptions),
+    /// Needs the `rtf` feature, without which this writes plain text.
+    Rtf(RtfOptions),
+    Plain(PlainOptions),
+    /// A format implemented outside this crate.
+    Custom(CustomMarkup),
 }
 
-/// Controls how the output is formatted.
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+/// Controls how the output is formatted, with a set of options for each format. Only the ones for
+/// the format in use apply, but the rest are kept for when you switch.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 pub struct FormatOptions {
+    pub html: HtmlOptions,
+    pub rtf: RtfOptions,
+    pub plain: PlainOptions,
+}
+
+impl FormatOptions {
+    pub fn test_suite() -> Self {
+        FormatOptions::default().with_link_anchors(false)
+    }
+
+    /// Sets `link_anchors` for every format that makes links.
+    pub fn with_link_anchors(mut self, link_anchors: bool) -> Self {
+        self.html.link_anchors = link_anchors;
+        self.rtf.link_anchors = link_anchors;
+        self
+    }
+}
+
+/// Options for [Markup::Html].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HtmlOptions {
     /// See CSL 1.1, Appendix VI -- enable or disable making urls clickable. Default is enabled.
     pub link_anchors: bool,
+    /// Wraps runs of right-to-left text, like a Hebrew or Arabic title in an English citation, in
+    /// `<span dir="rtl">`, so the punctuation around them isn't reordered. Default is disabled.
+    pub bidi_isolation: bool,
+    /// Starts each bibliography entry with an empty `<a>` whose `id` is the entry's anchor, so
+    /// in-text citations can link to it. Default is disabled.
+    pub entry_anchors: bool,
 }
 
-impl Default for FormatOptions {
+impl Default for HtmlOptions {
     fn default() -> Self {
-        FormatOptions { link_anchors: true }
+        HtmlOptions {
+            link_anchors: true,
+            bidi_isolation: false,
+            entry_anchors: false,
+        }
     }
 }
 
-impl FormatOptions {
-    pub fn test_suite() -> Self {
-        FormatOptions {
-            link_anchors: false,
-        }
+/// Options for [Markup::Rtf].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RtfOptions {
+    /// Enable or disable making urls clickable with `HYPERLINK` fields. Default is enabled.
+    pub link_anchors: bool,
+}
+
+impl Default for RtfOptions {
+    fn default() -> Self {
+        RtfOptions { link_anchors: true }
     }
 }
 
+/// Options for [Markup::Plain].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PlainOptions {
+    /// Replaces the locale's quotation marks, en and em dashes, ellipses and non-breaking spaces
+    /// with the nearest ASCII, for environments that can't display anything else. Default is
+    /// disabled.
+    pub ascii_only: bool,
+    /// Wraps runs of right-to-left text in the Unicode directional isolates U+2067 and U+2069.
+    /// See [HtmlOptions::bidi_isolation]. Default is disabled.
+    pub bidi_isolation: bool,
+}
+
 /// TODO: serialize and deserialize using an HTML parser?
 #[derive(Debug, Clone, Eq, PartialEq, Serialize)]
 pub enum InlineElement {
@@ -81,26 +143,40 @@ pub enum InlineElement {
     Div(DisplayMode, Vec<InlineElement>),
 }
 
-impl InlineElement {}
+impl InlineElement {
+    /// Whether this contains a `display` div anywhere.
+    pub fn has_display(&self) -> bool {
+        match self {
+            InlineElement::Div(..) => true,
+            InlineElement::Formatted(inlines, _) | InlineElement::Quoted { inlines, .. } => {
+                inlines.iter().any(InlineElement::has_display)
+            }
+            InlineElement::Micro(_) | InlineElement::Text(_) | InlineElement::Linked(_) => false,
+        }
+    }
+}
 
 impl Markup {
     pub fn html() -> Self {
-        Markup::Html(FormatOptions::default())
+        Markup::Html(HtmlOptions::default())
     }
     pub fn test_html() -> Self {
-        Markup::Html(FormatOptions::test_suite())
+        Markup::Html(FormatOptions::test_suite().html)
     }
     pub fn rtf() -> Self {
-        Markup::Rtf(FormatOptions::default())
+        Markup::Rtf(RtfOptions::default())
     }
     pub fn plain() -> Self {
-        Markup::Plain(FormatOptions::default())
+        Markup::Plain(PlainOptions::default())
+    }
+    pub fn custom(format: std::sync::Arc<dyn CustomFormat>) -> Self {
+        Markup::Custom(CustomMarkup(format))
     }
 }
 
 impl Default for Markup {
     fn default() -> Self {
-        Markup::Html(FormatOptions::default())
+        Markup::Html(HtmlOptions::default())
     }
 }
 
@@ -123,6 +199,7 @@ impl OutputFormat for Markup {
             Markup::Html(_) => ("<div class=\"csl-bib-body\">", "</div>"),
             Markup::Rtf(_) => ("", ""),
             Markup::Plain(_) => ("", ""),
+            Markup::Custom(custom) => custom.0.bibliography_wrapper(),
         };
         MarkupBibMeta {
             markup_pre: pre.into(),
@@ -240,8 +317,9 @@ impl OutputFormat for Markup {
     fn stack_preorder(&self, dest: &mut String, stack: &[FormatCmd]) {
         match *self {
             Markup::Html(options) => HtmlWriter::new(dest, options).stack_preorder(stack),
-            Markup::Rtf(options) => PlainWriter::new(dest, options).stack_preorder(stack),
+            Markup::Rtf(_) => PlainWriter::new(dest, PlainOptions::default()).stack_preorder(stack),
             Markup::Plain(options) => PlainWriter::new(dest, options).stack_preorder(stack),
+            Markup::Custom(ref custom) => CustomWriter::new(dest, &*custom.0).stack_preorder(stack),
         }
     }
 
@@ -249,8 +327,13 @@ impl OutputFormat for Markup {
     fn stack_postorder(&self, dest: &mut String, stack: &[FormatCmd]) {
         match *self {
             Markup::Html(options) => HtmlWriter::new(dest, options).stack_postorder(stack),
-            Markup::Rtf(options) => PlainWriter::new(dest, options).stack_postorder(stack),
+            Markup::Rtf(_) => {
+                PlainWriter::new(dest, PlainOptions::default()).stack_postorder(stack)
+            }
             Markup::Plain(options) => PlainWriter::new(dest, options).stack_postorder(stack),
+            Markup::Custom(ref custom) => {
+                CustomWriter::new(dest, &*custom.0).stack_postorder(stack)
+            }
         }
     }
 
@@ -307,15 +390,86 @@ impl Markup {
             Markup::Html(options) => {
                 HtmlWriter::new(&mut dest, options).write_inlines(&flipped, false)
             }
+            #[cfg(feature = "rtf")]
             Markup::Rtf(options) => {
                 RtfWriter::new(&mut dest, options).write_inlines(&flipped, false)
             }
+            #[cfg(not(feature = "rtf"))]
+            Markup::Rtf(_) => {
+                PlainWriter::new(&mut dest, PlainOptions::default()).write_inlines(&flipped, false)
+            }
             Markup::Plain(options) => {
                 PlainWriter::new(&mut dest, options).write_inlines(&flipped, false)
             }
+            Markup::Custom(ref custom) => {
+                CustomWriter::new(&mut dest, &*custom.0).write_inlines(&flipped, false)
+            }
         }
         dest
     }
+
+    /// Like [OutputFormat::output], but as a list of [TextRun]s instead of a string. The runs are
+    /// the same whatever the output format is, because they carry formatting as attributes.
+    pub fn output_runs(
+        &self,
+        intermediate: <Self as OutputFormat>::Build,
+        punctuation_in_quote: bool,
+    ) -> Vec<TextRun> {
+        let mut flipped = FlipFlopState::default().flip_flop_inlines(&intermediate);
+        move_punctuation(&mut flipped, Some(punctuation_in_quote));
+        let mut runs = Vec::new();
+        let mut writer = RunsWriter::new(&mut runs);
+        writer.write_inlines(&flipped, false);
+        writer.flush();
+        runs
+    }
+}
+
+/// Calls `f` with consecutive pieces of `text`, and whether each is a right-to-left run. A run
+/// goes from one right-to-left letter to the last one before a left-to-right letter, so the
+/// neutral characters between two runs belong to neither.
+fn split_rtl_runs<'a>(text: &'a str, mut f: impl FnMut(&'a str, bool)) {
+    use unicode_bidi::{bidi_class, BidiClass};
+    let mut emitted = 0;
+    let mut run: Option<(usize, usize)> = None;
+    let mut flush = |run: Option<(usize, usize)>, emitted: &mut usize| {
+        if let Some((start, end)) = run {
+            if start > *emitted {
+                f(&text[*emitted..start], false);
+            }
+            f(&text[start..end], true);
+            *emitted = end;
+        }
+    };
+    for (ix, c) in text.char_indices() {
+        match bidi_class(c) {
+            BidiClass::R | BidiClass::AL => {
+                let end = ix + c.len_utf8();
+                run = Some(run.map_or((ix, end), |(start, _)| (start, end)));
+            }
+            BidiClass::L => flush(run.take(), &mut emitted),
+            _ => {}
+        }
+    }
+    flush(run, &mut emitted);
+    if emitted < text.len() {
+        f(&text[emitted..], false);
+    }
+}
+
+#[test]
+fn test_split_rtl_runs() {
+    fn split(text: &str) -> Vec<(&str, bool)> {
+        let mut runs = Vec::new();
+        split_rtl_runs(text, |run, rtl| runs.push((run, rtl)));
+        runs
+    }
+    assert_eq!(split("Title"), vec![("Title", false)]);
+    assert_eq!(split("שלום"), vec![("שלום", true)]);
+    assert_eq!(
+        split("In: שלום עולם. Other"),
+        vec![("In: ", false), ("שלום עולם", true), (". Other", false)]
+    );
 }
 
 pub trait MarkupWriter {
@@ -333,14 +487,14 @@ pub trait MarkupWriter {
         link: &Link,
         href_close: &str,
         a_close: &str,
-        options: FormatOptions,
+        link_anchors: bool,
     ) {
         match link {
             Link::Url {
                 url,
                 trailing_slash,
             } if allow_url_scheme(url.scheme()) => {
-                if options.link_anchors {
+                if link_anchors {
                     self.write_raw(a_href);
                     self.write_url(url, *trailing_slash, true);
                     self.write_raw(href_close);
@@ -363,7 +517,7 @@ pub trait MarkupWriter {
                 self.write_url(&url, *trailing_slash, false);
             }
             Link::Id { id, url } => {
-                if options.link_anchors {
+                if link_anchors {
                     self.write_raw(a_href);
                     self.write_url(url, false, true);
                     self.write_raw(href_close);
@@ -391,7 +545,33 @@ pub trait MarkupWriter {
         self.stack_postorder(&stack);
     }
 
-    fn write_micro(&mut self, micro: &MicroNode, trim_start: bool);
+    fn write_micro(&mut self, micro: &MicroNode, trim_start: bool) {
+        match micro {
+            MicroNode::Text(text) => {
+                self.write_escaped(text.trim_start_if(trim_start));
+            }
+            MicroNode::Quoted {
+                is_inner,
+                localized,
+                children,
+            } => {
+                self.write_escaped(localized.opening(*is_inner).trim_start_if(trim_start));
+                self.write_micros(children, false);
+                self.write_escaped(localized.closing(*is_inner));
+            }
+            MicroNode::Formatted(nodes, cmd) => {
+                self.stack_preorder(&[*cmd][..]);
+                self.write_micros(nodes, trim_start);
+                self.stack_postorder(&[*cmd][..]);
+            }
+            MicroNode::NoCase(inners) => {
+                self.write_micros(inners, trim_start);
+            }
+            MicroNode::NoDecor(inners) => {
+                self.write_micros(inners, trim_start);
+            }
+        }
+    }
     /// Returned boolean = true if it used the peeked element to move some punctuation inside, and
     /// hence should skip it.
     fn write_micros(&mut self, micros: &[MicroNode], trim_start: bool) {
@@ -401,7 +581,37 @@ pub trait MarkupWriter {
             seen = true;
         }
     }
-    fn write_inline(&mut self, inline: &InlineElement, trim_start: bool);
+    fn write_inline(&mut self, inline: &InlineElement, trim_start: bool) {
+        match inline {
+            Text(text) => {
+                self.write_escaped(text.trim_start_if(trim_start));
+            }
+            Div(display, inlines) => {
+                self.stack_formats(inlines, Formatting::default(), Some(*display));
+            }
+            Micro(micros) => {
+                self.write_micros(micros, trim_start);
+            }
+            Formatted(inlines, formatting) => {
+                self.stack_formats(inlines, *formatting, None);
+            }
+            Quoted {
+                is_inner,
+                localized,
+                inlines,
+            } => {
+                // TODO: move punctuation
+                self.write_escaped(localized.opening(*is_inner).trim_start_if(trim_start));
+                self.write_inlines(inlines, false);
+                self.write_escaped(localized.closing(*is_inner));
+            }
+            Linked(link) => self.write_inline_link(link),
+        }
+    }
+    /// Writes an InlineElement::Linked. By default, writes the url or id without an anchor.
+    fn write_inline_link(&mut self, link: &Link) {
+        self.write_link("", link, "", "", false)
+    }
     fn write_inlines(&mut self, inlines: &[InlineElement], trim_start: bool) {
         let mut seen = false;
         for inline in inlines {
@@ -503,6 +713,21 @@ fn write_url(
     })
 }
 
+/// A url as unescaped text, for writers that escape it themselves.
+fn url_text(url: &Url, trailing_slash: bool, in_attr: bool) -> String {
+    let mut text = String::new();
+    write_url(
+        &mut text,
+        url,
+        trailing_slash,
+        in_attr,
+        |b, s| Ok(b.push_str(s)),
+        |b, s| Ok(b.push_str(s)),
+    )
+    .unwrap();
+    text
+}
+
 fn allow_url_scheme(scheme: &str) -> bool {
     // see https://security.stackexchange.com/questions/148428/which-url-schemes-are-dangerous-xss-exploitable
     // list from wordpress https://developer.wordpress.org/reference/functions/wp_allowed_protocols/
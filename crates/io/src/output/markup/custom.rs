@@ -0,0 +1,113 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright © 2021 Corporation for Digital Scholarship
+
+use super::MarkupWriter;
+use crate::output::markup::Link;
+use crate::output::FormatCmd;
+use crate::String;
+use std::fmt;
+use std::sync::Arc;
+
+/// An output format defined outside citeproc-rs, like Typst, ANSI terminal colours or ConTeXt.
+/// Use one with [Markup::Custom](super::Markup::Custom).
+///
+/// The processor builds the same tree of formatted text whatever the output format is. A
+/// `CustomFormat` only decides how that tree is written out, one piece at a time.
+pub trait CustomFormat: fmt::Debug + Send + Sync {
+    /// Appends `text`, escaped however the format requires.
+    fn write_text(&self, dest: &mut String, text: &str);
+
+    /// Appends whatever starts `cmd`, e.g. `#emph[` for italics in Typst.
+    fn open(&self, dest: &mut String, cmd: FormatCmd);
+
+    /// Appends whatever ends `cmd`. Commands are closed in the reverse order they were opened.
+    fn close(&self, dest: &mut String, cmd: FormatCmd);
+
+    /// Appends a link to `url`, displayed as `text`. By default, just writes `text`.
+    fn write_link(&self, dest: &mut String, url: &str, text: &str) {
+        let _ = url;
+        self.write_text(dest, text);
+    }
+
+    /// Markup to put before and after a whole bibliography. Empty by default.
+    fn bibliography_wrapper(&self) -> (&str, &str) {
+        ("", "")
+    }
+}
+
+/// A shared [CustomFormat]. Two of these are equal only if they are the same instance.
+#[derive(Debug, Clone)]
+pub struct CustomMarkup(pub Arc<dyn CustomFormat>);
+
+impl PartialEq for CustomMarkup {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::as_ptr(&self.0) as *const () == Arc::as_ptr(&other.0) as *const ()
+    }
+}
+
+impl Eq for CustomMarkup {}
+
+pub struct CustomWriter<'a> {
+    dest: &'a mut String,
+    format: &'a dyn CustomFormat,
+}
+
+impl<'a> CustomWriter<'a> {
+    pub fn new(dest: &'a mut String, format: &'a dyn CustomFormat) -> Self {
+        CustomWriter { dest, format }
+    }
+}
+
+impl<'a> MarkupWriter for CustomWriter<'a> {
+    fn buf(&mut self) -> &mut String {
+        self.dest
+    }
+
+    fn write_escaped(&mut self, text: &str) {
+        self.format.write_text(self.dest, text);
+    }
+
+    fn write_url(&mut self, url: &url::Url, trailing_slash: bool, in_attr: bool) {
+        let text = super::url_text(url, trailing_slash, in_attr);
+        self.format.write_text(self.dest, &text);
+    }
+
+    fn stack_preorder(&mut self, stack: &[FormatCmd]) {
+        for cmd in stack.iter() {
+            self.format.open(self.dest, *cmd);
+        }
+    }
+
+    fn stack_postorder(&mut self, stack: &[FormatCmd]) {
+        for cmd in stack.iter().rev() {
+            self.format.close(self.dest, *cmd);
+        }
+    }
+
+    fn write_link(&mut self, _: &str, link: &Link, _: &str, _: &str, _: bool) {
+        match link {
+            Link::Url {
+                url,
+                trailing_slash,
+            } if super::allow_url_scheme(url.scheme()) => {
+                let text = super::url_text(url, *trailing_slash, false);
+                self.format.write_link(self.dest, url.as_str(), &text);
+            }
+            Link::Url {
+                url,
+                trailing_slash,
+            } => {
+                warn!(
+                    "refusing to render url anchor for scheme {} on url {}",
+                    url.scheme(),
+                    url
+                );
+                self.write_url(url, *trailing_slash, false);
+            }
+            Link::Id { id, url } => self.format.write_link(self.dest, url.as_str(), id),
+        }
+    }
+}
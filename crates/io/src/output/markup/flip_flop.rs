@@ -259,3 +259,52 @@ fn flip_flop_node(node: &MicroNode, state: &FlipFlopState) -> Result<MicroNode,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::output::markup::Markup;
+    use crate::output::{LocalizedQuotes, OutputFormat};
+    use crate::IngestOptions;
+    use csl::Locale;
+
+    fn quoted_field(value: &str, quotes: LocalizedQuotes) -> String {
+        let fmt = Markup::plain();
+        let options = IngestOptions::default_with_quotes(quotes.clone());
+        let b = fmt.ingest(value, &options);
+        let b = fmt.quoted(b, quotes);
+        fmt.output(b, false).to_string()
+    }
+
+    #[test]
+    fn quoted_field_flips_inner_quotes() {
+        let simple = LocalizedQuotes::simple();
+        assert_eq!(
+            quoted_field(r#"Tom's "Big" Day"#, simple.clone()),
+            "\u{201C}Tom\u{2019}s \u{2018}Big\u{2019} Day\u{201D}"
+        );
+        assert_eq!(
+            quoted_field("The 'Inner' one", simple),
+            "\u{201C}The \u{2018}Inner\u{2019} one\u{201D}"
+        );
+    }
+
+    #[test]
+    fn quoted_field_uses_locale_quotes() {
+        let locale = Locale::parse(
+            r#"<locale xml:lang="de-DE"><terms>
+                <term name="open-quote">„</term>
+                <term name="close-quote">“</term>
+                <term name="open-inner-quote">‚</term>
+                <term name="close-inner-quote">‘</term>
+            </terms></locale>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            quoted_field(r#"Der "Titel""#, LocalizedQuotes::from_locale(&locale)),
+            "„Der ‚Titel‘“"
+        );
+        // missing quote terms don't panic
+        let empty = LocalizedQuotes::from_locale(&Locale::default());
+        assert_eq!(empty, LocalizedQuotes::simple());
+    }
+}
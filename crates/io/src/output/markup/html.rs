@@ -4,22 +4,21 @@
 //
 // Copyright © 2019 Corporation for Digital Scholarship
 
-use super::{FormatOptions, InlineElement, MarkupWriter, MaybeTrimStart};
-use crate::output::micro_html::MicroNode;
+use super::{HtmlOptions, MarkupWriter};
+use crate::output::markup::Link;
 use crate::output::FormatCmd;
 use crate::String;
 use core::fmt::{self, Write};
-use csl::Formatting;
 use url::Url;
 
 #[derive(Debug)]
 pub struct HtmlWriter<'a> {
     dest: &'a mut String,
-    options: FormatOptions,
+    options: HtmlOptions,
 }
 
 impl<'a> HtmlWriter<'a> {
-    pub fn new(dest: &'a mut String, options: FormatOptions) -> Self {
+    pub fn new(dest: &'a mut String, options: HtmlOptions) -> Self {
         HtmlWriter { dest, options }
     }
 }
@@ -29,7 +28,20 @@ impl<'a> MarkupWriter for HtmlWriter<'a> {
         self.dest
     }
     fn write_escaped(&mut self, text: &str) {
-        write!(self.dest, "{}", escape_html(text)).unwrap();
+        if !self.options.bidi_isolation {
+            write!(self.dest, "{}", escape_html(text)).unwrap();
+            return;
+        }
+        let dest = &mut *self.dest;
+        super::split_rtl_runs(text, |run, rtl| {
+            if rtl {
+                dest.push_str(r#"<span dir="rtl">"#);
+            }
+            write!(dest, "{}", escape_html(run)).unwrap();
+            if rtl {
+                dest.push_str("</span>");
+            }
+        });
     }
     fn stack_preorder(&mut self, stack: &[FormatCmd]) {
         for cmd in stack.iter() {
@@ -54,62 +66,14 @@ impl<'a> MarkupWriter for HtmlWriter<'a> {
         }
     }
 
-    fn write_micro(&mut self, micro: &MicroNode, trim_start: bool) {
-        use MicroNode::*;
-        match micro {
-            Text(text) => {
-                self.write_escaped(text.trim_start_if(trim_start));
-            }
-            Quoted {
-                is_inner,
-                localized,
-                children,
-            } => {
-                self.write_escaped(localized.opening(*is_inner).trim_start_if(trim_start));
-                self.write_micros(children, false);
-                self.write_escaped(localized.closing(*is_inner));
-            }
-            Formatted(nodes, cmd) => {
-                self.stack_preorder(&[*cmd][..]);
-                self.write_micros(nodes, trim_start);
-                self.stack_postorder(&[*cmd][..]);
-            }
-            NoCase(inners) => {
-                self.write_micros(inners, trim_start);
-            }
-            NoDecor(inners) => {
-                self.write_micros(inners, trim_start);
-            }
-        }
-    }
-
-    fn write_inline(&mut self, inline: &InlineElement, trim_start: bool) {
-        use super::InlineElement::*;
-        match inline {
-            Text(text) => {
-                self.write_escaped(text.trim_start_if(trim_start));
-            }
-            Div(display, inlines) => {
-                self.stack_formats(inlines, Formatting::default(), Some(*display));
-            }
-            Micro(micros) => {
-                self.write_micros(micros, trim_start);
-            }
-            Formatted(inlines, formatting) => {
-                self.stack_formats(inlines, *formatting, None);
-            }
-            Quoted {
-                is_inner,
-                localized,
-                inlines,
-            } => {
-                // TODO: move punctuation
-                self.write_escaped(localized.opening(*is_inner).trim_start_if(trim_start));
-                self.write_inlines(inlines, false);
-                self.write_escaped(localized.closing(*is_inner));
-            }
-            Linked(link) => self.write_link(r#"<a href=""#, link, r#"">"#, "</a>", self.options),
-        }
+    fn write_inline_link(&mut self, link: &Link) {
+        self.write_link(
+            r#"<a href=""#,
+            link,
+            r#"">"#,
+            "</a>",
+            self.options.link_anchors,
+        )
     }
 
     fn write_url(&mut self, url: &Url, trailing_slash: bool, in_attr: bool) {
@@ -126,7 +90,7 @@ impl<'a> MarkupWriter for HtmlWriter<'a> {
 }
 
 impl FormatCmd {
-    fn html_tag(self, _options: &FormatOptions) -> (&'static str, &'static str) {
+    fn html_tag(self, _options: &HtmlOptions) -> (&'static str, &'static str) {
         match self {
             FormatCmd::DisplayBlock => ("div", r#" class="csl-block""#),
             FormatCmd::DisplayIndent => ("div", r#" class="csl-indent""#),
@@ -4,22 +4,46 @@
 //
 // Copyright © 2019 Corporation for Digital Scholarship
 
-use super::{FormatOptions, InlineElement, MarkupWriter, MaybeTrimStart};
+use super::{MarkupWriter, PlainOptions};
 use crate::output::markup::Link;
-use crate::output::micro_html::MicroNode;
 use crate::output::FormatCmd;
 use crate::String;
-use csl::Formatting;
 
 #[derive(Debug)]
 pub struct PlainWriter<'a> {
     dest: &'a mut String,
-    #[allow(unused)]
-    options: FormatOptions,
+    options: PlainOptions,
+}
+
+/// The nearest ASCII for typographic characters that locales and smart quoting produce, for
+/// [PlainOptions::ascii_only].
+fn ascii_approximation(c: char) -> Option<&'static str> {
+    Some(match c {
+        '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{2039}' | '\u{203A}' => "'",
+        '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{00AB}' | '\u{00BB}' => "\"",
+        '\u{2010}' | '\u{2011}' | '\u{2012}' | '\u{2013}' | '\u{2212}' => "-",
+        '\u{2014}' | '\u{2015}' => "--",
+        '\u{2026}' => "...",
+        '\u{00A0}' | '\u{2007}' | '\u{2009}' | '\u{202F}' => " ",
+        _ => return None,
+    })
+}
+
+fn push_plain(dest: &mut String, text: &str, ascii_only: bool) {
+    if !ascii_only {
+        dest.push_str(text);
+        return;
+    }
+    for c in text.chars() {
+        match ascii_approximation(c) {
+            Some(ascii) => dest.push_str(ascii),
+            None => dest.push(c),
+        }
+    }
 }
 
 impl<'a> PlainWriter<'a> {
-    pub fn new(dest: &'a mut String, options: FormatOptions) -> Self {
+    pub fn new(dest: &'a mut String, options: PlainOptions) -> Self {
         PlainWriter { dest, options }
     }
 }
@@ -30,7 +54,23 @@ impl<'a> MarkupWriter for PlainWriter<'a> {
     }
 
     fn write_escaped(&mut self, text: &str) {
-        self.dest.push_str(text);
+        let ascii_only = self.options.ascii_only;
+        if !self.options.bidi_isolation {
+            push_plain(self.dest, text, ascii_only);
+            return;
+        }
+        let dest = &mut *self.dest;
+        super::split_rtl_runs(text, |run, rtl| {
+            if rtl {
+                // RIGHT-TO-LEFT ISOLATE
+                dest.push('\u{2067}');
+            }
+            push_plain(dest, run, ascii_only);
+            if rtl {
+                // POP DIRECTIONAL ISOLATE
+                dest.push('\u{2069}');
+            }
+        });
     }
 
     fn write_url(&mut self, url: &url::Url, trailing_slash: bool, in_attr: bool) {
@@ -49,65 +89,7 @@ impl<'a> MarkupWriter for PlainWriter<'a> {
 
     fn stack_postorder(&mut self, _stack: &[FormatCmd]) {}
 
-    fn write_micro(&mut self, micro: &MicroNode, trim_start: bool) {
-        use MicroNode::*;
-        match micro {
-            Text(text) => {
-                self.write_escaped(text.trim_start_if(trim_start));
-            }
-            Quoted {
-                is_inner,
-                localized,
-                children,
-            } => {
-                self.dest
-                    .push_str(localized.opening(*is_inner).trim_start_if(trim_start));
-                self.write_micros(children, false);
-                self.dest.push_str(localized.closing(*is_inner));
-            }
-            Formatted(nodes, _cmd) => {
-                self.write_micros(nodes, trim_start);
-            }
-            NoCase(inners) => {
-                self.write_micros(inners, trim_start);
-            }
-            NoDecor(inners) => {
-                self.write_micros(inners, trim_start);
-            }
-        }
-    }
-
-    fn write_inline(&mut self, inline: &InlineElement, trim_start: bool) {
-        use super::InlineElement::*;
-        match inline {
-            Text(text) => {
-                self.write_escaped(text.trim_start_if(trim_start));
-            }
-            Div(display, inlines) => {
-                self.stack_formats(inlines, Formatting::default(), Some(*display));
-            }
-            Micro(micros) => {
-                self.write_micros(micros, trim_start);
-            }
-            Formatted(inlines, formatting) => {
-                self.stack_formats(inlines, *formatting, None);
-            }
-            Quoted {
-                is_inner,
-                localized,
-                inlines,
-            } => {
-                // TODO: move punctuation
-                self.write_escaped(localized.opening(*is_inner).trim_start_if(trim_start));
-                self.write_inlines(inlines, false);
-                self.write_escaped(localized.closing(*is_inner));
-            }
-            Linked(link) => {
-                self.write_link("", link, "", "", self.options);
-            }
-        }
-    }
-    fn write_link(&mut self, _: &str, link: &Link, _: &str, _: &str, _: FormatOptions) {
+    fn write_link(&mut self, _: &str, link: &Link, _: &str, _: &str, _: bool) {
         match link {
             Link::Url {
                 url,
@@ -4,7 +4,7 @@
 //
 // Copyright © 2019 Corporation for Digital Scholarship
 
-use super::{FormatOptions, InlineElement, MarkupWriter, MaybeTrimStart};
+use super::{InlineElement, MarkupWriter, MaybeTrimStart, RtfOptions};
 use crate::output::micro_html::MicroNode;
 use crate::output::FormatCmd;
 use crate::String;
@@ -14,11 +14,11 @@ use csl::Formatting;
 #[derive(Debug)]
 pub struct RtfWriter<'a> {
     dest: &'a mut String,
-    options: FormatOptions,
+    options: RtfOptions,
 }
 
 impl<'a> RtfWriter<'a> {
-    pub fn new(dest: &'a mut String, options: FormatOptions) -> Self {
+    pub fn new(dest: &'a mut String, options: RtfOptions) -> Self {
         RtfWriter { dest, options }
     }
 }
@@ -124,7 +124,7 @@ impl<'a> MarkupWriter for RtfWriter<'a> {
                     link,
                     r#""}}{\fldrslt "#,
                     "}}",
-                    self.options,
+                    self.options.link_anchors,
                 );
             }
         }
@@ -0,0 +1,223 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright © 2021 Corporation for Digital Scholarship
+
+use super::MarkupWriter;
+use crate::output::markup::Link;
+use crate::output::FormatCmd;
+use crate::String;
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+/// A piece of output text with the same formatting throughout, for hosts that build their own
+/// attributed strings (e.g. `NSAttributedString` or Android's `Spannable`) instead of parsing
+/// HTML. See [Markup::output_runs](super::Markup::output_runs).
+///
+/// Only the attributes that are set appear in the serialized form.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextRun {
+    pub text: String,
+    #[serde(skip_serializing_if = "is_false")]
+    pub italic: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub bold: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub small_caps: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub underline: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub superscript: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub subscript: bool,
+    /// The url this run links to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+}
+
+impl TextRun {
+    fn same_attributes(&self, other: &Self) -> bool {
+        self.italic == other.italic
+            && self.bold == other.bold
+            && self.small_caps == other.small_caps
+            && self.underline == other.underline
+            && self.superscript == other.superscript
+            && self.subscript == other.subscript
+            && self.link == other.link
+    }
+}
+
+pub struct RunsWriter<'a> {
+    runs: &'a mut Vec<TextRun>,
+    text: String,
+    stack: Vec<FormatCmd>,
+    link: Option<String>,
+}
+
+impl<'a> RunsWriter<'a> {
+    pub fn new(runs: &'a mut Vec<TextRun>) -> Self {
+        RunsWriter {
+            runs,
+            text: String::new(),
+            stack: Vec::new(),
+            link: None,
+        }
+    }
+
+    /// Turns the text written since the last formatting change into a run, joining it onto the
+    /// previous run if nothing actually changed.
+    pub fn flush(&mut self) {
+        if self.text.is_empty() {
+            return;
+        }
+        use FormatCmd::*;
+        let mut run = TextRun {
+            text: std::mem::take(&mut self.text),
+            link: self.link.clone(),
+            ..Default::default()
+        };
+        for cmd in &self.stack {
+            match cmd {
+                FontStyleItalic | FontStyleOblique => run.italic = true,
+                FontStyleNormal => run.italic = false,
+                FontWeightBold => run.bold = true,
+                FontWeightNormal | FontWeightLight => run.bold = false,
+                FontVariantSmallCaps => run.small_caps = true,
+                FontVariantNormal => run.small_caps = false,
+                TextDecorationUnderline => run.underline = true,
+                TextDecorationNone => run.underline = false,
+                VerticalAlignmentSuperscript => {
+                    run.superscript = true;
+                    run.subscript = false;
+                }
+                VerticalAlignmentSubscript => {
+                    run.superscript = false;
+                    run.subscript = true;
+                }
+                VerticalAlignmentBaseline => {
+                    run.superscript = false;
+                    run.subscript = false;
+                }
+                DisplayBlock | DisplayIndent | DisplayLeftMargin | DisplayRightInline => {}
+            }
+        }
+        match self.runs.last_mut() {
+            Some(last) if last.same_attributes(&run) => last.text.push_str(&run.text),
+            _ => self.runs.push(run),
+        }
+    }
+
+    fn write_linked(&mut self, url: &url::Url, text: &str) {
+        self.flush();
+        self.link = Some(url.as_str().into());
+        self.text.push_str(text);
+        self.flush();
+        self.link = None;
+    }
+}
+
+impl<'a> MarkupWriter for RunsWriter<'a> {
+    fn buf(&mut self) -> &mut String {
+        &mut self.text
+    }
+
+    fn write_escaped(&mut self, text: &str) {
+        self.text.push_str(text);
+    }
+
+    fn write_url(&mut self, url: &url::Url, trailing_slash: bool, in_attr: bool) {
+        super::write_url(
+            &mut self.text,
+            url,
+            trailing_slash,
+            in_attr,
+            |b, s| Ok(b.push_str(s)),
+            |b, s| Ok(b.push_str(s)),
+        )
+        .unwrap()
+    }
+
+    fn stack_preorder(&mut self, stack: &[FormatCmd]) {
+        self.flush();
+        self.stack.extend_from_slice(stack);
+    }
+
+    fn stack_postorder(&mut self, stack: &[FormatCmd]) {
+        self.flush();
+        let len = self.stack.len().saturating_sub(stack.len());
+        self.stack.truncate(len);
+    }
+
+    fn write_link(&mut self, _: &str, link: &Link, _: &str, _: &str, _: bool) {
+        match link {
+            Link::Url {
+                url,
+                trailing_slash,
+            } if super::allow_url_scheme(url.scheme()) => {
+                let text = super::url_text(url, *trailing_slash, false);
+                self.write_linked(url, &text);
+            }
+            Link::Url {
+                url,
+                trailing_slash,
+            } => {
+                warn!(
+                    "refusing to render url anchor for scheme {} on url {}",
+                    url.scheme(),
+                    url
+                );
+                self.write_url(url, *trailing_slash, false);
+            }
+            Link::Id { id, url } => self.write_linked(url, id),
+        }
+    }
+}
+
+#[test]
+fn test_output_runs() {
+    use super::{InlineElement, Markup};
+    use csl::{FontStyle, Formatting, VerticalAlignment};
+    let italic = Formatting {
+        font_style: Some(FontStyle::Italic),
+        ..Default::default()
+    };
+    let sup = Formatting {
+        vertical_alignment: Some(VerticalAlignment::Superscript),
+        ..Default::default()
+    };
+    let build = vec![
+        InlineElement::Text("Smith, ".into()),
+        InlineElement::Formatted(vec![InlineElement::Text("Title".into())], italic),
+        InlineElement::Formatted(vec![InlineElement::Text("2".into())], sup),
+        InlineElement::Text(" (".into()),
+        InlineElement::Text("2000)".into()),
+    ];
+    let runs = Markup::html().output_runs(build, false);
+    let run = |text: &str| TextRun {
+        text: text.into(),
+        ..Default::default()
+    };
+    assert_eq!(
+        runs,
+        vec![
+            run("Smith, "),
+            TextRun {
+                italic: true,
+                ..run("Title")
+            },
+            TextRun {
+                superscript: true,
+                ..run("2")
+            },
+            run(" (2000)"),
+        ]
+    );
+    assert_eq!(
+        serde_json::to_string(&runs[1]).unwrap(),
+        r#"{"text":"Title","italic":true}"#
+    );
+}
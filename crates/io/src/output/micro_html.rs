@@ -84,6 +84,137 @@ pub fn micro_html_to_string(fragment: &str, options: &IngestOptions) -> String {
     res.unwrap_or_default()
 }
 
+/// A field's rich text, as the renderer sees it.
+pub type RichText = Vec<MicroNode>;
+
+/// Parses a field's rich text with the same parser used when rendering references, with default
+/// [IngestOptions]. Unsupported markup is dropped rather than reported; see [validate].
+pub fn parse(field: &str) -> RichText {
+    MicroNode::parse(field, &IngestOptions::default())
+}
+
+/// A problem with the markup in a field, found by [validate]. Offsets are byte offsets of the
+/// offending tag in the field.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum MarkupError {
+    /// A tag other than `<i>`, `<b>`, `<sup>`, `<sub>` and `<span>`. The renderer ignores the tag
+    /// but keeps its contents.
+    UnknownTag { name: String, offset: usize },
+    /// A `<span>` without exactly one of `class="nocase"`, `class="nodecor"` or
+    /// `style="font-variant:small-caps;"`. The renderer drops it along with its contents.
+    UnknownSpan { offset: usize },
+    /// An opening tag that is never closed.
+    UnclosedTag { name: String, offset: usize },
+    /// A closing tag with no matching opening tag.
+    UnexpectedClosingTag { name: String, offset: usize },
+}
+
+/// Checks a field's markup, reporting everything that [parse] would silently repair or drop. The
+/// tags are checked as the same parser reads them, so this and [parse] see the same markup.
+pub fn validate(field: &str) -> Result<(), Vec<MarkupError>> {
+    let errors = TagParser::new(field).errors;
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+#[test]
+fn test_validate() {
+    assert_eq!(
+        validate(r#"<i>An</i> <span class="nocase">iPod</span> &lt; 5"#),
+        Ok(())
+    );
+    assert_eq!(validate("a < b <!-- <i> -->"), Ok(()));
+    assert_eq!(
+        validate("<i><b>x</i></b><u>y</u>"),
+        Err(vec![
+            MarkupError::UnclosedTag {
+                name: "b".into(),
+                offset: 3
+            },
+            MarkupError::UnexpectedClosingTag {
+                name: "b".into(),
+                offset: 11
+            },
+            MarkupError::UnknownTag {
+                name: "u".into(),
+                offset: 15
+            },
+        ])
+    );
+    assert_eq!(
+        validate(r#"<span class="x">y</span><sup>2"#),
+        Err(vec![
+            MarkupError::UnknownSpan { offset: 0 },
+            MarkupError::UnclosedTag {
+                name: "sup".into(),
+                offset: 24
+            },
+        ])
+    );
+}
+
+#[test]
+fn validate_agrees_with_parse() {
+    use FormatCmd::*;
+    use MicroNode::*;
+    let unknown = "<u>y</u>";
+    assert_eq!(parse(unknown), vec![Text("y".into())]);
+    assert_eq!(
+        validate(unknown),
+        Err(vec![MarkupError::UnknownTag {
+            name: "u".into(),
+            offset: 0
+        }])
+    );
+    let unknown_span = r#"<span class="x">y</span>"#;
+    assert_eq!(parse(unknown_span), vec![]);
+    assert_eq!(
+        validate(unknown_span),
+        Err(vec![MarkupError::UnknownSpan { offset: 0 }])
+    );
+    // Only class and style count, as in the renderer
+    let extra_attr = r#"<span class="nocase" id="x">y</span>"#;
+    assert_eq!(parse(extra_attr), vec![NoCase(vec![Text("y".into())])]);
+    assert_eq!(validate(extra_attr), Ok(()));
+    let unclosed = "<i>x";
+    assert_eq!(
+        parse(unclosed),
+        vec![Formatted(vec![Text("x".into())], FontStyleItalic)]
+    );
+    assert_eq!(
+        validate(unclosed),
+        Err(vec![MarkupError::UnclosedTag {
+            name: "i".into(),
+            offset: 0
+        }])
+    );
+    // The <b> is closed along with the <i>, and the </b> left over
+    let misnested = "<i><b>x</i></b>";
+    assert_eq!(
+        parse(misnested),
+        vec![Formatted(
+            vec![Formatted(vec![Text("x".into())], FontWeightBold)],
+            FontStyleItalic
+        )]
+    );
+    assert_eq!(
+        validate(misnested),
+        Err(vec![
+            MarkupError::UnclosedTag {
+                name: "b".into(),
+                offset: 3
+            },
+            MarkupError::UnexpectedClosingTag {
+                name: "b".into(),
+                offset: 11
+            },
+        ])
+    );
+}
+
 struct PlainHtmlReader {
     options: IngestOptions,
 }
@@ -174,27 +305,28 @@ fn test_sanitize() {
 //
 // https://github.com/Trangar/html_sanitizer/blob/master/src/lib.rs
 
-use html5ever::driver::ParseOpts;
-use html5ever::interface::QualName;
-use html5ever::tendril::TendrilSink;
-use html5ever::tree_builder::TreeBuilderOpts;
-use html5ever::{local_name, parse_fragment, Namespace};
+use html5ever::interface::{Attribute, QualName};
+use html5ever::tendril::StrTendril;
+use html5ever::tokenizer::{
+    BufferQueue, TagKind, Token, TokenSink, TokenSinkResult, Tokenizer, TokenizerOpts,
+    TokenizerResult,
+};
+use html5ever::tree_builder::{create_element, TreeBuilder, TreeBuilderOpts};
+use html5ever::{local_name, Namespace};
 use markup5ever_rcdom::{Handle, NodeData, RcDom};
 
 struct TagParser {
     dom: RcDom,
+    /// What [validate] reports. Collected on the way in, as the tree builder repairs misnested
+    /// and unclosed tags without saying so.
+    errors: Vec<MarkupError>,
 }
 
-use stringreader::StringReader;
-
 impl<'input> TagParser {
     fn new(input: &'input str) -> Self {
-        let opts = ParseOpts {
-            tree_builder: TreeBuilderOpts {
-                drop_doctype: true,
-                scripting_enabled: false,
-                ..Default::default()
-            },
+        let opts = TreeBuilderOpts {
+            drop_doctype: true,
+            scripting_enabled: false,
             ..Default::default()
         };
         let html_p = QualName::new(
@@ -202,14 +334,43 @@ impl<'input> TagParser {
             Namespace::from("http://www.w3.org/1999/xhtml"),
             local_name!("p"),
         );
-        let mut reader = StringReader::new(input);
-        let dom = parse_fragment(RcDom::default(), opts, html_p, vec![])
-            .from_utf8()
-            .read_from(&mut reader)
-            .unwrap();
-        // println!("Errors: {:?}", dom.errors);
-
-        TagParser { dom }
+        let mut dom = RcDom::default();
+        let context = create_element(&mut dom, html_p, vec![]);
+        let builder = TreeBuilder::new_for_fragment(dom, context, None, opts);
+        let tokenizer_opts = TokenizerOpts {
+            initial_state: Some(builder.tokenizer_state_for_context_elem()),
+            ..Default::default()
+        };
+        let checker = MarkupChecker {
+            builder,
+            input,
+            fed: 0,
+            open: Vec::new(),
+            errors: Vec::new(),
+        };
+        let mut tokenizer = Tokenizer::new(checker, tokenizer_opts);
+        let mut queue = BufferQueue::new();
+        // Fed up to one `>` at a time, so each tag comes out knowing where in `input` it ends
+        while tokenizer.sink.fed < input.len() {
+            let fed = tokenizer.sink.fed;
+            let end = input[fed..]
+                .find('>')
+                .map_or(input.len(), |gt| fed + gt + 1);
+            tokenizer.sink.fed = end;
+            queue.push_back(StrTendril::from_slice(&input[fed..end]));
+            while let TokenizerResult::Script(_) = tokenizer.feed(&mut queue) {}
+        }
+        tokenizer.end();
+        let mut checker = tokenizer.sink;
+        for (name, offset) in checker.open.drain(..) {
+            checker
+                .errors
+                .push(MarkupError::UnclosedTag { name, offset });
+        }
+        TagParser {
+            dom: checker.builder.sink,
+            errors: checker.errors,
+        }
     }
 
     fn internal_walk_micro<T, R>(handle: &Handle, callbacks: &R) -> Vec<T>
@@ -291,6 +452,91 @@ impl<'input> TagParser {
     }
 }
 
+/// Passes the tokens on to the tree builder, keeping track of the tags as written.
+struct MarkupChecker<'input> {
+    builder: TreeBuilder<Handle, RcDom>,
+    input: &'input str,
+    /// How much of `input` the tokenizer has been given.
+    fed: usize,
+    open: Vec<(String, usize)>,
+    errors: Vec<MarkupError>,
+}
+
+impl MarkupChecker<'_> {
+    fn check(&mut self, tag: &html5ever::tokenizer::Tag) {
+        // The tag ends at the last `>` fed
+        let offset = self.input[..self.fed].rfind('<').unwrap_or(0);
+        let name: String = (&*tag.name).into();
+        let known = match name.as_str() {
+            "i" | "b" | "sup" | "sub" | "span" => true,
+            _ => false,
+        };
+        match tag.kind {
+            TagKind::EndTag if !known => {}
+            TagKind::EndTag => match self.open.iter().rposition(|(n, _)| *n == name) {
+                Some(ix) => {
+                    for (name, offset) in self.open.drain(ix + 1..) {
+                        self.errors.push(MarkupError::UnclosedTag { name, offset });
+                    }
+                    self.open.pop();
+                }
+                None => self
+                    .errors
+                    .push(MarkupError::UnexpectedClosingTag { name, offset }),
+            },
+            TagKind::StartTag if !known => {
+                self.errors.push(MarkupError::UnknownTag { name, offset });
+            }
+            TagKind::StartTag => {
+                if name == "span" && !is_supported_span(&tag.attrs) {
+                    self.errors.push(MarkupError::UnknownSpan { offset });
+                }
+                if !tag.self_closing {
+                    self.open.push((name, offset));
+                }
+            }
+        }
+    }
+}
+
+impl TokenSink for MarkupChecker<'_> {
+    type Handle = Handle;
+
+    fn process_token(&mut self, token: Token, line_number: u64) -> TokenSinkResult<Handle> {
+        if let Token::TagToken(ref tag) = token {
+            self.check(tag);
+        }
+        self.builder.process_token(token, line_number)
+    }
+
+    fn end(&mut self) {
+        self.builder.end()
+    }
+
+    fn adjusted_current_node_present_but_not_in_html_namespace(&self) -> bool {
+        self.builder
+            .adjusted_current_node_present_but_not_in_html_namespace()
+    }
+}
+
+/// The readers only look at `class` and `style` on a span, so neither does this.
+fn is_supported_span(attrs: &[Attribute]) -> bool {
+    let mut kept = attrs
+        .iter()
+        .filter(|attr| &*attr.name.local == "class" || &*attr.name.local == "style");
+    let attr = match (kept.next(), kept.next()) {
+        (Some(attr), None) => attr,
+        _ => return false,
+    };
+    match (&*attr.name.local, &*attr.value) {
+        ("style", "font-variant:small-caps;")
+        | ("style", "font-variant: small-caps;")
+        | ("class", "nocase")
+        | ("class", "nodecor") => true,
+        _ => false,
+    }
+}
+
 /// Represents a single HTML node. You can read the `name` and `attrs` properties to figure out what tag you're sanitizing.
 ///
 /// By default all html nodes will be printed, but attributes will be stripped from a tag unless they are added with `allow_attribute` and `allow_attributes`.
@@ -57,18 +57,19 @@ impl LocalizedQuotes {
         }
     }
 
+    /// Reads the four quote terms from a locale. Any that are missing (e.g. in a custom locale
+    /// that was not merged over `en-US`) fall back to the English curly quotes.
     pub fn from_locale(locale: &Locale) -> Self {
-        let getter = |qt: QuoteTerm| {
+        let getter = |qt: QuoteTerm, default: &'static str| {
             locale
                 .simple_terms
                 .get(&SimpleTermSelector::Quote(qt))
-                .unwrap()
-                .singular()
+                .map_or(default, |term| term.singular())
         };
-        let open_outer = getter(QuoteTerm::OpenQuote);
-        let close_outer = getter(QuoteTerm::CloseQuote);
-        let open_inner = getter(QuoteTerm::OpenInnerQuote);
-        let close_inner = getter(QuoteTerm::CloseInnerQuote);
+        let open_outer = getter(QuoteTerm::OpenQuote, "\u{201C}");
+        let close_outer = getter(QuoteTerm::CloseQuote, "\u{201D}");
+        let open_inner = getter(QuoteTerm::OpenInnerQuote, "\u{2018}");
+        let close_inner = getter(QuoteTerm::CloseInnerQuote, "\u{2019}");
         LocalizedQuotes {
             outer: (Atom::from(open_outer), Atom::from(close_outer)),
             inner: (Atom::from(open_inner), Atom::from(close_inner)),
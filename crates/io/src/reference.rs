@@ -30,6 +30,11 @@ pub struct Reference {
     pub number: FnvHashMap<NumberVariable, NumberLike>,
     pub name: FnvHashMap<NameVariable, Vec<Name>>,
     pub date: FnvHashMap<DateVariable, DateOrRange>,
+
+    /// Strings to sort by in place of a variable's value, from CSL-JSON `sort-keys` (and
+    /// `title-sort`, for the title). Leading articles are not stripped from these. Only ordinary
+    /// variables can have one.
+    pub sort_keys: FnvHashMap<Variable, String>,
 }
 
 impl Reference {
@@ -42,6 +47,7 @@ impl Reference {
             number: FnvHashMap::default(),
             name: FnvHashMap::default(),
             date: FnvHashMap::default(),
+            sort_keys: FnvHashMap::default(),
         }
     }
 }
@@ -2,9 +2,54 @@ use crate::output::markup::InlineElement;
 use crate::output::micro_html::MicroNode;
 use crate::output::LocalizedQuotes;
 use crate::{lazy, IngestOptions, SmartCow, String};
-use csl::{FontVariant, TextCase, VerticalAlignment};
+use csl::{FontVariant, IsoLang, Lang, TextCase, VerticalAlignment};
+use fnv::FnvHashMap;
+use std::sync::Arc;
 use unic_segment::{GraphemeIndices, WordBoundIndices, Words};
 
+/// Words that text-case transforms must leave spelled exactly as given, like "mRNA", "iOS" or
+/// "LaTeX". Title case, sentence case and the capitalize-* forms all consult it;
+/// lowercase and uppercase do not.
+///
+/// Each entry is a single word. Matching is case-insensitive, so "IOS" in an all-caps title still
+/// comes out as "iOS".
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CaseExceptions {
+    words: Arc<FnvHashMap<String, String>>,
+}
+
+impl CaseExceptions {
+    pub fn new<I, S>(words: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let words = words
+            .into_iter()
+            .map(|word| String::from(word.as_ref().trim()))
+            .filter(|word| !word.is_empty())
+            .map(|word| (String::from(word.to_lowercase()), word))
+            .collect();
+        CaseExceptions {
+            words: Arc::new(words),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// The preferred spelling of `word`, if it is an exception.
+    pub fn get(&self, word: &str) -> Option<&str> {
+        if self.words.is_empty() {
+            return None;
+        }
+        self.words
+            .get(word.to_lowercase().as_str())
+            .map(|w| w.as_str())
+    }
+}
+
 // from the unic_segment example code
 fn has_alphanumeric(s: &&str) -> bool {
     is_word(*s)
@@ -37,8 +82,71 @@ where
     }
 }
 
-fn transform_uppercase_first(word: &str) -> SmartCow {
-    transform_first_char_of_word(word, |c| c.to_uppercase())
+/// Casing rules that depend on the language of the text, where the Unicode default mappings
+/// are wrong.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CaseLanguage {
+    /// Turkish and Azerbaijani, where `i` uppercases to `İ` and `I` lowercases to `ı`.
+    Turkic,
+    /// Dutch, where a word starting with the digraph `ij` is capitalized as `IJ`.
+    Dutch,
+    /// Unicode default case mappings.
+    Other,
+}
+
+impl Default for CaseLanguage {
+    fn default() -> Self {
+        CaseLanguage::Other
+    }
+}
+
+impl CaseLanguage {
+    pub fn from_lang(lang: &Lang) -> Self {
+        match lang {
+            Lang::Iso(IsoLang::Other(code), _) => match code.as_str() {
+                "tr" | "tur" | "az" | "aze" => CaseLanguage::Turkic,
+                "nl" | "nld" => CaseLanguage::Dutch,
+                _ => CaseLanguage::Other,
+            },
+            _ => CaseLanguage::Other,
+        }
+    }
+}
+
+fn transform_uppercase_first(word: &str, lang: CaseLanguage) -> SmartCow {
+    let (upper, rest) = match lang {
+        CaseLanguage::Dutch if word.starts_with("ij") || word.starts_with("Ij") => {
+            ("IJ", &word[2..])
+        }
+        CaseLanguage::Turkic if word.starts_with('i') => ("İ", &word[1..]),
+        _ => return transform_first_char_of_word(word, |c| c.to_uppercase()),
+    };
+    let mut s = String::from(upper);
+    s.push_str(rest);
+    SmartCow::Owned(s)
+}
+
+/// The char-by-char mapping can't do Greek final sigma, which depends on its position in the
+/// word, or the Turkic dotted and dotless i.
+fn needs_contextual_lowercase(s: &str, lang: CaseLanguage) -> bool {
+    s.contains('Σ') || (lang == CaseLanguage::Turkic && s.contains(|c: char| c == 'I' || c == 'İ'))
+}
+
+fn contextual_lowercase(s: &str, lang: CaseLanguage) -> String {
+    if lang == CaseLanguage::Turkic {
+        String::from(s.replace('I', "ı").replace('İ', "i").to_lowercase())
+    } else {
+        // std's to_lowercase knows about final sigma
+        String::from(s.to_lowercase())
+    }
+}
+
+fn lowercase(s: &str, lang: CaseLanguage) -> SmartCow {
+    if needs_contextual_lowercase(s, lang) {
+        SmartCow::Owned(contextual_lowercase(s, lang))
+    } else {
+        lazy::lazy_lowercase(s)
+    }
 }
 
 // use phf::phf_set;
@@ -220,14 +328,19 @@ fn is_stopword(word_and_rest: &str) -> Option<usize> {
     stopword_regex().find(word_and_rest).map(|mat| mat.end())
 }
 
-fn upper_word_to_title(word: &str) -> Option<String> {
+fn upper_word_to_title(word: &str, lang: CaseLanguage) -> Option<String> {
+    if lang == CaseLanguage::Dutch && word.starts_with("IJ") {
+        let mut ret = String::from("IJ");
+        ret.push_str(&lowercase(&word[2..], lang));
+        return Some(ret);
+    }
     let mut upper_gs = GraphemeIndices::new(word);
     if let Some((_, first_g)) = upper_gs.next() {
         let mut ret = String::new();
         ret.push_str(first_g);
         if let Some((rest_ix, _)) = upper_gs.next() {
             let rest = &word[rest_ix..];
-            let rest_lower = lazy::lazy_lowercase(rest);
+            let rest_lower = lowercase(rest, lang);
             ret.push_str(rest_lower.as_ref());
         }
         return Some(ret);
@@ -235,23 +348,31 @@ fn upper_word_to_title(word: &str) -> Option<String> {
     None
 }
 
-fn transform_sentence_case(s: String, seen_one: bool, is_last: bool, is_uppercase: bool) -> String {
+fn transform_sentence_case(
+    s: String,
+    seen_one: bool,
+    is_last: bool,
+    is_uppercase: bool,
+    exceptions: &CaseExceptions,
+    lang: CaseLanguage,
+) -> String {
     if is_uppercase {
         transform_each_word(
             &s,
             seen_one,
             is_last,
+            exceptions,
             |word, _word_and_rest, is_first, _no_stop| {
                 if is_first {
-                    if let Some(upper) = upper_word_to_title(word) {
+                    if let Some(upper) = upper_word_to_title(word, lang) {
                         return (SmartCow::Owned(upper), None);
                     }
                 }
-                (lazy::lazy_lowercase(word), None)
+                (lowercase(word, lang), None)
             },
         )
     } else {
-        transform_first_word(s, transform_uppercase_first)
+        transform_first_word(s, exceptions, |word| transform_uppercase_first(word, lang))
     }
 }
 
@@ -285,7 +406,7 @@ fn title_case_word<'a>(
         return (SmartCow::Borrowed(word), None);
     }
     if entire_is_uppercase {
-        if let Some(ret) = upper_word_to_title(word) {
+        if let Some(ret) = upper_word_to_title(word, CaseLanguage::Other) {
             return (SmartCow::Owned(ret), None);
         }
     }
@@ -295,18 +416,30 @@ fn title_case_word<'a>(
     )
 }
 
-fn transform_title_case(s: &str, seen_one: bool, is_last: bool) -> String {
+fn transform_title_case(
+    s: &str,
+    seen_one: bool,
+    is_last: bool,
+    exceptions: &CaseExceptions,
+) -> String {
     transform_each_word(
         &s,
         seen_one,
         is_last,
+        exceptions,
         |word, word_and_rest, _is_first, no_stop| {
             title_case_word(word, word_and_rest, false, no_stop)
         },
     )
 }
 
-fn transform_each_word<'a, F>(mut s: &'a str, seen_one: bool, is_last: bool, transform: F) -> String
+fn transform_each_word<'a, F>(
+    mut s: &'a str,
+    seen_one: bool,
+    is_last: bool,
+    exceptions: &CaseExceptions,
+    transform: F,
+) -> String
 where
     F: Fn(&'a str, &'a str, bool, bool) -> (SmartCow<'a>, Option<usize>),
 {
@@ -336,6 +469,11 @@ where
             let is_last = is_last && (rest.is_empty() || !is_word(rest));
             let no_stopword = is_first || is_last || follows_colon;
             let word = substr;
+            if let Some(exception) = exceptions.get(word) {
+                acc.push_str(exception);
+                is_first = false;
+                continue;
+            }
             let (tx, fast_forward) = transform(word, &s[ix..], is_first, no_stopword);
             acc.push_str(&tx);
             if let Some(ff) = fast_forward {
@@ -351,11 +489,18 @@ where
     acc
 }
 
-fn transform_first_word<'a>(s: String, transform: impl Fn(&str) -> SmartCow) -> String {
+fn transform_first_word<'a>(
+    s: String,
+    exceptions: &CaseExceptions,
+    transform: impl Fn(&str) -> SmartCow,
+) -> String {
     let mut bounds = WordBoundIndices::new(&s);
     while let Some((ix, bound)) = bounds.next() {
         if is_word(bound) {
-            let tx = transform(bound);
+            let tx = match exceptions.get(bound) {
+                Some(exception) => SmartCow::Borrowed(exception),
+                None => transform(bound),
+            };
             if tx.as_ref() != bound {
                 let mut ret = String::new();
                 ret.push_str(&s[..ix]);
@@ -501,20 +646,38 @@ impl IngestOptions {
         is_last: bool,
         entire_is_uppercase: bool,
     ) -> String {
+        let lang = self.case_language;
         match self.text_case {
+            TextCase::Lowercase if needs_contextual_lowercase(&s, lang) => {
+                contextual_lowercase(&s, lang)
+            }
             TextCase::Lowercase => lazy::lazy_lowercase_owned(s),
-            TextCase::Uppercase => lazy::lazy_uppercase_owned(s),
-            TextCase::CapitalizeFirst => transform_first_word(s, transform_uppercase_first),
-            TextCase::Sentence if !seen_one => {
-                transform_sentence_case(s, seen_one, is_last, entire_is_uppercase)
+            TextCase::Uppercase if lang == CaseLanguage::Turkic && s.contains('i') => {
+                String::from(s.replace('i', "İ").to_uppercase())
             }
+            TextCase::Uppercase => lazy::lazy_uppercase_owned(s),
+            TextCase::CapitalizeFirst => transform_first_word(s, &self.case_exceptions, |word| {
+                transform_uppercase_first(word, lang)
+            }),
+            TextCase::Sentence if !seen_one => transform_sentence_case(
+                s,
+                seen_one,
+                is_last,
+                entire_is_uppercase,
+                &self.case_exceptions,
+                lang,
+            ),
             // Fallback is nothing
-            TextCase::Title if self.is_english => transform_title_case(&s, seen_one, is_last),
-            TextCase::CapitalizeAll => {
-                transform_each_word(&s, seen_one, is_last, |word, _, _, _| {
-                    (transform_uppercase_first(word), None)
-                })
+            TextCase::Title if self.is_english => {
+                transform_title_case(&s, seen_one, is_last, &self.case_exceptions)
             }
+            TextCase::CapitalizeAll => transform_each_word(
+                &s,
+                seen_one,
+                is_last,
+                &self.case_exceptions,
+                |word, _, _, _| (transform_uppercase_first(word, lang), None),
+            ),
             TextCase::None | _ => s,
         }
     }
@@ -563,3 +726,58 @@ fn test_any_micros() {
     assert_eq!(upper("HELLOSUPERSCRIPT"), true);
     assert_eq!(upper("HELLO, <sup>SUPERSCRIPT</sup>"), true);
 }
+
+#[test]
+fn test_case_language() {
+    fn tx(text_case: TextCase, case_language: CaseLanguage, s: &str) -> std::string::String {
+        let options = IngestOptions {
+            text_case,
+            case_language,
+            ..Default::default()
+        };
+        let uppercase = !any_lowercase(s);
+        options
+            .transform_case(String::from(s), false, true, uppercase)
+            .to_string()
+    }
+    use CaseLanguage::*;
+    assert_eq!(
+        tx(TextCase::CapitalizeFirst, Turkic, "istanbul"),
+        "İstanbul"
+    );
+    assert_eq!(tx(TextCase::CapitalizeFirst, Other, "istanbul"), "Istanbul");
+    assert_eq!(tx(TextCase::Lowercase, Turkic, "ISPARTA"), "ısparta");
+    assert_eq!(tx(TextCase::Uppercase, Turkic, "izmir"), "İZMİR");
+    assert_eq!(
+        tx(TextCase::CapitalizeFirst, Dutch, "ijsselmeer"),
+        "IJsselmeer"
+    );
+    assert_eq!(tx(TextCase::Sentence, Dutch, "IJSSELMEER"), "IJsselmeer");
+    assert_eq!(
+        tx(TextCase::Sentence, Other, "ΟΔΟΣ ΠΡΟΣ ΤΗΝ ΠΟΛΗ"),
+        "Οδος προς την πολη"
+    );
+    assert_eq!(tx(TextCase::Lowercase, Other, "ΟΔΟΣ"), "οδος");
+}
+
+#[test]
+fn test_case_combining_marks() {
+    fn tx(text_case: TextCase, s: &str) -> std::string::String {
+        let options = IngestOptions {
+            text_case,
+            ..Default::default()
+        };
+        let uppercase = !any_lowercase(s);
+        options
+            .transform_case(String::from(s), false, true, uppercase)
+            .to_string()
+    }
+    // E + COMBINING ACUTE ACCENT stays together whichever way it is cased
+    assert_eq!(tx(TextCase::Sentence, "E\u{301}TUDE"), "E\u{301}tude");
+    assert_eq!(tx(TextCase::Title, "e\u{301}tude"), "E\u{301}tude");
+    assert_eq!(
+        tx(TextCase::CapitalizeFirst, "e\u{301}tude"),
+        "E\u{301}tude"
+    );
+    assert_eq!(tx(TextCase::Sentence, "ØYSTEIN"), "Øystein");
+}
@@ -492,3 +492,18 @@ fn test_edtf_date() {
     let doc = json!({ "id": 1, "issued": "199X" });
     let _refr: Reference = serde_json::from_value(doc).unwrap();
 }
+
+#[test]
+fn sort_keys_only_ordinary() {
+    setup();
+    let doc = json!({
+        "id": 1,
+        "type": "book",
+        "title-sort": "Hobbit",
+        "sort-keys": { "publisher": "Allen", "author": "Tolkien", "issued": "1937" }
+    });
+    let refr: Reference = serde_json::from_value(doc).unwrap();
+    let mut keys: Vec<_> = refr.sort_keys.keys().copied().collect();
+    keys.sort_by_key(|var| var.as_ref().to_owned());
+    assert_eq!(keys, vec![Variable::Publisher, Variable::Title]);
+}
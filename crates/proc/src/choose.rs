@@ -4,11 +4,12 @@
 //
 // Copyright © 2018 Corporation for Digital Scholarship
 
+use crate::jurisdiction::{has_subjurisdictions, jurisdiction_matches};
 use crate::prelude::*;
 
 use crate::ir::ConditionalDisambIR;
 use citeproc_io::DateOrRange;
-use csl::{AnyVariable, DateVariable};
+use csl::{AnyVariable, Context, DateVariable, NameVariable};
 use csl::{Choose, Cond, CondSet, Conditions, CslType, Element, Else, IfThen, Match, Position};
 use std::sync::Arc;
 
@@ -269,15 +270,14 @@ where
             Cond::HasYearOnly(dvar) => checker.has_year_only(*dvar),
             Cond::HasMonthOrSeason(dvar) => checker.has_month_or_season(*dvar),
             Cond::HasDay(dvar) => checker.has_day(*dvar),
-
-            // Not implemented
-            Cond::Context(_)
-            | Cond::IsPlural(_)
-            | Cond::Jurisdiction(_)
-            | Cond::SubJurisdiction(_) => {
-                log::warn!("unimplemented choose condition: {:?}", cond);
-                return None;
-            }
+            Cond::Context(context) => checker.context() == *context,
+            Cond::IsPlural(nvar) => checker.is_plural(*nvar),
+            Cond::Jurisdiction(test) => checker
+                .jurisdiction()
+                .map_or(false, |item| jurisdiction_matches(item, test)),
+            Cond::SubJurisdiction(at_least) => checker
+                .jurisdiction()
+                .map_or(false, |item| has_subjurisdictions(item, *at_least)),
         })
     });
 
@@ -295,6 +295,15 @@ impl CondChecker for UselessCondChecker {
     fn is_numeric(&self, _var: AnyVariable) -> bool {
         false
     }
+    fn is_plural(&self, _var: NameVariable) -> bool {
+        false
+    }
+    fn context(&self) -> Context {
+        Context::Citation
+    }
+    fn jurisdiction(&self) -> Option<&str> {
+        None
+    }
     fn is_disambiguate(&self, _: u32) -> bool {
         false
     }
@@ -324,6 +333,12 @@ pub trait CondChecker {
     /// Note this will be subtly different from `RenderContext::get_ordinary().is_some()`
     fn has_variable(&self, var: AnyVariable) -> bool;
     fn is_numeric(&self, var: AnyVariable) -> bool;
+    /// CSL-M `is-plural`.
+    fn is_plural(&self, var: NameVariable) -> bool;
+    /// Whether we are rendering a citation or a bibliography entry, for CSL-M `context`.
+    fn context(&self) -> Context;
+    /// The reference's CSL-M `jurisdiction`, like `us:ca`.
+    fn jurisdiction(&self) -> Option<&str>;
     /// Count is for references only, so IRs can slowly increase the disamb count and incrementally
     /// enable disambiguate="true" (not technically part of the spec, but seems worthwhile); see
     /// disambiguate_IncrementalExtraText.txt
@@ -334,39 +349,14 @@ pub trait CondChecker {
     fn position(&self) -> Option<Position>;
     fn features(&self) -> &Features;
     fn has_year_only(&self, dvar: DateVariable) -> bool {
-        self.get_date(dvar)
-            .map(|dor| match dor {
-                DateOrRange::Single(d) => d.month == 0 && d.day == 0,
-                DateOrRange::Range(d1, d2) => {
-                    d1.month == 0 && d1.day == 0 && d2.month == 0 && d2.day == 0
-                }
-                _ => false,
-            })
-            .unwrap_or(false)
+        self.get_date(dvar).map_or(false, DateOrRange::is_year_only)
     }
     fn has_month_or_season(&self, dvar: DateVariable) -> bool {
         self.get_date(dvar)
-            .map(|dor| match dor {
-                DateOrRange::Single(d) => d.month != 0,
-                DateOrRange::Range(d1, d2) => {
-                    // XXX: is OR the right operator here?
-                    d1.month != 0 || d2.month != 0
-                }
-                _ => false,
-            })
-            .unwrap_or(false)
+            .map_or(false, DateOrRange::has_month_or_season)
     }
     fn has_day(&self, dvar: DateVariable) -> bool {
-        self.get_date(dvar)
-            .map(|dor| match dor {
-                DateOrRange::Single(d) => d.day != 0,
-                DateOrRange::Range(d1, d2) => {
-                    // XXX: is OR the right operator here?
-                    d1.day != 0 || d2.day != 0
-                }
-                _ => false,
-            })
-            .unwrap_or(false)
+        self.get_date(dvar).map_or(false, DateOrRange::has_day)
     }
     /// This comes from CSL-JSON { circa: true }
     fn is_uncertain_date(&self, dvar: DateVariable) -> bool {
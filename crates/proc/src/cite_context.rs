@@ -8,9 +8,10 @@ use crate::prelude::*;
 
 use super::DisambPass;
 use crate::choose::CondChecker;
+use crate::locator::{split_locator, SubLocator};
 use crate::SmartString;
 use citeproc_io::output::markup::Markup;
-use citeproc_io::{Cite, DateOrRange, Locator, Name, NumericValue, Reference};
+use citeproc_io::{CaseExceptions, Cite, DateOrRange, Locator, Name, NumberLike, NumericValue, Reference};
 use csl::Features;
 use csl::Locale;
 use csl::*;
@@ -18,6 +19,28 @@ use csl::{CslType, LocatorType, Name as NameEl, Position, SortKey, Style, Variab
 use std::borrow::Cow;
 use std::sync::Arc;
 
+/// Which part of the output a [`CiteContext`] is rendering for.
+///
+/// Conditions and macros see this through the context, so a `<choose>` shared between a
+/// citation layout and the bibliography can branch on it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum RenderingContext {
+    Citation,
+    Bibliography,
+    /// The CSL-M `<intext>` element. Conditions treat this like a citation.
+    InText,
+}
+
+impl RenderingContext {
+    /// The CSL-M `context="..."` value this corresponds to.
+    pub fn csl_context(self) -> Context {
+        match self {
+            RenderingContext::Bibliography => Context::Bibliography,
+            RenderingContext::Citation | RenderingContext::InText => Context::Citation,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct CiteContext<
     'c,
@@ -40,7 +63,7 @@ pub struct CiteContext<
     pub cite: &'c Cite<Input>,
     pub bib_number: Option<u32>,
 
-    pub in_bibliography: bool,
+    pub rendering: RenderingContext,
     pub sort_key: Option<SortKey>,
 
     /// It isn't easy to sort by year-suffix. Year-suffix disambiguation requires a representation
@@ -56,6 +79,9 @@ pub struct CiteContext<
     /// et-al-subsequent-* are used. Also, should not be reference-specific, so none of the
     /// normally-dependent variables can be used.)
     pub year_suffix: Option<u32>,
+
+    pub case_exceptions: CaseExceptions,
+    pub compat_mode: CompatMode,
 }
 
 use std::fmt;
@@ -65,7 +91,7 @@ impl<'c, O: OutputFormat, I: OutputFormat> fmt::Debug for CiteContext<'c, O, I>
             .field("ref_id", &AsRef::<str>::as_ref(&self.reference.id))
             .field("position", &self.position)
             .field("bib_number", &self.bib_number)
-            .field("in_bibliography", &self.in_bibliography)
+            .field("rendering", &self.rendering)
             .field("sort_key", &self.sort_key)
             .field("disamb_pass", &self.disamb_pass)
             .field("sort_key", &self.sort_key)
@@ -77,6 +103,9 @@ impl<'c, O: OutputFormat, I: OutputFormat> fmt::Debug for CiteContext<'c, O, I>
 // helper methods to access both cite and reference properties via Variables
 
 impl<'c, O: OutputFormat, I: OutputFormat> CiteContext<'c, O, I> {
+    pub fn in_bibliography(&self) -> bool {
+        self.rendering == RenderingContext::Bibliography
+    }
     pub fn change_format<O2: OutputFormat>(&self, new_fmt: O2) -> CiteContext<'c, O2, I> {
         CiteContext {
             format: new_fmt,
@@ -90,9 +119,21 @@ impl<'c, O: OutputFormat, I: OutputFormat> CiteContext<'c, O, I> {
             position: self.position,
             disamb_pass: self.disamb_pass,
             bib_number: self.bib_number,
-            in_bibliography: self.in_bibliography,
+            rendering: self.rendering,
             sort_key: self.sort_key.clone(),
             year_suffix: self.year_suffix,
+            case_exceptions: self.case_exceptions.clone(),
+            compat_mode: self.compat_mode,
+        }
+    }
+    /// The labelled parts of the locator after the first, like "n. 3" in "12-13, 15 n. 3".
+    pub fn sub_locators(&self) -> Vec<SubLocator<'_>> {
+        match self.cite.locators.as_ref().and_then(|ls| ls.single()) {
+            Some(Locator {
+                locator: NumberLike::Str(s),
+                ..
+            }) => split_locator(s, self.locale).1,
+            _ => Vec::new(),
         }
     }
 }
@@ -126,7 +167,14 @@ impl<'a, O: OutputFormat, I: OutputFormat> RenderContext for CiteContext<'a, O,
                 // For now we'll just ignore any more than the one.
                 .and_then(|ls| ls.single())
                 .map(Locator::value)
-                .map(NumericValue::from_localized(and_term)),
+                .map(|value| match value {
+                    // Only the part the cite's label applies to. See [CiteContext::sub_locators].
+                    NumberLike::Str(s) => {
+                        let (first, _) = split_locator(s, self.locale);
+                        NumericValue::from_localized_str(first, and_term)
+                    }
+                    _ => NumericValue::from_localized(and_term)(value),
+                }),
             NumberVariable::FirstReferenceNoteNumber => self.position.1.map(NumericValue::num),
             NumberVariable::CitationNumber => self.bib_number.map(NumericValue::num),
             NumberVariable::PageFirst => get(NumberVariable::Page).and_then(|pp| pp.page_first()),
@@ -150,17 +198,11 @@ pub trait RenderContext {
     fn get_ordinary(&self, var: Variable, form: VariableForm) -> Option<Cow<'_, str>> {
         let refr = self.reference();
         let get = |v: Variable| refr.ordinary.get(&v).map(|s| s.as_str()).map(Cow::Borrowed);
-        match (var, form) {
-            (Variable::Title, VariableForm::Short) => {
-                get(Variable::TitleShort).or_else(|| get(Variable::Title))
-            }
-            (Variable::ContainerTitleShort, _) => {
-                get(Variable::ContainerTitleShort).or_else(|| get(Variable::JournalAbbreviation))
-            }
-            (Variable::ContainerTitle, VariableForm::Short) => get(Variable::ContainerTitleShort)
-                .or_else(|| get(Variable::JournalAbbreviation))
-                .or_else(|| get(Variable::ContainerTitle)),
-            (Variable::CitationLabel, _) if refr.ordinary.get(&var).is_none() => {
+        if let Some(fallbacks) = var.form_fallbacks(form) {
+            return fallbacks.iter().find_map(|&v| get(v));
+        }
+        match var {
+            Variable::CitationLabel if refr.ordinary.get(&var).is_none() => {
                 let tri = crate::citation_label::Trigraph::default();
                 Some(Cow::Owned(tri.make_label(self.reference())))
             }
@@ -175,6 +217,23 @@ pub trait RenderContext {
             _ => self.reference().name.get(&var).map(|x| x.as_slice()),
         }
     }
+
+    /// `is-numeric` for variables that are not number variables, which are parsed the same way
+    /// when tested.
+    fn ordinary_is_numeric(&self, var: Variable) -> bool {
+        let and_term = self.locale().and_term(None).unwrap_or("and");
+        self.get_ordinary(var, VariableForm::Long)
+            .map_or(false, |value| {
+                let like = NumberLike::Str(value.into_owned());
+                let num = NumericValue::from_localized(and_term)(&like);
+                num.is_numeric()
+            })
+    }
+
+    /// CSL-M `is-plural`: whether a name variable holds more than one name.
+    fn name_is_plural(&self, var: NameVariable) -> bool {
+        self.get_name(var).map_or(false, |names| names.len() > 1)
+    }
 }
 
 // Implemented here privately so we don't use it by mistake.
@@ -220,17 +279,36 @@ where
     ///   not aware of any version numbers that actually are numbers. Semver hyphens, for example,
     ///   are literal hyphens, not number ranges.
     ///   By not representing them as numbers, `is-numeric="version"` won't work.
+    ///
+    /// [CompatMode::CiteprocJs] follows citeproc-js on page-first. Versions are tested on their
+    /// text like any other ordinary variable, in both modes, so `"2"` is numeric and `"2.0.9"` is
+    /// not.
     fn is_numeric(&self, var: AnyVariable) -> bool {
+        if let Some(answer) = self.compat_mode.is_numeric_override(var) {
+            return answer;
+        }
         match var {
             AnyVariable::Number(num) => self
                 .get_number(num)
                 .map(|r| r.is_numeric())
                 .unwrap_or(false),
-
-            // TODO: this isn't very useful
+            AnyVariable::Ordinary(v) => self.ordinary_is_numeric(v),
+            // names and dates are never numeric
             _ => false,
         }
     }
+    fn is_plural(&self, var: NameVariable) -> bool {
+        self.name_is_plural(var)
+    }
+    fn context(&self) -> Context {
+        self.rendering.csl_context()
+    }
+    fn jurisdiction(&self) -> Option<&str> {
+        self.reference
+            .ordinary
+            .get(&Variable::Jurisdiction)
+            .map(|s| s.as_str())
+    }
 
     fn csl_type(&self) -> CslType {
         self.reference.csl_type
@@ -244,11 +322,12 @@ where
     fn get_date(&self, dvar: DateVariable) -> Option<&DateOrRange> {
         self.reference.date.get(&dvar)
     }
+    /// Per the spec, position tests are always false in the bibliography.
     fn position(&self) -> Option<Position> {
-        if self.in_bibliography {
-            return None;
+        match self.rendering {
+            RenderingContext::Bibliography => None,
+            RenderingContext::Citation | RenderingContext::InText => Some(self.position.0),
         }
-        Some(self.position.0)
     }
     fn is_disambiguate(&self, _current_count: u32) -> bool {
         // ignore count as that's for references
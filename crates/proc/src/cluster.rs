@@ -21,16 +21,34 @@ mod layout;
 use layout::DelimKind;
 pub(crate) use layout::WhichStream;
 
+mod spans;
+pub use spans::built_cluster_spans;
+use spans::CiteMarkers;
+
 pub fn built_cluster_before_output(
     db: &dyn IrDatabase,
     cluster_id: ClusterId,
     fmt: &Markup,
+) -> MarkupBuild {
+    build_cluster(db, cluster_id, fmt, false)
+}
+
+fn build_cluster(
+    db: &dyn IrDatabase,
+    cluster_id: ClusterId,
+    fmt: &Markup,
+    mark_cites: bool,
 ) -> MarkupBuild {
     let cite_ids = if let Some(x) = db.cluster_cites_sorted(cluster_id) {
         x
     } else {
         return fmt.plain("");
     };
+    let markers = Some(CiteMarkers::new(db.cluster_cites(cluster_id), fmt)).filter(|_| mark_cites);
+    let mark = |cite_id: CiteId, built: MarkupBuild| match &markers {
+        Some(markers) => markers.wrap(cite_id, built),
+        None => built,
+    };
     let style = db.style();
     let sorted_refs_arc = db.sorted_refs();
     let mut irs: Vec<_> = cite_ids
@@ -87,13 +105,10 @@ pub fn built_cluster_before_output(
         }
     }
 
-    let default_locale = db.default_locale();
+    let locale = db.cluster_locale(cluster_id);
     let citation_delims = layout::LayoutDelimiters::from_citation(&style.citation);
-    let intext_delimiters = layout::LayoutDelimiters::from_intext(
-        style.intext.as_ref(),
-        &style.citation,
-        &default_locale,
-    );
+    let intext_delimiters =
+        layout::LayoutDelimiters::from_intext(style.intext.as_ref(), &style.citation, &locale);
 
     let mut citation_stream = layout::LayoutStream::new(irs.len() * 2, citation_delims, fmt);
     let mut intext_stream = layout::LayoutStream::new(0, intext_delimiters, fmt);
@@ -127,6 +142,7 @@ pub fn built_cluster_before_output(
                         // this is something @fbennett made up specifically for author-only / clusters.
                         .flatten(fmt, None)
                 })
+                .map(|built| mark(cite.cite_id, built))
                 .unwrap_or_else(|| fmt.plain(CLUSTER_NO_PRINTED_FORM))
         });
 
@@ -138,7 +154,7 @@ pub fn built_cluster_before_output(
                 continue;
             }
             _ => {
-                citation_stream.write_flat(cite, None);
+                citation_stream.write_flat(cite, None, &mark);
             }
         }
     }
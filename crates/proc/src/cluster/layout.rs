@@ -86,13 +86,15 @@ impl<'a> LayoutStream<'a> {
         }
     }
 
+    /// `mark` gets a chance to wrap the cite's own output, without its affixes.
     pub(crate) fn write_flat(
         &mut self,
         single: &CiteInCluster<Markup>,
         override_delim_kind: Option<DelimKind>,
+        mark: impl Fn(CiteId, MarkupBuild) -> MarkupBuild,
     ) {
         let (pre, built, suf) = flatten_with_affixes(single, self.fmt);
-        self.write_cite(pre, built, suf);
+        self.write_cite(pre, mark(single.cite_id, built), suf);
         self.write_delim(override_delim_kind.or(single.own_delimiter));
     }
 
@@ -0,0 +1,119 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright © 2021 Corporation for Digital Scholarship
+
+//! Finds out which parts of a cluster's output came from which cite.
+//!
+//! Rather than teach every output writer to track byte offsets, we wrap each cite's rendered
+//! output in a pair of characters from Supplementary Private Use Area-B, which never appear in
+//! real citations, and look for their encoded forms in the final string.
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use citeproc_db::ClusterId;
+
+use crate::prelude::*;
+
+const MARKER_BASE: u32 = 0x10_0000;
+const MAX_MARKED: usize = 0x7FFE;
+
+pub(crate) struct CiteMarkers<'a> {
+    cites: Arc<Vec<CiteId>>,
+    fmt: &'a Markup,
+}
+
+impl<'a> CiteMarkers<'a> {
+    pub(crate) fn new(cites: Arc<Vec<CiteId>>, fmt: &'a Markup) -> Self {
+        CiteMarkers { cites, fmt }
+    }
+
+    /// Surrounds `built` with the markers for `cite_id`, if it rendered anything.
+    pub(crate) fn wrap(&self, cite_id: CiteId, built: MarkupBuild) -> MarkupBuild {
+        let index = self.cites.iter().position(|&c| c == cite_id);
+        match index.and_then(markers) {
+            Some((open, close)) if !built.is_empty() => {
+                let fmt = self.fmt;
+                fmt.seq(vec![
+                    fmt.plain(open.encode_utf8(&mut [0; 4])),
+                    built,
+                    fmt.plain(close.encode_utf8(&mut [0; 4])),
+                ])
+            }
+            _ => built,
+        }
+    }
+}
+
+fn markers(index: usize) -> Option<(char, char)> {
+    if index >= MAX_MARKED {
+        return None;
+    }
+    let open = MARKER_BASE + 2 * index as u32;
+    Some((std::char::from_u32(open)?, std::char::from_u32(open + 1)?))
+}
+
+/// Renders a cluster and returns, alongside the output, the byte range each cite occupies in
+/// it. Each range is tagged with the index of the cite in the cluster as it was inserted. A cite
+/// can have two ranges, e.g. when its author appears in-text as well as in the citation, or none
+/// when it was collapsed away.
+///
+/// The output is identical to [`built_cluster`](crate::db::IrDatabase::built_cluster) except in
+/// rare cases where the markers interfere with punctuation handling, when the ranges are empty.
+pub fn built_cluster_spans(
+    db: &dyn IrDatabase,
+    cluster_id: ClusterId,
+    fmt: &Markup,
+) -> (SmartString, Vec<(usize, Range<usize>)>) {
+    let piq = crate::db::get_piq(db);
+    let plain = fmt.output(super::build_cluster(db, cluster_id, fmt, false), piq);
+    let marked = fmt.output(super::build_cluster(db, cluster_id, fmt, true), piq);
+    if marked == plain {
+        // nothing rendered, or too many cites to mark
+        return (plain, Vec::new());
+    }
+    let count = db.cluster_cites(cluster_id).len().min(MAX_MARKED);
+    let encoded: Vec<(SmartString, SmartString)> = (0..count)
+        .filter_map(markers)
+        .map(|(open, close)| {
+            let enc = |c: char| fmt.output(fmt.plain(c.encode_utf8(&mut [0; 4])), false);
+            (enc(open), enc(close))
+        })
+        .collect();
+
+    let mut stripped = SmartString::new();
+    let mut spans = Vec::new();
+    let mut open_at: Vec<Option<usize>> = vec![None; encoded.len()];
+    let mut remain = marked.as_str();
+    'outer: while !remain.is_empty() {
+        for (index, (open, close)) in encoded.iter().enumerate() {
+            if let Some(rest) = remain.strip_prefix(open.as_str()) {
+                open_at[index] = Some(stripped.len());
+                remain = rest;
+                continue 'outer;
+            }
+            if let Some(rest) = remain.strip_prefix(close.as_str()) {
+                if let Some(start) = open_at[index].take() {
+                    spans.push((index, start..stripped.len()));
+                }
+                remain = rest;
+                continue 'outer;
+            }
+        }
+        let ch = remain.chars().next().unwrap();
+        stripped.push(ch);
+        remain = &remain[ch.len_utf8()..];
+    }
+
+    if stripped != plain {
+        log::warn!(
+            "cite markers changed the output of cluster {:?}; not reporting spans",
+            cluster_id
+        );
+        return (plain, Vec::new());
+    }
+    spans.sort_by_key(|(_, range)| range.start);
+    (plain, spans)
+}
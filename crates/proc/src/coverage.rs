@@ -0,0 +1,228 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright © 2021 Corporation for Digital Scholarship
+
+//! Works out which of a reference's fields a style renders, so users can tell why a field they
+//! filled in never shows up.
+
+use crate::choose::{eval_conditions, CondChecker};
+use crate::disamb::FreeCond;
+use crate::prelude::*;
+use citeproc_io::Reference;
+use csl::visit::{walk_text, Visitor};
+use csl::{
+    AnyVariable, Atom, BodyDate, Choose, IsIndependent, Names, NumberElement, NumberVariable,
+    StandardVariable, Style, TextElement, TextSource, Variable, VariableForm,
+};
+
+/// Which of a reference's fields a style renders. See [variable_coverage].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariableCoverage {
+    pub ref_id: Atom,
+    /// Fields the reference has that the style renders, in the order the style reaches them.
+    pub used: Vec<AnyVariable>,
+    /// Fields the reference has that the style never renders for a reference of its type and
+    /// with its other fields.
+    pub ignored: Vec<AnyVariable>,
+    /// Fields the style would render for this reference, but that the reference does not have.
+    pub missing: Vec<AnyVariable>,
+}
+
+/// Walks the citation, in the first, subsequent and ibid positions, and the bibliography, taking
+/// the branches of `<choose>` that this reference would take, and reports which of its fields
+/// are rendered.
+///
+/// Variables that do not come from the reference, like `locator` and `citation-number`, are left
+/// out. A field counts as used if the style could fall back to it, e.g. `title` is used by
+/// `<text variable="title" form="short" />` whether or not there is a `title-short`.
+pub fn variable_coverage(db: &dyn IrDatabase, refr: &Reference) -> VariableCoverage {
+    let style = db.style();
+    let locale = db.default_locale();
+    let fmt = db.get_formatter();
+    let case_exceptions = db.case_exceptions();
+    let compat_mode = db.compat_mode();
+    let mut read = Vec::new();
+    let mut walk = |fc: FreeCond, location: CiteOrBib| {
+        let ctx = RefContext::from_free_cond(
+            fc,
+            &fmt,
+            &style,
+            &locale,
+            refr,
+            location,
+            case_exceptions.clone(),
+            compat_mode,
+        );
+        let mut walker = CoverageWalker {
+            ctx: &ctx,
+            style: &style,
+            read: std::mem::take(&mut read),
+        };
+        match location {
+            CiteOrBib::Citation => walker.elements(&style.citation.layout.elements),
+            CiteOrBib::Bibliography => match &style.bibliography {
+                Some(bib) => walker.elements(&bib.layout.elements),
+                None => false,
+            },
+        };
+        read = walker.read;
+    };
+    walk(FreeCond::empty(), CiteOrBib::Citation);
+    walk(FreeCond::SUBSEQUENT, CiteOrBib::Citation);
+    walk(FreeCond::IBID, CiteOrBib::Citation);
+    walk(FreeCond::empty(), CiteOrBib::Bibliography);
+
+    let present = reference_fields(refr);
+    let used = read
+        .iter()
+        .filter(|v| present.contains(v))
+        .cloned()
+        .collect();
+    let ignored = present
+        .iter()
+        .filter(|v| !read.contains(v))
+        .cloned()
+        .collect();
+    let missing = read
+        .iter()
+        .filter(|v| !present.contains(v))
+        .cloned()
+        .collect();
+    VariableCoverage {
+        ref_id: refr.id.clone(),
+        used,
+        ignored,
+        missing,
+    }
+}
+
+/// Every field the reference has, sorted by name.
+fn reference_fields(refr: &Reference) -> Vec<AnyVariable> {
+    let mut fields: Vec<AnyVariable> = refr
+        .ordinary
+        .keys()
+        .map(|&v| AnyVariable::Ordinary(v))
+        .chain(refr.number.keys().map(|&v| AnyVariable::Number(v)))
+        .chain(refr.name.keys().map(|&v| AnyVariable::Name(v)))
+        .chain(refr.date.keys().map(|&v| AnyVariable::Date(v)))
+        .collect();
+    fields.sort_by_cached_key(|&v| variable_name(v));
+    fields
+}
+
+fn variable_name(var: AnyVariable) -> String {
+    match var {
+        AnyVariable::Ordinary(v) => v.as_ref().to_owned(),
+        AnyVariable::Number(v) => v.as_ref().to_owned(),
+        AnyVariable::Name(v) => v.as_ref().to_owned(),
+        AnyVariable::Date(v) => v.as_ref().to_owned(),
+    }
+}
+
+struct CoverageWalker<'a> {
+    ctx: &'a RefContext<'a, Markup>,
+    style: &'a Style,
+    read: Vec<AnyVariable>,
+}
+
+impl<'a> CoverageWalker<'a> {
+    fn uses(&mut self, var: AnyVariable) {
+        let from_reference = match var {
+            AnyVariable::Ordinary(Variable::CitationLabel) => false,
+            AnyVariable::Number(NumberVariable::CitationNumber) => false,
+            AnyVariable::Name(csl::NameVariable::Dummy) => false,
+            _ => !var.is_independent(),
+        };
+        if from_reference && !self.read.contains(&var) {
+            self.read.push(var);
+        }
+    }
+
+    fn uses_ordinary(&mut self, var: Variable, form: VariableForm) {
+        match var.form_fallbacks(form) {
+            Some(fallbacks) => {
+                for &var in fallbacks {
+                    self.uses(AnyVariable::Ordinary(var));
+                }
+            }
+            None => self.uses(AnyVariable::Ordinary(var)),
+        }
+    }
+
+    fn uses_number(&mut self, var: NumberVariable) {
+        match var {
+            NumberVariable::PageFirst => self.uses(AnyVariable::Number(NumberVariable::Page)),
+            _ => self.uses(AnyVariable::Number(var)),
+        }
+    }
+}
+
+impl<'a> Visitor<'a> for CoverageWalker<'a> {
+    fn style(&self) -> &'a Style {
+        self.style
+    }
+
+    fn text(&mut self, text: &'a TextElement) -> bool {
+        match text.source {
+            TextSource::Variable(StandardVariable::Ordinary(var), form) => {
+                self.uses_ordinary(var, form)
+            }
+            TextSource::Variable(StandardVariable::Number(var), _) => self.uses_number(var),
+            _ => return walk_text(self, text),
+        }
+        false
+    }
+
+    /// Only the branch this reference would take.
+    fn choose(&mut self, choose: &'a Choose) -> bool {
+        let Choose(iff, elseifs, elsee) = choose;
+        let taken = std::iter::once(iff)
+            .chain(elseifs)
+            .find(|branch| eval_conditions(&branch.0, self.ctx, std::u32::MAX).0);
+        match taken {
+            Some(branch) => self.elements(&branch.1),
+            None => self.elements(&elsee.0),
+        }
+    }
+
+    fn number(&mut self, number: &'a NumberElement) -> bool {
+        self.uses_number(number.variable);
+        false
+    }
+
+    fn names(&mut self, names: &'a Names) -> bool {
+        for &var in &names.variables {
+            self.uses(AnyVariable::Name(var));
+        }
+        let has_names = names
+            .variables
+            .iter()
+            .any(|&var| self.ctx.has_variable(AnyVariable::Name(var)));
+        if has_names {
+            return false;
+        }
+        // Only the first substitute that renders anything is used. Each one's variables are
+        // collected on their own, as `read` may already have them from elsewhere in the style.
+        for el in names.substitute.iter().flat_map(|sub| &sub.0) {
+            let outer = std::mem::take(&mut self.read);
+            self.element(el);
+            let branch = std::mem::replace(&mut self.read, outer);
+            let ctx = self.ctx;
+            let renders = branch.iter().any(|&var| ctx.has_variable(var));
+            for var in branch {
+                self.uses(var);
+            }
+            if renders {
+                break;
+            }
+        }
+        false
+    }
+
+    fn date(&mut self, date: &'a BodyDate) -> bool {
+        self.uses(AnyVariable::Date(date.variable()));
+        false
+    }
+}
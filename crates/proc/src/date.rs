@@ -300,17 +300,17 @@ impl<'a, O: OutputFormat> PartBuilder<'a, O> {
                 if each.is_empty() {
                     return Either::Build(None);
                 }
-                let mut built = fmt.affixed(
-                    fmt.group(each, "", bits.overall_formatting),
-                    bits.overall_affixes.as_ref(),
-                );
-                let options = IngestOptions {
-                    text_case: bits.overall_text_case,
+                // Same as what flattening the IrSeq from upgrade() would do. The display was only
+                // kept if it was going in a bibliography.
+                let seq = RenderSeq {
+                    formatting: bits.overall_formatting,
+                    affixes: bits.overall_affixes.as_ref(),
+                    display: bits.display,
+                    in_bibliography: true,
                     ..Default::default()
                 };
-                if bits.overall_text_case != TextCase::None {
-                    fmt.apply_text_case(&mut built, &options);
-                }
+                let grp = fmt.group(each, "", None);
+                let built = seq.render_cased(fmt, grp, bits.overall_text_case);
                 Either::Build(Some(built))
             }
             PartAccumulator::Seq(seq) => Either::Ir(seq),
@@ -534,8 +534,12 @@ fn build_parts<'c, O: OutputFormat, I: OutputFormat>(
                 ..Default::default()
             };
             let b = fmt.ingest(&literal, &options);
-            let b = fmt.with_format(b, gen_date.overall_formatting);
-            let b = fmt.affixed(b, gen_date.overall_affixes.as_ref());
+            let b = RenderSeq {
+                formatting: gen_date.overall_formatting,
+                affixes: gen_date.overall_affixes.as_ref(),
+                ..Default::default()
+            }
+            .render(fmt, b);
             Some(Either::Build(Some(b)))
         }
     }
@@ -768,8 +772,12 @@ fn dp_render_either<'c, O: OutputFormat, I: OutputFormat>(
                     ..Default::default()
                 };
                 let b = fmt.ingest(&s, &options);
-                let b = fmt.with_format(b, part.formatting);
-                let b = fmt.affixed(b, affixes.as_ref());
+                let b = RenderSeq {
+                    formatting: part.formatting,
+                    affixes: affixes.as_ref(),
+                    ..Default::default()
+                }
+                .render(fmt, b);
                 Either::Build(Some(b))
             }
         })
@@ -14,19 +14,32 @@ use crate::cluster;
 use crate::disamb::names::{replace_single_child, NameDisambPass};
 use crate::disamb::{Dfa, DisambName, DisambNameData, EdgeData, FreeCondSets};
 use crate::prelude::*;
-use crate::sort::BibNumber;
-use crate::{CiteContext, DisambPass, IrState, Proc, IR};
+use crate::sort::{BibNumber, CitationNumbering};
+use crate::warnings::Warning;
+use crate::{CiteContext, DisambPass, EntryDecoration, IrState, NameTermOverrides, Proc, IR};
 use citeproc_db::{CiteData, ClusterData, ClusterId, ClusterNumber, IntraNote};
-use citeproc_io::output::{markup::Markup, OutputFormat};
-use citeproc_io::{Cite, Name, Reference};
+use citeproc_io::output::{
+    markup::{Markup, TextRun},
+    OutputFormat,
+};
+use citeproc_io::{CaseExceptions, Cite, Name, Reference};
+use csl::visit::Visitor;
 use csl::GivenNameDisambiguationRule as GNDR;
-use csl::{Atom, Bibliography, Position, SortKey};
+use csl::{
+    Atom, Bibliography, Locale, Position, SortKey, StandardVariable, TextElement, TextSource,
+    Variable, VariableForm,
+};
 
 use indextree::NodeId;
 
 pub trait ImplementationDetails {
     fn get_formatter(&self) -> Markup;
     fn lookup_cluster_id(&self, symbol: ClusterId) -> Option<SmartString>;
+    /// Called for non-fatal problems found while rendering. Queries are memoized, so each
+    /// problem is only reported when the output it affects is (re)computed.
+    fn report_warning(&self, warning: Warning) {
+        log::warn!("{}", warning);
+    }
 }
 
 // trait ParallelIrDatabase {
@@ -55,7 +68,15 @@ pub trait IrDatabase:
     fn ir_gen2_matching_refs(&self, id: CiteId) -> Arc<Vec<Atom>>;
     fn year_suffixes(&self) -> Arc<FnvHashMap<Atom, u32>>;
     fn year_suffix_for(&self, ref_id: Atom) -> Option<u32>;
+    /// See [crate::disamb::explicit_year_suffix_placement].
+    fn explicit_year_suffix_placement(&self) -> (bool, bool);
     fn ir_fully_disambiguated(&self, key: CiteId) -> Arc<IrGen>;
+    /// Other references that could still have produced this cite's output once every
+    /// disambiguation method has been tried.
+    fn cite_ambiguous_with(&self, key: CiteId) -> Arc<Vec<Atom>>;
+    /// The formatted cluster before it is written out, which is the same for every output
+    /// format. See [built_cluster_for].
+    fn built_cluster_inlines(&self, key: ClusterId) -> Arc<MarkupBuild>;
     fn built_cluster(&self, key: ClusterId) -> Arc<MarkupOutput>;
 
     /// render the `<intext>` element on demand
@@ -107,10 +128,41 @@ pub trait IrDatabase:
     /// referring to this cite's reference. This is None for a [`Position::First`].
     fn cite_position(&self, key: CiteId) -> (Position, Option<u32>);
 
+    /// Renders every cite in one cluster in a given position, whatever the document says. For
+    /// previews, e.g. to show how a cite looks as an ibid.
+    #[salsa::input]
+    fn position_override(&self) -> Option<(ClusterId, Position)>;
+
     #[salsa::invoke(crate::sort::sorted_refs)]
     fn sorted_refs(&self) -> Arc<(Vec<Atom>, FnvHashMap<Atom, BibNumber>)>;
     #[salsa::input]
     fn bibliography_no_sort(&self) -> bool;
+    #[salsa::input]
+    fn citation_numbering(&self) -> CitationNumbering;
+    /// Citation numbers that must not change. See [CitationNumbering].
+    #[salsa::input]
+    fn frozen_citation_numbers(&self) -> Arc<FnvHashMap<Atom, u32>>;
+    /// Processor-level replacements for the "and" and et-al terms. See [NameTermOverrides].
+    #[salsa::input]
+    fn name_term_overrides(&self) -> NameTermOverrides;
+    /// Words the title-casing engine must leave alone. See [CaseExceptions].
+    #[salsa::input]
+    fn case_exceptions(&self) -> CaseExceptions;
+    /// Whether to follow the spec or citeproc-js where they differ. See [CompatMode].
+    #[salsa::input]
+    fn compat_mode(&self) -> CompatMode;
+    /// Whether `issued` sort keys use a reprint's `original-date`. See [ReprintSort].
+    #[salsa::input]
+    fn reprint_sort(&self) -> ReprintSort;
+    /// How many macros one cite may expand before the rest are left out.
+    #[salsa::input]
+    fn max_macro_expansions(&self) -> Option<u32>;
+    /// Host-supplied text around bibliography entries, keyed by reference id. See
+    /// [EntryDecoration].
+    #[salsa::input]
+    fn entry_decorations(&self) -> Arc<FnvHashMap<Atom, EntryDecoration>>;
+    /// So that changing one reference's decoration only reformats that entry.
+    fn entry_decoration(&self, ref_id: Atom) -> Option<EntryDecoration>;
 
     #[salsa::invoke(crate::sort::bib_number)]
     fn bib_number(&self, id: CiteId) -> Option<BibNumber>;
@@ -118,6 +170,19 @@ pub trait IrDatabase:
 
 pub fn safe_default(db: &mut dyn IrDatabase) {
     db.set_bibliography_no_sort_with_durability(false, salsa::Durability::HIGH);
+    db.set_citation_numbering_with_durability(Default::default(), salsa::Durability::HIGH);
+    db.set_frozen_citation_numbers_with_durability(Default::default(), salsa::Durability::MEDIUM);
+    db.set_name_term_overrides_with_durability(Default::default(), salsa::Durability::HIGH);
+    db.set_case_exceptions_with_durability(Default::default(), salsa::Durability::HIGH);
+    db.set_compat_mode_with_durability(Default::default(), salsa::Durability::HIGH);
+    db.set_reprint_sort_with_durability(Default::default(), salsa::Durability::HIGH);
+    db.set_max_macro_expansions_with_durability(None, salsa::Durability::HIGH);
+    db.set_entry_decorations_with_durability(Default::default(), salsa::Durability::MEDIUM);
+    db.set_position_override(None);
+}
+
+fn entry_decoration(db: &dyn IrDatabase, ref_id: Atom) -> Option<EntryDecoration> {
+    db.entry_decorations().get(&ref_id).cloned()
 }
 
 fn all_person_names(db: &dyn IrDatabase) -> Arc<Vec<DisambNameData>> {
@@ -163,6 +228,9 @@ fn all_person_names(db: &dyn IrDatabase) -> Arc<Vec<DisambNameData>> {
 use crate::disamb::create_dfa;
 
 fn ref_dfa(db: &dyn IrDatabase, key: Atom) -> Option<Arc<Dfa>> {
+    if !cfg!(feature = "disambiguation") {
+        return None;
+    }
     if let Some(refr) = db.reference(key) {
         Some(Arc::new(create_dfa::<Markup>(db, &refr)))
     } else {
@@ -189,6 +257,10 @@ fn year_suffix_for(db: &dyn IrDatabase, ref_id: Atom) -> Option<u32> {
     ys.get(&ref_id).cloned()
 }
 
+fn explicit_year_suffix_placement(db: &dyn IrDatabase) -> (bool, bool) {
+    crate::disamb::explicit_year_suffix_placement(&db.style())
+}
+
 /// This deviates from citeproc-js in one important way.
 ///
 /// Since there are no 'groups of ambiguous cites', it is not quite simple
@@ -207,7 +279,7 @@ fn year_suffix_for(db: &dyn IrDatabase, ref_id: Atom) -> Option<u32> {
 fn year_suffixes(db: &dyn IrDatabase) -> Arc<FnvHashMap<Atom, u32>> {
     use fnv::FnvHashSet;
     let style = db.style();
-    if !style.citation.disambiguate_add_year_suffix {
+    if !cfg!(feature = "disambiguation") || !style.citation.disambiguate_add_year_suffix {
         return Arc::new(FnvHashMap::default());
     }
 
@@ -287,7 +359,14 @@ fn year_suffixes(db: &dyn IrDatabase) -> Arc<FnvHashMap<Atom, u32>> {
         for atom in group {
             vec.push(atom);
         }
-        vec.sort_by_key(|ref_id| ref_bib_number(bib_numbers, ref_id));
+        // The group is a hash set, which comes out in an order that depends on everything else
+        // that was inserted into it, so sort on the bibliography number. References not in the
+        // bibliography all share one, and fall back to their ids.
+        vec.sort_by(|a, b| {
+            ref_bib_number(bib_numbers, a)
+                .cmp(&ref_bib_number(bib_numbers, b))
+                .then_with(|| (**a).cmp(&**b))
+        });
         let mut i = 1; // "a" = 1
         for ref_id in &vec {
             if !suffixes.contains_key(ref_id) {
@@ -319,13 +398,19 @@ pub struct IrGen {
     pub(crate) tree: IrTree<Markup>,
     pub(crate) state: IrState,
     pub(crate) used_disambiguate_true: bool,
+    /// The short title was appended by the `disambiguate-add-titles` extension. Reference DFAs
+    /// know nothing about it, so the tree can't be matched against them any more.
+    pub(crate) used_add_title: bool,
     pub(crate) disambiguation_finished: bool,
+    /// What the tree was rendered for, so IR dumps say which layout produced them.
+    pub(crate) rendering: RenderingContext,
 }
 
 use std::fmt;
 impl fmt::Debug for IrGen {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut dbg = f.debug_struct("IrGen");
+        dbg.field("rendering", &self.rendering);
         dbg.field("tree", &self.tree);
         dbg.field("state", &self.state);
         dbg.finish()
@@ -333,12 +418,19 @@ impl fmt::Debug for IrGen {
 }
 
 impl IrGen {
-    pub(crate) fn new(tree: IrTree<Markup>, state: IrState, disambiguation_finished: bool) -> Self {
+    pub(crate) fn new(
+        tree: IrTree<Markup>,
+        state: IrState,
+        disambiguation_finished: bool,
+        rendering: RenderingContext,
+    ) -> Self {
         IrGen {
             tree,
             state,
             used_disambiguate_true: false,
+            used_add_title: false,
             disambiguation_finished,
+            rendering,
         }
     }
     pub(crate) fn tree(&self) -> &IrTree {
@@ -361,7 +453,20 @@ fn ref_not_found(db: &dyn IrDatabase, ref_id: &Atom, log: bool) -> Arc<IrGen> {
         IR::Rendered(Some(CiteEdgeData::Output(db.get_formatter().plain("???")))),
         GroupVars::Plain,
     ));
-    Arc::new(IrGen::new(IrTree::new(root, arena), IrState::new(), true))
+    Arc::new(IrGen::new(
+        IrTree::new(root, arena),
+        IrState::new(),
+        true,
+        RenderingContext::Citation,
+    ))
+}
+
+/// The locale for a cite's terms. That is its cluster's, as bibliography ghosts have no cluster.
+fn cite_locale(db: &dyn IrDatabase, id: CiteId) -> Arc<Locale> {
+    match db.lookup_cite(id) {
+        CiteData::RealCite { cluster, .. } => db.cluster_locale(cluster),
+        CiteData::BibliographyGhost { .. } => db.default_locale(),
+    }
 }
 
 // IR gen0 depends on:
@@ -375,7 +480,7 @@ fn ref_not_found(db: &dyn IrDatabase, ref_id: &Atom, log: bool) -> Arc<IrGen> {
 macro_rules! preamble {
     ($style:ident, $locale:ident, $cite:ident, $refr:ident, $ctx:ident, $db:expr, $id:expr, $pass:expr) => {{
         $style = $db.style();
-        $locale = $db.default_locale();
+        $locale = cite_locale($db, $id);
         // Avoid making bibliography ghosts all depend any positional / note num info
         let cite_stuff = match $db.lookup_cite($id) {
             CiteData::RealCite { cite, .. } => (cite, $db.cite_position($id)),
@@ -402,11 +507,13 @@ macro_rules! preamble {
             style: &$style,
             locale: &$locale,
             bib_number: $db.bib_number($id).map(|x| x.get()),
-            in_bibliography: false,
+            rendering: RenderingContext::Citation,
             names_delimiter,
             name_citation: name_el,
             sort_key: None,
             year_suffix: None,
+            case_exceptions: $db.case_exceptions(),
+            compat_mode: $db.compat_mode(),
         };
     }};
 }
@@ -487,6 +594,10 @@ fn refs_accepting_cite(
     ref_id: &Atom,
     disamb_pass: Option<DisambPass>,
 ) -> Vec<Atom> {
+    if !cfg!(feature = "disambiguation") {
+        // Without the DFAs, every cite is taken to match only its own reference.
+        return vec![ref_id.clone()];
+    }
     // Out of ctx, we need:
     // - cite_id
     // - reference.id
@@ -892,7 +1003,14 @@ fn disambiguate_add_givennames(
     None
 }
 
-fn disambiguate_add_year_suffix(tree: &mut IrTree, ctx: &CiteContext<'_, Markup>, suffix: u32) {
+/// `implicit` is false when only an explicit `<text variable="year-suffix" />` may be filled in,
+/// because the other layout (citation or bibliography) renders year-suffix explicitly.
+fn disambiguate_add_year_suffix(
+    tree: &mut IrTree,
+    ctx: &CiteContext<'_, Markup>,
+    suffix: u32,
+    implicit: bool,
+) {
     // First see if we can do it with an explicit one
     let hooks = tree.tree_ref().list_year_suffix_hooks();
     let mut added_suffix = false;
@@ -915,6 +1033,9 @@ fn disambiguate_add_year_suffix(tree: &mut IrTree, ctx: &CiteContext<'_, Markup>
         tree.recompute_group_vars();
         return;
     }
+    if !implicit {
+        return;
+    }
 
     // Then attempt to do it for the ones that are embedded in date output
     for yid in hooks {
@@ -984,7 +1105,7 @@ fn ir_gen0(db: &dyn IrDatabase, id: CiteId) -> Arc<IrGen> {
     let root = style
         .citation
         .intermediate(db, &mut state, &ctx, &mut arena);
-    let irgen = IrGen::new(IrTree::new(root, arena), state, false);
+    let irgen = IrGen::new(IrTree::new(root, arena), state, false, ctx.rendering);
     log::debug!("ir_gen0: {}", irgen.tree);
     Arc::new(irgen)
 }
@@ -1063,7 +1184,8 @@ impl IrGenCow {
             };
             let cloned = self.to_mut();
             ctx.disamb_pass = Some(DisambPass::AddYearSuffix(year_suffix));
-            disambiguate_add_year_suffix(cloned.tree_mut(), &ctx, year_suffix);
+            let (_, in_bibliography) = db.explicit_year_suffix_placement();
+            disambiguate_add_year_suffix(cloned.tree_mut(), &ctx, year_suffix, !in_bibliography);
             // if it's already unambiguous on names alone, then adding year suffixes is hardly
             // going to improve it. So avoid the cost.
             if !self.disambiguation_finished {
@@ -1080,6 +1202,62 @@ impl IrGenCow {
         }
     }
 
+    /// Development extension (the `disambiguate-add-titles` feature): in a note style, a cite
+    /// still ambiguous with other works by the same author after every other pass gets their
+    /// short title appended, if that tells it apart from all of them.
+    fn disambiguate_add_title(&mut self, db: &dyn IrDatabase, ctx: &mut CiteContext<Markup>) {
+        if ctx.style.class != csl::StyleClass::Note || !ctx.style.features.disambiguate_add_titles {
+            return;
+        }
+        // The earlier passes don't all keep disambiguation_finished up to date.
+        self.update_is_ambiguous(db, ctx);
+        if self.disambiguation_finished {
+            return;
+        }
+        let short_title = |refr: &Reference| {
+            refr.ordinary
+                .get(&Variable::TitleShort)
+                .or_else(|| refr.ordinary.get(&Variable::Title))
+                .cloned()
+        };
+        let own = match short_title(ctx.reference) {
+            Some(title) => title,
+            None => return,
+        };
+        let matching = refs_accepting_cite(
+            db,
+            self.tree_ref(),
+            ctx.cite_id,
+            &ctx.reference.id,
+            ctx.disamb_pass,
+        );
+        let same_title = matching
+            .iter()
+            .filter(|&id| id != &ctx.reference.id)
+            .filter_map(|id| db.reference(id.clone()))
+            .any(|other| short_title(&other).as_ref() == Some(&own));
+        if same_title {
+            return;
+        }
+        let title = Element::Text(TextElement {
+            source: TextSource::Variable(
+                StandardVariable::Ordinary(Variable::Title),
+                VariableForm::Short,
+            ),
+            affixes: Some(Affixes {
+                prefix: add_title_delimiter(ctx.style),
+                suffix: "".into(),
+            }),
+            ..Default::default()
+        });
+        let cloned = self.to_mut();
+        let node = title.intermediate(db, &mut cloned.state, ctx, &mut cloned.tree.arena);
+        cloned.tree.root.append(node, &mut cloned.tree.arena);
+        cloned.tree.recompute_group_vars();
+        cloned.used_add_title = true;
+        cloned.disambiguation_finished = true;
+    }
+
     fn disambiguate_conditionals(&mut self, db: &dyn IrDatabase, ctx: &mut CiteContext<Markup>) {
         if self.disambiguation_finished {
             return;
@@ -1091,6 +1269,17 @@ impl IrGenCow {
     }
 }
 
+/// What goes before a title added by [IrGenCow::disambiguate_add_title]: the delimiter of the
+/// group the citation layout is wrapped in, if it is one, like `<group delimiter=" ">`, or else a
+/// comma.
+fn add_title_delimiter(style: &csl::Style) -> SmartString {
+    match &style.citation.layout.elements[..] {
+        [Element::Group(group)] => group.delimiter.clone(),
+        _ => None,
+    }
+    .unwrap_or_else(|| ", ".into())
+}
+
 /// Starts with ir_gen0, and disambiguates through add_names and add_givenname
 fn ir_gen2_add_given_name(db: &dyn IrDatabase, id: CiteId) -> Arc<IrGen> {
     let style;
@@ -1098,6 +1287,9 @@ fn ir_gen2_add_given_name(db: &dyn IrDatabase, id: CiteId) -> Arc<IrGen> {
     let cite;
     let refr;
     let mut ctx;
+    if !cfg!(feature = "disambiguation") {
+        return db.ir_gen0(id);
+    }
     preamble!(style, locale, cite, refr, ctx, db, id, None);
 
     let mut irgen = IrGenCow::new(db.ir_gen0(id));
@@ -1114,6 +1306,9 @@ fn ir_fully_disambiguated(db: &dyn IrDatabase, id: CiteId) -> Arc<IrGen> {
     let cite;
     let refr;
     let mut ctx;
+    if !cfg!(feature = "disambiguation") {
+        return db.ir_gen0(id);
+    }
     preamble!(style, locale, cite, refr, ctx, db, id, None);
 
     // Start with the given names done.
@@ -1121,18 +1316,37 @@ fn ir_fully_disambiguated(db: &dyn IrDatabase, id: CiteId) -> Arc<IrGen> {
     irgen.disambiguate_add_year_suffix(db, &mut ctx);
     log::debug!("ir_add_year_suffix: {}", irgen.deref().tree);
     irgen.disambiguate_conditionals(db, &mut ctx);
+    irgen.disambiguate_add_title(db, &mut ctx);
     log::debug!("ir_fully_disambiguated: {}", irgen.deref().tree);
     irgen.into_arc()
 }
 
-fn get_piq(db: &dyn IrDatabase) -> bool {
+fn cite_ambiguous_with(db: &dyn IrDatabase, id: CiteId) -> Arc<Vec<Atom>> {
+    let ref_id = id.lookup(db).ref_id.clone();
+    if db.reference(ref_id.clone()).is_none() {
+        return Arc::new(Vec::new());
+    }
+    let gen = db.ir_fully_disambiguated(id);
+    if gen.used_add_title {
+        // Only added when the title sets it apart from every other match
+        return Arc::new(Vec::new());
+    }
+    let mut others = refs_accepting_cite(db, gen.tree_ref(), Some(id), &ref_id, None);
+    others.retain(|k| k != &ref_id);
+    Arc::new(others)
+}
+
+/// Whether punctuation moves inside quotes, for the whole document.
+pub fn get_piq(db: &dyn IrDatabase) -> bool {
     // We pant PIQ to be global in a document, not change within a cluster because one cite
     // decided to use a different language. Use the default locale to get it.
     let default_locale = db.default_locale();
-    default_locale
-        .options_node
-        .punctuation_in_quote
-        .unwrap_or(false)
+    db.compat_mode().punctuation_in_quote(&default_locale)
+}
+
+fn built_cluster_inlines(db: &dyn IrDatabase, cluster_id: ClusterId) -> Arc<MarkupBuild> {
+    let fmt = db.get_formatter();
+    Arc::new(cluster::built_cluster_before_output(db, cluster_id, &fmt))
 }
 
 fn built_cluster(
@@ -1140,11 +1354,34 @@ fn built_cluster(
     cluster_id: ClusterId,
 ) -> Arc<<Markup as OutputFormat>::Output> {
     let fmt = db.get_formatter();
-    let build = cluster::built_cluster_before_output(db, cluster_id, &fmt);
+    let build = (*db.built_cluster_inlines(cluster_id)).clone();
     let string = fmt.output(build, get_piq(db));
     Arc::new(string)
 }
 
+/// Writes out a cluster in a format other than the database's own. Every format shares the
+/// expensive part, [IrDatabase::built_cluster_inlines], so this only costs the final flattening
+/// into a string.
+pub fn built_cluster_for(
+    db: &dyn IrDatabase,
+    cluster_id: ClusterId,
+    fmt: &Markup,
+) -> Arc<<Markup as OutputFormat>::Output> {
+    if *fmt == db.get_formatter() {
+        return db.built_cluster(cluster_id);
+    }
+    let build = (*db.built_cluster_inlines(cluster_id)).clone();
+    Arc::new(fmt.output(build, get_piq(db)))
+}
+
+/// A cluster as [TextRun]s, for hosts that apply formatting themselves. Shares
+/// [IrDatabase::built_cluster_inlines] with every other output format.
+pub fn built_cluster_runs(db: &dyn IrDatabase, cluster_id: ClusterId) -> Vec<TextRun> {
+    let fmt = db.get_formatter();
+    let build = (*db.built_cluster_inlines(cluster_id)).clone();
+    fmt.output_runs(build, get_piq(db))
+}
+
 pub fn built_cluster_preview(
     db: &dyn IrDatabase,
     cluster_id: ClusterId,
@@ -1159,7 +1396,7 @@ pub fn built_cluster_preview(
 pub fn test_preview_unicode_escape_issue_91() {
     use crate::test::{test_style_layout, MockProcessor};
     use citeproc_io::{NumberLike, Reference};
-    use csl::{CslType, NameVariable, NumberVariable, Variable};
+    use csl::{CslType, NameVariable, NumberVariable};
 
     // ugh. this should be easier.
 
@@ -1227,7 +1464,7 @@ pub fn with_cite_context<T>(
     f: impl FnOnce(CiteContext) -> T,
 ) -> Option<T> {
     let style = db.style();
-    let locale = db.default_locale();
+    let locale = cite_locale(db, id);
     let cite = id.lookup(db);
     let refr = db.reference(cite.ref_id.clone())?;
     let (names_delimiter, name_el) = db.name_info_citation();
@@ -1245,11 +1482,13 @@ pub fn with_cite_context<T>(
         style: &style,
         locale: &locale,
         bib_number,
-        in_bibliography: false,
+        rendering: RenderingContext::Citation,
         names_delimiter,
         name_citation: name_el,
         sort_key,
         year_suffix,
+        case_exceptions: db.case_exceptions(),
+        compat_mode: db.compat_mode(),
     };
     Some(f(ctx))
 }
@@ -1288,11 +1527,13 @@ pub fn with_bib_context<T>(
         style: &style,
         locale: &locale,
         bib_number,
-        in_bibliography: true,
+        rendering: RenderingContext::Bibliography,
         names_delimiter,
         name_citation: name_el,
         sort_key,
         year_suffix,
+        case_exceptions: db.case_exceptions(),
+        compat_mode: db.compat_mode(),
     };
     if is_ref_missing {
         ref_missing(bib, ctx, false)
@@ -1304,34 +1545,52 @@ pub fn with_bib_context<T>(
 fn bib_item_gen0(db: &dyn IrDatabase, ref_id: Atom) -> Option<Arc<IrGen>> {
     let sorted_refs_arc = db.sorted_refs();
     let (_keys, citation_numbers_by_id) = &*sorted_refs_arc;
-    let bib_number = citation_numbers_by_id
-        .get(&ref_id)
-        .expect("sorted_refs should contain a bib_item key")
-        .get();
+    // Not in the bibliography, eg. asked for by ref id without being cited.
+    let bib_number = citation_numbers_by_id.get(&ref_id)?.get();
 
     let refr_arc = db.reference(ref_id.clone());
 
     bib_item_gen0_acontextual(db, ref_id, refr_arc.as_deref(), Some(bib_number))
 }
 
-fn format_single_bib_item(ir_gen: Option<&IrGen>, fmt: &Markup, piq: bool) -> SmartString {
+/// `anchor` is the reference id to give the entry an anchor for, if the format asks for one.
+fn format_single_bib_item(
+    anchor: Option<&str>,
+    ir_gen: Option<&IrGen>,
+    decoration: Option<&EntryDecoration>,
+    fmt: &Markup,
+    piq: bool,
+) -> SmartString {
     ir_gen
         .and_then(|ir_gen| {
-            let flat = ir_gen.tree_ref().flatten(&fmt, None)?;
+            let mut flat = ir_gen.tree_ref().flatten(&fmt, None)?;
+            if let Some(decoration) = decoration {
+                flat = decoration.wrap(fmt, flat);
+            }
             let string = fmt.output(flat, piq);
             if string.is_empty() {
                 return None;
             }
-            Some(string)
+            match (fmt, anchor) {
+                // An empty anchor, because the entry may contain display divs, which can't go
+                // inside a span.
+                (Markup::Html(options), Some(ref_id)) if options.entry_anchors => Some(
+                    smart_format!("<a id=\"{}\"></a>{}", crate::entry_anchor(ref_id), string),
+                ),
+                _ => Some(string),
+            }
         })
         .unwrap_or_else(|| CSL_STYLE_ERROR.into())
 }
 
 fn bib_item(db: &dyn IrDatabase, ref_id: Atom) -> Arc<MarkupOutput> {
     let fmt = db.get_formatter();
-    let gen0_arc = db.bib_item_gen0(ref_id);
+    let gen0_arc = db.bib_item_gen0(ref_id.clone());
+    let decoration = db.entry_decoration(ref_id.clone());
     Arc::new(format_single_bib_item(
+        Some(&ref_id),
         gen0_arc.as_deref(),
+        decoration.as_ref(),
         &fmt,
         get_piq(db),
     ))
@@ -1346,8 +1605,141 @@ pub fn bib_item_preview(
     fmt: &Markup,
 ) -> SmartString {
     // Pretend it's the first item in the bibliography
-    let gen0_arc = bib_item_gen0_acontextual(db, ref_id, Some(refr), Some(1));
-    format_single_bib_item(gen0_arc.as_deref(), fmt, get_piq(db))
+    let gen0_arc = bib_item_gen0_acontextual(db, ref_id.clone(), Some(refr), Some(1));
+    let decoration = db.entry_decoration(ref_id.clone());
+    // A preview is not in the document, so there is nothing to link to.
+    format_single_bib_item(
+        None,
+        gen0_arc.as_deref(),
+        decoration.as_ref(),
+        fmt,
+        get_piq(db),
+    )
+}
+
+/// Renders one of the style's macros for a reference on its own, as it would render in a first
+/// cite or in the bibliography. Nothing is disambiguated. `None` if the style has no such macro,
+/// or it renders nothing for this reference.
+pub fn macro_preview(
+    db: &dyn IrDatabase,
+    name: &str,
+    refr: &Reference,
+    context: CiteOrBib,
+    fmt: &Markup,
+) -> Option<SmartString> {
+    if !db.style().macros.contains_key(name) {
+        return None;
+    }
+    let text = Element::Text(TextElement {
+        source: TextSource::Macro(name.into()),
+        ..Default::default()
+    });
+    element_preview(db, &text, refr, context, fmt)
+}
+
+/// Renders a reference's authors as they appear in a first cite, using the `<name>` options of
+/// the first `<names variable="author">` in the citation layout, or just those inherited from
+/// `<citation>` if there is none. Its affixes, formatting and label are left off. With `max`,
+/// at most that many names are shown before "et al." instead of the style's et-al settings.
+pub fn author_list_preview(
+    db: &dyn IrDatabase,
+    refr: &Reference,
+    max: Option<u32>,
+    fmt: &Markup,
+) -> Option<SmartString> {
+    let style = db.style();
+    let found = first_author_names(&style, &style.citation.layout.elements);
+    let mut name = found.and_then(|n| n.name.clone()).unwrap_or_default();
+    if let Some(max) = max {
+        let max = max.max(1);
+        name.et_al_min = Some(max + 1);
+        name.et_al_use_first = Some(max);
+        name.et_al_use_last = Some(false);
+    }
+    let names = Element::Names(Arc::new(csl::Names {
+        variables: vec![csl::NameVariable::Author],
+        name: Some(name),
+        et_al: found.and_then(|n| n.et_al.clone()),
+        delimiter: found.and_then(|n| n.delimiter.clone()),
+        ..Default::default()
+    }));
+    element_preview(db, &names, refr, CiteOrBib::Citation, fmt)
+}
+
+/// Looks through macros and every branch of `<choose>`.
+fn first_author_names<'a>(
+    style: &'a csl::Style,
+    elements: &'a [Element],
+) -> Option<&'a csl::Names> {
+    let mut finder = FirstAuthorNames { style, found: None };
+    finder.elements(elements);
+    finder.found
+}
+
+struct FirstAuthorNames<'a> {
+    style: &'a csl::Style,
+    found: Option<&'a csl::Names>,
+}
+
+impl<'a> Visitor<'a> for FirstAuthorNames<'a> {
+    fn style(&self) -> &'a csl::Style {
+        self.style
+    }
+
+    /// Stops at the first, without looking in its substitute.
+    fn names(&mut self, names: &'a csl::Names) -> bool {
+        if names.variables.contains(&csl::NameVariable::Author) {
+            self.found = Some(names);
+        }
+        self.found.is_some()
+    }
+}
+
+/// Renders one element outside any layout, with no disambiguation.
+fn element_preview(
+    db: &dyn IrDatabase,
+    element: &Element,
+    refr: &Reference,
+    context: CiteOrBib,
+    fmt: &Markup,
+) -> Option<SmartString> {
+    let style = db.style();
+    let locale = db.default_locale();
+    let cite = Cite::basic(refr.id.clone());
+    let (rendering, (names_delimiter, name_el)) = match context {
+        CiteOrBib::Citation => (RenderingContext::Citation, db.name_info_citation()),
+        CiteOrBib::Bibliography => (RenderingContext::Bibliography, db.name_info_bibliography()),
+    };
+    let bib_number = db.sorted_refs().1.get(&refr.id).map(|n| n.get());
+    let ctx = CiteContext {
+        reference: refr,
+        format: fmt.clone(),
+        cite_id: None,
+        cite: &cite,
+        position: (Position::First, None),
+        disamb_pass: None,
+        style: &style,
+        locale: &locale,
+        bib_number,
+        rendering,
+        names_delimiter,
+        name_citation: name_el,
+        sort_key: None,
+        year_suffix: None,
+        case_exceptions: db.case_exceptions(),
+        compat_mode: db.compat_mode(),
+    };
+    let mut state = IrState::new();
+    let mut arena = IrArena::new();
+    let root = element.intermediate(db, &mut state, &ctx, &mut arena);
+    let tree = IrTree { root, arena };
+    let flat = tree.tree_ref().flatten(fmt, None)?;
+    let string = fmt.output(flat, get_piq(db));
+    if string.is_empty() {
+        None
+    } else {
+        Some(string)
+    }
 }
 
 fn bib_item_gen0_acontextual(
@@ -1356,6 +1748,9 @@ fn bib_item_gen0_acontextual(
     refr: Option<&Reference>,
     bib_number: Option<u32>,
 ) -> Option<Arc<IrGen>> {
+    if !cfg!(feature = "bibliography") {
+        return None;
+    }
     with_bib_context(
         db,
         ref_id.clone(),
@@ -1375,12 +1770,14 @@ fn bib_item_gen0_acontextual(
             //
             // Can't apply them the first time round, because IR may contain many suffix hooks, and we
             // need to only supply the first appearing explicit one, or the first appearing implicit one.
-            // TODO: comply with the spec where "hook in cite == explicit => no implicit in bib" and "vice
-            // versa"
+            //
+            // Per the spec, an explicit year-suffix in the citation suppresses the implicit one here,
+            // unless the bibliography has its own explicit one, and vice versa.
             log::debug!("bib_ir_gen0: {}", tree);
             if let Some(suffix) = db.year_suffix_for(ref_id.clone()) {
+                let (in_citation, _) = db.explicit_year_suffix_placement();
                 ctx.disamb_pass = Some(DisambPass::AddYearSuffix(suffix));
-                disambiguate_add_year_suffix(&mut tree, &ctx, suffix);
+                disambiguate_add_year_suffix(&mut tree, &ctx, suffix, !in_citation);
                 log::debug!("bib_ir add_year_suffix: {}", tree);
             }
 
@@ -1405,7 +1802,7 @@ fn bib_item_gen0_acontextual(
                 None
             } else {
                 // Disambiguation is over already
-                Some(Arc::new(IrGen::new(tree, state, true)))
+                Some(Arc::new(IrGen::new(tree, state, true, ctx.rendering)))
             }
         },
         |bib, ctx, _just_empty_output| {
@@ -1448,7 +1845,7 @@ fn bib_item_gen0_acontextual(
             transforms::fix_left_right_layout_affixes(tree.root, &mut tree.arena);
 
             // Disambiguation is over already
-            Some(Arc::new(IrGen::new(tree, state, true)))
+            Some(Arc::new(IrGen::new(tree, state, true, ctx.rendering)))
         },
     )
 }
@@ -1466,6 +1863,9 @@ fn first_cite_used_disambiguate_true(db: &dyn IrDatabase, ref_id: Atom) -> bool
 }
 
 fn get_bibliography_map(db: &dyn IrDatabase) -> Arc<FnvHashMap<Atom, Arc<MarkupOutput>>> {
+    if !cfg!(feature = "bibliography") {
+        return Arc::new(FnvHashMap::default());
+    }
     let fmt = db.get_formatter();
     let style = db.style();
     let sorted_refs = db.sorted_refs();
@@ -1697,10 +2097,13 @@ fn cite_positions(db: &dyn IrDatabase) -> Arc<FnvHashMap<CiteId, (Position, Opti
                                 map.insert(cite_id, (Position::FarNote, Some(unsigned)));
                             }
                         }
-                        ClusterNumber::InText(_this_intext) => {
-                            log::warn!("InText should not be sorted after a Note");
-                            // this won't happen; InText is sorted before Note. Nevertheless:
-                            map.insert(cite_id, (Position::First, None));
+                        ClusterNumber::InText(_) => {
+                            // An in-text cite in a document that mixes in-text and note
+                            // clusters. It is subsequent to the note, but it is not in a note
+                            // itself, so it can't be near one.
+                            let unsigned = first_note_number.note_number();
+                            let pos = matching_prev.unwrap_or(Position::FarNote);
+                            map.insert(cite_id, (pos, Some(unsigned)));
                         }
                         ClusterNumber::OutsideFlow => {
                             map.insert(cite_id, (Position::First, None));
@@ -1766,6 +2169,24 @@ fn cite_positions(db: &dyn IrDatabase) -> Arc<FnvHashMap<CiteId, (Position, Opti
         };
     }
 
+    if let Some((cluster_id, position)) = db.position_override() {
+        // An ibid points at the cite just before it, so it is also near-note
+        let position = match position {
+            Position::Ibid => Position::IbidNear,
+            Position::IbidWithLocator => Position::IbidWithLocatorNear,
+            other => other,
+        };
+        for &cite_id in db.cluster_cites(cluster_id).iter() {
+            let entry = map.entry(cite_id).or_insert((Position::First, None));
+            let frnn = if position == Position::First {
+                None
+            } else {
+                entry.1
+            };
+            *entry = (position, frnn);
+        }
+    }
+
     Arc::new(map)
 }
 
@@ -1785,13 +2206,14 @@ fn intext(db: &dyn IrDatabase, id: CiteId) -> Option<Arc<IrGen>> {
         let locale;
         let cite;
         let refr;
-        let ctx;
+        let mut ctx;
         preamble!(style, locale, cite, refr, ctx, db, id, None);
+        ctx.rendering = RenderingContext::InText;
         let mut state = IrState::new();
         let mut arena = IrArena::new();
         let root = intext.intermediate(db, &mut state, &ctx, &mut arena);
         // disambiguation cannot be done on <intext>
-        let irgen = IrGen::new(IrTree::new(root, arena), state, true);
+        let irgen = IrGen::new(IrTree::new(root, arena), state, true, ctx.rendering);
         Arc::new(irgen)
     })
 }
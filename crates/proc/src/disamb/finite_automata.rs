@@ -6,11 +6,11 @@
 
 use crate::db::IrDatabase;
 use citeproc_io::output::{markup::Markup, OutputFormat};
+use indexmap::IndexMap;
 use petgraph::dot::Dot;
 use petgraph::graph::{Graph, NodeIndex};
 use petgraph::visit::EdgeRef;
 use std::collections::BTreeSet;
-use std::collections::HashMap;
 
 // XXX(pandoc): maybe force this to be a string and coerce pandoc output into a string
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -310,12 +310,14 @@ pub fn to_dfa(nfa: &Nfa) -> Dfa {
     }
     work.push((start_set.clone(), dfa_start_node));
 
-    let mut dfa_states = HashMap::new();
+    // Ordered maps throughout, so the same NFA always numbers its DFA nodes the same way. Dfa's
+    // PartialEq compares node indices, and salsa relies on it to avoid recomputing dependents.
+    let mut dfa_states = IndexMap::new();
     dfa_states.insert(start_set, dfa_start_node);
 
     while !work.is_empty() {
         let (dfa_state, current_node) = work.pop().unwrap();
-        let mut by_edge_weight = HashMap::<EdgeData, BTreeSet<NodeIndex>>::new();
+        let mut by_edge_weight = IndexMap::<EdgeData, BTreeSet<NodeIndex>>::new();
         for nfa_node in dfa_state {
             for edge in nfa.graph.edges(nfa_node) {
                 let weight = edge.weight();
@@ -334,7 +336,7 @@ pub fn to_dfa(nfa: &Nfa) -> Dfa {
                 }
             }
         }
-        for (k, mut set) in by_edge_weight.drain() {
+        for (k, mut set) in by_edge_weight.drain(..) {
             epsilon_closure(&nfa.graph, &mut set);
             if !dfa_states.contains_key(&set) {
                 let node = dfa.add_node(());
@@ -551,3 +553,25 @@ fn test_brzozowski_minimise() {
     assert!(dfa.accepts(&[a(), b(), e()]));
     assert!(!dfa.accepts(&[a(), b(), c(), d(), e()]));
 }
+
+#[test]
+fn to_dfa_is_deterministic() {
+    let out = |s: &str| EdgeData::Output(s.into());
+    let nfa = {
+        let mut nfa = Nfa::new();
+        nfa.add_complete_sequence(vec![out("a"), out("b"), out("c")]);
+        nfa.add_complete_sequence(vec![out("a"), out("c")]);
+        nfa.add_complete_sequence(vec![out("b"), out("d"), out("e")]);
+        nfa.add_complete_sequence(vec![out("b"), out("e"), out("f")]);
+        nfa.add_complete_sequence(vec![out("c"), out("f")]);
+        nfa
+    };
+    // Each HashMap gets its own random seed, so repeating this is enough to catch an ordering
+    // that depends on one.
+    let first = to_dfa(&nfa);
+    let first_brz = nfa.clone().brzozowski_minimise();
+    for _ in 0..20 {
+        assert!(to_dfa(&nfa) == first);
+        assert!(nfa.clone().brzozowski_minimise() == first_brz);
+    }
+}
@@ -335,8 +335,6 @@ fn cond_to_frees(c: &Cond) -> Option<(FreeCond, FreeCond)> {
             LocatorType::SubVerbo => (FreeCond::LT_SUBVERBO, FreeCond::LT_SUBVERBO_FALSE),
             LocatorType::Verse => (FreeCond::LT_VERSE, FreeCond::LT_VERSE_FALSE),
             LocatorType::Volume => (FreeCond::LT_VOLUME, FreeCond::LT_VOLUME_FALSE),
-            _ => unimplemented!("CSL-M locator types")
-
             // TODO(CSL-M) enable
             // Article => (FreeCond::LT_LINE, FreeCond::LT_LINE_FALSE),
             // Subparagraph => (FreeCond::LT_LINE, FreeCond::LT_LINE_FALSE),
@@ -345,6 +343,8 @@ fn cond_to_frees(c: &Cond) -> Option<(FreeCond, FreeCond)> {
             // Schedule => (FreeCond::LT_LINE, FreeCond::LT_LINE_FALSE),
             // Title => (FreeCond::LT_LINE, FreeCond::LT_LINE_FALSE),
             // Supplement => (FreeCond::LT_LINE, FreeCond::LT_LINE_FALSE),
+            // Until then, they are tested like any other cond.
+            _ => return None,
         },
         _ => return None,
     };
@@ -534,27 +534,28 @@ fn condset_to_frees(c: &CondSet, inner: FreeCondSets) -> (FreeCondSets, FreeCond
             none.insert_validated(all_false);
             (any, none.at_least_1())
         }
-        Match::Nand => unimplemented!(),
-        // // Completely Untested
-        // Match::Nand => {
-        //     // _exactly one_ of them is true
-        //     // So negate the rest, for each
-        //     let vec: Vec<_> = conds
-        //         .iter()
-        //         .filter_map(cond_to_frees)
-        //         .map(|(a, _neg_a)| a)
-        //         .collect();
-        //     let mut all = fnv_set_with_cap(inner.0.len());
-        //     for each in vec {
-        //         let rest: FreeCond = conds
-        //             .iter()
-        //             .filter_map(cond_to_frees)
-        //             .filter_map(|(a, neg_a)| if a != each { Some(neg_a) } else { None })
-        //             .collect();
-        //         all.extend(inner.scalar_multiply(rest | each).0.drain());
-        //     }
-        //     FreeCondSets(all)
-        // }
+        // The mirror image of All: the branch is taken wherever not all of them are true.
+        Match::Nand => {
+            let mut outside = FreeCondSets::empty();
+            get_nand_outside(conds, &mut outside.0);
+            let nand = if outside.0.is_empty() {
+                inner
+            } else {
+                let mut nand = FreeCondSets::empty();
+                for x in outside.0 {
+                    nand.0.extend(inner.scalar_multiply(x).0.drain());
+                }
+                nand
+            };
+            let all: FreeCond = conds
+                .iter()
+                .filter_map(cond_to_frees)
+                .map(|(a, _neg_a)| a)
+                .collect();
+            let mut all_true = FreeCondSets::empty();
+            all_true.insert_validated(all);
+            (nand, all_true.at_least_1())
+        }
     }
 }
 
@@ -779,3 +780,26 @@ fn free_all_branches_match_any() {
     result.insert(FreeCond::LOCATOR_FALSE | FreeCond::LT_PAGE_FALSE);
     assert_eq!(all.0, result);
 }
+
+#[test]
+fn free_all_branches_match_nand() {
+    use csl::Position;
+    let ibid = Cond::Position(Position::Ibid);
+    let mut if_inner = FreeCondSets::empty();
+    if_inner.scalar_multiply_cond(ibid, true);
+    let mut if_branch_conds = FnvHashSet::default();
+    if_branch_conds.insert(Cond::Position(Position::First));
+    let if_branch = CondSet {
+        match_type: Match::Nand,
+        // should not end up in the output
+        conds: if_branch_conds,
+    };
+    let cs = vec![(&if_branch, if_inner)];
+    let all = FreeCondSets::all_branches(cs.into_iter(), None);
+    let mut result = FnvHashSet::default();
+    // the branch is taken when FIRST is false
+    result.insert(FreeCond::IBID_FALSE | FreeCond::FIRST_FALSE);
+    // and not taken when it is true
+    result.insert(FreeCond::FIRST);
+    assert_eq!(all.0, result);
+}
@@ -195,17 +195,19 @@ impl Disambiguation<Markup> for Element {
                     (RefIR::Edge(content), gv)
                 }
                 TextSource::Macro(ref name) => {
-                    let macro_elements = ctx
-                        .style
-                        .macros
-                        .get(name)
-                        .expect("undefined macro should not be valid CSL");
+                    // Must match what the cite itself leaves out
+                    if !state.count_macro_expansion(db.max_macro_expansions()) {
+                        return (RefIR::Edge(None), GroupVars::Plain);
+                    }
+                    let modules = db.jurisdiction_modules();
+                    let macro_elements =
+                        crate::jurisdiction::lookup_macro(ctx.style, &modules, ctx.reference, name);
                     state.push_macro(name);
                     let (seq, group_vars) = ref_sequence(
                         db,
                         state,
                         ctx,
-                        &macro_elements,
+                        macro_elements,
                         true,
                         text.formatting,
                         Some(&|| RefIrSeq {
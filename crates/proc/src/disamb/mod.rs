@@ -127,6 +127,8 @@ impl<'a> StyleWalker for FreeCondWalker<'a> {
 
     fn text_macro(&mut self, text: &TextElement, name: &SmartString) -> Self::Output {
         // TODO: same todos as in Proc
+        // Jurisdiction modules are not walked here, so conditions that only appear in a module's
+        // macros don't get their own free condition sets.
         let style = self.db.style();
         let macro_elements = style
             .macros
@@ -231,6 +233,79 @@ impl<'a> StyleWalker for FreeCondWalker<'a> {
     }
 }
 
+/// Whether the citation and the bibliography layouts (in that order) contain an explicit
+/// `<text variable="year-suffix" />`, in any branch or macro.
+///
+/// The spec says an explicit year-suffix in one of them suppresses the implicit one (attached to
+/// the first year of a date) in the other, unless the other has an explicit one as well.
+pub fn explicit_year_suffix_placement(style: &csl::Style) -> (bool, bool) {
+    let mut walker = ExplicitYearSuffixWalker {
+        style,
+        macros: Vec::new(),
+    };
+    let in_citation = walker.walk_citation(style);
+    let in_bibliography = walker.walk_bibliography(style).unwrap_or(false);
+    (in_citation, in_bibliography)
+}
+
+struct ExplicitYearSuffixWalker<'a> {
+    style: &'a csl::Style,
+    macros: Vec<SmartString>,
+}
+
+impl<'a> StyleWalker for ExplicitYearSuffixWalker<'a> {
+    type Output = bool;
+    type Checker = crate::choose::UselessCondChecker;
+
+    fn default(&mut self) -> Self::Output {
+        false
+    }
+
+    fn fold(&mut self, elements: &[Element], _fold_type: WalkerFoldType) -> Self::Output {
+        elements.iter().any(|el| self.element(el))
+    }
+
+    fn text_macro(&mut self, text: &TextElement, name: &SmartString) -> Self::Output {
+        // Don't go round in circles on a recursive macro
+        if self.macros.contains(name) {
+            return false;
+        }
+        let style = self.style;
+        let macro_elements = match style.macros.get(name) {
+            Some(els) => els,
+            None => return false,
+        };
+        self.macros.push(name.clone());
+        let ret = self.fold(macro_elements, WalkerFoldType::Macro(text));
+        self.macros.pop();
+        ret
+    }
+
+    fn text_variable(
+        &mut self,
+        _text: &TextElement,
+        sv: StandardVariable,
+        _form: VariableForm,
+    ) -> Self::Output {
+        sv == StandardVariable::Ordinary(Variable::YearSuffix)
+    }
+
+    fn names(&mut self, names: &Names) -> Self::Output {
+        match &names.substitute {
+            Some(subst) => self.fold(&subst.0, WalkerFoldType::Substitute),
+            None => false,
+        }
+    }
+
+    fn choose(&mut self, choose: &Choose) -> Self::Output {
+        let Choose(ifthen, elseifs, else_) = choose;
+        std::iter::once(ifthen)
+            .chain(elseifs.iter())
+            .any(|IfThen(_, els)| self.fold(els, WalkerFoldType::IfThen))
+            || self.fold(&else_.0, WalkerFoldType::Else)
+    }
+}
+
 pub trait Disambiguation<O: OutputFormat = Markup> {
     fn ref_ir(
         &self,
@@ -277,14 +352,24 @@ pub fn create_ref_ir<O: OutputFormat>(
     let ysh_edge = EdgeData::YearSuffix;
     let fcs = db.branch_runs();
     let fmt = db.get_formatter();
+    let case_exceptions = db.case_exceptions();
+    let compat_mode = db.compat_mode();
     let mut vec: Vec<(FreeCond, RefIR)> = fcs
         .0
         .iter()
         .cloned()
         .flat_map(|fc| {
             // Now we construct one ctx for every different count of disambiguate="X" checks
-            let ctx =
-                RefContext::from_free_cond(fc, &fmt, &style, &locale, refr, CiteOrBib::Citation);
+            let ctx = RefContext::from_free_cond(
+                fc,
+                &fmt,
+                &style,
+                &locale,
+                refr,
+                CiteOrBib::Citation,
+                case_exceptions.clone(),
+                compat_mode,
+            );
             let count = ctx.disamb_count;
             // 0 = none of them enabled
             // 1 = first disambiguate="X" tests as true
@@ -399,10 +484,10 @@ pub fn add_to_graph(
                     None
                 })
             };
-            let delim = override_delim
+            let inherited = override_delim
                 .filter(|_| should_inherit_delim)
-                .or(delimiter.as_opt_str())
-                .map(|d| mkedge(d));
+                .or(delimiter.as_opt_str());
+            let delim = inherited.map(|d| mkedge(d));
             graph_with_stack(fmt, nfa, formatting, affixes, spot, |nfa, mut spot| {
                 let mut seen = false;
                 for x in contents {
@@ -414,7 +499,7 @@ pub fn add_to_graph(
                         }
                         seen = true;
                     }
-                    spot = add_to_graph(fmt, nfa, x, spot, delimiter.as_opt_str());
+                    spot = add_to_graph(fmt, nfa, x, spot, inherited);
                 }
                 spot
             })
@@ -28,10 +28,11 @@ impl Disambiguation<Markup> for Names {
         let fmt = ctx.format;
         let style = ctx.style;
         let locale = ctx.locale;
-        let names_inheritance =
+        let mut names_inheritance =
             state
                 .name_override
                 .inherited_names_options(&ctx.name_el, &ctx.names_delimiter, &self);
+        names_inheritance.apply_term_overrides(db.name_term_overrides());
 
         // TODO: resolve which parts of name_el's Formatting are irrelevant due to 'stack'
         // and get a reduced formatting to work with
@@ -44,6 +45,7 @@ impl Disambiguation<Markup> for Names {
             fmt,
             demote_non_dropping_particle: style.demote_non_dropping_particle,
             initialize_with_hyphen: style.initialize_with_hyphen,
+            compat_mode: ctx.compat_mode,
         };
 
         let mut seq = RefIrSeq {
@@ -307,6 +309,7 @@ impl DisambNameData {
             bump_name_count: 0,
             demote_non_dropping_particle: style.demote_non_dropping_particle,
             initialize_with_hyphen: style.initialize_with_hyphen,
+            compat_mode: db.compat_mode(),
         };
         let built = builder.render_person_name(&self.value, !self.primary);
         let o = fmt.output_in_context(built, stack, None);
@@ -585,7 +588,7 @@ pub fn disambiguated_person_names(
     let rule = style.citation.givenname_disambiguation_rule;
     let dagn = style.citation.disambiguate_add_givenname;
 
-    if !dagn || rule == GNDR::ByCite {
+    if !cfg!(feature = "disambiguation") || !dagn || rule == GNDR::ByCite {
         return Arc::new(Default::default());
     }
 
@@ -713,10 +716,11 @@ pub struct NameIR<O: OutputFormat> {
     pub disamb_names: Vec<DisambNameRatchet<O::Build>>,
     pub built_label: Option<O::Build>,
 
-    // These three avoid having to pass in style & locale every time you want to recompute the IR
+    // These avoid having to pass in style & locale every time you want to recompute the IR
     // or make name tokens.
     pub demote_non_dropping_particle: DemoteNonDroppingParticle,
     pub initialize_with_hyphen: bool,
+    pub compat_mode: CompatMode,
     pub etal_term: Option<(SmartString, Option<Formatting>)>,
     pub and_term: Option<SmartString>,
 }
@@ -737,7 +741,7 @@ where
     ) -> Self {
         let built_label = names_inheritance.label.as_ref().and_then(|label| {
             let renderer = Renderer::gen(gen_ctx.clone());
-            renderer.name_label(&label.concrete(), variable, label_variable)
+            renderer.name_label(&label.concrete(), label_variable, ratchets.len())
         });
         NameIR {
             names_inheritance,
@@ -748,6 +752,7 @@ where
             achieved_at: (std::u16::MAX, NameCounter::default()),
             demote_non_dropping_particle: style.demote_non_dropping_particle,
             initialize_with_hyphen: style.initialize_with_hyphen,
+            compat_mode: gen_ctx.compat_mode(),
             etal_term,
             and_term,
             built_label,
@@ -2,7 +2,7 @@ use crate::choose::CondChecker;
 use crate::cite_context::RenderContext;
 use crate::prelude::*;
 use citeproc_io::output::markup::Markup;
-use citeproc_io::{DateOrRange, NumericValue, Reference};
+use citeproc_io::{CaseExceptions, DateOrRange, NumericValue, Reference};
 use csl::{style::*, terms::*, variables::*, Features, Locale, Name as NameEl};
 use std::sync::Arc;
 
@@ -20,6 +20,9 @@ pub struct RefContext<'a, O: OutputFormat = Markup> {
     pub names_delimiter: Option<SmartString>,
     pub name_el: Arc<NameEl>,
     pub disamb_count: u32,
+    pub location: CiteOrBib,
+    pub case_exceptions: CaseExceptions,
+    pub compat_mode: CompatMode,
 }
 
 impl From<FreeCond> for Position {
@@ -65,6 +68,9 @@ where
             names_delimiter: ctx.names_delimiter.clone(),
             name_el: ctx.name_citation.clone(),
             disamb_count: 0,
+            location: CiteOrBib::Citation,
+            case_exceptions: ctx.case_exceptions.clone(),
+            compat_mode: ctx.compat_mode,
         };
         ctx.count_disambiguate_branches(CiteOrBib::Citation);
         ctx
@@ -77,6 +83,8 @@ where
         locale: &'c Locale,
         reference: &'c Reference,
         location: CiteOrBib,
+        case_exceptions: CaseExceptions,
+        compat_mode: CompatMode,
     ) -> Self {
         let name_info = match location {
             CiteOrBib::Citation => style.name_info_citation(),
@@ -93,6 +101,9 @@ where
             names_delimiter: name_info.0,
             name_el: name_info.1,
             disamb_count: 0,
+            location,
+            case_exceptions,
+            compat_mode,
         };
         ctx.count_disambiguate_branches(location);
         ctx
@@ -161,8 +172,8 @@ where
             AnyVariable::Ordinary(v) => match v {
                 // Generated on demand
                 Variable::CitationLabel => true,
-                // TODO: make Hereinafter a FreeCond
-                Variable::Hereinafter => unimplemented!("Hereinafter as a FreeCond"),
+                // TODO: make Hereinafter a FreeCond. Until then it is read off the reference,
+                // as CiteContext does.
                 Variable::YearSuffix => self.year_suffix,
                 _ => self.get_ordinary(v, VariableForm::Long).is_some(),
             },
@@ -173,12 +184,30 @@ where
     }
 
     fn is_numeric(&self, var: AnyVariable) -> bool {
+        if let Some(answer) = self.compat_mode.is_numeric_override(var) {
+            return answer;
+        }
         match &var {
             AnyVariable::Number(num) => self.get_number(*num).map_or(false, |r| r.is_numeric()),
+            AnyVariable::Ordinary(v) => self.ordinary_is_numeric(*v),
             _ => false,
-            // TODO: not very useful; implement for non-number variables (see CiteContext)
         }
     }
+    fn is_plural(&self, var: NameVariable) -> bool {
+        self.name_is_plural(var)
+    }
+    fn context(&self) -> Context {
+        match self.location {
+            CiteOrBib::Citation => Context::Citation,
+            CiteOrBib::Bibliography => Context::Bibliography,
+        }
+    }
+    fn jurisdiction(&self) -> Option<&str> {
+        self.reference
+            .ordinary
+            .get(&Variable::Jurisdiction)
+            .map(|s| s.as_str())
+    }
     fn csl_type(&self) -> CslType {
         self.reference.csl_type
     }
@@ -280,6 +309,8 @@ mod test {
                     &locale,
                     &reference,
                     CiteOrBib::Citation,
+                    CaseExceptions::default(),
+                    CompatMode::default(),
                 );
                 let mut counter = DisambCounter::new(&ctx);
                 counter.walk_citation(&style)
@@ -1,5 +1,6 @@
 use crate::helpers::plain_text_element;
 use crate::prelude::*;
+use crate::warnings::{Warning, WarningCode};
 use csl::{style::*, variables::*};
 
 impl<'c, O, I> Proc<'c, O, I> for Citation
@@ -101,12 +102,27 @@ where
             Element::Text(ref text) => {
                 match text.source {
                     TextSource::Macro(ref name) => {
+                        let limit = db.max_macro_expansions();
+                        if !state.count_macro_expansion(limit) {
+                            db.report_warning(Warning::new(
+                                WarningCode::LimitExceeded,
+                                format!(
+                                    "macro {:?} left out, as the cite already expanded {} macros",
+                                    name,
+                                    limit.unwrap_or_default()
+                                ),
+                                Some(ctx.reference.id.clone()),
+                            ));
+                            return arena.new_node((IR::Rendered(None), GroupVars::Plain));
+                        }
                         // TODO: be able to return errors
-                        let macro_elements = ctx
-                            .style
-                            .macros
-                            .get(name)
-                            .expect("undefined macro should not be valid CSL");
+                        let modules = db.jurisdiction_modules();
+                        let macro_elements = crate::jurisdiction::lookup_macro(
+                            ctx.style,
+                            &modules,
+                            ctx.reference,
+                            name,
+                        );
                         // Technically, if re-running a style with a fresh IrState, you might
                         // get an extra level of recursion before it panics. BUT, then it will
                         // already have panicked when it was run the first time! So we're OK.
@@ -117,7 +133,7 @@ where
                             state,
                             ctx,
                             arena,
-                            &macro_elements,
+                            macro_elements,
                             // Not sure about this, but it acted like a group before...
                             true,
                             Some(&|| IrSeq {
@@ -182,8 +198,10 @@ where
                         }
                         let content = match var {
                             StandardVariable::Ordinary(v) => state.maybe_suppress(v, |_| {
-                                ctx.get_ordinary(v, form)
-                                    .map(|val| renderer.text_variable(text, var, &val))
+                                ctx.get_ordinary(v, form).map(|val| {
+                                    check_field_markup(db, ctx, v, &val);
+                                    renderer.text_variable(text, var, &val)
+                                })
                             }),
                             StandardVariable::Number(v) => state.maybe_suppress_num(v, |_| {
                                 ctx.get_number(v)
@@ -195,6 +213,16 @@ where
                         arena.new_node((IR::Rendered(content), gv))
                     }
                     TextSource::Term(term_selector, plural) => {
+                        if ctx.locale.get_text_term(term_selector, plural).is_none() {
+                            db.report_warning(Warning::new(
+                                WarningCode::MissingTerm,
+                                format!(
+                                    "term {:?} is not defined in locale {:?}",
+                                    term_selector, ctx.locale.lang
+                                ),
+                                Some(ctx.reference.id.clone()),
+                            ));
+                        }
                         let content = renderer
                             .text_term(text, term_selector, plural)
                             .map(CiteEdgeData::Term);
@@ -259,6 +287,29 @@ where
     }
 }
 
+/// Warns about markup in a field that the renderer will ignore or repair.
+fn check_field_markup<O: OutputFormat, I: OutputFormat>(
+    db: &dyn IrDatabase,
+    ctx: &CiteContext<'_, O, I>,
+    var: Variable,
+    value: &str,
+) {
+    if !value.contains('<') {
+        return;
+    }
+    if let Err(errors) = citeproc_io::output::micro_html::validate(value) {
+        db.report_warning(Warning::new(
+            WarningCode::FieldMarkup,
+            format!(
+                "markup in {} will not render as written: {:?}",
+                var.as_ref(),
+                errors
+            ),
+            Some(ctx.reference.id.clone()),
+        ));
+    }
+}
+
 impl YearSuffixHook {
     pub(crate) fn render<'c, O: OutputFormat, I: OutputFormat>(
         &self,
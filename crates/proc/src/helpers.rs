@@ -72,7 +72,7 @@ where
                 Default::default()
             }
         };
-        if !ctx.in_bibliography {
+        if !ctx.in_bibliography() {
             seq.display = None;
         }
         IR::Seq(seq)
@@ -367,7 +367,10 @@ impl IR<Markup> {
         let tree = IrTreeRef { node, arena };
         match me {
             IR::Rendered(None) => {}
-            IR::Rendered(Some(ed)) => edges.push(ed.to_edge_data(fmt, formatting)),
+            IR::Rendered(Some(ed)) if !fmt.is_empty(ed.build()) => {
+                edges.push(ed.to_edge_data(fmt, formatting))
+            }
+            IR::Rendered(Some(_)) => {}
             IR::YearSuffix(_ys) => {
                 if !tree.is_empty() {
                     edges.push(EdgeData::YearSuffix);
@@ -461,7 +464,7 @@ impl<'a, O: OutputFormat<Output = SmartString>> IrTreeRef<'a, O> {
         let (ref ir, gv) = *self.arena.get(self.node)?.get();
         match ir {
             IR::Rendered(None) => None,
-            IR::Rendered(Some(ref x)) => Some(x.inner()),
+            IR::Rendered(Some(ref x)) => Some(x.inner()).filter(|b| !fmt.is_empty(b)),
             IR::ConditionalDisamb(_) => self.flatten_children(fmt, override_delim),
             IR::YearSuffix(_) | IR::NameCounter(_) | IR::Name(_) | IR::Substitute => {
                 self.flatten_children(fmt, None)
@@ -525,29 +528,28 @@ impl IrSeq {
             should_inherit_delim,
             is_layout: _,
         } = *self;
+        // A branch of a choose inside another branch still has to join its children with the
+        // enclosing group's delimiter, so pass down the one we are actually using.
+        let delim = override_delim
+            .filter(|_| should_inherit_delim)
+            .or(delimiter.as_opt_str());
         let xs: Vec<_> = tree
             .children()
-            .filter_map(|child| child.flatten(fmt, delimiter.as_opt_str()))
+            .filter_map(|child| child.flatten(fmt, delim))
             .collect();
         if xs.is_empty() {
             return None;
         }
-        let delim = override_delim
-            .filter(|_| should_inherit_delim)
-            .or(delimiter.as_opt_str())
-            .unwrap_or("");
-        let grp = fmt.group(xs, delim, formatting);
-        let grp = fmt.affixed_quoted(grp, affixes.as_ref(), quotes.clone());
-        // TODO: pass in_bibliography from ctx
-        let mut grp = fmt.with_display(grp, display, true);
-        fmt.apply_text_case(
-            &mut grp,
-            &IngestOptions {
-                text_case,
-                ..Default::default()
-            },
-        );
-        Some(grp)
+        let grp = fmt.group(xs, delim.unwrap_or(""), None);
+        let seq = RenderSeq {
+            formatting,
+            quotes: quotes.clone(),
+            affixes: affixes.as_ref(),
+            display,
+            // TODO: pass in_bibliography from ctx
+            in_bibliography: true,
+        };
+        Some(seq.render_cased(fmt, grp, text_case))
     }
 
     fn append_edges(
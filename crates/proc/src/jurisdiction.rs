@@ -0,0 +1,82 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright © 2021 Corporation for Digital Scholarship
+
+//! CSL-M jurisdictions. A jurisdiction is a colon-separated path from the most general to the
+//! most specific, like `us:ca:la`. Tests and style modules for `us` apply to everything under it.
+
+use citeproc_io::Reference;
+use csl::{Element, Style, Variable};
+use fnv::FnvHashMap;
+use std::sync::Arc;
+
+use crate::prelude::*;
+
+/// `us:ca:la`, then `us:ca`, then `us`.
+fn self_and_ancestors(jurisdiction: &str) -> impl Iterator<Item = &str> {
+    std::iter::successors(Some(jurisdiction), |j| j.rfind(':').map(|ix| &j[..ix]))
+}
+
+/// `<if jurisdiction="us">` matches an item in `us` or `us:ca`, but not one in `usa`.
+pub(crate) fn jurisdiction_matches(item: &str, test: &str) -> bool {
+    self_and_ancestors(item).any(|j| j == test)
+}
+
+/// `<if subjurisdictions="1">` matches an item in `us:ca` or `us:ca:la`, but not one in `us`.
+pub(crate) fn has_subjurisdictions(item: &str, at_least: u32) -> bool {
+    (item.split(':').count() - 1) as u32 >= at_least
+}
+
+/// Finds the macro to use for `refr`, preferring the most specific jurisdiction module that
+/// defines it over the style itself.
+pub(crate) fn lookup_macro<'a>(
+    style: &'a Style,
+    modules: &'a FnvHashMap<SmartString, Arc<Style>>,
+    refr: &Reference,
+    name: &str,
+) -> &'a [Element] {
+    refr.ordinary
+        .get(&Variable::Jurisdiction)
+        .into_iter()
+        .flat_map(|j| self_and_ancestors(j))
+        .find_map(|j| modules.get(j)?.macros.get(name))
+        .or_else(|| style.macros.get(name))
+        .expect("undefined macro should not be valid CSL")
+}
+
+/// The macros a reference in `jurisdiction` can reach, as [lookup_macro] would find them: the
+/// style's, with each module from the most general ancestor down to `jurisdiction` laid over
+/// them. Parsing only checks each module's macros on their own, so this is what to check for
+/// cycles and depth once they are combined.
+pub fn merged_macros(
+    style: &Style,
+    modules: &FnvHashMap<SmartString, Arc<Style>>,
+    jurisdiction: &str,
+) -> FnvHashMap<SmartString, Vec<Element>> {
+    let mut merged = style.macros.clone();
+    let mut layers: Vec<&str> = self_and_ancestors(jurisdiction).collect();
+    layers.reverse();
+    for module in layers.into_iter().filter_map(|j| modules.get(j)) {
+        merged.extend(
+            module
+                .macros
+                .iter()
+                .map(|(name, elements)| (name.clone(), elements.clone())),
+        );
+    }
+    merged
+}
+
+#[test]
+fn test_jurisdiction_matches() {
+    assert!(jurisdiction_matches("us", "us"));
+    assert!(jurisdiction_matches("us:ca", "us"));
+    assert!(jurisdiction_matches("us:ca:la", "us:ca"));
+    assert!(!jurisdiction_matches("us", "us:ca"));
+    assert!(!jurisdiction_matches("usa", "us"));
+    assert!(!has_subjurisdictions("us", 1));
+    assert!(has_subjurisdictions("us:ca", 1));
+    assert!(!has_subjurisdictions("us:ca", 2));
+}
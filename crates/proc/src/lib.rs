@@ -28,13 +28,16 @@ mod choose;
 mod citation_label;
 mod cite_context;
 mod cluster;
+mod coverage;
 mod date;
 pub mod db;
 pub mod disamb;
 mod element;
 mod group;
 mod helpers;
+mod jurisdiction;
 mod ir;
+mod locator;
 mod names;
 mod number;
 mod page_range;
@@ -43,11 +46,15 @@ mod renderer;
 mod sort;
 mod tree;
 mod walker;
+mod warnings;
 
-pub use crate::cluster::built_cluster_before_output;
-pub use crate::db::bib_item_preview;
+pub use crate::cluster::{built_cluster_before_output, built_cluster_spans};
+pub use crate::coverage::{variable_coverage, VariableCoverage};
 pub use crate::db::safe_default;
-pub use crate::sort::BibNumber;
+pub use crate::db::{author_list_preview, bib_item_preview, macro_preview};
+pub use crate::jurisdiction::merged_macros;
+pub use crate::sort::{bib_sort_keys, BibNumber, CitationNumbering, SortKeyData, SortKeyValue};
+pub use crate::warnings::{Warning, WarningCode};
 
 pub(crate) mod prelude {
     pub(crate) trait AsRefOptStr {
@@ -82,16 +89,19 @@ pub(crate) mod prelude {
 
     pub use csl::{Affixes, DisplayMode, Element, Formatting, TextCase};
 
-    pub use crate::cite_context::CiteContext;
+    pub use crate::cite_context::{CiteContext, RenderingContext};
     pub use crate::group::GroupVars;
     pub use crate::ir::*;
     pub use crate::ref_ir::*;
     pub use crate::sort::BibNumber;
+    pub use crate::CompatMode;
+    pub use crate::ReprintSort;
 
     pub(crate) type MarkupBuild = <Markup as OutputFormat>::Build;
     pub(crate) type MarkupOutput = <Markup as OutputFormat>::Output;
     pub(crate) use crate::disamb::{Disambiguation, EdgeData, RefContext};
     pub(crate) use crate::helpers::*;
+    pub(crate) use crate::renderer::RenderSeq;
     pub(crate) use crate::renderer::Renderer;
     pub(crate) use crate::{IrState, Proc};
     pub(crate) const CSL_STYLE_ERROR: &'static str =
@@ -129,8 +139,8 @@ where
     ) -> NodeId;
 }
 
-use csl::{Affixes, DisplayMode, Formatting, Name, NameEtAl, NameLabelInput, Names};
-use csl::{AnyVariable, DateVariable, NameAsSortOrder, NameVariable, NumberVariable, Variable};
+use csl::{Affixes, DisplayMode, Formatting, Name, NameAnd, NameEtAl, NameLabelInput, Names};
+use csl::{AnyVariable, DateVariable, NameVariable, NumberVariable, Variable};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 enum DidSupplyName {
@@ -203,17 +213,8 @@ impl NamesInheritance {
         }
     }
     fn from_sort_key(sort_key: &SortKey) -> Self {
-        let name_el = Name {
-            et_al_min: sort_key.names_min,
-            et_al_subsequent_min: sort_key.names_min,
-            et_al_use_first: sort_key.names_use_first,
-            et_al_subsequent_use_first: sort_key.names_use_first,
-            et_al_use_last: sort_key.names_use_last,
-            name_as_sort_order: Some(NameAsSortOrder::All),
-            ..Default::default()
-        };
         NamesInheritance {
-            name: name_el,
+            name: sort_key.name_overrides(),
             did_supply_name: DidSupplyName::SortKey, // makes no difference
             delimiter: None,
             label: None,
@@ -225,6 +226,146 @@ impl NamesInheritance {
     }
 }
 
+/// Overrides for the terms used between names, set on the processor rather than in the style, so
+/// end users can switch between "&" and "and" or "et al." and "and others" without editing CSL.
+/// These take precedence over the style wherever it uses the corresponding term.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct NameTermOverrides {
+    /// Replaces `and="text"` or `and="symbol"`. Names that the style does not join with an "and"
+    /// at all are left alone.
+    pub and: Option<NameAnd>,
+    /// Replaces the term in `<et-al term="...">`, or the default `et-al` term if there is no such
+    /// element.
+    pub et_al: Option<EtAlTerm>,
+}
+
+/// The two terms CSL allows in `<et-al term="...">`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum EtAlTerm {
+    EtAl,
+    AndOthers,
+}
+
+impl EtAlTerm {
+    fn as_str(self) -> &'static str {
+        match self {
+            EtAlTerm::EtAl => "et-al",
+            EtAlTerm::AndOthers => "and others",
+        }
+    }
+}
+
+impl NamesInheritance {
+    pub(crate) fn apply_term_overrides(&mut self, overrides: NameTermOverrides) {
+        if let (Some(and), Some(_)) = (overrides.and, self.name.and) {
+            self.name.and = Some(and);
+        }
+        if let Some(term) = overrides.et_al {
+            let et_al = self.et_al.get_or_insert_with(|| NameEtAl {
+                term: String::new(),
+                formatting: None,
+            });
+            et_al.term = term.as_str().into();
+        }
+    }
+}
+
+/// Where citeproc-js knowingly renders differently from the CSL specification, which of the two
+/// to follow. Users migrating from citeproc-js can pick [CompatMode::CiteprocJs] to keep their
+/// output byte-for-byte the same.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum CompatMode {
+    /// Follow the specification. This is the default.
+    Spec,
+    /// Follow citeproc-js:
+    ///
+    /// * `is-numeric="page-first"` is always false, even if the page is numeric.
+    /// * `punctuation-in-quote` is true for an English default locale that does not set it,
+    ///   instead of false.
+    /// * The et-al term is always separated from the names by a space. Under the spec, there is
+    ///   no space before a term in a non-Latin, non-Cyrillic script, like `等`.
+    CiteprocJs,
+}
+
+impl Default for CompatMode {
+    fn default() -> Self {
+        CompatMode::Spec
+    }
+}
+
+impl CompatMode {
+    /// The `is-numeric` answers that do not depend on the variable's value.
+    pub(crate) fn is_numeric_override(self, var: AnyVariable) -> Option<bool> {
+        match (self, var) {
+            (CompatMode::CiteprocJs, AnyVariable::Number(NumberVariable::PageFirst)) => Some(false),
+            _ => None,
+        }
+    }
+
+    /// The document's `punctuation-in-quote` setting, given its default locale.
+    pub fn punctuation_in_quote(self, locale: &csl::Locale) -> bool {
+        locale
+            .options_node
+            .punctuation_in_quote
+            .unwrap_or_else(|| match self {
+                CompatMode::Spec => false,
+                CompatMode::CiteprocJs => locale.lang.as_ref().map_or(true, |l| l.is_english()),
+            })
+    }
+}
+
+/// Which date a sort key on the `issued` variable compares, for reprints that also carry an
+/// `original-date`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ReprintSort {
+    /// Sort by `issued`, as written. This is the default.
+    Issued,
+    /// Sort by `original-date` where a reference has one, so that a reprint sorts with the year
+    /// it was first published. References without one still sort by `issued`.
+    Original,
+}
+
+impl Default for ReprintSort {
+    fn default() -> Self {
+        ReprintSort::Issued
+    }
+}
+
+/// Text the host adds around one bibliography entry, outside anything the style renders, like
+/// "[Online]" or a library call number. It is written out as plain text in the output format, so
+/// it is escaped for HTML and RTF.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct EntryDecoration {
+    pub prefix: Option<SmartString>,
+    pub suffix: Option<SmartString>,
+}
+
+impl EntryDecoration {
+    pub(crate) fn wrap(&self, fmt: &Markup, entry: MarkupBuild) -> MarkupBuild {
+        let prefix = self.prefix.as_opt_str().map(|p| fmt.plain(p));
+        let suffix = self.suffix.as_opt_str().map(|s| fmt.plain(s));
+        fmt.seq(prefix.into_iter().chain(Some(entry)).chain(suffix))
+    }
+}
+
+/// The HTML `id` of a reference's bibliography entry, made from the reference id alone so that it
+/// is the same every time. In-text citations can link to `#` followed by this.
+///
+/// ASCII letters, digits and `-` are kept as they are. Every other byte, including `_`, becomes `_`
+/// and two hex digits, so two different reference ids never share an anchor.
+pub fn entry_anchor(ref_id: &str) -> SmartString {
+    use std::fmt::Write;
+    let mut anchor = SmartString::from("ref-");
+    for byte in ref_id.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' {
+            anchor.push(byte as char);
+        } else {
+            let _ = write!(anchor, "_{:02x}", byte);
+        }
+    }
+    anchor
+}
+
 use fnv::FnvHashSet;
 
 #[derive(Default, Debug, PartialEq, Eq, Clone)]
@@ -233,6 +374,8 @@ pub struct IrState {
     /// So the same macro name anywhere above indicates attempted recursion.
     /// When you exit a frame, delete from the set.
     macro_stack: HashSet<SmartString>,
+    /// How many macros have been expanded so far, including ones that have finished.
+    macro_expansions: u32,
     pub name_override: NameOverrider,
     suppressed: FnvHashSet<AnyVariable>,
     pub disamb_count: u32,
@@ -390,4 +533,14 @@ impl IrState {
     pub fn pop_macro(&mut self, macro_name: &SmartString) {
         self.macro_stack.remove(macro_name);
     }
+
+    /// Counts one more macro expansion, unless `limit` have been done already, in which case the
+    /// macro should be left out.
+    pub fn count_macro_expansion(&mut self, limit: Option<u32>) -> bool {
+        if limit.map_or(false, |limit| self.macro_expansions >= limit) {
+            return false;
+        }
+        self.macro_expansions += 1;
+        true
+    }
 }
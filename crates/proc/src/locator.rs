@@ -0,0 +1,184 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright © 2021 Corporation for Digital Scholarship
+
+//! Locators like "12-13, 15 n. 3" or "4 l. 7" point at more than one kind of thing. The cite's
+//! label only covers the first part ("12-13, 15", pages). Each later part starts with a label of
+//! its own, written as any form of that locator type's term in the locale, so "n. 3", "note 3"
+//! and "notes 3-4" are all notes. Those parts are rendered after the first with the locale's
+//! short term, pluralised for their own value.
+
+use crate::number::arabic_number;
+use crate::prelude::*;
+use citeproc_io::NumericValue;
+use csl::{
+    GenderedTermSelector, Locale, LocatorType, NumberVariable, PageRangeFormat, TermForm,
+    TextTermSelector,
+};
+use strum::IntoEnumIterator;
+
+/// One labelled part of a locator after the first, like the "n. 3" in "12-13, 15 n. 3".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubLocator<'a> {
+    /// Whatever came between it and the part before, like `" "` or `", "`.
+    pub delimiter: &'a str,
+    pub loc_type: LocatorType,
+    pub value: &'a str,
+}
+
+/// Returns the part of `locator` that the cite's own label applies to, and the labelled parts
+/// after it.
+pub fn split_locator<'a>(locator: &'a str, locale: &Locale) -> (&'a str, Vec<SubLocator<'a>>) {
+    let mut subs = Vec::new();
+    let trimmed = locator.trim();
+    // No point collecting the terms if there is nowhere for a second label to go
+    if !trimmed.contains(char::is_whitespace) {
+        return (locator, subs);
+    }
+    let labels = locator_labels(locale);
+    // (where the delimiter starts, where the value starts, the type)
+    let mut found: Vec<(usize, usize, LocatorType)> = Vec::new();
+    let mut prev_ws = false;
+    for (ix, ch) in trimmed.char_indices() {
+        let word_start = prev_ws && !ch.is_whitespace();
+        prev_ws = ch.is_whitespace();
+        if !word_start {
+            continue;
+        }
+        let tail = &trimmed[ix..];
+        let matched = labels.iter().find_map(|(label, loc_type)| {
+            let value_start = label_len(tail, label)?;
+            Some((ix + value_start, *loc_type))
+        });
+        if let Some((value_start, loc_type)) = matched {
+            let delim_start = trimmed[..ix]
+                .trim_end_matches(|c: char| c.is_whitespace() || c == ',' || c == ';')
+                .len();
+            found.push((delim_start, value_start, loc_type));
+        }
+    }
+    let first_end = match found.first() {
+        Some(&(delim_start, ..)) => delim_start,
+        None => return (locator, subs),
+    };
+    let mut delim_from = first_end;
+    for (i, &(_, value_start, loc_type)) in found.iter().enumerate() {
+        let value_end = found.get(i + 1).map_or(trimmed.len(), |next| next.0);
+        let label_start = trimmed[delim_from..]
+            .find(|c: char| !(c.is_whitespace() || c == ',' || c == ';'))
+            .map_or(delim_from, |x| delim_from + x);
+        subs.push(SubLocator {
+            delimiter: &trimmed[delim_from..label_start],
+            loc_type,
+            value: trimmed[value_start..value_end].trim(),
+        });
+        delim_from = value_end;
+    }
+    (&trimmed[..first_end], subs)
+}
+
+/// If `tail` starts with `label` (ignoring case) and then a number, how far along the number
+/// starts.
+fn label_len(tail: &str, label: &str) -> Option<usize> {
+    let head = tail.get(..label.len())?;
+    if head.to_lowercase() != label {
+        return None;
+    }
+    let after = &tail[label.len()..];
+    let value = after.trim_start();
+    // "n" must not match the start of "no. 5", but "§3" and "n.3" are fine
+    let needs_space = label.chars().last().map_or(true, char::is_alphanumeric);
+    if needs_space && value.len() == after.len() {
+        return None;
+    }
+    if !value.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(tail.len() - value.len())
+}
+
+/// Every form of every locator term in the locale, lowercased, longest first so "para." wins
+/// over "p.".
+fn locator_labels(locale: &Locale) -> Vec<(String, LocatorType)> {
+    let mut labels: Vec<(String, LocatorType)> = Vec::new();
+    for loc_type in LocatorType::iter() {
+        for &form in &[TermForm::Long, TermForm::Short, TermForm::Symbol] {
+            for &plural in &[false, true] {
+                let sel = TextTermSelector::Gendered(GenderedTermSelector::Locator(loc_type, form));
+                let term = match locale.get_text_term(sel, plural) {
+                    Some(term) if !term.trim().is_empty() => term.trim().to_lowercase(),
+                    _ => continue,
+                };
+                if !labels.iter().any(|(l, _)| *l == term) {
+                    labels.push((term, loc_type));
+                }
+            }
+        }
+    }
+    labels.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+    labels
+}
+
+/// Writes the labelled parts after the first onto `out`, e.g. `" n. 3"`.
+pub fn write_sub_locators(
+    out: &mut SmartString,
+    subs: &[SubLocator],
+    locale: &Locale,
+    and_term: &str,
+    page_range_format: Option<PageRangeFormat>,
+) {
+    for sub in subs {
+        let value = NumericValue::from_localized_str(sub.value, and_term);
+        let plural = value.is_multiple(NumberVariable::Locator);
+        let prf = page_range_format.filter(|_| sub.loc_type == LocatorType::Page);
+        let sel = TextTermSelector::Gendered(GenderedTermSelector::Locator(
+            sub.loc_type,
+            TermForm::Short,
+        ));
+        out.push_str(sub.delimiter);
+        if let Some(term) = locale.get_text_term(sel, plural).filter(|t| !t.is_empty()) {
+            out.push_str(term);
+            out.push(' ');
+        }
+        out.push_str(&arabic_number(&value, locale, NumberVariable::Locator, prf));
+    }
+}
+
+#[test]
+fn test_split_locator() {
+    let locale = Locale::parse(csl::locale::EN_US).unwrap();
+    let split = |s: &'static str| split_locator(s, &locale);
+    assert_eq!(split("12-13"), ("12-13", vec![]));
+    assert_eq!(split("12 and 13"), ("12 and 13", vec![]));
+    assert_eq!(
+        split("12-13, 15 n. 3"),
+        (
+            "12-13, 15",
+            vec![SubLocator {
+                delimiter: " ",
+                loc_type: LocatorType::Note,
+                value: "3",
+            }]
+        )
+    );
+    assert_eq!(
+        split("4, para. 2 l. 7-9"),
+        (
+            "4",
+            vec![
+                SubLocator {
+                    delimiter: ", ",
+                    loc_type: LocatorType::Paragraph,
+                    value: "2",
+                },
+                SubLocator {
+                    delimiter: " ",
+                    loc_type: LocatorType::Line,
+                    value: "7-9",
+                },
+            ]
+        )
+    );
+}
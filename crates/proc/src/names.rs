@@ -14,9 +14,10 @@ use crate::NamesInheritance;
 use citeproc_io::utils::Intercalate;
 use citeproc_io::{Name, PersonName, Reference};
 use csl::{
-    Atom, DelimiterPrecedes, DemoteNonDroppingParticle, Name as NameEl, NameAnd, NameAsSortOrder,
-    NameEtAl, NameForm, NamePart, NameVariable, Names, Position,
+    Atom, DelimiterPrecedes, DemoteNonDroppingParticle, Locale, Name as NameEl, NameAnd,
+    NameAsSortOrder, NameEtAl, NameForm, NamePart, NameVariable, Names, Position,
 };
+use std::borrow::Cow;
 
 mod initials;
 
@@ -171,7 +172,20 @@ pub fn to_individual_name_irs<'a, O: OutputFormat, I: OutputFormat>(
         .filter(move |var| !state.is_suppressed_name(**var))
         .filter_map(move |var| {
             let ovar = var_override.as_ref().unwrap_or(var);
-            refr.name.get(var).map(|val| (*var, *ovar, val.clone()))
+            let mut val = refr.name.get(var)?.clone();
+            let suppress = ctx.suppress_names();
+            if *var == NameVariable::Author && !suppress.is_empty() {
+                let mut index = 0;
+                val.retain(|name| {
+                    let keep = !suppress.iter().any(|sel| sel.selects(index, name));
+                    index += 1;
+                    keep
+                });
+                if val.is_empty() {
+                    return None;
+                }
+            }
+            Some((*var, *ovar, val))
         })
         .map(get_name_ir)
 }
@@ -186,6 +200,7 @@ pub(crate) fn sort_strings_for_names(
     var: NameVariable,
     sort_key: &SortKey,
     loc: CiteOrBib,
+    locale: &Locale,
 ) -> Option<Vec<Natural<SmartString>>> {
     let style = db.style();
     let fmt = db.get_formatter();
@@ -206,6 +221,7 @@ pub(crate) fn sort_strings_for_names(
         bump_name_count: 0,
         demote_non_dropping_particle: style.demote_non_dropping_particle,
         initialize_with_hyphen: style.initialize_with_hyphen,
+        compat_mode: db.compat_mode(),
         fmt: &fmt,
     };
     let mut out = Vec::new();
@@ -216,8 +232,10 @@ pub(crate) fn sort_strings_for_names(
                     runner.person_name_sort_keys(pn, &mut out);
                 }
                 Name::Literal { literal, .. } => {
+                    // "The Beatles" sorts under B
+                    let literal = crate::sort::strip_sort_article(refr, locale, literal);
                     if !literal.is_empty() {
-                        out.push(Natural::new(literal.clone()));
+                        out.push(Natural::new(literal.into()));
                     }
                 }
             }
@@ -250,6 +268,7 @@ pub fn intermediate<'c, O: OutputFormat, I: OutputFormat>(
             NamesInheritance::from_sort_key(key),
         );
     }
+    names_inheritance.apply_term_overrides(db.name_term_overrides());
 
     let gen = GenericContext::Cit(ctx);
     let nirs_iterator = to_individual_name_irs(&gen, names, &names_inheritance, db, state, true);
@@ -370,7 +389,7 @@ pub fn intermediate<'c, O: OutputFormat, I: OutputFormat>(
         formatting: names_inheritance.formatting,
         affixes: names_inheritance.affixes.clone(),
         delimiter: names_inheritance.delimiter.clone(),
-        display: if ctx.in_bibliography {
+        display: if ctx.in_bibliography() {
             names.display
         } else {
             None
@@ -438,6 +457,7 @@ impl<'c, O: OutputFormat> NameIR<O> {
             bump_name_count: self.name_counter.bump,
             demote_non_dropping_particle: self.demote_non_dropping_particle,
             initialize_with_hyphen: self.initialize_with_hyphen,
+            compat_mode: self.compat_mode,
         }
     }
 
@@ -508,8 +528,12 @@ impl<'c, O: OutputFormat> NameIR<O> {
                     }
                     DisambNameRatchet::Person(pn) => {
                         cloned_runner.name_el = &pn.data.el;
-                        let ret =
-                            cloned_runner.render_person_name(&pn.data.value, !pn.data.primary);
+                        let value = if is_sort_key {
+                            sortable_form(&pn.data.value)
+                        } else {
+                            Cow::Borrowed(&pn.data.value)
+                        };
+                        let ret = cloned_runner.render_person_name(&value, !pn.data.primary);
                         cloned_runner.name_el = &self.names_inheritance.name;
                         Some(maybe_subst(ret)).filter(|x| !fmt.is_empty(&x))
                     }
@@ -575,6 +599,20 @@ impl<'c, O: OutputFormat> NameIR<O> {
     }
 }
 
+/// Names in other scripts sort by their romanized form if they have one, so they are interfiled
+/// with the rest.
+fn sortable_form(pn: &PersonName) -> Cow<'_, PersonName> {
+    match &pn.romanized {
+        Some(rn) => Cow::Owned(PersonName {
+            family: rn.family.clone(),
+            given: rn.given.clone(),
+            is_latin_cyrillic: true,
+            ..Default::default()
+        }),
+        None => Cow::Borrowed(pn),
+    }
+}
+
 /// For a given display order, not all the name parts will have data in them at the end. So for
 /// this PersonName, reduce the DisplayOrdering to include only those parts that will end up
 /// with content.
@@ -682,6 +720,7 @@ pub struct OneNameVar<'a, O: OutputFormat> {
     // From Style
     pub demote_non_dropping_particle: DemoteNonDroppingParticle,
     pub initialize_with_hyphen: bool,
+    pub compat_mode: CompatMode,
     pub fmt: &'a O,
 }
 
@@ -740,8 +779,11 @@ impl<'a, O: OutputFormat> OneNameVar<'a, O> {
                     .take(ea_use_first)
                     .intercalate(&NameToken::Delimiter);
                 nms.push(NameToken::Delimiter);
-                nms.push(NameToken::Ellipsis);
-                nms.push(NameToken::Space);
+                // Like the et-al term, the ellipsis is left out of sort keys
+                if !is_sort_key {
+                    nms.push(NameToken::Ellipsis);
+                    nms.push(NameToken::Space);
+                }
                 nms.push(NameToken::Name(last));
                 nms
             } else {
@@ -795,8 +837,9 @@ impl<'a, O: OutputFormat> OneNameVar<'a, O> {
         pn: &PersonName,
         out: &mut Vec<Natural<SmartString>>,
     ) {
+        let pn = &*sortable_form(pn);
         let order = get_sort_order(
-            pn.is_latin_cyrillic,
+            NameScript::of(pn),
             self.name_el.form == Some(NameForm::Long),
             self.demote_non_dropping_particle,
         );
@@ -927,7 +970,7 @@ impl<'a, O: OutputFormat> OneNameVar<'a, O> {
         let fmt = self.fmt;
 
         let order = get_display_order(
-            pn.is_latin_cyrillic,
+            NameScript::of(pn),
             self.name_el.form == Some(NameForm::Long),
             self.naso(seen_one),
             self.demote_non_dropping_particle,
@@ -1132,7 +1175,10 @@ impl<'a, O: OutputFormat> OneNameVar<'a, O> {
                     if is_sort_key {
                         return None;
                     }
-                    let lat_cy = citeproc_io::unicode::is_latin_cyrillic(&text);
+                    // The space before it is dropped unless this is latin/cyrillic, but
+                    // citeproc-js always keeps it
+                    let lat_cy = self.compat_mode == CompatMode::CiteprocJs
+                        || citeproc_io::unicode::is_latin_cyrillic(&text);
                     NameTokenBuilt::Built(fmt.text_node(text, formatting), lat_cy)
                 }
                 NameToken::Ellipsis => NameTokenBuilt::Built(fmt.plain("…"), true),
@@ -1179,7 +1225,7 @@ impl<B> NameTokenBuilt<B> {
     }
 }
 
-use self::ord::{get_display_order, get_sort_order, DisplayOrdering, NamePartToken};
+use self::ord::{get_display_order, get_sort_order, DisplayOrdering, NamePartToken, NameScript};
 
 #[allow(dead_code)]
 mod ord {
@@ -1220,18 +1266,52 @@ mod ord {
 
     use self::NamePartToken::*;
 
-    pub fn get_display_order(latin: bool, long: bool, naso: bool, demote: DNDP) -> DisplayOrdering {
-        match (latin, long, naso, demote) {
-            (false, long, ..) => {
+    /// Decides the order of the parts of a name, before name-as-sort-order is applied.
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    pub enum NameScript {
+        /// Given name first, or family name first with a comma under name-as-sort-order.
+        Latin,
+        /// Family name first with a space and no comma, e.g. a romanized Chinese name marked
+        /// `static-ordering`. Not affected by name-as-sort-order.
+        StaticLatin,
+        /// Family name first with no space, e.g. 毛泽东. Not affected by name-as-sort-order.
+        NonLatin,
+    }
+
+    impl NameScript {
+        pub fn of(pn: &citeproc_io::PersonName) -> Self {
+            match (pn.is_latin_cyrillic, pn.static_ordering) {
+                (false, _) => NameScript::NonLatin,
+                (true, true) => NameScript::StaticLatin,
+                (true, false) => NameScript::Latin,
+            }
+        }
+    }
+
+    pub fn get_display_order(
+        script: NameScript,
+        long: bool,
+        naso: bool,
+        demote: DNDP,
+    ) -> DisplayOrdering {
+        match (script, long, naso, demote) {
+            (NameScript::NonLatin, long, ..) => {
                 if long {
                     NON_LATIN_LONG
                 } else {
                     NON_LATIN_SHORT
                 }
             }
-            (true, false, ..) => LATIN_SHORT,
-            (true, true, false, _) => LATIN_LONG,
-            (true, true, true, demote) => {
+            (NameScript::StaticLatin, long, ..) => {
+                if long {
+                    STATIC_LATIN_LONG
+                } else {
+                    LATIN_SHORT
+                }
+            }
+            (NameScript::Latin, false, ..) => LATIN_SHORT,
+            (NameScript::Latin, true, false, _) => LATIN_LONG,
+            (NameScript::Latin, true, true, demote) => {
                 if demote == DNDP::DisplayAndSort {
                     LATIN_LONG_NASO_DEMOTED
                 } else {
@@ -1241,16 +1321,16 @@ mod ord {
         }
     }
 
-    pub fn get_sort_order(latin: bool, long: bool, demote: DNDP) -> SortOrdering {
-        match (latin, long, demote) {
-            (false, long, _) => {
+    pub fn get_sort_order(script: NameScript, long: bool, demote: DNDP) -> SortOrdering {
+        match (script, long, demote) {
+            (NameScript::NonLatin, long, _) | (NameScript::StaticLatin, long, _) => {
                 if long {
                     NON_LATIN_SORT_LONG
                 } else {
                     NON_LATIN_SORT_SHORT
                 }
             }
-            (true, _, demote) => {
+            (NameScript::Latin, _, demote) => {
                 if demote == DNDP::Never {
                     LATIN_SORT_NEVER
                 } else {
@@ -1298,6 +1378,8 @@ mod ord {
     ];
     /// 毛 [Mao]
     static NON_LATIN_SHORT: DisplayOrdering = &[Family];
+    /// [Mao] [Zedong]
+    static STATIC_LATIN_LONG: DisplayOrdering = &[Family, Space, Given];
     /// 毛泽东 [Mao Zedong]
     static NON_LATIN_SORT_LONG: SortOrdering = &[&[Family], &[Given]];
     /// 毛 [Mao]
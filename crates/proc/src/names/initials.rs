@@ -5,6 +5,7 @@
 // Copyright © 2019 Corporation for Digital Scholarship
 
 use std::borrow::Cow;
+use unic_segment::Graphemes;
 /// use GivenNameToken::*;
 /// "John R L" == &[Name("John"), Initial("R"), Initial("L")]
 /// "Jean-Luc K" = &[Name("Jean"), HyphenSegment("Luc"), Initial("K")]
@@ -48,24 +49,23 @@ pub fn initialize<'n>(
                         }
                         // name_LongAbbreviation.txt i.e. GIven => Gi.
                         if n.chars().any(|c| c.is_lowercase()) {
-                            let mut iter = n.chars();
                             let mut seen_one = false;
-                            while let Some(c) = iter.next() {
-                                let upper = c.is_uppercase();
+                            for g in Graphemes::new(n) {
+                                let upper = starts_uppercase(g);
                                 if upper && seen_one {
-                                    build.extend(c.to_lowercase());
+                                    build.push_str(&g.to_lowercase());
                                     continue;
                                 } else if upper {
-                                    build.push(c);
+                                    build.push_str(g);
                                     seen_one = true;
                                     continue;
                                 } else if !seen_one {
-                                    build.push(c);
+                                    build.push_str(g);
                                 }
                                 break;
                             }
                         } else {
-                            build.push(n.chars().nth(0).unwrap());
+                            build.push_str(first_grapheme(n));
                         }
                         build.push_str(with);
                         State::AfterInitial
@@ -98,7 +98,7 @@ pub fn initialize<'n>(
                             build.truncate(build.trim_end().len());
                             build.push('-');
                         }
-                        build.push(n.chars().nth(0).unwrap());
+                        build.push_str(first_grapheme(n));
                         build.push_str(with);
                         State::AfterInitial
                     } else {
@@ -129,11 +129,22 @@ pub fn initialize<'n>(
     }
 }
 
+/// The first user-perceived character, so an initial keeps its combining marks. "Émile" may be
+/// written with a separate U+0301 after the E, and "É" is the initial, not "E".
+fn first_grapheme(s: &str) -> &str {
+    Graphemes::new(s).next().unwrap_or("")
+}
+
+fn starts_uppercase(s: &str) -> bool {
+    s.chars().next().map_or(false, |c| c.is_uppercase())
+}
+
 use nom::{
     branch::alt,
-    bytes::complete::{take_while, take_while1, take_while_m_n},
+    bytes::complete::{take_while, take_while1},
     character::complete::char as nom_char,
     combinator::{map, opt, recognize, rest},
+    error::{Error, ErrorKind},
     sequence::{preceded, terminated, tuple},
     IResult,
 };
@@ -146,8 +157,14 @@ use nom::{
 // Ma   => [Name("Ma")]
 // aa   => [Other("aa")]
 
+// One uppercase grapheme, including any combining marks after it.
 fn uppercase_char(inp: &str) -> IResult<&str, &str> {
-    take_while_m_n(1, 1, |c: char| c.is_uppercase())(inp)
+    let g = first_grapheme(inp);
+    if starts_uppercase(g) {
+        Ok((&inp[g.len()..], g))
+    } else {
+        Err(nom::Err::Error(Error::new(inp, ErrorKind::TakeWhileMN)))
+    }
 }
 
 // Don't need to be certain there's a dot on the end, as the whole-string-no-dots case is
@@ -355,3 +372,19 @@ fn test_initialize_false_period_space() {
     assert_eq!(init("好 好"), "好 好");
     assert_eq!(init("Immel, Ph. M.E."), "Immel, Ph. M. E.")
 }
+
+#[test]
+fn test_initialize_graphemes() {
+    fn init(given_name: &str) -> Cow<'_, str> {
+        initialize(given_name, true, Some(". "), true)
+    }
+    assert_eq!(init("Øystein"), "Ø.");
+    assert_eq!(init("Åse-Marie"), "Å.-M.");
+    // E + COMBINING ACUTE ACCENT
+    assert_eq!(init("E\u{301}mile"), "E\u{301}.");
+    assert_eq!(init("Jean-E\u{301}mile"), "J.-E\u{301}.");
+    assert_eq!(init("E\u{301}. Zola"), "E\u{301}. Z.");
+    assert_eq!(init("E\u{301}M"), "E\u{301}.");
+    assert_eq!(init("ØIvind"), "Øi.");
+    assert_eq!(init("O\u{308}Ivind"), "O\u{308}i.");
+}
@@ -1,8 +1,11 @@
 use crate::cite_context::RenderContext;
+use crate::locator::write_sub_locators;
 use crate::number::{arabic_number, render_ordinal, roman_lower, roman_representable};
 use crate::prelude::*;
 use citeproc_io::output::LocalizedQuotes;
-use citeproc_io::{Name, NumericToken, NumericValue, Reference};
+use citeproc_io::{
+    CaseExceptions, CaseLanguage, Name, NameSelector, NumericToken, NumericValue, Reference,
+};
 use csl::{
     Features, GenderedTermSelector, LabelElement, Lang, Locale, LocatorType, NameLabel,
     NameVariable, NumberElement, NumberVariable, NumericForm, PageRangeFormat, Plural,
@@ -13,7 +16,7 @@ use csl::{
 use crate::choose::CondChecker;
 use citeproc_io::DateOrRange;
 use csl::{AnyVariable, DateVariable};
-use csl::{CslType, Position};
+use csl::{Context, CslType, Position};
 use std::borrow::Cow;
 
 #[derive(Clone)]
@@ -51,6 +54,9 @@ impl<'a, O: OutputFormat, I: OutputFormat> CondChecker for GenericContext<'a, O,
     forward_inner! {
         fn has_variable(&self, var: AnyVariable) -> bool;
         fn is_numeric(&self, var: AnyVariable) -> bool;
+        fn is_plural(&self, var: NameVariable) -> bool;
+        fn context(&self) -> Context;
+        fn jurisdiction(&self) -> Option<&str>;
         fn is_disambiguate(&self, current_count: u32) -> bool;
         fn csl_type(&self) -> CslType;
         fn locator_type(&self) -> Option<LocatorType>;
@@ -81,11 +87,40 @@ impl<'a, O: OutputFormat, I: OutputFormat> GenericContext<'a, O, I> {
         cite.map_or(default_is_english, |l| l.is_english())
     }
 
+    /// The reference's language, or failing that the output locale's.
+    pub fn case_language(&self) -> CaseLanguage {
+        self.cite_lang()
+            .or_else(|| self.locale().lang.as_ref())
+            .map_or(CaseLanguage::default(), CaseLanguage::from_lang)
+    }
+
+    pub fn case_exceptions(&self) -> &CaseExceptions {
+        match self {
+            GenericContext::Cit(ctx) => &ctx.case_exceptions,
+            GenericContext::Ref(ctx) => &ctx.case_exceptions,
+        }
+    }
+
+    pub fn compat_mode(&self) -> CompatMode {
+        match self {
+            GenericContext::Cit(ctx) => ctx.compat_mode,
+            GenericContext::Ref(ctx) => ctx.compat_mode,
+        }
+    }
+
+    /// Authors the cite asked to leave out. Always empty when matching against references.
+    pub fn suppress_names(&self) -> &[NameSelector] {
+        match self {
+            GenericContext::Cit(ctx) => &ctx.cite.suppress_names,
+            GenericContext::Ref(_ctx) => &[],
+        }
+    }
+
     /// For setting display="X" on elements, where this should only take effect in the
     /// bibliography.
     pub fn in_bibliography(&self) -> bool {
         match self {
-            GenericContext::Cit(ctx) => ctx.in_bibliography,
+            GenericContext::Cit(ctx) => ctx.in_bibliography(),
             // Do not say "we're in a bibliography" if you're generating RefIR for cites to match
             // against.
             GenericContext::Ref(_ctx) => false,
@@ -107,6 +142,50 @@ impl<'a, O: OutputFormat, I: OutputFormat> GenericContext<'a, O, I> {
     }
 }
 
+/// How an element's content gets wrapped once it has been ingested, in the order the spec gives.
+/// Text-case and strip-periods come first, as part of ingesting the text. Then formatting, then
+/// quotes, then affixes outside all of that, so a prefix is never italicised or quoted. Display
+/// goes on the very outside.
+///
+/// Text, labels, numbers, groups and dates go through this, so none of them can get the order
+/// wrong on its own. Names do not: `names.rs` applies the formatting and affixes of each name part
+/// and of the `<name>` element itself. Only those of the `<names>` element, which are on the
+/// sequence it builds, go through here.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct RenderSeq<'a> {
+    pub formatting: Option<Formatting>,
+    pub quotes: Option<LocalizedQuotes>,
+    pub affixes: Option<&'a Affixes>,
+    pub display: Option<DisplayMode>,
+    pub in_bibliography: bool,
+}
+
+impl<'a> RenderSeq<'a> {
+    pub fn render<O: OutputFormat>(self, fmt: &O, b: O::Build) -> O::Build {
+        let b = fmt.with_format(b, self.formatting);
+        let b = fmt.affixed_quoted(b, self.affixes, self.quotes);
+        fmt.with_display(b, self.display, self.in_bibliography)
+    }
+
+    /// For content put together from several pieces (a group, a date), which could not be cased
+    /// as it was ingested. The case still goes on before anything else.
+    pub fn render_cased<O: OutputFormat>(
+        self,
+        fmt: &O,
+        mut b: O::Build,
+        text_case: TextCase,
+    ) -> O::Build {
+        if text_case != TextCase::None {
+            let options = IngestOptions {
+                text_case,
+                ..Default::default()
+            };
+            fmt.apply_text_case(&mut b, &options);
+        }
+        self.render(fmt, b)
+    }
+}
+
 pub struct Renderer<'a, O: OutputFormat, Custom: OutputFormat = O> {
     ctx: GenericContext<'a, O, Custom>,
 }
@@ -185,21 +264,20 @@ impl<'c, O: OutputFormat, I: OutputFormat> Renderer<'c, O, I> {
         }
     }
 
-    /// With variable="locator", this assumes ctx has a locator_type and will panic otherwise.
+    /// With variable="locator", ordinals take their gender from ctx's locator_type, or from the
+    /// default locator type if there isn't one.
     pub fn number(&self, number: &NumberElement, val: &NumericValue<'_>) -> O::Build {
         let locale = self.ctx.locale();
         debug!("number {:?}", val);
         let prf = self.page_range_format(number.variable);
-        let string = if let NumericValue::Tokens(_s, ts, true) = val {
+        let mut string = if let NumericValue::Tokens(_s, ts, true) = val {
             match number.form {
                 NumericForm::Roman if roman_representable(&val) => {
                     roman_lower(&ts, locale, number.variable, prf)
                 }
                 NumericForm::Ordinal | NumericForm::LongOrdinal => {
                     let loc_type = if number.variable == NumberVariable::Locator {
-                        self.ctx
-                            .locator_type()
-                            .expect("already known that locator exists and therefore has a type")
+                        self.ctx.locator_type().unwrap_or_default()
                     } else {
                         // Not used
                         LocatorType::default()
@@ -213,17 +291,28 @@ impl<'c, O: OutputFormat, I: OutputFormat> Renderer<'c, O, I> {
         } else {
             arabic_number(val, locale, number.variable, prf)
         };
+        if let (NumberVariable::Locator, GenericContext::Cit(ctx)) = (number.variable, &self.ctx) {
+            let and_term = locale.and_term(None).unwrap_or("and");
+            let prf = self.ctx.style().page_range_format;
+            write_sub_locators(&mut string, &ctx.sub_locators(), locale, and_term, prf);
+        }
         let fmt = self.fmt();
         let options = IngestOptions {
             text_case: number.text_case,
             quotes: self.quotes(),
             is_english: self.ctx.is_english(),
+            case_language: self.ctx.case_language(),
             ..Default::default()
         };
         let b = fmt.ingest(&string, &options);
-        let b = fmt.with_format(b, number.formatting);
-        let b = fmt.affixed(b, number.affixes.as_ref());
-        fmt.with_display(b, number.display, self.ctx.in_bibliography())
+        RenderSeq {
+            formatting: number.formatting,
+            affixes: number.affixes.as_ref(),
+            display: number.display,
+            in_bibliography: self.ctx.in_bibliography(),
+            ..Default::default()
+        }
+        .render(fmt, b)
     }
     pub fn quotes(&self) -> LocalizedQuotes {
         LocalizedQuotes::from_locale(self.ctx.locale())
@@ -275,6 +364,8 @@ impl<'c, O: OutputFormat, I: OutputFormat> Renderer<'c, O, I> {
             quotes: self.quotes(),
             strip_periods: text.strip_periods,
             is_english: self.ctx.is_english(),
+            case_language: self.ctx.case_language(),
+            case_exceptions: self.ctx.case_exceptions().clone(),
             ..Default::default()
         };
         let hyper = match var {
@@ -293,6 +384,8 @@ impl<'c, O: OutputFormat, I: OutputFormat> Renderer<'c, O, I> {
             quotes: self.quotes(),
             strip_periods: text.strip_periods,
             is_english: self.ctx.is_english(),
+            case_language: self.ctx.case_language(),
+            case_exceptions: self.ctx.case_exceptions().clone(),
             ..Default::default()
         };
         Some(self.render_text_el(value, text, &options, None))
@@ -314,6 +407,7 @@ impl<'c, O: OutputFormat, I: OutputFormat> Renderer<'c, O, I> {
                     quotes: self.quotes(),
                     strip_periods: text.strip_periods,
                     is_english: self.ctx.is_english(),
+                    case_language: self.ctx.case_language(),
                     ..Default::default()
                 };
                 self.render_text_el(val, text, &options, None)
@@ -329,11 +423,16 @@ impl<'c, O: OutputFormat, I: OutputFormat> Renderer<'c, O, I> {
     ) -> O::Build {
         let fmt = self.fmt();
         let mut affixes = text.affixes.as_ref();
-        let (mut b, fixed_af) = self.try_link(string, options, hyper, affixes);
+        let (b, fixed_af) = self.try_link(string, options, hyper, affixes);
         affixes = fixed_af.as_ref().or(affixes);
-        b = fmt.with_format(b, text.formatting);
-        b = fmt.affixed_quoted(b, affixes, self.quotes_if(text.quotes));
-        fmt.with_display(b, text.display, self.ctx.in_bibliography())
+        RenderSeq {
+            formatting: text.formatting,
+            quotes: self.quotes_if(text.quotes),
+            affixes,
+            display: text.display,
+            in_bibliography: self.ctx.in_bibliography(),
+        }
+        .render(fmt, b)
     }
 
     fn try_link(
@@ -355,11 +454,13 @@ impl<'c, O: OutputFormat, I: OutputFormat> Renderer<'c, O, I> {
         (fmt.ingest(string, options), None)
     }
 
+    /// `count` is how many names the label is for. With partial author suppression, that can be
+    /// fewer than the variable holds.
     pub fn name_label(
         &self,
         label: &NameLabel,
-        var: NameVariable,
         label_var: NameVariable,
+        count: usize,
     ) -> Option<O::Build> {
         let NameLabel {
             form,
@@ -372,9 +473,7 @@ impl<'c, O: OutputFormat, I: OutputFormat> Renderer<'c, O, I> {
         } = *label;
         let fmt = self.fmt();
         let selector = RoleTermSelector::from_name_variable(label_var, form);
-        let val = self.ctx.get_name(var);
-        let len = val.map(|v| v.len()).unwrap_or(0);
-        let plural = match (len, plural) {
+        let plural = match (count, plural) {
             (0, Plural::Contextual) => return None,
             (1, Plural::Contextual) => false,
             (_, Plural::Contextual) => true,
@@ -392,11 +491,16 @@ impl<'c, O: OutputFormat, I: OutputFormat> Renderer<'c, O, I> {
                         strip_periods,
                         quotes: self.quotes(),
                         is_english: self.ctx.is_english(),
+                        case_language: self.ctx.case_language(),
                         ..Default::default()
                     };
                     let b = fmt.ingest(term_text, &options);
-                    let b = fmt.with_format(b, formatting);
-                    fmt.affixed(b, affixes.as_ref())
+                    RenderSeq {
+                        formatting,
+                        affixes: affixes.as_ref(),
+                        ..Default::default()
+                    }
+                    .render(fmt, b)
                 })
         })
     }
@@ -420,8 +524,10 @@ impl<'c, O: OutputFormat, I: OutputFormat> Renderer<'c, O, I> {
         selector.and_then(|sel| {
             let options = IngestOptions {
                 text_case: label.text_case,
+                strip_periods: label.strip_periods,
                 quotes: self.quotes(),
                 is_english: self.ctx.is_english(),
+                case_language: self.ctx.case_language(),
                 ..Default::default()
             };
             self.ctx
@@ -430,8 +536,12 @@ impl<'c, O: OutputFormat, I: OutputFormat> Renderer<'c, O, I> {
                 .filter(|x| !x.is_empty())
                 .map(|val| {
                     let b = fmt.ingest(val, &options);
-                    let b = fmt.with_format(b, label.formatting);
-                    fmt.affixed(b, label.affixes.as_ref())
+                    RenderSeq {
+                        formatting: label.formatting,
+                        affixes: label.affixes.as_ref(),
+                        ..Default::default()
+                    }
+                    .render(fmt, b)
                 })
         })
     }
@@ -1,9 +1,10 @@
 use crate::db::{with_bib_context, with_cite_context};
 use crate::prelude::*;
 use citeproc_db::{ClusterData, ClusterId, ClusterNumber};
-use citeproc_io::{ClusterMode, DateOrRange};
-use csl::{style::*, terms::*, variables::*, Atom};
+use citeproc_io::{ClusterMode, DateOrRange, Reference};
+use csl::{style::*, terms::*, variables::*, Atom, Locale};
 use fnv::FnvHashMap;
+use std::borrow::Cow;
 use std::sync::Arc;
 
 mod lexical;
@@ -69,6 +70,27 @@ impl BibNumber {
     }
 }
 
+/// How citation numbers are assigned to references.
+///
+/// Separately from this, numbers can be frozen with
+/// [IrDatabase::set_frozen_citation_numbers]. A reference with a frozen number keeps it no
+/// matter what; the others are numbered after the highest frozen number, in the order this
+/// setting would give them.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum CitationNumbering {
+    /// Numbers follow the sorted bibliography. This is what CSL specifies, and the default.
+    BibliographyOrder,
+    /// Numbers follow the order references were first cited in, even if the bibliography is
+    /// sorted differently.
+    FirstCited,
+}
+
+impl Default for CitationNumbering {
+    fn default() -> Self {
+        CitationNumbering::BibliographyOrder
+    }
+}
+
 pub fn sorted_refs(db: &dyn IrDatabase) -> Arc<(Vec<Atom>, FnvHashMap<Atom, BibNumber>)> {
     let style = db.style();
     let bib = match style.bibliography {
@@ -109,7 +131,7 @@ pub fn sorted_refs(db: &dyn IrDatabase) -> Arc<(Vec<Atom>, FnvHashMap<Atom, BibN
 
     let max_cnum = preordered.len() as u32;
     let mut reverse = false;
-    let now_sorted = if db.bibliography_no_sort() {
+    let mut now_sorted = if db.bibliography_no_sort() {
         preordered
     } else if let Some(ref sort) = bib {
         preordered.sort_by_cached_key(|a| {
@@ -152,21 +174,76 @@ pub fn sorted_refs(db: &dyn IrDatabase) -> Arc<(Vec<Atom>, FnvHashMap<Atom, BibN
         // they are cited. The uncited ones come last.
         preordered
     };
-    for (i, ref_id) in now_sorted.iter().enumerate() {
-        let mut i = i as u32 + 1u32;
-        if reverse {
-            i = max_cnum + 1 - i;
+    if db.citation_numbering() == CitationNumbering::BibliographyOrder {
+        for (i, ref_id) in now_sorted.iter().enumerate() {
+            let mut i = i as u32 + 1u32;
+            if reverse {
+                i = max_cnum + 1 - i;
+            }
+            if let Some(bn) = citation_numbers.get_mut(&ref_id) {
+                set_bib_number(bn, i);
+            }
         }
-        if let Some(bn) = citation_numbers.get_mut(&ref_id) {
-            match bn {
-                BibNumber::Cited(x) => *x = i,
-                BibNumber::Uncited(x) => *x = i,
+    }
+    let frozen = db.frozen_citation_numbers();
+    if !frozen.is_empty() {
+        apply_frozen_numbers(&frozen, &mut citation_numbers);
+        // Where the bibliography is in citation number order, keep it that way.
+        let first_key = bib
+            .filter(|_| !db.bibliography_no_sort())
+            .and_then(|sort| sort.keys.first());
+        let cnum_direction = match first_key {
+            None => Some(SortDirection::Ascending),
+            Some(SortKey {
+                sort_source:
+                    SortSource::Variable(AnyVariable::Number(NumberVariable::CitationNumber)),
+                direction,
+                ..
+            }) => Some(direction.unwrap_or(SortDirection::Ascending)),
+            Some(_) => None,
+        };
+        if let Some(direction) = cnum_direction {
+            now_sorted.sort_by_key(|id| citation_numbers.get(id).map(BibNumber::get));
+            if direction == SortDirection::Descending {
+                now_sorted.reverse();
             }
         }
     }
     Arc::new((now_sorted, citation_numbers))
 }
 
+fn set_bib_number(bn: &mut BibNumber, i: u32) {
+    match bn {
+        BibNumber::Cited(x) => *x = i,
+        BibNumber::Uncited(x) => *x = i,
+    }
+}
+
+/// References with a frozen number get it back. The rest keep their relative order, numbered
+/// after the highest frozen number.
+fn apply_frozen_numbers(
+    frozen: &FnvHashMap<Atom, u32>,
+    citation_numbers: &mut FnvHashMap<Atom, BibNumber>,
+) {
+    let max_frozen = frozen.values().copied().max().unwrap_or(0);
+    let mut unfrozen: Vec<(u32, Atom)> = citation_numbers
+        .iter()
+        .filter(|(id, _)| !frozen.contains_key(*id))
+        .map(|(id, bn)| (bn.get(), id.clone()))
+        .collect();
+    unfrozen.sort();
+    for (i, (_, id)) in unfrozen.into_iter().enumerate() {
+        if let Some(bn) = citation_numbers.get_mut(&id) {
+            set_bib_number(bn, max_frozen + 1 + i as u32);
+        }
+    }
+    for (id, &number) in frozen {
+        if let Some(bn) = citation_numbers.get_mut(id) {
+            set_bib_number(bn, number);
+        }
+    }
+}
+
 pub fn clusters_cites_sorted(db: &dyn IrDatabase) -> Arc<Vec<ClusterData>> {
     let cluster_ids = db.clusters_ordered();
     let mut clusters: Vec<_> = cluster_ids
@@ -175,7 +252,10 @@ pub fn clusters_cites_sorted(db: &dyn IrDatabase) -> Arc<Vec<ClusterData>> {
         // Although may be disamb participant.
         .filter_map(|&id| db.cluster_data_sorted(id))
         .collect();
-    clusters.sort_by_key(|cluster| cluster.number);
+    // clusters_ordered is already in document order. Sorting by number would put every in-text
+    // cluster before every note cluster, which is wrong for documents that mix the two. Only
+    // move the clusters that are outside the flow of the document to the front.
+    clusters.sort_by_key(|cluster| cluster.number != ClusterNumber::OutsideFlow);
     Arc::new(clusters)
 }
 
@@ -197,13 +277,8 @@ pub fn cluster_data_sorted(db: &dyn IrDatabase, id: ClusterId) -> Option<Cluster
             let mut neu = (*cites).clone();
             let getter = |cite_id: &CiteId| -> Option<BibNumber> {
                 let cite = cite_id.lookup(db);
-                let cnum = db.reference(cite.ref_id.clone()).map(|refr| {
-                    citation_numbers_by_id
-                        .get(&refr.id)
-                        .expect("every cited reference should appear in sorted_refs")
-                        .clone()
-                });
-                cnum
+                db.reference(cite.ref_id.clone())
+                    .and_then(|refr| citation_numbers_by_id.get(&refr.id).cloned())
             };
             neu.sort_by_cached_key(|a| {
                 getter(a).map(|a_cnum| {
@@ -246,6 +321,92 @@ pub fn bib_number(db: &dyn IrDatabase, id: CiteId) -> Option<BibNumber> {
     lookup_ref_ids.get(&cite.ref_id).cloned()
 }
 
+/// One computed bibliography sort key for a reference. See [bib_sort_keys].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortKeyValue {
+    pub direction: SortDirection,
+    pub value: SortKeyData,
+}
+
+/// The value of a sort key. `None` means the key had no value for the reference, and such
+/// references sort last regardless of direction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SortKeyData {
+    /// A macro rendered as a sort string. Numbers and dates within it are delimited by private use
+    /// characters (U+E000 to U+E005) and compared numerically.
+    Macro(Option<SmartString>),
+    /// Produced by a `citation-number` key, or by a macro key that renders the citation number.
+    CitationNumber(Option<u32>),
+    Variable(Option<SmartString>),
+    Number(Option<citeproc_io::NumericValueOwned>),
+    /// One sort string per name.
+    Names(Option<Vec<SmartString>>),
+    Date(Option<DateOrRange>),
+}
+
+impl SortKeyValue {
+    fn from_item(item: SortItem) -> Self {
+        let value = match item.value {
+            SortValue::Macro(m) => SortKeyData::Macro(m.map(|m| m.0)),
+            SortValue::Cnum(c) => SortKeyData::CitationNumber(c),
+            SortValue::OrdinaryVariable(v) => SortKeyData::Variable(v.map(Natural::into_inner)),
+            SortValue::Number(n) => SortKeyData::Number(n),
+            SortValue::Names(n) => {
+                SortKeyData::Names(n.map(|n| n.into_iter().map(Natural::into_inner).collect()))
+            }
+            SortValue::Date(d) => SortKeyData::Date(d),
+        };
+        SortKeyValue {
+            direction: item.direction.unwrap_or(SortDirection::Ascending),
+            value,
+        }
+    }
+}
+
+/// Computes the bibliography sort keys for a reference, in the order they are compared. Returns
+/// `None` if the style has no bibliography `<sort>`, or the reference is not in the bibliography.
+///
+/// `citation-number` keys, and macros that render the citation number, get the reference's
+/// citation number as the processor ends up assigning it, i.e. after numbering in bibliography
+/// order or freezing numbers. The sort itself compares the order of first citation instead, as
+/// the final numbers are not known until it is done, so those keys can differ from what was
+/// compared.
+pub fn bib_sort_keys(db: &dyn IrDatabase, ref_id: Atom) -> Option<Vec<SortKeyValue>> {
+    let style = db.style();
+    let sort = style.bibliography.as_ref()?.sort.as_ref()?;
+    let sorted_refs = db.sorted_refs();
+    let (_, citation_numbers) = &*sorted_refs;
+    let cnum = *citation_numbers.get(&ref_id)?;
+    let max_cnum = citation_numbers.len() as u32;
+    let refr = db.reference(ref_id.clone())?;
+    let demoting = with_bib_context(
+        db,
+        ref_id,
+        Some(&*refr),
+        cnum.cited_only(),
+        None,
+        None,
+        |_, mut ctx| {
+            Some(ctx_sort_items(
+                db,
+                CiteOrBib::Bibliography,
+                &mut ctx,
+                cnum,
+                sort,
+                max_cnum,
+            ))
+        },
+        |_, _, _| None,
+    )?;
+    Some(
+        demoting
+            .items
+            .into_iter()
+            .map(SortKeyValue::from_item)
+            .collect(),
+    )
+}
+
 #[derive(Debug, PartialEq, Eq)]
 struct SortItem {
     direction: Option<SortDirection>,
@@ -334,6 +495,52 @@ fn compare_demoting_none<T: PartialOrd>(
     }
 }
 
+/// Strips a leading article before sorting. A reference in another language than the locale's
+/// uses the built-in list for its own language; otherwise the locale decides.
+pub(crate) fn strip_sort_article<'a>(refr: &Reference, locale: &Locale, s: &'a str) -> &'a str {
+    match refr.language {
+        Some(ref lang) if locale.lang.as_ref() != Some(lang) => lang.strip_leading_article(s),
+        _ => locale.strip_leading_article(s),
+    }
+}
+
+fn is_title_variable(var: Variable) -> bool {
+    match var {
+        Variable::Title
+        | Variable::TitleShort
+        | Variable::ContainerTitle
+        | Variable::ContainerTitleShort
+        | Variable::CollectionTitle
+        | Variable::VolumeTitle
+        | Variable::OriginalTitle
+        | Variable::ReviewedTitle
+        | Variable::EventTitle
+        | Variable::PartTitle => true,
+        _ => false,
+    }
+}
+
+/// An ordinary variable's value, as it should be sorted. A `sort-keys` entry on the reference
+/// wins; otherwise titles have their leading article stripped ("The Hobbit" sorts under H).
+fn ordinary_sort_value<'a, O: OutputFormat, I: OutputFormat>(
+    ctx: &'a CiteContext<'_, O, I>,
+    var: Variable,
+    form: VariableForm,
+) -> Option<Cow<'a, str>> {
+    if let Some(key) = ctx.reference.sort_keys.get(&var) {
+        return Some(Cow::Borrowed(key.as_str()));
+    }
+    let value = ctx.get_ordinary(var, form)?;
+    if !is_title_variable(var) {
+        return Some(value);
+    }
+    let (refr, locale) = (&ctx.reference, ctx.locale);
+    Some(match value {
+        Cow::Borrowed(s) => Cow::Borrowed(strip_sort_article(refr, locale, s)),
+        Cow::Owned(s) => Cow::Owned(strip_sort_article(refr, locale, &s).to_owned()),
+    })
+}
+
 fn ctx_sort_items(
     db: &dyn IrDatabase,
     // Cached lookup from (id, macro name, sort key) -> a comparable string
@@ -404,8 +611,7 @@ fn ctx_sort_items(
                     fn strip_markup(s: impl AsRef<str>) -> SmartString {
                         micro_html_to_string(s.as_ref(), &Default::default())
                     }
-                    let got = a_ctx
-                        .get_ordinary(v, VariableForm::default())
+                    let got = ordinary_sort_value(a_ctx, v, VariableForm::default())
                         .map(strip_markup)
                         .map(Natural::new);
                     SortValue::OrdinaryVariable(got)
@@ -421,12 +627,19 @@ fn ctx_sort_items(
                         v,
                         key,
                         cite_or_bib,
+                        a_ctx.locale,
                     );
                     SortValue::Names(a_strings)
                 }
                 // TODO: compare dates, using details from spec for ranges
                 AnyVariable::Date(v) => {
-                    let a_date = a_ctx.reference.date.get(&v);
+                    let dates = &a_ctx.reference.date;
+                    let a_date = match (v, db.reprint_sort()) {
+                        (DateVariable::Issued, ReprintSort::Original) => dates
+                            .get(&DateVariable::OriginalDate)
+                            .or_else(|| dates.get(&v)),
+                        _ => dates.get(&v),
+                    };
                     SortValue::Date(a_date.cloned())
                 }
             },
@@ -542,9 +755,7 @@ impl<'a, O: OutputFormat> StyleWalker for SortingWalker<'a, O> {
                     renderer.text_variable(text, svar, nval.verbatim())
                 }
             }),
-            StandardVariable::Ordinary(var) => self
-                .ctx
-                .get_ordinary(var, form)
+            StandardVariable::Ordinary(var) => ordinary_sort_value(&self.ctx, var, form)
                 .map(|val| renderer.text_variable(text, svar, &val)),
         };
         let gv = GroupVars::rendered_if(res.is_some());
@@ -618,11 +829,9 @@ impl<'a, O: OutputFormat> StyleWalker for SortingWalker<'a, O> {
 
     fn text_macro(&mut self, text: &TextElement, name: &SmartString) -> Self::Output {
         // TODO: same todos as in Proc
-        let style = self.ctx.style;
-        let macro_elements = style
-            .macros
-            .get(name)
-            .expect("undefined macro should not be valid CSL");
+        let modules = self.db.jurisdiction_modules();
+        let macro_elements =
+            crate::jurisdiction::lookup_macro(self.ctx.style, &modules, self.ctx.reference, name);
 
         self.state.push_macro(name);
         let ret = self.fold(macro_elements, WalkerFoldType::Macro(text));
@@ -1,6 +1,51 @@
+#[cfg(feature = "collation")]
 use lexical_sort::{lexical_cmp, natural_lexical_cmp};
 use std::cmp::Ordering;
 
+/// Stands in for lexical-sort's comparison without the `collation` feature. Ignores case, but
+/// does not transliterate, so "Ångström" sorts after "Zhou".
+#[cfg(not(feature = "collation"))]
+fn lexical_cmp(a: &str, b: &str) -> Ordering {
+    let lower = |s: &'_ str| s.chars().flat_map(char::to_lowercase).collect::<Vec<_>>();
+    lower(a).cmp(&lower(b))
+}
+
+/// Like [lexical_cmp], but runs of digits compare as numbers, so "Article 3" comes before
+/// "Article 20".
+#[cfg(not(feature = "collation"))]
+fn natural_lexical_cmp(a: &str, b: &str) -> Ordering {
+    use std::iter::Peekable;
+    use std::str::Chars;
+    fn digits(chars: &mut Peekable<Chars>) -> String {
+        let mut number = String::new();
+        while let Some(c) = chars.peek().copied().filter(char::is_ascii_digit) {
+            number.push(c);
+            chars.next();
+        }
+        number.trim_start_matches('0').to_owned()
+    }
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        let ord = match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (x, y) = (digits(&mut a), digits(&mut b));
+                x.len().cmp(&y.len()).then_with(|| x.cmp(&y))
+            }
+            (Some(x), Some(y)) => {
+                a.next();
+                b.next();
+                x.to_lowercase().cmp(y.to_lowercase())
+            }
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct Lexical<S: AsRef<str>>(S);
 
@@ -44,6 +89,9 @@ impl<S: AsRef<str>> Natural<S> {
     pub(crate) fn new(inner: S) -> Self {
         Natural(inner)
     }
+    pub(crate) fn into_inner(self) -> S {
+        self.0
+    }
 }
 impl<S: AsRef<str>> Eq for Natural<S> {}
 impl<S: AsRef<str>> PartialEq for Natural<S> {
@@ -267,7 +267,7 @@ impl<'a> Iterator for TokenIterator<'a> {
 use citeproc_io::SmartString;
 
 #[derive(Debug, PartialEq, Eq)]
-pub struct NaturalCmp(SmartString);
+pub struct NaturalCmp(pub(crate) SmartString);
 impl NaturalCmp {
     pub fn new(s: SmartString) -> Option<Self> {
         if s.is_empty() {
@@ -0,0 +1,51 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright © 2021 Corporation for Digital Scholarship
+
+use citeproc_io::SmartString;
+use csl::Atom;
+use std::fmt;
+
+/// What kind of problem a [`Warning`] is about.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum WarningCode {
+    /// The style asked for a term that neither the locale nor its fallbacks define.
+    MissingTerm,
+    /// Something was left out to stay within the processor's resource limits.
+    LimitExceeded,
+    /// A field's markup has tags the renderer does not understand, or that do not match up. They
+    /// are ignored or repaired, so the field may not look as its author meant.
+    FieldMarkup,
+}
+
+/// A non-fatal problem found while rendering. The output is still produced, but may not be
+/// what the style author intended.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Warning {
+    pub code: WarningCode,
+    pub message: SmartString,
+    /// The reference being rendered at the time, if any.
+    pub ref_id: Option<Atom>,
+}
+
+impl Warning {
+    pub fn new(code: WarningCode, message: impl Into<SmartString>, ref_id: Option<Atom>) -> Self {
+        Warning {
+            code,
+            message: message.into(),
+            ref_id,
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message)?;
+        if let Some(ref_id) = &self.ref_id {
+            write!(f, " (rendering reference {})", ref_id)?;
+        }
+        Ok(())
+    }
+}
@@ -93,7 +93,7 @@ describe("batchedUpdates", () => {
             oneOneOne(driver);
             let full = driver.fullRender();
             expect(full.allClusters).toHaveProperty("one", "TEST_TITLE");
-            expect(full.bibEntries).toContainEqual({ id: "citekey", value: "TEST_TITLE" });
+            expect(full.bibEntries).toContainEqual({ id: "citekey", anchor: "ref-citekey", value: "TEST_TITLE" });
         })
     });
 
@@ -190,6 +190,64 @@ describe("batchedUpdates", () => {
         })
     });
 
+    test("batchedDiff sends only changes, and bibliography moves", () => {
+        withDriver({ style: bibStyle }, driver => {
+            oneOneOne(driver);
+            let diff = driver.batchedDiff();
+            expect(diff.clusters).toEqual({ one: "TEST_TITLE" });
+            expect(diff.bibliography?.moves).toEqual([{ id: "citekey", index: 0 }]);
+
+            driver.insertReference({ id: "added", type: "book", title: "ADDED" });
+            driver.insertCluster({ id: "two", cites: [{ id: "added" }] });
+            driver.setClusterOrder([{ id: "two" }, { id: "one" }]);
+            diff = driver.batchedDiff({ idsOnly: true });
+            expect(diff.clusters).toBeUndefined();
+            expect(diff.clusterIds).toEqual(["two"]);
+            expect(diff.bibliography?.updatedIds).toEqual(["added"]);
+            expect(diff.bibliography?.moves).toEqual([{ id: "added", index: 0 }]);
+
+            driver.setClusterOrder([{ id: "two" }]);
+            diff = driver.batchedDiff();
+            expect(diff.bibliography?.removed).toEqual(["citekey"]);
+            expect(diff.bibliography?.moves).toEqual([]);
+        })
+    });
+
+});
+
+describe("bibliography", () => {
+    let bibStyle = mkNoteStyle(
+        '<text variable="title" />',
+        `<bibliography>
+            <layout>
+                <text variable = "title" />
+            </layout>
+        </bibliography>`
+    );
+
+    test("includeUncited takes a list of ids", () => {
+        withDriver({ style: bibStyle }, driver => {
+            oneOneOne(driver);
+            driver.insertReference({ id: "uncited", type: "article-journal", title: "UNCITED" });
+            driver.insertReference({ id: "other", type: "book", title: "OTHER" });
+            driver.includeUncited(["uncited"]);
+            expect(driver.makeBibliography().map(e => e.id)).toEqual(["citekey", "uncited"]);
+            driver.includeUncited("All");
+            expect(driver.makeBibliography().length).toBe(3);
+            driver.includeUncited("None");
+            expect(driver.makeBibliography().map(e => e.id)).toEqual(["citekey"]);
+        })
+    });
+
+    test("makeBibliography with a filter", () => {
+        withDriver({ style: bibStyle }, driver => {
+            oneOneOne(driver);
+            driver.insertReference({ id: "uncited", type: "article-journal", title: "UNCITED" });
+            driver.includeUncited("All");
+            let articles = driver.makeBibliography({ select: [{ field: "type", value: "article-journal" }] });
+            expect(articles).toEqual([{ id: "uncited", anchor: "ref-uncited", value: "UNCITED" }]);
+        })
+    });
 });
 
 describe("previewCluster", () => {
@@ -0,0 +1,213 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright © 2021 Corporation for Digital Scholarship
+
+//! A smaller alternative to `UpdateSummary` for `Driver.batchedDiff`. Only what changed crosses
+//! into JS, keyed by id, and bibliography reordering is a list of moves rather than every entry
+//! id again. Maps are sorted by id, so the same update always serializes the same way.
+
+use citeproc::prelude::*;
+use citeproc::BibliographyUpdate;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DiffOptions {
+    pub ids_only: bool,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateDiff {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clusters: Option<BTreeMap<SmartString, Arc<SmartString>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cluster_ids: Option<Vec<SmartString>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bibliography: Option<BibliographyDiff>,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct BibliographyDiff {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated: Option<BTreeMap<Atom, Arc<SmartString>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_ids: Option<Vec<Atom>>,
+    pub removed: Vec<Atom>,
+    pub moves: Vec<BibliographyMove>,
+}
+
+/// Put the entry `id` at `index`, taking it out of its old place first if it had one.
+#[derive(Serialize)]
+pub struct BibliographyMove {
+    pub id: Atom,
+    pub index: usize,
+}
+
+impl UpdateDiff {
+    pub fn new(
+        clusters: Vec<(SmartString, Arc<SmartString>)>,
+        bibliography: Option<BibliographyDiff>,
+        ids_only: bool,
+    ) -> Self {
+        let mut diff = UpdateDiff {
+            bibliography,
+            ..Default::default()
+        };
+        if ids_only {
+            diff.cluster_ids = Some(clusters.into_iter().map(|(id, _)| id).collect());
+        } else {
+            diff.clusters = Some(clusters.into_iter().collect());
+        }
+        diff
+    }
+}
+
+impl BibliographyDiff {
+    /// `order` is the entry order the JS side last saw. It is updated to the new order.
+    pub fn new(update: BibliographyUpdate, order: &mut Vec<Atom>, ids_only: bool) -> Self {
+        let mut diff = BibliographyDiff::default();
+        let updated: BTreeMap<Atom, Arc<SmartString>> =
+            update.updated_entries.into_iter().collect();
+        if ids_only {
+            diff.updated_ids = Some(updated.into_iter().map(|(k, _)| k).collect());
+        } else {
+            diff.updated = Some(updated);
+        }
+        if let Some(new_order) = update.entry_ids {
+            let (removed, moves) = reorder(order, &new_order);
+            diff.removed = removed;
+            diff.moves = moves;
+            *order = new_order;
+        }
+        diff
+    }
+}
+
+/// The removals, then the moves, that turn `old` into `new`. Only the entries outside one longest
+/// run that is already in the new order are moved, so e.g. moving the first entry to the end is a
+/// single move.
+fn reorder(old: &[Atom], new: &[Atom]) -> (Vec<Atom>, Vec<BibliographyMove>) {
+    let new_index: HashMap<&Atom, usize> = new.iter().enumerate().map(|(i, id)| (id, i)).collect();
+    let removed: Vec<Atom> = old
+        .iter()
+        .filter(|id| !new_index.contains_key(id))
+        .cloned()
+        .collect();
+    let mut current: Vec<Atom> = old
+        .iter()
+        .filter(|id| new_index.contains_key(id))
+        .cloned()
+        .collect();
+    let indices: Vec<usize> = current.iter().map(|id| new_index[id]).collect();
+    let stable: HashSet<usize> = longest_increasing(&indices)
+        .into_iter()
+        .map(|pos| indices[pos])
+        .collect();
+    let mut moves = Vec::new();
+    for (index, id) in new.iter().enumerate() {
+        if stable.contains(&index) {
+            continue;
+        }
+        if let Some(pos) = current.iter().position(|x| x == id) {
+            current.remove(pos);
+        }
+        // Straight after the entry before it in `new`, which is already where it should be.
+        let to = match index.checked_sub(1) {
+            Some(prev) => current
+                .iter()
+                .position(|x| *x == new[prev])
+                .map_or(0, |pos| pos + 1),
+            None => 0,
+        };
+        current.insert(to, id.clone());
+        moves.push(BibliographyMove {
+            id: id.clone(),
+            index: to,
+        });
+    }
+    (removed, moves)
+}
+
+/// The positions in `seq` of one of its longest increasing subsequences.
+fn longest_increasing(seq: &[usize]) -> Vec<usize> {
+    // tails[k] is the position of the smallest value that ends an increasing run of length k + 1
+    let mut tails: Vec<usize> = Vec::new();
+    let mut prev = vec![None; seq.len()];
+    for (pos, &x) in seq.iter().enumerate() {
+        let k = match tails.binary_search_by(|&t| seq[t].cmp(&x)) {
+            Ok(k) | Err(k) => k,
+        };
+        if k > 0 {
+            prev[pos] = Some(tails[k - 1]);
+        }
+        if k == tails.len() {
+            tails.push(pos);
+        } else {
+            tails[k] = pos;
+        }
+    }
+    let mut run = Vec::with_capacity(tails.len());
+    let mut next = tails.last().copied();
+    while let Some(pos) = next {
+        run.push(pos);
+        next = prev[pos];
+    }
+    run.reverse();
+    run
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn atoms(ids: &[&str]) -> Vec<Atom> {
+        ids.iter().map(|&id| Atom::from(id)).collect()
+    }
+
+    /// Checks the moves against `new` by applying them to `old`, and returns them.
+    fn moves(old: &[&str], new: &[&str]) -> Vec<(String, usize)> {
+        let (old, new) = (atoms(old), atoms(new));
+        let (removed, moves) = reorder(&old, &new);
+        let mut applied: Vec<Atom> = old.into_iter().filter(|id| !removed.contains(id)).collect();
+        for mv in &moves {
+            if let Some(pos) = applied.iter().position(|id| *id == mv.id) {
+                applied.remove(pos);
+            }
+            applied.insert(mv.index, mv.id.clone());
+        }
+        assert_eq!(applied, new);
+        moves
+            .into_iter()
+            .map(|mv| (mv.id.to_string(), mv.index))
+            .collect()
+    }
+
+    #[test]
+    fn reorder_rotation() {
+        let abcd = &["a", "b", "c", "d"];
+        assert_eq!(moves(abcd, &["b", "c", "d", "a"]), vec![("a".into(), 3)]);
+        assert_eq!(moves(abcd, &["d", "a", "b", "c"]), vec![("d".into(), 0)]);
+    }
+
+    #[test]
+    fn reorder_swap() {
+        assert_eq!(moves(&["a", "b"], &["b", "a"]).len(), 1);
+        assert_eq!(moves(&["a", "b", "c", "d"], &["a", "c", "b", "d"]).len(), 1);
+    }
+
+    #[test]
+    fn reorder_no_op() {
+        assert!(moves(&["a", "b", "c"], &["a", "b", "c"]).is_empty());
+        // Removing and adding entries moves nothing else
+        let (removed, _) = reorder(&atoms(&["a", "b", "c"]), &atoms(&["a", "c"]));
+        assert_eq!(removed, atoms(&["b"]));
+        assert!(moves(&["a", "b", "c"], &["a", "c"]).is_empty());
+        assert_eq!(moves(&["a", "c"], &["a", "b", "c"]), vec![("b".into(), 1)]);
+    }
+}
@@ -20,6 +20,10 @@ pub enum Error {
 
     #[error("Unknown output format {0:?}")]
     UnknownOutputFormat(String),
+    #[error("Output format {0:?} was left out of this build")]
+    UnavailableOutputFormat(String),
+    #[error("Unknown format mode {0:?}, expected \"citation\" or \"bibliography\"")]
+    UnknownFormatMode(String),
     #[error("Unknown CSL feature {0:?}")]
     UnknownCSLFeature(String),
     #[error("JSON Deserialization Error: {0}")]
@@ -30,6 +34,12 @@ pub enum Error {
     ),
     #[error("Invalid fetcher object: {0}")]
     GetFetcherError(#[from] GetFetcherError),
+    #[error("Locale fetch error: {0}")]
+    LocaleFetchError(
+        #[from]
+        #[serde(skip_serializing)]
+        citeproc::prelude::LocaleFetchError,
+    ),
     #[error("Non-Existent Cluster id: {0}")]
     NonExistentCluster(String),
     #[error("Reordering error: {0}")]
@@ -39,6 +49,12 @@ pub enum Error {
         string_id::ReorderingError,
     ),
 
+    #[error("Limit exceeded: {0}")]
+    LimitExceeded(String),
+
+    #[error("Style bundle error: {0}")]
+    Bundle(String),
+
     // This should not be necessary
     #[error("Reordering error: {0}")]
     ReorderingErrorNumericId(
@@ -48,6 +64,22 @@ pub enum Error {
     ),
 }
 
+impl From<citeproc::Error> for Error {
+    fn from(e: citeproc::Error) -> Self {
+        match e {
+            citeproc::Error::Style(e) => Error::StyleError(e),
+            citeproc::Error::LocaleFetch(e) => Error::LocaleFetchError(e),
+            citeproc::Error::Json(e) => Error::JsonError(e),
+            citeproc::Error::Reordering(e) => Error::ReorderingErrorNumericId(e),
+            citeproc::Error::LimitExceeded(e) => Error::LimitExceeded(e.to_string()),
+            citeproc::Error::Bundle(e) => Error::Bundle(e.to_string()),
+            citeproc::Error::UnavailableFormat(format) => {
+                Error::UnavailableOutputFormat(format.as_str().to_owned())
+            }
+        }
+    }
+}
+
 fn style_error_to_js_err(se: &StyleError) -> JsValue {
     let mut string = se.to_string();
     let data = JsValue::from_serde(&se);
@@ -135,10 +167,19 @@ type StyleError = {
 type DriverError = {
     tag: "UnknownOutputFormat",
     content: string,
+} | {
+    /** The format, e.g. "rtf", was left out of this build with a Cargo feature. */
+    tag: "UnavailableOutputFormat",
+    content: string,
+} | {
+    tag: "UnknownFormatMode",
+    content: string,
 } | {
     tag: "JsonError",
 } | {
     tag: "GetFetcherError",
+} | {
+    tag: "LocaleFetchError",
 } | {
     tag: "NonExistentCluster",
     content: string,
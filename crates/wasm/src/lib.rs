@@ -12,6 +12,7 @@ mod utils;
 pub mod typescript;
 #[macro_use]
 pub mod errors;
+mod diff;
 mod options;
 
 pub use errors::Error;
@@ -30,7 +31,7 @@ use wasm_bindgen_futures::{future_to_promise, JsFuture};
 
 use citeproc::prelude::*;
 use citeproc::string_id;
-use csl::{Lang, StyleMeta};
+use csl::{CiteOrBib, Lang, StyleMeta};
 
 /// Parses a CSL style, either independent or dependent, and returns its metadata.
 #[wasm_bindgen]
@@ -43,6 +44,9 @@ pub fn parseStyleMetadata(style: &str) -> Result<typescript::StyleMeta, Error> {
 pub struct Driver {
     engine: Rc<RefCell<Processor>>,
     fetcher: Option<Fetcher>,
+    /// The bibliography order as of the last `batchedUpdates`, `batchedDiff`, `fullRender` or
+    /// `drain`, to work out `BibliographyDiff.moves` from.
+    bib_order: RefCell<Vec<Atom>>,
 }
 
 #[wasm_bindgen]
@@ -75,6 +79,7 @@ impl Driver {
             locale_override: options.locale_override,
             test_mode: false,
             csl_features: Some(csl_features),
+            style_mode: options.style_mode,
             ..Default::default()
         };
         let engine = Processor::new(init)?;
@@ -86,7 +91,11 @@ impl Driver {
         let engine = Rc::new(RefCell::new(engine));
         // The Driver manually adds locales fetched via Fetcher, which asks the consumer
         // asynchronously.
-        Ok(Driver { engine, fetcher })
+        Ok(Driver {
+            engine,
+            fetcher,
+            bib_order: RefCell::new(Vec::new()),
+        })
     }
 
     /// Sets the style (which will also cause everything to be recomputed, use sparingly)
@@ -118,15 +127,15 @@ impl Driver {
             .unwrap_or_else(Default::default);
         self.engine
             .borrow_mut()
-            .set_output_format(format, format_options);
+            .set_output_format(format, format_options)?;
         Ok(())
     }
 
     /// Completely overwrites the references library.
     /// This **will** delete references that are not in the provided list.
     #[wasm_bindgen(js_name = "resetReferences")]
-    pub fn reset_references(&self, refs: Box<[JsValue]>) -> Result<(), Error> {
-        let refs = utils::read_js_array_2(refs)?;
+    pub fn reset_references(&self, refs: typescript::ReferenceArray) -> Result<(), Error> {
+        let refs = refs.ts_deserialize()?;
         self.engine.borrow_mut().reset_references(refs);
         Ok(())
     }
@@ -134,8 +143,8 @@ impl Driver {
     /// Inserts or overwrites references as a batch operation.
     /// This **will not** delete references that are not in the provided list.
     #[wasm_bindgen(js_name = "insertReferences")]
-    pub fn insert_references(&self, refs: Box<[JsValue]>) -> Result<(), Error> {
-        let refs = utils::read_js_array_2(refs)?;
+    pub fn insert_references(&self, refs: typescript::ReferenceArray) -> Result<(), Error> {
+        let refs = refs.ts_deserialize()?;
         self.engine.borrow_mut().extend_references(refs);
         Ok(())
     }
@@ -162,11 +171,11 @@ impl Driver {
 
     /// Sets the references to be included in the bibliography despite not being directly cited.
     ///
-    /// * `refr` is a
+    /// * `uncited` is `"All"`, `"None"`, or an array of reference ids.
     #[wasm_bindgen(js_name = "includeUncited")]
     pub fn include_uncited(&self, uncited: typescript::IncludeUncited) -> Result<(), Error> {
-        let uncited = uncited.into_serde()?;
-        self.engine.borrow_mut().include_uncited(uncited);
+        let uncited = uncited.ts_deserialize()?;
+        self.engine.borrow_mut().include_uncited(uncited.into());
         Ok(())
     }
 
@@ -212,8 +221,8 @@ impl Driver {
     ///
     /// * `clusters` is a Cluster[]
     #[wasm_bindgen(js_name = "initClusters")]
-    pub fn init_clusters(&self, clusters: Box<[JsValue]>) -> Result<(), Error> {
-        let clusters: Vec<string_id::Cluster> = utils::read_js_array_2(clusters)?;
+    pub fn init_clusters(&self, clusters: typescript::ClusterArray) -> Result<(), Error> {
+        let clusters = clusters.ts_deserialize()?;
         self.engine.borrow_mut().init_clusters_str(clusters);
         Ok(())
     }
@@ -237,7 +246,7 @@ impl Driver {
     pub fn preview_citation_cluster(
         &self,
         cites: Box<[JsValue]>,
-        positions: Box<[JsValue]>,
+        positions: typescript::ClusterPositionArray,
         format: Option<String>,
     ) -> Result<String, Error> {
         let cites = utils::read_js_array_2(cites)?;
@@ -259,7 +268,7 @@ impl Driver {
     pub fn preview_cluster(
         &self,
         preview_cluster: typescript::PreviewCluster,
-        positions: Box<[JsValue]>,
+        positions: typescript::ClusterPositionArray,
         format: Option<String>,
     ) -> Result<String, Error> {
         let preview_cluster: PreviewCluster = preview_cluster.into_serde()?;
@@ -270,10 +279,10 @@ impl Driver {
     fn preview_cluster_inner(
         &self,
         preview_cluster: PreviewCluster,
-        positions: Box<[JsValue]>,
+        positions: typescript::ClusterPositionArray,
         format: Option<String>,
     ) -> Result<String, Error> {
-        let positions: Vec<string_id::ClusterPosition> = utils::read_js_array_2(positions)?;
+        let positions = positions.ts_deserialize()?;
         let mut eng = self.engine.borrow_mut();
         let preview = eng.preview_citation_cluster(
             preview_cluster,
@@ -288,10 +297,76 @@ impl Driver {
         Ok(preview.to_string())
     }
 
+    /// Formats each reference on its own, without creating any clusters, e.g. for a "copy
+    /// formatted citation" feature. Returns one string per id, in the same order.
+    ///
+    /// - `ids`: a string[] of reference ids.
+    /// - `mode`: either `"citation"` or `"bibliography"`.
+    #[wasm_bindgen(js_name = "formatReferences")]
+    pub fn format_references(
+        &self,
+        ids: typescript::IdArray,
+        mode: &str,
+    ) -> Result<typescript::StringArray, Error> {
+        let ids = ids.ts_deserialize()?;
+        let ids: Vec<Atom> = ids.iter().map(|id| Atom::from(id.as_str())).collect();
+        let mode = match mode {
+            "citation" => CiteOrBib::Citation,
+            "bibliography" => CiteOrBib::Bibliography,
+            _ => return Err(Error::UnknownFormatMode(mode.into())),
+        };
+        let formatted = self.engine.borrow_mut().format_references(&ids, mode);
+        formatted.serialize_jsvalue()
+    }
+
+    /// Renders one of the style's macros for a reference on its own, e.g. just its authors, or
+    /// returns `undefined` if there is no such reference or macro, or it renders nothing.
+    ///
+    /// - `mode`: either `"citation"` or `"bibliography"`, for the context to render it in.
+    #[wasm_bindgen(js_name = "renderMacro")]
+    pub fn render_macro(
+        &self,
+        name: &str,
+        ref_id: &str,
+        mode: &str,
+    ) -> Result<Option<String>, Error> {
+        let mode = match mode {
+            "citation" => CiteOrBib::Citation,
+            "bibliography" => CiteOrBib::Bibliography,
+            _ => return Err(Error::UnknownFormatMode(mode.into())),
+        };
+        let eng = self.engine.borrow();
+        let rendered = eng.render_macro(name, Atom::from(ref_id), mode);
+        Ok(rendered.map(|s| s.to_string()))
+    }
+
+    /// A reference's authors as the style formats them in a first cite, e.g. "Doe et al.", or
+    /// `undefined` if there is no such reference or it has no authors.
+    ///
+    /// - `max`: optionally, show at most this many names before "et al.", instead of following
+    ///   the style.
+    #[wasm_bindgen(js_name = "authorList")]
+    pub fn author_list(&self, ref_id: &str, max: Option<u32>) -> Option<String> {
+        let eng = self.engine.borrow();
+        let authors = eng.author_list(Atom::from(ref_id), max);
+        authors.map(|s| s.to_string())
+    }
+
+    /// Returns the bibliography entries, in order.
+    ///
+    /// - `filter`: optionally, only include the references that pass this `BibliographyFilter`,
+    ///   e.g. for a per-chapter or per-type bibliography. Numbering and disambiguation still take
+    ///   the whole bibliography into account.
     #[wasm_bindgen(js_name = "makeBibliography")]
-    pub fn make_bibliography(&self) -> Result<typescript::BibEntries, Error> {
+    pub fn make_bibliography(
+        &self,
+        filter: Option<typescript::BibliographyFilter>,
+    ) -> Result<typescript::BibEntries, Error> {
         let eng = self.engine.borrow();
-        let bib = eng.get_bibliography();
+        let bib = match filter {
+            Some(filter) => eng.get_bibliography_filtered(&filter.ts_deserialize()?),
+            None => eng.get_bibliography(),
+        };
         bib.serialize_jsvalue()
     }
 
@@ -326,8 +401,11 @@ impl Driver {
     ///
     /// May error without having set_cluster_ids, but with some set_cluster_note_number-s executed.
     #[wasm_bindgen(js_name = "setClusterOrder")]
-    pub fn set_cluster_order(&self, positions: Box<[JsValue]>) -> Result<(), Error> {
-        let positions: Vec<string_id::ClusterPosition> = utils::read_js_array_2(positions)?;
+    pub fn set_cluster_order(
+        &self,
+        positions: typescript::ClusterPositionArray,
+    ) -> Result<(), Error> {
+        let positions = positions.ts_deserialize()?;
         let mut eng = self.engine.borrow_mut();
         eng.set_cluster_order_str(&positions)?;
         Ok(())
@@ -344,9 +422,50 @@ impl Driver {
     pub fn batched_updates(&self) -> Result<typescript::UpdateSummary, Error> {
         let eng = self.engine.borrow();
         let summary = eng.batched_updates_str();
+        if let Some(ids) = summary
+            .bibliography
+            .as_ref()
+            .and_then(|bib| bib.entry_ids.as_ref())
+        {
+            *self.bib_order.borrow_mut() = ids.clone();
+        }
         summary.serialize_jsvalue()
     }
 
+    /// Records input changes, so that `batchedUpdates` can report in `causes` which of them each
+    /// updated cluster depends on. For debugging unexpected re-renders; leave it off otherwise.
+    #[wasm_bindgen(js_name = "setInvalidationTracing")]
+    pub fn set_invalidation_tracing(&self, enabled: bool) {
+        self.engine.borrow_mut().set_invalidation_tracing(enabled);
+    }
+
+    /// Like `batchedUpdates`, but smaller, for calling on every keystroke. Changed clusters are
+    /// keyed by id, and a reordered bibliography comes as removals and moves instead of the
+    /// whole list of entry ids. Shares its queue with `batchedUpdates`.
+    ///
+    /// With `{ idsOnly: true }`, only the ids of changed clusters and bibliography entries are
+    /// returned, so you can fetch the ones on screen with `builtCluster` and `makeBibliography`.
+    ///
+    /// * returns an `UpdateDiff`
+    #[wasm_bindgen(js_name = "batchedDiff")]
+    pub fn batched_diff(
+        &self,
+        options: Option<typescript::DiffOptions>,
+    ) -> Result<typescript::UpdateDiff, Error> {
+        let options = options
+            .map(|o| o.ts_deserialize())
+            .transpose()?
+            .unwrap_or_default();
+        let eng = self.engine.borrow();
+        let summary = eng.batched_updates_str();
+        let mut order = self.bib_order.borrow_mut();
+        let bibliography = summary
+            .bibliography
+            .map(|bib| diff::BibliographyDiff::new(bib, &mut order, options.ids_only));
+        let diff = diff::UpdateDiff::new(summary.clusters, bibliography, options.ids_only);
+        diff.serialize_jsvalue()
+    }
+
     /// Returns all the clusters and bibliography entries in the document.
     /// Also drains the queue, just like batchedUpdates().
     /// Use this to rehydrate a document or run non-interactively.
@@ -360,6 +479,7 @@ impl Driver {
             bib_entries,
         };
         eng.drain();
+        self.reset_bib_order(&eng);
         all.serialize_jsvalue()
     }
 
@@ -368,6 +488,13 @@ impl Driver {
     pub fn drain(&self) {
         let mut eng = self.engine.borrow_mut();
         eng.drain();
+        self.reset_bib_order(&eng);
+    }
+
+    /// After a full render or a drain, the JS side is assumed to be up to date, so later
+    /// `BibliographyDiff.moves` start from the current order.
+    fn reset_bib_order(&self, eng: &Processor) {
+        *self.bib_order.borrow_mut() = eng.sorted_refs().0.clone();
     }
 
     /// Asynchronously fetches all the locales that may be required, and saves them into the
@@ -1,29 +1,99 @@
 use crate::Fetcher;
 use citeproc::prelude::*;
 use csl::Lang;
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
 use wasm_bindgen::prelude::*;
 
-#[derive(Deserialize)]
-#[serde(rename_all = "camelCase", remote = "FormatOptions")]
-pub(crate) struct JsFormatOptions {
-    #[serde(default = "bool_true")]
-    link_anchors: bool,
+/// The top-level options are shorthands that apply to every format that has them. The `html`,
+/// `rtf` and `plain` objects override them for one format.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct JsFormatOptions {
+    link_anchors: Option<bool>,
+    ascii_only: Option<bool>,
+    bidi_isolation: Option<bool>,
+    entry_anchors: Option<bool>,
+    html: JsFormatOptionsFor,
+    rtf: JsFormatOptionsFor,
+    plain: JsFormatOptionsFor,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct JsFormatOptionsFor {
+    link_anchors: Option<bool>,
+    ascii_only: Option<bool>,
+    bidi_isolation: Option<bool>,
+    entry_anchors: Option<bool>,
+}
+
+impl From<JsFormatOptions> for FormatOptions {
+    fn from(js: JsFormatOptions) -> Self {
+        let pick = |specific: Option<bool>, shorthand: Option<bool>, default: bool| {
+            specific.or(shorthand).unwrap_or(default)
+        };
+        let d = FormatOptions::default();
+        FormatOptions {
+            html: HtmlOptions {
+                link_anchors: pick(js.html.link_anchors, js.link_anchors, d.html.link_anchors),
+                bidi_isolation: pick(
+                    js.html.bidi_isolation,
+                    js.bidi_isolation,
+                    d.html.bidi_isolation,
+                ),
+                entry_anchors: pick(
+                    js.html.entry_anchors,
+                    js.entry_anchors,
+                    d.html.entry_anchors,
+                ),
+            },
+            rtf: RtfOptions {
+                link_anchors: pick(js.rtf.link_anchors, js.link_anchors, d.rtf.link_anchors),
+            },
+            plain: PlainOptions {
+                ascii_only: pick(js.plain.ascii_only, js.ascii_only, d.plain.ascii_only),
+                bidi_isolation: pick(
+                    js.plain.bidi_isolation,
+                    js.bidi_isolation,
+                    d.plain.bidi_isolation,
+                ),
+            },
+        }
+    }
 }
 
-fn bool_true() -> bool {
-    true
+fn deserialize_format_options<'de, D>(d: D) -> Result<FormatOptions, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    JsFormatOptions::deserialize(d).map(FormatOptions::from)
 }
 
-/// `remote = "FormatOptions` means it doesn't implement `DeserializeOwned`, which we need to use
-/// `JsValue::into_serde()`. A wrapper works.
+/// FormatOptions doesn't implement `Deserialize`, which we need to use `JsValue::into_serde()`. A
+/// wrapper works.
 #[derive(Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct FormatOptionsArg (
-    #[serde(with = "JsFormatOptions")]
+pub struct FormatOptionsArg(
+    #[serde(deserialize_with = "deserialize_format_options")]
     pub FormatOptions,
 );
 
+/// Accepts a plain array of ids as well as the core `IncludeUncited` forms.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum IncludeUncitedArg {
+    Specific(Vec<String>),
+    Core(IncludeUncited),
+}
+
+impl From<IncludeUncitedArg> for IncludeUncited {
+    fn from(arg: IncludeUncitedArg) -> Self {
+        match arg {
+            IncludeUncitedArg::Specific(ids) => IncludeUncited::Specific(ids),
+            IncludeUncitedArg::Core(core) => core,
+        }
+    }
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct WasmInitOptions {
@@ -38,7 +108,7 @@ pub struct WasmInitOptions {
     #[serde(default)]
     pub format: SupportedFormat,
 
-    #[serde(default, with = "JsFormatOptions")]
+    #[serde(default, deserialize_with = "deserialize_format_options")]
     pub format_options: FormatOptions,
 
     /// You might get this from a dependent style via `StyleMeta::parse(dependent_xml_string)`
@@ -47,6 +117,9 @@ pub struct WasmInitOptions {
     /// Disables sorting on the bibliography
     #[serde(default)]
     pub bibliography_no_sort: bool,
+    /// Lenient, strict CSL or CSL-M
+    #[serde(default)]
+    pub style_mode: StyleMode,
 }
 
 #[wasm_bindgen]
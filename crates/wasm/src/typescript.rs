@@ -88,8 +88,23 @@ typescript_deserialize!(
 // wasm-bindgen pointer).
 #[wasm_bindgen(typescript_custom_section)]
 const TS_APPEND_CONTENT_1: &'static str = r#"
-interface FormatOptions {
+interface FormatOptionsFor {
+    /** HTML and RTF: make URLs, DOIs, etc. clickable (default: true) */
     linkAnchors?: boolean;
+    /** Plain text only: use ASCII quotes, dashes and spaces instead of the locale's. */
+    asciiOnly?: boolean;
+    /** HTML and plain text: isolate right-to-left runs so surrounding punctuation stays put. */
+    bidiIsolation?: boolean;
+    /** HTML only: start each bibliography entry with an `<a>` whose id is its anchor (default: false) */
+    entryAnchors?: boolean;
+}
+
+/** The top-level options apply to every format that has them. Each format's own options
+  * override them. */
+interface FormatOptions extends FormatOptionsFor {
+    html?: FormatOptionsFor;
+    rtf?: FormatOptionsFor;
+    plain?: FormatOptionsFor;
 }
 
 interface InitOptions {
@@ -119,6 +134,11 @@ interface InitOptions {
 
     /** Disables sorting in the bibliography; items appear in cited order. */
     bibliographyNoSort?: boolean;
+
+    /** Parse the style leniently (the default), accepting CSL-M variables and attribute
+      * values, as strict CSL, or as CSL-M, which also turns on CSL-M extensions like
+      * jurisdiction conditions and legal variables. */
+    styleMode?: "lenient" | "csl" | "csl-m";
 }
 
 /** This interface lets citeproc retrieve locales or modules asynchronously,
@@ -139,12 +159,57 @@ interface BibliographyUpdate {
     entryIds?: string[];
 }
 
+/** A change to the driver's inputs, from `setInvalidationTracing`. */
+type InputChange =
+    | { kind: "style" | "locale" | "outputFormat" | "settings" | "clusterOrder" | "uncitedReferences" }
+    | { kind: "reference"; id: string }
+    | { kind: "cluster"; id: string };
+
 type UpdateSummary<Output = string> = {
     clusters: [string, Output][];
     bibliography?: BibliographyUpdate;
+    /** Only with setInvalidationTracing(true): the input changes each updated cluster depends on. */
+    causes?: [string, InputChange[]][];
 };
 "#
 );
+typescript_deserialize!(
+    crate::diff::DiffOptions,
+    DiffOptions,
+    "DiffOptions",
+    r#"
+interface DiffOptions {
+    /** Leave out the formatted output, and only say which clusters and entries changed. */
+    idsOnly?: boolean;
+}
+"#
+);
+typescript_serialize!(
+    crate::diff::UpdateDiff,
+    UpdateDiff,
+    "UpdateDiff",
+    r#"
+interface BibliographyDiff {
+    /** Changed entries, by reference id. Absent with idsOnly. */
+    updated?: { [id: string]: string };
+    /** Ids of changed entries. Only with idsOnly. */
+    updatedIds?: string[];
+    /** Entries no longer in the bibliography. Apply these first. */
+    removed: string[];
+    /** Then, in order, put each entry at its index, taking it out of its old place first if it
+      * had one. */
+    moves: { id: string; index: number }[];
+}
+
+interface UpdateDiff {
+    /** Changed clusters, by cluster id. Absent with idsOnly. */
+    clusters?: { [id: string]: string };
+    /** Ids of changed clusters. Only with idsOnly. */
+    clusterIds?: string[];
+    bibliography?: BibliographyDiff;
+}
+"#
+);
 typescript_serialize!(
     Vec<citeproc::BibEntry>,
     BibEntries,
@@ -152,6 +217,7 @@ typescript_serialize!(
     r#"
 interface BibEntry {
     id: string;
+    anchor: string;
     value: string;
 }
 "#
@@ -263,6 +329,8 @@ export type Cite = {
     id: string;
     prefix?: string;
     suffix?: string;
+    /** Authors to leave out of this cite, by position (from 0) or family name. */
+    "suppress-names"?: (number | string)[];
 } & Partial<CiteLocator> & CiteMode;
 
 export type ClusterMode
@@ -278,6 +346,8 @@ export type Cluster = {
 
 export type PreviewCluster = {
     cites: Cite[];
+    /** Renders the cites in this position, whatever their position in the document would be. */
+    positionOverride?: "first" | "subsequent" | "ibid" | "ibid-with-locator" | "near-note" | "far-note";
 } & ClusterMode;
 
 export type ClusterPosition = {
@@ -292,21 +362,83 @@ typescript_deserialize!(
     Reference,
     "Reference",
     r#"
+/** A CSL-JSON reference. The common variables are typed; any other CSL variable is accepted as
+  * well, e.g. "container-title" or "page". */
 type Reference = {
     id: string;
     type: CslType;
+    title?: string;
+    author?: Name[];
+    editor?: Name[];
+    translator?: Name[];
+    issued?: DateOrRange;
+    accessed?: DateOrRange;
+    DOI?: string;
+    URL?: string;
+    language?: string;
     [key: string]: any;
 };
 export type CslType = "book" | "legal_case" | "article-journal" | string;
+
+export type PersonName = {
+    family?: string;
+    given?: string;
+    "non-dropping-particle"?: string;
+    "dropping-particle"?: string;
+    suffix?: string;
+    "comma-suffix"?: boolean;
+    "static-ordering"?: boolean;
+    "static-particles"?: boolean;
+    /** A transliteration of a name written in another script, used for sorting. */
+    romanized?: { family?: string; given?: string; };
+};
+/** A name given as a single string, like an institution. */
+export type LiteralName = { literal: string; };
+export type Name = PersonName | LiteralName;
 "#
 );
 
-typescript_serialize!(
-    citeproc::IncludeUncited,
+typescript_deserialize!(Vec<citeproc_io::Reference>, ReferenceArray, "Reference[]");
+typescript_deserialize!(Vec<citeproc::string_id::Cluster>, ClusterArray, "Cluster[]");
+typescript_deserialize!(
+    Vec<citeproc::string_id::ClusterPosition>,
+    ClusterPositionArray,
+    "ClusterPosition[]"
+);
+typescript_deserialize!(Vec<String>, IdArray, "string[]");
+
+typescript_deserialize!(
+    crate::options::IncludeUncitedArg,
     IncludeUncited,
     "IncludeUncited",
     r#"
-    type IncludeUncited = "None" | "All" | { Specific: string[] };
+/** Which uncited references go in the bibliography: none (the default), all of them, or the ones
+  * with these ids. */
+type IncludeUncited = "None" | "All" | string[] | { Specific: string[] };
+"#
+);
+
+typescript_deserialize!(
+    citeproc::BibliographyFilter,
+    BibliographyFilter,
+    "BibliographyFilter",
+    r#"
+/** `field` is "type", "id", or a CSL variable like "keyword". Keywords also match each of their
+  * ";"- or ","-separated terms. */
+interface FieldMatch {
+    field: string;
+    value: string;
+}
+interface BibliographyFilter {
+    /** Every one of these must match. */
+    select?: FieldMatch[];
+    /** At least one of these must match. */
+    include?: FieldMatch[];
+    /** None of these may match. */
+    exclude?: FieldMatch[];
+    /** References matching all of these are left out. */
+    quash?: FieldMatch[];
+}
 "#
 );
 
@@ -0,0 +1,10 @@
+#![no_main]
+use citeproc_io::micro_html;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(text) = std::str::from_utf8(data) {
+        let _ = micro_html::parse(text);
+        let _ = micro_html::validate(text);
+    }
+});
@@ -0,0 +1,7 @@
+#![no_main]
+use citeproc_io::Reference;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = serde_json::from_slice::<Reference>(data);
+});